@@ -0,0 +1,379 @@
+//! The import of users and groups from Active Directory. AD has no content synchronisation, so
+//! the domain is read with a paged search, and the binary attributes that identify its entries
+//! are converted to text. The mapping is fixed to the AD schema, rather than configured.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::pin::Pin;
+use std::str::FromStr;
+
+use futures_util::sink::SinkExt;
+use futures_util::stream::StreamExt;
+use ldap3_proto::proto::*;
+use ldap3_proto::LdapCodec;
+use openssl::ssl::{SslConnector, SslMethod};
+use serde::Deserialize;
+use tokio::net::TcpStream;
+use tokio_openssl::SslStream;
+use tokio_util::codec::Framed;
+use tracing::{debug, error, info};
+use uuid::Uuid;
+
+use kanidm_proto::scim_v1::{ScimEntry, ScimExternalMember, ScimSyncGroup, ScimSyncPerson};
+
+use crate::config::LdapSource;
+use crate::ldif::LdifEntry;
+use crate::ImportReport;
+
+const AD_DEFAULT_PORT: u16 = 636;
+
+/// The attributes that the mapping uses, or that are reported when it does not.
+const AD_ATTRS: [&str; 12] = [
+    "objectclass",
+    "objectguid",
+    "objectsid",
+    "samaccountname",
+    "displayname",
+    "cn",
+    "description",
+    "member",
+    "primarygroupid",
+    "uidnumber",
+    "gidnumber",
+    "loginshell",
+];
+
+#[derive(Debug, Deserialize, Default)]
+pub struct AdConfig {
+    /// The gidnumber of an entry without one is this plus the relative id of its objectSid.
+    /// Without it, kanidm allocates the gidnumbers of those entries.
+    pub rid_base: Option<u32>,
+}
+
+/// An objectSid in its string form, such as `S-1-5-21-1004336348-1177238915-682003330-512`.
+pub fn sid_to_string(sid: &[u8]) -> Option<String> {
+    let (revision, count) = (*sid.first()?, *sid.get(1)? as usize);
+    let authority = sid
+        .get(2..8)?
+        .iter()
+        .fold(0u64, |acc, b| (acc << 8) | *b as u64);
+    let subauthorities = sid.get(8..)?;
+    if subauthorities.len() != count * 4 {
+        return None;
+    }
+
+    let mut s = format!("S-{}-{}", revision, authority);
+    for sub in subauthorities.chunks_exact(4) {
+        s.push_str(&format!(
+            "-{}",
+            u32::from_le_bytes([sub[0], sub[1], sub[2], sub[3]])
+        ));
+    }
+    Some(s)
+}
+
+/// The relative id of an objectSid string, which is its last subauthority.
+fn sid_rid(sid: &str) -> Option<u32> {
+    sid.rsplit('-')
+        .next()
+        .and_then(|rid| u32::from_str(rid).ok())
+}
+
+/// The text of a value of an AD entry. The guid and sid are binary, and anything else that is
+/// not text, such as a photo, has no use in kanidm.
+fn ad_value(attr: &str, value: Vec<u8>) -> Option<String> {
+    match attr {
+        "objectguid" => <[u8; 16]>::try_from(value.as_slice())
+            .ok()
+            .map(|bytes| Uuid::from_bytes_le(bytes).to_string()),
+        "objectsid" => sid_to_string(&value),
+        _ => String::from_utf8(value).ok(),
+    }
+}
+
+fn ad_entry(entry: LdapSearchResultEntry) -> LdifEntry {
+    let mut attrs: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for attr in entry.attributes {
+        let name = attr.atype.to_lowercase();
+        let values: Vec<_> = attr
+            .vals
+            .into_iter()
+            .filter_map(|v| ad_value(&name, v))
+            .collect();
+        attrs.entry(name).or_default().extend(values);
+    }
+    LdifEntry {
+        dn: entry.dn,
+        attrs,
+    }
+}
+
+/// Read the users and groups under the base dn of a domain controller, over LDAPS.
+pub async fn read_ad(ldap_source: &LdapSource) -> Option<Vec<LdifEntry>> {
+    let uri = &ldap_source.uri;
+    let host = match uri.host_str() {
+        Some(h) => h,
+        None => {
+            error!("No host in {}", uri);
+            return None;
+        }
+    };
+    let port = uri.port().unwrap_or(AD_DEFAULT_PORT);
+
+    let tcpstream = match TcpStream::connect((host, port)).await {
+        Ok(t) => t,
+        Err(e) => {
+            error!(?e, "Failed to connect to {}", uri);
+            return None;
+        }
+    };
+
+    let tlsstream = SslConnector::builder(SslMethod::tls_client())
+        .and_then(|mut tls_builder| {
+            tls_builder.set_ca_file(&ldap_source.ca)?;
+            tls_builder.build().configure()?.into_ssl(host)
+        })
+        .and_then(|ssl| SslStream::new(ssl, tcpstream));
+    let mut tlsstream = match tlsstream {
+        Ok(s) => s,
+        Err(e) => {
+            error!(?e, "Failed to initialise TLS to {}", uri);
+            return None;
+        }
+    };
+    if let Err(e) = SslStream::connect(Pin::new(&mut tlsstream)).await {
+        error!(?e, "Failed to initialise TLS to {}", uri);
+        return None;
+    }
+
+    let mut framed = Framed::new(tlsstream, LdapCodec);
+
+    let bind = LdapMsg {
+        msgid: 1,
+        op: LdapOp::BindRequest(LdapBindRequest {
+            dn: ldap_source.bind_dn.clone(),
+            cred: LdapBindCred::Simple(ldap_source.bind_password.clone()),
+        }),
+        ctrl: vec![],
+    };
+    if let Err(e) = framed.send(bind).await {
+        error!(?e, "Failed to bind (authenticate) to {}", uri);
+        return None;
+    }
+    match framed.next().await {
+        Some(Ok(LdapMsg {
+            op: LdapOp::BindResponse(res),
+            ..
+        })) if res.res.code == LdapResultCode::Success => {}
+        res => {
+            error!(?res, "Failed to bind (authenticate) to {}", uri);
+            return None;
+        }
+    }
+
+    // Users, but not computers which are also of the user class, and groups.
+    let filter = LdapFilter::Or(vec![
+        LdapFilter::And(vec![
+            LdapFilter::Equality("objectclass".to_string(), "user".to_string()),
+            LdapFilter::Not(Box::new(LdapFilter::Equality(
+                "objectclass".to_string(),
+                "computer".to_string(),
+            ))),
+        ]),
+        LdapFilter::Equality("objectclass".to_string(), "group".to_string()),
+    ]);
+
+    let mut entries = Vec::new();
+    let mut msgid = 1;
+    let mut cookie = String::new();
+    loop {
+        msgid += 1;
+        let search = LdapMsg {
+            msgid,
+            op: LdapOp::SearchRequest(LdapSearchRequest {
+                base: ldap_source.base_dn.clone(),
+                scope: LdapSearchScope::Subtree,
+                aliases: LdapDerefAliases::Never,
+                sizelimit: 0,
+                timelimit: 0,
+                typesonly: false,
+                filter: filter.clone(),
+                attrs: AD_ATTRS.iter().map(|a| a.to_string()).collect(),
+            }),
+            // AD limits a search to 1000 entries unless it is paged.
+            ctrl: vec![LdapControl::SimplePagedResults {
+                size: 500,
+                cookie: cookie.clone(),
+            }],
+        };
+        if let Err(e) = framed.send(search).await {
+            error!(?e, "Failed to search {}", uri);
+            return None;
+        }
+
+        // The entries of the page, until it is done.
+        cookie = loop {
+            match framed.next().await {
+                Some(Ok(LdapMsg {
+                    op: LdapOp::SearchResultEntry(entry),
+                    ..
+                })) => entries.push(ad_entry(entry)),
+                // Referrals to other domains are not followed.
+                Some(Ok(LdapMsg {
+                    op: LdapOp::SearchResultReference(_),
+                    ..
+                })) => {}
+                Some(Ok(LdapMsg {
+                    op: LdapOp::SearchResultDone(res),
+                    ctrl,
+                    ..
+                })) if res.code == LdapResultCode::Success => {
+                    break ctrl
+                        .into_iter()
+                        .find_map(|c| match c {
+                            LdapControl::SimplePagedResults { cookie, .. } => Some(cookie),
+                            _ => None,
+                        })
+                        .unwrap_or_default();
+                }
+                res => {
+                    error!(?res, "Failed to search {}", uri);
+                    return None;
+                }
+            }
+        };
+
+        debug!("Read {} entries from {}", entries.len(), uri);
+        // An empty cookie is the last page.
+        if cookie.is_empty() {
+            break;
+        }
+    }
+
+    Some(entries)
+}
+
+/// Map the users and groups of AD to scim entries. The members of each group are the users
+/// whose primary group it is, which AD leaves out of the member attribute, and the users and
+/// groups of its member attribute that are part of the import. Groups that are members of
+/// groups remain nested in kanidm.
+pub fn ad_to_scim_entries(
+    entries: Vec<LdifEntry>,
+    ad_config: &AdConfig,
+    exclude: &[String],
+    report: &mut ImportReport,
+) -> Result<Vec<ScimEntry>, ()> {
+    let (entries, excluded): (Vec<_>, Vec<_>) = entries
+        .into_iter()
+        .partition(|e| !exclude.iter().any(|ex| ex.eq_ignore_ascii_case(&e.dn)));
+    for e in excluded {
+        info!("import config excludes {}", e.dn);
+    }
+
+    let imported: BTreeSet<String> = entries.iter().map(|e| e.dn.to_lowercase()).collect();
+
+    // The users of each primary group, by the relative id of the group.
+    let mut primary_members: BTreeMap<u32, Vec<String>> = BTreeMap::new();
+    for e in entries.iter().filter(|e| !e.has_class("group")) {
+        if let Some(rid) = e
+            .get("primarygroupid")
+            .and_then(|v| v.first())
+            .and_then(|rid| u32::from_str(rid).ok())
+        {
+            primary_members.entry(rid).or_default().push(e.dn.clone());
+        }
+    }
+
+    let mut scim_entries = Vec::with_capacity(entries.len());
+    for mut entry in entries {
+        let dn = entry.dn.clone();
+        let is_group = entry.has_class("group");
+
+        let id = entry
+            .remove_ava_single("objectguid")
+            .ok_or_else(|| {
+                error!("Missing required attribute objectGUID on {}", dn);
+            })
+            .and_then(|id| {
+                Uuid::from_str(&id).map_err(|_| {
+                    error!("Invalid objectGUID {} on {}", id, dn);
+                })
+            })?;
+        let sid = entry.remove_ava_single("objectsid");
+        let rid = sid.as_deref().and_then(sid_rid);
+        let name = entry.remove_ava_single("samaccountname").ok_or_else(|| {
+            error!("Missing required attribute sAMAccountName on {}", dn);
+        })?;
+
+        let gid_attr = if is_group { "gidnumber" } else { "uidnumber" };
+        let gidnumber = match entry.remove_ava_single(gid_attr) {
+            Some(gid) => Some(u32::from_str(&gid).map_err(|_| {
+                error!("Invalid {} {} on {}", gid_attr, gid, dn);
+            })?),
+            None => ad_config
+                .rid_base
+                .zip(rid)
+                .and_then(|(base, rid)| base.checked_add(rid)),
+        };
+
+        let external_id = Some(dn.clone());
+
+        if is_group {
+            let description = entry.remove_ava_single("description");
+            let mut members: Vec<_> = entry.remove_ava("member").unwrap_or_default();
+            if let Some(primary) = rid.and_then(|rid| primary_members.remove(&rid)) {
+                members.extend(primary);
+            }
+            let members = members
+                .into_iter()
+                .filter(|member| {
+                    let known = imported.contains(&member.to_lowercase());
+                    if !known {
+                        // Such as foreign security principals, or built in accounts.
+                        report.missing_members.push((dn.clone(), member.clone()));
+                    }
+                    known
+                })
+                .map(|external_id| ScimExternalMember { external_id })
+                .collect();
+            entry.remove_ava("cn");
+            crate::report_unmapped(report, entry);
+
+            scim_entries.push(
+                ScimSyncGroup {
+                    id,
+                    external_id,
+                    name,
+                    description,
+                    gidnumber,
+                    members,
+                }
+                .into(),
+            );
+        } else {
+            let cn = entry.remove_ava_single("cn");
+            let display_name = entry
+                .remove_ava_single("displayname")
+                .or(cn)
+                .unwrap_or_else(|| name.clone());
+            let login_shell = entry.remove_ava_single("loginshell");
+            entry.remove_ava("primarygroupid");
+            crate::report_unmapped(report, entry);
+
+            scim_entries.push(
+                ScimSyncPerson {
+                    id,
+                    external_id,
+                    user_name: name,
+                    display_name,
+                    gidnumber,
+                    // AD does not disclose password hashes over LDAP.
+                    password_import: None,
+                    login_shell,
+                }
+                .into(),
+            );
+        }
+    }
+
+    Ok(scim_entries)
+}
@@ -0,0 +1,95 @@
+use serde::Deserialize;
+use url::Url;
+
+use crate::ad::AdConfig;
+
+/// How the entries of the ldif map to kanidm. Attribute and class names are matched in any case.
+#[derive(Debug, Deserialize)]
+pub struct Config {
+    pub sync_token: String,
+
+    /// The attribute with the uuid of each entry, which becomes its uuid in kanidm.
+    #[serde(default = "default_id_attr")]
+    pub id_attr: String,
+
+    #[serde(default)]
+    pub person: PersonMap,
+    #[serde(default)]
+    pub group: GroupMap,
+
+    /// The dns of entries to leave out of the import.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+
+    /// The LDAP server to read the entries from, when no ldif is given.
+    pub ldap: Option<LdapSource>,
+
+    /// Import users and groups from Active Directory, with its own mapping in place of the
+    /// person and group maps. The domain controller is given in the ldap section.
+    pub ad: Option<AdConfig>,
+}
+
+/// A 389-ds or OpenLDAP server to migrate from. The entries are read with a refresh of the
+/// content synchronisation (syncrepl), so the server must have its content sync plugin or
+/// syncprov overlay enabled, and the bind dn must be able to read the password hashes.
+#[derive(Debug, Deserialize)]
+pub struct LdapSource {
+    pub uri: Url,
+    /// The path to the CA of the server's certificate.
+    pub ca: String,
+    pub bind_dn: String,
+    pub bind_password: String,
+    pub base_dn: String,
+}
+
+fn default_id_attr() -> String {
+    "entryuuid".to_string()
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct PersonMap {
+    pub objectclass: String,
+    pub user_name: String,
+    pub display_name: String,
+    pub gidnumber: String,
+    /// The attribute with the password hash, such as `{SSHA512}...`.
+    pub password_import: String,
+    pub login_shell: String,
+}
+
+impl Default for PersonMap {
+    fn default() -> Self {
+        PersonMap {
+            objectclass: "person".to_string(),
+            user_name: "uid".to_string(),
+            display_name: "cn".to_string(),
+            gidnumber: "gidnumber".to_string(),
+            password_import: "userpassword".to_string(),
+            login_shell: "loginshell".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct GroupMap {
+    pub objectclass: String,
+    pub name: String,
+    pub description: String,
+    pub gidnumber: String,
+    /// The attribute with the dns of the members.
+    pub member: String,
+}
+
+impl Default for GroupMap {
+    fn default() -> Self {
+        GroupMap {
+            objectclass: "groupofnames".to_string(),
+            name: "cn".to_string(),
+            description: "description".to_string(),
+            gidnumber: "gidnumber".to_string(),
+            member: "member".to_string(),
+        }
+    }
+}
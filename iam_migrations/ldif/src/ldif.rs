@@ -0,0 +1,173 @@
+//! A parser of the content records of RFC 2849 LDIF, as exported by an LDAP server. Change
+//! records and values given by URL are not supported.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use tracing::debug;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct LdifEntry {
+    pub dn: String,
+    /// Values by lower case attribute name, without its options such as `;binary`.
+    pub attrs: BTreeMap<String, Vec<String>>,
+}
+
+impl LdifEntry {
+    pub fn get(&self, attr: &str) -> Option<&Vec<String>> {
+        self.attrs.get(&attr.to_lowercase())
+    }
+
+    pub fn remove_ava(&mut self, attr: &str) -> Option<Vec<String>> {
+        self.attrs.remove(&attr.to_lowercase())
+    }
+
+    pub fn remove_ava_single(&mut self, attr: &str) -> Option<String> {
+        self.remove_ava(attr).and_then(|mut values| values.pop())
+    }
+
+    /// If the entry has the objectclass, in any case.
+    pub fn has_class(&self, class: &str) -> bool {
+        self.get("objectclass")
+            .map(|classes| classes.iter().any(|c| c.eq_ignore_ascii_case(class)))
+            .unwrap_or(false)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum LdifError {
+    /// A line that is not an attribute and value.
+    InvalidLine(usize),
+    /// A record that does not start with its dn.
+    MissingDn(usize),
+    InvalidBase64(usize),
+    /// A record that changes entries, rather than describing one.
+    ChangeRecord(usize),
+    /// A value given by url, which would have to be fetched.
+    UrlValue(usize),
+}
+
+impl fmt::Display for LdifError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LdifError::InvalidLine(l) => write!(f, "line {}: expected 'attribute: value'", l),
+            LdifError::MissingDn(l) => write!(f, "line {}: a record must start with its dn", l),
+            LdifError::InvalidBase64(l) => write!(f, "line {}: invalid base64 value", l),
+            LdifError::ChangeRecord(l) => write!(f, "line {}: change records are not supported", l),
+            LdifError::UrlValue(l) => write!(f, "line {}: values from urls are not supported", l),
+        }
+    }
+}
+
+/// A line with its folded continuations, and the number of its first line.
+struct Line {
+    number: usize,
+    text: String,
+}
+
+/// Split the input into records of unfolded lines, without comments.
+fn records(input: &str) -> Vec<Vec<Line>> {
+    let mut records = Vec::new();
+    let mut record: Vec<Line> = Vec::new();
+    let mut in_comment = false;
+
+    for (i, line) in input.lines().enumerate() {
+        let number = i + 1;
+        if let Some(folded) = line.strip_prefix(' ') {
+            if !in_comment {
+                if let Some(last) = record.last_mut() {
+                    last.text.push_str(folded);
+                }
+            }
+        } else if line.is_empty() {
+            in_comment = false;
+            if !record.is_empty() {
+                records.push(std::mem::take(&mut record));
+            }
+        } else if line.starts_with('#') {
+            in_comment = true;
+        } else {
+            in_comment = false;
+            record.push(Line {
+                number,
+                text: line.to_string(),
+            });
+        }
+    }
+    if !record.is_empty() {
+        records.push(record);
+    }
+    records
+}
+
+/// The attribute name and value of a line.
+fn attr_value(line: &Line) -> Result<(String, Option<String>), LdifError> {
+    let (name, rest) = line
+        .text
+        .split_once(':')
+        .ok_or(LdifError::InvalidLine(line.number))?;
+    let name = name.split(';').next().unwrap_or(name).trim().to_lowercase();
+    if name.is_empty() {
+        return Err(LdifError::InvalidLine(line.number));
+    }
+
+    let value = if let Some(encoded) = rest.strip_prefix(':') {
+        let bytes =
+            base64::decode(encoded.trim()).map_err(|_| LdifError::InvalidBase64(line.number))?;
+        match String::from_utf8(bytes) {
+            Ok(value) => Some(value),
+            Err(_) => {
+                // Binary values, such as photos, have no use in kanidm.
+                debug!("Skipping binary value of {} on line {}", name, line.number);
+                None
+            }
+        }
+    } else if rest.starts_with('<') {
+        return Err(LdifError::UrlValue(line.number));
+    } else {
+        Some(rest.trim_start_matches(' ').to_string())
+    };
+    Ok((name, value))
+}
+
+pub fn parse_ldif(input: &str) -> Result<Vec<LdifEntry>, LdifError> {
+    let mut entries = Vec::new();
+
+    for (i, record) in records(input).into_iter().enumerate() {
+        let mut lines = record.iter().peekable();
+        // The version may only precede the first record.
+        if i == 0 {
+            if let Some(line) = lines.peek() {
+                if line.text.to_lowercase().starts_with("version:") {
+                    lines.next();
+                }
+            }
+        }
+
+        let dn = match lines.next() {
+            Some(line) => match attr_value(line)? {
+                (name, Some(dn)) if name == "dn" => dn,
+                _ => return Err(LdifError::MissingDn(line.number)),
+            },
+            // The record was only the version.
+            None => continue,
+        };
+
+        let mut entry = LdifEntry {
+            dn,
+            attrs: BTreeMap::new(),
+        };
+        for line in lines {
+            let (name, value) = attr_value(line)?;
+            if name == "changetype" {
+                return Err(LdifError::ChangeRecord(line.number));
+            }
+            if let Some(value) = value {
+                entry.attrs.entry(name).or_default().push(value);
+            }
+        }
+        entries.push(entry);
+    }
+
+    Ok(entries)
+}
@@ -0,0 +1,568 @@
+#![deny(warnings)]
+#![warn(unused_extern_crates)]
+#![deny(clippy::todo)]
+#![deny(clippy::unimplemented)]
+#![deny(clippy::unwrap_used)]
+#![deny(clippy::panic)]
+#![deny(clippy::unreachable)]
+#![deny(clippy::await_holding_lock)]
+#![deny(clippy::needless_pass_by_value)]
+#![deny(clippy::trivially_copy_pass_by_ref)]
+// We allow expect since it forces good error messages at the least.
+#![allow(clippy::expect_used)]
+
+mod ad;
+mod config;
+mod ldif;
+
+#[cfg(test)]
+mod tests;
+
+use crate::config::{Config, LdapSource};
+use crate::ldif::{parse_ldif, LdifEntry};
+use base64urlsafedata::Base64UrlSafeData;
+use clap::Parser;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::fs::metadata;
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io::Read;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::thread;
+use tokio::runtime;
+use tracing::{debug, error, info, warn};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{fmt, EnvFilter};
+use uuid::Uuid;
+
+use kanidm_client::KanidmClientBuilder;
+use kanidm_proto::scim_v1::{
+    ScimEntry, ScimExternalMember, ScimSyncGroup, ScimSyncPerson, ScimSyncRequest, ScimSyncState,
+};
+use kanidmd_lib::utils::file_permissions_readonly;
+use ldap3_client::{proto, proto::LdapFilter, LdapClientBuilder, LdapSyncRepl};
+
+use users::{get_current_gid, get_current_uid, get_effective_gid, get_effective_uid};
+
+include!("./opt.rs");
+
+fn read_file(path: &Path) -> Option<String> {
+    let mut f = match File::open(path) {
+        Ok(f) => f,
+        Err(e) => {
+            error!("Unable to open {} [{:?}] 🥺", path.to_string_lossy(), e);
+            return None;
+        }
+    };
+
+    let mut contents = String::new();
+    if let Err(e) = f.read_to_string(&mut contents) {
+        error!("unable to read {} contents {:?}", path.to_string_lossy(), e);
+        return None;
+    };
+    Some(contents)
+}
+
+async fn driver_main(opt: Opt) {
+    debug!("Starting kanidm ldif import tool.");
+    // Parse the configs.
+
+    let contents = match read_file(&opt.ldif_import_config) {
+        Some(c) => c,
+        None => return,
+    };
+
+    let import_config: Config = match toml::from_str(contents.as_str()) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("unable to parse config {:?}", e);
+            return;
+        }
+    };
+
+    debug!(?import_config);
+
+    let (entries, source) = match (&opt.ldif, &import_config.ldap) {
+        (Some(_), _) if import_config.ad.is_some() => {
+            error!("AD is read from the domain controller in the [ldap] section, not an ldif");
+            return;
+        }
+        (None, Some(ldap_source)) if import_config.ad.is_some() => {
+            match ad::read_ad(ldap_source).await {
+                Some(entries) => (entries, ldap_source.uri.to_string()),
+                None => return,
+            }
+        }
+        (Some(ldif_path), _) => match read_ldif(ldif_path) {
+            Some(entries) => (entries, ldif_path.to_string_lossy().to_string()),
+            None => return,
+        },
+        (None, Some(ldap_source)) => match read_ldap(ldap_source, &import_config.id_attr).await {
+            Some(entries) => (entries, ldap_source.uri.to_string()),
+            None => return,
+        },
+        (None, None) => {
+            error!("An ldif to import, or an [ldap] section in the config, is required");
+            return;
+        }
+    };
+
+    debug!("Read {} entries from {}", entries.len(), source);
+
+    let cb = match KanidmClientBuilder::new().read_options_from_optional_config(&opt.client_config)
+    {
+        Ok(v) => v,
+        Err(_) => {
+            error!("Failed to parse {}", opt.client_config.to_string_lossy());
+            return;
+        }
+    };
+
+    let rsclient = match cb.build() {
+        Ok(rsc) => rsc,
+        Err(_e) => {
+            error!("Failed to build async client");
+            return;
+        }
+    };
+
+    rsclient.set_token(import_config.sync_token.clone()).await;
+
+    // Preflight check.
+    //  * can we connect to kanidm?
+    let scim_sync_status = match rsclient.scim_v1_sync_status().await {
+        Ok(s) => s,
+        Err(e) => {
+            error!(?e, "Failed to access scim sync status");
+            return;
+        }
+    };
+
+    debug!(state=?scim_sync_status);
+
+    // The entries are a complete snapshot rather than a set of changes, so each import is a
+    // refresh whatever state kanidm is in. The cookie records which snapshot was imported last.
+    let (scim_sync_request, report) = match process_entries(entries, &import_config) {
+        Ok(r) => r,
+        Err(()) => return,
+    };
+
+    report.log();
+
+    if opt.proto_dump {
+        let stdout = std::io::stdout();
+        // write it out.
+        if let Err(e) = serde_json::to_writer_pretty(stdout, &scim_sync_request) {
+            error!(?e, "Failed to serialise scim sync request");
+        };
+    } else if opt.dry_run {
+        info!(
+            "dry-run complete, {} entries would be imported",
+            scim_sync_request.entries.len()
+        );
+        info!("Success!");
+    } else if let Err(e) = rsclient.scim_v1_sync_update(&scim_sync_request).await {
+        error!(
+            ?e,
+            "Failed to submit scim sync update - see the kanidmd server log for more details."
+        );
+    } else {
+        info!(
+            "Imported {} entries from {}",
+            scim_sync_request.entries.len(),
+            source
+        );
+        info!("Success!");
+    }
+    // done!
+}
+
+fn read_ldif(path: &Path) -> Option<Vec<LdifEntry>> {
+    let ldif = read_file(path)?;
+    parse_ldif(&ldif)
+        .map_err(|e| {
+            error!("Unable to parse ldif {} - {}", path.to_string_lossy(), e);
+        })
+        .ok()
+}
+
+/// Read every entry under the base dn of the server. The uuid of each entry from the sync is
+/// added as the id attribute, in case the server does not return it as an attribute.
+async fn read_ldap(ldap_source: &LdapSource, id_attr: &str) -> Option<Vec<LdifEntry>> {
+    let mut ldap_client = match LdapClientBuilder::new(&ldap_source.uri)
+        .add_tls_ca(&ldap_source.ca)
+        .build()
+        .await
+    {
+        Ok(lc) => lc,
+        Err(e) => {
+            error!(?e, "Failed to connect to {}", ldap_source.uri);
+            return None;
+        }
+    };
+
+    if let Err(e) = ldap_client
+        .bind(
+            ldap_source.bind_dn.clone(),
+            ldap_source.bind_password.clone(),
+        )
+        .await
+    {
+        error!(?e, "Failed to bind (authenticate) to {}", ldap_source.uri);
+        return None;
+    }
+
+    let filter = LdapFilter::Present("objectclass".to_string());
+    let sync_result = match ldap_client
+        .syncrepl(
+            ldap_source.base_dn.clone(),
+            filter,
+            None,
+            proto::SyncRequestMode::RefreshOnly,
+        )
+        .await
+    {
+        Ok(results) => results,
+        Err(e) => {
+            error!(?e, "Failed to perform syncrepl from {}", ldap_source.uri);
+            return None;
+        }
+    };
+
+    match sync_result {
+        LdapSyncRepl::Success { entries, .. } => Some(
+            entries
+                .into_iter()
+                .map(|sync_entry| {
+                    let mut entry = LdifEntry {
+                        dn: sync_entry.entry.dn,
+                        attrs: sync_entry
+                            .entry
+                            .attrs
+                            .into_iter()
+                            .map(|(attr, values)| {
+                                (attr.to_lowercase(), values.into_iter().collect())
+                            })
+                            .collect(),
+                    };
+                    entry
+                        .attrs
+                        .entry(id_attr.to_lowercase())
+                        .or_insert_with(|| vec![sync_entry.entry_uuid.to_string()]);
+                    entry
+                })
+                .collect(),
+        ),
+        LdapSyncRepl::RefreshRequired => {
+            error!("Invalid state, a refresh without a cookie can not require a refresh");
+            None
+        }
+    }
+}
+
+/// What the import could not map to kanidm, so that it can be migrated by other means.
+#[derive(Debug, Default)]
+struct ImportReport {
+    /// The dns of the entries that are neither a person nor a group.
+    skipped: Vec<String>,
+    /// The attributes that were left out of the import, with the number of entries that had them.
+    unmapped_attrs: BTreeMap<String, usize>,
+    /// The groups, and their members that are not part of the import.
+    missing_members: Vec<(String, String)>,
+}
+
+impl ImportReport {
+    fn log(&self) {
+        if !self.skipped.is_empty() {
+            warn!(
+                "{} entries are not a person or group, and were not imported",
+                self.skipped.len()
+            );
+            for dn in &self.skipped {
+                info!("not imported: {}", dn);
+            }
+        }
+        for (attr, count) in &self.unmapped_attrs {
+            warn!("attribute {} on {} entries was not imported", attr, count);
+        }
+        for (group, member) in &self.missing_members {
+            warn!("member {} of {} is not imported", member, group);
+        }
+    }
+}
+
+fn process_entries(
+    entries: Vec<LdifEntry>,
+    import_config: &Config,
+) -> Result<(ScimSyncRequest, ImportReport), ()> {
+    let mut hasher = DefaultHasher::new();
+    entries.hash(&mut hasher);
+    let cookie = Base64UrlSafeData(hasher.finish().to_be_bytes().to_vec());
+
+    let mut report = ImportReport::default();
+    let scim_entries = match &import_config.ad {
+        Some(ad_config) => {
+            ad::ad_to_scim_entries(entries, ad_config, &import_config.exclude, &mut report)
+                .map_err(|()| error!("Failed to process AD entries to SCIM"))?
+        }
+        None => {
+            let mut scim_entries = Vec::with_capacity(entries.len());
+            for e in entries {
+                match ldif_to_scim_entry(e, import_config, &mut report) {
+                    Ok(Some(e)) => scim_entries.push(e),
+                    Ok(None) => {}
+                    Err(()) => {
+                        error!("Failed to process LDIF entries to SCIM");
+                        return Err(());
+                    }
+                }
+            }
+            scim_entries
+        }
+    };
+
+    Ok((
+        ScimSyncRequest {
+            from_state: ScimSyncState::Refresh,
+            to_state: ScimSyncState::Active { cookie },
+            entries: scim_entries,
+            delete_uuids: Vec::new(),
+        },
+        report,
+    ))
+}
+
+fn ldif_to_scim_entry(
+    mut entry: LdifEntry,
+    import_config: &Config,
+    report: &mut ImportReport,
+) -> Result<Option<ScimEntry>, ()> {
+    debug!("{:#?}", entry);
+
+    let dn = entry.dn.clone();
+
+    // Is this an entry we need to observe/look at?
+    if import_config
+        .exclude
+        .iter()
+        .any(|ex| ex.eq_ignore_ascii_case(&dn))
+    {
+        info!("import config excludes {}", dn);
+        return Ok(None);
+    }
+
+    let is_person = entry.has_class(&import_config.person.objectclass);
+    let is_group = !is_person && entry.has_class(&import_config.group.objectclass);
+    if !is_person && !is_group {
+        debug!(
+            "Skipping entry {} with oc {:?}",
+            dn,
+            entry.get("objectclass")
+        );
+        report.skipped.push(dn);
+        return Ok(None);
+    }
+
+    let id = entry
+        .remove_ava_single(&import_config.id_attr)
+        .ok_or_else(|| {
+            error!(
+                "Missing required attribute {} on {}",
+                import_config.id_attr, dn
+            );
+        })
+        .and_then(|id| {
+            Uuid::from_str(&id).map_err(|_| {
+                error!("Invalid uuid {} on {}", id, dn);
+            })
+        })?;
+
+    let external_id = Some(dn.clone());
+
+    if is_person {
+        let map = &import_config.person;
+
+        let user_name = entry.remove_ava_single(&map.user_name).ok_or_else(|| {
+            error!("Missing required attribute {} on {}", map.user_name, dn);
+        })?;
+
+        // ⚠️  hardcoded skip on admin here!!!
+        if user_name == "admin" {
+            info!("kanidm excludes {}", dn);
+            return Ok(None);
+        }
+
+        let display_name = entry.remove_ava_single(&map.display_name).ok_or_else(|| {
+            error!("Missing required attribute {} on {}", map.display_name, dn);
+        })?;
+
+        let gidnumber = remove_gidnumber(&mut entry, &map.gidnumber)?;
+        let password_import = entry.remove_ava_single(&map.password_import);
+        let login_shell = entry.remove_ava_single(&map.login_shell);
+        report_unmapped(report, entry);
+
+        Ok(Some(
+            ScimSyncPerson {
+                id,
+                external_id,
+                user_name,
+                display_name,
+                gidnumber,
+                password_import,
+                login_shell,
+            }
+            .into(),
+        ))
+    } else {
+        let map = &import_config.group;
+
+        let name = entry.remove_ava_single(&map.name).ok_or_else(|| {
+            error!("Missing required attribute {} on {}", map.name, dn);
+        })?;
+
+        let description = entry.remove_ava_single(&map.description);
+        let gidnumber = remove_gidnumber(&mut entry, &map.gidnumber)?;
+
+        let members: Vec<_> = entry
+            .remove_ava(&map.member)
+            .map(|set| {
+                set.into_iter()
+                    .map(|external_id| ScimExternalMember { external_id })
+                    .collect()
+            })
+            .unwrap_or_default();
+        report_unmapped(report, entry);
+
+        Ok(Some(
+            ScimSyncGroup {
+                id,
+                external_id,
+                name,
+                description,
+                gidnumber,
+                members,
+            }
+            .into(),
+        ))
+    }
+}
+
+/// Count the attributes that remain on an entry once it is mapped. The classes are only used to
+/// choose the mapping.
+fn report_unmapped(report: &mut ImportReport, mut entry: LdifEntry) {
+    entry.remove_ava("objectclass");
+    for attr in entry.attrs.into_keys() {
+        *report.unmapped_attrs.entry(attr).or_default() += 1;
+    }
+}
+
+fn remove_gidnumber(entry: &mut LdifEntry, attr: &str) -> Result<Option<u32>, ()> {
+    entry
+        .remove_ava_single(attr)
+        .map(|gid| {
+            u32::from_str(&gid).map_err(|_| {
+                error!("Invalid gidnumber {} on {}", gid, entry.dn);
+            })
+        })
+        .transpose()
+}
+
+fn config_security_checks(cfg_path: &Path) -> bool {
+    let cfg_path_str = cfg_path.to_string_lossy();
+
+    if !cfg_path.exists() {
+        // there's no point trying to start up if we can't read a usable config!
+        error!(
+            "Config missing from {} - cannot start up. Quitting.",
+            cfg_path_str
+        );
+        false
+    } else {
+        let cfg_meta = match metadata(cfg_path) {
+            Ok(v) => v,
+            Err(e) => {
+                error!("Unable to read metadata for {} - {:?}", cfg_path_str, e);
+                return false;
+            }
+        };
+        if !file_permissions_readonly(&cfg_meta) {
+            warn!("permissions on {} may not be secure. Should be readonly to running uid. This could be a security risk ...",
+                cfg_path_str
+                );
+        }
+
+        let cuid = get_current_uid();
+        let ceuid = get_effective_uid();
+
+        if cfg_meta.uid() == cuid || cfg_meta.uid() == ceuid {
+            warn!("WARNING: {} owned by the current uid, which may allow file permission changes. This could be a security risk ...",
+                cfg_path_str
+            );
+        }
+
+        true
+    }
+}
+
+fn main() {
+    let cuid = get_current_uid();
+    let ceuid = get_effective_uid();
+    let cgid = get_current_gid();
+    let cegid = get_effective_gid();
+
+    let opt = Opt::parse();
+
+    let fmt_layer = fmt::layer().with_writer(std::io::stderr);
+
+    let filter_layer = if opt.debug {
+        match EnvFilter::try_new("kanidm_client=debug,kanidm_ldif_import=debug,ldap3_client=debug")
+        {
+            Ok(f) => f,
+            Err(e) => {
+                eprintln!("ERROR! Unable to start tracing {:?}", e);
+                return;
+            }
+        }
+    } else {
+        match EnvFilter::try_from_default_env() {
+            Ok(f) => f,
+            Err(_) => {
+                EnvFilter::new("kanidm_client=warn,kanidm_ldif_import=info,ldap3_client=warn")
+            }
+        }
+    };
+
+    tracing_subscriber::registry()
+        .with(filter_layer)
+        .with(fmt_layer)
+        .init();
+
+    // Startup sanity checks.
+    if opt.skip_root_check {
+        warn!("Skipping root user check, if you're running this for testing, ensure you clean up temporary files.")
+    } else if cuid == 0 || ceuid == 0 || cgid == 0 || cegid == 0 {
+        error!("Refusing to run - this process must not operate as root.");
+        return;
+    };
+
+    if !config_security_checks(&opt.client_config)
+        || !config_security_checks(&opt.ldif_import_config)
+    {
+        return;
+    }
+
+    let par_count = thread::available_parallelism()
+        .expect("Failed to determine available parallelism")
+        .get();
+
+    let rt = runtime::Builder::new_current_thread()
+        .max_blocking_threads(par_count)
+        .enable_all()
+        .build()
+        .expect("Failed to initialise tokio runtime!");
+
+    rt.block_on(async move { driver_main(opt).await });
+}
@@ -0,0 +1,39 @@
+use kanidm_proto::constants::DEFAULT_CLIENT_CONFIG_PATH;
+pub const DEFAULT_LDIF_IMPORT_CONFIG_PATH: &str = "/etc/kanidm/ldif-import";
+
+#[derive(Debug, clap::Parser)]
+#[clap(about = "Kanidm LDIF Import Tool")]
+pub struct Opt {
+    /// Enable debugging of the import tool
+    #[clap(short, long, env = "KANIDM_DEBUG")]
+    pub debug: bool,
+    /// Path to the client config file.
+    #[clap(parse(from_os_str), short, long, default_value_os_t = DEFAULT_CLIENT_CONFIG_PATH.into())]
+    pub client_config: PathBuf,
+
+    /// Path to the ldif-import config file, with the sync token and the attribute mapping.
+    #[clap(parse(from_os_str), short, long, default_value_os_t = DEFAULT_LDIF_IMPORT_CONFIG_PATH.into())]
+    pub ldif_import_config: PathBuf,
+
+    /// Path to the ldif to import. It must contain every entry to be synced, since entries
+    /// imported before that are not in it are removed. Without it, the entries are read from
+    /// the server in the [ldap] section of the config.
+    #[clap(parse(from_os_str))]
+    pub ldif: Option<PathBuf>,
+
+    /// Dump the scim output. This can be used to check the attribute mapping.
+    ///
+    /// No actions are taken on the kanidm instance, this is purely a dump of the
+    /// state in/out.
+    #[clap(short, long, hide = true)]
+    pub proto_dump: bool,
+
+    /// Read entries from the ldif or server, and check the connection to kanidm, but take no
+    /// actions against kanidm that would change state.
+    #[clap(short = 'n')]
+    pub dry_run: bool,
+
+    /// Skip the root user permission check.
+    #[clap(short, long, hide = true)]
+    pub skip_root_check: bool,
+}
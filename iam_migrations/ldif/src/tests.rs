@@ -0,0 +1,273 @@
+use crate::ad::sid_to_string;
+use crate::config::Config;
+use crate::ldif::{parse_ldif, LdifEntry, LdifError};
+use crate::process_entries;
+use kanidm_proto::scim_v1::{
+    ScimEntry, ScimExternalMember, ScimSyncGroup, ScimSyncPerson, ScimSyncState,
+};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use uuid::Uuid;
+
+#[test]
+fn test_ldif_parse() {
+    let entries = parse_ldif(TEST_LDIF_1).expect("failed to parse ldif");
+    assert_eq!(entries.len(), 3);
+
+    let person = &entries[1];
+    assert_eq!(person.dn, "uid=testuser,ou=people,dc=example,dc=com");
+    // Folded lines are joined, and base64 values decoded.
+    assert_eq!(
+        person.get("cn"),
+        Some(&vec!["Test User With A Long Name".to_string()])
+    );
+    assert_eq!(person.get("description"), Some(&vec!["Tëst".to_string()]));
+    // Attribute options are removed.
+    assert!(person.get("displayname").is_some());
+    // Binary values are skipped.
+    assert!(person.get("jpegphoto").is_none());
+    assert!(person.has_class("posixAccount"));
+
+    assert_eq!(
+        parse_ldif("dn: cn=a\nchangetype: delete\n"),
+        Err(LdifError::ChangeRecord(2))
+    );
+    assert_eq!(parse_ldif("cn: a\n"), Err(LdifError::MissingDn(1)));
+    assert_eq!(
+        parse_ldif("dn: cn=a\njpegphoto:< file:///tmp/a.jpg\n"),
+        Err(LdifError::UrlValue(2))
+    );
+}
+
+#[test]
+fn test_ldif_to_scim() {
+    let _ = tracing_subscriber::fmt::try_init();
+
+    let config: Config = toml::from_str(TEST_CONFIG_1).expect("failed to parse import config");
+    let entries = parse_ldif(TEST_LDIF_1).expect("failed to parse ldif");
+
+    let (scim_sync_request, report) =
+        process_entries(entries, &config).expect("failed to process ldif to scim");
+
+    assert!(scim_sync_request.from_state == ScimSyncState::Refresh);
+    assert!(matches!(
+        scim_sync_request.to_state,
+        ScimSyncState::Active { .. }
+    ));
+    // The domain entry is not a person or group, and is skipped.
+    assert_eq!(scim_sync_request.entries.len(), 2);
+    assert_eq!(report.skipped, vec!["dc=example,dc=com".to_string()]);
+    // The attributes with no mapping are reported, but not the binary photo which the parser
+    // drops.
+    assert_eq!(report.unmapped_attrs.get("description"), Some(&1));
+    assert_eq!(report.unmapped_attrs.get("displayname"), Some(&1));
+    assert!(report.unmapped_attrs.get("objectclass").is_none());
+    assert!(report.unmapped_attrs.get("jpegphoto").is_none());
+
+    let expect_person: ScimEntry = ScimSyncPerson {
+        id: Uuid::from_str("babb8302-43a1-11ed-a50d-919b4b1a5ec0").expect("invalid uuid"),
+        external_id: Some("uid=testuser,ou=people,dc=example,dc=com".to_string()),
+        user_name: "testuser".to_string(),
+        display_name: "Test User With A Long Name".to_string(),
+        gidnumber: Some(12345),
+        password_import: Some("{SSHA512}JwrSUHkI7FTAfHRVR6KoFlSN0E3dmaQWARjZ+/UsShYlENOqDtFVU77HJLLrY2MuSp0jve52+pwtdVl2QUAHukQ0XUf5LDtM".to_string()),
+        login_shell: Some("/bin/zsh".to_string()),
+    }
+    .into();
+    assert!(scim_sync_request.entries[0] == expect_person);
+
+    let expect_group: ScimEntry = ScimSyncGroup {
+        id: Uuid::from_str("d547c581-5f26-11ed-a50d-919b4b1a5ec0").expect("invalid uuid"),
+        external_id: Some("cn=testgroup,ou=groups,dc=example,dc=com".to_string()),
+        name: "testgroup".to_string(),
+        description: Some("Test Group".to_string()),
+        gidnumber: None,
+        members: vec![ScimExternalMember {
+            external_id: "uid=testuser,ou=people,dc=example,dc=com".to_string(),
+        }],
+    }
+    .into();
+    assert!(scim_sync_request.entries[1] == expect_group);
+
+    // The same ldif gives the same cookie.
+    let entries = parse_ldif(TEST_LDIF_1).expect("failed to parse ldif");
+    let (again, _) = process_entries(entries, &config).expect("failed to process ldif");
+    assert!(again.to_state == scim_sync_request.to_state);
+}
+
+#[test]
+fn test_ad_sid_to_string() {
+    let mut sid = vec![1, 5, 0, 0, 0, 0, 0, 5];
+    for sub in [21u32, 1004336348, 1177238915, 682003330, 512] {
+        sid.extend_from_slice(&sub.to_le_bytes());
+    }
+    assert_eq!(
+        sid_to_string(&sid).as_deref(),
+        Some("S-1-5-21-1004336348-1177238915-682003330-512")
+    );
+    // The count of subauthorities must match.
+    assert_eq!(sid_to_string(&sid[..sid.len() - 4]), None);
+}
+
+fn ad_entry(dn: &str, attrs: &[(&str, &str)]) -> LdifEntry {
+    let mut entry = LdifEntry {
+        dn: dn.to_string(),
+        attrs: BTreeMap::new(),
+    };
+    for (attr, value) in attrs {
+        entry
+            .attrs
+            .entry(attr.to_string())
+            .or_default()
+            .push(value.to_string());
+    }
+    entry
+}
+
+#[test]
+fn test_ad_to_scim() {
+    let _ = tracing_subscriber::fmt::try_init();
+
+    let config: Config = toml::from_str(TEST_CONFIG_AD).expect("failed to parse import config");
+
+    let user_dn = "CN=Test User,CN=Users,DC=ad,DC=example,DC=com";
+    let users_dn = "CN=Domain Users,CN=Users,DC=ad,DC=example,DC=com";
+    let nested_dn = "CN=Nested,CN=Users,DC=ad,DC=example,DC=com";
+    let foreign_dn = "CN=S-1-5-11,CN=ForeignSecurityPrincipals,DC=ad,DC=example,DC=com";
+
+    let entries = vec![
+        ad_entry(
+            user_dn,
+            &[
+                ("objectclass", "person"),
+                ("objectclass", "user"),
+                ("objectguid", "0a1b2c3d-4e5f-4a6b-8c7d-9e0f1a2b3c4d"),
+                ("objectsid", "S-1-5-21-1-2-3-1105"),
+                ("samaccountname", "testuser"),
+                ("cn", "Test User"),
+                ("primarygroupid", "513"),
+                ("mail", "testuser@ad.example.com"),
+            ],
+        ),
+        ad_entry(
+            users_dn,
+            &[
+                ("objectclass", "group"),
+                ("objectguid", "1a1b2c3d-4e5f-4a6b-8c7d-9e0f1a2b3c4d"),
+                ("objectsid", "S-1-5-21-1-2-3-513"),
+                ("samaccountname", "Domain Users"),
+                ("cn", "Domain Users"),
+                ("gidnumber", "5000"),
+            ],
+        ),
+        ad_entry(
+            nested_dn,
+            &[
+                ("objectclass", "group"),
+                ("objectguid", "2a1b2c3d-4e5f-4a6b-8c7d-9e0f1a2b3c4d"),
+                ("objectsid", "S-1-5-21-1-2-3-1106"),
+                ("samaccountname", "nested"),
+                ("cn", "Nested"),
+                ("member", users_dn),
+                ("member", foreign_dn),
+            ],
+        ),
+    ];
+
+    let (scim_sync_request, report) =
+        process_entries(entries, &config).expect("failed to process ad to scim");
+    assert_eq!(scim_sync_request.entries.len(), 3);
+
+    // The user has no uidnumber, so it is the rid base plus its relative id.
+    let expect_person: ScimEntry = ScimSyncPerson {
+        id: Uuid::from_str("0a1b2c3d-4e5f-4a6b-8c7d-9e0f1a2b3c4d").expect("invalid uuid"),
+        external_id: Some(user_dn.to_string()),
+        user_name: "testuser".to_string(),
+        display_name: "Test User".to_string(),
+        gidnumber: Some(101105),
+        password_import: None,
+        login_shell: None,
+    }
+    .into();
+    assert!(scim_sync_request.entries[0] == expect_person);
+
+    // The primary group has the user as a member, although AD does not list them.
+    let expect_users: ScimEntry = ScimSyncGroup {
+        id: Uuid::from_str("1a1b2c3d-4e5f-4a6b-8c7d-9e0f1a2b3c4d").expect("invalid uuid"),
+        external_id: Some(users_dn.to_string()),
+        name: "Domain Users".to_string(),
+        description: None,
+        gidnumber: Some(5000),
+        members: vec![ScimExternalMember {
+            external_id: user_dn.to_string(),
+        }],
+    }
+    .into();
+    assert!(scim_sync_request.entries[1] == expect_users);
+
+    // The nested group keeps its member group, but not the foreign principal.
+    let expect_nested: ScimEntry = ScimSyncGroup {
+        id: Uuid::from_str("2a1b2c3d-4e5f-4a6b-8c7d-9e0f1a2b3c4d").expect("invalid uuid"),
+        external_id: Some(nested_dn.to_string()),
+        name: "nested".to_string(),
+        description: None,
+        gidnumber: Some(101106),
+        members: vec![ScimExternalMember {
+            external_id: users_dn.to_string(),
+        }],
+    }
+    .into();
+    assert!(scim_sync_request.entries[2] == expect_nested);
+
+    assert_eq!(
+        report.missing_members,
+        vec![(nested_dn.to_string(), foreign_dn.to_string())]
+    );
+    assert_eq!(report.unmapped_attrs.get("mail"), Some(&1));
+}
+
+const TEST_CONFIG_AD: &str = r#"
+sync_token = "token"
+
+[ad]
+rid_base = 100000
+"#;
+
+const TEST_CONFIG_1: &str = r#"
+sync_token = "token"
+
+[group]
+objectclass = "groupOfUniqueNames"
+member = "uniqueMember"
+"#;
+
+const TEST_LDIF_1: &str = r#"version: 1
+
+# The base of the tree.
+dn: dc=example,dc=com
+objectClass: domain
+dc: example
+entryUUID: 5b3ff9c1-5f26-11ed-a50d-919b4b1a5ec0
+
+dn: uid=testuser,ou=people,dc=example,dc=com
+objectClass: person
+objectClass: posixAccount
+uid: testuser
+cn: Test User With
+  A Long Name
+displayName;lang-en: Test User
+description:: VMOrc3Q=
+gidNumber: 12345
+loginShell: /bin/zsh
+userPassword: {SSHA512}JwrSUHkI7FTAfHRVR6KoFlSN0E3dmaQWARjZ+/UsShYlENOqDtFVU77
+ HJLLrY2MuSp0jve52+pwtdVl2QUAHukQ0XUf5LDtM
+jpegPhoto:: /9j/4AAQ
+entryUUID: babb8302-43a1-11ed-a50d-919b4b1a5ec0
+
+dn: cn=testgroup,ou=groups,dc=example,dc=com
+objectClass: groupOfUniqueNames
+cn: testgroup
+description: Test Group
+uniqueMember: uid=testuser,ou=people,dc=example,dc=com
+entryUUID: d547c581-5f26-11ed-a50d-919b4b1a5ec0
+"#;
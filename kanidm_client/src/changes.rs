@@ -0,0 +1,128 @@
+use std::time::Duration;
+
+use kanidm_proto::v1::{EntryChangeNotification, ErrorResponse, Filter};
+
+use crate::{error_from_response, ClientError, KanidmClient, KOPID};
+
+// The stream is a single long request, which would otherwise be ended by the request timeout
+// of the client. It ends after this instead, and can be subscribed to again.
+const CHANGES_STREAM_TIMEOUT: Duration = Duration::from_secs(3600);
+
+/// A subscription to the changes of entries, from [KanidmClient::changes_subscribe].
+pub struct ChangeStream {
+    response: reqwest::Response,
+    buf: Vec<u8>,
+}
+
+impl ChangeStream {
+    /// The next change, or None when the stream has ended. The stream ends when it times out,
+    /// and when the client fell too far behind the changes of the server, so any changes since
+    /// it ended have to be found by searching again. An error sent by the server is returned
+    /// with the status of the stream, which is OK.
+    pub async fn next(&mut self) -> Result<Option<EntryChangeNotification>, ClientError> {
+        loop {
+            // Events are separated by an empty line.
+            while let Some(end) = self.buf.windows(2).position(|w| w == b"\n\n") {
+                let event: Vec<u8> = self.buf.drain(..end + 2).collect();
+                let event = String::from_utf8_lossy(&event);
+
+                let mut name = "message";
+                let mut data = String::new();
+                for line in event.lines() {
+                    if let Some(v) = line.strip_prefix("event:") {
+                        name = v.trim_start();
+                    } else if let Some(v) = line.strip_prefix("data:") {
+                        if !data.is_empty() {
+                            data.push('\n');
+                        }
+                        data.push_str(v.strip_prefix(' ').unwrap_or(v));
+                    }
+                }
+
+                match name {
+                    "change" => match serde_json::from_str(&data) {
+                        Ok(change) => return Ok(Some(change)),
+                        Err(e) => warn!(?e, "Ignoring invalid change notification"),
+                    },
+                    "lagged" => {
+                        warn!(missed = %data, "Change stream lagged behind the server");
+                        return Ok(None);
+                    }
+                    "error" => {
+                        return match serde_json::from_str::<ErrorResponse>(&data) {
+                            Ok(er) => Err(ClientError::Http(
+                                self.response.status(),
+                                Some(er.error),
+                                er.opid,
+                            )),
+                            Err(_) => Err(ClientError::EmptyResponse),
+                        };
+                    }
+                    _ => debug!(%name, "Ignoring change stream event"),
+                }
+            }
+
+            match self
+                .response
+                .chunk()
+                .await
+                .map_err(ClientError::Transport)?
+            {
+                Some(chunk) => self.buf.extend_from_slice(&chunk),
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+impl KanidmClient {
+    /// Subscribe to the changes of the entries that match the filter, as this session can see
+    /// them.
+    pub async fn changes_subscribe(&self, filter: &Filter) -> Result<ChangeStream, ClientError> {
+        let filter = serde_json::to_string(filter).map_err(ClientError::JsonEncode)?;
+        let dest = format!("{}/v1/changes", self.get_url());
+        let request = self
+            .client
+            .get(dest.as_str())
+            .query(&[("filter", filter)])
+            .timeout(CHANGES_STREAM_TIMEOUT);
+
+        let request = {
+            let tguard = self.bearer_token.read().await;
+            if let Some(token) = &(*tguard) {
+                request.bearer_auth(token)
+            } else {
+                request
+            }
+        };
+
+        let response = request.send().await.map_err(ClientError::Transport)?;
+
+        self.expect_version(&response).await;
+
+        let opid = response
+            .headers()
+            .get(KOPID)
+            .and_then(|hv| hv.to_str().ok())
+            .unwrap_or("missing_kopid")
+            .to_string();
+
+        debug!("opid -> {:?}", opid);
+
+        match response.status() {
+            reqwest::StatusCode::OK => {}
+            unexpect => {
+                return Err(ClientError::Http(
+                    unexpect,
+                    error_from_response(response).await,
+                    opid,
+                ))
+            }
+        }
+
+        Ok(ChangeStream {
+            response,
+            buf: Vec::new(),
+        })
+    }
+}
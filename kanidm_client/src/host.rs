@@ -0,0 +1,55 @@
+use std::collections::BTreeMap;
+
+use kanidm_proto::v1::Entry;
+
+use crate::{ClientError, KanidmClient};
+
+impl KanidmClient {
+    pub async fn idm_host_list(&self) -> Result<Vec<Entry>, ClientError> {
+        self.perform_get_request("/v1/host").await
+    }
+
+    pub async fn idm_host_get(&self, id: &str) -> Result<Option<Entry>, ClientError> {
+        self.perform_get_request(format!("/v1/host/{}", id).as_str())
+            .await
+    }
+
+    pub async fn idm_host_create(&self, name: &str, displayname: &str) -> Result<(), ClientError> {
+        let mut new_host = Entry {
+            attrs: BTreeMap::new(),
+        };
+        new_host
+            .attrs
+            .insert("name".to_string(), vec![name.to_string()]);
+        new_host
+            .attrs
+            .insert("displayname".to_string(), vec![displayname.to_string()]);
+        self.perform_post_request("/v1/host", new_host).await
+    }
+
+    pub async fn idm_host_delete(&self, id: &str) -> Result<(), ClientError> {
+        self.perform_delete_request(["/v1/host/", id].concat().as_str())
+            .await
+    }
+
+    /// Create the one time token that a host exchanges for its credential with [host_enrol].
+    ///
+    /// [host_enrol]: KanidmClient::host_enrol
+    pub async fn idm_host_enrol_token_create(&self, id: &str) -> Result<String, ClientError> {
+        self.perform_post_request(format!("/v1/host/{}/_enrol_token", id).as_str(), ())
+            .await
+    }
+
+    pub async fn idm_host_credential_revoke(&self, id: &str) -> Result<(), ClientError> {
+        self.perform_delete_request(format!("/v1/host/{}/_credential", id).as_str())
+            .await
+    }
+
+    /// Enrol this host with a one time enrolment token, and return the credential of the host.
+    /// The credential is an api token, that is used with [set_token].
+    ///
+    /// [set_token]: KanidmClient::set_token
+    pub async fn host_enrol(&self, token: &str) -> Result<String, ClientError> {
+        self.perform_post_request("/v1/host/_enrol", token).await
+    }
+}
@@ -24,6 +24,7 @@ use std::os::unix::fs::MetadataExt;
 use std::path::Path;
 use std::time::Duration;
 
+use kanidm_proto::internal::Oauth2Consent;
 use kanidm_proto::v1::*;
 use reqwest::header::CONTENT_TYPE;
 pub use reqwest::StatusCode;
@@ -37,12 +38,16 @@ use webauthn_rs_proto::{
     PublicKeyCredential, RegisterPublicKeyCredential, RequestChallengeResponse,
 };
 
+mod changes;
+mod host;
 mod person;
 mod scim;
 mod service_account;
 mod sync_account;
 mod system;
 
+pub use crate::changes::ChangeStream;
+
 pub const APPLICATION_JSON: &str = "application/json";
 pub const KOPID: &str = "X-KANIDM-OPID";
 pub const KSESSIONID: &str = "X-KANIDM-AUTH-SESSION-ID";
@@ -50,6 +55,10 @@ pub const KSESSIONID: &str = "X-KANIDM-AUTH-SESSION-ID";
 const KVERSION: &str = "X-KANIDM-VERSION";
 const EXPECT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+// The page size that searches request. Sessions that are not privileged are refused a search
+// that is not paged once it has more results than the server default page size.
+const SEARCH_PAGE_SIZE: usize = 64;
+
 #[derive(Debug)]
 pub enum ClientError {
     Unauthorized,
@@ -64,6 +73,16 @@ pub enum ClientError {
     SystemError,
 }
 
+/// Extract the error from the body of an unsuccessful response. Servers prior to structured
+/// error responses return the error alone.
+async fn error_from_response(response: reqwest::Response) -> Option<OperationError> {
+    let body = response.bytes().await.ok()?;
+    serde_json::from_slice::<ErrorResponse>(&body)
+        .map(|er| er.error)
+        .or_else(|_| serde_json::from_slice::<OperationError>(&body))
+        .ok()
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct KanidmClientConfig {
     pub uri: Option<String>,
@@ -513,7 +532,7 @@ impl KanidmClient {
             unexpect => {
                 return Err(ClientError::Http(
                     unexpect,
-                    response.json().await.ok(),
+                    error_from_response(response).await,
                     opid,
                 ))
             }
@@ -585,7 +604,7 @@ impl KanidmClient {
             unexpect => {
                 return Err(ClientError::Http(
                     unexpect,
-                    response.json().await.ok(),
+                    error_from_response(response).await,
                     opid,
                 ))
             }
@@ -637,7 +656,7 @@ impl KanidmClient {
             unexpect => {
                 return Err(ClientError::Http(
                     unexpect,
-                    response.json().await.ok(),
+                    error_from_response(response).await,
                     opid,
                 ))
             }
@@ -693,7 +712,7 @@ impl KanidmClient {
             unexpect => {
                 return Err(ClientError::Http(
                     unexpect,
-                    response.json().await.ok(),
+                    error_from_response(response).await,
                     opid,
                 ))
             }
@@ -745,7 +764,7 @@ impl KanidmClient {
             unexpect => {
                 return Err(ClientError::Http(
                     unexpect,
-                    response.json().await.ok(),
+                    error_from_response(response).await,
                     opid,
                 ))
             }
@@ -788,7 +807,7 @@ impl KanidmClient {
             unexpect => {
                 return Err(ClientError::Http(
                     unexpect,
-                    response.json().await.ok(),
+                    error_from_response(response).await,
                     opid,
                 ))
             }
@@ -834,7 +853,7 @@ impl KanidmClient {
             unexpect => {
                 return Err(ClientError::Http(
                     unexpect,
-                    response.json().await.ok(),
+                    error_from_response(response).await,
                     opid,
                 ))
             }
@@ -886,7 +905,7 @@ impl KanidmClient {
             unexpect => {
                 return Err(ClientError::Http(
                     unexpect,
-                    response.json().await.ok(),
+                    error_from_response(response).await,
                     opid,
                 ))
             }
@@ -1257,7 +1276,7 @@ impl KanidmClient {
             unexpect => {
                 return Err(ClientError::Http(
                     unexpect,
-                    response.json().await.ok(),
+                    error_from_response(response).await,
                     opid,
                 ))
             }
@@ -1271,11 +1290,52 @@ impl KanidmClient {
         Ok(Some(r.youare))
     }
 
+    pub async fn idm_account_oauth2_consent_list(&self) -> Result<Vec<Oauth2Consent>, ClientError> {
+        self.perform_get_request("/v1/self/_oauth2_consent").await
+    }
+
+    pub async fn idm_account_oauth2_consent_revoke(
+        &self,
+        rs_name: &str,
+    ) -> Result<(), ClientError> {
+        self.perform_delete_request(format!("/v1/self/_oauth2_consent/{}", rs_name).as_str())
+            .await
+    }
+
     // Raw DB actions
+    pub async fn raw_changes_get(
+        &self,
+        id: &str,
+    ) -> Result<Option<Vec<AttributeChange>>, ClientError> {
+        self.perform_get_request(format!("/v1/raw/changes/{}", id).as_str())
+            .await
+    }
+
     pub async fn search(&self, filter: Filter) -> Result<Vec<Entry>, ClientError> {
-        let sr = SearchRequest { filter };
-        let r: Result<SearchResponse, _> = self.perform_post_request("/v1/raw/search", sr).await;
-        r.map(|v| v.entries)
+        // Follow the pages until all entries are returned.
+        let mut entries = Vec::new();
+        let mut page_token = None;
+        loop {
+            let r = self
+                .search_page(filter.clone(), Some(SEARCH_PAGE_SIZE), page_token.take())
+                .await?;
+            entries.extend(r.entries);
+            match r.next_page_token {
+                Some(token) => page_token = Some(token),
+                None => break,
+            }
+        }
+        Ok(entries)
+    }
+
+    pub async fn search_page(
+        &self,
+        filter: Filter,
+        page_size: Option<usize>,
+        page_token: Option<String>,
+    ) -> Result<SearchResponse, ClientError> {
+        let sr = SearchRequest::new_paged(filter, page_size, page_token);
+        self.perform_post_request("/v1/raw/search", sr).await
     }
 
     pub async fn create(&self, entries: Vec<Entry>) -> Result<(), ClientError> {
@@ -1293,6 +1353,16 @@ impl KanidmClient {
         self.perform_post_request("/v1/raw/delete", dr).await
     }
 
+    /// Apply operations in order in one transaction. If any fails, none are applied, and the
+    /// response has the result of each.
+    pub async fn batch(
+        &self,
+        operations: Vec<BatchOperation>,
+    ) -> Result<BatchResponse, ClientError> {
+        let br = BatchRequest { operations };
+        self.perform_post_request("/v1/batch", br).await
+    }
+
     // === idm actions here ==
 
     // ===== GROUPS
@@ -1380,6 +1450,11 @@ impl KanidmClient {
             .await
     }
 
+    pub async fn idm_group_unix_remove(&self, id: &str) -> Result<(), ClientError> {
+        self.perform_delete_request(format!("/v1/group/{}/_unix", id).as_str())
+            .await
+    }
+
     pub async fn idm_group_unix_token_get(&self, id: &str) -> Result<UnixGroupToken, ClientError> {
         self.perform_get_request(["/v1/group/", id, "/_unix/_token"].concat().as_str())
             .await
@@ -1600,6 +1675,44 @@ impl KanidmClient {
         .await
     }
 
+    /// Sets the base DN presented by the LDAP interface
+    pub async fn idm_domain_set_ldap_basedn(&self, new_basedn: &str) -> Result<(), ClientError> {
+        self.perform_put_request(
+            "/v1/domain/_attr/domain_ldap_basedn",
+            vec![new_basedn.to_string()],
+        )
+        .await
+    }
+
+    /// Sets how entries are presented as DNs by the LDAP interface. Each value is either
+    /// the default rdn attribute, or `class:attr` to override it for a class.
+    pub async fn idm_domain_set_ldap_rdn_attr(
+        &self,
+        rdn_attrs: &[&str],
+    ) -> Result<(), ClientError> {
+        let m: Vec<_> = rdn_attrs.iter().map(|v| (*v).to_string()).collect();
+        self.perform_put_request("/v1/domain/_attr/domain_ldap_rdn_attr", m)
+            .await
+    }
+
+    /// Sets aliases of LDAP attribute names to Kanidm attributes, each in the form
+    /// `ldapname:attr`.
+    pub async fn idm_domain_set_ldap_attr_map(&self, attr_map: &[&str]) -> Result<(), ClientError> {
+        let m: Vec<_> = attr_map.iter().map(|v| (*v).to_string()).collect();
+        self.perform_put_request("/v1/domain/_attr/domain_ldap_attr_map", m)
+            .await
+    }
+
+    /// Sets the server uuids of the replicas that reject client writes.
+    pub async fn idm_domain_set_read_only_replicas(
+        &self,
+        server_uuids: &[&str],
+    ) -> Result<(), ClientError> {
+        let m: Vec<_> = server_uuids.iter().map(|v| (*v).to_string()).collect();
+        self.perform_put_request("/v1/domain/_attr/domain_read_only_replica", m)
+            .await
+    }
+
     pub async fn idm_domain_get_ssid(&self) -> Result<String, ClientError> {
         self.perform_get_request("/v1/domain/_attr/domain_ssid")
             .await
@@ -1621,6 +1734,48 @@ impl KanidmClient {
             .await
     }
 
+    // ==== audit log
+    /// Search the audit log, oldest record first. This requires the audit log to be enabled
+    /// on the server.
+    pub async fn system_audit_log_search(
+        &self,
+        req: AuditSearchRequest,
+    ) -> Result<Vec<AuditRecord>, ClientError> {
+        self.perform_post_request("/v1/system/_audit/_search", req)
+            .await
+    }
+
+    // ==== replication
+    pub async fn system_replication_status(&self) -> Result<ReplTopologyStatus, ClientError> {
+        self.perform_get_request("/v1/system/_replication/status")
+            .await
+    }
+
+    pub async fn system_replication_conflict_list(&self) -> Result<Vec<Entry>, ClientError> {
+        self.perform_get_request("/v1/system/_replication/conflict")
+            .await
+    }
+
+    pub async fn system_replication_conflict_resolve(
+        &self,
+        id: &str,
+        resolution: ReplConflictResolution,
+    ) -> Result<(), ClientError> {
+        self.perform_post_request(
+            format!("/v1/system/_replication/conflict/{}", id).as_str(),
+            resolution,
+        )
+        .await
+    }
+
+    pub async fn system_replication_join_token_create(
+        &self,
+        name: &str,
+    ) -> Result<String, ClientError> {
+        self.perform_post_request("/v1/system/_replication/join_token", name)
+            .await
+    }
+
     // ==== schema
     pub async fn idm_schema_list(&self) -> Result<Vec<Entry>, ClientError> {
         self.perform_get_request("/v1/schema").await
@@ -1672,6 +1827,27 @@ impl KanidmClient {
             .await
     }
 
+    pub async fn idm_oauth2_rs_public_create(
+        &self,
+        name: &str,
+        displayname: &str,
+        redirect_uri: &str,
+    ) -> Result<(), ClientError> {
+        let mut new_oauth2_rs = Entry::default();
+        new_oauth2_rs
+            .attrs
+            .insert("oauth2_rs_name".to_string(), vec![name.to_string()]);
+        new_oauth2_rs
+            .attrs
+            .insert("displayname".to_string(), vec![displayname.to_string()]);
+        new_oauth2_rs.attrs.insert(
+            "oauth2_rs_origin".to_string(),
+            vec![redirect_uri.to_string()],
+        );
+        self.perform_post_request("/v1/oauth2/_public", new_oauth2_rs)
+            .await
+    }
+
     // TODO: the "id" here is actually the *name* not the uuid of the entry...
     pub async fn idm_oauth2_rs_get(&self, id: &str) -> Result<Option<Entry>, ClientError> {
         self.perform_get_request(format!("/v1/oauth2/{}", id).as_str())
@@ -1868,6 +2044,101 @@ impl KanidmClient {
             .await
     }
 
+    pub async fn idm_oauth2_rs_set_jwt_sign_alg(
+        &self,
+        id: &str,
+        alg: &str,
+    ) -> Result<(), ClientError> {
+        let mut update_oauth2_rs = Entry {
+            attrs: BTreeMap::new(),
+        };
+        update_oauth2_rs
+            .attrs
+            .insert("oauth2_jwt_sign_alg".to_string(), vec![alg.to_string()]);
+        self.perform_patch_request(format!("/v1/oauth2/{}", id).as_str(), update_oauth2_rs)
+            .await
+    }
+
+    pub async fn idm_oauth2_rs_set_access_token_expiry(
+        &self,
+        id: &str,
+        expiry: u32,
+    ) -> Result<(), ClientError> {
+        let mut update_oauth2_rs = Entry {
+            attrs: BTreeMap::new(),
+        };
+        update_oauth2_rs.attrs.insert(
+            "oauth2_access_token_expiry".to_string(),
+            vec![expiry.to_string()],
+        );
+        self.perform_patch_request(format!("/v1/oauth2/{}", id).as_str(), update_oauth2_rs)
+            .await
+    }
+
+    /// Set how long refresh tokens may be used for. If `None`, refresh tokens are
+    /// no longer issued to this resource server.
+    pub async fn idm_oauth2_rs_set_refresh_token_expiry(
+        &self,
+        id: &str,
+        expiry: Option<u32>,
+    ) -> Result<(), ClientError> {
+        let mut update_oauth2_rs = Entry {
+            attrs: BTreeMap::new(),
+        };
+        update_oauth2_rs.attrs.insert(
+            "oauth2_refresh_token_expiry".to_string(),
+            expiry.map(|e| vec![e.to_string()]).unwrap_or_default(),
+        );
+        self.perform_patch_request(format!("/v1/oauth2/{}", id).as_str(), update_oauth2_rs)
+            .await
+    }
+
+    pub async fn idm_oauth2_rs_enable_session_outlive_parent(
+        &self,
+        id: &str,
+    ) -> Result<(), ClientError> {
+        let mut update_oauth2_rs = Entry {
+            attrs: BTreeMap::new(),
+        };
+        update_oauth2_rs.attrs.insert(
+            "oauth2_session_outlive_parent".to_string(),
+            vec!["true".to_string()],
+        );
+        self.perform_patch_request(format!("/v1/oauth2/{}", id).as_str(), update_oauth2_rs)
+            .await
+    }
+
+    pub async fn idm_oauth2_rs_disable_session_outlive_parent(
+        &self,
+        id: &str,
+    ) -> Result<(), ClientError> {
+        let mut update_oauth2_rs = Entry {
+            attrs: BTreeMap::new(),
+        };
+        update_oauth2_rs.attrs.insert(
+            "oauth2_session_outlive_parent".to_string(),
+            vec!["false".to_string()],
+        );
+        self.perform_patch_request(format!("/v1/oauth2/{}", id).as_str(), update_oauth2_rs)
+            .await
+    }
+
+    pub async fn idm_oauth2_rs_set_token_exchange_audience(
+        &self,
+        id: &str,
+        audience: Vec<&str>,
+    ) -> Result<(), ClientError> {
+        let mut update_oauth2_rs = Entry {
+            attrs: BTreeMap::new(),
+        };
+        update_oauth2_rs.attrs.insert(
+            "oauth2_token_exchange_audience".to_string(),
+            audience.into_iter().map(str::to_string).collect(),
+        );
+        self.perform_patch_request(format!("/v1/oauth2/{}", id).as_str(), update_oauth2_rs)
+            .await
+    }
+
     // ==== recycle bin
     pub async fn recycle_bin_list(&self) -> Result<Vec<Entry>, ClientError> {
         self.perform_get_request("/v1/recycle_bin").await
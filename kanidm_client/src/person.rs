@@ -1,7 +1,7 @@
 use std::collections::BTreeMap;
 
 use kanidm_proto::v1::{
-    AccountUnixExtend, CredentialStatus, Entry, SingleStringRequest, UatStatus,
+    AccountUnixExtend, CredentialStatus, Entry, Oauth2SessionStatus, SingleStringRequest, UatStatus,
 };
 use uuid::Uuid;
 
@@ -194,6 +194,11 @@ impl KanidmClient {
             .await
     }
 
+    pub async fn idm_person_account_unix_remove(&self, id: &str) -> Result<(), ClientError> {
+        self.perform_delete_request(format!("/v1/person/{}/_unix", id).as_str())
+            .await
+    }
+
     pub async fn idm_person_account_unix_cred_put(
         &self,
         id: &str,
@@ -258,4 +263,28 @@ impl KanidmClient {
         )
         .await
     }
+
+    pub async fn idm_account_list_oauth2_session(
+        &self,
+        id: &str,
+    ) -> Result<Vec<Oauth2SessionStatus>, ClientError> {
+        self.perform_get_request(format!("/v1/account/{}/_oauth2_session", id).as_str())
+            .await
+    }
+
+    pub async fn idm_account_destroy_oauth2_session(
+        &self,
+        id: &str,
+        session_id: Uuid,
+    ) -> Result<(), ClientError> {
+        self.perform_delete_request(
+            format!(
+                "/v1/account/{}/_oauth2_session/{}",
+                id,
+                &session_id.to_string()
+            )
+            .as_str(),
+        )
+        .await
+    }
 }
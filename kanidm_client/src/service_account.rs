@@ -153,6 +153,11 @@ impl KanidmClient {
             .await
     }
 
+    pub async fn idm_service_account_unix_remove(&self, id: &str) -> Result<(), ClientError> {
+        self.perform_delete_request(format!("/v1/service_account/{}/_unix", id).as_str())
+            .await
+    }
+
     pub async fn idm_service_account_into_person(&self, id: &str) -> Result<(), ClientError> {
         self.perform_post_request(
             format!("/v1/service_account/{}/_into_person", id).as_str(),
@@ -13,3 +13,12 @@ pub enum AppLink {
         icon: Option<Url>,
     },
 }
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+/// The scopes a user has consented to release to an oauth2 resource server. Revoking
+/// this consent also ends the users sessions with that resource server.
+pub struct Oauth2Consent {
+    pub name: String,
+    pub display_name: String,
+    pub scopes: Vec<String>,
+}
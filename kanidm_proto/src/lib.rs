@@ -12,7 +12,9 @@ pub mod constants;
 pub mod internal;
 pub mod messages;
 pub mod oauth2;
+pub mod openapi;
 pub mod scim_v1;
+pub mod scim_v2;
 pub mod utils;
 pub mod v1;
 
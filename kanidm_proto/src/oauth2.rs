@@ -58,6 +58,42 @@ pub struct AuthorisationRequestOidc {
     pub acr: Option<String>,
 }
 
+/// A reference to an authorisation request that the client previously submitted to the
+/// pushed authorisation request endpoint. See RFC 9126.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AuthorisationRequestReference {
+    pub client_id: String,
+    pub request_uri: String,
+}
+
+/// The authorisation endpoint accepts either a complete request, or a reference to
+/// a request that was pushed ahead of time.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum AuthorisationRequestOrPushed {
+    Request(AuthorisationRequest),
+    Pushed(AuthorisationRequestReference),
+}
+
+/// A pushed authorisation request, sent by the client directly to the server. This
+/// carries the same parameters as an authorisation request.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PushedAuthorisationRequest {
+    #[serde(flatten)]
+    pub auth_req: AuthorisationRequest,
+    // REQUIRED, if the client is not authenticating with basic auth and has a secret.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_secret: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PushedAuthorisationResponse {
+    // The value to send as request_uri to the authorisation endpoint.
+    pub request_uri: String,
+    // Seconds until the request_uri expires.
+    pub expires_in: u32,
+}
+
 /// When we request to authorise, it can either prompt us for consent,
 /// or it can immediately be granted due the past grant.
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -65,6 +101,9 @@ pub enum AuthorisationResponse {
     ConsentRequested {
         // A pretty-name of the client
         client_name: String,
+        // The logo of the client as a data url, if it has one.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        client_image: Option<String>,
         // A list of scopes requested / to be issued.
         scopes: Vec<String>,
         // Extra PII that may be requested
@@ -77,21 +116,48 @@ pub enum AuthorisationResponse {
     Permitted,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "snake_case", tag = "grant_type")]
+pub enum GrantTypeReq {
+    AuthorizationCode {
+        // As sent by the authorisationCode
+        code: String,
+        // Must be the same as the original redirect uri.
+        redirect_uri: Url,
+        code_verifier: Option<String>,
+    },
+    RefreshToken {
+        refresh_token: String,
+        // If present, must be equal to or a subset of the originally granted scopes.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        scope: Option<String>,
+    },
+    // https://datatracker.ietf.org/doc/html/rfc8693#section-2.1
+    #[serde(rename = "urn:ietf:params:oauth:grant-type:token-exchange")]
+    TokenExchange {
+        // The token to exchange. Must be an access token issued to the requesting client.
+        subject_token: String,
+        subject_token_type: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        requested_token_type: Option<String>,
+        // The client_id of the resource server the new token is for.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        audience: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        scope: Option<String>,
+    },
+}
+
 // The resource server then contacts the token endpoint with
 //
 #[derive(Serialize, Deserialize, Debug)]
 pub struct AccessTokenRequest {
-    // must be authorization_code
-    pub grant_type: String,
-    // As sent by the authorisationCode
-    pub code: String,
-    // Must be the same as the original redirect uri.
-    pub redirect_uri: Url,
+    #[serde(flatten)]
+    pub grant_type: GrantTypeReq,
     // REQUIRED, if the client is not authenticating with the
     //  authorization server as described in Section 3.2.1.
     pub client_id: Option<String>,
     pub client_secret: Option<String>,
-    pub code_verifier: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -122,6 +188,9 @@ pub struct AccessTokenResponse {
     #[serde(skip_serializing_if = "Option::is_none")]
     /// Oidc puts the token here.
     pub id_token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// Only present in the response to a token exchange.
+    pub issued_token_type: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -204,6 +273,9 @@ pub enum GrantType {
     #[serde(rename = "authorization_code")]
     AuthorisationCode,
     Implicit,
+    RefreshToken,
+    #[serde(rename = "urn:ietf:params:oauth:grant-type:token-exchange")]
+    TokenExchange,
 }
 
 fn grant_types_supported_default() -> Vec<GrantType> {
@@ -223,11 +295,13 @@ pub enum SubjectType {
 pub enum IdTokenSignAlg {
     ES256,
     RS256,
+    HS256,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum TokenEndpointAuthMethod {
+    None,
     ClientSecretPost,
     ClientSecretBasic,
     ClientSecretJwt,
@@ -350,6 +424,11 @@ pub struct OidcDiscoveryResponse {
     pub op_policy_uri: Option<Url>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub op_tos_uri: Option<Url>,
+    // https://datatracker.ietf.org/doc/html/rfc9126#section-5
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pushed_authorization_request_endpoint: Option<Url>,
+    #[serde(default)]
+    pub require_pushed_authorization_requests: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
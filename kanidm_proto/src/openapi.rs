@@ -0,0 +1,385 @@
+//! OpenAPI schemas of the v1 request and response types. These are written to match how the
+//! types are serialised, so they must be updated when a type changes.
+
+use serde_json::{json, Map, Value};
+
+use crate::v1::{
+    BatchOperation, BatchOperationResult, BatchRequest, BatchResponse, CreateRequest,
+    DeleteRequest, Entry, EntryChangeNotification, ErrorResponse, Filter, Modify, ModifyList,
+    ModifyRequest, SearchRequest, SearchResponse, SingleStringRequest, WhoamiResponse,
+};
+
+/// A type that is sent or received by the v1 api, and its OpenAPI schema.
+pub trait ApiSchema {
+    /// The name of the schema in the components of the document.
+    const NAME: &'static str;
+
+    fn schema() -> Value;
+
+    /// A reference to the schema, for use in other schemas and operations.
+    fn schema_ref() -> Value {
+        schema_ref(Self::NAME)
+    }
+}
+
+pub fn schema_ref(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{}", name) })
+}
+
+fn string_pair() -> Value {
+    json!({
+        "type": "array",
+        "items": { "type": "string" },
+        "minItems": 2,
+        "maxItems": 2
+    })
+}
+
+fn operation_error() -> Value {
+    json!({
+        "description": "The lowercase name of the error, or an object of the name and its detail.",
+        "oneOf": [
+            { "type": "string" },
+            { "type": "object", "minProperties": 1, "maxProperties": 1 }
+        ]
+    })
+}
+
+/// A serde externally tagged enum variant, which is an object with the variant as its only key.
+fn variant(name: &str, content: Value) -> Value {
+    json!({
+        "type": "object",
+        "properties": { name: content },
+        "required": [name],
+        "additionalProperties": false
+    })
+}
+
+impl ApiSchema for Entry {
+    const NAME: &'static str = "Entry";
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "attrs": {
+                    "type": "object",
+                    "additionalProperties": { "type": "array", "items": { "type": "string" } }
+                }
+            },
+            "required": ["attrs"]
+        })
+    }
+}
+
+impl ApiSchema for Filter {
+    const NAME: &'static str = "Filter";
+
+    fn schema() -> Value {
+        let filters = json!({ "type": "array", "items": Filter::schema_ref() });
+        json!({
+            "oneOf": [
+                variant("eq", string_pair()),
+                variant("sub", string_pair()),
+                variant("pres", json!({ "type": "string" })),
+                variant("gte", string_pair()),
+                variant("lte", string_pair()),
+                variant("or", filters.clone()),
+                variant("and", filters),
+                variant("andnot", Filter::schema_ref()),
+                { "type": "string", "enum": ["self"] }
+            ]
+        })
+    }
+}
+
+impl ApiSchema for Modify {
+    const NAME: &'static str = "Modify";
+
+    fn schema() -> Value {
+        json!({
+            "oneOf": [
+                variant("present", string_pair()),
+                variant("removed", string_pair()),
+                variant("purged", json!({ "type": "string" }))
+            ]
+        })
+    }
+}
+
+impl ApiSchema for ModifyList {
+    const NAME: &'static str = "ModifyList";
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "mods": { "type": "array", "items": Modify::schema_ref() }
+            },
+            "required": ["mods"]
+        })
+    }
+}
+
+impl ApiSchema for SearchRequest {
+    const NAME: &'static str = "SearchRequest";
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "filter": Filter::schema_ref(),
+                "page_size": { "type": "integer", "minimum": 0 },
+                "page_token": { "type": "string" }
+            },
+            "required": ["filter"]
+        })
+    }
+}
+
+impl ApiSchema for SearchResponse {
+    const NAME: &'static str = "SearchResponse";
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "entries": { "type": "array", "items": Entry::schema_ref() },
+                "next_page_token": { "type": "string" }
+            },
+            "required": ["entries"]
+        })
+    }
+}
+
+impl ApiSchema for CreateRequest {
+    const NAME: &'static str = "CreateRequest";
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "entries": { "type": "array", "items": Entry::schema_ref() }
+            },
+            "required": ["entries"]
+        })
+    }
+}
+
+impl ApiSchema for ModifyRequest {
+    const NAME: &'static str = "ModifyRequest";
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "filter": Filter::schema_ref(),
+                "modlist": ModifyList::schema_ref()
+            },
+            "required": ["filter", "modlist"]
+        })
+    }
+}
+
+impl ApiSchema for DeleteRequest {
+    const NAME: &'static str = "DeleteRequest";
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": { "filter": Filter::schema_ref() },
+            "required": ["filter"]
+        })
+    }
+}
+
+impl ApiSchema for BatchOperation {
+    const NAME: &'static str = "BatchOperation";
+
+    fn schema() -> Value {
+        json!({
+            "oneOf": [
+                variant("create", CreateRequest::schema_ref()),
+                variant("modify", ModifyRequest::schema_ref()),
+                variant("delete", DeleteRequest::schema_ref())
+            ]
+        })
+    }
+}
+
+impl ApiSchema for BatchRequest {
+    const NAME: &'static str = "BatchRequest";
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "operations": { "type": "array", "items": BatchOperation::schema_ref() }
+            },
+            "required": ["operations"]
+        })
+    }
+}
+
+impl ApiSchema for BatchOperationResult {
+    const NAME: &'static str = "BatchOperationResult";
+
+    fn schema() -> Value {
+        json!({
+            "oneOf": [
+                { "type": "string", "enum": ["success", "notattempted"] },
+                variant("failed", operation_error())
+            ]
+        })
+    }
+}
+
+impl ApiSchema for BatchResponse {
+    const NAME: &'static str = "BatchResponse";
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "committed": { "type": "boolean" },
+                "results": { "type": "array", "items": BatchOperationResult::schema_ref() }
+            },
+            "required": ["committed", "results"]
+        })
+    }
+}
+
+impl ApiSchema for WhoamiResponse {
+    const NAME: &'static str = "WhoamiResponse";
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": { "youare": Entry::schema_ref() },
+            "required": ["youare"]
+        })
+    }
+}
+
+impl ApiSchema for SingleStringRequest {
+    const NAME: &'static str = "SingleStringRequest";
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": { "value": { "type": "string" } },
+            "required": ["value"]
+        })
+    }
+}
+
+impl ApiSchema for EntryChangeNotification {
+    const NAME: &'static str = "EntryChangeNotification";
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "uuid": { "type": "string", "format": "uuid" },
+                "change": { "type": "string", "enum": ["create", "modify", "delete"] }
+            },
+            "required": ["uuid", "change"]
+        })
+    }
+}
+
+impl ApiSchema for ErrorResponse {
+    const NAME: &'static str = "ErrorResponse";
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "error": operation_error(),
+                "opid": { "type": "string" },
+                "message": { "type": "string" }
+            },
+            "required": ["error", "opid", "message"]
+        })
+    }
+}
+
+fn insert<T: ApiSchema>(schemas: &mut Map<String, Value>) {
+    schemas.insert(T::NAME.to_string(), T::schema());
+}
+
+/// The schemas of every type that has one, keyed by name.
+pub fn schemas() -> Map<String, Value> {
+    let mut schemas = Map::new();
+    insert::<Entry>(&mut schemas);
+    insert::<Filter>(&mut schemas);
+    insert::<Modify>(&mut schemas);
+    insert::<ModifyList>(&mut schemas);
+    insert::<SearchRequest>(&mut schemas);
+    insert::<SearchResponse>(&mut schemas);
+    insert::<CreateRequest>(&mut schemas);
+    insert::<ModifyRequest>(&mut schemas);
+    insert::<DeleteRequest>(&mut schemas);
+    insert::<BatchOperation>(&mut schemas);
+    insert::<BatchRequest>(&mut schemas);
+    insert::<BatchOperationResult>(&mut schemas);
+    insert::<BatchResponse>(&mut schemas);
+    insert::<WhoamiResponse>(&mut schemas);
+    insert::<SingleStringRequest>(&mut schemas);
+    insert::<EntryChangeNotification>(&mut schemas);
+    insert::<ErrorResponse>(&mut schemas);
+    schemas
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::{schemas, ApiSchema};
+    use crate::v1::Filter;
+
+    fn refs<'a>(v: &'a Value, found: &mut Vec<&'a str>) {
+        match v {
+            Value::Object(m) => m.iter().for_each(|(k, v)| match (k.as_str(), v) {
+                ("$ref", Value::String(r)) => found.push(r),
+                _ => refs(v, found),
+            }),
+            Value::Array(a) => a.iter().for_each(|v| refs(v, found)),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn test_openapi_schema_refs_resolve() {
+        let schemas = schemas();
+        let mut found = Vec::new();
+        schemas.values().for_each(|s| refs(s, &mut found));
+        assert!(!found.is_empty());
+        for r in found {
+            let name = r.trim_start_matches("#/components/schemas/");
+            assert!(schemas.contains_key(name), "unresolved {}", r);
+        }
+    }
+
+    #[test]
+    fn test_openapi_filter_variants() {
+        let schema = Filter::schema();
+        let variants = schema["oneOf"].as_array().expect("oneOf missing");
+        let filters = [
+            Filter::Gte("gidnumber".to_string(), "1000".to_string()),
+            Filter::Lte("gidnumber".to_string(), "1000".to_string()),
+        ];
+        for f in filters {
+            let v = serde_json::to_value(&f).expect("failed to serialise");
+            let name = v
+                .as_object()
+                .and_then(|m| m.keys().next())
+                .expect("not an externally tagged variant");
+            assert!(
+                variants.iter().any(|s| s["properties"].get(name).is_some()),
+                "no schema for {}",
+                name
+            );
+        }
+    }
+}
@@ -0,0 +1,782 @@
+//! SCIM 2.0 (RFC 7643, RFC 7644) users and groups. These are a view of the person and group
+//! entries of the v1 api, so this also translates SCIM filters and patches to v1 filters and
+//! modifications. References, such as the members of a group, are given by spn.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+use crate::v1::{Entry, Filter, Modify, ModifyList};
+
+pub const SCIM_SCHEMA_USER: &str = "urn:ietf:params:scim:schemas:core:2.0:User";
+pub const SCIM_SCHEMA_GROUP: &str = "urn:ietf:params:scim:schemas:core:2.0:Group";
+pub const SCIM_SCHEMA_LIST_RESPONSE: &str = "urn:ietf:params:scim:api:messages:2.0:ListResponse";
+pub const SCIM_SCHEMA_PATCH_OP: &str = "urn:ietf:params:scim:api:messages:2.0:PatchOp";
+pub const SCIM_SCHEMA_ERROR: &str = "urn:ietf:params:scim:api:messages:2.0:Error";
+
+/// A request that can't be translated, with the scimType of its error response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScimRequestError {
+    InvalidFilter(String),
+    InvalidPath(String),
+    InvalidSyntax(String),
+    InvalidValue(String),
+    Mutability(String),
+}
+
+impl ScimRequestError {
+    pub fn scim_type(&self) -> &'static str {
+        match self {
+            ScimRequestError::InvalidFilter(_) => "invalidFilter",
+            ScimRequestError::InvalidPath(_) => "invalidPath",
+            ScimRequestError::InvalidSyntax(_) => "invalidSyntax",
+            ScimRequestError::InvalidValue(_) => "invalidValue",
+            ScimRequestError::Mutability(_) => "mutability",
+        }
+    }
+
+    pub fn detail(&self) -> &str {
+        match self {
+            ScimRequestError::InvalidFilter(d)
+            | ScimRequestError::InvalidPath(d)
+            | ScimRequestError::InvalidSyntax(d)
+            | ScimRequestError::InvalidValue(d)
+            | ScimRequestError::Mutability(d) => d.as_str(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ScimErrorResponse {
+    pub schemas: Vec<String>,
+    /// The http status code, as a string.
+    pub status: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scim_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl ScimErrorResponse {
+    pub fn new(status: u16, scim_type: Option<String>, detail: Option<String>) -> Self {
+        ScimErrorResponse {
+            schemas: vec![SCIM_SCHEMA_ERROR.to_string()],
+            status: status.to_string(),
+            scim_type,
+            detail,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScimListResponse {
+    pub schemas: Vec<String>,
+    #[serde(rename = "totalResults")]
+    pub total_results: usize,
+    #[serde(rename = "startIndex")]
+    pub start_index: usize,
+    #[serde(rename = "itemsPerPage")]
+    pub items_per_page: usize,
+    #[serde(rename = "Resources")]
+    pub resources: Vec<Value>,
+}
+
+impl ScimListResponse {
+    /// The page of resources from `start_index`, which counts from 1.
+    pub fn new(resources: Vec<Value>, start_index: usize, count: Option<usize>) -> Self {
+        let total_results = resources.len();
+        let start_index = start_index.max(1);
+        let resources: Vec<Value> = resources
+            .into_iter()
+            .skip(start_index - 1)
+            .take(count.unwrap_or(usize::MAX))
+            .collect();
+        ScimListResponse {
+            schemas: vec![SCIM_SCHEMA_LIST_RESPONSE.to_string()],
+            total_results,
+            start_index,
+            items_per_page: resources.len(),
+            resources,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScimPatchOperation {
+    /// One of add, remove or replace. Some clients capitalise these.
+    pub op: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScimPatchRequest {
+    pub schemas: Vec<String>,
+    #[serde(rename = "Operations")]
+    pub operations: Vec<ScimPatchOperation>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScimResourceType {
+    User,
+    Group,
+}
+
+impl ScimResourceType {
+    pub fn name(self) -> &'static str {
+        match self {
+            ScimResourceType::User => "User",
+            ScimResourceType::Group => "Group",
+        }
+    }
+
+    pub fn schema(self) -> &'static str {
+        match self {
+            ScimResourceType::User => SCIM_SCHEMA_USER,
+            ScimResourceType::Group => SCIM_SCHEMA_GROUP,
+        }
+    }
+
+    /// The classes of the entries of this resource type.
+    pub fn classes(self) -> &'static [&'static str] {
+        match self {
+            ScimResourceType::User => &["person", "account", "object"],
+            ScimResourceType::Group => &["group", "object"],
+        }
+    }
+
+    /// The filter that matches every entry of this resource type.
+    pub fn class_filter(self) -> Filter {
+        match self {
+            ScimResourceType::User => Filter::Eq("class".to_string(), "person".to_string()),
+            ScimResourceType::Group => Filter::Eq("class".to_string(), "group".to_string()),
+        }
+    }
+
+    /// The entry attribute of a SCIM attribute. SCIM attribute names are case insensitive, and
+    /// may be prefixed by the schema of the resource.
+    pub fn attr(self, scim_attr: &str) -> Option<&'static str> {
+        let scim_attr = scim_attr.to_lowercase();
+        let prefix = format!("{}:", self.schema().to_lowercase());
+        let scim_attr = scim_attr.strip_prefix(&prefix).unwrap_or(&scim_attr);
+        match (self, scim_attr) {
+            (_, "id") => Some("uuid"),
+            (ScimResourceType::User, "username") => Some("name"),
+            (ScimResourceType::User, "displayname") => Some("displayname"),
+            (ScimResourceType::User, "emails") | (ScimResourceType::User, "emails.value") => {
+                Some("mail")
+            }
+            (ScimResourceType::User, "groups") | (ScimResourceType::User, "groups.value") => {
+                Some("memberof")
+            }
+            (ScimResourceType::Group, "displayname") => Some("name"),
+            (ScimResourceType::Group, "members") | (ScimResourceType::Group, "members.value") => {
+                Some("member")
+            }
+            _ => None,
+        }
+    }
+
+    /// If an entry attribute can be changed through SCIM.
+    fn is_writable(attr: &str) -> bool {
+        !matches!(attr, "uuid" | "memberof")
+    }
+
+    /// The SCIM resource of an entry of this resource type.
+    pub fn to_resource(self, entry: &Entry) -> Value {
+        let first = |attr: &str| {
+            entry
+                .attrs
+                .get(attr)
+                .and_then(|vs| vs.first())
+                .map(|v| json!(v))
+                .unwrap_or(Value::Null)
+        };
+        let refs = |attr: &str| {
+            let vs: Vec<Value> = entry
+                .attrs
+                .get(attr)
+                .map(|vs| {
+                    vs.iter()
+                        .map(|v| json!({ "value": v, "display": v }))
+                        .collect()
+                })
+                .unwrap_or_default();
+            vs
+        };
+
+        let mut resource = Map::new();
+        resource.insert("schemas".to_string(), json!([self.schema()]));
+        resource.insert("id".to_string(), first("uuid"));
+        match self {
+            ScimResourceType::User => {
+                resource.insert("userName".to_string(), first("name"));
+                resource.insert("displayName".to_string(), first("displayname"));
+                let emails: Vec<Value> = entry
+                    .attrs
+                    .get("mail")
+                    .map(|vs| {
+                        vs.iter()
+                            .enumerate()
+                            .map(|(i, v)| json!({ "value": v, "primary": i == 0 }))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                if !emails.is_empty() {
+                    resource.insert("emails".to_string(), json!(emails));
+                }
+                let groups = refs("memberof");
+                if !groups.is_empty() {
+                    resource.insert("groups".to_string(), json!(groups));
+                }
+            }
+            ScimResourceType::Group => {
+                resource.insert("displayName".to_string(), first("name"));
+                let members = refs("member");
+                if !members.is_empty() {
+                    resource.insert("members".to_string(), json!(members));
+                }
+            }
+        }
+        resource.insert("meta".to_string(), json!({ "resourceType": self.name() }));
+        Value::Object(resource)
+    }
+
+    /// The entry to create from a SCIM resource. Attributes that aren't stored, or are read
+    /// only, are ignored as RFC 7643 allows.
+    pub fn from_resource(self, resource: &Value) -> Result<Entry, ScimRequestError> {
+        let resource = resource.as_object().ok_or_else(|| {
+            ScimRequestError::InvalidSyntax("The resource must be an object".to_string())
+        })?;
+
+        let mut entry = Entry::default();
+        for (scim_attr, value) in resource.iter() {
+            match self.attr(scim_attr) {
+                Some(attr) if Self::is_writable(attr) => {
+                    let values = scim_values(value)?;
+                    if !values.is_empty() {
+                        entry.attrs.insert(attr.to_string(), values);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let name = match self {
+            ScimResourceType::User => "userName",
+            ScimResourceType::Group => "displayName",
+        };
+        let name = entry.attrs.get("name").cloned().ok_or_else(|| {
+            ScimRequestError::InvalidValue(format!("The resource must have a {}", name))
+        })?;
+        // A person must have a display name, which isn't required by SCIM.
+        if self == ScimResourceType::User && !entry.attrs.contains_key("displayname") {
+            entry.attrs.insert("displayname".to_string(), name);
+        }
+
+        entry.attrs.insert(
+            "class".to_string(),
+            self.classes().iter().map(|c| c.to_string()).collect(),
+        );
+        Ok(entry)
+    }
+
+    /// The filter of a SCIM filter expression, which only matches entries of this resource type.
+    pub fn filter(self, scim_filter: Option<&str>) -> Result<Filter, ScimRequestError> {
+        let class = self.class_filter();
+        match scim_filter {
+            Some(f) if !f.trim().is_empty() => {
+                let f = ScimFilter::parse(f)?;
+                Ok(Filter::And(vec![class, self.translate_filter(&f)?]))
+            }
+            _ => Ok(class),
+        }
+    }
+
+    fn translate_filter(self, f: &ScimFilter) -> Result<Filter, ScimRequestError> {
+        let attr = |a: &str| {
+            self.attr(a)
+                .ok_or_else(|| ScimRequestError::InvalidFilter(format!("Unknown attribute {}", a)))
+        };
+        match f {
+            ScimFilter::Pres(a) => Ok(Filter::Pres(attr(a)?.to_string())),
+            ScimFilter::Compare(a, op, v) => {
+                let a = attr(a)?.to_string();
+                match op.as_str() {
+                    "eq" => Ok(Filter::Eq(a, v.clone())),
+                    "ne" => Ok(not(Filter::Eq(a, v.clone()))),
+                    "co" => Ok(Filter::Sub(a, v.clone())),
+                    _ => Err(ScimRequestError::InvalidFilter(format!(
+                        "The operator {} is not supported",
+                        op
+                    ))),
+                }
+            }
+            ScimFilter::And(fs) => fs
+                .iter()
+                .map(|f| self.translate_filter(f))
+                .collect::<Result<_, _>>()
+                .map(Filter::And),
+            ScimFilter::Or(fs) => fs
+                .iter()
+                .map(|f| self.translate_filter(f))
+                .collect::<Result<_, _>>()
+                .map(Filter::Or),
+            ScimFilter::Not(f) => self.translate_filter(f).map(not),
+        }
+    }
+
+    /// The modifications of a SCIM patch.
+    pub fn modlist(self, patch: &ScimPatchRequest) -> Result<ModifyList, ScimRequestError> {
+        if !patch.schemas.iter().any(|s| s == SCIM_SCHEMA_PATCH_OP) {
+            return Err(ScimRequestError::InvalidSyntax(format!(
+                "The request must have the schema {}",
+                SCIM_SCHEMA_PATCH_OP
+            )));
+        }
+
+        let mut mods = Vec::new();
+        for operation in patch.operations.iter() {
+            let op = operation.op.to_lowercase();
+            match &operation.path {
+                Some(path) => self.patch_path(&op, path, operation.value.as_ref(), &mut mods)?,
+                // Without a path, the value is an object of the attributes to change.
+                None => match (op.as_str(), &operation.value) {
+                    ("add" | "replace", Some(Value::Object(attrs))) => {
+                        for (path, value) in attrs.iter() {
+                            self.patch_path(&op, path, Some(value), &mut mods)?;
+                        }
+                    }
+                    _ => {
+                        return Err(ScimRequestError::InvalidPath(format!(
+                            "A {} operation must have a path",
+                            op
+                        )))
+                    }
+                },
+            }
+        }
+        Ok(ModifyList::new_list(mods))
+    }
+
+    fn patch_path(
+        self,
+        op: &str,
+        path: &str,
+        value: Option<&Value>,
+        mods: &mut Vec<Modify>,
+    ) -> Result<(), ScimRequestError> {
+        // A value filter, such as members[value eq "x"], selects the values to remove.
+        let (path, selected) = match path.split_once('[') {
+            Some((attr_path, rest)) => {
+                let f = rest.strip_suffix(']').ok_or_else(|| {
+                    ScimRequestError::InvalidPath(format!("Unsupported path {}", path))
+                })?;
+                (attr_path, Some(selected_values(&ScimFilter::parse(f)?)?))
+            }
+            None => (path, None),
+        };
+
+        let attr = self
+            .attr(path)
+            .ok_or_else(|| ScimRequestError::InvalidPath(format!("Unknown attribute {}", path)))?;
+        if !Self::is_writable(attr) {
+            return Err(ScimRequestError::Mutability(format!(
+                "The attribute {} is read only",
+                path
+            )));
+        }
+        let attr = attr.to_string();
+
+        let values = match (selected, value) {
+            (Some(_), Some(_)) => {
+                return Err(ScimRequestError::InvalidPath(
+                    "A value filter is only supported to remove values".to_string(),
+                ))
+            }
+            (Some(selected), None) => selected,
+            (None, Some(value)) => scim_values(value)?,
+            (None, None) => Vec::new(),
+        };
+
+        match op {
+            "add" if !values.is_empty() => {
+                mods.extend(values.into_iter().map(|v| Modify::Present(attr.clone(), v)));
+            }
+            "replace" if !values.is_empty() => {
+                mods.push(Modify::Purged(attr.clone()));
+                mods.extend(values.into_iter().map(|v| Modify::Present(attr.clone(), v)));
+            }
+            "remove" if values.is_empty() => mods.push(Modify::Purged(attr)),
+            "remove" => {
+                mods.extend(values.into_iter().map(|v| Modify::Removed(attr.clone(), v)));
+            }
+            "add" | "replace" => {
+                return Err(ScimRequestError::InvalidValue(format!(
+                    "A {} operation must have a value",
+                    op
+                )))
+            }
+            _ => {
+                return Err(ScimRequestError::InvalidSyntax(format!(
+                    "Unknown operation {}",
+                    op
+                )))
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Negate a filter. An isolated AndNot matches nothing, so it is joined with a term that
+/// matches every entry.
+fn not(f: Filter) -> Filter {
+    Filter::And(vec![
+        Filter::Pres("class".to_string()),
+        Filter::AndNot(Box::new(f)),
+    ])
+}
+
+/// The string values of a SCIM attribute. Multi valued attributes are arrays of values, or of
+/// objects with a value.
+fn scim_values(value: &Value) -> Result<Vec<String>, ScimRequestError> {
+    match value {
+        Value::Null => Ok(Vec::new()),
+        Value::String(s) => Ok(vec![s.clone()]),
+        Value::Bool(b) => Ok(vec![b.to_string()]),
+        Value::Number(n) => Ok(vec![n.to_string()]),
+        Value::Object(o) => match o.get("value") {
+            Some(v) => scim_values(v),
+            None => Err(ScimRequestError::InvalidValue(
+                "A complex value must have a value".to_string(),
+            )),
+        },
+        Value::Array(vs) => vs.iter().try_fold(Vec::new(), |mut acc, v| {
+            acc.extend(scim_values(v)?);
+            Ok(acc)
+        }),
+    }
+}
+
+/// The values selected by the filter of a value path, which may only compare the value.
+fn selected_values(f: &ScimFilter) -> Result<Vec<String>, ScimRequestError> {
+    match f {
+        ScimFilter::Compare(a, op, v) if a.eq_ignore_ascii_case("value") && op == "eq" => {
+            Ok(vec![v.clone()])
+        }
+        ScimFilter::Or(fs) => fs.iter().try_fold(Vec::new(), |mut acc, f| {
+            acc.extend(selected_values(f)?);
+            Ok(acc)
+        }),
+        _ => Err(ScimRequestError::InvalidPath(
+            "A value filter may only select values with value eq".to_string(),
+        )),
+    }
+}
+
+/// A parsed SCIM filter (RFC 7644 3.4.2.2). Attribute names and operators are not yet checked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScimFilter {
+    Pres(String),
+    /// The attribute, the lowercase operator, and the value.
+    Compare(String, String, String),
+    And(Vec<ScimFilter>),
+    Or(Vec<ScimFilter>),
+    Not(Box<ScimFilter>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Open,
+    Close,
+    Word(String),
+    Value(String),
+}
+
+fn tokenise(s: &str) -> Result<Vec<Token>, ScimRequestError> {
+    let mut tokens = Vec::new();
+    let mut chars = s.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            '"' => {
+                // Find the closing quote, then let serde decode the escapes.
+                let mut escaped = false;
+                let mut end = None;
+                for (i, c) in chars.by_ref() {
+                    match c {
+                        '\\' if !escaped => escaped = true,
+                        '"' if !escaped => {
+                            end = Some(i);
+                            break;
+                        }
+                        _ => escaped = false,
+                    }
+                }
+                let end = end.ok_or_else(|| {
+                    ScimRequestError::InvalidFilter("Unterminated string".to_string())
+                })?;
+                let v: String = serde_json::from_str(&s[start..=end])
+                    .map_err(|_| ScimRequestError::InvalidFilter("Invalid string".to_string()))?;
+                tokens.push(Token::Value(v));
+            }
+            _ => {
+                let mut end = start + c.len_utf8();
+                while let Some((i, c)) = chars.peek() {
+                    if c.is_whitespace() || *c == '(' || *c == ')' || *c == '"' {
+                        break;
+                    }
+                    end = i + c.len_utf8();
+                    chars.next();
+                }
+                tokens.push(Token::Word(s[start..end].to_string()));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(self.tokens.get(self.pos), Some(Token::Word(w)) if w.eq_ignore_ascii_case(keyword))
+    }
+
+    fn next_token(&mut self) -> Option<Token> {
+        let t = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        t
+    }
+
+    fn or_expr(&mut self) -> Result<ScimFilter, ScimRequestError> {
+        let mut fs = vec![self.and_expr()?];
+        while self.peek_keyword("or") {
+            self.pos += 1;
+            fs.push(self.and_expr()?);
+        }
+        Ok(if fs.len() == 1 {
+            fs.remove(0)
+        } else {
+            ScimFilter::Or(fs)
+        })
+    }
+
+    fn and_expr(&mut self) -> Result<ScimFilter, ScimRequestError> {
+        let mut fs = vec![self.term()?];
+        while self.peek_keyword("and") {
+            self.pos += 1;
+            fs.push(self.term()?);
+        }
+        Ok(if fs.len() == 1 {
+            fs.remove(0)
+        } else {
+            ScimFilter::And(fs)
+        })
+    }
+
+    fn group(&mut self) -> Result<ScimFilter, ScimRequestError> {
+        match self.next_token() {
+            Some(Token::Open) => {}
+            _ => return Err(ScimRequestError::InvalidFilter("Expected (".to_string())),
+        }
+        let f = self.or_expr()?;
+        match self.next_token() {
+            Some(Token::Close) => Ok(f),
+            _ => Err(ScimRequestError::InvalidFilter("Expected )".to_string())),
+        }
+    }
+
+    fn term(&mut self) -> Result<ScimFilter, ScimRequestError> {
+        if self.peek_keyword("not") {
+            self.pos += 1;
+            return self.group().map(|f| ScimFilter::Not(Box::new(f)));
+        }
+        if self.tokens.get(self.pos) == Some(&Token::Open) {
+            return self.group();
+        }
+
+        let attr = match self.next_token() {
+            Some(Token::Word(attr)) => attr,
+            _ => {
+                return Err(ScimRequestError::InvalidFilter(
+                    "Expected an attribute".to_string(),
+                ))
+            }
+        };
+        let op = match self.next_token() {
+            Some(Token::Word(op)) => op.to_lowercase(),
+            _ => {
+                return Err(ScimRequestError::InvalidFilter(
+                    "Expected an operator".to_string(),
+                ))
+            }
+        };
+        match op.as_str() {
+            "pr" => Ok(ScimFilter::Pres(attr)),
+            "eq" | "ne" | "co" | "sw" | "ew" | "gt" | "ge" | "lt" | "le" => match self.next_token()
+            {
+                Some(Token::Value(v)) => Ok(ScimFilter::Compare(attr, op, v)),
+                // true, false and numbers are not quoted.
+                Some(Token::Word(v)) if v != "null" => Ok(ScimFilter::Compare(attr, op, v)),
+                _ => Err(ScimRequestError::InvalidFilter(
+                    "Expected a value".to_string(),
+                )),
+            },
+            _ => Err(ScimRequestError::InvalidFilter(format!(
+                "Unknown operator {}",
+                op
+            ))),
+        }
+    }
+}
+
+impl ScimFilter {
+    pub fn parse(s: &str) -> Result<Self, ScimRequestError> {
+        let mut parser = Parser {
+            tokens: tokenise(s)?,
+            pos: 0,
+        };
+        let f = parser.or_expr()?;
+        if parser.pos != parser.tokens.len() {
+            return Err(ScimRequestError::InvalidFilter(
+                "Unexpected trailing input".to_string(),
+            ));
+        }
+        Ok(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_scim_filter_parse() {
+        let f = ScimFilter::parse(
+            r#"userName eq "a\"b" and (emails co "@example.com" or not (displayName pr))"#,
+        )
+        .expect("Failed to parse filter");
+        assert_eq!(
+            f,
+            ScimFilter::And(vec![
+                ScimFilter::Compare("userName".to_string(), "eq".to_string(), "a\"b".to_string()),
+                ScimFilter::Or(vec![
+                    ScimFilter::Compare(
+                        "emails".to_string(),
+                        "co".to_string(),
+                        "@example.com".to_string()
+                    ),
+                    ScimFilter::Not(Box::new(ScimFilter::Pres("displayName".to_string()))),
+                ]),
+            ])
+        );
+
+        assert!(ScimFilter::parse(r#"userName eq "a"#).is_err());
+        assert!(ScimFilter::parse("userName eq").is_err());
+        assert!(ScimFilter::parse("(userName pr").is_err());
+        assert!(ScimFilter::parse("userName pr)").is_err());
+    }
+
+    #[test]
+    fn test_scim_filter_translate() {
+        let f = ScimResourceType::User
+            .filter(Some(r#"USERNAME eq "alice" or id ne "x""#))
+            .expect("Failed to translate filter");
+        assert_eq!(
+            f,
+            Filter::And(vec![
+                Filter::Eq("class".to_string(), "person".to_string()),
+                Filter::Or(vec![
+                    Filter::Eq("name".to_string(), "alice".to_string()),
+                    not(Filter::Eq("uuid".to_string(), "x".to_string())),
+                ]),
+            ])
+        );
+
+        assert_eq!(
+            ScimResourceType::Group.filter(Some(r#"members sw "a""#)),
+            Err(ScimRequestError::InvalidFilter(
+                "The operator sw is not supported".to_string()
+            ))
+        );
+        assert!(ScimResourceType::Group
+            .filter(Some(r#"userName eq "a""#))
+            .is_err());
+    }
+
+    #[test]
+    fn test_scim_resource_roundtrip() {
+        let entry = ScimResourceType::User
+            .from_resource(&json!({
+                "schemas": [SCIM_SCHEMA_USER],
+                "id": "ignored",
+                "userName": "alice",
+                "emails": [{ "value": "alice@example.com", "type": "work" }],
+                "active": true
+            }))
+            .expect("Failed to read resource");
+        assert_eq!(entry.attrs.get("name"), Some(&vec!["alice".to_string()]));
+        assert_eq!(
+            entry.attrs.get("displayname"),
+            Some(&vec!["alice".to_string()])
+        );
+        assert!(!entry.attrs.contains_key("uuid"));
+
+        let resource = ScimResourceType::User.to_resource(&entry);
+        assert_eq!(resource["userName"], json!("alice"));
+        assert_eq!(
+            resource["emails"],
+            json!([{ "value": "alice@example.com", "primary": true }])
+        );
+
+        assert!(ScimResourceType::Group
+            .from_resource(&json!({ "members": [] }))
+            .is_err());
+    }
+
+    #[test]
+    fn test_scim_patch_modlist() {
+        let patch: ScimPatchRequest = serde_json::from_value(json!({
+            "schemas": [SCIM_SCHEMA_PATCH_OP],
+            "Operations": [
+                { "op": "Add", "path": "members", "value": [{ "value": "alice" }] },
+                { "op": "remove", "path": "members[value eq \"bob\"]" },
+                { "op": "replace", "value": { "displayName": "staff" } }
+            ]
+        }))
+        .expect("Invalid patch");
+        let modlist = ScimResourceType::Group
+            .modlist(&patch)
+            .expect("Failed to translate patch");
+        assert_eq!(
+            serde_json::to_value(&modlist).expect("Invalid modlist"),
+            serde_json::to_value(ModifyList::new_list(vec![
+                Modify::Present("member".to_string(), "alice".to_string()),
+                Modify::Removed("member".to_string(), "bob".to_string()),
+                Modify::Purged("name".to_string()),
+                Modify::Present("name".to_string(), "staff".to_string()),
+            ]))
+            .expect("Invalid modlist")
+        );
+
+        let patch: ScimPatchRequest = serde_json::from_value(json!({
+            "schemas": [SCIM_SCHEMA_PATCH_OP],
+            "Operations": [{ "op": "add", "path": "groups", "value": "admins" }]
+        }))
+        .expect("Invalid patch");
+        assert!(matches!(
+            ScimResourceType::User.modlist(&patch),
+            Err(ScimRequestError::Mutability(_))
+        ));
+    }
+}
@@ -50,6 +50,7 @@ pub enum ConsistencyError {
     SchemaClassMissingAttribute(String, String),
     SchemaClassPhantomAttribute(String, String),
     SchemaUuidNotUnique(Uuid),
+    SchemaAttributeEncryptedIndexed(String),
     QueryServerSearchFailure,
     EntryUuidCorrupt(u64),
     UuidIndexCorrupt(String),
@@ -249,6 +250,93 @@ pub enum OperationError {
     ReplReplayFailure,
     ReplEntryNotChanged,
     ReplInvalidRUVState,
+    ReplDomainUuidMismatch,
+    ReplReadOnlyReplica,
+    ReplSchemaUnknown,
+    /// The entry was changed after the client read it.
+    EntryChanged,
+}
+
+impl fmt::Display for OperationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OperationError::SessionExpired => write!(f, "The session has expired"),
+            OperationError::EmptyRequest => write!(f, "The request contained no changes"),
+            OperationError::NoMatchingEntries => write!(f, "No entries matched the request"),
+            OperationError::NoMatchingAttributes => {
+                write!(f, "No attributes matched the request")
+            }
+            OperationError::SchemaViolation(e) => write!(f, "Schema violation: {:?}", e),
+            OperationError::Plugin(e) => write!(f, "Plugin failure: {:?}", e),
+            OperationError::InvalidAttributeName(a) => write!(f, "Invalid attribute name: {}", a),
+            OperationError::InvalidAttribute(a) => write!(f, "Invalid attribute: {}", a),
+            OperationError::InvalidAccountState(s) => write!(f, "Invalid account state: {}", s),
+            OperationError::InvalidAuthState(s) => {
+                write!(f, "Invalid authentication state: {}", s)
+            }
+            OperationError::AccessDenied => write!(f, "Access denied"),
+            OperationError::NotAuthenticated => write!(f, "Authentication is required"),
+            OperationError::NotAuthorised => write!(f, "Not authorised"),
+            OperationError::SystemProtectedObject => {
+                write!(f, "The entry is protected by the system")
+            }
+            OperationError::SystemProtectedAttribute => {
+                write!(f, "The attribute is protected by the system")
+            }
+            OperationError::PasswordQuality(feedback) => {
+                write!(f, "The password does not meet the quality requirements")?;
+                feedback.iter().try_for_each(|fb| write!(f, ". {}", fb))
+            }
+            OperationError::ResourceLimit => {
+                write!(f, "The request exceeded the resource limits of the server")
+            }
+            OperationError::Wait(until) => write!(f, "Try again after {}", until),
+            OperationError::ReplReadOnlyReplica => {
+                write!(f, "Changes can't be made on a read only replica")
+            }
+            OperationError::EntryChanged => {
+                write!(f, "The entry was changed after it was read")
+            }
+            e => write!(f, "Internal server error: {:?}", e),
+        }
+    }
+}
+
+/// The kind of change made to an entry.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryChange {
+    Create,
+    Modify,
+    Delete,
+}
+
+/// A notification that an entry was changed, sent to subscribers of changes.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct EntryChangeNotification {
+    pub uuid: Uuid,
+    pub change: EntryChange,
+}
+
+/// The body of an unsuccessful api response.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ErrorResponse {
+    /// The error, which clients can match on to determine the cause of the failure.
+    pub error: OperationError,
+    /// The id of the operation, which identifies it in the server logs.
+    pub opid: String,
+    /// A description of the error that can be displayed.
+    pub message: String,
+}
+
+impl ErrorResponse {
+    pub fn new(error: OperationError, opid: String) -> Self {
+        ErrorResponse {
+            message: error.to_string(),
+            error,
+            opid,
+        }
+    }
 }
 
 impl PartialEq for OperationError {
@@ -363,6 +451,7 @@ pub enum UatPurposeStatus {
 pub struct UatStatus {
     pub account_id: Uuid,
     pub session_id: Uuid,
+    pub label: String,
     #[serde(with = "time::serde::timestamp::option")]
     pub expiry: Option<time::OffsetDateTime>,
     #[serde(with = "time::serde::timestamp")]
@@ -374,6 +463,7 @@ impl fmt::Display for UatStatus {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "account_id: {}", self.account_id)?;
         writeln!(f, "session_id: {}", self.session_id)?;
+        writeln!(f, "label: {}", self.label)?;
         if let Some(exp) = self.expiry {
             writeln!(f, "expiry: {}", exp)?;
         } else {
@@ -389,6 +479,176 @@ impl fmt::Display for UatStatus {
     }
 }
 
+/// An oauth2 session of an account, and the resource server it was issued to. These
+/// sessions are children of a user auth token session.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "lowercase")]
+pub struct Oauth2SessionStatus {
+    pub account_id: Uuid,
+    pub session_id: Uuid,
+    pub parent_session_id: Uuid,
+    pub rs_uuid: Uuid,
+    /// The name of the resource server, if it still exists.
+    pub rs_name: Option<String>,
+    #[serde(with = "time::serde::timestamp::option")]
+    pub expiry: Option<time::OffsetDateTime>,
+    #[serde(with = "time::serde::timestamp")]
+    pub issued_at: time::OffsetDateTime,
+}
+
+impl fmt::Display for Oauth2SessionStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "account_id: {}", self.account_id)?;
+        writeln!(f, "session_id: {}", self.session_id)?;
+        writeln!(f, "parent_session_id: {}", self.parent_session_id)?;
+        if let Some(rs_name) = &self.rs_name {
+            writeln!(f, "resource_server: {}", rs_name)?;
+        } else {
+            writeln!(f, "resource_server: {}", self.rs_uuid)?;
+        }
+        if let Some(exp) = self.expiry {
+            writeln!(f, "expiry: {}", exp)?;
+        } else {
+            writeln!(f, "expiry: -")?;
+        }
+        writeln!(f, "issued_at: {}", self.issued_at)?;
+        Ok(())
+    }
+}
+
+/// The range of changes from a single server that are held by a replica.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub struct ReplRangeStatus {
+    pub server_uuid: Uuid,
+    #[serde(with = "time::serde::timestamp")]
+    pub first_change: time::OffsetDateTime,
+    #[serde(with = "time::serde::timestamp")]
+    pub last_change: time::OffsetDateTime,
+}
+
+/// A replication agreement and the state of replication with its partner, as observed by
+/// the server that reports it.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "lowercase")]
+pub struct ReplAgreementStatus {
+    pub uuid: Uuid,
+    pub name: String,
+    pub partner_address: Option<String>,
+    /// The changes the partner held when changes were last exchanged with it.
+    pub partner_ruv: Vec<ReplRangeStatus>,
+    #[serde(with = "time::serde::timestamp::option")]
+    pub last_sync: Option<time::OffsetDateTime>,
+    /// The number of entries with changes that the partner has not received, if changes
+    /// have been exchanged with it since this server started.
+    pub pending_changes: Option<u64>,
+    /// How long the oldest change the partner has not received has been waiting, in seconds.
+    pub lag_secs: Option<u64>,
+    /// The rate entries from the partner were applied at the last exchange.
+    pub changes_per_sec: Option<f64>,
+    /// The last failure to exchange changes with the partner.
+    pub last_error: Option<String>,
+    #[serde(with = "time::serde::timestamp::option")]
+    pub last_error_time: Option<time::OffsetDateTime>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "lowercase")]
+pub struct ReplTopologyStatus {
+    pub domain_uuid: Uuid,
+    pub server_uuid: Uuid,
+    /// The changes held by this server.
+    pub ruv: Vec<ReplRangeStatus>,
+    pub agreements: Vec<ReplAgreementStatus>,
+}
+
+/// The change that last modified an attribute of an entry.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub struct AttributeChange {
+    pub attr: String,
+    /// The change identifier, as replication orders changes by it.
+    pub cid: String,
+    /// The server the change was made on.
+    pub server_uuid: Uuid,
+    #[serde(with = "time::serde::timestamp")]
+    pub changed: time::OffsetDateTime,
+}
+
+/// How an administrator resolves a replication conflict entry.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReplConflictResolution {
+    /// Return the conflict entry to life. The entry it conflicts with must first be changed
+    /// so that they no longer share unique values.
+    Keep,
+    /// Permanently delete the conflict entry.
+    Discard,
+}
+
+impl fmt::Display for ReplRangeStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} - {}",
+            self.server_uuid, self.first_change, self.last_change
+        )
+    }
+}
+
+impl fmt::Display for AttributeChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} on {} ({})",
+            self.attr, self.changed, self.server_uuid, self.cid
+        )
+    }
+}
+
+impl fmt::Display for ReplTopologyStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "domain_uuid: {}", self.domain_uuid)?;
+        writeln!(f, "server_uuid: {}", self.server_uuid)?;
+        for range in &self.ruv {
+            writeln!(f, "ruv: {}", range)?;
+        }
+        for agreement in &self.agreements {
+            writeln!(f, "---")?;
+            writeln!(f, "agreement: {}", agreement.name)?;
+            writeln!(f, "uuid: {}", agreement.uuid)?;
+            if let Some(address) = &agreement.partner_address {
+                writeln!(f, "partner_address: {}", address)?;
+            }
+            if let Some(last_sync) = agreement.last_sync {
+                writeln!(f, "last_sync: {}", last_sync)?;
+            } else {
+                writeln!(f, "last_sync: -")?;
+            }
+            if let Some(pending) = agreement.pending_changes {
+                writeln!(f, "pending_changes: {}", pending)?;
+            } else {
+                writeln!(f, "pending_changes: -")?;
+            }
+            if let Some(lag) = agreement.lag_secs {
+                writeln!(f, "lag_secs: {}", lag)?;
+            } else {
+                writeln!(f, "lag_secs: -")?;
+            }
+            if let Some(rate) = agreement.changes_per_sec {
+                writeln!(f, "changes_per_sec: {:.1}", rate)?;
+            }
+            if let (Some(err), Some(time)) = (&agreement.last_error, agreement.last_error_time) {
+                writeln!(f, "last_error: {} at {}", err, time)?;
+            }
+            for range in &agreement.partner_ruv {
+                writeln!(f, "partner_ruv: {}", range)?;
+            }
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "lowercase")]
 pub enum UatPurpose {
@@ -758,6 +1018,12 @@ pub enum Filter {
     Sub(String, String),
     #[serde(alias = "Pres")]
     Pres(String),
+    /// The attribute has a value that is greater than or equal to this.
+    #[serde(alias = "Gte")]
+    Gte(String, String),
+    /// The attribute has a value that is less than or equal to this.
+    #[serde(alias = "Lte")]
+    Lte(String, String),
     #[serde(alias = "Or")]
     Or(Vec<Filter>),
     #[serde(alias = "And")]
@@ -790,22 +1056,46 @@ impl ModifyList {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SearchRequest {
     pub filter: Filter,
+    /// The maximum number of entries to return in a page of results.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page_size: Option<usize>,
+    /// The continuation token of the page to return, from the previous page of results.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page_token: Option<String>,
 }
 
 impl SearchRequest {
     pub fn new(filter: Filter) -> Self {
-        SearchRequest { filter }
+        SearchRequest {
+            filter,
+            page_size: None,
+            page_token: None,
+        }
+    }
+
+    pub fn new_paged(filter: Filter, page_size: Option<usize>, page_token: Option<String>) -> Self {
+        SearchRequest {
+            filter,
+            page_size,
+            page_token,
+        }
     }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SearchResponse {
     pub entries: Vec<Entry>,
+    /// The continuation token of the next page of results, if there are more entries.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_page_token: Option<String>,
 }
 
 impl SearchResponse {
     pub fn new(entries: Vec<Entry>) -> Self {
-        SearchResponse { entries }
+        SearchResponse {
+            entries,
+            next_page_token: None,
+        }
     }
 }
 
@@ -844,6 +1134,81 @@ impl ModifyRequest {
     }
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BatchOperation {
+    Create(CreateRequest),
+    Modify(ModifyRequest),
+    Delete(DeleteRequest),
+}
+
+/// Operations that are applied in order in one transaction, so that either all or none of them
+/// are applied.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BatchRequest {
+    pub operations: Vec<BatchOperation>,
+}
+
+impl BatchRequest {
+    pub fn new(operations: Vec<BatchOperation>) -> Self {
+        BatchRequest { operations }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BatchOperationResult {
+    Success,
+    Failed(OperationError),
+    /// An earlier operation failed, so this one was not attempted.
+    NotAttempted,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BatchResponse {
+    /// If the operations were applied, which is only the case if every one succeeded.
+    pub committed: bool,
+    /// The result of each operation, in the order of the request.
+    pub results: Vec<BatchOperationResult>,
+}
+
+/// If an audited change succeeded.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditOutcome {
+    Success,
+    Failure,
+}
+
+/// A change that an account made, or attempted, as kept in the audit log.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct AuditRecord {
+    pub time: time::OffsetDateTime,
+    pub operation: EntryChange,
+    /// The uuid of the account that made the change.
+    pub identity: Uuid,
+    /// The entries that were changed, including those changed as a consequence, such as the
+    /// members of a group. This is empty if the change failed.
+    pub targets: Vec<Uuid>,
+    pub outcome: AuditOutcome,
+    /// Why the change failed.
+    pub error: Option<String>,
+}
+
+/// The records of the audit log to return. A record is returned if it matches every
+/// condition that is given.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct AuditSearchRequest {
+    pub operation: Option<EntryChange>,
+    pub identity: Option<Uuid>,
+    /// A uuid that must be one of the targets of the change.
+    pub target: Option<Uuid>,
+    pub outcome: Option<AuditOutcome>,
+    pub not_before: Option<time::OffsetDateTime>,
+    pub not_after: Option<time::OffsetDateTime>,
+}
+
 // Login is a multi-step process potentially. First the client says who they
 // want to request
 //
@@ -4,6 +4,10 @@ impl DomainOpt {
     pub fn debug(&self) -> bool {
         match self {
             DomainOpt::SetDomainDisplayName(copt) => copt.copt.debug,
+            DomainOpt::SetLdapBasedn(copt) => copt.copt.debug,
+            DomainOpt::SetLdapRdnAttr(copt) => copt.copt.debug,
+            DomainOpt::SetLdapAttrMap(copt) => copt.copt.debug,
+            DomainOpt::SetReadOnlyReplicas(copt) => copt.copt.debug,
             DomainOpt::Show(copt) | DomainOpt::ResetTokenKey(copt) => copt.debug,
         }
     }
@@ -24,6 +28,56 @@ impl DomainOpt {
                     Err(e) => eprintln!("{:?}", e),
                 }
             }
+            DomainOpt::SetLdapBasedn(opt) => {
+                eprintln!(
+                    "Attempting to set the domain's ldap basedn to: {:?}",
+                    opt.new_basedn
+                );
+                let client = opt.copt.to_client().await;
+                match client.idm_domain_set_ldap_basedn(&opt.new_basedn).await {
+                    Ok(_) => println!("Success"),
+                    Err(e) => eprintln!("{:?}", e),
+                }
+            }
+            DomainOpt::SetLdapRdnAttr(opt) => {
+                eprintln!(
+                    "Attempting to set the domain's ldap rdn attributes to: {:?}",
+                    opt.rdn_attrs
+                );
+                let rdn_attrs: Vec<_> = opt.rdn_attrs.iter().map(|s| s.as_str()).collect();
+                let client = opt.copt.to_client().await;
+                match client.idm_domain_set_ldap_rdn_attr(&rdn_attrs).await {
+                    Ok(_) => println!("Success"),
+                    Err(e) => eprintln!("{:?}", e),
+                }
+            }
+            DomainOpt::SetLdapAttrMap(opt) => {
+                eprintln!(
+                    "Attempting to set the domain's ldap attribute map to: {:?}",
+                    opt.attr_map
+                );
+                let attr_map: Vec<_> = opt.attr_map.iter().map(|s| s.as_str()).collect();
+                let client = opt.copt.to_client().await;
+                match client.idm_domain_set_ldap_attr_map(&attr_map).await {
+                    Ok(_) => println!("Success"),
+                    Err(e) => eprintln!("{:?}", e),
+                }
+            }
+            DomainOpt::SetReadOnlyReplicas(opt) => {
+                eprintln!(
+                    "Attempting to set the domain's read only replicas to: {:?}",
+                    opt.server_uuids
+                );
+                let server_uuids: Vec<_> = opt.server_uuids.iter().map(|s| s.as_str()).collect();
+                let client = opt.copt.to_client().await;
+                match client
+                    .idm_domain_set_read_only_replicas(&server_uuids)
+                    .await
+                {
+                    Ok(_) => println!("Success"),
+                    Err(e) => eprintln!("{:?}", e),
+                }
+            }
             DomainOpt::Show(copt) => {
                 let client = copt.to_client().await;
                 match client.idm_domain_get().await {
@@ -15,6 +15,7 @@ impl GroupOpt {
             GroupOpt::Posix { commands } => match commands {
                 GroupPosix::Show(gcopt) => gcopt.copt.debug,
                 GroupPosix::Set(gcopt) => gcopt.copt.debug,
+                GroupPosix::Remove(gcopt) => gcopt.copt.debug,
             },
         }
     }
@@ -132,6 +133,16 @@ impl GroupOpt {
                         ),
                     }
                 }
+                GroupPosix::Remove(gcopt) => {
+                    let client = gcopt.copt.to_client().await;
+                    match client.idm_group_unix_remove(gcopt.name.as_str()).await {
+                        Err(e) => error!("Error -> {:?}", e),
+                        Ok(_) => println!(
+                            "Success removing POSIX configuration for group {}",
+                            gcopt.name.as_str()
+                        ),
+                    }
+                }
             },
         } // end match
     }
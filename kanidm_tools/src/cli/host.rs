@@ -0,0 +1,73 @@
+use crate::HostOpt;
+
+impl HostOpt {
+    pub fn debug(&self) -> bool {
+        match self {
+            HostOpt::List(copt) => copt.debug,
+            HostOpt::Get(aopt)
+            | HostOpt::Delete(aopt)
+            | HostOpt::EnrolToken(aopt)
+            | HostOpt::Revoke(aopt) => aopt.copt.debug,
+            HostOpt::Create(aopt) => aopt.copt.debug,
+        }
+    }
+
+    pub async fn exec(&self) {
+        match self {
+            HostOpt::List(copt) => {
+                let client = copt.to_client().await;
+                match client.idm_host_list().await {
+                    Ok(r) => r.iter().for_each(|ent| println!("{}", ent)),
+                    Err(e) => error!("Error -> {:?}", e),
+                }
+            }
+            HostOpt::Get(aopt) => {
+                let client = aopt.copt.to_client().await;
+                match client.idm_host_get(aopt.aopts.account_id.as_str()).await {
+                    Ok(Some(e)) => println!("{}", e),
+                    Ok(None) => println!("No matching entries"),
+                    Err(e) => error!("Error -> {:?}", e),
+                }
+            }
+            HostOpt::Create(acopt) => {
+                let client = acopt.copt.to_client().await;
+                if let Err(e) = client
+                    .idm_host_create(acopt.aopts.account_id.as_str(), acopt.display_name.as_str())
+                    .await
+                {
+                    error!("Error -> {:?}", e)
+                }
+            }
+            HostOpt::Delete(aopt) => {
+                let client = aopt.copt.to_client().await;
+                if let Err(e) = client.idm_host_delete(aopt.aopts.account_id.as_str()).await {
+                    error!("Error -> {:?}", e)
+                }
+            }
+            HostOpt::EnrolToken(aopt) => {
+                let client = aopt.copt.to_client().await;
+                match client
+                    .idm_host_enrol_token_create(aopt.aopts.account_id.as_str())
+                    .await
+                {
+                    Ok(token) => {
+                        println!("Set host_enrol_token in the unixd configuration of the host.");
+                        println!("This token can only be used once.");
+                        println!("{}", token);
+                    }
+                    Err(e) => error!("Error -> {:?}", e),
+                }
+            }
+            HostOpt::Revoke(aopt) => {
+                let client = aopt.copt.to_client().await;
+                match client
+                    .idm_host_credential_revoke(aopt.aopts.account_id.as_str())
+                    .await
+                {
+                    Ok(()) => println!("Success"),
+                    Err(e) => error!("Error -> {:?}", e),
+                }
+            }
+        }
+    }
+}
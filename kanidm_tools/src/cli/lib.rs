@@ -24,10 +24,13 @@ pub mod badlist;
 pub mod common;
 pub mod domain;
 pub mod group;
+pub mod host;
 pub mod oauth2;
 pub mod person;
 pub mod raw;
 pub mod recycle;
+pub mod replication;
+pub mod schema;
 pub mod serviceaccount;
 pub mod session;
 pub mod synch;
@@ -37,6 +40,10 @@ impl SelfOpt {
     pub fn debug(&self) -> bool {
         match self {
             SelfOpt::Whoami(copt) => copt.debug,
+            SelfOpt::Consent { commands } => match commands {
+                ConsentOpt::List(copt) => copt.debug,
+                ConsentOpt::Revoke(nopt) => nopt.copt.debug,
+            },
         }
     }
 
@@ -60,6 +67,27 @@ impl SelfOpt {
                     Err(e) => println!("Error: {:?}", e),
                 }
             }
+            SelfOpt::Consent { commands } => match commands {
+                ConsentOpt::List(copt) => {
+                    let client = copt.to_client().await;
+                    match client.idm_account_oauth2_consent_list().await {
+                        Ok(consents) => consents.iter().for_each(|c| {
+                            println!("{} ({}): {}", c.display_name, c.name, c.scopes.join(" "))
+                        }),
+                        Err(e) => error!("Error -> {:?}", e),
+                    }
+                }
+                ConsentOpt::Revoke(nopt) => {
+                    let client = nopt.copt.to_client().await;
+                    match client
+                        .idm_account_oauth2_consent_revoke(nopt.name.as_str())
+                        .await
+                    {
+                        Ok(_) => println!("Success"),
+                        Err(e) => error!("Error -> {:?}", e),
+                    }
+                }
+            },
         }
     }
 }
@@ -70,6 +98,7 @@ impl SystemOpt {
             SystemOpt::PwBadlist { commands } => commands.debug(),
             SystemOpt::Oauth2 { commands } => commands.debug(),
             SystemOpt::Domain { commands } => commands.debug(),
+            SystemOpt::Replication { commands } => commands.debug(),
             SystemOpt::Synch { commands } => commands.debug(),
         }
     }
@@ -79,6 +108,7 @@ impl SystemOpt {
             SystemOpt::PwBadlist { commands } => commands.exec().await,
             SystemOpt::Oauth2 { commands } => commands.exec().await,
             SystemOpt::Domain { commands } => commands.exec().await,
+            SystemOpt::Replication { commands } => commands.exec().await,
             SystemOpt::Synch { commands } => commands.exec().await,
         }
     }
@@ -95,8 +125,10 @@ impl KanidmClientOpt {
             KanidmClientOpt::Group { commands } => commands.debug(),
             KanidmClientOpt::Person { commands } => commands.debug(),
             KanidmClientOpt::ServiceAccount { commands } => commands.debug(),
+            KanidmClientOpt::Host { commands } => commands.debug(),
             KanidmClientOpt::System { commands } => commands.debug(),
             KanidmClientOpt::Recycle { commands } => commands.debug(),
+            KanidmClientOpt::Schema { commands } => commands.debug(),
             KanidmClientOpt::Version {} => {
                 kanidm_proto::utils::show_version("kanidm");
                 true
@@ -113,9 +145,11 @@ impl KanidmClientOpt {
             KanidmClientOpt::CSelf { commands } => commands.exec().await,
             KanidmClientOpt::Person { commands } => commands.exec().await,
             KanidmClientOpt::ServiceAccount { commands } => commands.exec().await,
+            KanidmClientOpt::Host { commands } => commands.exec().await,
             KanidmClientOpt::Group { commands } => commands.exec().await,
             KanidmClientOpt::System { commands } => commands.exec().await,
             KanidmClientOpt::Recycle { commands } => commands.exec().await,
+            KanidmClientOpt::Schema { commands } => commands.exec().await,
             KanidmClientOpt::Version {} => (),
         }
     }
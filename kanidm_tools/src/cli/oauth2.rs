@@ -6,6 +6,7 @@ impl Oauth2Opt {
             Oauth2Opt::List(copt) => copt.debug,
             Oauth2Opt::Get(nopt) => nopt.copt.debug,
             Oauth2Opt::CreateBasic(cbopt) => cbopt.nopt.copt.debug,
+            Oauth2Opt::CreatePublic(cpopt) => cpopt.nopt.copt.debug,
             Oauth2Opt::UpdateScopeMap(cbopt) => cbopt.nopt.copt.debug,
             Oauth2Opt::DeleteScopeMap(cbopt) => cbopt.nopt.copt.debug,
             Oauth2Opt::UpdateSupScopeMap(cbopt) => cbopt.nopt.copt.debug,
@@ -20,8 +21,15 @@ impl Oauth2Opt {
             Oauth2Opt::DisablePkce(nopt) => nopt.copt.debug,
             Oauth2Opt::EnableLegacyCrypto(nopt) => nopt.copt.debug,
             Oauth2Opt::DisableLegacyCrypto(nopt) => nopt.copt.debug,
+            Oauth2Opt::SetJwtSignAlg { nopt, .. } => nopt.copt.debug,
             Oauth2Opt::PreferShortUsername(nopt) => nopt.copt.debug,
             Oauth2Opt::PreferSPNUsername(nopt) => nopt.copt.debug,
+            Oauth2Opt::SetAccessTokenExpiry { nopt, .. } => nopt.copt.debug,
+            Oauth2Opt::SetRefreshTokenExpiry { nopt, .. } => nopt.copt.debug,
+            Oauth2Opt::DisableRefreshTokens(nopt) => nopt.copt.debug,
+            Oauth2Opt::EnableSessionOutliveParent(nopt) => nopt.copt.debug,
+            Oauth2Opt::DisableSessionOutliveParent(nopt) => nopt.copt.debug,
+            Oauth2Opt::SetTokenExchangeAudience { nopt, .. } => nopt.copt.debug,
         }
     }
 
@@ -56,6 +64,20 @@ impl Oauth2Opt {
                     Err(e) => error!("Error -> {:?}", e),
                 }
             }
+            Oauth2Opt::CreatePublic(cpopt) => {
+                let client = cpopt.nopt.copt.to_client().await;
+                match client
+                    .idm_oauth2_rs_public_create(
+                        cpopt.nopt.name.as_str(),
+                        cpopt.displayname.as_str(),
+                        cpopt.redirect_uri.as_str(),
+                    )
+                    .await
+                {
+                    Ok(_) => println!("Success"),
+                    Err(e) => error!("Error -> {:?}", e),
+                }
+            }
             Oauth2Opt::UpdateScopeMap(cbopt) => {
                 let client = cbopt.nopt.copt.to_client().await;
                 match client
@@ -240,6 +262,16 @@ impl Oauth2Opt {
                     Err(e) => error!("Error -> {:?}", e),
                 }
             }
+            Oauth2Opt::SetJwtSignAlg { nopt, alg } => {
+                let client = nopt.copt.to_client().await;
+                match client
+                    .idm_oauth2_rs_set_jwt_sign_alg(nopt.name.as_str(), alg.as_str())
+                    .await
+                {
+                    Ok(_) => println!("Success"),
+                    Err(e) => error!("Error -> {:?}", e),
+                }
+            }
             Oauth2Opt::PreferShortUsername(nopt) => {
                 let client = nopt.copt.to_client().await;
                 match client
@@ -260,6 +292,69 @@ impl Oauth2Opt {
                     Err(e) => error!("Error -> {:?}", e),
                 }
             }
+            Oauth2Opt::SetAccessTokenExpiry { nopt, expiry } => {
+                let client = nopt.copt.to_client().await;
+                match client
+                    .idm_oauth2_rs_set_access_token_expiry(nopt.name.as_str(), *expiry)
+                    .await
+                {
+                    Ok(_) => println!("Success"),
+                    Err(e) => error!("Error -> {:?}", e),
+                }
+            }
+            Oauth2Opt::SetRefreshTokenExpiry { nopt, expiry } => {
+                let client = nopt.copt.to_client().await;
+                match client
+                    .idm_oauth2_rs_set_refresh_token_expiry(nopt.name.as_str(), Some(*expiry))
+                    .await
+                {
+                    Ok(_) => println!("Success"),
+                    Err(e) => error!("Error -> {:?}", e),
+                }
+            }
+            Oauth2Opt::DisableRefreshTokens(nopt) => {
+                let client = nopt.copt.to_client().await;
+                match client
+                    .idm_oauth2_rs_set_refresh_token_expiry(nopt.name.as_str(), None)
+                    .await
+                {
+                    Ok(_) => println!("Success"),
+                    Err(e) => error!("Error -> {:?}", e),
+                }
+            }
+            Oauth2Opt::EnableSessionOutliveParent(nopt) => {
+                let client = nopt.copt.to_client().await;
+                match client
+                    .idm_oauth2_rs_enable_session_outlive_parent(nopt.name.as_str())
+                    .await
+                {
+                    Ok(_) => println!("Success"),
+                    Err(e) => error!("Error -> {:?}", e),
+                }
+            }
+            Oauth2Opt::DisableSessionOutliveParent(nopt) => {
+                let client = nopt.copt.to_client().await;
+                match client
+                    .idm_oauth2_rs_disable_session_outlive_parent(nopt.name.as_str())
+                    .await
+                {
+                    Ok(_) => println!("Success"),
+                    Err(e) => error!("Error -> {:?}", e),
+                }
+            }
+            Oauth2Opt::SetTokenExchangeAudience { nopt, audience } => {
+                let client = nopt.copt.to_client().await;
+                match client
+                    .idm_oauth2_rs_set_token_exchange_audience(
+                        nopt.name.as_str(),
+                        audience.iter().map(|s| s.as_str()).collect(),
+                    )
+                    .await
+                {
+                    Ok(_) => println!("Success"),
+                    Err(e) => error!("Error -> {:?}", e),
+                }
+            }
         }
     }
 }
@@ -33,10 +33,13 @@ impl PersonOpt {
                 PersonPosix::Show(apo) => apo.copt.debug,
                 PersonPosix::Set(apo) => apo.copt.debug,
                 PersonPosix::SetPassword(apo) => apo.copt.debug,
+                PersonPosix::Remove(apo) => apo.copt.debug,
             },
             PersonOpt::Session { commands } => match commands {
                 AccountUserAuthToken::Status(apo) => apo.copt.debug,
                 AccountUserAuthToken::Destroy { copt, .. } => copt.debug,
+                AccountUserAuthToken::Oauth2Status(apo) => apo.copt.debug,
+                AccountUserAuthToken::Oauth2Destroy { copt, .. } => copt.debug,
             },
             PersonOpt::Ssh { commands } => match commands {
                 AccountSsh::List(ano) => ano.copt.debug,
@@ -168,6 +171,15 @@ impl PersonOpt {
                         error!("Error -> {:?}", e);
                     }
                 }
+                PersonPosix::Remove(aopt) => {
+                    let client = aopt.copt.to_client().await;
+                    if let Err(e) = client
+                        .idm_person_account_unix_remove(aopt.aopts.account_id.as_str())
+                        .await
+                    {
+                        error!("Error -> {:?}", e);
+                    }
+                }
             }, // end PersonOpt::Posix
             PersonOpt::Session { commands } => match commands {
                 AccountUserAuthToken::Status(apo) => {
@@ -208,6 +220,44 @@ impl PersonOpt {
                         }
                     }
                 }
+                AccountUserAuthToken::Oauth2Status(apo) => {
+                    let client = apo.copt.to_client().await;
+                    match client
+                        .idm_account_list_oauth2_session(apo.aopts.account_id.as_str())
+                        .await
+                    {
+                        Ok(sessions) => {
+                            if sessions.is_empty() {
+                                println!("No oauth2 sessions exist");
+                            } else {
+                                for session in sessions {
+                                    println!("session: {}", session);
+                                }
+                            }
+                        }
+                        Err(e) => {
+                            error!("Error listing oauth2 sessions -> {:?}", e);
+                        }
+                    }
+                }
+                AccountUserAuthToken::Oauth2Destroy {
+                    aopts,
+                    copt,
+                    session_id,
+                } => {
+                    let client = copt.to_client().await;
+                    match client
+                        .idm_account_destroy_oauth2_session(aopts.account_id.as_str(), *session_id)
+                        .await
+                    {
+                        Ok(()) => {
+                            println!("Success");
+                        }
+                        Err(e) => {
+                            error!("Error destroying oauth2 session -> {:?}", e);
+                        }
+                    }
+                }
             }, // End PersonOpt::Session
             PersonOpt::Ssh { commands } => match commands {
                 AccountSsh::List(aopt) => {
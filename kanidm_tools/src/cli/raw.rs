@@ -23,6 +23,7 @@ impl RawOpt {
             RawOpt::Create(copt) => copt.commonopts.debug,
             RawOpt::Modify(mopt) => mopt.commonopts.debug,
             RawOpt::Delete(dopt) => dopt.commonopts.debug,
+            RawOpt::Changes(nopt) => nopt.copt.debug,
         }
     }
 
@@ -99,6 +100,14 @@ impl RawOpt {
                     error!("Error -> {:?}", e);
                 }
             }
+            RawOpt::Changes(nopt) => {
+                let client = nopt.copt.to_client().await;
+                match client.raw_changes_get(nopt.name.as_str()).await {
+                    Ok(Some(changes)) => changes.iter().for_each(|c| println!("{}", c)),
+                    Ok(None) => println!("No matching entries"),
+                    Err(e) => error!("Error -> {:?}", e),
+                }
+            }
         }
     }
 }
@@ -0,0 +1,68 @@
+use kanidm_proto::v1::ReplConflictResolution;
+
+use crate::ReplicationOpt;
+
+impl ReplicationOpt {
+    pub fn debug(&self) -> bool {
+        match self {
+            ReplicationOpt::Status(copt) | ReplicationOpt::ListConflicts(copt) => copt.debug,
+            ReplicationOpt::KeepConflict(opt) | ReplicationOpt::DiscardConflict(opt) => {
+                opt.copt.debug
+            }
+            ReplicationOpt::CreateJoinToken(nopt) => nopt.copt.debug,
+        }
+    }
+
+    pub async fn exec(&self) {
+        match self {
+            ReplicationOpt::Status(copt) => {
+                let client = copt.to_client().await;
+                match client.system_replication_status().await {
+                    Ok(status) => print!("{}", status),
+                    Err(e) => error!("Error -> {:?}", e),
+                }
+            }
+            ReplicationOpt::ListConflicts(copt) => {
+                let client = copt.to_client().await;
+                match client.system_replication_conflict_list().await {
+                    Ok(r) => r.iter().for_each(|e| println!("{}", e)),
+                    Err(e) => error!("Error -> {:?}", e),
+                }
+            }
+            ReplicationOpt::KeepConflict(opt) => {
+                let client = opt.copt.to_client().await;
+                match client
+                    .system_replication_conflict_resolve(&opt.uuid, ReplConflictResolution::Keep)
+                    .await
+                {
+                    Ok(_) => println!("Success"),
+                    Err(e) => error!("Error -> {:?}", e),
+                }
+            }
+            ReplicationOpt::DiscardConflict(opt) => {
+                let client = opt.copt.to_client().await;
+                match client
+                    .system_replication_conflict_resolve(&opt.uuid, ReplConflictResolution::Discard)
+                    .await
+                {
+                    Ok(_) => println!("Success"),
+                    Err(e) => error!("Error -> {:?}", e),
+                }
+            }
+            ReplicationOpt::CreateJoinToken(nopt) => {
+                let client = nopt.copt.to_client().await;
+                match client
+                    .system_replication_join_token_create(nopt.name.as_str())
+                    .await
+                {
+                    Ok(token) => {
+                        println!("Set repl_join_token in the configuration of the new server.");
+                        println!("This token can only be used once.");
+                        println!("{}", token);
+                    }
+                    Err(e) => error!("Error -> {:?}", e),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+
+use kanidm_proto::v1::Entry;
+
+use crate::{CommonOpt, SchemaDiffOpt, SchemaOpt};
+
+/// Attributes of schema entries that change with each write, rather than with the schema.
+const SCHEMA_DIFF_IGNORE: [&str; 1] = ["last_modified_cid"];
+
+/// The kind and name of a schema entry, such as ("attribute", "mail").
+fn schema_key(entry: &Entry) -> Option<(&'static str, String)> {
+    let name = |attr: &str| entry.attrs.get(attr).and_then(|v| v.first()).cloned();
+    name("attributename")
+        .map(|n| ("attribute", n))
+        .or_else(|| name("classname").map(|n| ("class", n)))
+}
+
+fn schema_by_key(entries: Vec<Entry>) -> BTreeMap<(&'static str, String), Entry> {
+    entries
+        .into_iter()
+        .filter_map(|e| schema_key(&e).map(|k| (k, e)))
+        .collect()
+}
+
+fn sorted_values(entry: &Entry, attr: &str) -> Vec<String> {
+    let mut values = entry.attrs.get(attr).cloned().unwrap_or_default();
+    values.sort_unstable();
+    values
+}
+
+/// Print the differences of the schema `theirs` from `ours`, and return how many there are.
+fn print_schema_diff(ours: Vec<Entry>, theirs: Vec<Entry>, their_name: &str) -> usize {
+    let ours = schema_by_key(ours);
+    let theirs = schema_by_key(theirs);
+    let mut differences = 0;
+
+    for ((kind, name), entry) in ours.iter() {
+        let their_entry = match theirs.get(&(*kind, name.clone())) {
+            Some(e) => e,
+            None => {
+                println!("- {} {}: only on this instance", kind, name);
+                differences += 1;
+                continue;
+            }
+        };
+
+        let attrs: BTreeSet<&String> = entry
+            .attrs
+            .keys()
+            .chain(their_entry.attrs.keys())
+            .filter(|a| !SCHEMA_DIFF_IGNORE.contains(&a.as_str()))
+            .collect();
+        for attr in attrs {
+            let (our_values, their_values) =
+                (sorted_values(entry, attr), sorted_values(their_entry, attr));
+            if our_values != their_values {
+                println!(
+                    "~ {} {}: {} {:?} -> {:?} in {}",
+                    kind, name, attr, our_values, their_values, their_name
+                );
+                differences += 1;
+            }
+        }
+    }
+
+    for (kind, name) in theirs.keys().filter(|k| !ours.contains_key(k)) {
+        println!("+ {} {}: only in {}", kind, name, their_name);
+        differences += 1;
+    }
+
+    differences
+}
+
+impl SchemaOpt {
+    pub fn debug(&self) -> bool {
+        match self {
+            SchemaOpt::Dump { copt, .. } => copt.debug,
+            SchemaOpt::Diff(dopt) => dopt.copt.debug,
+        }
+    }
+
+    pub async fn exec(&self) {
+        match self {
+            SchemaOpt::Dump { copt, path } => {
+                let client = copt.to_client().await;
+                let schema = match client.idm_schema_list().await {
+                    Ok(s) => s,
+                    Err(e) => {
+                        error!("Error -> {:?}", e);
+                        return;
+                    }
+                };
+
+                let res = File::create(path).map_err(|e| e.to_string()).and_then(|f| {
+                    serde_json::to_writer_pretty(BufWriter::new(f), &schema)
+                        .map_err(|e| e.to_string())
+                });
+                match res {
+                    Ok(()) => println!(
+                        "Wrote {} schema entries to {}",
+                        schema.len(),
+                        path.display()
+                    ),
+                    Err(e) => error!("Unable to write {} -> {}", path.display(), e),
+                }
+            }
+            SchemaOpt::Diff(SchemaDiffOpt {
+                copt,
+                file,
+                other_url,
+            }) => {
+                let (theirs, their_name) = match (file, other_url) {
+                    (Some(path), None) => {
+                        let res = File::open(path).map_err(|e| e.to_string()).and_then(|f| {
+                            serde_json::from_reader::<_, Vec<Entry>>(BufReader::new(f))
+                                .map_err(|e| e.to_string())
+                        });
+                        match res {
+                            Ok(s) => (s, path.display().to_string()),
+                            Err(e) => {
+                                error!("Unable to read {} -> {}", path.display(), e);
+                                return;
+                            }
+                        }
+                    }
+                    (None, Some(url)) => {
+                        let other_opt = CommonOpt {
+                            debug: copt.debug,
+                            addr: Some(url.clone()),
+                            username: None,
+                            ca_path: copt.ca_path.clone(),
+                        };
+                        let other = other_opt.to_unauth_client();
+                        if let Err(e) = other.auth_anonymous().await {
+                            error!("Unable to authenticate to {} -> {:?}", url, e);
+                            return;
+                        }
+                        match other.idm_schema_list().await {
+                            Ok(s) => (s, url.clone()),
+                            Err(e) => {
+                                error!("Error -> {:?}", e);
+                                return;
+                            }
+                        }
+                    }
+                    _ => {
+                        error!("One of --file or --other-url is required");
+                        return;
+                    }
+                };
+
+                let client = copt.to_client().await;
+                let ours = match client.idm_schema_list().await {
+                    Ok(s) => s,
+                    Err(e) => {
+                        error!("Error -> {:?}", e);
+                        return;
+                    }
+                };
+
+                match print_schema_diff(ours, theirs, &their_name) {
+                    0 => println!("The schema is the same as {}", their_name),
+                    n => println!("{} differences from {}", n, their_name),
+                }
+            }
+        }
+    }
+}
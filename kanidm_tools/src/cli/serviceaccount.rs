@@ -21,10 +21,13 @@ impl ServiceAccountOpt {
             ServiceAccountOpt::Posix { commands } => match commands {
                 ServiceAccountPosix::Show(apo) => apo.copt.debug,
                 ServiceAccountPosix::Set(apo) => apo.copt.debug,
+                ServiceAccountPosix::Remove(apo) => apo.copt.debug,
             },
             ServiceAccountOpt::Session { commands } => match commands {
                 AccountUserAuthToken::Status(apo) => apo.copt.debug,
                 AccountUserAuthToken::Destroy { copt, .. } => copt.debug,
+                AccountUserAuthToken::Oauth2Status(apo) => apo.copt.debug,
+                AccountUserAuthToken::Oauth2Destroy { copt, .. } => copt.debug,
             },
             ServiceAccountOpt::Ssh { commands } => match commands {
                 AccountSsh::List(ano) => ano.copt.debug,
@@ -191,6 +194,15 @@ impl ServiceAccountOpt {
                         error!("Error -> {:?}", e);
                     }
                 }
+                ServiceAccountPosix::Remove(aopt) => {
+                    let client = aopt.copt.to_client().await;
+                    if let Err(e) = client
+                        .idm_service_account_unix_remove(aopt.aopts.account_id.as_str())
+                        .await
+                    {
+                        error!("Error -> {:?}", e);
+                    }
+                }
             }, // end ServiceAccountOpt::Posix
             ServiceAccountOpt::Session { commands } => match commands {
                 AccountUserAuthToken::Status(apo) => {
@@ -231,6 +243,44 @@ impl ServiceAccountOpt {
                         }
                     }
                 }
+                AccountUserAuthToken::Oauth2Status(apo) => {
+                    let client = apo.copt.to_client().await;
+                    match client
+                        .idm_account_list_oauth2_session(apo.aopts.account_id.as_str())
+                        .await
+                    {
+                        Ok(sessions) => {
+                            if sessions.is_empty() {
+                                println!("No oauth2 sessions exist");
+                            } else {
+                                for session in sessions {
+                                    println!("session: {}", session);
+                                }
+                            }
+                        }
+                        Err(e) => {
+                            error!("Error listing oauth2 sessions -> {:?}", e);
+                        }
+                    }
+                }
+                AccountUserAuthToken::Oauth2Destroy {
+                    aopts,
+                    copt,
+                    session_id,
+                } => {
+                    let client = copt.to_client().await;
+                    match client
+                        .idm_account_destroy_oauth2_session(aopts.account_id.as_str(), *session_id)
+                        .await
+                    {
+                        Ok(()) => {
+                            println!("Success");
+                        }
+                        Err(e) => {
+                            error!("Error destroying oauth2 session -> {:?}", e);
+                        }
+                    }
+                }
             }, // End ServiceAccountOpt::Session
             ServiceAccountOpt::Ssh { commands } => match commands {
                 AccountSsh::List(aopt) => {
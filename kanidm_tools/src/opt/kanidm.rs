@@ -55,6 +55,9 @@ pub enum GroupPosix {
     /// Setup posix group properties, or alter them
     #[clap(name = "set")]
     Set(GroupPosixOpt),
+    /// Remove the posix extension from a group, which removes its gidnumber
+    #[clap(name = "remove")]
+    Remove(Named),
 }
 
 #[derive(Debug, Subcommand)]
@@ -225,6 +228,9 @@ pub enum PersonPosix {
     Set(AccountPosixOpt),
     #[clap(name = "set_password")]
     SetPassword(AccountNamedOpt),
+    /// Remove the posix extension, which removes the gidnumber, shell and posix password
+    #[clap(name = "remove")]
+    Remove(AccountNamedOpt),
 }
 
 #[derive(Debug, Subcommand)]
@@ -233,6 +239,9 @@ pub enum ServiceAccountPosix {
     Show(AccountNamedOpt),
     #[clap(name = "set")]
     Set(AccountPosixOpt),
+    /// Remove the posix extension, which removes the gidnumber and shell
+    #[clap(name = "remove")]
+    Remove(AccountNamedOpt),
 }
 
 #[derive(Debug, Args)]
@@ -295,6 +304,21 @@ pub enum AccountUserAuthToken {
         #[clap(name = "session_id")]
         session_id: Uuid,
     },
+    /// Show the status of oauth2 sessions associated to this account, and the
+    /// resource servers they were issued to.
+    #[clap(name = "oauth2-status")]
+    Oauth2Status(AccountNamedOpt),
+    /// Destroy / revoke an oauth2 session for this account.
+    #[clap(name = "oauth2-destroy")]
+    Oauth2Destroy {
+        #[clap(flatten)]
+        aopts: AccountCommonOpt,
+        #[clap(flatten)]
+        copt: CommonOpt,
+        /// The UUID of the oauth2 session to destroy.
+        #[clap(name = "session_id")]
+        session_id: Uuid,
+    },
 }
 
 #[derive(Debug, Subcommand)]
@@ -366,7 +390,7 @@ pub enum ServiceAccountCredential {
 #[derive(Debug, Subcommand)]
 pub enum ServiceAccountApiToken {
     /// Show the status of api tokens associated to this service account.
-    #[clap(name = "status")]
+    #[clap(name = "status", visible_aliases=&["list"])]
     Status(AccountNamedOpt),
     /// Generate a new api token for this service account.
     #[clap(name = "generate")]
@@ -383,6 +407,7 @@ pub enum ServiceAccountApiToken {
         /// An optional rfc3339 time of the format "YYYY-MM-DDTHH:MM:SS+TZ", "2020-09-25T11:22:02+10:00".
         /// After this time the api token will no longer be valid.
         expiry: Option<String>,
+        /// Allow the token to perform writes. Without this, the token is read only.
         #[clap(long = "rw")]
         read_write: bool,
     },
@@ -482,6 +507,30 @@ pub enum ServiceAccountOpt {
     IntoPerson(AccountNamedOpt),
 }
 
+#[derive(Debug, Subcommand)]
+pub enum HostOpt {
+    /// List all hosts
+    #[clap(name = "list")]
+    List(CommonOpt),
+    /// View a specific host
+    #[clap(name = "get")]
+    Get(AccountNamedOpt),
+    /// Create a new host. It authenticates once it is enrolled with an enrolment token.
+    #[clap(name = "create")]
+    Create(AccountCreateOpt),
+    /// Delete a host
+    #[clap(name = "delete")]
+    Delete(AccountNamedOpt),
+    /// Create a one time token that enrols the host. Enrolling the host again revokes the
+    /// credential it held before.
+    #[clap(name = "enrol-token")]
+    EnrolToken(AccountNamedOpt),
+    /// Revoke the credential of a host, which can then only authenticate once it is enrolled
+    /// again
+    #[clap(name = "revoke")]
+    Revoke(AccountNamedOpt),
+}
+
 #[derive(Debug, Subcommand)]
 pub enum RecycleOpt {
     #[clap(name = "list")]
@@ -558,12 +607,32 @@ pub enum RawOpt {
     Modify(ModifyOpt),
     #[clap(name = "delete")]
     Delete(FilterOpt),
+    /// Show the change that last modified each attribute of an entry
+    #[clap(name = "changes")]
+    Changes(Named),
 }
 
 #[derive(Debug, Subcommand)]
 pub enum SelfOpt {
     /// Show the current authenticated user's identity
     Whoami(CommonOpt),
+    #[clap(name = "consent")]
+    /// Manage the oauth2 applications you have consented to
+    Consent {
+        #[clap(subcommand)]
+        commands: ConsentOpt,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConsentOpt {
+    #[clap(name = "list")]
+    /// List the oauth2 applications you have consented to, and the scopes they were granted
+    List(CommonOpt),
+    #[clap(name = "revoke")]
+    /// Revoke your consent to an oauth2 application. This also ends your sessions
+    /// with that application.
+    Revoke(Named),
 }
 
 #[derive(Debug, Args)]
@@ -576,6 +645,18 @@ pub struct Oauth2BasicCreateOpt {
     origin: String,
 }
 
+#[derive(Debug, Args)]
+pub struct Oauth2PublicCreateOpt {
+    #[clap(flatten)]
+    nopt: Named,
+    #[clap(name = "displayname")]
+    displayname: String,
+    /// The exact redirect uri of the client. This should be a loopback address
+    /// (the port is ignored) or a private-use uri scheme.
+    #[clap(name = "redirect_uri")]
+    redirect_uri: String,
+}
+
 #[derive(Debug, Args)]
 pub struct Oauth2SetDisplayname {
     #[clap(flatten)]
@@ -624,6 +705,10 @@ pub enum Oauth2Opt {
     #[clap(name = "create")]
     /// Create a new oauth2 resource server
     CreateBasic(Oauth2BasicCreateOpt),
+    #[clap(name = "create-public")]
+    /// Create a new public oauth2 client for native or browser applications. These
+    /// clients have no secret and must use PKCE.
+    CreatePublic(Oauth2PublicCreateOpt),
     #[clap(name = "update-scope-map", visible_aliases=&["create-scope-map"])]
     /// Update or add a new mapping from a group to scopes that it provides to members
     UpdateScopeMap(Oauth2CreateScopeMapOpt),
@@ -684,12 +769,58 @@ pub enum Oauth2Opt {
     /// Disable legacy signing crypto on this oauth2 resource server. This is the default.
     #[clap(name = "disable-legacy-crypto")]
     DisableLegacyCrypto(Named),
+    /// Set the algorithm used to sign id tokens for this oauth2 resource server. es256 is the
+    /// default. rs256 is for clients that do not support es256. hs256 signs with the client
+    /// secret and is only available to basic clients.
+    #[clap(name = "set-jwt-sign-alg")]
+    SetJwtSignAlg {
+        #[clap(flatten)]
+        nopt: Named,
+        #[clap(name = "alg", possible_values = &["es256", "rs256", "hs256"])]
+        alg: String,
+    },
     #[clap(name = "prefer-short-username")]
     /// Use the 'name' attribute instead of 'spn' for the preferred_username
     PreferShortUsername(Named),
     #[clap(name = "prefer-spn-username")]
     /// Use the 'spn' attribute instead of 'name' for the preferred_username
     PreferSPNUsername(Named),
+    /// Set how many seconds access tokens issued to this resource server are valid for.
+    #[clap(name = "set-access-token-expiry")]
+    SetAccessTokenExpiry {
+        #[clap(flatten)]
+        nopt: Named,
+        #[clap(name = "seconds")]
+        expiry: u32,
+    },
+    /// Set how many seconds this resource server may use refresh tokens for. Setting this
+    /// enables the refresh token grant for this resource server.
+    #[clap(name = "set-refresh-token-expiry")]
+    SetRefreshTokenExpiry {
+        #[clap(flatten)]
+        nopt: Named,
+        #[clap(name = "seconds")]
+        expiry: u32,
+    },
+    #[clap(name = "disable-refresh-tokens")]
+    /// Stop issuing refresh tokens to this resource server. This is the default.
+    DisableRefreshTokens(Named),
+    #[clap(name = "enable-session-outlive-parent")]
+    /// Allow sessions of this resource server to remain valid after the user logs out
+    /// of Kanidm, until they expire.
+    EnableSessionOutliveParent(Named),
+    #[clap(name = "disable-session-outlive-parent")]
+    /// End sessions of this resource server when the user logs out of Kanidm. This is the default.
+    DisableSessionOutliveParent(Named),
+    /// Set the resource servers that this resource server may exchange user access tokens for.
+    /// Provide no audiences to disable token exchange.
+    #[clap(name = "set-token-exchange-audience")]
+    SetTokenExchangeAudience {
+        #[clap(flatten)]
+        nopt: Named,
+        #[clap(name = "audience")]
+        audience: Vec<String>,
+    },
 }
 
 #[derive(Args, Debug)]
@@ -700,6 +831,38 @@ pub struct OptSetDomainDisplayName {
     new_display_name: String,
 }
 
+#[derive(Args, Debug)]
+pub struct OptSetDomainLdapBasedn {
+    #[clap(flatten)]
+    copt: CommonOpt,
+    #[clap(name = "new_basedn")]
+    new_basedn: String,
+}
+
+#[derive(Args, Debug)]
+pub struct OptSetDomainLdapRdnAttr {
+    #[clap(flatten)]
+    copt: CommonOpt,
+    #[clap(name = "rdn_attrs", required = true)]
+    rdn_attrs: Vec<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct OptSetDomainLdapAttrMap {
+    #[clap(flatten)]
+    copt: CommonOpt,
+    #[clap(name = "attr_map", required = true)]
+    attr_map: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct OptSetDomainReadOnlyReplicas {
+    #[clap(flatten)]
+    copt: CommonOpt,
+    #[clap(name = "server_uuids", required = true)]
+    server_uuids: Vec<String>,
+}
+
 
 #[derive(Debug, Subcommand)]
 pub enum PwBadlistOpt {
@@ -732,6 +895,23 @@ pub enum DomainOpt {
     #[clap[name = "set_domain_display_name"]]
     /// Set the domain display name
     SetDomainDisplayName(OptSetDomainDisplayName),
+    #[clap[name = "set_ldap_basedn"]]
+    /// Set the base DN that the LDAP interface presents entries under. This takes
+    /// effect when the server is restarted.
+    SetLdapBasedn(OptSetDomainLdapBasedn),
+    #[clap[name = "set_ldap_rdn_attr"]]
+    /// Set the attribute used to form the RDN of entries in the LDAP interface, one of
+    /// spn, name, uid, cn or uuid. This can be overridden per class as `class:attr`, for
+    /// example `group:cn`. This takes effect when the server is restarted.
+    SetLdapRdnAttr(OptSetDomainLdapRdnAttr),
+    #[clap[name = "set_ldap_attr_map"]]
+    /// Set aliases of LDAP attribute names to Kanidm attributes as `ldapname:attr`, for
+    /// example `mailprimary:mail`. This takes effect when the server is restarted.
+    SetLdapAttrMap(OptSetDomainLdapAttrMap),
+    #[clap[name = "set_read_only_replicas"]]
+    /// Set the server uuids of the replicas that only accept changes from their replication
+    /// suppliers. Client writes to these servers are rejected.
+    SetReadOnlyReplicas(OptSetDomainReadOnlyReplicas),
     #[clap(name = "show")]
     /// Show information about this system's domain
     Show(CommonOpt),
@@ -741,6 +921,37 @@ pub enum DomainOpt {
     ResetTokenKey(CommonOpt),
 }
 
+#[derive(Debug, Subcommand)]
+pub enum ReplicationOpt {
+    #[clap(name = "status")]
+    /// Show the replication agreements of this system, the changes held by each partner
+    /// and the number of changes waiting to be sent to them.
+    Status(CommonOpt),
+    #[clap(name = "list-conflicts")]
+    /// List the entries that conflicted with another entry during replication. These are
+    /// held in the recycle bin until they are resolved.
+    ListConflicts(CommonOpt),
+    #[clap(name = "keep-conflict")]
+    /// Return a conflict entry to life. The entry it conflicts with must first be changed
+    /// so that they no longer share unique values, such as a name.
+    KeepConflict(OptReplConflict),
+    #[clap(name = "discard-conflict")]
+    /// Permanently delete a conflict entry
+    DiscardConflict(OptReplConflict),
+    #[clap(name = "create-join-token")]
+    /// Create a one time token that a new server uses to join this system as a replica. The
+    /// name is given to the replication agreement with the new server.
+    CreateJoinToken(Named),
+}
+
+#[derive(Debug, Args)]
+pub struct OptReplConflict {
+    #[clap(flatten)]
+    copt: CommonOpt,
+    #[clap(name = "uuid")]
+    uuid: String,
+}
+
 #[derive(Debug, Subcommand)]
 pub enum SynchOpt {
     #[clap(name = "list")]
@@ -807,6 +1018,12 @@ pub enum SystemOpt {
         #[clap(subcommand)]
         commands: DomainOpt,
     },
+    #[clap(name = "replication")]
+    /// Display the state of replication with this system's partners
+    Replication {
+        #[clap(subcommand)]
+        commands: ReplicationOpt,
+    },
     #[clap(name = "sync", hide = true)]
     Synch {
         #[clap(subcommand)]
@@ -814,6 +1031,34 @@ pub enum SystemOpt {
     }
 }
 
+#[derive(Debug, Args)]
+pub struct SchemaDiffOpt {
+    #[clap(flatten)]
+    pub copt: CommonOpt,
+    /// A schema written by `schema dump` to compare to.
+    #[clap(parse(from_os_str), short, long)]
+    pub file: Option<PathBuf>,
+    /// The URL of another kanidm instance to compare to. Its schema is read anonymously.
+    #[clap(long = "other-url")]
+    pub other_url: Option<String>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum SchemaOpt {
+    #[clap(name = "dump")]
+    /// Write the schema of this instance to a file, to compare other instances to.
+    Dump {
+        #[clap(flatten)]
+        copt: CommonOpt,
+        #[clap(parse(from_os_str))]
+        path: PathBuf,
+    },
+    #[clap(name = "diff")]
+    /// Compare the schema of this instance to a schema file or another instance, and show the
+    /// attributes and classes that were added, removed or changed, such as by their indexes.
+    Diff(SchemaDiffOpt),
+}
+
 #[derive(Debug, Subcommand)]
 #[clap(about = "Kanidm Client Utility")]
 pub enum KanidmClientOpt {
@@ -848,6 +1093,11 @@ pub enum KanidmClientOpt {
         #[clap(subcommand)]
         commands: ServiceAccountOpt,
     },
+    /// Actions to manage and enrol hosts
+    Host {
+        #[clap(subcommand)]
+        commands: HostOpt,
+    },
     /// System configuration operations
     System {
         #[clap(subcommand)]
@@ -859,6 +1109,11 @@ pub enum KanidmClientOpt {
         #[clap(subcommand)]
         commands: RecycleOpt,
     },
+    /// Inspect and compare the schema of this instance
+    Schema {
+        #[clap(subcommand)]
+        commands: SchemaOpt,
+    },
     /// Unsafe - low level, raw database queries and operations.
     #[clap(hide = true)]
     Raw {
@@ -33,6 +33,7 @@ struct Options {
     debug: bool,
     use_first_pass: bool,
     ignore_unknown_user: bool,
+    sudo: bool,
 }
 
 impl TryFrom<&Vec<&CStr>> for Options {
@@ -52,6 +53,7 @@ impl TryFrom<&Vec<&CStr>> for Options {
             debug: gopts.contains("debug"),
             use_first_pass: gopts.contains("use_first_pass"),
             ignore_unknown_user: gopts.contains("ignore_unknown_user"),
+            sudo: gopts.contains("sudo"),
         })
     }
 }
@@ -92,7 +94,12 @@ impl PamHooks for PamKanidm {
             Ok(cfg) => cfg,
             Err(e) => return e,
         };
-        let req = ClientRequest::PamAccountAllowed(account_id);
+        // With sudo, the account must be in the sudo groups rather than the login groups.
+        let req = if opts.sudo {
+            ClientRequest::SudoAccountAllowed(account_id)
+        } else {
+            ClientRequest::PamAccountAllowed(account_id)
+        };
         // PamResultCode::PAM_IGNORE
 
         match call_daemon_blocking(cfg.sock_path.as_str(), &req, cfg.unix_sock_timeout) {
@@ -6,12 +6,13 @@ use std::string::ToString;
 use std::time::{Duration, SystemTime};
 
 use kanidm_client::{ClientError, KanidmClient};
-use kanidm_proto::v1::{OperationError, UnixGroupToken, UnixUserToken};
+use kanidm_proto::v1::{EntryChange, Filter, OperationError, UnixGroupToken, UnixUserToken};
 use lru::LruCache;
 use reqwest::StatusCode;
 use tokio::sync::{Mutex, RwLock};
 
 use crate::db::Db;
+use crate::seal::CacheSeal;
 use crate::unix_config::{HomeAttr, UidAttr};
 use crate::unix_proto::{HomeDirectoryInfo, NssGroup, NssUser};
 
@@ -34,9 +35,12 @@ enum CacheState {
 pub struct CacheLayer {
     db: Db,
     client: RwLock<KanidmClient>,
+    host_credential: Option<String>,
     state: Mutex<CacheState>,
     pam_allow_groups: BTreeSet<String>,
+    sudo_allow_groups: BTreeSet<String>,
     timeout_seconds: u64,
+    offline_cred_timeout: Option<u64>,
     default_shell: String,
     home_prefix: String,
     home_attr: HomeAttr,
@@ -61,11 +65,18 @@ impl CacheLayer {
     pub async fn new(
         // need db path
         path: &str,
+        // seal of the cached passwords
+        seal: CacheSeal,
         // cache timeout
         timeout_seconds: u64,
+        // how long a cached password can be used offline
+        offline_cred_timeout: Option<u64>,
         //
         client: KanidmClient,
+        // the credential of this host, if it is enrolled
+        host_credential: Option<String>,
         pam_allow_groups: Vec<String>,
+        sudo_allow_groups: Vec<String>,
         default_shell: String,
         home_prefix: String,
         home_attr: HomeAttr,
@@ -73,7 +84,7 @@ impl CacheLayer {
         uid_attr_map: UidAttr,
         gid_attr_map: UidAttr,
     ) -> Result<Self, ()> {
-        let db = Db::new(path)?;
+        let db = Db::new(path, seal)?;
 
         // setup and do a migrate.
         {
@@ -91,9 +102,12 @@ impl CacheLayer {
         Ok(CacheLayer {
             db,
             client: RwLock::new(client),
+            host_credential,
             state: Mutex::new(CacheState::OfflineNextCheck(SystemTime::now())),
             timeout_seconds,
+            offline_cred_timeout,
             pam_allow_groups: pam_allow_groups.into_iter().collect(),
+            sudo_allow_groups: sudo_allow_groups.into_iter().collect(),
             default_shell,
             home_prefix,
             home_attr,
@@ -138,6 +152,71 @@ impl CacheLayer {
         dbtxn.invalidate().and_then(|_| dbtxn.commit())
     }
 
+    /// Follow the changes of the posix accounts and groups on the server, and invalidate each
+    /// as it changes. Changes that can't be followed, from before the subscription or after it
+    /// lagged, invalidate the whole cache instead. This returns when the subscription ends.
+    pub async fn watch_changes(&self) -> Result<(), ()> {
+        if !self.test_connection().await {
+            return Err(());
+        }
+
+        // The subscription holds its request open, so it has a session of its own.
+        let client = self.client.read().await.new_session().map_err(|e| {
+            error!("client session error -> {:?}", e);
+        })?;
+        self.authenticate(&client).await.map_err(|e| {
+            debug!("change subscription authentication error -> {:?}", e);
+        })?;
+
+        let filter = Filter::Or(vec![
+            Filter::Eq("class".to_string(), "posixaccount".to_string()),
+            Filter::Eq("class".to_string(), "posixgroup".to_string()),
+        ]);
+        let mut changes = client.changes_subscribe(&filter).await.map_err(|e| {
+            debug!("change subscription error -> {:?}", e);
+        })?;
+        info!("Following changes of posix accounts and groups");
+        self.invalidate().await?;
+
+        loop {
+            match changes.next().await {
+                Ok(Some(change)) => {
+                    debug!(?change, "invalidating changed entry");
+                    if change.change == EntryChange::Create {
+                        // The names and gids of the new entry may be in the nxcache.
+                        self.nxcache.lock().await.clear();
+                    }
+                    let uuid = change.uuid.to_string();
+                    let dbtxn = self.db.write().await;
+                    dbtxn
+                        .invalidate_uuid(uuid.as_str())
+                        .and_then(|_| dbtxn.commit())?;
+                }
+                Ok(None) => return Ok(()),
+                Err(e) => {
+                    error!("change subscription error -> {:?}", e);
+                    return Err(());
+                }
+            }
+        }
+    }
+
+    /// Authenticate the client as this host if it is enrolled, otherwise as anonymous.
+    async fn authenticate(&self, client: &KanidmClient) -> Result<(), ClientError> {
+        match &self.host_credential {
+            Some(credential) => {
+                client.set_token(credential.clone()).await;
+                // The credential is only checked when it is used, and is refused if the host
+                // was revoked.
+                match client.whoami().await? {
+                    Some(_) => Ok(()),
+                    None => Err(ClientError::AuthenticationFailed),
+                }
+            }
+            None => client.auth_anonymous().await,
+        }
+    }
+
     async fn get_cached_usertokens(&self) -> Result<Vec<UnixUserToken>, ()> {
         let dbtxn = self.db.write().await;
         dbtxn.get_accounts()
@@ -343,7 +422,7 @@ impl CacheLayer {
     async fn check_cache_userpassword(&self, a_uuid: &str, cred: &str) -> Result<bool, ()> {
         let dbtxn = self.db.write().await;
         dbtxn
-            .check_account_password(a_uuid, cred)
+            .check_account_password(a_uuid, cred, self.offline_cred_timeout)
             .and_then(|x| dbtxn.commit().map(|_| x))
     }
 
@@ -862,32 +941,69 @@ impl CacheLayer {
     }
 
     pub async fn pam_account_allowed(&self, account_id: &str) -> Result<Option<bool>, ()> {
-        let token = self.get_usertoken(Id::Name(account_id.to_string())).await?;
-
         if self.pam_allow_groups.len() == 0 {
             // can't allow anything if the group list is zero...
             eprintln!("Cannot authenticate users, no allowed groups in configuration!");
-            Ok(Some(false))
-        } else {
-            Ok(token.map(|tok| {
-                let user_set: BTreeSet<_> = tok
-                    .groups
-                    .iter()
-                    .map(|g| vec![g.name.clone(), g.spn.clone(), g.uuid.clone()])
-                    .flatten()
-                    .collect();
-
-                debug!(
-                    "Checking if user is in allowed groups ({:?}) -> {:?}",
-                    self.pam_allow_groups, user_set,
-                );
-                let intersection_count = user_set.intersection(&self.pam_allow_groups).count();
-                debug!("Number of intersecting groups: {}", intersection_count);
-                debug!("User has valid token: {}", tok.valid);
+            return Ok(Some(false));
+        }
+        let allowed = self
+            .account_in_groups(account_id, &self.pam_allow_groups)
+            .await?;
+        if allowed == Some(false) {
+            info!(
+                "Denying login to {}, not in pam_allowed_login_groups or account is not valid",
+                account_id
+            );
+        }
+        Ok(allowed)
+    }
 
-                intersection_count > 0 && tok.valid
-            }))
+    pub async fn sudo_account_allowed(&self, account_id: &str) -> Result<Option<bool>, ()> {
+        if self.sudo_allow_groups.len() == 0 {
+            warn!(
+                "Denying sudo to {}, no sudo_allowed_groups in configuration",
+                account_id
+            );
+            return Ok(Some(false));
+        }
+        let allowed = self
+            .account_in_groups(account_id, &self.sudo_allow_groups)
+            .await?;
+        if allowed == Some(false) {
+            info!(
+                "Denying sudo to {}, not in sudo_allowed_groups or account is not valid",
+                account_id
+            );
         }
+        Ok(allowed)
+    }
+
+    /// If the account is valid and a member of any of the groups, by name, spn or uuid.
+    async fn account_in_groups(
+        &self,
+        account_id: &str,
+        groups: &BTreeSet<String>,
+    ) -> Result<Option<bool>, ()> {
+        let token = self.get_usertoken(Id::Name(account_id.to_string())).await?;
+
+        Ok(token.map(|tok| {
+            let user_set: BTreeSet<_> = tok
+                .groups
+                .iter()
+                .map(|g| vec![g.name.clone(), g.spn.clone(), g.uuid.clone()])
+                .flatten()
+                .collect();
+
+            debug!(
+                "Checking if user is in allowed groups ({:?}) -> {:?}",
+                groups, user_set,
+            );
+            let intersection_count = user_set.intersection(groups).count();
+            debug!("Number of intersecting groups: {}", intersection_count);
+            debug!("User has valid token: {}", tok.valid);
+
+            intersection_count > 0 && tok.valid
+        }))
     }
 
     pub async fn pam_account_authenticate(
@@ -946,7 +1062,8 @@ impl CacheLayer {
                 false
             }
             CacheState::OfflineNextCheck(_time) => {
-                match self.client.write().await.auth_anonymous().await {
+                let client = self.client.write().await;
+                match self.authenticate(&client).await {
                     Ok(_uat) => {
                         debug!("OfflineNextCheck -> authenticated");
                         self.set_cachestate(CacheState::Online).await;
@@ -1,9 +1,13 @@
+use crate::seal::SealBackend;
 use crate::unix_config::{HomeAttr, UidAttr};
 
 pub const DEFAULT_CONFIG_PATH: &str = "/etc/kanidm/unixd";
 pub const DEFAULT_SOCK_PATH: &str = "/var/run/kanidm-unixd/sock";
 pub const DEFAULT_TASK_SOCK_PATH: &str = "/var/run/kanidm-unixd/task_sock";
 pub const DEFAULT_DB_PATH: &str = "/var/cache/kanidm-unixd/kanidm.cache.db";
+pub const DEFAULT_CACHE_SEAL: SealBackend = SealBackend::None;
+pub const DEFAULT_CACHE_SEAL_KEY_PATH: &str = "/var/lib/kanidm-unixd/cache_seal.key";
+pub const DEFAULT_HOST_CREDENTIAL_PATH: &str = "/var/lib/kanidm-unixd/host_credential";
 pub const DEFAULT_CONN_TIMEOUT: u64 = 2;
 pub const DEFAULT_CACHE_TIMEOUT: u64 = 15;
 pub const DEFAULT_SHELL: &str = "/bin/sh";
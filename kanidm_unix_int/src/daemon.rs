@@ -13,8 +13,8 @@
 use std::error::Error;
 use std::fs::metadata;
 use std::io;
-use std::io::{Error as IoError, ErrorKind};
-use std::os::unix::fs::MetadataExt;
+use std::io::{Error as IoError, ErrorKind, Write};
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
@@ -22,10 +22,11 @@ use std::time::Duration;
 use bytes::{BufMut, BytesMut};
 use clap::{Arg, ArgAction, Command};
 use futures::{SinkExt, StreamExt};
-use kanidm_client::KanidmClientBuilder;
+use kanidm_client::{KanidmClient, KanidmClientBuilder};
 use kanidm_proto::constants::DEFAULT_CLIENT_CONFIG_PATH;
 use kanidm_unix_common::cache::CacheLayer;
 use kanidm_unix_common::constants::DEFAULT_CONFIG_PATH;
+use kanidm_unix_common::seal::CacheSeal;
 use kanidm_unix_common::unix_config::KanidmUnixdConfig;
 use kanidm_unix_common::unix_proto::{ClientRequest, ClientResponse, TaskRequest, TaskResponse};
 use kanidmd_lib::utils::file_permissions_readonly;
@@ -135,6 +136,46 @@ fn rm_if_exist(p: &str) {
     }
 }
 
+/// The credential of this host, which is enrolled with the configured enrolment token the first
+/// time the daemon starts with it. Without a credential the daemon authenticates as anonymous.
+async fn host_credential(cfg: &KanidmUnixdConfig, client: &KanidmClient) -> Option<String> {
+    let path = cfg.host_credential_path.as_str();
+    match std::fs::read_to_string(path) {
+        Ok(credential) => return Some(credential.trim().to_string()),
+        Err(e) if e.kind() == ErrorKind::NotFound => {}
+        Err(e) => {
+            error!("Unable to read host credential {} -> {:?}", path, e);
+            return None;
+        }
+    }
+
+    let token = cfg.host_enrol_token.as_ref()?;
+    let credential = match client.host_enrol(token).await {
+        Ok(c) => c,
+        Err(e) => {
+            error!(
+                "Unable to enrol this host, it will be retried when the daemon restarts -> {:?}",
+                e
+            );
+            return None;
+        }
+    };
+
+    if let Err(e) = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)
+        .and_then(|mut f| f.write_all(credential.as_bytes()))
+    {
+        // The token can't be used again, so this host must be enrolled with a new one.
+        error!("Unable to save host credential {} -> {:?}", path, e);
+    } else {
+        info!("Enrolled this host, and saved its credential to {}", path);
+    }
+    Some(credential)
+}
+
 async fn handle_task_client(
     stream: UnixStream,
     task_channel_tx: &Sender<AsyncTaskRequest>,
@@ -285,6 +326,14 @@ async fn handle_client(
                     .map(ClientResponse::PamStatus)
                     .unwrap_or(ClientResponse::Error)
             }
+            ClientRequest::SudoAccountAllowed(account_id) => {
+                debug!("sudo account allowed");
+                cachelayer
+                    .sudo_account_allowed(account_id.as_str())
+                    .await
+                    .map(ClientResponse::PamStatus)
+                    .unwrap_or(ClientResponse::Error)
+            }
             ClientRequest::PamAccountBeginSession(account_id) => {
                 debug!("pam account begin session");
                 match cachelayer
@@ -630,11 +679,25 @@ async fn main() {
             };
 
 
+            let host_credential = host_credential(&cfg, &rsclient).await;
+
+            let seal = match CacheSeal::new(cfg.cache_seal, cfg.cache_seal_key_path.as_str()) {
+                Ok(s) => s,
+                Err(_e) => {
+                    error!("Failed to load the cache seal key.");
+                    return
+                }
+            };
+
             let cl_inner = match CacheLayer::new(
                 cfg.db_path.as_str(), // The sqlite db path
+                seal,
                 cfg.cache_timeout,
+                cfg.offline_cred_timeout,
                 rsclient,
+                host_credential,
                 cfg.pam_allowed_login_groups.clone(),
+                cfg.sudo_allowed_groups.clone(),
                 cfg.default_shell.clone(),
                 cfg.home_prefix.clone(),
                 cfg.home_attr,
@@ -716,6 +779,18 @@ async fn main() {
                 }
             });
 
+            // Follow the changes on the server so the cache is invalidated as soon as entries
+            // change. While offline, or if the server doesn't offer changes, retry later.
+            let cachelayer_watch = cachelayer.clone();
+            tokio::spawn(async move {
+                loop {
+                    if cachelayer_watch.watch_changes().await.is_err() {
+                        debug!("Not following changes, will retry ...");
+                    }
+                    time::sleep(Duration::from_secs(30)).await;
+                }
+            });
+
             // TODO: Setup a task that handles pre-fetching here.
 
             let server = async move {
@@ -1,6 +1,6 @@
 use std::convert::TryFrom;
 use std::fmt;
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use kanidm_proto::v1::{UnixGroupToken, UnixUserToken};
 use kanidmd_lib::be::dbvalue::DbPasswordV1;
@@ -12,11 +12,13 @@ use r2d2_sqlite::SqliteConnectionManager;
 use tokio::sync::{Mutex, MutexGuard};
 
 use crate::cache::Id;
+use crate::seal::CacheSeal;
 
 pub struct Db {
     pool: Pool<SqliteConnectionManager>,
     lock: Mutex<()>,
     crypto_policy: CryptoPolicy,
+    seal: CacheSeal,
 }
 
 pub struct DbTxn<'a> {
@@ -24,10 +26,11 @@ pub struct DbTxn<'a> {
     committed: bool,
     conn: r2d2::PooledConnection<SqliteConnectionManager>,
     crypto_policy: &'a CryptoPolicy,
+    seal: &'a CacheSeal,
 }
 
 impl Db {
-    pub fn new(path: &str) -> Result<Self, ()> {
+    pub fn new(path: &str, seal: CacheSeal) -> Result<Self, ()> {
         let before = unsafe { umask(0o0027) };
         let manager = SqliteConnectionManager::file(path);
         let _ = unsafe { umask(before) };
@@ -46,6 +49,7 @@ impl Db {
             pool,
             lock: Mutex::new(()),
             crypto_policy,
+            seal,
         })
     }
 
@@ -56,7 +60,7 @@ impl Db {
             .pool
             .get()
             .expect("Unable to get connection from pool!!!");
-        DbTxn::new(conn, guard, &self.crypto_policy)
+        DbTxn::new(conn, guard, &self.crypto_policy, &self.seal)
     }
 }
 
@@ -66,11 +70,25 @@ impl fmt::Debug for Db {
     }
 }
 
+fn unix_now() -> Result<i64, ()> {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| {
+            error!("system time error -> {:?}", e);
+        })
+        .and_then(|d| {
+            i64::try_from(d.as_secs()).map_err(|e| {
+                error!("unix time i64 conversion error -> {:?}", e);
+            })
+        })
+}
+
 impl<'a> DbTxn<'a> {
     pub fn new(
         conn: r2d2::PooledConnection<SqliteConnectionManager>,
         guard: MutexGuard<'a, ()>,
         crypto_policy: &'a CryptoPolicy,
+        seal: &'a CacheSeal,
     ) -> Self {
         // Start the transaction
         // debug!("Starting db WR txn ...");
@@ -82,6 +100,7 @@ impl<'a> DbTxn<'a> {
             conn,
             _guard: guard,
             crypto_policy,
+            seal,
         }
     }
 
@@ -125,6 +144,7 @@ impl<'a> DbTxn<'a> {
                 spn TEXT NOT NULL UNIQUE,
                 gidnumber INTEGER NOT NULL UNIQUE,
                 password BLOB,
+                password_time NUMERIC,
                 token BLOB NOT NULL,
                 expiry NUMERIC NOT NULL
             )
@@ -135,6 +155,19 @@ impl<'a> DbTxn<'a> {
                 self.sqlite_error("account_t create", e);
             })?;
 
+        // Caches from before the offline window have no time for their passwords.
+        if self
+            .conn
+            .prepare("SELECT password_time FROM account_t")
+            .is_err()
+        {
+            self.conn
+                .execute("ALTER TABLE account_t ADD COLUMN password_time NUMERIC", [])
+                .map_err(|e| {
+                    self.sqlite_error("account_t alter", e);
+                })?;
+        }
+
         self.conn
             .execute(
                 "CREATE TABLE IF NOT EXISTS group_t (
@@ -202,6 +235,26 @@ impl<'a> DbTxn<'a> {
         Ok(())
     }
 
+    /// Expire the cached account or group of the uuid, so it is fetched again on its next use.
+    pub fn invalidate_uuid(&self, uuid: &str) -> Result<(), ()> {
+        self.conn
+            .execute("UPDATE group_t SET expiry = 0 WHERE uuid = :uuid", &[uuid])
+            .map_err(|e| {
+                self.sqlite_error("update group_t", e);
+            })?;
+
+        self.conn
+            .execute(
+                "UPDATE account_t SET expiry = 0 WHERE uuid = :uuid",
+                &[uuid],
+            )
+            .map_err(|e| {
+                self.sqlite_error("update account_t", e);
+            })?;
+
+        Ok(())
+    }
+
     pub fn clear_cache(&self) -> Result<(), ()> {
         self.conn.execute("DELETE FROM group_t", []).map_err(|e| {
             self.sqlite_error("delete group_t", e);
@@ -452,16 +505,20 @@ impl<'a> DbTxn<'a> {
             error!("password error -> {:?}", e);
         })?;
         let dbpw = pw.to_dbpasswordv1();
-        let data = serde_json::to_vec(&dbpw).map_err(|e| {
-            error!("json error -> {:?}", e);
-        })?;
+        let data = serde_json::to_vec(&dbpw)
+            .map_err(|e| {
+                error!("json error -> {:?}", e);
+            })
+            .and_then(|data| self.seal.seal(data))?;
+        let now = unix_now()?;
 
         self.conn
             .execute(
-                "UPDATE account_t SET password = :data WHERE uuid = :a_uuid",
+                "UPDATE account_t SET password = :data, password_time = :now WHERE uuid = :a_uuid",
                 named_params! {
                     ":a_uuid": &a_uuid,
                     ":data": &data,
+                    ":now": &now,
                 },
             )
             .map_err(|e| {
@@ -470,21 +527,28 @@ impl<'a> DbTxn<'a> {
             .map(|_| ())
     }
 
-    pub fn check_account_password(&self, a_uuid: &str, cred: &str) -> Result<bool, ()> {
+    /// Check the cached password of an account. With a `max_age` in seconds, a password that was
+    /// cached longer ago than that no longer matches.
+    pub fn check_account_password(
+        &self,
+        a_uuid: &str,
+        cred: &str,
+        max_age: Option<u64>,
+    ) -> Result<bool, ()> {
         let mut stmt = self
             .conn
-            .prepare("SELECT password FROM account_t WHERE uuid = :a_uuid AND password IS NOT NULL")
+            .prepare("SELECT password, password_time FROM account_t WHERE uuid = :a_uuid AND password IS NOT NULL")
             .map_err(|e| {
                 self.sqlite_error("select prepare", e);
             })?;
 
-        // Makes tuple (token, expiry)
+        // Makes tuple (password, password_time)
         let data_iter = stmt
-            .query_map(&[a_uuid], |row| Ok(row.get(0)?))
+            .query_map(&[a_uuid], |row| Ok((row.get(0)?, row.get(1)?)))
             .map_err(|e| {
                 self.sqlite_error("query_map", e);
             })?;
-        let data: Result<Vec<Vec<u8>>, _> = data_iter
+        let data: Result<Vec<(Vec<u8>, Option<i64>)>, _> = data_iter
             .map(|v| {
                 v.map_err(|e| {
                     self.sqlite_error("map", e);
@@ -504,9 +568,36 @@ impl<'a> DbTxn<'a> {
             return Err(());
         }
 
+        if let Some(max_age) = max_age {
+            let max_age = i64::try_from(max_age).map_err(|e| {
+                error!("check_account_password i64 conversion error -> {:?}", e);
+            })?;
+            let now = unix_now()?;
+            // A password without a time was cached before there was a window, so it is too old.
+            let in_window = data
+                .first()
+                .and_then(|(_, time)| *time)
+                .map(|time| now.saturating_sub(time) <= max_age)
+                .unwrap_or(false);
+            if !in_window {
+                info!("Cached password is older than offline_cred_timeout, failing authentication");
+                return Ok(false);
+            }
+        }
+
         let r: Result<bool, ()> = data
-            .first()
-            .map(|raw| {
+            .into_iter()
+            .next()
+            .map(|(raw, _)| {
+                let raw = match self.seal.unseal(raw) {
+                    Some(raw) => raw,
+                    None => {
+                        info!(
+                            "Cached password was sealed with another key, failing authentication"
+                        );
+                        return Ok(false);
+                    }
+                };
                 // Map the option from data.first.
                 let dbpw: DbPasswordV1 = serde_json::from_slice(raw.as_slice()).map_err(|e| {
                     error!("json error -> {:?}", e);
@@ -736,6 +827,7 @@ mod tests {
 
     use super::Db;
     use crate::cache::Id;
+    use crate::seal::CacheSeal;
 
     const TESTACCOUNT1_PASSWORD_A: &str = "password a for account1 test";
     const TESTACCOUNT1_PASSWORD_B: &str = "password b for account1 test";
@@ -743,7 +835,8 @@ mod tests {
     #[tokio::test]
     async fn test_cache_db_account_basic() {
         let _ = sketching::test_init();
-        let db = Db::new("").expect("failed to create.");
+        let db = Db::new("", CacheSeal::ephemeral().expect("failed to create seal"))
+            .expect("failed to create.");
         let dbtxn = db.write().await;
         assert!(dbtxn.migrate().is_ok());
 
@@ -808,6 +901,12 @@ mod tests {
         let r4 = dbtxn.get_account(&id_gid).unwrap();
         assert!(r4.is_some());
 
+        // invalidating the uuid of the account expires it, but keeps it for offline use
+        dbtxn.update_account(&ut1, 300).unwrap();
+        assert!(dbtxn.invalidate_uuid(&ut1.uuid).is_ok());
+        let r3 = dbtxn.get_account(&id_uuid).unwrap();
+        assert!(r3.map(|(_, expiry)| expiry) == Some(0));
+
         // Clear cache
         assert!(dbtxn.clear_cache().is_ok());
 
@@ -827,7 +926,8 @@ mod tests {
     #[tokio::test]
     async fn test_cache_db_group_basic() {
         let _ = sketching::test_init();
-        let db = Db::new("").expect("failed to create.");
+        let db = Db::new("", CacheSeal::ephemeral().expect("failed to create seal"))
+            .expect("failed to create.");
         let dbtxn = db.write().await;
         assert!(dbtxn.migrate().is_ok());
 
@@ -902,7 +1002,8 @@ mod tests {
     #[tokio::test]
     async fn test_cache_db_account_group_update() {
         let _ = sketching::test_init();
-        let db = Db::new("").expect("failed to create.");
+        let db = Db::new("", CacheSeal::ephemeral().expect("failed to create seal"))
+            .expect("failed to create.");
         let dbtxn = db.write().await;
         assert!(dbtxn.migrate().is_ok());
 
@@ -970,7 +1071,8 @@ mod tests {
     #[tokio::test]
     async fn test_cache_db_account_password() {
         let _ = sketching::test_init();
-        let db = Db::new("").expect("failed to create.");
+        let db = Db::new("", CacheSeal::ephemeral().expect("failed to create seal"))
+            .expect("failed to create.");
         let dbtxn = db.write().await;
         assert!(dbtxn.migrate().is_ok());
 
@@ -988,30 +1090,52 @@ mod tests {
         };
 
         // Test that with no account, is false
-        assert!(dbtxn.check_account_password(uuid1, TESTACCOUNT1_PASSWORD_A) == Ok(false));
+        assert!(dbtxn.check_account_password(uuid1, TESTACCOUNT1_PASSWORD_A, None) == Ok(false));
         // test adding an account
         dbtxn.update_account(&ut1, 0).unwrap();
         // check with no password is false.
-        assert!(dbtxn.check_account_password(uuid1, TESTACCOUNT1_PASSWORD_A) == Ok(false));
+        assert!(dbtxn.check_account_password(uuid1, TESTACCOUNT1_PASSWORD_A, None) == Ok(false));
         // update the pw
         assert!(dbtxn
             .update_account_password(uuid1, TESTACCOUNT1_PASSWORD_A)
             .is_ok());
         // Check it now works.
-        assert!(dbtxn.check_account_password(uuid1, TESTACCOUNT1_PASSWORD_A) == Ok(true));
-        assert!(dbtxn.check_account_password(uuid1, TESTACCOUNT1_PASSWORD_B) == Ok(false));
+        assert!(dbtxn.check_account_password(uuid1, TESTACCOUNT1_PASSWORD_A, None) == Ok(true));
+        assert!(dbtxn.check_account_password(uuid1, TESTACCOUNT1_PASSWORD_B, None) == Ok(false));
         // Update the pw
         assert!(dbtxn
             .update_account_password(uuid1, TESTACCOUNT1_PASSWORD_B)
             .is_ok());
         // Check it matches.
-        assert!(dbtxn.check_account_password(uuid1, TESTACCOUNT1_PASSWORD_A) == Ok(false));
-        assert!(dbtxn.check_account_password(uuid1, TESTACCOUNT1_PASSWORD_B) == Ok(true));
+        assert!(dbtxn.check_account_password(uuid1, TESTACCOUNT1_PASSWORD_A, None) == Ok(false));
+        assert!(dbtxn.check_account_password(uuid1, TESTACCOUNT1_PASSWORD_B, None) == Ok(true));
 
         // Check that updating the account does not break the password.
         ut1.displayname = "Test User Update".to_string();
         dbtxn.update_account(&ut1, 0).unwrap();
-        assert!(dbtxn.check_account_password(uuid1, TESTACCOUNT1_PASSWORD_B) == Ok(true));
+        assert!(dbtxn.check_account_password(uuid1, TESTACCOUNT1_PASSWORD_B, None) == Ok(true));
+
+        // Check the password is sealed in the cache.
+        let raw: Vec<u8> = dbtxn
+            .conn
+            .query_row(
+                "SELECT password FROM account_t WHERE uuid = :a_uuid",
+                &[uuid1],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert!(serde_json::from_slice::<serde_json::Value>(&raw).is_err());
+
+        // Check the offline window.
+        assert!(dbtxn.check_account_password(uuid1, TESTACCOUNT1_PASSWORD_B, Some(60)) == Ok(true));
+        dbtxn
+            .conn
+            .execute("UPDATE account_t SET password_time = 0", [])
+            .unwrap();
+        assert!(
+            dbtxn.check_account_password(uuid1, TESTACCOUNT1_PASSWORD_B, Some(60)) == Ok(false)
+        );
+        assert!(dbtxn.check_account_password(uuid1, TESTACCOUNT1_PASSWORD_B, None) == Ok(true));
 
         assert!(dbtxn.commit().is_ok());
     }
@@ -1019,7 +1143,8 @@ mod tests {
     #[tokio::test]
     async fn test_cache_db_group_rename_duplicate() {
         let _ = sketching::test_init();
-        let db = Db::new("").expect("failed to create.");
+        let db = Db::new("", CacheSeal::ephemeral().expect("failed to create seal"))
+            .expect("failed to create.");
         let dbtxn = db.write().await;
         assert!(dbtxn.migrate().is_ok());
 
@@ -1074,7 +1199,8 @@ mod tests {
     #[tokio::test]
     async fn test_cache_db_account_rename_duplicate() {
         let _ = sketching::test_init();
-        let db = Db::new("").expect("failed to create.");
+        let db = Db::new("", CacheSeal::ephemeral().expect("failed to create seal"))
+            .expect("failed to create.");
         let dbtxn = db.write().await;
         assert!(dbtxn.migrate().is_ok());
 
@@ -20,5 +20,6 @@ pub mod client;
 pub mod client_sync;
 pub mod constants;
 pub(crate) mod db;
+pub mod seal;
 pub mod unix_config;
 pub mod unix_proto;
@@ -0,0 +1,277 @@
+//! Sealing of the cached password hashes. They are encrypted with a key that is kept outside of
+//! the cache, in the kernel keyring or a separate key file, so that a copy of the cache alone
+//! can't be used to attack the passwords of its users.
+
+use std::convert::TryFrom;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io::{ErrorKind, Write};
+use std::os::unix::fs::OpenOptionsExt;
+
+use openssl::rand::rand_bytes;
+use openssl::symm::{decrypt_aead, encrypt_aead, Cipher};
+
+const SEAL_KEY_LEN: usize = 32;
+const SEAL_NONCE_LEN: usize = 12;
+const SEAL_TAG_LEN: usize = 16;
+// Sealed values start with this, where unsealed ones are json and start with '{'.
+const SEAL_V1: u8 = 0x01;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SealBackend {
+    None,
+    Keyring,
+    File,
+}
+
+impl Display for SealBackend {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                SealBackend::None => "none",
+                SealBackend::Keyring => "keyring",
+                SealBackend::File => "file",
+            }
+        )
+    }
+}
+
+pub struct CacheSeal {
+    key: Option<[u8; SEAL_KEY_LEN]>,
+}
+
+fn new_key() -> Result<[u8; SEAL_KEY_LEN], ()> {
+    let mut key = [0; SEAL_KEY_LEN];
+    rand_bytes(&mut key).map_err(|e| {
+        error!("seal key generation error -> {:?}", e);
+    })?;
+    Ok(key)
+}
+
+#[cfg(target_os = "linux")]
+mod keyring {
+    use std::ffi::CString;
+
+    use super::SEAL_KEY_LEN;
+
+    // From keyctl(2).
+    const KEY_SPEC_USER_KEYRING: libc::c_long = -4;
+    const KEYCTL_READ: libc::c_long = 11;
+    const KEY_DESCRIPTION: &str = "kanidm-unixd:cache-seal";
+
+    fn read_key(serial: libc::c_long) -> Option<[u8; SEAL_KEY_LEN]> {
+        let mut key = [0; SEAL_KEY_LEN];
+        let len = unsafe {
+            libc::syscall(
+                libc::SYS_keyctl,
+                KEYCTL_READ,
+                serial,
+                key.as_mut_ptr(),
+                SEAL_KEY_LEN,
+            )
+        };
+        if len == SEAL_KEY_LEN as libc::c_long {
+            Some(key)
+        } else {
+            None
+        }
+    }
+
+    /// The key of the user keyring of the daemon, which is added if there is none, and whether
+    /// it was added. The keyring is not persisted, so a key added here is lost when the host
+    /// restarts.
+    pub(super) fn get_or_add_key(
+        new_key: [u8; SEAL_KEY_LEN],
+    ) -> Result<([u8; SEAL_KEY_LEN], bool), ()> {
+        let key_type = CString::new("user").map_err(|_| ())?;
+        let description = CString::new(KEY_DESCRIPTION).map_err(|_| ())?;
+
+        let serial = unsafe {
+            libc::syscall(
+                libc::SYS_request_key,
+                key_type.as_ptr(),
+                description.as_ptr(),
+                std::ptr::null::<libc::c_char>(),
+                KEY_SPEC_USER_KEYRING,
+            )
+        };
+        if serial > 0 {
+            return read_key(serial).map(|key| (key, false)).ok_or_else(|| {
+                error!("Unable to read the cache seal key from the kernel keyring");
+            });
+        }
+
+        let serial = unsafe {
+            libc::syscall(
+                libc::SYS_add_key,
+                key_type.as_ptr(),
+                description.as_ptr(),
+                new_key.as_ptr(),
+                SEAL_KEY_LEN,
+                KEY_SPEC_USER_KEYRING,
+            )
+        };
+        if serial > 0 {
+            Ok((new_key, true))
+        } else {
+            error!("Unable to add the cache seal key to the kernel keyring");
+            Err(())
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod keyring {
+    use super::SEAL_KEY_LEN;
+
+    pub(super) fn get_or_add_key(
+        _new_key: [u8; SEAL_KEY_LEN],
+    ) -> Result<([u8; SEAL_KEY_LEN], bool), ()> {
+        error!("The kernel keyring is only available on Linux");
+        Err(())
+    }
+}
+
+/// The key of the key file, which is created if it does not exist.
+fn get_or_create_key_file(key_path: &str) -> Result<[u8; SEAL_KEY_LEN], ()> {
+    match fs::read(key_path) {
+        Ok(key) => <[u8; SEAL_KEY_LEN]>::try_from(key.as_slice()).map_err(|_| {
+            error!("Invalid cache seal key in {}", key_path);
+        }),
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            let key = new_key()?;
+            fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .mode(0o600)
+                .open(key_path)
+                .and_then(|mut f| f.write_all(&key))
+                .map_err(|e| {
+                    error!("Unable to create cache seal key {} -> {:?}", key_path, e);
+                })?;
+            info!("Created cache seal key {}", key_path);
+            Ok(key)
+        }
+        Err(e) => {
+            error!("Unable to read cache seal key {} -> {:?}", key_path, e);
+            Err(())
+        }
+    }
+}
+
+impl CacheSeal {
+    pub fn new(backend: SealBackend, key_path: &str) -> Result<Self, ()> {
+        let key = match backend {
+            SealBackend::None => None,
+            // The key file would be in the same state directory as the cache, so if the keyring
+            // is unavailable we refuse to start rather than quietly weaken the seal.
+            SealBackend::Keyring => {
+                let (key, added) = keyring::get_or_add_key(new_key()?).map_err(|()| {
+                    error!("Kernel keyring unavailable, set cache_seal to \"file\" to use a key file instead");
+                })?;
+                if added {
+                    warn!("Added a new cache seal key to the kernel keyring. Passwords cached before the host restarted can't be unsealed, so offline login is unavailable for each user until they next login online.");
+                }
+                Some(key)
+            }
+            SealBackend::File => Some(get_or_create_key_file(key_path)?),
+        };
+        Ok(CacheSeal { key })
+    }
+
+    /// A seal that does not encrypt.
+    pub fn none() -> Self {
+        CacheSeal { key: None }
+    }
+
+    /// A seal with a key only in memory, which is lost with the cache.
+    pub fn ephemeral() -> Result<Self, ()> {
+        Ok(CacheSeal {
+            key: Some(new_key()?),
+        })
+    }
+
+    pub fn seal(&self, data: Vec<u8>) -> Result<Vec<u8>, ()> {
+        let key = match &self.key {
+            Some(key) => key,
+            None => return Ok(data),
+        };
+
+        let mut nonce = [0; SEAL_NONCE_LEN];
+        rand_bytes(&mut nonce).map_err(|e| {
+            error!("seal nonce generation error -> {:?}", e);
+        })?;
+        let mut tag = [0; SEAL_TAG_LEN];
+        let ciphertext = encrypt_aead(
+            Cipher::aes_256_gcm(),
+            key,
+            Some(&nonce),
+            &[SEAL_V1],
+            &data,
+            &mut tag,
+        )
+        .map_err(|e| {
+            error!("seal error -> {:?}", e);
+        })?;
+
+        let mut sealed = Vec::with_capacity(1 + SEAL_NONCE_LEN + ciphertext.len() + SEAL_TAG_LEN);
+        sealed.push(SEAL_V1);
+        sealed.extend_from_slice(&nonce);
+        sealed.extend_from_slice(&ciphertext);
+        sealed.extend_from_slice(&tag);
+        Ok(sealed)
+    }
+
+    /// The unsealed value, or None if it was sealed with a key that is no longer available.
+    /// Values from before sealing was configured are returned as they are.
+    pub fn unseal(&self, data: Vec<u8>) -> Option<Vec<u8>> {
+        if data.first() != Some(&SEAL_V1) {
+            return Some(data);
+        }
+        let key = self.key.as_ref()?;
+        if data.len() < 1 + SEAL_NONCE_LEN + SEAL_TAG_LEN {
+            return None;
+        }
+
+        let (nonce, rest) = data[1..].split_at(SEAL_NONCE_LEN);
+        let (ciphertext, tag) = rest.split_at(rest.len() - SEAL_TAG_LEN);
+        decrypt_aead(
+            Cipher::aes_256_gcm(),
+            key,
+            Some(nonce),
+            &[SEAL_V1],
+            ciphertext,
+            tag,
+        )
+        .map_err(|e| {
+            debug!("unseal error -> {:?}", e);
+        })
+        .ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CacheSeal;
+
+    #[test]
+    fn test_cache_seal_roundtrip() {
+        let data = b"{\"cached\": \"value\"}".to_vec();
+
+        let seal = CacheSeal::ephemeral().expect("failed to create seal");
+        let sealed = seal.seal(data.clone()).expect("failed to seal");
+        assert!(sealed != data);
+        assert!(seal.unseal(sealed.clone()) == Some(data.clone()));
+
+        // A different key, or no key, can't unseal it.
+        let other = CacheSeal::ephemeral().expect("failed to create seal");
+        assert!(other.unseal(sealed.clone()).is_none());
+        assert!(CacheSeal::none().unseal(sealed).is_none());
+
+        // Values from before sealing remain readable.
+        assert!(seal.unseal(data.clone()) == Some(data.clone()));
+        assert!(CacheSeal::none().seal(data.clone()) == Ok(data));
+    }
+}
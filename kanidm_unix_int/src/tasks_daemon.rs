@@ -148,14 +148,20 @@ fn create_home_directory(
     }
 
     let name_rel_path = Path::new(&name);
+    // Sanity check the aliases.
+    let aliases: Vec<String> = info
+        .aliases
+        .iter()
+        .map(|alias| {
+            alias
+                .trim_start_matches('.')
+                .replace("/", "")
+                .replace("\\", "")
+        })
+        .collect();
+
     // Does the aliases exist
-    for alias in info.aliases.iter() {
-        // Sanity check the alias.
-        // let alias = alias.replace(".", "").replace("/", "").replace("\\", "");
-        let alias = alias
-            .trim_start_matches('.')
-            .replace("/", "")
-            .replace("\\", "");
+    for alias in aliases.iter() {
         let alias_path_raw = format!("{}{}", home_prefix, alias);
         let alias_path = Path::new(&alias_path_raw);
 
@@ -192,6 +198,31 @@ fn create_home_directory(
             }
         }
     }
+
+    remove_stale_aliases(home_prefix_path, name_rel_path, &aliases)
+}
+
+/// Remove the aliases of a home directory from before the account was renamed, so that the old
+/// name is free for a new account, rather than leading into this home.
+fn remove_stale_aliases(
+    home_prefix_path: &Path,
+    name_rel_path: &Path,
+    aliases: &[String],
+) -> Result<(), String> {
+    let entries = fs::read_dir(home_prefix_path).map_err(|e| format!("{:?}", e))?;
+    for entry in entries.filter_map(|e| e.ok()) {
+        let is_symlink = entry.file_type().map(|ft| ft.is_symlink()).unwrap_or(false);
+        if !is_symlink || aliases.iter().any(|a| entry.file_name() == a.as_str()) {
+            continue;
+        }
+        match fs::read_link(entry.path()) {
+            Ok(target) if target == name_rel_path => {
+                info!("removing stale home directory alias {:?}", entry.path());
+                fs::remove_file(entry.path()).map_err(|e| format!("{:?}", e))?;
+            }
+            _ => {}
+        }
+    }
     Ok(())
 }
 
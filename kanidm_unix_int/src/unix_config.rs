@@ -6,20 +6,29 @@ use std::path::Path;
 
 use serde::Deserialize;
 
+use crate::seal::SealBackend;
+
 use crate::constants::{
-    DEFAULT_CACHE_TIMEOUT, DEFAULT_CONN_TIMEOUT, DEFAULT_DB_PATH, DEFAULT_GID_ATTR_MAP,
-    DEFAULT_HOME_ALIAS, DEFAULT_HOME_ATTR, DEFAULT_HOME_PREFIX, DEFAULT_SHELL, DEFAULT_SOCK_PATH,
+    DEFAULT_CACHE_SEAL, DEFAULT_CACHE_SEAL_KEY_PATH, DEFAULT_CACHE_TIMEOUT, DEFAULT_CONN_TIMEOUT,
+    DEFAULT_DB_PATH, DEFAULT_GID_ATTR_MAP, DEFAULT_HOME_ALIAS, DEFAULT_HOME_ATTR,
+    DEFAULT_HOME_PREFIX, DEFAULT_HOST_CREDENTIAL_PATH, DEFAULT_SHELL, DEFAULT_SOCK_PATH,
     DEFAULT_TASK_SOCK_PATH, DEFAULT_UID_ATTR_MAP, DEFAULT_USE_ETC_SKEL,
 };
 
 #[derive(Debug, Deserialize)]
 struct ConfigInt {
     db_path: Option<String>,
+    cache_seal: Option<String>,
+    cache_seal_key_path: Option<String>,
+    host_enrol_token: Option<String>,
+    host_credential_path: Option<String>,
     sock_path: Option<String>,
     task_sock_path: Option<String>,
     conn_timeout: Option<u64>,
     cache_timeout: Option<u64>,
+    offline_cred_timeout: Option<u64>,
     pam_allowed_login_groups: Option<Vec<String>>,
+    sudo_allowed_groups: Option<Vec<String>>,
     default_shell: Option<String>,
     home_prefix: Option<String>,
     home_attr: Option<String>,
@@ -72,12 +81,18 @@ impl Display for UidAttr {
 #[derive(Debug)]
 pub struct KanidmUnixdConfig {
     pub db_path: String,
+    pub cache_seal: SealBackend,
+    pub cache_seal_key_path: String,
+    pub host_enrol_token: Option<String>,
+    pub host_credential_path: String,
     pub sock_path: String,
     pub task_sock_path: String,
     pub conn_timeout: u64,
     pub cache_timeout: u64,
+    pub offline_cred_timeout: Option<u64>,
     pub unix_sock_timeout: u64,
     pub pam_allowed_login_groups: Vec<String>,
+    pub sudo_allowed_groups: Vec<String>,
     pub default_shell: String,
     pub home_prefix: String,
     pub home_attr: HomeAttr,
@@ -96,16 +111,28 @@ impl Default for KanidmUnixdConfig {
 impl Display for KanidmUnixdConfig {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "db_path: {}", &self.db_path)?;
+        writeln!(f, "cache_seal: {}", self.cache_seal)?;
+        writeln!(f, "cache_seal_key_path: {}", self.cache_seal_key_path)?;
+        match self.host_enrol_token {
+            Some(_) => writeln!(f, "host_enrol_token: set")?,
+            None => writeln!(f, "host_enrol_token: unset")?,
+        }
+        writeln!(f, "host_credential_path: {}", self.host_credential_path)?;
         writeln!(f, "sock_path: {}", self.sock_path)?;
         writeln!(f, "task_sock_path: {}", self.task_sock_path)?;
         writeln!(f, "conn_timeout: {}", self.conn_timeout)?;
         writeln!(f, "unix_sock_timeout: {}", self.unix_sock_timeout)?;
         writeln!(f, "cache_timeout: {}", self.cache_timeout)?;
+        match self.offline_cred_timeout {
+            Some(val) => writeln!(f, "offline_cred_timeout: {}", val)?,
+            None => writeln!(f, "offline_cred_timeout: unset")?,
+        }
         writeln!(
             f,
             "pam_allowed_login_groups: {:#?}",
             self.pam_allowed_login_groups
         )?;
+        writeln!(f, "sudo_allowed_groups: {:#?}", self.sudo_allowed_groups)?;
         writeln!(f, "default_shell: {}", self.default_shell)?;
         writeln!(f, "home_prefix: {}", self.home_prefix)?;
         writeln!(f, "home_attr: {}", self.home_attr)?;
@@ -127,12 +154,18 @@ impl KanidmUnixdConfig {
         };
         KanidmUnixdConfig {
             db_path,
+            cache_seal: DEFAULT_CACHE_SEAL,
+            cache_seal_key_path: DEFAULT_CACHE_SEAL_KEY_PATH.to_string(),
+            host_enrol_token: None,
+            host_credential_path: DEFAULT_HOST_CREDENTIAL_PATH.to_string(),
             sock_path: DEFAULT_SOCK_PATH.to_string(),
             task_sock_path: DEFAULT_TASK_SOCK_PATH.to_string(),
             conn_timeout: DEFAULT_CONN_TIMEOUT,
             unix_sock_timeout: DEFAULT_CONN_TIMEOUT * 2,
             cache_timeout: DEFAULT_CACHE_TIMEOUT,
+            offline_cred_timeout: None,
             pam_allowed_login_groups: Vec::new(),
+            sudo_allowed_groups: Vec::new(),
             default_shell: DEFAULT_SHELL.to_string(),
             home_prefix: DEFAULT_HOME_PREFIX.to_string(),
             home_attr: DEFAULT_HOME_ATTR,
@@ -188,14 +221,37 @@ impl KanidmUnixdConfig {
         // Now map the values into our config.
         Ok(KanidmUnixdConfig {
             db_path: config.db_path.unwrap_or(self.db_path),
+            cache_seal: config
+                .cache_seal
+                .and_then(|v| match v.as_str() {
+                    "none" => Some(SealBackend::None),
+                    "keyring" => Some(SealBackend::Keyring),
+                    "file" => Some(SealBackend::File),
+                    _ => {
+                        warn!("Invalid cache_seal configured, using default ...");
+                        None
+                    }
+                })
+                .unwrap_or(self.cache_seal),
+            cache_seal_key_path: config
+                .cache_seal_key_path
+                .unwrap_or(self.cache_seal_key_path),
+            host_enrol_token: config.host_enrol_token.or(self.host_enrol_token),
+            host_credential_path: config
+                .host_credential_path
+                .unwrap_or(self.host_credential_path),
             sock_path: config.sock_path.unwrap_or(self.sock_path),
             task_sock_path: config.task_sock_path.unwrap_or(self.task_sock_path),
             conn_timeout: config.conn_timeout.unwrap_or(self.conn_timeout),
             unix_sock_timeout: config.conn_timeout.unwrap_or(self.conn_timeout) * 2,
             cache_timeout: config.cache_timeout.unwrap_or(self.cache_timeout),
+            offline_cred_timeout: config.offline_cred_timeout.or(self.offline_cred_timeout),
             pam_allowed_login_groups: config
                 .pam_allowed_login_groups
                 .unwrap_or(self.pam_allowed_login_groups),
+            sudo_allowed_groups: config
+                .sudo_allowed_groups
+                .unwrap_or(self.sudo_allowed_groups),
             default_shell: config.default_shell.unwrap_or(self.default_shell),
             home_prefix: config.home_prefix.unwrap_or(self.home_prefix),
             home_attr: config
@@ -11,6 +11,7 @@ use kanidm_unix_common::constants::{
     DEFAULT_GID_ATTR_MAP, DEFAULT_HOME_ALIAS, DEFAULT_HOME_ATTR, DEFAULT_HOME_PREFIX,
     DEFAULT_SHELL, DEFAULT_UID_ATTR_MAP,
 };
+use kanidm_unix_common::seal::CacheSeal;
 use kanidmd_core::config::{Configuration, IntegrationTestConfig, ServerRole};
 use kanidmd_core::create_server_core;
 use tokio::task;
@@ -101,9 +102,13 @@ async fn setup_test(fix_fn: Fixture) -> (CacheLayer, KanidmClient) {
 
     let cachelayer = CacheLayer::new(
         "", // The sqlite db path, this is in memory.
+        CacheSeal::ephemeral().expect("Failed to create cache seal."),
         300,
+        None,
         rsclient,
+        None,
         vec!["allowed_group".to_string()],
+        vec!["sudo_group".to_string()],
         DEFAULT_SHELL.to_string(),
         DEFAULT_HOME_PREFIX.to_string(),
         DEFAULT_HOME_ATTR,
@@ -171,6 +176,13 @@ async fn test_fixture(rsclient: KanidmClient) {
         .idm_group_unix_extend("allowed_group", Some(20002))
         .await
         .unwrap();
+
+    // Setup the sudo group
+    rsclient.idm_group_create("sudo_group").await.unwrap();
+    rsclient
+        .idm_group_unix_extend("sudo_group", Some(20003))
+        .await
+        .unwrap();
 }
 
 #[tokio::test]
@@ -511,6 +523,48 @@ async fn test_cache_account_pam_allowed() {
     assert!(a2 == Some(true));
 }
 
+#[tokio::test]
+async fn test_cache_account_sudo_allowed() {
+    let (cachelayer, adminclient) = setup_test(fixture(test_fixture)).await;
+    cachelayer.attempt_online().await;
+
+    adminclient
+        .auth_simple_password("admin", ADMIN_TEST_PASSWORD)
+        .await
+        .expect("failed to auth as admin");
+    adminclient
+        .idm_group_add_members("allowed_group", &["testaccount1"])
+        .await
+        .unwrap();
+    assert!(cachelayer.invalidate().await.is_ok());
+
+    // Allowed to login, but not to sudo.
+    let a1 = cachelayer
+        .sudo_account_allowed("testaccount1")
+        .await
+        .expect("failed to authenticate");
+    assert!(a1 == Some(false));
+
+    adminclient
+        .idm_group_add_members("sudo_group", &["testaccount1"])
+        .await
+        .unwrap();
+    assert!(cachelayer.invalidate().await.is_ok());
+
+    let a2 = cachelayer
+        .sudo_account_allowed("testaccount1")
+        .await
+        .expect("failed to authenticate");
+    assert!(a2 == Some(true));
+
+    // Unknown accounts are not for us to decide.
+    let a3 = cachelayer
+        .sudo_account_allowed("NO_SUCH_ACCOUNT")
+        .await
+        .expect("failed to authenticate");
+    assert!(a3 == None);
+}
+
 #[tokio::test]
 async fn test_cache_account_pam_nonexist() {
     let (cachelayer, _adminclient) = setup_test(fixture(test_fixture)).await;
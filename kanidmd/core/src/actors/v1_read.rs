@@ -1,25 +1,30 @@
+use std::collections::BTreeSet;
 use std::convert::TryFrom;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
-use kanidm_proto::internal::AppLink;
+use kanidm_proto::internal::{AppLink, Oauth2Consent};
 use kanidm_proto::v1::{
-    ApiToken, AuthRequest, BackupCodesView, CURequest, CUSessionToken, CUStatus, CredentialStatus,
-    Entry as ProtoEntry, OperationError, RadiusAuthToken, SearchRequest, SearchResponse, UatStatus,
-    UnixGroupToken, UnixUserToken, UserAuthToken, WhoamiResponse,
+    ApiToken, AttributeChange, AuditRecord, AuditSearchRequest, AuthRequest, BackupCodesView,
+    CURequest, CUSessionToken, CUStatus, CredentialStatus, Entry as ProtoEntry,
+    Filter as ProtoFilter, Oauth2SessionStatus, OperationError, RadiusAuthToken,
+    ReplTopologyStatus, SearchRequest, SearchResponse, UatStatus, UnixGroupToken, UnixUserToken,
+    UserAuthToken, WhoamiResponse,
 };
+use ldap3_proto::proto::{LdapBindResponse, LdapExtendedResponse, LdapOp, LdapResult};
 use ldap3_proto::simple::*;
 use regex::Regex;
+use tokio::sync::broadcast;
 use tracing::{error, info, instrument, trace};
 use uuid::Uuid;
 
 use kanidmd_lib::be::BackendTransaction;
 use kanidmd_lib::prelude::*;
 use kanidmd_lib::{
-    event::{OnlineBackupEvent, SearchEvent, SearchResult, WhoamiResult},
+    event::{OnlineBackupEvent, SearchEvent, SearchPage, SearchResult, WhoamiResult},
     filter::{Filter, FilterInvalid},
-    idm::account::ListUserAuthTokenEvent,
+    idm::account::{ListOauth2SessionEvent, ListUserAuthTokenEvent},
     idm::credupdatesession::CredentialUpdateSessionToken,
     idm::event::{
         AuthEvent, AuthResult, CredentialStatusEvent, RadiusAuthTokenEvent, ReadBackupCodeEvent,
@@ -27,12 +32,16 @@ use kanidmd_lib::{
     },
     idm::oauth2::{
         AccessTokenIntrospectRequest, AccessTokenIntrospectResponse, AccessTokenRequest,
-        AccessTokenResponse, AuthorisationRequest, AuthorisePermitSuccess, AuthoriseResponse,
-        JwkKeySet, Oauth2Error, OidcDiscoveryResponse, OidcToken,
+        AccessTokenResponse, AuthorisationRequestOrPushed, AuthorisePermitSuccess,
+        AuthoriseResponse, JwkKeySet, Oauth2Error, OidcDiscoveryResponse, OidcToken,
+        PushedAuthorisationRequest, PushedAuthorisationResponse,
     },
     idm::server::{IdmServer, IdmServerTransaction},
     idm::serviceaccount::ListApiTokenEvent,
-    ldap::{LdapBoundToken, LdapResponseState, LdapServer},
+    ldap::{LdapBoundToken, LdapResponseState, LdapServer, LDAP_PASSWORD_MODIFY_OID},
+    repl::agreement::ReplAgreement,
+    repl::proto::{ReplIncrementalContext, ReplRefreshContext, ReplRefreshRequest, ReplRuvRange},
+    server::EntryChanges,
 };
 
 // ===========================================================
@@ -79,13 +88,15 @@ impl QueryServerReadV1 {
         // Begin a read
         let ct = duration_from_epoch_now();
         let idms_prox_read = self.idms.proxy_read().await;
-        let ident = idms_prox_read
+        let mut ident = idms_prox_read
             .validate_and_parse_token_to_ident(uat.as_deref(), ct)
             .map_err(|e| {
                 admin_error!(?e, "Invalid identity");
                 e
             })?;
 
+        let page = SearchPage::from_request(&mut ident, req.page_size, req.page_token.as_deref())?;
+
         // Make an event from the request
         let search =
             SearchEvent::from_message(ident, &req, &idms_prox_read.qs_read).map_err(|e| {
@@ -95,9 +106,98 @@ impl QueryServerReadV1 {
 
         trace!(?search, "Begin event");
 
-        let entries = idms_prox_read.qs_read.search_ext(&search)?;
+        let (entries, next_page_token) = match page {
+            Some(page) => {
+                let (entries, next) = idms_prox_read.qs_read.search_ext_page(&search, &page)?;
+                (entries, next.map(SearchPage::token))
+            }
+            None => (idms_prox_read.qs_read.search_ext(&search)?, None),
+        };
+
+        SearchResult::new(&idms_prox_read.qs_read, &entries)
+            .map(|sr| sr.paged_response(next_page_token))
+    }
+
+    /// As [Self::handle_search], with the entries as an LDIF document, and the token of the
+    /// next page.
+    #[instrument(
+        level = "info",
+        name = "search_ldif",
+        skip(self, uat, req, eventid)
+        fields(uuid = ?eventid)
+    )]
+    pub async fn handle_search_ldif(
+        &self,
+        uat: Option<String>,
+        req: SearchRequest,
+        eventid: Uuid,
+    ) -> Result<(String, Option<String>), OperationError> {
+        let ct = duration_from_epoch_now();
+        let idms_prox_read = self.idms.proxy_read().await;
+        let mut ident = idms_prox_read
+            .validate_and_parse_token_to_ident(uat.as_deref(), ct)
+            .map_err(|e| {
+                admin_error!(?e, "Invalid identity");
+                e
+            })?;
+
+        let page = SearchPage::from_request(&mut ident, req.page_size, req.page_token.as_deref())?;
+
+        let search =
+            SearchEvent::from_message(ident, &req, &idms_prox_read.qs_read).map_err(|e| {
+                admin_error!(?e, "Failed to begin search");
+                e
+            })?;
+
+        trace!(?search, "Begin event");
+
+        let (entries, next_page_token) = match page {
+            Some(page) => {
+                let (entries, next) = idms_prox_read.qs_read.search_ext_page(&search, &page)?;
+                (entries, next.map(SearchPage::token))
+            }
+            None => (idms_prox_read.qs_read.search_ext(&search)?, None),
+        };
+
+        self.ldap
+            .to_ldif(&idms_prox_read.qs_read, &entries)
+            .map(|ldif| (ldif, next_page_token))
+    }
+
+    /// As [Self::handle_search], without paging, for interfaces such as SCIM that page the
+    /// entries themself. The search is bounded by the resource limits of the session.
+    #[instrument(
+        level = "info",
+        name = "search_unpaged",
+        skip(self, uat, filter, eventid)
+        fields(uuid = ?eventid)
+    )]
+    pub async fn handle_search_unpaged(
+        &self,
+        uat: Option<String>,
+        filter: ProtoFilter,
+        eventid: Uuid,
+    ) -> Result<Vec<ProtoEntry>, OperationError> {
+        let ct = duration_from_epoch_now();
+        let idms_prox_read = self.idms.proxy_read().await;
+        let ident = idms_prox_read
+            .validate_and_parse_token_to_ident(uat.as_deref(), ct)
+            .map_err(|e| {
+                admin_error!(?e, "Invalid identity");
+                e
+            })?;
+
+        let search =
+            SearchEvent::from_message(ident, &SearchRequest::new(filter), &idms_prox_read.qs_read)
+                .map_err(|e| {
+                    admin_error!(?e, "Failed to begin search");
+                    e
+                })?;
 
-        SearchResult::new(&idms_prox_read.qs_read, &entries).map(SearchResult::response)
+        trace!(?search, "Begin event");
+
+        let entries = idms_prox_read.qs_read.search_ext(&search)?;
+        SearchResult::new(&idms_prox_read.qs_read, &entries).map(SearchResult::into_proto_array)
     }
 
     #[instrument(
@@ -321,6 +421,76 @@ impl QueryServerReadV1 {
         }
     }
 
+    /// Subscribe to changes of the entries that match `filter`, and return the entries that
+    /// match it now.
+    #[instrument(
+        level = "info",
+        skip(self, uat, filter, eventid)
+        fields(uuid = ?eventid)
+    )]
+    pub async fn handle_changes_subscribe(
+        &self,
+        uat: Option<String>,
+        filter: ProtoFilter,
+        eventid: Uuid,
+    ) -> Result<(broadcast::Receiver<EntryChanges>, BTreeSet<Uuid>), OperationError> {
+        // Subscribe before searching, so that no change can be missed between the two.
+        let rx = self.idms.subscribe_changes();
+        self.handle_changes_match(uat, filter, None, eventid)
+            .await
+            .map(|uuids| (rx, uuids))
+    }
+
+    /// The entries that match `filter` and that the caller can search for. If `changed` is
+    /// given only those entries are considered.
+    #[instrument(
+        level = "debug",
+        skip(self, uat, filter, changed, eventid)
+        fields(uuid = ?eventid)
+    )]
+    pub async fn handle_changes_match(
+        &self,
+        uat: Option<String>,
+        filter: ProtoFilter,
+        changed: Option<&[Uuid]>,
+        eventid: Uuid,
+    ) -> Result<BTreeSet<Uuid>, OperationError> {
+        let ct = duration_from_epoch_now();
+        let idms_prox_read = self.idms.proxy_read().await;
+        // The session is checked for each change, so a subscription ends when the session does.
+        let ident = idms_prox_read
+            .validate_and_parse_token_to_ident(uat.as_deref(), ct)
+            .map_err(|e| {
+                admin_error!(?e, "Invalid identity");
+                e
+            })?;
+
+        let filter = match changed {
+            Some(uuids) => ProtoFilter::And(vec![
+                filter,
+                ProtoFilter::Or(
+                    uuids
+                        .iter()
+                        .map(|u| ProtoFilter::Eq("uuid".to_string(), u.to_string()))
+                        .collect(),
+                ),
+            ]),
+            None => filter,
+        };
+
+        let search =
+            SearchEvent::from_message(ident, &SearchRequest::new(filter), &idms_prox_read.qs_read)
+                .map_err(|e| {
+                    admin_error!(?e, "Failed to begin change subscription search");
+                    e
+                })?;
+
+        idms_prox_read
+            .qs_read
+            .search_ext(&search)
+            .map(|entries| entries.iter().map(|e| e.get_uuid()).collect())
+    }
+
     #[instrument(
         level = "info",
         name = "whoami_uat",
@@ -392,6 +562,120 @@ impl QueryServerReadV1 {
         }
     }
 
+    /// As [Self::handle_internalsearch], with the etag of each entry.
+    #[instrument(
+        level = "info",
+        skip_all,
+        fields(uuid = ?eventid)
+    )]
+    pub async fn handle_internalsearch_etags(
+        &self,
+        uat: Option<String>,
+        filter: Filter<FilterInvalid>,
+        attrs: Option<Vec<String>>,
+        eventid: Uuid,
+    ) -> Result<Vec<(ProtoEntry, Option<String>)>, OperationError> {
+        let ct = duration_from_epoch_now();
+        let idms_prox_read = self.idms.proxy_read().await;
+        let ident = idms_prox_read
+            .validate_and_parse_token_to_ident(uat.as_deref(), ct)
+            .map_err(|e| {
+                admin_error!("Invalid identity: {:?}", e);
+                e
+            })?;
+        let srch = SearchEvent::from_internal_message(
+            ident,
+            &filter,
+            attrs.as_deref(),
+            &idms_prox_read.qs_read,
+        )
+        .map_err(|e| {
+            admin_error!("Failed to begin internal api search: {:?}", e);
+            e
+        })?;
+
+        trace!(?srch, "Begin event");
+
+        let entries = idms_prox_read.qs_read.search_ext(&srch)?;
+        let etags: Vec<_> = entries.iter().map(|e| e.get_etag()).collect();
+        SearchResult::new(&idms_prox_read.qs_read, &entries)
+            .map(|sr| sr.into_proto_array().into_iter().zip(etags).collect())
+    }
+
+    /// The change that last modified each attribute of the entry, of those the identity may
+    /// read.
+    #[instrument(
+        level = "info",
+        skip_all,
+        fields(uuid = ?eventid)
+    )]
+    pub async fn handle_attr_changes(
+        &self,
+        uat: Option<String>,
+        filter: Filter<FilterInvalid>,
+        eventid: Uuid,
+    ) -> Result<Option<Vec<AttributeChange>>, OperationError> {
+        let ct = duration_from_epoch_now();
+        let idms_prox_read = self.idms.proxy_read().await;
+        let ident = idms_prox_read
+            .validate_and_parse_token_to_ident(uat.as_deref(), ct)
+            .map_err(|e| {
+                admin_error!("Invalid identity: {:?}", e);
+                e
+            })?;
+        let srch =
+            SearchEvent::from_internal_message(ident, &filter, None, &idms_prox_read.qs_read)
+                .map_err(|e| {
+                    admin_error!("Failed to begin internal api search: {:?}", e);
+                    e
+                })?;
+
+        trace!(?srch, "Begin event");
+
+        idms_prox_read
+            .qs_read
+            .search_ext(&srch)
+            .map(|mut entries| entries.pop().map(|e| e.get_attr_changes()))
+    }
+
+    /// As [Self::handle_internalsearch], with the entries as an LDIF document.
+    #[instrument(
+        level = "info",
+        skip_all,
+        fields(uuid = ?eventid)
+    )]
+    pub async fn handle_internalsearch_ldif(
+        &self,
+        uat: Option<String>,
+        filter: Filter<FilterInvalid>,
+        attrs: Option<Vec<String>>,
+        eventid: Uuid,
+    ) -> Result<String, OperationError> {
+        let ct = duration_from_epoch_now();
+        let idms_prox_read = self.idms.proxy_read().await;
+        let ident = idms_prox_read
+            .validate_and_parse_token_to_ident(uat.as_deref(), ct)
+            .map_err(|e| {
+                admin_error!("Invalid identity: {:?}", e);
+                e
+            })?;
+        let srch = SearchEvent::from_internal_message(
+            ident,
+            &filter,
+            attrs.as_deref(),
+            &idms_prox_read.qs_read,
+        )
+        .map_err(|e| {
+            admin_error!("Failed to begin internal api search: {:?}", e);
+            e
+        })?;
+
+        trace!(?srch, "Begin event");
+
+        let entries = idms_prox_read.qs_read.search_ext(&srch)?;
+        self.ldap.to_ldif(&idms_prox_read.qs_read, &entries)
+    }
+
     #[instrument(
         level = "info",
         skip_all,
@@ -827,6 +1111,197 @@ impl QueryServerReadV1 {
         idms_prox_read.account_list_user_auth_tokens(&lte)
     }
 
+    #[instrument(
+        level = "info",
+        skip_all,
+        fields(uuid = ?eventid)
+    )]
+    pub async fn handle_account_oauth2_session_get(
+        &self,
+        uat: Option<String>,
+        uuid_or_name: String,
+        eventid: Uuid,
+    ) -> Result<Vec<Oauth2SessionStatus>, OperationError> {
+        let ct = duration_from_epoch_now();
+        let idms_prox_read = self.idms.proxy_read().await;
+        let ident = idms_prox_read
+            .validate_and_parse_token_to_ident(uat.as_deref(), ct)
+            .map_err(|e| {
+                admin_error!("Invalid identity: {:?}", e);
+                e
+            })?;
+        let target = idms_prox_read
+            .qs_read
+            .name_to_uuid(uuid_or_name.as_str())
+            .map_err(|e| {
+                admin_error!("Error resolving id to target");
+                e
+            })?;
+
+        let lte = ListOauth2SessionEvent { ident, target };
+
+        idms_prox_read.account_list_oauth2_sessions(&lte)
+    }
+
+    #[instrument(
+        level = "info",
+        skip_all,
+        fields(uuid = ?eventid)
+    )]
+    pub async fn handle_replsupplierchanges(
+        &self,
+        uat: Option<String>,
+        ctx_ruv: ReplRuvRange,
+        eventid: Uuid,
+    ) -> Result<ReplIncrementalContext, OperationError> {
+        let ct = duration_from_epoch_now();
+        let idms_prox_read = self.idms.proxy_read().await;
+        let ident = idms_prox_read
+            .validate_and_parse_token_to_ident(uat.as_deref(), ct)
+            .map_err(|e| {
+                admin_error!(err = ?e, "Invalid identity");
+                e
+            })?;
+
+        // Replication supplies every attribute of every entry including credentials, so
+        // rather than access controls this requires a system administrator.
+        if !ident.is_memberof(UUID_SYSTEM_ADMINS) {
+            security_info!("Replication consumer is not a member of system_admins, denying");
+            return Err(OperationError::AccessDenied);
+        }
+
+        idms_prox_read.qs_read.supplier_provide_changes(&ctx_ruv)
+    }
+
+    #[instrument(
+        level = "info",
+        skip_all,
+        fields(uuid = ?eventid)
+    )]
+    pub async fn handle_replsupplierchanges_pinned(
+        &self,
+        cert_sha256: String,
+        ctx_ruv: ReplRuvRange,
+        eventid: Uuid,
+    ) -> Result<ReplIncrementalContext, OperationError> {
+        let ct = duration_from_epoch_now();
+        let idms_prox_read = self.idms.proxy_read().await;
+        let agreement = check_repl_partner_pinned(&idms_prox_read.qs_read, &cert_sha256)?;
+        let repl_status = idms_prox_read.qs_read.get_repl_status();
+
+        let res = idms_prox_read.qs_read.supplier_provide_changes(&ctx_ruv);
+        match &res {
+            Ok(ReplIncrementalContext::DomainMismatch) => {}
+            Ok(_) => repl_status.record_sync(agreement.uuid, Some(ctx_ruv.ranges), ct),
+            Err(e) => repl_status.record_error(agreement.uuid, e, ct),
+        }
+        idms_prox_read.qs_read.repl_emit_metrics(agreement, ct);
+        res
+    }
+
+    #[instrument(
+        level = "info",
+        skip_all,
+        fields(uuid = ?eventid)
+    )]
+    pub async fn handle_replsupplierrefresh(
+        &self,
+        uat: Option<String>,
+        req: ReplRefreshRequest,
+        eventid: Uuid,
+    ) -> Result<ReplRefreshContext, OperationError> {
+        let ct = duration_from_epoch_now();
+        let idms_prox_read = self.idms.proxy_read().await;
+        let ident = idms_prox_read
+            .validate_and_parse_token_to_ident(uat.as_deref(), ct)
+            .map_err(|e| {
+                admin_error!(err = ?e, "Invalid identity");
+                e
+            })?;
+
+        // As with incremental changes, a refresh supplies every attribute of every entry.
+        if !ident.is_memberof(UUID_SYSTEM_ADMINS) {
+            security_info!("Replication consumer is not a member of system_admins, denying");
+            return Err(OperationError::AccessDenied);
+        }
+
+        idms_prox_read.qs_read.supplier_provide_refresh(&req)
+    }
+
+    #[instrument(
+        level = "info",
+        skip_all,
+        fields(uuid = ?eventid)
+    )]
+    pub async fn handle_replsupplierrefresh_pinned(
+        &self,
+        cert_sha256: String,
+        req: ReplRefreshRequest,
+        eventid: Uuid,
+    ) -> Result<ReplRefreshContext, OperationError> {
+        let idms_prox_read = self.idms.proxy_read().await;
+        check_repl_partner_pinned(&idms_prox_read.qs_read, &cert_sha256)?;
+        idms_prox_read.qs_read.supplier_provide_refresh(&req)
+    }
+
+    #[instrument(
+        level = "info",
+        skip_all,
+        fields(uuid = ?eventid)
+    )]
+    pub async fn handle_repltopologystatus(
+        &self,
+        uat: Option<String>,
+        eventid: Uuid,
+    ) -> Result<ReplTopologyStatus, OperationError> {
+        let ct = duration_from_epoch_now();
+        let idms_prox_read = self.idms.proxy_read().await;
+        let ident = idms_prox_read
+            .validate_and_parse_token_to_ident(uat.as_deref(), ct)
+            .map_err(|e| {
+                admin_error!(err = ?e, "Invalid identity");
+                e
+            })?;
+
+        // Agreements can only be read by system administrators, so the same applies to
+        // their status.
+        if !ident.is_memberof(UUID_SYSTEM_ADMINS) {
+            security_info!("Requestor is not a member of system_admins, denying");
+            return Err(OperationError::AccessDenied);
+        }
+
+        idms_prox_read.qs_read.repl_topology_status(ct)
+    }
+
+    #[instrument(
+        level = "info",
+        skip_all,
+        fields(uuid = ?eventid)
+    )]
+    pub async fn handle_auditlogsearch(
+        &self,
+        uat: Option<String>,
+        req: AuditSearchRequest,
+        eventid: Uuid,
+    ) -> Result<Vec<AuditRecord>, OperationError> {
+        let ct = duration_from_epoch_now();
+        let idms_prox_read = self.idms.proxy_read().await;
+        let ident = idms_prox_read
+            .validate_and_parse_token_to_ident(uat.as_deref(), ct)
+            .map_err(|e| {
+                admin_error!(err = ?e, "Invalid identity");
+                e
+            })?;
+
+        // The audit log names who changed what, so only system administrators may read it.
+        if !ident.is_memberof(UUID_SYSTEM_ADMINS) {
+            security_info!("Requestor is not a member of system_admins, denying");
+            return Err(OperationError::AccessDenied);
+        }
+
+        idms_prox_read.qs_read.search_audit_log(&req)
+    }
+
     #[instrument(
         level = "info",
         skip_all,
@@ -1187,7 +1662,7 @@ impl QueryServerReadV1 {
     pub async fn handle_oauth2_authorise(
         &self,
         uat: Option<String>,
-        auth_req: AuthorisationRequest,
+        auth_req: AuthorisationRequestOrPushed,
         eventid: Uuid,
     ) -> Result<AuthoriseResponse, Oauth2Error> {
         let ct = duration_from_epoch_now();
@@ -1204,10 +1679,33 @@ impl QueryServerReadV1 {
                 Oauth2Error::AuthenticationRequired
             })?;
 
+        let auth_req = match auth_req {
+            AuthorisationRequestOrPushed::Request(auth_req) => auth_req,
+            AuthorisationRequestOrPushed::Pushed(auth_ref) => {
+                idms_prox_read.check_oauth2_pushed_authorisation_reference(&auth_ref, ct)?
+            }
+        };
+
         // Now we can send to the idm server for authorisation checking.
         idms_prox_read.check_oauth2_authorisation(&ident, &uat, &auth_req, ct)
     }
 
+    #[instrument(
+        level = "info",
+        skip_all,
+        fields(uuid = ?eventid)
+    )]
+    pub async fn handle_oauth2_pushed_authorisation(
+        &self,
+        client_authz: Option<String>,
+        par_req: PushedAuthorisationRequest,
+        eventid: Uuid,
+    ) -> Result<PushedAuthorisationResponse, Oauth2Error> {
+        let ct = duration_from_epoch_now();
+        let idms_prox_read = self.idms.proxy_read().await;
+        idms_prox_read.check_oauth2_pushed_authorisation(client_authz.as_deref(), &par_req, ct)
+    }
+
     #[instrument(
         level = "info",
         skip_all,
@@ -1365,6 +1863,28 @@ impl QueryServerReadV1 {
         idms_prox_read.list_applinks(&ident)
     }
 
+    #[instrument(
+        level = "info",
+        skip_all,
+        fields(uuid = ?eventid)
+    )]
+    pub async fn handle_list_oauth2_consent(
+        &self,
+        uat: Option<String>,
+        eventid: Uuid,
+    ) -> Result<Vec<Oauth2Consent>, OperationError> {
+        let ct = duration_from_epoch_now();
+        let idms_prox_read = self.idms.proxy_read().await;
+        let ident = idms_prox_read
+            .validate_and_parse_token_to_ident(uat.as_deref(), ct)
+            .map_err(|e| {
+                admin_error!("Invalid identity: {:?}", e);
+                e
+            })?;
+
+        idms_prox_read.oauth2_consent_list(&ident)
+    }
+
     #[instrument(
         level = "info",
         skip_all,
@@ -1375,6 +1895,16 @@ impl QueryServerReadV1 {
         idms_prox_read.qs_read.get_domain_display_name().to_string()
     }
 
+    #[instrument(
+        level = "info",
+        skip_all,
+        fields(uuid = ?eventid)
+    )]
+    pub async fn handle_ready(&self, eventid: Uuid) -> Result<(), OperationError> {
+        let idms_prox_read = self.idms.proxy_read().await;
+        idms_prox_read.qs_read.ready_check()
+    }
+
     #[instrument(
         level = "info",
         skip_all,
@@ -1408,23 +1938,97 @@ impl QueryServerReadV1 {
         protomsg: LdapMsg,
         uat: Option<LdapBoundToken>,
     ) -> Option<LdapResponseState> {
-        let res = match ServerOps::try_from(protomsg) {
-            Ok(server_op) => self
-                .ldap
-                .do_op(&self.idms, server_op, uat, &eventid)
-                .await
-                .unwrap_or_else(|e| {
-                    admin_error!("do_op failed -> {:?}", e);
-                    LdapResponseState::Disconnect(DisconnectionNotice::gen(
-                        LdapResultCode::Other,
-                        format!("Internal Server Error {:?}", &eventid).as_str(),
-                    ))
-                }),
-            Err(_) => LdapResponseState::Disconnect(DisconnectionNotice::gen(
-                LdapResultCode::ProtocolError,
-                format!("Invalid Request {:?}", &eventid).as_str(),
-            )),
-        };
-        Some(res)
+        // Controls are not carried by the server op, so retain them for the search.
+        let ctrl = protomsg.ctrl.clone();
+        let res = match protomsg.op {
+            LdapOp::CompareRequest(cr) => {
+                self.ldap
+                    .do_compare_op(&self.idms, protomsg.msgid, &cr, uat)
+                    .await
+            }
+            LdapOp::ExtendedRequest(er) if er.name == LDAP_PASSWORD_MODIFY_OID => {
+                self.ldap
+                    .do_password_modify_op(&self.idms, protomsg.msgid, &er, uat)
+                    .await
+            }
+            LdapOp::ModifyRequest(mr) => {
+                self.ldap
+                    .do_modify_op(&self.idms, protomsg.msgid, &mr, uat)
+                    .await
+            }
+            op => match ServerOps::try_from(LdapMsg { op, ..protomsg }) {
+                Ok(server_op) => {
+                    self.ldap
+                        .do_op(&self.idms, server_op, &ctrl, uat, &eventid)
+                        .await
+                }
+                Err(_) => {
+                    return Some(LdapResponseState::Disconnect(DisconnectionNotice::gen(
+                        LdapResultCode::ProtocolError,
+                        format!("Invalid Request {:?}", &eventid).as_str(),
+                    )))
+                }
+            },
+        }
+        .unwrap_or_else(|e| {
+            admin_error!("do_op failed -> {:?}", e);
+            LdapResponseState::Disconnect(DisconnectionNotice::gen(
+                LdapResultCode::Other,
+                format!("Internal Server Error {:?}", &eventid).as_str(),
+            ))
+        });
+        Some(ldap_response_with_opid(res, &eventid))
+    }
+}
+
+/// Add the operation id to the diagnostic message of a failed result, so that a client can
+/// report it and it can be matched to the server log.
+fn ldap_response_with_opid(mut res: LdapResponseState, eventid: &Uuid) -> LdapResponseState {
+    let msg = match &mut res {
+        LdapResponseState::Bind(_, msg) | LdapResponseState::Respond(msg) => Some(msg),
+        LdapResponseState::MultiPartResponse(msgs)
+        | LdapResponseState::BindMultiPartResponse(_, msgs) => msgs.last_mut(),
+        LdapResponseState::Unbind | LdapResponseState::Disconnect(_) => None,
+    };
+    let result: Option<&mut LdapResult> = msg.and_then(|msg| match &mut msg.op {
+        LdapOp::BindResponse(LdapBindResponse { res, .. })
+        | LdapOp::ExtendedResponse(LdapExtendedResponse { res, .. }) => Some(res),
+        LdapOp::SearchResultDone(res)
+        | LdapOp::ModifyResponse(res)
+        | LdapOp::AddResponse(res)
+        | LdapOp::DelResponse(res)
+        | LdapOp::CompareResult(res) => Some(res),
+        _ => None,
+    });
+    if let Some(result) = result {
+        if !matches!(
+            result.code,
+            LdapResultCode::Success | LdapResultCode::CompareTrue | LdapResultCode::CompareFalse
+        ) {
+            result.message = if result.message.is_empty() {
+                format!("opid: {}", eventid)
+            } else {
+                format!("{} (opid: {})", result.message, eventid)
+            };
+        }
+    }
+    res
+}
+
+/// Partners on the replication listener are identified only by the certificate they
+/// presented, which must be pinned on a replication agreement.
+fn check_repl_partner_pinned(
+    qs_read: &QueryServerReadTransaction,
+    cert_sha256: &str,
+) -> Result<ReplAgreement, OperationError> {
+    match qs_read.get_repl_agreement_by_cert(cert_sha256)? {
+        Some(agreement) => {
+            security_info!(agreement = %agreement.name, "Replication partner certificate is pinned");
+            Ok(agreement)
+        }
+        None => {
+            security_info!(%cert_sha256, "Replication partner certificate is not pinned, denying");
+            Err(OperationError::AccessDenied)
+        }
     }
 }
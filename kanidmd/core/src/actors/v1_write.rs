@@ -1,11 +1,13 @@
+use std::collections::BTreeMap;
 use std::iter;
 use std::sync::Arc;
 use std::time::Duration;
 
 use kanidm_proto::v1::{
-    AccountUnixExtend, AuthType, CUIntentToken, CUSessionToken, CUStatus, CreateRequest,
-    DeleteRequest, Entry as ProtoEntry, GroupUnixExtend, Modify as ProtoModify,
-    ModifyList as ProtoModifyList, ModifyRequest, OperationError,
+    AccountUnixExtend, AuthType, BatchRequest, BatchResponse, CUIntentToken, CUSessionToken,
+    CUStatus, CreateRequest, DeleteRequest, Entry as ProtoEntry, GroupUnixExtend,
+    Modify as ProtoModify, ModifyList as ProtoModifyList, ModifyRequest, OperationError,
+    ReplConflictResolution,
 };
 use time::OffsetDateTime;
 use tracing::{info, instrument, span, trace, Level};
@@ -13,11 +15,11 @@ use uuid::Uuid;
 
 use kanidmd_lib::{
     event::{
-        CreateEvent, DeleteEvent, ModifyEvent, PurgeRecycledEvent, PurgeTombstoneEvent,
-        ReviveRecycledEvent,
+        CreateEvent, DeleteEvent, ModifyEvent, PurgeAuditLogEvent, PurgeRecycledEvent,
+        PurgeTombstoneEvent, ReviveRecycledEvent,
     },
     filter::{Filter, FilterInvalid},
-    idm::account::DestroySessionTokenEvent,
+    idm::account::{DestroyOauth2SessionEvent, DestroySessionTokenEvent},
     idm::credupdatesession::{
         CredentialUpdateIntentToken, CredentialUpdateSessionToken, InitCredentialUpdateEvent,
         InitCredentialUpdateIntentEvent,
@@ -28,24 +30,38 @@ use kanidmd_lib::{
     idm::server::{IdmServer, IdmServerTransaction},
     idm::serviceaccount::{DestroyApiTokenEvent, GenerateApiTokenEvent},
     modify::{Modify, ModifyInvalid, ModifyList},
+    repl::agreement::ReplAgreement,
+    repl::join::ReplJoinToken,
+    repl::proto::{ReplCidRange, ReplIncrementalContext, ReplRuvRange},
+    repl::refresh::ReplRefreshCollector,
+    repl::status::ReplStatus,
     utils::duration_from_epoch_now,
     value::{PartialValue, Value},
 };
 
 use kanidmd_lib::prelude::*;
 
+use crate::repl::ReplJoinSupplier;
+
 pub struct QueryServerWriteV1 {
     pub(crate) idms: Arc<IdmServer>,
+    repl_join_supplier: Option<ReplJoinSupplier>,
 }
 
 impl QueryServerWriteV1 {
-    pub fn new(idms: Arc<IdmServer>) -> Self {
+    pub fn new(idms: Arc<IdmServer>, repl_join_supplier: Option<ReplJoinSupplier>) -> Self {
         info!("Starting query server v1 worker ...");
-        QueryServerWriteV1 { idms }
+        QueryServerWriteV1 {
+            idms,
+            repl_join_supplier,
+        }
     }
 
-    pub fn start_static(idms: Arc<IdmServer>) -> &'static QueryServerWriteV1 {
-        let x = Box::new(QueryServerWriteV1::new(idms));
+    pub fn start_static(
+        idms: Arc<IdmServer>,
+        repl_join_supplier: Option<ReplJoinSupplier>,
+    ) -> &'static QueryServerWriteV1 {
+        let x = Box::new(QueryServerWriteV1::new(idms, repl_join_supplier));
 
         let x_ptr = Box::leak(x);
         &(*x_ptr)
@@ -58,6 +74,7 @@ impl QueryServerWriteV1 {
         uuid_or_name: &str,
         proto_ml: &ProtoModifyList,
         filter: Filter<FilterInvalid>,
+        if_match: Option<Vec<String>>,
     ) -> Result<(), OperationError> {
         let mut idms_prox_write = self.idms.proxy_write(duration_from_epoch_now()).await;
         let ct = duration_from_epoch_now();
@@ -77,6 +94,16 @@ impl QueryServerWriteV1 {
                 e
             })?;
 
+        if let Some(etags) = if_match {
+            let f_target = Filter::join_parts_and(
+                filter_all!(f_eq("uuid", PartialValue::new_uuid(target_uuid))),
+                filter.clone(),
+            );
+            idms_prox_write
+                .qs_write
+                .check_etags(&ident, &f_target, &etags)?;
+        }
+
         let mdf = match ModifyEvent::from_parts(
             ident,
             target_uuid,
@@ -258,6 +285,34 @@ impl QueryServerWriteV1 {
             .and_then(|_| idms_prox_write.commit())
     }
 
+    #[instrument(
+        level = "info",
+        skip_all,
+        fields(uuid = ?eventid)
+    )]
+    pub async fn handle_batch(
+        &self,
+        uat: Option<String>,
+        req: BatchRequest,
+        eventid: Uuid,
+    ) -> Result<BatchResponse, OperationError> {
+        let mut idms_prox_write = self.idms.proxy_write(duration_from_epoch_now()).await;
+        let ct = duration_from_epoch_now();
+        let ident = idms_prox_write
+            .validate_and_parse_token_to_ident(uat.as_deref(), ct)
+            .map_err(|e| {
+                admin_error!(err = ?e, "Invalid identity");
+                e
+            })?;
+
+        let (committed, results) = idms_prox_write.qs_write.batch(&ident, &req)?;
+        // If an operation failed, the transaction is dropped so that none are applied.
+        if committed {
+            idms_prox_write.commit()?;
+        }
+        Ok(BatchResponse { committed, results })
+    }
+
     #[instrument(
         level = "info",
         skip_all,
@@ -268,6 +323,7 @@ impl QueryServerWriteV1 {
         uat: Option<String>,
         filter: Filter<FilterInvalid>,
         update: ProtoEntry,
+        if_match: Option<Vec<String>>,
         eventid: Uuid,
     ) -> Result<(), OperationError> {
         // Given a protoEntry, turn this into a modification set.
@@ -280,6 +336,13 @@ impl QueryServerWriteV1 {
                 e
             })?;
 
+        // Refuse the change if the entry changed since the client read it.
+        if let Some(etags) = if_match {
+            idms_prox_write
+                .qs_write
+                .check_etags(&ident, &filter, &etags)?;
+        }
+
         // Transform the ProtoEntry to a Modlist
         let modlist = ModifyList::from_patch(&update, &idms_prox_write.qs_write).map_err(|e| {
             admin_error!(err = ?e, "Invalid Patch Request");
@@ -310,6 +373,7 @@ impl QueryServerWriteV1 {
         &self,
         uat: Option<String>,
         filter: Filter<FilterInvalid>,
+        if_match: Option<Vec<String>>,
         eventid: Uuid,
     ) -> Result<(), OperationError> {
         let mut idms_prox_write = self.idms.proxy_write(duration_from_epoch_now()).await;
@@ -320,6 +384,12 @@ impl QueryServerWriteV1 {
                 admin_error!(err = ?e, "Invalid identity");
                 e
             })?;
+
+        if let Some(etags) = if_match {
+            idms_prox_write
+                .qs_write
+                .check_etags(&ident, &filter, &etags)?;
+        }
         let del = match DeleteEvent::from_parts(ident, &filter, &idms_prox_write.qs_write) {
             Ok(d) => d,
             Err(e) => {
@@ -500,6 +570,89 @@ impl QueryServerWriteV1 {
             .and_then(|r| idms_prox_write.commit().map(|_| r))
     }
 
+    #[instrument(
+        level = "info",
+        skip_all,
+        fields(uuid = ?eventid)
+    )]
+    pub async fn handle_host_enrol_token_create(
+        &self,
+        uat: Option<String>,
+        uuid_or_name: String,
+        eventid: Uuid,
+    ) -> Result<String, OperationError> {
+        let ct = duration_from_epoch_now();
+        let mut idms_prox_write = self.idms.proxy_write(ct).await;
+        let ident = idms_prox_write
+            .validate_and_parse_token_to_ident(uat.as_deref(), ct)
+            .map_err(|e| {
+                admin_error!(err = ?e, "Invalid identity");
+                e
+            })?;
+
+        let target = idms_prox_write
+            .qs_write
+            .name_to_uuid(uuid_or_name.as_str())
+            .map_err(|e| {
+                admin_error!(err = ?e, "Error resolving id to target");
+                e
+            })?;
+
+        idms_prox_write
+            .host_enrol_token_create(&ident, target)
+            .and_then(|r| idms_prox_write.commit().map(|_| r))
+    }
+
+    #[instrument(
+        level = "info",
+        skip_all,
+        fields(uuid = ?eventid)
+    )]
+    pub async fn handle_host_enrol(
+        &self,
+        token: String,
+        eventid: Uuid,
+    ) -> Result<String, OperationError> {
+        let ct = duration_from_epoch_now();
+        let mut idms_prox_write = self.idms.proxy_write(ct).await;
+        idms_prox_write
+            .host_enrol(&token, ct)
+            .and_then(|r| idms_prox_write.commit().map(|_| r))
+    }
+
+    #[instrument(
+        level = "info",
+        skip_all,
+        fields(uuid = ?eventid)
+    )]
+    pub async fn handle_host_credential_revoke(
+        &self,
+        uat: Option<String>,
+        uuid_or_name: String,
+        eventid: Uuid,
+    ) -> Result<(), OperationError> {
+        let ct = duration_from_epoch_now();
+        let mut idms_prox_write = self.idms.proxy_write(ct).await;
+        let ident = idms_prox_write
+            .validate_and_parse_token_to_ident(uat.as_deref(), ct)
+            .map_err(|e| {
+                admin_error!(err = ?e, "Invalid identity");
+                e
+            })?;
+
+        let target = idms_prox_write
+            .qs_write
+            .name_to_uuid(uuid_or_name.as_str())
+            .map_err(|e| {
+                admin_error!(err = ?e, "Error resolving id to target");
+                e
+            })?;
+
+        idms_prox_write
+            .host_credential_revoke(&ident, target)
+            .and_then(|r| idms_prox_write.commit().map(|_| r))
+    }
+
     #[instrument(
         level = "info",
         skip_all,
@@ -540,6 +693,71 @@ impl QueryServerWriteV1 {
             .and_then(|r| idms_prox_write.commit().map(|_| r))
     }
 
+    #[instrument(
+        level = "info",
+        skip_all,
+        fields(uuid = ?eventid)
+    )]
+    pub async fn handle_account_oauth2_session_destroy(
+        &self,
+        uat: Option<String>,
+        uuid_or_name: String,
+        session_id: Uuid,
+        eventid: Uuid,
+    ) -> Result<(), OperationError> {
+        let ct = duration_from_epoch_now();
+        let mut idms_prox_write = self.idms.proxy_write(ct).await;
+        let ident = idms_prox_write
+            .validate_and_parse_token_to_ident(uat.as_deref(), ct)
+            .map_err(|e| {
+                admin_error!(err = ?e, "Invalid identity");
+                e
+            })?;
+
+        let target = idms_prox_write
+            .qs_write
+            .name_to_uuid(uuid_or_name.as_str())
+            .map_err(|e| {
+                admin_error!(err = ?e, "Error resolving id to target");
+                e
+            })?;
+
+        let dte = DestroyOauth2SessionEvent {
+            ident,
+            target,
+            session_id,
+        };
+
+        idms_prox_write
+            .account_destroy_oauth2_session(&dte)
+            .and_then(|r| idms_prox_write.commit().map(|_| r))
+    }
+
+    #[instrument(
+        level = "info",
+        skip_all,
+        fields(uuid = ?eventid)
+    )]
+    pub async fn handle_oauth2_consent_revoke(
+        &self,
+        uat: Option<String>,
+        client_id: String,
+        eventid: Uuid,
+    ) -> Result<(), OperationError> {
+        let ct = duration_from_epoch_now();
+        let mut idms_prox_write = self.idms.proxy_write(ct).await;
+        let ident = idms_prox_write
+            .validate_and_parse_token_to_ident(uat.as_deref(), ct)
+            .map_err(|e| {
+                admin_error!(err = ?e, "Invalid identity");
+                e
+            })?;
+
+        idms_prox_write
+            .oauth2_consent_revoke(&ident, &client_id)
+            .and_then(|r| idms_prox_write.commit().map(|_| r))
+    }
+
     #[instrument(
         level = "info",
         skip_all,
@@ -861,6 +1079,7 @@ impl QueryServerWriteV1 {
         uuid_or_name: String,
         attr: String,
         filter: Filter<FilterInvalid>,
+        if_match: Option<Vec<String>>,
         eventid: Uuid,
     ) -> Result<(), OperationError> {
         let ct = duration_from_epoch_now();
@@ -879,6 +1098,16 @@ impl QueryServerWriteV1 {
                 e
             })?;
 
+        if let Some(etags) = if_match {
+            let f_target = Filter::join_parts_and(
+                filter_all!(f_eq("uuid", PartialValue::new_uuid(target_uuid))),
+                filter.clone(),
+            );
+            idms_prox_write
+                .qs_write
+                .check_etags(&ident, &f_target, &etags)?;
+        }
+
         let mdf = match ModifyEvent::from_target_uuid_attr_purge(
             ident,
             target_uuid,
@@ -913,6 +1142,7 @@ impl QueryServerWriteV1 {
         attr: String,
         values: Vec<String>,
         filter: Filter<FilterInvalid>,
+        if_match: Option<Vec<String>>,
         eventid: Uuid,
     ) -> Result<(), OperationError> {
         let mut idms_prox_write = self.idms.proxy_write(duration_from_epoch_now()).await;
@@ -931,6 +1161,16 @@ impl QueryServerWriteV1 {
                 e
             })?;
 
+        if let Some(etags) = if_match {
+            let f_target = Filter::join_parts_and(
+                filter_all!(f_eq("uuid", PartialValue::new_uuid(target_uuid))),
+                filter.clone(),
+            );
+            idms_prox_write
+                .qs_write
+                .check_etags(&ident, &f_target, &etags)?;
+        }
+
         let proto_ml = ProtoModifyList::new_list(
             values
                 .into_iter()
@@ -963,7 +1203,7 @@ impl QueryServerWriteV1 {
     #[instrument(
         level = "info",
         name = "append_attribute",
-        skip(self, uat, uuid_or_name, attr, values, filter, eventid)
+        skip(self, uat, uuid_or_name, attr, values, filter, if_match, eventid)
         fields(uuid = ?eventid)
     )]
     pub async fn handle_appendattribute(
@@ -973,6 +1213,7 @@ impl QueryServerWriteV1 {
         attr: String,
         values: Vec<String>,
         filter: Filter<FilterInvalid>,
+        if_match: Option<Vec<String>>,
         eventid: Uuid,
     ) -> Result<(), OperationError> {
         // We need to turn these into proto modlists so they can be converted
@@ -983,14 +1224,14 @@ impl QueryServerWriteV1 {
                 .map(|v| ProtoModify::Present(attr.clone(), v))
                 .collect(),
         );
-        self.modify_from_parts(uat, &uuid_or_name, &proto_ml, filter)
+        self.modify_from_parts(uat, &uuid_or_name, &proto_ml, filter, if_match)
             .await
     }
 
     #[instrument(
         level = "info",
         name = "set_attribute",
-        skip(self, uat, uuid_or_name, attr, values, filter, eventid)
+        skip(self, uat, uuid_or_name, attr, values, filter, if_match, eventid)
         fields(uuid = ?eventid)
     )]
     pub async fn handle_setattribute(
@@ -1000,6 +1241,7 @@ impl QueryServerWriteV1 {
         attr: String,
         values: Vec<String>,
         filter: Filter<FilterInvalid>,
+        if_match: Option<Vec<String>>,
         eventid: Uuid,
     ) -> Result<(), OperationError> {
         // We need to turn these into proto modlists so they can be converted
@@ -1013,7 +1255,7 @@ impl QueryServerWriteV1 {
                 )
                 .collect(),
         );
-        self.modify_from_parts(uat, &uuid_or_name, &proto_ml, filter)
+        self.modify_from_parts(uat, &uuid_or_name, &proto_ml, filter, if_match)
             .await
     }
 
@@ -1085,6 +1327,32 @@ impl QueryServerWriteV1 {
             .await
     }
 
+    #[instrument(
+        level = "info",
+        name = "idm_account_unix_remove",
+        skip(self, uat, uuid_or_name, eventid)
+        fields(uuid = ?eventid)
+    )]
+    pub async fn handle_idmaccountunixremove(
+        &self,
+        uat: Option<String>,
+        uuid_or_name: String,
+        eventid: Uuid,
+    ) -> Result<(), OperationError> {
+        // The posix attributes must go with the class, else the entry fails schema.
+        let ml = ModifyList::new_list(vec![
+            Modify::Removed("class".into(), PartialValue::new_class("posixaccount")),
+            Modify::Purged("gidnumber".into()),
+            Modify::Purged("loginshell".into()),
+            Modify::Purged("unix_password".into()),
+        ]);
+
+        let filter = filter_all!(f_eq("class", PartialValue::new_class("posixaccount")));
+
+        self.modify_from_internal_parts(uat, &uuid_or_name, &ml, filter)
+            .await
+    }
+
     #[instrument(
         level = "info",
         name = "idm_group_unix_extend",
@@ -1118,6 +1386,29 @@ impl QueryServerWriteV1 {
             .await
     }
 
+    #[instrument(
+        level = "info",
+        name = "idm_group_unix_remove",
+        skip(self, uat, uuid_or_name, eventid)
+        fields(uuid = ?eventid)
+    )]
+    pub async fn handle_idmgroupunixremove(
+        &self,
+        uat: Option<String>,
+        uuid_or_name: String,
+        eventid: Uuid,
+    ) -> Result<(), OperationError> {
+        let ml = ModifyList::new_list(vec![
+            Modify::Removed("class".into(), PartialValue::new_class("posixgroup")),
+            Modify::Purged("gidnumber".into()),
+        ]);
+
+        let filter = filter_all!(f_eq("class", PartialValue::new_class("posixgroup")));
+
+        self.modify_from_internal_parts(uat, &uuid_or_name, &ml, filter)
+            .await
+    }
+
     #[instrument(
         level = "info",
         skip_all,
@@ -1412,6 +1703,225 @@ impl QueryServerWriteV1 {
     }
 
     // ===== These below are internal only event types. =====
+    #[instrument(level = "debug", skip_all)]
+    pub async fn handle_replconsumerruvrange(&self) -> ReplRuvRange {
+        let idms_prox_read = self.idms.proxy_read().await;
+        idms_prox_read.qs_read.consumer_get_ruv_range()
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    pub async fn handle_replconsumeragreement(
+        &self,
+        supplier: &str,
+    ) -> Result<Option<ReplAgreement>, OperationError> {
+        let idms_prox_read = self.idms.proxy_read().await;
+        idms_prox_read
+            .qs_read
+            .get_repl_agreement_by_address(supplier)
+    }
+
+    #[instrument(level = "info", skip_all)]
+    pub async fn handle_replconsumerapply(
+        &self,
+        ctx: ReplIncrementalContext,
+    ) -> Result<(), OperationError> {
+        let mut idms_prox_write = self.idms.proxy_write(duration_from_epoch_now()).await;
+        idms_prox_write
+            .qs_write
+            .consumer_apply_changes(ctx)
+            .and_then(|_| idms_prox_write.commit())
+    }
+
+    #[instrument(
+        level = "info",
+        skip_all,
+        fields(uuid = ?eventid)
+    )]
+    pub async fn handle_replconflictresolve(
+        &self,
+        uat: Option<String>,
+        id: String,
+        resolution: ReplConflictResolution,
+        eventid: Uuid,
+    ) -> Result<(), OperationError> {
+        let ct = duration_from_epoch_now();
+        let mut idms_prox_write = self.idms.proxy_write(ct).await;
+        let ident = idms_prox_write
+            .validate_and_parse_token_to_ident(uat.as_deref(), ct)
+            .map_err(|e| {
+                admin_error!(err = ?e, "Invalid identity");
+                e
+            })?;
+
+        // A conflict entry may be revived over the access controls of the recycle bin, so
+        // this is limited to system administrators, as with other replication maintenance.
+        if !ident.is_memberof(UUID_SYSTEM_ADMINS) {
+            security_info!("Requestor is not a member of system_admins, denying");
+            return Err(OperationError::AccessDenied);
+        }
+
+        // Conflict entries are recycled, so can only be identified by uuid.
+        let target = Uuid::parse_str(&id).map_err(|_| {
+            admin_error!("Conflict entry id is not a uuid");
+            OperationError::InvalidUuid
+        })?;
+
+        idms_prox_write
+            .qs_write
+            .repl_conflict_resolve(target, resolution)
+            .and_then(|_| idms_prox_write.commit())
+    }
+
+    #[instrument(
+        level = "info",
+        skip_all,
+        fields(uuid = ?eventid)
+    )]
+    pub async fn handle_repljointokencreate(
+        &self,
+        uat: Option<String>,
+        name: String,
+        eventid: Uuid,
+    ) -> Result<String, OperationError> {
+        let ct = duration_from_epoch_now();
+        let mut idms_prox_write = self.idms.proxy_write(ct).await;
+        let ident = idms_prox_write
+            .validate_and_parse_token_to_ident(uat.as_deref(), ct)
+            .map_err(|e| {
+                admin_error!(err = ?e, "Invalid identity");
+                e
+            })?;
+
+        // The token allows a new server to pin its own certificate on an agreement, so
+        // this is limited to system administrators, as with other replication maintenance.
+        if !ident.is_memberof(UUID_SYSTEM_ADMINS) {
+            security_info!("Requestor is not a member of system_admins, denying");
+            return Err(OperationError::AccessDenied);
+        }
+
+        let supplier = self.repl_join_supplier.as_ref().ok_or_else(|| {
+            admin_error!("The replication listener is not enabled, unable to create join token");
+            OperationError::InvalidState
+        })?;
+
+        let secret = idms_prox_write.qs_write.repl_join_token_create(&name)?;
+        let token = ReplJoinToken {
+            name,
+            supplier: supplier.address.clone(),
+            cert_sha256: supplier.cert_sha256.clone(),
+            secret,
+        }
+        .encode()?;
+
+        idms_prox_write.commit().map(|_| token)
+    }
+
+    #[instrument(
+        level = "info",
+        skip_all,
+        fields(uuid = ?eventid)
+    )]
+    pub async fn handle_repljoinaccept(
+        &self,
+        cert_sha256: String,
+        secret: String,
+        eventid: Uuid,
+    ) -> Result<(), OperationError> {
+        let mut idms_prox_write = self.idms.proxy_write(duration_from_epoch_now()).await;
+        idms_prox_write
+            .qs_write
+            .supplier_accept_join(&secret, &cert_sha256)
+            .and_then(|_| idms_prox_write.commit())
+    }
+
+    /// Set a new random password on an account, as requested on the admin socket.
+    #[instrument(
+        level = "info",
+        skip_all,
+        fields(uuid = ?eventid)
+    )]
+    pub async fn handle_admin_recover_account(
+        &self,
+        name: String,
+        eventid: Uuid,
+    ) -> Result<String, OperationError> {
+        let mut idms_prox_write = self.idms.proxy_write(duration_from_epoch_now()).await;
+        let password = idms_prox_write.recover_account(&name, None)?;
+        idms_prox_write.commit().map(|()| password)
+    }
+
+    #[instrument(level = "info", skip_all)]
+    pub async fn handle_repljoincomplete(
+        &self,
+        refresh: ReplRefreshCollector,
+        token: &ReplJoinToken,
+    ) -> Result<(), OperationError> {
+        let mut idms_prox_write = self.idms.proxy_write(duration_from_epoch_now()).await;
+        idms_prox_write
+            .qs_write
+            .consumer_complete_join(refresh, token)
+            .and_then(|_| idms_prox_write.commit())
+    }
+
+    /// Record that changes were received from a supplier, if it has a replication agreement.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn handle_replconsumersynced(
+        &self,
+        supplier: &str,
+        ranges: Option<BTreeMap<Uuid, ReplCidRange>>,
+        changes: u64,
+        elapsed: Duration,
+    ) {
+        self.update_replconsumer_status(supplier, |repl_status, agreement_uuid, ct| {
+            repl_status.record_sync(agreement_uuid, ranges, ct);
+            repl_status.record_applied(agreement_uuid, changes, elapsed);
+        })
+        .await
+    }
+
+    /// Record that changes could not be received from a supplier, if it has a replication
+    /// agreement.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn handle_replconsumerfailed(&self, supplier: &str, err: &OperationError) {
+        self.update_replconsumer_status(supplier, |repl_status, agreement_uuid, ct| {
+            repl_status.record_error(agreement_uuid, err, ct);
+        })
+        .await
+    }
+
+    async fn update_replconsumer_status<F>(&self, supplier: &str, f: F)
+    where
+        F: FnOnce(&ReplStatus, Uuid, Duration),
+    {
+        let ct = duration_from_epoch_now();
+        let idms_prox_read = self.idms.proxy_read().await;
+        match idms_prox_read
+            .qs_read
+            .get_repl_agreement_by_address(supplier)
+        {
+            Ok(Some(agreement)) => {
+                f(idms_prox_read.qs_read.get_repl_status(), agreement.uuid, ct);
+                idms_prox_read.qs_read.repl_emit_metrics(agreement, ct);
+            }
+            Ok(None) => {
+                debug!(%supplier, "No replication agreement for supplier, not recording status")
+            }
+            Err(e) => error!(?e, "Unable to find replication agreement for supplier"),
+        }
+    }
+
+    #[instrument(level = "info", skip_all)]
+    pub async fn handle_replconsumerrefresh(
+        &self,
+        refresh: ReplRefreshCollector,
+    ) -> Result<(), OperationError> {
+        let mut idms_prox_write = self.idms.proxy_write(duration_from_epoch_now()).await;
+        idms_prox_write
+            .qs_write
+            .consumer_apply_refresh(refresh)
+            .and_then(|_| idms_prox_write.commit())
+    }
+
     #[instrument(
         level = "info",
         skip_all,
@@ -1423,7 +1933,7 @@ impl QueryServerWriteV1 {
 
         let res = idms_prox_write
             .qs_write
-            .purge_tombstones()
+            .purge_tombstones_retain(msg.changelog_retention)
             .and_then(|_| idms_prox_write.commit());
         admin_info!(?res, "Purge tombstones result");
         #[allow(clippy::expect_used)]
@@ -1447,6 +1957,23 @@ impl QueryServerWriteV1 {
         res.expect("Invalid Server State");
     }
 
+    #[instrument(
+        level = "info",
+        skip_all,
+        fields(uuid = ?msg.eventid)
+    )]
+    pub async fn handle_purgeauditlogevent(&self, msg: PurgeAuditLogEvent) {
+        trace!(?msg, "Begin purge audit log event");
+        let idms_prox_write = self.idms.proxy_write(duration_from_epoch_now()).await;
+        let res = idms_prox_write
+            .qs_write
+            .purge_audit_log(msg.retention)
+            .and_then(|_| idms_prox_write.commit());
+        admin_info!(?res, "Purge audit log result");
+        #[allow(clippy::expect_used)]
+        res.expect("Invalid Server State");
+    }
+
     pub(crate) async fn handle_delayedaction(&self, da: DelayedAction) {
         let eventid = Uuid::new_v4();
         let nspan = span!(Level::INFO, "process_delayed_action", uuid = ?eventid);
@@ -0,0 +1,356 @@
+//! The local admin socket, which carries privileged tasks to a running server. It doesn't
+//! depend on the network listeners or on any credential in the database, so that an
+//! administrator can always recover the server. Clients are authenticated by the credentials
+//! of the socket's peer, and must be root or the user the server runs as. Messages are json
+//! encoded, one per line.
+
+use std::fs;
+use std::io::{Error as IoError, ErrorKind};
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+use futures_util::sink::SinkExt;
+use futures_util::stream::StreamExt;
+use kanidmd_lib::event::OnlineBackupEvent;
+use kanidmd_lib::prelude::*;
+use serde::{Deserialize, Serialize};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::broadcast;
+use tokio_util::codec::{Framed, LinesCodec};
+
+use crate::actors::v1_read::QueryServerReadV1;
+use crate::actors::v1_write::QueryServerWriteV1;
+use crate::config::OnlineBackup;
+use crate::CoreAction;
+
+/// Requests are small, so a client can't make us buffer a large line.
+const ADMIN_MAX_LINE_BYTES: usize = 64 * 1024;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum AdminTaskRequest {
+    /// Set a new random password on an account.
+    RecoverAccount { name: String },
+    /// Create an online backup in the configured path.
+    OnlineBackup,
+    /// Reload the certificates of the HTTPS listener from disk.
+    ReloadTls,
+    /// Replace the directives of the log filter until the server restarts.
+    SetLogFilter { filter: String },
+    /// Stream the events of the log that match the directives, as [AdminTaskResponse::LogLine],
+    /// until the client disconnects.
+    Tail { filter: String },
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum AdminTaskResponse {
+    RecoverAccount { password: String },
+    LogLine(String),
+    Success,
+    Error(OperationError),
+}
+
+/// If the peer of the socket may submit tasks. The socket is also only accessible to its
+/// owner, but that depends on the directory it is created in.
+fn peer_is_authorised(stream: &UnixStream) -> bool {
+    // Safety: geteuid is always successful.
+    let our_uid = unsafe { libc::geteuid() };
+    match stream.peer_cred() {
+        Ok(cred) => {
+            let allowed = cred.uid() == 0 || cred.uid() == our_uid;
+            security_info!(
+                uid = %cred.uid(),
+                gid = %cred.gid(),
+                pid = ?cred.pid(),
+                %allowed,
+                "Admin socket client connected"
+            );
+            allowed
+        }
+        Err(e) => {
+            error!(
+                ?e,
+                "Unable to read the credentials of the admin socket client"
+            );
+            false
+        }
+    }
+}
+
+struct AdminTaskContext {
+    qe_r_ref: &'static QueryServerReadV1,
+    qe_w_ref: &'static QueryServerWriteV1,
+    online_backup: Option<OnlineBackup>,
+    tx: broadcast::Sender<CoreAction>,
+}
+
+impl AdminTaskContext {
+    async fn handle(&self, req: AdminTaskRequest) -> AdminTaskResponse {
+        let eventid = sketching::tracing_forest::id();
+        match req {
+            AdminTaskRequest::RecoverAccount { name } => {
+                match self
+                    .qe_w_ref
+                    .handle_admin_recover_account(name, eventid)
+                    .await
+                {
+                    Ok(password) => AdminTaskResponse::RecoverAccount { password },
+                    Err(e) => AdminTaskResponse::Error(e),
+                }
+            }
+            AdminTaskRequest::OnlineBackup => match &self.online_backup {
+                Some(cfg) => match self
+                    .qe_r_ref
+                    .handle_online_backup(OnlineBackupEvent::new(), &cfg.path, cfg.versions)
+                    .await
+                {
+                    Ok(()) => AdminTaskResponse::Success,
+                    Err(e) => AdminTaskResponse::Error(e),
+                },
+                None => {
+                    error!("Online backup requested, but online_backup is not configured");
+                    AdminTaskResponse::Error(OperationError::InvalidState)
+                }
+            },
+            AdminTaskRequest::ReloadTls => match self.tx.send(CoreAction::ReloadTls) {
+                Ok(_) => AdminTaskResponse::Success,
+                Err(_) => AdminTaskResponse::Error(OperationError::InvalidState),
+            },
+            AdminTaskRequest::SetLogFilter { filter } => {
+                match sketching::filter::set_log_filter(&filter) {
+                    Ok(()) => AdminTaskResponse::Success,
+                    Err(e) => {
+                        error!(?e, "Invalid log filter");
+                        AdminTaskResponse::Error(OperationError::InvalidRequestState)
+                    }
+                }
+            }
+            // This is streamed by the client process.
+            AdminTaskRequest::Tail { .. } => {
+                AdminTaskResponse::Error(OperationError::InvalidRequestState)
+            }
+        }
+    }
+}
+
+async fn send_response(
+    framed: &mut Framed<UnixStream, LinesCodec>,
+    resp: &AdminTaskResponse,
+) -> Result<(), ()> {
+    let line = serde_json::to_string(resp).map_err(|e| {
+        error!(?e, "Unable to encode admin task response");
+    })?;
+    framed.send(line).await.map_err(|_| ())
+}
+
+/// Stream the log to the client until it disconnects. The connection serves no other tasks.
+async fn admin_tail_process(framed: &mut Framed<UnixStream, LinesCodec>, filter: &str) {
+    let mut rx = match sketching::tail::subscribe(filter) {
+        Ok(rx) => rx,
+        Err(e) => {
+            error!(?e, "Invalid tail filter");
+            let resp = AdminTaskResponse::Error(OperationError::InvalidRequestState);
+            let _ = send_response(framed, &resp).await;
+            return;
+        }
+    };
+    if send_response(framed, &AdminTaskResponse::Success)
+        .await
+        .is_err()
+    {
+        return;
+    }
+
+    loop {
+        tokio::select! {
+            line = rx.recv() => match line {
+                Some(line) => {
+                    if send_response(framed, &AdminTaskResponse::LogLine(line))
+                        .await
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+                None => break,
+            },
+            // The client sends nothing more, so this is its disconnection.
+            _ = framed.next() => break,
+        }
+    }
+}
+
+async fn admin_client_process(stream: UnixStream, ctx: &AdminTaskContext) {
+    if !peer_is_authorised(&stream) {
+        security_info!("Admin socket client is not authorised, disconnecting");
+        return;
+    }
+
+    let mut framed = Framed::new(
+        stream,
+        LinesCodec::new_with_max_length(ADMIN_MAX_LINE_BYTES),
+    );
+
+    while let Some(Ok(line)) = framed.next().await {
+        let resp = match serde_json::from_str::<AdminTaskRequest>(&line) {
+            Ok(AdminTaskRequest::Tail { filter }) => {
+                admin_info!(%filter, "Admin tail requested");
+                admin_tail_process(&mut framed, &filter).await;
+                break;
+            }
+            Ok(req) => {
+                admin_info!(?req, "Admin task requested");
+                ctx.handle(req).await
+            }
+            Err(e) => {
+                error!(?e, "Invalid admin task request");
+                AdminTaskResponse::Error(OperationError::SerdeJsonError)
+            }
+        };
+
+        if send_response(&mut framed, &resp).await.is_err() {
+            break;
+        }
+    }
+}
+
+async fn admin_acceptor(
+    listener: UnixListener,
+    ctx: &'static AdminTaskContext,
+    mut rx: broadcast::Receiver<CoreAction>,
+) {
+    loop {
+        tokio::select! {
+            Ok(action) = rx.recv() => {
+                match action {
+                    CoreAction::Shutdown => break,
+                    CoreAction::ReloadTls => {}
+                }
+            }
+            accept_result = listener.accept() => {
+                match accept_result {
+                    Ok((stream, _addr)) => {
+                        tokio::spawn(admin_client_process(stream, ctx));
+                    }
+                    Err(e) => {
+                        error!("Admin socket acceptor error, continuing -> {:?}", e);
+                    }
+                }
+            }
+        }
+    }
+    info!("Stopped AdminSocketActor");
+}
+
+pub(crate) async fn create_admin_socket(
+    path: &str,
+    qe_r_ref: &'static QueryServerReadV1,
+    qe_w_ref: &'static QueryServerWriteV1,
+    online_backup: Option<OnlineBackup>,
+    tx: broadcast::Sender<CoreAction>,
+) -> Result<tokio::task::JoinHandle<()>, ()> {
+    // A socket left by a previous run would prevent us binding.
+    if Path::new(path).exists() {
+        fs::remove_file(path).map_err(|e| {
+            error!("Could not remove stale admin socket {} -> {:?}", path, e);
+        })?;
+    }
+
+    let listener = UnixListener::bind(path).map_err(|e| {
+        error!("Could not bind to admin socket {} -> {:?}", path, e);
+    })?;
+
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600)).map_err(|e| {
+        error!(
+            "Could not set the permissions of admin socket {} -> {:?}",
+            path, e
+        );
+    })?;
+
+    let rx = tx.subscribe();
+    let ctx = Box::new(AdminTaskContext {
+        qe_r_ref,
+        qe_w_ref,
+        online_backup,
+        tx,
+    });
+    let ctx: &'static AdminTaskContext = Box::leak(ctx);
+
+    info!("Started admin socket at {}", path);
+    Ok(tokio::spawn(admin_acceptor(listener, ctx, rx)))
+}
+
+async fn recv_response(
+    framed: &mut Framed<UnixStream, LinesCodec>,
+) -> Result<AdminTaskResponse, IoError> {
+    match framed.next().await {
+        Some(Ok(line)) => serde_json::from_str(&line)
+            .map_err(|e| IoError::new(ErrorKind::InvalidData, e.to_string())),
+        Some(Err(e)) => Err(IoError::new(ErrorKind::Other, e.to_string())),
+        // The server closes the connection if we aren't authorised.
+        None => Err(IoError::new(
+            ErrorKind::PermissionDenied,
+            "The server closed the admin socket",
+        )),
+    }
+}
+
+async fn send_request(
+    path: &str,
+    req: &AdminTaskRequest,
+) -> Result<Framed<UnixStream, LinesCodec>, IoError> {
+    let stream = UnixStream::connect(path).await?;
+    let mut framed = Framed::new(
+        stream,
+        // Log lines may be larger than requests.
+        LinesCodec::new_with_max_length(ADMIN_MAX_LINE_BYTES * 16),
+    );
+
+    let line = serde_json::to_string(req)
+        .map_err(|e| IoError::new(ErrorKind::InvalidData, e.to_string()))?;
+    framed
+        .send(line)
+        .await
+        .map_err(|e| IoError::new(ErrorKind::Other, e.to_string()))?;
+    Ok(framed)
+}
+
+/// Submit a task to the admin socket of a running server.
+pub async fn submit_admin_task(
+    path: &str,
+    req: AdminTaskRequest,
+) -> Result<AdminTaskResponse, IoError> {
+    let mut framed = send_request(path, &req).await?;
+    recv_response(&mut framed).await
+}
+
+/// Follow the log of a running server, calling `f` with each line that matches `filter`, until
+/// the server stops.
+pub async fn tail_admin_socket<F>(path: &str, filter: String, mut f: F) -> Result<(), IoError>
+where
+    F: FnMut(String),
+{
+    let mut framed = send_request(path, &AdminTaskRequest::Tail { filter }).await?;
+    match recv_response(&mut framed).await? {
+        AdminTaskResponse::Success => {}
+        resp => {
+            return Err(IoError::new(
+                ErrorKind::Other,
+                format!("Unable to start the tail -> {:?}", resp),
+            ))
+        }
+    }
+    loop {
+        match recv_response(&mut framed).await {
+            Ok(AdminTaskResponse::LogLine(line)) => f(line),
+            Ok(resp) => {
+                return Err(IoError::new(
+                    ErrorKind::InvalidData,
+                    format!("Unexpected admin task response -> {:?}", resp),
+                ))
+            }
+            // The server closed the socket as it stopped.
+            Err(e) if e.kind() == ErrorKind::PermissionDenied => return Ok(()),
+            Err(e) => return Err(e),
+        }
+    }
+}
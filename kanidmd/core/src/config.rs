@@ -5,9 +5,14 @@
 //! or domain entries that are able to be replicated.
 
 use std::fmt;
+use std::net::ToSocketAddrs;
+use std::path::Path;
 use std::str::FromStr;
 
 use kanidm_proto::messages::ConsoleOutputMode;
+use kanidmd_lib::constants::{CHANGELOG_MAX_AGE, MAXIMUM_REQUEST_SIZE_DEFAULT};
+use kanidmd_lib::prelude::Url;
+use kanidmd_lib::repl::join::ReplJoinToken;
 use rand::prelude::*;
 use serde::{Deserialize, Serialize};
 
@@ -17,7 +22,7 @@ pub struct IntegrationTestConfig {
     pub admin_password: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct OnlineBackup {
     pub path: String,
     #[serde(default = "default_online_backup_schedule")]
@@ -34,7 +39,97 @@ fn default_online_backup_versions() -> usize {
     7
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReplicationConsumer {
+    /// The origin of the server to consume changes from. Use repl://host:port to connect
+    /// to the supplier's replication listener with mutual TLS, or https:// to use its api.
+    pub supplier: String,
+    /// An api token of a service account that is a member of system_admins on the supplier.
+    /// Only used when the supplier is reached over https.
+    pub token: Option<String>,
+    /// The CA that issued the supplier's certificate, if it is not trusted by the system.
+    pub supplier_ca: Option<String>,
+    #[serde(default = "default_replication_interval")]
+    pub interval: u64,
+    /// If this server is not a member of the supplier's domain, replace all of its content
+    /// with a full copy from the supplier. This should only be enabled on a new replica.
+    #[serde(default)]
+    pub refresh: bool,
+}
+
+fn default_replication_interval() -> u64 {
+    60
+}
+
+/// Rate limits of the http api. Requests are limited per source address, and per session
+/// for authenticated requests. Each is a bucket that holds `burst` requests, and is refilled
+/// at `per_second` requests each second. IPv6 sources share a bucket per network of
+/// `source_ipv6_prefix` bits, as a client is usually assigned a whole /64.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RateLimit {
+    #[serde(default = "default_rate_limit_source_per_second")]
+    pub source_per_second: u32,
+    #[serde(default = "default_rate_limit_source_burst")]
+    pub source_burst: u32,
+    #[serde(default = "default_rate_limit_session_per_second")]
+    pub session_per_second: u32,
+    #[serde(default = "default_rate_limit_session_burst")]
+    pub session_burst: u32,
+    #[serde(default = "default_rate_limit_source_ipv6_prefix")]
+    pub source_ipv6_prefix: u8,
+}
+
+fn default_rate_limit_source_per_second() -> u32 {
+    20
+}
+
+fn default_rate_limit_source_burst() -> u32 {
+    100
+}
+
+fn default_rate_limit_session_per_second() -> u32 {
+    10
+}
+
+fn default_rate_limit_session_burst() -> u32 {
+    50
+}
+
+fn default_rate_limit_source_ipv6_prefix() -> u8 {
+    64
+}
+
+/// The cross origin resource sharing policy of the http api, allowing browser applications
+/// on other origins to call it.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Cors {
+    /// The origins that may make requests, such as `https://app.example.com`.
+    pub allowed_origins: Vec<String>,
+    #[serde(default = "default_cors_allowed_headers")]
+    pub allowed_headers: Vec<String>,
+    /// Allow requests to include cookies. This can't be used if any origin is allowed.
+    #[serde(default)]
+    pub allow_credentials: bool,
+}
+
+fn default_cors_allowed_headers() -> Vec<String> {
+    vec!["Authorization".to_string(), "Content-Type".to_string()]
+}
+
+impl ReplicationConsumer {
+    /// Consume changes from the supplier of a join token once this server has joined it.
+    pub fn from_join_token(token: &ReplJoinToken) -> Self {
+        ReplicationConsumer {
+            supplier: token.supplier.clone(),
+            token: None,
+            supplier_ca: None,
+            interval: default_replication_interval(),
+            refresh: false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TlsConfiguration {
     pub chain: String,
     pub key: String,
@@ -80,11 +175,15 @@ impl FromStr for ServerRole {
 pub struct Configuration {
     pub address: String,
     pub ldapaddress: Option<String>,
+    pub ldapstarttlsaddress: Option<String>,
+    pub repladdress: Option<String>,
+    pub adminbindpath: Option<String>,
     pub threads: usize,
     // db type later
     pub db_path: String,
     pub db_fs_type: Option<String>,
     pub db_arc_size: Option<usize>,
+    pub db_seal_key_path: Option<String>,
     pub maximum_request: usize,
     pub secure_cookies: bool,
     pub trust_x_forward_for: bool,
@@ -92,6 +191,13 @@ pub struct Configuration {
     pub cookie_key: [u8; 32],
     pub integration_test_config: Option<Box<IntegrationTestConfig>>,
     pub online_backup: Option<OnlineBackup>,
+    pub replication_consumer: Option<ReplicationConsumer>,
+    pub repl_join_token: Option<String>,
+    pub changelog_retention: u64,
+    pub audit_log_retention: Option<u64>,
+    pub rate_limit: Option<RateLimit>,
+    pub cors: Option<Cors>,
+    pub maximum_request_size_bytes: usize,
     pub domain: String,
     pub origin: String,
     pub role: ServerRole,
@@ -106,12 +212,25 @@ impl fmt::Display for Configuration {
                 Some(la) => write!(f, "ldap address: {}, ", la),
                 None => write!(f, "ldap address: disabled, "),
             })
+            .and_then(|_| match &self.ldapstarttlsaddress {
+                Some(la) => write!(f, "ldap starttls address: {}, ", la),
+                None => write!(f, "ldap starttls address: disabled, "),
+            })
+            .and_then(|_| match &self.repladdress {
+                Some(ra) => write!(f, "replication address: {}, ", ra),
+                None => write!(f, "replication address: disabled, "),
+            })
+            .and_then(|_| match &self.adminbindpath {
+                Some(ap) => write!(f, "admin socket: {}, ", ap),
+                None => write!(f, "admin socket: disabled, "),
+            })
             .and_then(|_| write!(f, "thread count: {}, ", self.threads))
             .and_then(|_| write!(f, "dbpath: {}, ", self.db_path))
             .and_then(|_| match self.db_arc_size {
                 Some(v) => write!(f, "arcsize: {}, ", v),
                 None => write!(f, "arcsize: AUTO, "),
             })
+            .and_then(|_| write!(f, "sealed secrets: {}, ", self.db_seal_key_path.is_some()))
             .and_then(|_| write!(f, "max request size: {}b, ", self.maximum_request))
             .and_then(|_| write!(f, "secure cookies: {}, ", self.secure_cookies))
             .and_then(|_| write!(f, "trust X-Forwarded-For: {}, ", self.trust_x_forward_for))
@@ -121,6 +240,34 @@ impl fmt::Display for Configuration {
                 Some(_) => write!(f, "online_backup: enabled, "),
                 None => write!(f, "online_backup: disabled, "),
             })
+            .and_then(|_| match &self.replication_consumer {
+                Some(rc) => write!(f, "replication supplier: {}, ", rc.supplier),
+                None => write!(f, "replication consumer: disabled, "),
+            })
+            .and_then(|_| write!(f, "changelog retention: {}s, ", self.changelog_retention))
+            .and_then(|_| match &self.audit_log_retention {
+                Some(r) => write!(f, "audit log retention: {}s, ", r),
+                None => write!(f, "audit log: disabled, "),
+            })
+            .and_then(|_| match &self.rate_limit {
+                Some(rl) => write!(
+                    f,
+                    "rate limit: {}/s per source, {}/s per session, ",
+                    rl.source_per_second, rl.session_per_second
+                ),
+                None => write!(f, "rate limit: disabled, "),
+            })
+            .and_then(|_| match &self.cors {
+                Some(cors) => write!(f, "cors origins: {:?}, ", cors.allowed_origins),
+                None => write!(f, "cors: disabled, "),
+            })
+            .and_then(|_| {
+                write!(
+                    f,
+                    "maximum request size: {} bytes, ",
+                    self.maximum_request_size_bytes
+                )
+            })
             .and_then(|_| write!(f, "role: {}, ", self.role.to_string()))
             .and_then(|_| {
                 write!(
@@ -138,6 +285,9 @@ impl Configuration {
         let mut c = Configuration {
             address: String::from("127.0.0.1:8080"),
             ldapaddress: None,
+            ldapstarttlsaddress: None,
+            repladdress: None,
+            adminbindpath: None,
             threads: std::thread::available_parallelism()
                 .map(|t| t.get())
                 .unwrap_or_else(|_e| {
@@ -147,6 +297,7 @@ impl Configuration {
             db_path: String::from(""),
             db_fs_type: None,
             db_arc_size: None,
+            db_seal_key_path: None,
             maximum_request: 256 * 1024, // 256k
             // log path?
             // default true in prd
@@ -156,6 +307,13 @@ impl Configuration {
             cookie_key: [0; 32],
             integration_test_config: None,
             online_backup: None,
+            replication_consumer: None,
+            repl_join_token: None,
+            changelog_retention: CHANGELOG_MAX_AGE,
+            audit_log_retention: None,
+            rate_limit: None,
+            cors: None,
+            maximum_request_size_bytes: MAXIMUM_REQUEST_SIZE_DEFAULT,
             domain: "idm.example.com".to_string(),
             origin: "https://idm.example.com".to_string(),
             role: ServerRole::WriteReplica,
@@ -182,10 +340,44 @@ impl Configuration {
         }
     }
 
+    pub fn update_replication_consumer(&mut self, cfg: &Option<ReplicationConsumer>) {
+        self.replication_consumer = cfg.clone();
+    }
+
+    pub fn update_repl_join_token(&mut self, t: &Option<String>) {
+        self.repl_join_token = t.clone();
+    }
+
+    pub fn update_rate_limit(&mut self, cfg: &Option<RateLimit>) {
+        self.rate_limit = cfg.clone();
+    }
+
+    pub fn update_cors(&mut self, cfg: &Option<Cors>) {
+        self.cors = cfg.clone();
+    }
+
+    pub fn update_maximum_request_size(&mut self, v: Option<usize>) {
+        self.maximum_request_size_bytes = v.unwrap_or(MAXIMUM_REQUEST_SIZE_DEFAULT);
+    }
+
+    pub fn update_changelog_retention(&mut self, t: Option<u64>) {
+        self.changelog_retention = t.unwrap_or(CHANGELOG_MAX_AGE);
+    }
+
+    pub fn update_audit_log_retention(&mut self, t: Option<u64>) {
+        self.audit_log_retention = t;
+    }
+
     pub fn update_trust_x_forward_for(&mut self, t: Option<bool>) {
         self.trust_x_forward_for = t.unwrap_or(false);
     }
 
+    pub fn update_threads(&mut self, t: Option<usize>) {
+        if let Some(t) = t {
+            self.threads = t;
+        }
+    }
+
     pub fn update_db_path(&mut self, p: &str) {
         self.db_path = p.to_string();
     }
@@ -194,6 +386,10 @@ impl Configuration {
         self.db_arc_size = v
     }
 
+    pub fn update_db_seal_key_path(&mut self, p: &Option<String>) {
+        self.db_seal_key_path = p.clone();
+    }
+
     pub fn update_db_fs_type(&mut self, p: &Option<String>) {
         self.db_fs_type = p.as_ref().map(|v| v.to_lowercase());
     }
@@ -209,6 +405,18 @@ impl Configuration {
         self.ldapaddress = l.clone();
     }
 
+    pub fn update_ldapstarttlsbind(&mut self, l: &Option<String>) {
+        self.ldapstarttlsaddress = l.clone();
+    }
+
+    pub fn update_replbind(&mut self, r: &Option<String>) {
+        self.repladdress = r.clone();
+    }
+
+    pub fn update_adminbind(&mut self, p: &Option<String>) {
+        self.adminbindpath = p.clone();
+    }
+
     pub fn update_origin(&mut self, o: &str) {
         self.origin = o.to_string();
     }
@@ -240,4 +448,79 @@ impl Configuration {
             }
         }
     }
+
+    /// Check the configuration before any listener starts, so that every error is reported
+    /// together rather than by the first component that fails on it.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+
+        let addresses = [
+            ("bindaddress", Some(&self.address)),
+            ("ldapbindaddress", self.ldapaddress.as_ref()),
+            ("ldapstarttlsbindaddress", self.ldapstarttlsaddress.as_ref()),
+            ("replbindaddress", self.repladdress.as_ref()),
+        ];
+        for (name, address) in addresses {
+            if let Some(address) = address {
+                if address.to_socket_addrs().is_err() {
+                    errors.push(format!(
+                        "{} \"{}\" is not an address and port",
+                        name, address
+                    ));
+                }
+            }
+        }
+
+        if (self.ldapstarttlsaddress.is_some() || self.repladdress.is_some())
+            && self.tls_config.is_none()
+        {
+            errors.push(
+                "ldapstarttlsbindaddress and replbindaddress require tls_chain and tls_key"
+                    .to_string(),
+            );
+        }
+        if let Some(tls) = &self.tls_config {
+            for (name, path) in [("tls_chain", &tls.chain), ("tls_key", &tls.key)] {
+                if !Path::new(path).is_file() {
+                    errors.push(format!("{} \"{}\" is not a file", name, path));
+                }
+            }
+        }
+
+        // The origin must be in the domain, as the domain is the webauthn relying party.
+        match Url::parse(&self.origin) {
+            Ok(url) => {
+                let in_domain = url
+                    .domain()
+                    .map(|d| d == self.domain || d.ends_with(&format!(".{}", self.domain)))
+                    .unwrap_or(false);
+                if !in_domain {
+                    errors.push(format!(
+                        "origin \"{}\" is not in the domain \"{}\"",
+                        self.origin, self.domain
+                    ));
+                }
+            }
+            Err(e) => errors.push(format!("origin \"{}\" is not a url - {}", self.origin, e)),
+        }
+
+        if self.threads == 0 {
+            errors.push("threads must be at least 1".to_string());
+        }
+        if self.maximum_request_size_bytes == 0 {
+            errors.push("maximum_request_size_bytes must be at least 1".to_string());
+        }
+        if self.changelog_retention == 0 {
+            errors.push("changelog_retention must be at least 1 second".to_string());
+        }
+        if self.audit_log_retention == Some(0) {
+            errors.push("audit_log_retention must be at least 1 second".to_string());
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
 }
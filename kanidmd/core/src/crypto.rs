@@ -2,21 +2,55 @@
 //! and ciphers we accept.
 
 use openssl::error::ErrorStack;
-use openssl::ssl::{SslAcceptor, SslAcceptorBuilder, SslFiletype, SslMethod};
+use openssl::ssl::{
+    SslAcceptor, SslAcceptorBuilder, SslConnector, SslFiletype, SslMethod, SslVerifyMode,
+};
 
-use crate::config::Configuration;
+use crate::config::{Configuration, TlsConfiguration};
 
 /// From the server configuration, generate an OpenSSL acceptor that we can use
 /// to build our sockets for https/ldaps.
 pub fn setup_tls(config: &Configuration) -> Result<Option<SslAcceptorBuilder>, ErrorStack> {
-    match &config.tls_config {
-        Some(tls_config) => {
-            let mut ssl_builder = SslAcceptor::mozilla_modern(SslMethod::tls())?;
-            ssl_builder.set_certificate_chain_file(&tls_config.chain)?;
-            ssl_builder.set_private_key_file(&tls_config.key, SslFiletype::PEM)?;
-            ssl_builder.check_private_key()?;
-            Ok(Some(ssl_builder))
-        }
-        None => Ok(None),
-    }
+    config
+        .tls_config
+        .as_ref()
+        .map(tls_acceptor_builder)
+        .transpose()
+}
+
+/// Load the certificate chain and key, and check that they match.
+pub fn tls_acceptor_builder(
+    tls_config: &TlsConfiguration,
+) -> Result<SslAcceptorBuilder, ErrorStack> {
+    let mut ssl_builder = SslAcceptor::mozilla_modern(SslMethod::tls())?;
+    ssl_builder.set_certificate_chain_file(&tls_config.chain)?;
+    ssl_builder.set_private_key_file(&tls_config.key, SslFiletype::PEM)?;
+    ssl_builder.check_private_key()?;
+    Ok(ssl_builder)
+}
+
+/// Generate an acceptor for the replication listener. Partners must present a client
+/// certificate, but since partners are authenticated by the certificate pinned on their
+/// replication agreement, the chain is not verified here.
+pub fn setup_repl_tls(config: &Configuration) -> Result<Option<SslAcceptorBuilder>, ErrorStack> {
+    setup_tls(config).map(|opt_ssl_builder| {
+        opt_ssl_builder.map(|mut ssl_builder| {
+            ssl_builder.set_verify_callback(
+                SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT,
+                |_preverify_ok, _x509_ctx| true,
+            );
+            ssl_builder
+        })
+    })
+}
+
+/// Generate a connector for replication consumers, which presents the server's own
+/// certificate to the supplier. The supplier is authenticated by its pinned certificate.
+pub fn setup_repl_connector(tls_config: &TlsConfiguration) -> Result<SslConnector, ErrorStack> {
+    let mut ssl_builder = SslConnector::builder(SslMethod::tls_client())?;
+    ssl_builder.set_certificate_chain_file(&tls_config.chain)?;
+    ssl_builder.set_private_key_file(&tls_config.key, SslFiletype::PEM)?;
+    ssl_builder.check_private_key()?;
+    ssl_builder.set_verify_callback(SslVerifyMode::PEER, |_preverify_ok, _x509_ctx| true);
+    Ok(ssl_builder.build())
 }
@@ -1,7 +1,19 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use futures_util::io::AsyncReadExt;
+use kanidmd_lib::prelude::*;
+use kanidmd_lib::security_event::SecurityEvent;
 use regex::Regex;
+use tide_compress::CompressMiddleware;
+use tracing::error;
 
 ///! Custom tide middleware for Kanidm
-use crate::https::JavaScriptFile;
+use crate::config::{Cors, RateLimit};
+use crate::https::{AppState, JavaScriptFile, RequestExtensions};
 
 /// This is for the tide_compression middleware so that we only compress certain content types.
 ///
@@ -36,6 +48,62 @@ pub fn compression_content_type_checker() -> Regex {
     .expect("regex matcher for tide_compress content-type check failed to compile")
 }
 
+/// Path segments of api endpoints whose responses contain secrets, such as tokens, which must
+/// not be compressed.
+const COMPRESS_EXCLUDED_SEGMENTS: &[&str] = &[
+    "auth",
+    "oauth2",
+    "_api_token",
+    "_backup_code",
+    "_basic_secret",
+    "_credential",
+    "_radius",
+    "_uat",
+];
+
+/// Compresses the responses of api requests that read entries, such as searches of large
+/// groups.
+///
+/// Compressing a response that has both a secret and content chosen by an attacker allows the
+/// secret to be inferred by the TLS BREACH attack. This isn't a concern for the api, as it is
+/// authenticated with a bearer token that a browser won't send to it on behalf of another
+/// site, but the responses of endpoints that return secrets are never compressed regardless.
+pub struct ApiCompressMiddleware {
+    compress: CompressMiddleware,
+}
+
+impl Default for ApiCompressMiddleware {
+    fn default() -> Self {
+        ApiCompressMiddleware {
+            compress: CompressMiddleware::builder()
+                .threshold(1024)
+                .content_type_check(Some(compression_content_type_checker()))
+                .build(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<State: Clone + Send + Sync + 'static> tide::Middleware<State> for ApiCompressMiddleware {
+    async fn handle(
+        &self,
+        request: tide::Request<State>,
+        next: tide::Next<'_, State>,
+    ) -> tide::Result {
+        let path = request.url().path();
+        let is_read = request.method() == tide::http::Method::Get || path == "/v1/raw/search";
+        let has_secret = path
+            .split('/')
+            .any(|segment| COMPRESS_EXCLUDED_SEGMENTS.contains(&segment));
+
+        if is_read && !has_secret {
+            tide::Middleware::handle(&self.compress, request, next).await
+        } else {
+            Ok(next.run(request).await)
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct CacheableMiddleware;
 
@@ -212,6 +280,271 @@ impl<State: Clone + Send + Sync + 'static> tide::Middleware<State>
     }
 }
 
+/// How often buckets that have refilled are discarded.
+const RATE_LIMIT_PRUNE_INTERVAL: Duration = Duration::from_secs(10);
+
+struct RateBucket {
+    tokens: f64,
+    last: Instant,
+}
+
+impl RateBucket {
+    /// Refill the bucket for the time since it was last used, and take a request from it.
+    fn take(&mut self, now: Instant, per_second: u32, burst: u32) -> bool {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * f64::from(per_second)).min(f64::from(burst));
+        self.last = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn is_full(&self, now: Instant, per_second: u32, burst: u32) -> bool {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.tokens + elapsed * f64::from(per_second) >= f64::from(burst)
+    }
+}
+
+struct RateBuckets<K> {
+    per_second: u32,
+    burst: u32,
+    buckets: Mutex<HashMap<K, RateBucket>>,
+}
+
+impl<K: Hash + Eq> RateBuckets<K> {
+    fn new(per_second: u32, burst: u32) -> Self {
+        RateBuckets {
+            per_second,
+            burst,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<K, RateBucket>> {
+        match self.buckets.lock() {
+            Ok(buckets) => buckets,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    fn take(&self, key: K, now: Instant) -> bool {
+        self.lock()
+            .entry(key)
+            .or_insert_with(|| RateBucket {
+                tokens: f64::from(self.burst),
+                last: now,
+            })
+            .take(now, self.per_second, self.burst)
+    }
+
+    /// Buckets that have refilled are the same as new buckets, so they can be discarded.
+    fn prune(&self, now: Instant) {
+        self.lock()
+            .retain(|_, b| !b.is_full(now, self.per_second, self.burst));
+    }
+}
+
+/// The rate limit bucket of a source address. IPv6 addresses are reduced to their network, so
+/// that a client can't escape the limit by rotating through the addresses of its prefix.
+fn rate_limit_source(addr: IpAddr, ipv6_prefix: u8) -> String {
+    match addr {
+        IpAddr::V4(v4) => v4.to_string(),
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => v4.to_string(),
+            None => {
+                let prefix = u32::from(ipv6_prefix.min(128));
+                let mask = u128::MAX.checked_shl(128 - prefix).unwrap_or(0);
+                format!("{}/{}", Ipv6Addr::from(u128::from(v6) & mask), prefix)
+            }
+        },
+    }
+}
+
+/// Limits the rate of api requests from each source address, and from each session. Requests
+/// over the limit are refused with 429 Too Many Requests.
+pub struct RateLimitMiddleware {
+    trust_x_forward_for: bool,
+    source_ipv6_prefix: u8,
+    sources: Arc<RateBuckets<String>>,
+    // Sessions are keyed by a digest of their token, so that tokens are not held here.
+    sessions: Arc<RateBuckets<[u8; 32]>>,
+}
+
+impl RateLimitMiddleware {
+    pub fn new(limit: RateLimit, trust_x_forward_for: bool) -> Self {
+        RateLimitMiddleware {
+            trust_x_forward_for,
+            source_ipv6_prefix: limit.source_ipv6_prefix,
+            sources: Arc::new(RateBuckets::new(
+                limit.source_per_second,
+                limit.source_burst,
+            )),
+            sessions: Arc::new(RateBuckets::new(
+                limit.session_per_second,
+                limit.session_burst,
+            )),
+        }
+    }
+
+    /// Start a task that periodically discards refilled buckets, so that requests never need
+    /// to scan the buckets. The task stops once the middleware is dropped.
+    pub fn start_prune(&self) -> tokio::task::JoinHandle<()> {
+        let sources = Arc::downgrade(&self.sources);
+        let sessions = Arc::downgrade(&self.sessions);
+        tokio::spawn(async move {
+            let mut inter = tokio::time::interval(RATE_LIMIT_PRUNE_INTERVAL);
+            loop {
+                inter.tick().await;
+                let (sources, sessions) = match (sources.upgrade(), sessions.upgrade()) {
+                    (Some(sources), Some(sessions)) => (sources, sessions),
+                    _ => break,
+                };
+                let now = Instant::now();
+                sources.prune(now);
+                sessions.prune(now);
+            }
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl tide::Middleware<AppState> for RateLimitMiddleware {
+    async fn handle(
+        &self,
+        request: tide::Request<AppState>,
+        next: tide::Next<'_, AppState>,
+    ) -> tide::Result {
+        let now = Instant::now();
+
+        // The peer address includes the port, which must not be part of the source.
+        let source = if self.trust_x_forward_for {
+            request.remote()
+        } else {
+            request.peer_addr()
+        }
+        .map(|addr| {
+            addr.parse::<SocketAddr>()
+                .map(|sa| sa.ip())
+                .or_else(|_| addr.parse::<IpAddr>())
+                .map(|ip| rate_limit_source(ip, self.source_ipv6_prefix))
+                .unwrap_or_else(|_| addr.to_string())
+        })
+        .unwrap_or_else(|| "-".to_string());
+
+        let source_allowed = self.sources.take(source.clone(), now);
+
+        let session_allowed = source_allowed
+            && request
+                .get_current_uat()
+                .map(|uat| {
+                    self.sessions
+                        .take(openssl::sha::sha256(uat.as_bytes()), now)
+                })
+                .unwrap_or(true);
+
+        if session_allowed {
+            Ok(next.run(request).await)
+        } else {
+            let limit = if source_allowed { "session" } else { "source" };
+            security_info!(
+                remote_addr = %source,
+                path = %request.url().path(),
+                %limit,
+                "Rate limit exceeded, refusing request"
+            );
+            SecurityEvent::RateLimited {
+                source: source.clone(),
+                path: request.url().path().to_string(),
+                limit,
+            }
+            .emit();
+            let mut response = tide::Response::new(tide::StatusCode::TooManyRequests);
+            response.insert_header("Retry-After", "1");
+            Ok(response)
+        }
+    }
+}
+
+/// Refuses api requests with a body larger than the limit with 413 Payload Too Large, before
+/// the body is deserialised. Bodies without a content length are read up to the limit.
+pub struct RequestSizeLimitMiddleware {
+    max_bytes: usize,
+}
+
+impl RequestSizeLimitMiddleware {
+    pub fn new(max_bytes: usize) -> Self {
+        RequestSizeLimitMiddleware { max_bytes }
+    }
+
+    fn refuse(&self, request: &tide::Request<AppState>, size: usize) -> tide::Response {
+        request_warn!(
+            path = %request.url().path(),
+            %size,
+            max_bytes = %self.max_bytes,
+            "Request body too large, refusing request"
+        );
+        tide::Response::new(tide::StatusCode::PayloadTooLarge)
+    }
+}
+
+#[async_trait::async_trait]
+impl tide::Middleware<AppState> for RequestSizeLimitMiddleware {
+    async fn handle(
+        &self,
+        mut request: tide::Request<AppState>,
+        next: tide::Next<'_, AppState>,
+    ) -> tide::Result {
+        match request.len() {
+            Some(len) if len > self.max_bytes => return Ok(self.refuse(&request, len)),
+            Some(_) => {}
+            None => {
+                // Read one byte past the limit to know if the body exceeds it.
+                let mut body = Vec::new();
+                request
+                    .take_body()
+                    .take(self.max_bytes as u64 + 1)
+                    .read_to_end(&mut body)
+                    .await?;
+                if body.len() > self.max_bytes {
+                    return Ok(self.refuse(&request, body.len()));
+                }
+                request.set_body(body);
+            }
+        }
+        Ok(next.run(request).await)
+    }
+}
+
+/// Build the cross origin resource sharing middleware from the configured policy.
+pub fn cors_middleware(cors: &Cors) -> Result<tide::security::CorsMiddleware, ()> {
+    if cors.allow_credentials && cors.allowed_origins.iter().any(|o| o == "*") {
+        error!("CORS credentials can't be allowed for any origin, list the allowed origins");
+        return Err(());
+    }
+
+    let allow_methods = "GET, POST, PUT, PATCH, DELETE, OPTIONS"
+        .parse::<tide::http::headers::HeaderValue>()
+        .map_err(|e| {
+            error!(?e, "Invalid CORS allowed methods");
+        })?;
+    let allow_headers = cors
+        .allowed_headers
+        .join(", ")
+        .parse::<tide::http::headers::HeaderValue>()
+        .map_err(|e| {
+            error!(?e, "Invalid CORS allowed headers");
+        })?;
+
+    Ok(tide::security::CorsMiddleware::new()
+        .allow_methods(allow_methods)
+        .allow_headers(allow_headers)
+        .allow_origin(tide::security::Origin::from(cors.allowed_origins.clone()))
+        .allow_credentials(cors.allow_credentials))
+}
+
 const KANIDM_VERSION: &'static str = env!("CARGO_PKG_VERSION");
 
 #[derive(Default)]
@@ -229,3 +562,21 @@ impl<State: Clone + Send + Sync + 'static> tide::Middleware<State> for VersionHe
         Ok(response)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::rate_limit_source;
+
+    #[test]
+    fn test_rate_limit_source() {
+        let source = |addr: &str, prefix| rate_limit_source(addr.parse().unwrap(), prefix);
+        assert_eq!(source("192.0.2.1", 64), "192.0.2.1");
+        assert_eq!(source("::ffff:192.0.2.1", 64), "192.0.2.1");
+        // Addresses of the same network share a bucket.
+        assert_eq!(source("2001:db8:1:2:3:4:5:6", 64), "2001:db8:1:2::/64");
+        assert_eq!(source("2001:db8:1:2:ffff::1", 64), "2001:db8:1:2::/64");
+        assert_eq!(source("2001:db8:1:2:3:4:5:6", 48), "2001:db8:1::/48");
+        assert_eq!(source("2001:db8::1", 128), "2001:db8::1/128");
+        assert_eq!(source("2001:db8::1", 0), "::/0");
+    }
+}
@@ -1,15 +1,18 @@
 mod manifest;
 pub mod middleware;
 mod oauth2;
+mod openapi;
 mod routemaps;
 mod v1;
 mod v1_scim;
+mod v2_scim;
 
 use std::fs::canonicalize;
 use std::path::PathBuf;
 use std::str::FromStr;
 
 use compact_jwt::{Jws, JwsSigner, JwsUnverified, JwsValidator};
+use kanidm_proto::v1::ErrorResponse;
 use kanidmd_lib::prelude::*;
 use kanidmd_lib::status::StatusActor;
 use serde::Serialize;
@@ -24,9 +27,10 @@ use self::oauth2::*;
 use self::routemaps::{RouteMap, RouteMaps};
 use self::v1::*;
 use self::v1_scim::*;
+use self::v2_scim::scim_v2_route_setup;
 use crate::actors::v1_read::QueryServerReadV1;
 use crate::actors::v1_write::QueryServerWriteV1;
-use crate::config::{ServerRole, TlsConfiguration};
+use crate::config::{Cors, RateLimit, ServerRole, TlsConfiguration};
 
 use crate::CoreAction;
 use tokio::sync::broadcast;
@@ -83,6 +87,12 @@ pub trait RequestExtensions {
 
     fn get_url_param_uuid(&self, param: &str) -> Result<Uuid, tide::Error>;
 
+    /// The etags of the If-Match header, if the request has one.
+    fn get_if_match(&self) -> Option<Vec<String>>;
+
+    /// If the client asked for LDIF, by the Accept header or with `?format=ldif`.
+    fn wants_ldif(&self) -> bool;
+
     fn new_eventid(&self) -> (Uuid, String);
 }
 
@@ -163,6 +173,33 @@ impl RequestExtensions for tide::Request<AppState> {
             })
     }
 
+    fn get_if_match(&self) -> Option<Vec<String>> {
+        self.header("If-Match").map(|hvs| {
+            hvs.iter()
+                .flat_map(|hv| hv.as_str().split(','))
+                .map(|etag| {
+                    let etag = etag.trim();
+                    // An etag is the same for every representation of an entry, so the weak
+                    // form that compression can give it is as good as the strong one.
+                    etag.strip_prefix("W/").unwrap_or(etag).to_string()
+                })
+                .collect()
+        })
+    }
+
+    fn wants_ldif(&self) -> bool {
+        let accept = self.header(tide::http::headers::ACCEPT).map(|hvs| {
+            hvs.iter()
+                .flat_map(|hv| hv.as_str().split(','))
+                .any(|media| media.split(';').next().map(str::trim) == Some(LDIF_CONTENT_TYPE))
+        });
+        matches!(accept, Some(true))
+            || self
+                .url()
+                .query_pairs()
+                .any(|(k, v)| k == "format" && v == "ldif")
+    }
+
     fn new_eventid(&self) -> (Uuid, String) {
         let eventid = sketching::tracing_forest::id();
         let hv = eventid.as_hyphenated().to_string();
@@ -170,6 +207,29 @@ impl RequestExtensions for tide::Request<AppState> {
     }
 }
 
+pub const LDIF_CONTENT_TYPE: &str = "text/ldif";
+
+/// As [to_tide_response], with the body as an LDIF document, and the token of the next page
+/// in a header if there is one.
+pub fn to_ldif_response(
+    v: Result<(String, Option<String>), OperationError>,
+    hvalue: String,
+) -> tide::Result {
+    match v {
+        Ok((ldif, next_page_token)) => {
+            let mut res = tide::Response::new(200);
+            res.set_body(ldif);
+            res.set_content_type(LDIF_CONTENT_TYPE);
+            if let Some(token) = next_page_token {
+                res.insert_header("X-KANIDM-NEXT-PAGE-TOKEN", token);
+            }
+            res.insert_header("X-KANIDM-OPID", hvalue);
+            Ok(res)
+        }
+        Err(e) => to_tide_response::<()>(Err(e), hvalue),
+    }
+}
+
 pub fn to_tide_response<T: Serialize>(
     v: Result<T, OperationError>,
     hvalue: String,
@@ -190,12 +250,17 @@ pub fn to_tide_response<T: Serialize>(
                     res.insert_header("WWW-Authenticate", "Bearer");
                     res
                 }
-                OperationError::SystemProtectedObject | OperationError::AccessDenied => {
+                OperationError::SystemProtectedObject
+                | OperationError::AccessDenied
+                | OperationError::ReplReadOnlyReplica => {
                     tide::Response::new(tide::StatusCode::Forbidden)
                 }
                 OperationError::NoMatchingEntries => {
                     tide::Response::new(tide::StatusCode::NotFound)
                 }
+                OperationError::EntryChanged => {
+                    tide::Response::new(tide::StatusCode::PreconditionFailed)
+                }
                 OperationError::PasswordQuality(_)
                 | OperationError::EmptyRequest
                 | OperationError::SchemaViolation(_) => {
@@ -203,7 +268,7 @@ pub fn to_tide_response<T: Serialize>(
                 }
                 _ => tide::Response::new(tide::StatusCode::InternalServerError),
             };
-            tide::Body::from_json(&e).map(|b| {
+            tide::Body::from_json(&ErrorResponse::new(e, hvalue.clone())).map(|b| {
                 res.set_body(b);
                 res
             })
@@ -317,7 +382,6 @@ pub fn generate_integrity_hash(filename: String) -> Result<String, String> {
     }
 }
 
-// TODO: Add request limits.
 pub fn create_https_server(
     address: String,
     domain: String,
@@ -325,6 +389,9 @@ pub fn create_https_server(
     opt_tls_params: Option<&TlsConfiguration>,
     role: ServerRole,
     trust_x_forward_for: bool,
+    rate_limit: Option<RateLimit>,
+    cors: Option<&Cors>,
+    maximum_request_size_bytes: usize,
     cookie_key: &[u8; 32],
     jws_signer: JwsSigner,
     status_ref: &'static StatusActor,
@@ -398,6 +465,12 @@ pub fn create_https_server(
             .with_same_site_policy(tide::http::cookies::SameSite::Strict),
     );
 
+    // Cross origin requests, which must be handled before routing so that preflight
+    // requests are answered.
+    if let Some(cors) = cors {
+        tserver.with(cors_middleware(cors)?);
+    }
+
     // Strict responses.
     tserver.with(StrictResponseMiddleware::default());
 
@@ -428,8 +501,8 @@ pub fn create_https_server(
         This is not a concern for the paths covered by this configuration
         ( /, /ui/<and all sub-paths>, /pkg/<and all sub-paths> ),
         as they're all static content with no secrets in transit - all that data should
-        come from Kanidm's REST API, which is on a different path and not covered by
-        the compression middleware.
+        come from Kanidm's REST API, which is on a different path and is covered by
+        ApiCompressMiddleware instead.
 
 
         [1] - https://resources.infosecinstitute.com/topic/the-breach-attack/
@@ -507,9 +580,17 @@ pub fn create_https_server(
 
     // ==== These routes can not be cached
     let mut appserver = tserver.at("");
+    if let Some(rate_limit) = rate_limit {
+        let rate_limit = RateLimitMiddleware::new(rate_limit, trust_x_forward_for);
+        rate_limit.start_prune();
+        appserver.with(rate_limit);
+    }
+    appserver.with(RequestSizeLimitMiddleware::new(maximum_request_size_bytes));
     // Add our version injector, we only add this to apis.
     appserver.with(VersionHeaderMiddleware::default());
     appserver.with(NoCacheMiddleware::default());
+    // The compression middleware needs to be the last one added before routes
+    appserver.with(ApiCompressMiddleware::default());
 
     // let mut well_known = appserver.at("/.well-known");
 
@@ -517,17 +598,25 @@ pub fn create_https_server(
         .at("/status")
         .mapped_get(&mut routemap, self::status);
 
+    appserver.at("/ready").mapped_get(&mut routemap, ready);
+
     // == oauth endpoints.
     oauth2_route_setup(&mut appserver, &mut routemap);
 
     // == scim endpoints.
     scim_route_setup(&mut appserver, &mut routemap);
+    scim_v2_route_setup(&mut appserver, &mut routemap);
 
     let mut raw_route = appserver.at("/v1/raw");
     raw_route.at("/create").mapped_post(&mut routemap, create);
     raw_route.at("/modify").mapped_post(&mut routemap, modify);
     raw_route.at("/delete").mapped_post(&mut routemap, delete);
     raw_route.at("/search").mapped_post(&mut routemap, search);
+    raw_route
+        .at("/changes/:id")
+        .mapped_get(&mut routemap, raw_changes_get_id);
+
+    appserver.at("/v1/batch").mapped_post(&mut routemap, batch);
 
     appserver.at("/v1/auth").mapped_post(&mut routemap, auth);
     appserver
@@ -565,6 +654,10 @@ pub fn create_https_server(
         .at("/_basic")
         .mapped_post(&mut routemap, oauth2_basic_post);
 
+    oauth2_route
+        .at("/_public")
+        .mapped_post(&mut routemap, oauth2_public_post);
+
     oauth2_route
         .at("/:rs_name")
         .mapped_get(&mut routemap, oauth2_id_get)
@@ -591,6 +684,10 @@ pub fn create_https_server(
     self_route.at("/").mapped_get(&mut routemap, whoami);
     self_route.at("/_uat").mapped_get(&mut routemap, whoami_uat);
 
+    appserver
+        .at("/v1/changes")
+        .mapped_get(&mut routemap, changes_subscribe);
+
     self_route
         .at("/_attr/:attr")
         .mapped_get(&mut routemap, do_nothing);
@@ -623,6 +720,14 @@ pub fn create_https_server(
         .at("/_applinks")
         .mapped_get(&mut routemap, applinks_get);
 
+    // The oauth2 resource servers this account has consented to.
+    self_route
+        .at("/_oauth2_consent")
+        .mapped_get(&mut routemap, oauth2_consent_get);
+    self_route
+        .at("/_oauth2_consent/:rs_name")
+        .mapped_delete(&mut routemap, oauth2_consent_delete);
+
     let mut person_route = appserver.at("/v1/person");
     person_route
         .at("/")
@@ -679,7 +784,8 @@ pub fn create_https_server(
 
     person_route
         .at("/:id/_unix")
-        .mapped_post(&mut routemap, account_post_id_unix);
+        .mapped_post(&mut routemap, account_post_id_unix)
+        .mapped_delete(&mut routemap, account_delete_id_unix);
     person_route
         .at("/:id/_unix/_credential")
         .mapped_put(&mut routemap, account_put_id_unix_credential)
@@ -744,7 +850,29 @@ pub fn create_https_server(
 
     service_account_route
         .at("/:id/_unix")
-        .mapped_post(&mut routemap, account_post_id_unix);
+        .mapped_post(&mut routemap, account_post_id_unix)
+        .mapped_delete(&mut routemap, account_delete_id_unix);
+
+    // Hosts
+
+    let mut host_route = appserver.at("/v1/host");
+    host_route
+        .at("/")
+        .mapped_get(&mut routemap, host_get)
+        .mapped_post(&mut routemap, host_post);
+    host_route
+        .at("/_enrol")
+        .mapped_post(&mut routemap, host_enrol);
+    host_route
+        .at("/:id")
+        .mapped_get(&mut routemap, host_id_get)
+        .mapped_delete(&mut routemap, host_id_delete);
+    host_route
+        .at("/:id/_enrol_token")
+        .mapped_post(&mut routemap, host_enrol_token_post);
+    host_route
+        .at("/:id/_credential")
+        .mapped_delete(&mut routemap, host_credential_delete);
 
     // Shared account features only - mainly this is for unix-like
     // features.
@@ -764,6 +892,12 @@ pub fn create_https_server(
     account_route
         .at("/:id/_user_auth_token/:token_id")
         .mapped_delete(&mut routemap, account_user_auth_token_delete);
+    account_route
+        .at("/:id/_oauth2_session")
+        .mapped_get(&mut routemap, account_get_id_oauth2_session);
+    account_route
+        .at("/:id/_oauth2_session/:session_id")
+        .mapped_delete(&mut routemap, account_oauth2_session_delete);
 
     // Credential updates, don't require the account id.
     let mut cred_route = appserver.at("/v1/credential");
@@ -804,7 +938,8 @@ pub fn create_https_server(
         .mapped_post(&mut routemap, group_id_post_attr);
     group_route
         .at("/:id/_unix")
-        .mapped_post(&mut routemap, group_post_id_unix);
+        .mapped_post(&mut routemap, group_post_id_unix)
+        .mapped_delete(&mut routemap, group_delete_id_unix);
 
     let mut domain_route = appserver.at("/v1/domain");
     domain_route.at("/").mapped_get(&mut routemap, domain_get);
@@ -821,6 +956,27 @@ pub fn create_https_server(
         .mapped_get(&mut routemap, system_get_attr)
         .mapped_post(&mut routemap, system_post_attr)
         .mapped_delete(&mut routemap, system_delete_attr);
+    system_route
+        .at("/_replication/changes")
+        .mapped_post(&mut routemap, system_post_replication_changes);
+    system_route
+        .at("/_replication/refresh")
+        .mapped_post(&mut routemap, system_post_replication_refresh);
+    system_route
+        .at("/_replication/status")
+        .mapped_get(&mut routemap, system_get_replication_status);
+    system_route
+        .at("/_replication/conflict")
+        .mapped_get(&mut routemap, system_get_replication_conflict);
+    system_route
+        .at("/_replication/conflict/:id")
+        .mapped_post(&mut routemap, system_post_replication_conflict_id);
+    system_route
+        .at("/_replication/join_token")
+        .mapped_post(&mut routemap, system_post_replication_join_token);
+    system_route
+        .at("/_audit/_search")
+        .mapped_post(&mut routemap, system_post_audit_search);
 
     let mut recycle_route = appserver.at("/v1/recycle_bin");
     recycle_route
@@ -845,6 +1001,13 @@ pub fn create_https_server(
         .mapped_get(&mut routemap, do_nothing);
 
     routemap.push_self("/v1/routemap".to_string(), http_types::Method::Get);
+    routemap.push_self("/v1/openapi.json".to_string(), http_types::Method::Get);
+    let openapi_document = openapi::openapi_document(&routemap).to_string();
+    appserver.at("/v1/openapi.json").nest({
+        let mut route_api = tide::with_state(openapi_document);
+        route_api.at("/").get(do_openapi);
+        route_api
+    });
     appserver.at("/v1/routemap").nest({
         let mut route_api = tide::with_state(routemap);
         route_api.at("/").get(do_routemap);
@@ -856,6 +1019,7 @@ pub fn create_https_server(
     // Create listener?
     let handle = match opt_tls_params {
         Some(tls_param) => {
+            let tls_param = tls_param.clone();
             let tlsl = TlsListener::build()
                 .addrs(&address)
                 .cert(&tls_param.chain)
@@ -871,39 +1035,76 @@ pub fn create_https_server(
             */
 
             tokio::spawn(async move {
-                tokio::select! {
-                    Ok(action) = rx.recv() => {
-                        match action {
-                            CoreAction::Shutdown => {},
+                let mut listener = Box::pin(tserver.clone().listen(tlsl));
+                loop {
+                    tokio::select! {
+                        Ok(action) = rx.recv() => {
+                            match action {
+                                CoreAction::Shutdown => break,
+                                CoreAction::ReloadTls => {
+                                    // Check the new certificates before replacing the listener,
+                                    // so that a bad renewal doesn't take the server offline.
+                                    // Connections that are already established are not dropped.
+                                    let tlsl = crate::crypto::tls_acceptor_builder(&tls_param)
+                                        .map_err(|e| format!("{:?}", e))
+                                        .and_then(|_| {
+                                            TlsListener::build()
+                                                .addrs(&address)
+                                                .cert(&tls_param.chain)
+                                                .key(&tls_param.key)
+                                                .finish()
+                                                .map_err(|e| format!("{:?}", e))
+                                        });
+                                    match tlsl {
+                                        Ok(tlsl) => {
+                                            listener = Box::pin(tserver.clone().listen(tlsl));
+                                            info!("Reloaded HTTPS certificates");
+                                        }
+                                        Err(e) => {
+                                            error!(
+                                                "Failed to reload HTTPS certificates, continuing with current certificates -> {}",
+                                                e
+                                            );
+                                        }
+                                    }
+                                }
+                            }
                         }
-                    }
-                    server_result = tserver.listen(tlsl) => {
-                        if let Err(e) = server_result {
-                            error!(
-                                "Failed to start server listener on address {:?} -> {:?}",
-                                &address, e
-                            );
+                        server_result = &mut listener => {
+                            if let Err(e) = server_result {
+                                error!(
+                                    "Failed to start server listener on address {:?} -> {:?}",
+                                    &address, e
+                                );
+                            }
+                            break;
                         }
-                    }
-                };
+                    };
+                }
                 info!("Stopped HTTPSAcceptorActor");
             })
         }
         None => {
             // Create without https
             tokio::spawn(async move {
-                tokio::select! {
-                    Ok(action) = rx.recv() => {
-                        match action {
-                            CoreAction::Shutdown => {},
+                let listener = tserver.listen(&address);
+                tokio::pin!(listener);
+                loop {
+                    tokio::select! {
+                        Ok(action) = rx.recv() => {
+                            match action {
+                                CoreAction::Shutdown => break,
+                                CoreAction::ReloadTls => {}
+                            }
                         }
-                    }
-                    server_result = tserver.listen(&address) => {
-                        if let Err(e) = server_result {
-                            error!(
-                                "Failed to start server listener on address {:?} -> {:?}",
-                                &address, e
-                            );
+                        server_result = &mut listener => {
+                            if let Err(e) = server_result {
+                                error!(
+                                    "Failed to start server listener on address {:?} -> {:?}",
+                                    &address, e
+                                );
+                            }
+                            break;
                         }
                     }
                 }
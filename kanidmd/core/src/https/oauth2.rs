@@ -1,8 +1,9 @@
 use kanidm_proto::oauth2::AuthorisationResponse;
 use kanidm_proto::v1::Entry as ProtoEntry;
 use kanidmd_lib::idm::oauth2::{
-    AccessTokenIntrospectRequest, AccessTokenRequest, AuthorisationRequest, AuthorisePermitSuccess,
-    AuthoriseResponse, ErrorResponse, Oauth2Error, TokenRevokeRequest,
+    AccessTokenIntrospectRequest, AccessTokenRequest, AuthorisationRequestOrPushed,
+    AuthorisePermitSuccess, AuthoriseResponse, ErrorResponse, Oauth2Error,
+    PushedAuthorisationRequest, TokenRevokeRequest,
 };
 use kanidmd_lib::prelude::*;
 use serde::{Deserialize, Serialize};
@@ -30,6 +31,15 @@ pub async fn oauth2_basic_post(req: tide::Request<AppState>) -> tide::Result {
     json_rest_event_post(req, classes).await
 }
 
+pub async fn oauth2_public_post(req: tide::Request<AppState>) -> tide::Result {
+    let classes = vec![
+        "oauth2_resource_server".to_string(),
+        "oauth2_resource_server_public".to_string(),
+        "object".to_string(),
+    ];
+    json_rest_event_post(req, classes).await
+}
+
 fn oauth2_id(id: &str) -> Filter<FilterInvalid> {
     filter_all!(f_and!([
         f_eq("class", PartialValue::new_class("oauth2_resource_server")),
@@ -86,7 +96,7 @@ pub async fn oauth2_id_patch(mut req: tide::Request<AppState>) -> tide::Result {
     let res = req
         .state()
         .qe_w_ref
-        .handle_internalpatch(uat, filter, obj, eventid)
+        .handle_internalpatch(uat, filter, obj, req.get_if_match(), eventid)
         .await;
     to_tide_response(res, hvalue)
 }
@@ -171,7 +181,7 @@ pub async fn oauth2_id_delete(req: tide::Request<AppState>) -> tide::Result {
     let res = req
         .state()
         .qe_w_ref
-        .handle_internaldelete(uat, filter, eventid)
+        .handle_internaldelete(uat, filter, req.get_if_match(), eventid)
         .await;
     to_tide_response(res, hvalue)
 }
@@ -238,7 +248,7 @@ pub async fn oauth2_id_delete(req: tide::Request<AppState>) -> tide::Result {
 //
 
 pub async fn oauth2_authorise_post(mut req: tide::Request<AppState>) -> tide::Result {
-    let auth_req: AuthorisationRequest = req.body_json().await?;
+    let auth_req: AuthorisationRequestOrPushed = req.body_json().await?;
     oauth2_authorise(req, auth_req).await.map(|mut res| {
         if res.status() == 302 {
             // in post, we need the redirect not to be issued, so we mask 302 to 200
@@ -252,7 +262,7 @@ pub async fn oauth2_authorise_get(req: tide::Request<AppState>) -> tide::Result
     // Start the oauth2 authorisation flow to present to the user.
     debug!("Request Query - {:?}", req.url().query());
     // Get the authorisation request.
-    let auth_req: AuthorisationRequest = req.query().map_err(|e| {
+    let auth_req: AuthorisationRequestOrPushed = req.query().map_err(|e| {
         error!("{:?}", e);
         tide::Error::from_str(
             tide::StatusCode::BadRequest,
@@ -265,7 +275,7 @@ pub async fn oauth2_authorise_get(req: tide::Request<AppState>) -> tide::Result
 
 async fn oauth2_authorise(
     req: tide::Request<AppState>,
-    auth_req: AuthorisationRequest,
+    auth_req: AuthorisationRequestOrPushed,
 ) -> tide::Result {
     let uat = req.get_current_uat();
     let (eventid, hvalue) = req.new_eventid();
@@ -281,6 +291,7 @@ async fn oauth2_authorise(
     match res {
         Ok(AuthoriseResponse::ConsentRequested {
             client_name,
+            client_image,
             scopes,
             pii_scopes,
             consent_token,
@@ -291,6 +302,7 @@ async fn oauth2_authorise(
             // This is json so later we can expand it with better detail.
             tide::Body::from_json(&AuthorisationResponse::ConsentRequested {
                 client_name,
+                client_image,
                 scopes,
                 pii_scopes,
                 consent_token,
@@ -361,6 +373,63 @@ async fn oauth2_authorise(
     })
 }
 
+pub async fn oauth2_authorise_par_post(mut req: tide::Request<AppState>) -> tide::Result {
+    // This is called directly by the resource server to submit the authorisation request
+    // ahead of redirecting the user agent. See RFC 9126.
+    let (eventid, hvalue) = req.new_eventid();
+
+    let client_authz = req
+        .header("authorization")
+        .and_then(|hv| hv.get(0))
+        .and_then(|h| h.as_str().strip_prefix("Basic "))
+        .map(str::to_string);
+
+    let par_req: PushedAuthorisationRequest = req.body_form().await.map_err(|e| {
+        request_error!("{:?}", e);
+        tide::Error::from_str(
+            tide::StatusCode::BadRequest,
+            "Invalid Oauth2 PushedAuthorisationRequest",
+        )
+    })?;
+
+    let res = req
+        .state()
+        .qe_r_ref
+        .handle_oauth2_pushed_authorisation(client_authz, par_req, eventid)
+        .await;
+
+    match res {
+        Ok(par_resp) => {
+            let mut res = tide::Response::new(tide::StatusCode::Created);
+            tide::Body::from_json(&par_resp).map(|b| {
+                res.set_body(b);
+                res
+            })
+        }
+        Err(Oauth2Error::AuthenticationRequired) => {
+            Ok(tide::Response::new(tide::StatusCode::Unauthorized))
+        }
+        Err(e) => {
+            // https://datatracker.ietf.org/doc/html/rfc9126#section-2.3
+            let err = ErrorResponse {
+                error: e.to_string(),
+                error_description: None,
+                error_uri: None,
+            };
+
+            let mut res = tide::Response::new(400);
+            tide::Body::from_json(&err).map(|b| {
+                res.set_body(b);
+                res
+            })
+        }
+    }
+    .map(|mut res| {
+        res.insert_header("X-KANIDM-OPID", hvalue);
+        res
+    })
+}
+
 pub async fn oauth2_authorise_permit_post(mut req: tide::Request<AppState>) -> tide::Result {
     let consent_req: String = req.body_json().await?;
     oauth2_authorise_permit(req, consent_req)
@@ -794,6 +863,12 @@ pub fn oauth2_route_setup(appserver: &mut tide::Route<'_, AppState>, routemap: &
         .mapped_post(routemap, oauth2_authorise_post)
         .mapped_get(routemap, oauth2_authorise_get);
 
+    // ⚠️  ⚠️   WARNING  ⚠️  ⚠️
+    // IF YOU CHANGE THESE VALUES YOU MUST UPDATE OIDC DISCOVERY URLS
+    oauth2_process
+        .at("/authorise/par")
+        .mapped_post(routemap, oauth2_authorise_par_post);
+
     // ⚠️  ⚠️   WARNING  ⚠️  ⚠️
     // IF YOU CHANGE THESE VALUES YOU MUST UPDATE OIDC DISCOVERY URLS
     oauth2_process
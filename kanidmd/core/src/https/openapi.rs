@@ -0,0 +1,142 @@
+//! Generate an OpenAPI document of the http api from the route map, so that clients can be
+//! generated rather than written by hand.
+
+use kanidm_proto::openapi::{schemas, ApiSchema};
+use kanidm_proto::v1::{
+    BatchRequest, BatchResponse, CreateRequest, DeleteRequest, EntryChangeNotification,
+    ErrorResponse, ModifyRequest, SearchRequest, SearchResponse, WhoamiResponse,
+};
+use serde_json::{json, Map, Value};
+
+use super::routemaps::RouteMap;
+
+const KANIDM_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The body of a request or response of an operation.
+enum Body {
+    Json(Value),
+    EventStream(Value),
+}
+
+/// The request and response bodies of the operations whose types are known. Operations not
+/// listed here are documented without a schema.
+fn operation_bodies(path: &str, method: &str) -> (Option<Body>, Option<Body>) {
+    match (method, path) {
+        ("post", "/v1/raw/create") => (Some(Body::Json(CreateRequest::schema_ref())), None),
+        ("post", "/v1/raw/modify") => (Some(Body::Json(ModifyRequest::schema_ref())), None),
+        ("post", "/v1/raw/delete") => (Some(Body::Json(DeleteRequest::schema_ref())), None),
+        ("post", "/v1/batch") => (
+            Some(Body::Json(BatchRequest::schema_ref())),
+            Some(Body::Json(BatchResponse::schema_ref())),
+        ),
+        ("post", "/v1/raw/search") => (
+            Some(Body::Json(SearchRequest::schema_ref())),
+            Some(Body::Json(SearchResponse::schema_ref())),
+        ),
+        ("get", "/v1/self") => (None, Some(Body::Json(WhoamiResponse::schema_ref()))),
+        ("get", "/v1/changes") => (
+            None,
+            Some(Body::EventStream(EntryChangeNotification::schema_ref())),
+        ),
+        ("get", "/status") | ("get", "/ready") => {
+            (None, Some(Body::Json(json!({ "type": "boolean" }))))
+        }
+        _ => (None, None),
+    }
+}
+
+fn body_content(body: Option<Body>) -> Value {
+    match body {
+        Some(Body::Json(schema)) => json!({ "application/json": { "schema": schema } }),
+        Some(Body::EventStream(schema)) => json!({ "text/event-stream": { "schema": schema } }),
+        None => json!({ "application/json": {} }),
+    }
+}
+
+/// Convert a tide route path to an OpenAPI path, and the names of its parameters.
+fn openapi_path(path: &str) -> (String, Vec<String>) {
+    let mut params = Vec::new();
+    let segments: Vec<String> = path
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .map(|s| match s.strip_prefix(':') {
+            Some(param) => {
+                params.push(param.to_string());
+                format!("{{{}}}", param)
+            }
+            None => s.to_string(),
+        })
+        .collect();
+    (format!("/{}", segments.join("/")), params)
+}
+
+fn operation(path: &str, method: &str, params: &[String]) -> Value {
+    let (request, response) = operation_bodies(path, method);
+
+    let mut op = Map::new();
+    op.insert(
+        "operationId".to_string(),
+        json!(format!("{}{}", method, path.replace(['/', '{', '}'], "_"))),
+    );
+    if !params.is_empty() {
+        let params: Vec<Value> = params
+            .iter()
+            .map(|name| {
+                json!({
+                    "name": name,
+                    "in": "path",
+                    "required": true,
+                    "schema": { "type": "string" }
+                })
+            })
+            .collect();
+        op.insert("parameters".to_string(), json!(params));
+    }
+    if let Some(request) = request {
+        op.insert(
+            "requestBody".to_string(),
+            json!({ "required": true, "content": body_content(Some(request)) }),
+        );
+    }
+    op.insert(
+        "responses".to_string(),
+        json!({
+            "200": { "description": "Success", "content": body_content(response) },
+            "default": {
+                "description": "Failure",
+                "content": { "application/json": { "schema": ErrorResponse::schema_ref() } }
+            }
+        }),
+    );
+    Value::Object(op)
+}
+
+/// The OpenAPI 3 document of every route in the route map.
+pub fn openapi_document(routemap: &RouteMap) -> Value {
+    let mut paths = Map::new();
+    for route in routemap.routelist.iter() {
+        let (path, params) = openapi_path(&route.path);
+        let method = route.method.to_string().to_lowercase();
+        let op = operation(&path, &method, &params);
+        if let Value::Object(item) = paths.entry(path).or_insert_with(|| json!({})) {
+            item.insert(method, op);
+        }
+    }
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Kanidm",
+            "version": KANIDM_VERSION
+        },
+        "paths": paths,
+        "components": {
+            "schemas": schemas(),
+            "securitySchemes": {
+                "bearerAuth": { "type": "http", "scheme": "bearer" }
+            }
+        },
+        // Authentication is optional for some operations, such as authenticating.
+        "security": [{ "bearerAuth": [] }, {}]
+    })
+}
@@ -3,19 +3,22 @@ use std::time::Duration;
 
 use compact_jwt::Jws;
 use kanidm_proto::v1::{
-    AccountUnixExtend, ApiTokenGenerate, AuthIssueSession, AuthRequest, AuthResponse,
-    AuthState as ProtoAuthState, CUIntentToken, CURequest, CUSessionToken, CreateRequest,
-    DeleteRequest, Entry as ProtoEntry, GroupUnixExtend, ModifyRequest, OperationError,
-    SearchRequest, SingleStringRequest,
+    AccountUnixExtend, ApiTokenGenerate, AuditSearchRequest, AuthIssueSession, AuthRequest,
+    AuthResponse, AuthState as ProtoAuthState, BatchRequest, CUIntentToken, CURequest,
+    CUSessionToken, CreateRequest, DeleteRequest, Entry as ProtoEntry, EntryChangeNotification,
+    ErrorResponse, Filter as ProtoFilter, GroupUnixExtend, ModifyRequest, OperationError,
+    ReplConflictResolution, SearchRequest, SingleStringRequest,
 };
 use kanidmd_lib::filter::{Filter, FilterInvalid};
 use kanidmd_lib::idm::event::AuthResult;
 use kanidmd_lib::idm::AuthState;
 use kanidmd_lib::prelude::*;
+use kanidmd_lib::repl::proto::{ReplRefreshRequest, ReplRuvRange};
 use kanidmd_lib::status::StatusRequestEvent;
 use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::error::RecvError;
 
-use super::{to_tide_response, AppState, RequestExtensions, RouteMap};
+use super::{to_ldif_response, to_tide_response, AppState, RequestExtensions, RouteMap};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub(crate) struct SessionId {
@@ -49,14 +52,43 @@ pub async fn delete(mut req: tide::Request<AppState>) -> tide::Result {
     to_tide_response(res, hvalue)
 }
 
+pub async fn batch(mut req: tide::Request<AppState>) -> tide::Result {
+    let uat = req.get_current_uat();
+    let msg: BatchRequest = req.body_json().await?;
+    let (eventid, hvalue) = req.new_eventid();
+    let res = req.state().qe_w_ref.handle_batch(uat, msg, eventid).await;
+    to_tide_response(res, hvalue)
+}
+
 pub async fn search(mut req: tide::Request<AppState>) -> tide::Result {
     let uat = req.get_current_uat();
     let msg: SearchRequest = req.body_json().await?;
     let (eventid, hvalue) = req.new_eventid();
+    if req.wants_ldif() {
+        let res = req
+            .state()
+            .qe_r_ref
+            .handle_search_ldif(uat, msg, eventid)
+            .await;
+        return to_ldif_response(res, hvalue);
+    }
     let res = req.state().qe_r_ref.handle_search(uat, msg, eventid).await;
     to_tide_response(res, hvalue)
 }
 
+pub async fn raw_changes_get_id(req: tide::Request<AppState>) -> tide::Result {
+    let uat = req.get_current_uat();
+    let id = req.get_url_param("id")?;
+    let filter = filter_all!(f_id(id.as_str()));
+    let (eventid, hvalue) = req.new_eventid();
+    let res = req
+        .state()
+        .qe_r_ref
+        .handle_attr_changes(uat, filter, eventid)
+        .await;
+    to_tide_response(res, hvalue)
+}
+
 pub async fn whoami(req: tide::Request<AppState>) -> tide::Result {
     let uat = req.get_current_uat();
     let (eventid, hvalue) = req.new_eventid();
@@ -72,6 +104,132 @@ pub async fn whoami_uat(req: tide::Request<AppState>) -> tide::Result {
     to_tide_response(res, hvalue)
 }
 
+#[derive(Deserialize, Debug)]
+pub struct ChangesQuery {
+    /// The json encoded filter of the entries to watch.
+    pub filter: String,
+}
+
+fn changes_filter(req: &tide::Request<AppState>) -> Result<ProtoFilter, tide::Error> {
+    let query: ChangesQuery = req.query()?;
+    serde_json::from_str(&query.filter).map_err(|e| {
+        error!(?e, "Invalid change subscription filter");
+        tide::Error::from_str(tide::StatusCode::BadRequest, "Invalid filter")
+    })
+}
+
+/// Stream the changes of the entries that match a filter as server sent events. Each event is
+/// an [EntryChangeNotification] of an entry that matches the filter, or that matched it before
+/// the change. The stream ends when the session is no longer valid, or if the client falls too
+/// far behind the changes of the server.
+pub async fn changes_subscribe(req: tide::Request<AppState>) -> tide::Result {
+    let uat = req.get_current_uat();
+    let filter = changes_filter(&req)?;
+    let (eventid, hvalue) = req.new_eventid();
+
+    // Check the session and filter now, since errors can't be returned once the stream begins.
+    // Matching against an entry that can't exist avoids searching everything twice.
+    if let Err(e) = req
+        .state()
+        .qe_r_ref
+        .handle_changes_match(uat, filter, Some(&[UUID_DOES_NOT_EXIST]), eventid)
+        .await
+    {
+        return to_tide_response::<()>(Err(e), hvalue);
+    }
+
+    let mut res = tide::sse::upgrade(req, move |req, sender| async move {
+        // The stream runs as its own task since the handler's future must be Sync.
+        if let Err(e) = tokio::spawn(changes_stream(req, sender)).await {
+            error!(?e, "Change stream failed");
+        }
+        Ok(())
+    });
+    res.insert_header("X-KANIDM-OPID", hvalue);
+    Ok(res)
+}
+
+async fn changes_stream(req: tide::Request<AppState>, sender: tide::sse::Sender) {
+    let uat = req.get_current_uat();
+    let filter = match changes_filter(&req) {
+        Ok(f) => f,
+        Err(_) => return,
+    };
+    let (eventid, hvalue) = req.new_eventid();
+    let qe_r_ref = req.state().qe_r_ref;
+
+    let (mut rx, mut known) = match qe_r_ref
+        .handle_changes_subscribe(uat.clone(), filter.clone(), eventid)
+        .await
+    {
+        Ok(r) => r,
+        Err(e) => {
+            changes_stream_error(&sender, e, &hvalue).await;
+            return;
+        }
+    };
+
+    loop {
+        let changes = match rx.recv().await {
+            Ok(changes) => changes,
+            Err(RecvError::Lagged(missed)) => {
+                // The client must search again to know the current state.
+                error!(%missed, "Change subscriber lagged, ending stream");
+                let _ = sender.send("lagged", missed.to_string(), None).await;
+                break;
+            }
+            Err(RecvError::Closed) => break,
+        };
+
+        let (eventid, hvalue) = req.new_eventid();
+        let changed: Vec<Uuid> = changes.iter().map(|c| c.uuid).collect();
+        let matched = match qe_r_ref
+            .handle_changes_match(uat.clone(), filter.clone(), Some(&changed), eventid)
+            .await
+        {
+            Ok(m) => m,
+            Err(e) => {
+                changes_stream_error(&sender, e, &hvalue).await;
+                break;
+            }
+        };
+
+        for change in changes.iter() {
+            // Entries that no longer match, or were deleted, are still sent once so that
+            // the client can forget them.
+            let notify = if matched.contains(&change.uuid) {
+                known.insert(change.uuid);
+                true
+            } else {
+                known.remove(&change.uuid)
+            };
+            if !notify {
+                continue;
+            }
+            let data = match serde_json::to_string(change) {
+                Ok(d) => d,
+                Err(e) => {
+                    error!(?e, "Unable to serialise change notification");
+                    continue;
+                }
+            };
+            if sender.send("change", data, Some(&hvalue)).await.is_err() {
+                // The client went away.
+                return;
+            }
+        }
+    }
+}
+
+async fn changes_stream_error(sender: &tide::sse::Sender, e: OperationError, hvalue: &str) {
+    match serde_json::to_string(&ErrorResponse::new(e, hvalue.to_string())) {
+        Ok(data) => {
+            let _ = sender.send("error", data, Some(hvalue)).await;
+        }
+        Err(e) => error!(?e, "Unable to serialise change stream error"),
+    }
+}
+
 pub async fn logout(mut req: tide::Request<AppState>) -> tide::Result {
     let uat = req.get_current_uat();
     let (eventid, hvalue) = req.new_eventid();
@@ -98,6 +256,16 @@ pub async fn json_rest_event_get(
 
     let (eventid, hvalue) = req.new_eventid();
 
+    if req.wants_ldif() {
+        let res = req
+            .state()
+            .qe_r_ref
+            .handle_internalsearch_ldif(uat, filter, attrs, eventid)
+            .await
+            .map(|ldif| (ldif, None));
+        return to_ldif_response(res, hvalue);
+    }
+
     let res = req
         .state()
         .qe_r_ref
@@ -118,13 +286,24 @@ pub async fn json_rest_event_get_id(
 
     let (eventid, hvalue) = req.new_eventid();
 
-    let res = req
+    let (res, etag) = match req
         .state()
         .qe_r_ref
-        .handle_internalsearch(uat, filter, attrs, eventid)
+        .handle_internalsearch_etags(uat, filter, attrs, eventid)
         .await
-        .map(|mut r| r.pop());
-    to_tide_response(res, hvalue)
+        .map(|mut r| r.pop())
+    {
+        Ok(Some((entry, etag))) => (Ok(Some(entry)), etag),
+        Ok(None) => (Ok(None), None),
+        Err(e) => (Err(e), None),
+    };
+    to_tide_response(res, hvalue).map(|mut res| {
+        // Clients send this back in If-Match to only change the entry if it is unchanged.
+        if let Some(etag) = etag {
+            res.insert_header("ETag", etag);
+        }
+        res
+    })
 }
 
 pub async fn json_rest_event_delete_id(
@@ -140,7 +319,7 @@ pub async fn json_rest_event_delete_id(
     let res = req
         .state()
         .qe_w_ref
-        .handle_internaldelete(uat, filter, eventid)
+        .handle_internaldelete(uat, filter, req.get_if_match(), eventid)
         .await;
     to_tide_response(res, hvalue)
 }
@@ -196,6 +375,7 @@ pub async fn json_rest_event_post_id_attr(
     filter: Filter<FilterInvalid>,
 ) -> tide::Result {
     let uat = req.get_current_uat();
+    let if_match = req.get_if_match();
     let uuid_or_name = req.get_url_param("id")?;
     let attr = req.get_url_param("attr")?;
     let values: Vec<String> = req.body_json().await?;
@@ -203,7 +383,7 @@ pub async fn json_rest_event_post_id_attr(
     let res = req
         .state()
         .qe_w_ref
-        .handle_appendattribute(uat, uuid_or_name, attr, values, filter, eventid)
+        .handle_appendattribute(uat, uuid_or_name, attr, values, filter, if_match, eventid)
         .await;
     to_tide_response(res, hvalue)
 }
@@ -214,6 +394,7 @@ pub async fn json_rest_event_put_attr(
     filter: Filter<FilterInvalid>,
 ) -> tide::Result {
     let uat = req.get_current_uat();
+    let if_match = req.get_if_match();
     let attr = req.get_url_param("attr")?;
     let values: Vec<String> = req.body_json().await?;
 
@@ -221,7 +402,7 @@ pub async fn json_rest_event_put_attr(
     let res = req
         .state()
         .qe_w_ref
-        .handle_setattribute(uat, uuid_or_name, attr, values, filter, eventid)
+        .handle_setattribute(uat, uuid_or_name, attr, values, filter, if_match, eventid)
         .await;
     to_tide_response(res, hvalue)
 }
@@ -232,6 +413,7 @@ pub async fn json_rest_event_post_attr(
     filter: Filter<FilterInvalid>,
 ) -> tide::Result {
     let uat = req.get_current_uat();
+    let if_match = req.get_if_match();
     let attr = req.get_url_param("attr")?;
     let values: Vec<String> = req.body_json().await?;
 
@@ -239,7 +421,7 @@ pub async fn json_rest_event_post_attr(
     let res = req
         .state()
         .qe_w_ref
-        .handle_appendattribute(uat, uuid_or_name, attr, values, filter, eventid)
+        .handle_appendattribute(uat, uuid_or_name, attr, values, filter, if_match, eventid)
         .await;
     to_tide_response(res, hvalue)
 }
@@ -269,6 +451,7 @@ pub async fn json_rest_event_delete_attr(
     attr: String,
 ) -> tide::Result {
     let uat = req.get_current_uat();
+    let if_match = req.get_if_match();
     let (eventid, hvalue) = req.new_eventid();
 
     // TODO #211: Attempt to get an option Vec<String> here?
@@ -285,14 +468,22 @@ pub async fn json_rest_event_delete_attr(
         let res = req
             .state()
             .qe_w_ref
-            .handle_purgeattribute(uat, uuid_or_name, attr, filter, eventid)
+            .handle_purgeattribute(uat, uuid_or_name, attr, filter, if_match, eventid)
             .await;
         to_tide_response(res, hvalue)
     } else {
         let res = req
             .state()
             .qe_w_ref
-            .handle_removeattributevalues(uat, uuid_or_name, attr, values, filter, eventid)
+            .handle_removeattributevalues(
+                uat,
+                uuid_or_name,
+                attr,
+                values,
+                filter,
+                if_match,
+                eventid,
+            )
             .await;
         to_tide_response(res, hvalue)
     }
@@ -515,6 +706,68 @@ pub async fn service_account_api_token_delete(req: tide::Request<AppState>) -> t
     to_tide_response(res, hvalue)
 }
 
+// Hosts
+pub async fn host_get(req: tide::Request<AppState>) -> tide::Result {
+    let filter = filter_all!(f_eq("class", PartialValue::new_class("host")));
+    json_rest_event_get(req, filter, None).await
+}
+
+pub async fn host_post(req: tide::Request<AppState>) -> tide::Result {
+    let classes = vec![
+        "host".to_string(),
+        "service_account".to_string(),
+        "account".to_string(),
+        "object".to_string(),
+    ];
+    json_rest_event_post(req, classes).await
+}
+
+pub async fn host_id_get(req: tide::Request<AppState>) -> tide::Result {
+    let filter = filter_all!(f_eq("class", PartialValue::new_class("host")));
+    json_rest_event_get_id(req, filter, None).await
+}
+
+pub async fn host_id_delete(req: tide::Request<AppState>) -> tide::Result {
+    let filter = filter_all!(f_eq("class", PartialValue::new_class("host")));
+    json_rest_event_delete_id(req, filter).await
+}
+
+pub async fn host_enrol_token_post(req: tide::Request<AppState>) -> tide::Result {
+    let uat = req.get_current_uat();
+    let uuid_or_name = req.get_url_param("id")?;
+
+    let (eventid, hvalue) = req.new_eventid();
+
+    let res = req
+        .state()
+        .qe_w_ref
+        .handle_host_enrol_token_create(uat, uuid_or_name, eventid)
+        .await;
+    to_tide_response(res, hvalue)
+}
+
+pub async fn host_credential_delete(req: tide::Request<AppState>) -> tide::Result {
+    let uat = req.get_current_uat();
+    let uuid_or_name = req.get_url_param("id")?;
+
+    let (eventid, hvalue) = req.new_eventid();
+
+    let res = req
+        .state()
+        .qe_w_ref
+        .handle_host_credential_revoke(uat, uuid_or_name, eventid)
+        .await;
+    to_tide_response(res, hvalue)
+}
+
+pub async fn host_enrol(mut req: tide::Request<AppState>) -> tide::Result {
+    let (eventid, hvalue) = req.new_eventid();
+    let token: String = req.body_json().await?;
+
+    let res = req.state().qe_w_ref.handle_host_enrol(token, eventid).await;
+    to_tide_response(res, hvalue)
+}
+
 // Account stuff
 pub async fn account_id_get_attr(req: tide::Request<AppState>) -> tide::Result {
     let filter = filter_all!(f_eq("class", PartialValue::new_class("account")));
@@ -552,7 +805,7 @@ pub async fn account_id_patch(mut req: tide::Request<AppState>) -> tide::Result
     let res = req
         .state()
         .qe_w_ref
-        .handle_internalpatch(uat, filter, obj, eventid)
+        .handle_internalpatch(uat, filter, obj, req.get_if_match(), eventid)
         .await;
     to_tide_response(res, hvalue)
 }
@@ -625,6 +878,35 @@ pub async fn account_user_auth_token_delete(req: tide::Request<AppState>) -> tid
     to_tide_response(res, hvalue)
 }
 
+pub async fn account_get_id_oauth2_session(req: tide::Request<AppState>) -> tide::Result {
+    let uat = req.get_current_uat();
+    let uuid_or_name = req.get_url_param("id")?;
+
+    let (eventid, hvalue) = req.new_eventid();
+
+    let res = req
+        .state()
+        .qe_r_ref
+        .handle_account_oauth2_session_get(uat, uuid_or_name, eventid)
+        .await;
+    to_tide_response(res, hvalue)
+}
+
+pub async fn account_oauth2_session_delete(req: tide::Request<AppState>) -> tide::Result {
+    let uat = req.get_current_uat();
+    let uuid_or_name = req.get_url_param("id")?;
+    let session_id = req.get_url_param_uuid("session_id")?;
+
+    let (eventid, hvalue) = req.new_eventid();
+
+    let res = req
+        .state()
+        .qe_w_ref
+        .handle_account_oauth2_session_destroy(uat, uuid_or_name, session_id, eventid)
+        .await;
+    to_tide_response(res, hvalue)
+}
+
 pub async fn credential_update_exchange_intent(mut req: tide::Request<AppState>) -> tide::Result {
     let (eventid, hvalue) = req.new_eventid();
     let intent_token: CUIntentToken = req.body_json().await?;
@@ -747,6 +1029,7 @@ pub async fn account_get_id_ssh_pubkey_tag(req: tide::Request<AppState>) -> tide
 
 pub async fn account_delete_id_ssh_pubkey_tag(req: tide::Request<AppState>) -> tide::Result {
     let uat = req.get_current_uat();
+    let if_match = req.get_if_match();
     let uuid_or_name = req.get_url_param("id")?;
     let tag = req.get_url_param("tag")?;
     let attr = "ssh_publickey".to_string();
@@ -758,7 +1041,7 @@ pub async fn account_delete_id_ssh_pubkey_tag(req: tide::Request<AppState>) -> t
     let res = req
         .state()
         .qe_w_ref
-        .handle_removeattributevalues(uat, uuid_or_name, attr, values, filter, eventid)
+        .handle_removeattributevalues(uat, uuid_or_name, attr, values, filter, if_match, eventid)
         .await;
     to_tide_response(res, hvalue)
 }
@@ -826,6 +1109,18 @@ pub async fn account_post_id_unix(mut req: tide::Request<AppState>) -> tide::Res
     to_tide_response(res, hvalue)
 }
 
+pub async fn account_delete_id_unix(req: tide::Request<AppState>) -> tide::Result {
+    let uat = req.get_current_uat();
+    let uuid_or_name = req.get_url_param("id")?;
+    let (eventid, hvalue) = req.new_eventid();
+    let res = req
+        .state()
+        .qe_w_ref
+        .handle_idmaccountunixremove(uat, uuid_or_name, eventid)
+        .await;
+    to_tide_response(res, hvalue)
+}
+
 pub async fn account_get_id_unix_token(req: tide::Request<AppState>) -> tide::Result {
     let uat = req.get_current_uat();
     let uuid_or_name = req.get_url_param("id")?;
@@ -870,6 +1165,7 @@ pub async fn account_put_id_unix_credential(mut req: tide::Request<AppState>) ->
 
 pub async fn account_delete_id_unix_credential(req: tide::Request<AppState>) -> tide::Result {
     let uat = req.get_current_uat();
+    let if_match = req.get_if_match();
     let uuid_or_name = req.get_url_param("id")?;
     let attr = "unix_password".to_string();
     let filter = filter_all!(f_eq("class", PartialValue::new_class("posixaccount")));
@@ -879,7 +1175,7 @@ pub async fn account_delete_id_unix_credential(req: tide::Request<AppState>) ->
     let res = req
         .state()
         .qe_w_ref
-        .handle_purgeattribute(uat, uuid_or_name, attr, filter, eventid)
+        .handle_purgeattribute(uat, uuid_or_name, attr, filter, if_match, eventid)
         .await;
     to_tide_response(res, hvalue)
 }
@@ -938,6 +1234,18 @@ pub async fn group_post_id_unix(mut req: tide::Request<AppState>) -> tide::Resul
     to_tide_response(res, hvalue)
 }
 
+pub async fn group_delete_id_unix(req: tide::Request<AppState>) -> tide::Result {
+    let uat = req.get_current_uat();
+    let uuid_or_name = req.get_url_param("id")?;
+    let (eventid, hvalue) = req.new_eventid();
+    let res = req
+        .state()
+        .qe_w_ref
+        .handle_idmgroupunixremove(uat, uuid_or_name, eventid)
+        .await;
+    to_tide_response(res, hvalue)
+}
+
 pub async fn group_get_id_unix_token(req: tide::Request<AppState>) -> tide::Result {
     let uat = req.get_current_uat();
     let uuid_or_name = req.get_url_param("id")?;
@@ -994,6 +1302,95 @@ pub async fn system_delete_attr(req: tide::Request<AppState>) -> tide::Result {
     json_rest_event_delete_attr(req, filter, STR_UUID_SYSTEM_CONFIG.to_string(), attr).await
 }
 
+pub async fn system_post_replication_changes(mut req: tide::Request<AppState>) -> tide::Result {
+    let uat = req.get_current_uat();
+    let ctx_ruv: ReplRuvRange = req.body_json().await?;
+    let (eventid, hvalue) = req.new_eventid();
+    let res = req
+        .state()
+        .qe_r_ref
+        .handle_replsupplierchanges(uat, ctx_ruv, eventid)
+        .await;
+    to_tide_response(res, hvalue)
+}
+
+pub async fn system_post_replication_refresh(mut req: tide::Request<AppState>) -> tide::Result {
+    let uat = req.get_current_uat();
+    let refresh_req: ReplRefreshRequest = req.body_json().await?;
+    let (eventid, hvalue) = req.new_eventid();
+    let res = req
+        .state()
+        .qe_r_ref
+        .handle_replsupplierrefresh(uat, refresh_req, eventid)
+        .await;
+    to_tide_response(res, hvalue)
+}
+
+pub async fn system_get_replication_status(req: tide::Request<AppState>) -> tide::Result {
+    let uat = req.get_current_uat();
+    let (eventid, hvalue) = req.new_eventid();
+    let res = req
+        .state()
+        .qe_r_ref
+        .handle_repltopologystatus(uat, eventid)
+        .await;
+    to_tide_response(res, hvalue)
+}
+
+pub async fn system_post_audit_search(mut req: tide::Request<AppState>) -> tide::Result {
+    let uat = req.get_current_uat();
+    let msg: AuditSearchRequest = req.body_json().await?;
+    let (eventid, hvalue) = req.new_eventid();
+    let res = req
+        .state()
+        .qe_r_ref
+        .handle_auditlogsearch(uat, msg, eventid)
+        .await;
+    to_tide_response(res, hvalue)
+}
+
+pub async fn system_get_replication_conflict(req: tide::Request<AppState>) -> tide::Result {
+    let filter = filter_all!(f_eq("class", PartialValue::new_class("conflict")));
+    let uat = req.get_current_uat();
+    let attrs = None;
+
+    let (eventid, hvalue) = req.new_eventid();
+
+    let res = req
+        .state()
+        .qe_r_ref
+        .handle_internalsearchrecycled(uat, filter, attrs, eventid)
+        .await;
+    to_tide_response(res, hvalue)
+}
+
+pub async fn system_post_replication_conflict_id(mut req: tide::Request<AppState>) -> tide::Result {
+    let uat = req.get_current_uat();
+    let id = req.get_url_param("id")?;
+    let resolution: ReplConflictResolution = req.body_json().await?;
+
+    let (eventid, hvalue) = req.new_eventid();
+    let res = req
+        .state()
+        .qe_w_ref
+        .handle_replconflictresolve(uat, id, resolution, eventid)
+        .await;
+    to_tide_response(res, hvalue)
+}
+
+pub async fn system_post_replication_join_token(mut req: tide::Request<AppState>) -> tide::Result {
+    let uat = req.get_current_uat();
+    let name: String = req.body_json().await?;
+
+    let (eventid, hvalue) = req.new_eventid();
+    let res = req
+        .state()
+        .qe_w_ref
+        .handle_repljointokencreate(uat, name, eventid)
+        .await;
+    to_tide_response(res, hvalue)
+}
+
 pub async fn recycle_bin_get(req: tide::Request<AppState>) -> tide::Result {
     let filter = filter_all!(f_pres("class"));
     let uat = req.get_current_uat();
@@ -1051,6 +1448,29 @@ pub async fn applinks_get(req: tide::Request<AppState>) -> tide::Result {
     to_tide_response(res, hvalue)
 }
 
+pub async fn oauth2_consent_get(req: tide::Request<AppState>) -> tide::Result {
+    let uat = req.get_current_uat();
+    let (eventid, hvalue) = req.new_eventid();
+    let res = req
+        .state()
+        .qe_r_ref
+        .handle_list_oauth2_consent(uat, eventid)
+        .await;
+    to_tide_response(res, hvalue)
+}
+
+pub async fn oauth2_consent_delete(req: tide::Request<AppState>) -> tide::Result {
+    let uat = req.get_current_uat();
+    let client_id = req.get_url_param("rs_name")?;
+    let (eventid, hvalue) = req.new_eventid();
+    let res = req
+        .state()
+        .qe_w_ref
+        .handle_oauth2_consent_revoke(uat, client_id, eventid)
+        .await;
+    to_tide_response(res, hvalue)
+}
+
 pub async fn do_routemap(req: tide::Request<RouteMap>) -> tide::Result {
     let mut res = tide::Response::new(200);
 
@@ -1058,6 +1478,14 @@ pub async fn do_routemap(req: tide::Request<RouteMap>) -> tide::Result {
     Ok(res)
 }
 
+/// The OpenAPI document of this api.
+pub async fn do_openapi(req: tide::Request<String>) -> tide::Result {
+    let mut res = tide::Response::new(200);
+    res.set_content_type(tide::http::mime::JSON);
+    res.set_body(req.state().as_str());
+    Ok(res)
+}
+
 pub async fn do_nothing(_req: tide::Request<AppState>) -> tide::Result {
     let mut res = tide::Response::new(200);
     res.set_body("did nothing");
@@ -1213,3 +1641,22 @@ pub async fn status(req: tide::Request<AppState>) -> tide::Result {
     res.set_body(tide::Body::from_json(&r)?);
     Ok(res)
 }
+
+/// If the server can serve requests. Where `/status` only shows that the process is alive,
+/// this fails with a 503 while the backend, schema or replication is not usable, so that the
+/// server can be taken out of service without being restarted.
+pub async fn ready(req: tide::Request<AppState>) -> tide::Result {
+    let (eventid, hvalue) = req.new_eventid();
+    match req.state().qe_r_ref.handle_ready(eventid).await {
+        Ok(()) => to_tide_response(Ok(true), hvalue),
+        Err(e) => {
+            let mut res = tide::Response::new(tide::StatusCode::ServiceUnavailable);
+            res.set_body(tide::Body::from_json(&ErrorResponse::new(
+                e,
+                hvalue.clone(),
+            ))?);
+            res.insert_header("X-KANIDM-OPID", hvalue);
+            Ok(res)
+        }
+    }
+}
@@ -43,7 +43,7 @@ pub async fn sync_account_id_patch(mut req: tide::Request<AppState>) -> tide::Re
     let res = req
         .state()
         .qe_w_ref
-        .handle_internalpatch(uat, filter, obj, eventid)
+        .handle_internalpatch(uat, filter, obj, req.get_if_match(), eventid)
         .await;
     to_tide_response(res, hvalue)
 }
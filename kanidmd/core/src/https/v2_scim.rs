@@ -0,0 +1,335 @@
+//! SCIM 2.0 users and groups, so that HR systems and provisioning engines can manage persons
+//! and groups through a standard protocol. Errors are returned as SCIM error responses rather
+//! than as an [ErrorResponse], since that is what these clients understand.
+
+use kanidm_proto::scim_v2::{
+    ScimErrorResponse, ScimListResponse, ScimPatchRequest, ScimRequestError, ScimResourceType,
+};
+use kanidm_proto::v1::{
+    CreateRequest, DeleteRequest, Entry as ProtoEntry, ErrorResponse, Filter as ProtoFilter,
+    ModifyRequest, PluginError,
+};
+use kanidmd_lib::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tide::StatusCode;
+
+use super::routemaps::{RouteMap, RouteMaps};
+use super::{AppState, RequestExtensions};
+
+const SCIM_CONTENT_TYPE: &str = "application/scim+json";
+
+#[derive(Deserialize, Debug)]
+struct ScimListQuery {
+    filter: Option<String>,
+    #[serde(rename = "startIndex")]
+    start_index: Option<usize>,
+    count: Option<usize>,
+}
+
+fn scim_response<T: Serialize>(status: StatusCode, body: &T, hvalue: String) -> tide::Result {
+    let mut res = tide::Response::new(status);
+    res.set_body(tide::Body::from_json(body)?);
+    res.set_content_type(SCIM_CONTENT_TYPE);
+    res.insert_header("X-KANIDM-OPID", hvalue);
+    Ok(res)
+}
+
+fn scim_error_response(
+    status: StatusCode,
+    scim_type: Option<&str>,
+    detail: String,
+    hvalue: String,
+) -> tide::Result {
+    let body = ScimErrorResponse::new(status as u16, scim_type.map(str::to_string), Some(detail));
+    scim_response(status, &body, hvalue)
+}
+
+fn scim_request_error(e: ScimRequestError, hvalue: String) -> tide::Result {
+    request_warn!(?e, "Invalid scim request");
+    scim_error_response(
+        StatusCode::BadRequest,
+        Some(e.scim_type()),
+        e.detail().to_string(),
+        hvalue,
+    )
+}
+
+fn scim_operation_error(e: OperationError, hvalue: String) -> tide::Result {
+    let (status, scim_type) = match &e {
+        OperationError::NotAuthenticated | OperationError::SessionExpired => {
+            (StatusCode::Unauthorized, None)
+        }
+        OperationError::SystemProtectedObject
+        | OperationError::AccessDenied
+        | OperationError::ReplReadOnlyReplica => (StatusCode::Forbidden, None),
+        OperationError::NoMatchingEntries => (StatusCode::NotFound, None),
+        OperationError::EntryChanged => (StatusCode::PreconditionFailed, None),
+        OperationError::Plugin(PluginError::AttrUnique(_)) => {
+            (StatusCode::Conflict, Some("uniqueness"))
+        }
+        OperationError::EmptyRequest
+        | OperationError::SchemaViolation(_)
+        | OperationError::InvalidAttribute(_)
+        | OperationError::InvalidAttributeName(_) => (StatusCode::BadRequest, Some("invalidValue")),
+        _ => (StatusCode::InternalServerError, None),
+    };
+    let detail = ErrorResponse::new(e, hvalue.clone()).message;
+    let mut res = scim_error_response(status, scim_type, detail, hvalue)?;
+    if status == StatusCode::Unauthorized {
+        res.insert_header("WWW-Authenticate", "Bearer");
+    }
+    Ok(res)
+}
+
+async fn scim_body<T: DeserializeOwned>(
+    req: &mut tide::Request<AppState>,
+) -> Result<T, ScimRequestError> {
+    let body = req
+        .body_bytes()
+        .await
+        .map_err(|e| ScimRequestError::InvalidSyntax(e.to_string()))?;
+    serde_json::from_slice(&body).map_err(|e| ScimRequestError::InvalidSyntax(e.to_string()))
+}
+
+/// The filter of a resource by its id, which is the uuid of the entry.
+fn scim_id_filter(rtype: ScimResourceType, id: &str) -> Result<ProtoFilter, OperationError> {
+    let uuid = Uuid::parse_str(id).map_err(|_| OperationError::NoMatchingEntries)?;
+    Ok(ProtoFilter::And(vec![
+        rtype.class_filter(),
+        ProtoFilter::Eq("uuid".to_string(), uuid.to_string()),
+    ]))
+}
+
+/// SCIM lists are paged by startIndex and count over the whole result, so the search itself is
+/// not paged.
+async fn scim_search(
+    req: &tide::Request<AppState>,
+    filter: ProtoFilter,
+    eventid: Uuid,
+) -> Result<Vec<ProtoEntry>, OperationError> {
+    let uat = req.get_current_uat();
+    req.state()
+        .qe_r_ref
+        .handle_search_unpaged(uat, filter, eventid)
+        .await
+}
+
+async fn scim_resource_response(
+    req: &tide::Request<AppState>,
+    rtype: ScimResourceType,
+    filter: ProtoFilter,
+    status: StatusCode,
+    (eventid, hvalue): (Uuid, String),
+) -> tide::Result {
+    match scim_search(req, filter, eventid)
+        .await
+        .and_then(|mut entries| entries.pop().ok_or(OperationError::NoMatchingEntries))
+    {
+        Ok(entry) => scim_response(status, &rtype.to_resource(&entry), hvalue),
+        Err(e) => scim_operation_error(e, hvalue),
+    }
+}
+
+async fn scim_list(req: tide::Request<AppState>, rtype: ScimResourceType) -> tide::Result {
+    let (eventid, hvalue) = req.new_eventid();
+
+    let query: ScimListQuery = match req.query() {
+        Ok(q) => q,
+        Err(e) => {
+            return scim_request_error(ScimRequestError::InvalidSyntax(e.to_string()), hvalue)
+        }
+    };
+    let filter = match rtype.filter(query.filter.as_deref()) {
+        Ok(f) => f,
+        Err(e) => return scim_request_error(e, hvalue),
+    };
+
+    match scim_search(&req, filter, eventid).await {
+        Ok(mut entries) => {
+            // Sort so that pages are stable between requests.
+            entries.sort_by(|a, b| a.attrs.get("uuid").cmp(&b.attrs.get("uuid")));
+            let resources: Vec<Value> = entries.iter().map(|e| rtype.to_resource(e)).collect();
+            let list =
+                ScimListResponse::new(resources, query.start_index.unwrap_or(1), query.count);
+            scim_response(StatusCode::Ok, &list, hvalue)
+        }
+        Err(e) => scim_operation_error(e, hvalue),
+    }
+}
+
+async fn scim_create(mut req: tide::Request<AppState>, rtype: ScimResourceType) -> tide::Result {
+    let (eventid, hvalue) = req.new_eventid();
+
+    let entry = match scim_body::<Value>(&mut req)
+        .await
+        .and_then(|resource| rtype.from_resource(&resource))
+    {
+        Ok(entry) => entry,
+        Err(e) => return scim_request_error(e, hvalue),
+    };
+
+    // Names are unique, so the new entry is read back by its name.
+    let filter = ProtoFilter::And(
+        entry
+            .attrs
+            .get("name")
+            .into_iter()
+            .flatten()
+            .map(|name| ProtoFilter::Eq("name".to_string(), name.clone()))
+            .chain(std::iter::once(rtype.class_filter()))
+            .collect(),
+    );
+
+    let uat = req.get_current_uat();
+    let res = req
+        .state()
+        .qe_w_ref
+        .handle_create(
+            uat,
+            CreateRequest {
+                entries: vec![entry],
+            },
+            eventid,
+        )
+        .await;
+    match res {
+        Ok(()) => {
+            scim_resource_response(&req, rtype, filter, StatusCode::Created, (eventid, hvalue))
+                .await
+        }
+        Err(e) => scim_operation_error(e, hvalue),
+    }
+}
+
+async fn scim_get(req: tide::Request<AppState>, rtype: ScimResourceType) -> tide::Result {
+    let (eventid, hvalue) = req.new_eventid();
+    let id = req.get_url_param("id")?;
+
+    match scim_id_filter(rtype, &id) {
+        Ok(filter) => {
+            scim_resource_response(&req, rtype, filter, StatusCode::Ok, (eventid, hvalue)).await
+        }
+        Err(e) => scim_operation_error(e, hvalue),
+    }
+}
+
+async fn scim_patch(mut req: tide::Request<AppState>, rtype: ScimResourceType) -> tide::Result {
+    let (eventid, hvalue) = req.new_eventid();
+    let id = req.get_url_param("id")?;
+
+    let filter = match scim_id_filter(rtype, &id) {
+        Ok(f) => f,
+        Err(e) => return scim_operation_error(e, hvalue),
+    };
+    let modlist = match scim_body::<ScimPatchRequest>(&mut req)
+        .await
+        .and_then(|patch| rtype.modlist(&patch))
+    {
+        Ok(modlist) => modlist,
+        Err(e) => return scim_request_error(e, hvalue),
+    };
+
+    let uat = req.get_current_uat();
+    let res = req
+        .state()
+        .qe_w_ref
+        .handle_modify(uat, ModifyRequest::new(filter.clone(), modlist), eventid)
+        .await;
+    match res {
+        Ok(()) => {
+            scim_resource_response(&req, rtype, filter, StatusCode::Ok, (eventid, hvalue)).await
+        }
+        Err(e) => scim_operation_error(e, hvalue),
+    }
+}
+
+async fn scim_delete(req: tide::Request<AppState>, rtype: ScimResourceType) -> tide::Result {
+    let (eventid, hvalue) = req.new_eventid();
+    let id = req.get_url_param("id")?;
+
+    let filter = match scim_id_filter(rtype, &id) {
+        Ok(f) => f,
+        Err(e) => return scim_operation_error(e, hvalue),
+    };
+
+    let uat = req.get_current_uat();
+    let res = req
+        .state()
+        .qe_w_ref
+        .handle_delete(uat, DeleteRequest::new(filter), eventid)
+        .await;
+    match res {
+        Ok(()) => {
+            let mut res = tide::Response::new(StatusCode::NoContent);
+            res.insert_header("X-KANIDM-OPID", hvalue);
+            Ok(res)
+        }
+        Err(e) => scim_operation_error(e, hvalue),
+    }
+}
+
+async fn scim_users_get(req: tide::Request<AppState>) -> tide::Result {
+    scim_list(req, ScimResourceType::User).await
+}
+
+async fn scim_users_post(req: tide::Request<AppState>) -> tide::Result {
+    scim_create(req, ScimResourceType::User).await
+}
+
+async fn scim_users_id_get(req: tide::Request<AppState>) -> tide::Result {
+    scim_get(req, ScimResourceType::User).await
+}
+
+async fn scim_users_id_patch(req: tide::Request<AppState>) -> tide::Result {
+    scim_patch(req, ScimResourceType::User).await
+}
+
+async fn scim_users_id_delete(req: tide::Request<AppState>) -> tide::Result {
+    scim_delete(req, ScimResourceType::User).await
+}
+
+async fn scim_groups_get(req: tide::Request<AppState>) -> tide::Result {
+    scim_list(req, ScimResourceType::Group).await
+}
+
+async fn scim_groups_post(req: tide::Request<AppState>) -> tide::Result {
+    scim_create(req, ScimResourceType::Group).await
+}
+
+async fn scim_groups_id_get(req: tide::Request<AppState>) -> tide::Result {
+    scim_get(req, ScimResourceType::Group).await
+}
+
+async fn scim_groups_id_patch(req: tide::Request<AppState>) -> tide::Result {
+    scim_patch(req, ScimResourceType::Group).await
+}
+
+async fn scim_groups_id_delete(req: tide::Request<AppState>) -> tide::Result {
+    scim_delete(req, ScimResourceType::Group).await
+}
+
+pub fn scim_v2_route_setup(appserver: &mut tide::Route<'_, AppState>, routemap: &mut RouteMap) {
+    let mut scim_v2 = appserver.at("/scim/v2");
+
+    scim_v2
+        .at("/Users")
+        .mapped_get(routemap, scim_users_get)
+        .mapped_post(routemap, scim_users_post);
+    scim_v2
+        .at("/Users/:id")
+        .mapped_get(routemap, scim_users_id_get)
+        .mapped_patch(routemap, scim_users_id_patch)
+        .mapped_delete(routemap, scim_users_id_delete);
+
+    scim_v2
+        .at("/Groups")
+        .mapped_get(routemap, scim_groups_get)
+        .mapped_post(routemap, scim_groups_post);
+    scim_v2
+        .at("/Groups/:id")
+        .mapped_get(routemap, scim_groups_id_get)
+        .mapped_patch(routemap, scim_groups_id_patch)
+        .mapped_delete(routemap, scim_groups_id_delete);
+}
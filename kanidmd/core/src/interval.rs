@@ -8,21 +8,34 @@ use chrono::Utc;
 use saffron::parse::{CronExpr, English};
 use saffron::Cron;
 use tokio::sync::broadcast;
-use tokio::time::{interval, sleep, Duration};
+use tokio::time::{interval, sleep, Duration, Instant};
 
-use crate::config::OnlineBackup;
+use crate::config::{OnlineBackup, ReplicationConsumer, TlsConfiguration};
+use crate::crypto::setup_repl_connector;
+use crate::repl::ReplClient;
 use crate::CoreAction;
 
 use crate::actors::v1_read::QueryServerReadV1;
 use crate::actors::v1_write::QueryServerWriteV1;
+use kanidm_proto::v1::OperationError;
 use kanidmd_lib::constants::PURGE_FREQUENCY;
-use kanidmd_lib::event::{OnlineBackupEvent, PurgeRecycledEvent, PurgeTombstoneEvent};
+use kanidmd_lib::event::{
+    OnlineBackupEvent, PurgeAuditLogEvent, PurgeRecycledEvent, PurgeTombstoneEvent,
+};
+use kanidmd_lib::repl::join::ReplJoinToken;
+use kanidmd_lib::repl::proto::{ReplIncrementalContext, ReplRuvRange};
+use kanidmd_lib::repl::refresh::ReplRefreshCollector;
+use openssl::ssl::SslConnector;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 
 pub struct IntervalActor;
 
 impl IntervalActor {
     pub fn start(
         server: &'static QueryServerWriteV1,
+        changelog_retention: u64,
+        audit_log_retention: Option<u64>,
         mut rx: broadcast::Receiver<CoreAction>,
     ) -> tokio::task::JoinHandle<()> {
         tokio::spawn(async move {
@@ -33,15 +46,23 @@ impl IntervalActor {
                     Ok(action) = rx.recv() => {
                         match action {
                             CoreAction::Shutdown => break,
+                            CoreAction::ReloadTls => {}
                         }
                     }
                     _ = inter.tick() => {
                         server
-                            .handle_purgetombstoneevent(PurgeTombstoneEvent::new())
+                            .handle_purgetombstoneevent(PurgeTombstoneEvent::new_with_retention(
+                                changelog_retention,
+                            ))
                             .await;
                         server
                             .handle_purgerecycledevent(PurgeRecycledEvent::new())
                             .await;
+                        if let Some(retention) = audit_log_retention {
+                            server
+                                .handle_purgeauditlogevent(PurgeAuditLogEvent::new(retention))
+                                .await;
+                        }
                     }
                 }
             }
@@ -121,6 +142,7 @@ impl IntervalActor {
                     Ok(action) = rx.recv() => {
                         match action {
                             CoreAction::Shutdown => break,
+                            CoreAction::ReloadTls => {}
                         }
                     }
                     _ = sleep(Duration::from_secs(wait_seconds)) => {
@@ -142,4 +164,366 @@ impl IntervalActor {
 
         Ok(handle)
     }
+
+    // Allow this because result is the only way to map and ? to bubble up, but we aren't
+    // returning an op-error here because this is in early start up.
+    #[allow(clippy::result_unit_err)]
+    pub fn start_replication_consumer(
+        server: &'static QueryServerWriteV1,
+        cfg: &ReplicationConsumer,
+        tls_config: Option<&TlsConfiguration>,
+        mut join_token: Option<ReplJoinToken>,
+        mut rx: broadcast::Receiver<CoreAction>,
+    ) -> Result<tokio::task::JoinHandle<()>, ()> {
+        let transport = if cfg.supplier.starts_with("repl://") {
+            let tls_config = tls_config.ok_or_else(|| {
+                error!(
+                    "Replication consumer requires tls_* to connect to {}",
+                    cfg.supplier
+                );
+            })?;
+            let connector = setup_repl_connector(tls_config).map_err(|e| {
+                error!("Replication consumer failed to configure TLS -> {:?}", e);
+            })?;
+            ReplTransport::Mtls {
+                connector,
+                supplier: cfg.supplier.clone(),
+            }
+        } else {
+            Self::replication_https_transport(cfg)?
+        };
+
+        let frequency = cfg.interval;
+        let allow_refresh = cfg.refresh;
+
+        info!(
+            "Replication consumer will pull changes from {} every {}s",
+            cfg.supplier, frequency
+        );
+
+        let handle = tokio::spawn(async move {
+            let mut inter = interval(Duration::from_secs(frequency));
+            // The progress of a refresh, retained between intervals so it can resume.
+            let mut refresh = None;
+
+            loop {
+                tokio::select! {
+                    Ok(action) = rx.recv() => {
+                        match action {
+                            CoreAction::Shutdown => break,
+                            CoreAction::ReloadTls => {}
+                        }
+                    }
+                    _ = inter.tick() => {
+                        if let Some(token) = join_token.as_ref() {
+                            match transport.join(server, token).await {
+                                Ok(()) => join_token = None,
+                                Err(e) => error!(?e, "Unable to join replication supplier, it will be retried at the next interval"),
+                            }
+                        }
+                        if join_token.is_none() {
+                            Self::replication_consume(server, &transport, allow_refresh, &mut refresh).await;
+                        }
+                    }
+                }
+            }
+
+            info!("Stopped ReplicationConsumerActor");
+        });
+
+        Ok(handle)
+    }
+
+    fn replication_https_transport(cfg: &ReplicationConsumer) -> Result<ReplTransport, ()> {
+        let token = cfg.token.clone().ok_or_else(|| {
+            error!(
+                "Replication consumer requires a token to connect to {}",
+                cfg.supplier
+            );
+        })?;
+
+        let mut client_builder = reqwest::Client::builder();
+
+        if let Some(ca_path) = &cfg.supplier_ca {
+            let pem = fs::read(ca_path).map_err(|e| {
+                error!(
+                    "Replication consumer failed to read supplier ca '{}': {}",
+                    ca_path, e
+                )
+            })?;
+            let ca = reqwest::Certificate::from_pem(&pem).map_err(|e| {
+                error!(
+                    "Replication consumer supplier ca '{}' is not valid: {}",
+                    ca_path, e
+                )
+            })?;
+            client_builder = client_builder.add_root_certificate(ca);
+        }
+
+        let client = client_builder.build().map_err(|e| {
+            error!("Replication consumer failed to build http client: {}", e);
+        })?;
+
+        Ok(ReplTransport::Https {
+            client,
+            origin: cfg.supplier.trim_end_matches('/').to_string(),
+            token,
+        })
+    }
+
+    async fn replication_consume(
+        server: &'static QueryServerWriteV1,
+        transport: &ReplTransport,
+        allow_refresh: bool,
+        refresh: &mut Option<ReplRefreshCollector>,
+    ) {
+        if refresh.is_none() {
+            let ctx_ruv = server.handle_replconsumerruvrange().await;
+
+            let ctx = match transport.fetch_changes(server, ctx_ruv).await {
+                Ok(ctx) => ctx,
+                Err(e) => {
+                    error!(?e, "Unable to retrieve changes from replication supplier");
+                    server
+                        .handle_replconsumerfailed(transport.supplier(), &e)
+                        .await;
+                    return;
+                }
+            };
+
+            match ctx {
+                ReplIncrementalContext::DomainMismatch if allow_refresh => {
+                    warn!("This server is not a member of the supplier's domain, starting a full refresh");
+                    *refresh = Some(ReplRefreshCollector::new());
+                }
+                ctx => {
+                    let (ranges, changes) = match &ctx {
+                        ReplIncrementalContext::V1 {
+                            ranges, entries, ..
+                        } => (Some(ranges.clone()), entries.len() as u64),
+                        _ => (None, 0),
+                    };
+                    let start = Instant::now();
+                    match server.handle_replconsumerapply(ctx).await {
+                        Ok(()) => {
+                            server
+                                .handle_replconsumersynced(
+                                    transport.supplier(),
+                                    ranges,
+                                    changes,
+                                    start.elapsed(),
+                                )
+                                .await
+                        }
+                        Err(e) => {
+                            error!(?e, "Failed to apply changes from replication supplier");
+                            server
+                                .handle_replconsumerfailed(transport.supplier(), &e)
+                                .await;
+                        }
+                    }
+                    return;
+                }
+            }
+        }
+
+        if let Some(collector) = refresh.as_mut() {
+            if let Err(e) = transport.fetch_refresh(server, collector).await {
+                error!(
+                    ?e,
+                    received = collector.len(),
+                    "Replication refresh was interrupted, it will resume at the next interval"
+                );
+                server
+                    .handle_replconsumerfailed(transport.supplier(), &e)
+                    .await;
+                return;
+            }
+        }
+
+        if let Some(collector) = refresh.take() {
+            let received = collector.len();
+            match server.handle_replconsumerrefresh(collector).await {
+                Ok(()) => info!(
+                    received,
+                    "Replication refresh complete, continuing with incremental replication"
+                ),
+                Err(e) => {
+                    error!(?e, "Failed to apply refresh from replication supplier");
+                    server
+                        .handle_replconsumerfailed(transport.supplier(), &e)
+                        .await;
+                }
+            }
+        }
+    }
+}
+
+/// How a replication consumer reaches its supplier.
+enum ReplTransport {
+    Https {
+        client: reqwest::Client,
+        origin: String,
+        token: String,
+    },
+    Mtls {
+        connector: SslConnector,
+        supplier: String,
+    },
+}
+
+impl ReplTransport {
+    /// The address of the supplier, as it is written on its replication agreement.
+    fn supplier(&self) -> &str {
+        match self {
+            ReplTransport::Https { origin, .. } => origin,
+            ReplTransport::Mtls { supplier, .. } => supplier,
+        }
+    }
+
+    async fn https_post<Req: Serialize, Resp: DeserializeOwned>(
+        client: &reqwest::Client,
+        url: String,
+        token: &str,
+        req: &Req,
+    ) -> Result<Resp, OperationError> {
+        let response = client
+            .post(url)
+            .bearer_auth(token)
+            .json(req)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| {
+                error!(?e, "Replication supplier request failed");
+                OperationError::InvalidState
+            })?;
+
+        response.json().await.map_err(|e| {
+            error!(?e, "Replication supplier sent an invalid response");
+            OperationError::InvalidState
+        })
+    }
+
+    async fn mtls_connect(
+        server: &'static QueryServerWriteV1,
+        connector: &SslConnector,
+        supplier: &str,
+    ) -> Result<ReplClient, OperationError> {
+        // Look this up each time so that changes to the agreement apply without a restart.
+        let agreement = server
+            .handle_replconsumeragreement(supplier)
+            .await?
+            .ok_or_else(|| {
+                error!(
+                    "No replication agreement has repl_partner_address {}, unable to verify supplier",
+                    supplier
+                );
+                OperationError::NoMatchingEntries
+            })?;
+
+        ReplClient::connect(connector, supplier, &agreement).await
+    }
+
+    async fn fetch_changes(
+        &self,
+        server: &'static QueryServerWriteV1,
+        ctx_ruv: ReplRuvRange,
+    ) -> Result<ReplIncrementalContext, OperationError> {
+        match self {
+            ReplTransport::Https {
+                client,
+                origin,
+                token,
+            } => {
+                let url = format!("{}/v1/system/_replication/changes", origin);
+                Self::https_post(client, url, token, &ctx_ruv).await
+            }
+            ReplTransport::Mtls {
+                connector,
+                supplier,
+            } => {
+                Self::mtls_connect(server, connector, supplier)
+                    .await?
+                    .changes(ctx_ruv)
+                    .await
+            }
+        }
+    }
+
+    /// Retrieve chunks of a refresh until it is complete. If this fails, the chunks
+    /// already received are retained so the refresh can be resumed.
+    async fn fetch_refresh(
+        &self,
+        server: &'static QueryServerWriteV1,
+        refresh: &mut ReplRefreshCollector,
+    ) -> Result<(), OperationError> {
+        match self {
+            ReplTransport::Https {
+                client,
+                origin,
+                token,
+            } => {
+                while !refresh.is_complete() {
+                    let url = format!("{}/v1/system/_replication/refresh", origin);
+                    let ctx = Self::https_post(client, url, token, &refresh.next_request()).await?;
+                    refresh.add_chunk(ctx)?;
+                    debug!(received = refresh.len(), "Received refresh chunk");
+                }
+            }
+            ReplTransport::Mtls {
+                connector,
+                supplier,
+            } => {
+                // The whole refresh is streamed over a single connection.
+                let mut client = Self::mtls_connect(server, connector, supplier).await?;
+                while !refresh.is_complete() {
+                    let ctx = client.refresh(refresh.next_request()).await?;
+                    refresh.add_chunk(ctx)?;
+                    debug!(received = refresh.len(), "Received refresh chunk");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Join the supplier of a join token, and initialise this server from it. The supplier
+    /// accepts the same server again, so an interrupted join is resumed at the next interval.
+    async fn join(
+        &self,
+        server: &'static QueryServerWriteV1,
+        token: &ReplJoinToken,
+    ) -> Result<(), OperationError> {
+        let connector = match self {
+            ReplTransport::Mtls { connector, .. } => connector,
+            ReplTransport::Https { .. } => {
+                error!("A replication join token can only be used with a repl:// supplier");
+                return Err(OperationError::InvalidState);
+            }
+        };
+
+        if server
+            .handle_replconsumeragreement(&token.supplier)
+            .await?
+            .is_some()
+        {
+            debug!("This server has already joined the replication supplier");
+            return Ok(());
+        }
+
+        // Until we have joined, the supplier is pinned by the token.
+        let mut client =
+            ReplClient::connect(connector, &token.supplier, &token.supplier_agreement()).await?;
+        client.join(token.secret.clone()).await?;
+        info!(supplier = %token.supplier, "Joined replication supplier, starting a full refresh");
+
+        let mut refresh = ReplRefreshCollector::new();
+        while !refresh.is_complete() {
+            let ctx = client.refresh(refresh.next_request()).await?;
+            refresh.add_chunk(ctx)?;
+            debug!(received = refresh.len(), "Received refresh chunk");
+        }
+
+        server.handle_repljoincomplete(refresh, token).await
+    }
 }
@@ -7,17 +7,21 @@ use futures_util::sink::SinkExt;
 use futures_util::stream::StreamExt;
 use kanidmd_lib::ldap::{LdapBoundToken, LdapResponseState};
 use kanidmd_lib::prelude::*;
-use ldap3_proto::proto::LdapMsg;
+use ldap3_proto::proto::{LdapExtendedResponse, LdapMsg, LdapOp, LdapResult, LdapResultCode};
+use ldap3_proto::simple::DisconnectionNotice;
 use ldap3_proto::LdapCodec;
-use openssl::ssl::{Ssl, SslAcceptor, SslAcceptorBuilder};
+use openssl::ssl::{Ssl, SslAcceptor, SslAcceptorBuilder, SslContext};
 use tokio::io::{AsyncRead, AsyncWrite};
-use tokio::net::TcpListener;
+use tokio::net::{TcpListener, TcpStream};
 use tokio_openssl::SslStream;
-use tokio_util::codec::{FramedRead, FramedWrite};
+use tokio_util::codec::{Framed, FramedRead, FramedWrite};
 
 use crate::CoreAction;
 use tokio::sync::broadcast;
 
+// https://www.rfc-editor.org/rfc/rfc4511#section-4.14.1
+const LDAP_STARTTLS_OID: &str = "1.3.6.1.4.1.1466.20037";
+
 struct LdapSession {
     uat: Option<LdapBoundToken>,
 }
@@ -31,7 +35,7 @@ impl LdapSession {
     }
 }
 
-#[instrument(name = "ldap-request", skip(client_address, qe_r_ref))]
+#[instrument(name = "ldap-request", skip(client_address, qe_r_ref), fields(opid))]
 async fn client_process_msg(
     uat: Option<LdapBoundToken>,
     client_address: net::SocketAddr,
@@ -39,6 +43,7 @@ async fn client_process_msg(
     qe_r_ref: &'static QueryServerReadV1,
 ) -> Option<LdapResponseState> {
     let eventid = sketching::tracing_forest::id();
+    tracing::Span::current().record("opid", eventid.as_hyphenated().to_string().as_str());
     security_info!(
         client_ip = %client_address.ip(),
         client_port = %client_address.port(),
@@ -117,6 +122,7 @@ async fn tls_acceptor(
             Ok(action) = rx.recv() => {
                 match action {
                     CoreAction::Shutdown => break,
+                    CoreAction::ReloadTls => {}
                 }
             }
             accept_result = listener.accept() => {
@@ -152,12 +158,125 @@ async fn tls_acceptor(
     info!("Stopped LdapAcceptorActor");
 }
 
-pub(crate) async fn create_ldap_server(
-    address: &str,
-    opt_tls_params: Option<SslAcceptorBuilder>,
+/// Plaintext LDAP client that must negotiate StartTLS before any other operation. Once
+/// TLS is established, hands off to [client_process]
+async fn starttls_client_process(
+    tcpstream: TcpStream,
+    tls_ctx: SslContext,
+    client_address: net::SocketAddr,
     qe_r_ref: &'static QueryServerReadV1,
-    rx: broadcast::Receiver<CoreAction>,
-) -> Result<tokio::task::JoinHandle<()>, ()> {
+) {
+    let mut framed = Framed::new(tcpstream, LdapCodec);
+
+    let msgid = match framed.next().await {
+        Some(Ok(LdapMsg {
+            msgid,
+            op: LdapOp::ExtendedRequest(er),
+            ..
+        })) if er.name == LDAP_STARTTLS_OID => msgid,
+        Some(Ok(LdapMsg {
+            op: LdapOp::UnbindRequest,
+            ..
+        }))
+        | Some(Err(_))
+        | None => return,
+        Some(Ok(_)) => {
+            // We never allow plaintext operations, especially binds.
+            security_info!(
+                client_ip = %client_address.ip(),
+                "LDAP client attempted an operation before StartTLS"
+            );
+            let _ = framed
+                .send(DisconnectionNotice::gen(
+                    LdapResultCode::UnwillingToPerform,
+                    "StartTLS is required",
+                ))
+                .await;
+            return;
+        }
+    };
+
+    let rmsg = LdapMsg {
+        msgid,
+        op: LdapOp::ExtendedResponse(LdapExtendedResponse {
+            res: LdapResult {
+                code: LdapResultCode::Success,
+                matcheddn: "".to_string(),
+                message: "".to_string(),
+                referral: vec![],
+            },
+            name: Some(LDAP_STARTTLS_OID.to_string()),
+            value: None,
+        }),
+        ctrl: vec![],
+    };
+    if framed.send(rmsg).await.is_err() {
+        return;
+    }
+
+    // The client must wait for our response before starting the handshake, so anything
+    // already buffered is a protocol violation.
+    let parts = framed.into_parts();
+    if !parts.read_buf.is_empty() {
+        error!("LDAP client sent data before the StartTLS handshake, disconnecting");
+        return;
+    }
+
+    let mut tlsstream =
+        match Ssl::new(&tls_ctx).and_then(|tls_obj| SslStream::new(tls_obj, parts.io)) {
+            Ok(ta) => ta,
+            Err(e) => {
+                error!("LDAP StartTLS setup error -> {:?}", e);
+                return;
+            }
+        };
+    if let Err(e) = SslStream::accept(Pin::new(&mut tlsstream)).await {
+        error!("LDAP StartTLS accept error -> {:?}", e);
+        return;
+    };
+    let (r, w) = tokio::io::split(tlsstream);
+    let r = FramedRead::new(r, LdapCodec);
+    let w = FramedWrite::new(w, LdapCodec);
+    client_process(r, w, client_address, qe_r_ref).await
+}
+
+/// StartTLS LDAP Listener, hands off to [starttls_client_process]
+async fn starttls_acceptor(
+    listener: TcpListener,
+    tls_parms: SslAcceptor,
+    qe_r_ref: &'static QueryServerReadV1,
+    mut rx: broadcast::Receiver<CoreAction>,
+) {
+    loop {
+        tokio::select! {
+            Ok(action) = rx.recv() => {
+                match action {
+                    CoreAction::Shutdown => break,
+                    CoreAction::ReloadTls => {}
+                }
+            }
+            accept_result = listener.accept() => {
+                match accept_result {
+                    Ok((tcpstream, client_socket_addr)) => {
+                        let tls_ctx = tls_parms.context().to_owned();
+                        tokio::spawn(starttls_client_process(
+                            tcpstream,
+                            tls_ctx,
+                            client_socket_addr,
+                            qe_r_ref,
+                        ));
+                    }
+                    Err(e) => {
+                        error!("LDAP acceptor error, continuing -> {:?}", e);
+                    }
+                }
+            }
+        }
+    }
+    info!("Stopped LdapStartTlsAcceptorActor");
+}
+
+async fn bind_ldap_listener(address: &str) -> Result<TcpListener, ()> {
     if address.starts_with(":::") {
         // takes :::xxxx to xxxx
         let port = address.replacen(":::", "", 1);
@@ -168,12 +287,21 @@ pub(crate) async fn create_ldap_server(
         error!("Could not parse LDAP server address {} -> {:?}", address, e);
     })?;
 
-    let listener = TcpListener::bind(&addr).await.map_err(|e| {
+    TcpListener::bind(&addr).await.map_err(|e| {
         error!(
             "Could not bind to LDAP server address {} -> {:?}",
             address, e
         );
-    })?;
+    })
+}
+
+pub(crate) async fn create_ldap_server(
+    address: &str,
+    opt_tls_params: Option<SslAcceptorBuilder>,
+    qe_r_ref: &'static QueryServerReadV1,
+    rx: broadcast::Receiver<CoreAction>,
+) -> Result<tokio::task::JoinHandle<()>, ()> {
+    let listener = bind_ldap_listener(address).await?;
 
     let ldap_acceptor_handle = match opt_tls_params {
         Some(tls_params) => {
@@ -190,3 +318,27 @@ pub(crate) async fn create_ldap_server(
     info!("Created LDAP interface");
     Ok(ldap_acceptor_handle)
 }
+
+pub(crate) async fn create_ldap_starttls_server(
+    address: &str,
+    opt_tls_params: Option<SslAcceptorBuilder>,
+    qe_r_ref: &'static QueryServerReadV1,
+    rx: broadcast::Receiver<CoreAction>,
+) -> Result<tokio::task::JoinHandle<()>, ()> {
+    let listener = bind_ldap_listener(address).await?;
+
+    let ldap_acceptor_handle = match opt_tls_params {
+        Some(tls_params) => {
+            info!("Starting LDAP StartTLS interface ldap://{} ...", address);
+            let tls_parms = tls_params.build();
+            tokio::spawn(starttls_acceptor(listener, tls_parms, qe_r_ref, rx))
+        }
+        None => {
+            error!("The server won't run without TLS!");
+            return Err(());
+        }
+    };
+
+    info!("Created LDAP StartTLS interface");
+    Ok(ldap_acceptor_handle)
+}
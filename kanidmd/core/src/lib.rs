@@ -26,22 +26,27 @@ extern crate tracing;
 extern crate kanidmd_lib;
 
 pub mod actors;
+#[cfg(not(target_family = "windows"))]
+pub mod admin;
 pub mod config;
 mod crypto;
 pub mod https;
 mod interval;
 mod ldaps;
+mod repl;
 
 use std::sync::Arc;
 
 use compact_jwt::JwsSigner;
-use kanidm_proto::messages::{AccountChangeMessage, MessageStatus};
-use kanidm_proto::v1::OperationError;
+use kanidm_proto::messages::{AccountChangeMessage, ConsoleOutputMode, MessageStatus};
+use kanidm_proto::v1::{ConsistencyError, OperationError};
 use kanidmd_lib::be::{Backend, BackendConfig, BackendTransaction, FsType};
 use kanidmd_lib::idm::server::{IdmServer, IdmServerDelayed};
 use kanidmd_lib::ldap::LdapServer;
 use kanidmd_lib::prelude::*;
+use kanidmd_lib::repl::join::ReplJoinToken;
 use kanidmd_lib::schema::Schema;
+use kanidmd_lib::server::DomainRenamePlan;
 use kanidmd_lib::status::StatusActor;
 use kanidmd_lib::utils::{duration_from_epoch_now, touch_file_or_quit};
 #[cfg(not(target_family = "windows"))]
@@ -51,8 +56,10 @@ use tokio::sync::broadcast;
 
 use crate::actors::v1_read::QueryServerReadV1;
 use crate::actors::v1_write::QueryServerWriteV1;
-use crate::config::Configuration;
-use crate::crypto::setup_tls;
+#[cfg(not(target_family = "windows"))]
+use crate::admin::{submit_admin_task, tail_admin_socket, AdminTaskRequest, AdminTaskResponse};
+use crate::config::{Configuration, ReplicationConsumer};
+use crate::crypto::{setup_repl_tls, setup_tls};
 use crate::interval::IntervalActor;
 
 // === internal setup helpers
@@ -65,6 +72,23 @@ fn setup_backend_vacuum(
     config: &Configuration,
     schema: &Schema,
     vacuum: bool,
+) -> Result<Backend, OperationError> {
+    setup_backend_cfg(config, schema, vacuum, false)
+}
+
+/// Open an existing database without writing to it.
+fn setup_backend_readonly(
+    config: &Configuration,
+    schema: &Schema,
+) -> Result<Backend, OperationError> {
+    setup_backend_cfg(config, schema, false, true)
+}
+
+fn setup_backend_cfg(
+    config: &Configuration,
+    schema: &Schema,
+    vacuum: bool,
+    readonly: bool,
 ) -> Result<Backend, OperationError> {
     // Limit the scope of the schema txn.
     // let schema_txn = task::block_on(schema.write());
@@ -89,6 +113,11 @@ fn setup_backend_vacuum(
         fstype,
         config.db_arc_size,
     );
+    let cfg = if readonly { cfg.readonly() } else { cfg };
+    let cfg = match &config.db_seal_key_path {
+        Some(key_path) => cfg.seal_key_path(key_path),
+        None => cfg,
+    };
 
     Backend::new(cfg, idxmeta, vacuum)
 }
@@ -104,6 +133,9 @@ async fn setup_qs_idms(
 ) -> Result<(QueryServer, IdmServer, IdmServerDelayed), OperationError> {
     // Create a query_server implementation
     let query_server = QueryServer::new(be, schema, config.domain.clone());
+    if config.audit_log_retention.is_some() {
+        query_server.enable_audit_log();
+    }
 
     // TODO #62: Should the IDM parts be broken out to the IdmServer?
     // What's important about this initial setup here is that it also triggers
@@ -237,6 +269,49 @@ pub fn dbscan_get_id2entry_core(config: &Configuration, id: u64) {
     };
 }
 
+pub fn dbscan_get_uuid_core(config: &Configuration, uuid: Uuid) {
+    let be = dbscan_setup_be!(config);
+    let be_rotxn = be.read();
+
+    let id = match be_rotxn.get_uuid2id(uuid) {
+        Ok(Some(id)) => id,
+        Ok(None) => {
+            error!(
+                "No entry has uuid {} in the uuid index - if the index is damaged, use list_id2entry to find the entry",
+                uuid
+            );
+            return;
+        }
+        Err(e) => {
+            error!("Failed to retrieve uuid index: {:?}", e);
+            return;
+        }
+    };
+
+    // The stored entry includes the change that last modified each attribute.
+    match be_rotxn.get_id2entry(id) {
+        Ok((id, value)) => println!("{:>8}: {}", id, value),
+        Err(e) => {
+            error!("Failed to retrieve id2entry value: {:?}", e);
+        }
+    };
+
+    println!("index references:");
+    match be_rotxn.list_index_refs(id) {
+        Ok(refs) => refs.iter().for_each(|(idx_name, key)| {
+            println!("{:>50}: {}", idx_name, key);
+        }),
+        Err(e) => {
+            error!("Failed to retrieve index content: {:?}", e);
+        }
+    };
+
+    println!("replication changes:");
+    be_rotxn.list_ruv_changes(id).iter().for_each(|cid| {
+        println!("{:>50}", cid);
+    });
+}
+
 pub fn backup_server_core(config: &Configuration, dst_path: &str) {
     let schema = match Schema::new() {
         Ok(s) => s,
@@ -399,7 +474,45 @@ pub fn vacuum_server_core(config: &Configuration) {
     };
 }
 
-pub async fn domain_rename_core(config: &Configuration) {
+fn print_domain_rename_plan(plan: &DomainRenamePlan) {
+    eprintln!(
+        "Renaming the domain from {} to {} will:",
+        plan.old_domain_name, plan.new_domain_name
+    );
+    eprintln!("  regenerate the spn of {} entries", plan.spn_entries);
+    for o in &plan.origins {
+        eprintln!(
+            "  change {} of oauth2 resource server {} from {} to {}",
+            o.attr, o.rs_name, o.old, o.new
+        );
+    }
+    if plan.sessions > 0 || plan.api_tokens > 0 {
+        eprintln!(
+            "⚠️  invalidate {} sessions and {} api tokens issued with the spns of {} - their users must authenticate again and new tokens must be issued",
+            plan.sessions, plan.api_tokens, plan.old_domain_name
+        );
+    }
+    if plan.passkeys > 0 {
+        eprintln!(
+            "⚠️  leave {} passkeys and device keys unusable, as they are registered to {} - their users must enrol new ones",
+            plan.passkeys, plan.old_domain_name
+        );
+    }
+}
+
+/// Ask the operator to confirm on stdin.
+fn confirm(prompt: &str) -> bool {
+    eprint!("{} [y/N] ", prompt);
+    let mut answer = String::new();
+    match std::io::stdin().read_line(&mut answer) {
+        Ok(_) => matches!(answer.trim(), "y" | "Y" | "yes"),
+        Err(_) => false,
+    }
+}
+
+/// Rename the domain to the configured domain name. The changes are shown, and must be
+/// confirmed unless `assume_yes`, before they are committed in one transaction.
+pub async fn domain_rename_core(config: &Configuration, assume_yes: bool) {
     let schema = match Schema::new() {
         Ok(s) => s,
         Err(e) => {
@@ -449,11 +562,22 @@ pub async fn domain_rename_core(config: &Configuration) {
     }
 
     let mut qs_write = qs.write(duration_from_epoch_now()).await;
-    let r = qs_write
-        .domain_rename(new_domain_name)
-        .and_then(|_| qs_write.commit());
+    let plan = match qs_write.domain_rename(new_domain_name) {
+        Ok(plan) => plan,
+        Err(e) => {
+            error!("Domain Rename Failed - Rollback has occured: {:?}", e);
+            std::process::exit(1);
+        }
+    };
 
-    match r {
+    print_domain_rename_plan(&plan);
+    if !assume_yes && !confirm("Commit the domain rename?") {
+        // Dropping the transaction discards every change.
+        info!("Domain Rename cancelled, nothing was changed.");
+        return;
+    }
+
+    match qs_write.commit() {
         Ok(_) => info!("Domain Rename Success!"),
         Err(e) => {
             error!("Domain Rename Failed - Rollback has occured: {:?}", e);
@@ -471,33 +595,139 @@ pub async fn verify_server_core(config: &Configuration) {
             return;
         }
     };
-    // Setup the be
-    let be = match setup_backend(config, &schema_mem) {
+    // Setup the be. Verification must not change what it verifies.
+    let be = match setup_backend_readonly(config, &schema_mem) {
         Ok(be) => be,
         Err(e) => {
             error!("Failed to setup BE: {:?}", e);
-            return;
+            std::process::exit(1);
         }
     };
     let server = QueryServer::new(be, schema_mem, config.domain.clone());
 
     // Run verifications.
-    let r = server.verify().await;
-
-    if r.is_empty() {
-        eprintln!("Verification passed!");
-        std::process::exit(0);
-    } else {
-        for er in r {
-            error!("{:?}", er);
+    let errors: Vec<ConsistencyError> = server
+        .verify()
+        .await
+        .into_iter()
+        .filter_map(Result::err)
+        .collect();
+
+    match config.output_mode {
+        ConsoleOutputMode::JSON => {
+            println!(
+                "{}",
+                serde_json::json!({ "passed": errors.is_empty(), "errors": errors })
+            );
+        }
+        ConsoleOutputMode::Text => {
+            if errors.is_empty() {
+                eprintln!("Verification passed!");
+            }
+            for er in &errors {
+                error!("{:?}", er);
+            }
         }
-        std::process::exit(1);
     }
+    std::process::exit(if errors.is_empty() { 0 } else { 1 });
 
     // Now add IDM server verifications?
 }
 
+/// Submit a task to the admin socket of a running server. This is `None` if the server is not
+/// running, or has no admin socket.
+#[cfg(not(target_family = "windows"))]
+async fn submit_admin_task_core(
+    config: &Configuration,
+    req: AdminTaskRequest,
+) -> Option<AdminTaskResponse> {
+    let path = config.adminbindpath.as_ref()?;
+    match submit_admin_task(path, req).await {
+        Ok(resp) => Some(resp),
+        Err(e) => {
+            debug!(?e, "Unable to reach the admin socket {}", path);
+            None
+        }
+    }
+}
+
+/// Run a task on a running server through its admin socket, exiting on failure.
+#[cfg(not(target_family = "windows"))]
+pub async fn admin_task_core(config: &Configuration, req: AdminTaskRequest) {
+    match submit_admin_task_core(config, req).await {
+        Some(AdminTaskResponse::Success) => println!("Success"),
+        Some(AdminTaskResponse::Error(e)) => {
+            error!("Admin task failed -> {:?}", e);
+            std::process::exit(1);
+        }
+        Some(resp) => {
+            error!("Unexpected admin task response -> {:?}", resp);
+            std::process::exit(1);
+        }
+        None => {
+            error!(
+                "Unable to reach the admin socket, is the server running with adminbindpath set?"
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Follow the log of a running server through its admin socket, printing the lines that match
+/// `filter`.
+#[cfg(not(target_family = "windows"))]
+pub async fn tail_core(config: &Configuration, filter: String) {
+    let path = match config.adminbindpath.as_ref() {
+        Some(path) => path,
+        None => {
+            error!("Unable to tail the log, adminbindpath is not set");
+            std::process::exit(1);
+        }
+    };
+    if let Err(e) = tail_admin_socket(path, filter, |line| println!("{}", line)).await {
+        error!(
+            ?e,
+            "Unable to tail the log through the admin socket {}", path
+        );
+        std::process::exit(1);
+    }
+}
+
 pub async fn recover_account_core(config: &Configuration, name: &str) {
+    // A running server holds the database, so it must recover the account itself.
+    #[cfg(not(target_family = "windows"))]
+    match submit_admin_task_core(
+        config,
+        AdminTaskRequest::RecoverAccount {
+            name: name.to_string(),
+        },
+    )
+    .await
+    {
+        Some(AdminTaskResponse::RecoverAccount { password }) => {
+            println!(
+                "{}",
+                AccountChangeMessage {
+                    output_mode: config.output_mode,
+                    status: MessageStatus::Success,
+                    src_user: String::from("admin socket invocation"),
+                    dest_user: name.to_string(),
+                    result: password,
+                    action: String::from("recover_account password"),
+                }
+            );
+            return;
+        }
+        Some(resp) => {
+            error!(
+                "Account recovery through the admin socket failed -> {:?}",
+                resp
+            );
+            std::process::exit(1);
+        }
+        None => {}
+    }
+
     let schema = match Schema::new() {
         Ok(s) => s,
         Err(e) => {
@@ -556,6 +786,8 @@ pub async fn recover_account_core(config: &Configuration, name: &str) {
 #[derive(Clone, Debug)]
 pub enum CoreAction {
     Shutdown,
+    /// Reload the certificates of the HTTPS listener from disk.
+    ReloadTls,
 }
 
 pub struct CoreHandle {
@@ -567,6 +799,12 @@ pub struct CoreHandle {
 }
 
 impl CoreHandle {
+    pub fn reload_tls(&self) {
+        if self.tx.send(CoreAction::ReloadTls).is_err() {
+            eprintln!("No receivers acked TLS reload request.");
+        }
+    }
+
     pub async fn shutdown(&mut self) {
         if let Err(_) = self.tx.send(CoreAction::Shutdown) {
             eprintln!("No receivers acked shutdown request. Treating as unclean.");
@@ -711,7 +949,8 @@ pub async fn create_server_core(
     let server_read_ref = QueryServerReadV1::start_static(idms_arc.clone(), ldap_arc.clone());
 
     // Create the server async write entry point.
-    let server_write_ref = QueryServerWriteV1::start_static(idms_arc.clone());
+    let repl_join_supplier = repl::ReplJoinSupplier::new(&config)?;
+    let server_write_ref = QueryServerWriteV1::start_static(idms_arc.clone(), repl_join_supplier);
 
     let delayed_handle = tokio::spawn(async move {
         loop {
@@ -719,6 +958,7 @@ pub async fn create_server_core(
                 Ok(action) = broadcast_rx.recv() => {
                     match action {
                         CoreAction::Shutdown => break,
+                        CoreAction::ReloadTls => {}
                     }
                 }
                 delayed = idms_delayed.next() => {
@@ -734,7 +974,12 @@ pub async fn create_server_core(
     });
 
     // Setup timed events associated to the write thread
-    let interval_handle = IntervalActor::start(server_write_ref, broadcast_tx.subscribe());
+    let interval_handle = IntervalActor::start(
+        server_write_ref,
+        config.changelog_retention,
+        config.audit_log_retention,
+        broadcast_tx.subscribe(),
+    );
     // Setup timed events associated to the read thread
     let maybe_backup_handle = match &config.online_backup {
         Some(cfg) => {
@@ -748,6 +993,44 @@ pub async fn create_server_core(
         }
     };
 
+    let repl_join_token = match &config.repl_join_token {
+        Some(token) => Some(ReplJoinToken::decode(token).map_err(|e| {
+            error!("Invalid repl_join_token -> {:?}", e);
+        })?),
+        None => None,
+    };
+
+    // A join token is all that is needed to consume changes from its supplier.
+    let repl_consumer = match (&config.replication_consumer, &repl_join_token) {
+        (Some(cfg), Some(token)) if !cfg.supplier.eq_ignore_ascii_case(&token.supplier) => {
+            error!(
+                "replication_consumer supplier {} does not match the supplier of repl_join_token {}",
+                cfg.supplier, token.supplier
+            );
+            return Err(());
+        }
+        (Some(cfg), _) => Some(cfg.clone()),
+        (None, Some(token)) => Some(ReplicationConsumer::from_join_token(token)),
+        (None, None) => None,
+    };
+
+    let maybe_repl_consumer_handle = match &repl_consumer {
+        Some(cfg) if !config_test => {
+            let handle = IntervalActor::start_replication_consumer(
+                server_write_ref,
+                cfg,
+                config.tls_config.as_ref(),
+                repl_join_token,
+                broadcast_tx.subscribe(),
+            )?;
+            Some(handle)
+        }
+        _ => {
+            debug!("Replication consumer not requested, skipping");
+            None
+        }
+    };
+
     // If we have been requested to init LDAP, configure it now.
     let maybe_ldap_acceptor_handle = match &config.ldapaddress {
         Some(la) => {
@@ -778,6 +1061,65 @@ pub async fn create_server_core(
         }
     };
 
+    let maybe_ldap_starttls_acceptor_handle = match &config.ldapstarttlsaddress {
+        Some(la) => {
+            let opt_ldap_tls_params = match setup_tls(&config) {
+                Ok(t) => t,
+                Err(e) => {
+                    error!("Failed to configure LDAP StartTLS parameters -> {:?}", e);
+                    return Err(());
+                }
+            };
+            if !config_test {
+                // ⚠️  only start the sockets and listeners in non-config-test modes.
+                let h = ldaps::create_ldap_starttls_server(
+                    la.as_str(),
+                    opt_ldap_tls_params,
+                    server_read_ref,
+                    broadcast_tx.subscribe(),
+                )
+                .await?;
+                Some(h)
+            } else {
+                None
+            }
+        }
+        None => {
+            debug!("LDAP StartTLS not requested, skipping");
+            None
+        }
+    };
+
+    let maybe_repl_acceptor_handle = match &config.repladdress {
+        Some(ra) => {
+            let opt_repl_tls_params = match setup_repl_tls(&config) {
+                Ok(t) => t,
+                Err(e) => {
+                    error!("Failed to configure replication TLS parameters -> {:?}", e);
+                    return Err(());
+                }
+            };
+            if !config_test {
+                // ⚠️  only start the sockets and listeners in non-config-test modes.
+                let h = repl::create_repl_server(
+                    ra.as_str(),
+                    opt_repl_tls_params,
+                    server_read_ref,
+                    server_write_ref,
+                    broadcast_tx.subscribe(),
+                )
+                .await?;
+                Some(h)
+            } else {
+                None
+            }
+        }
+        None => {
+            debug!("Replication listener not requested, skipping");
+            None
+        }
+    };
+
     // TODO: Remove these when we go to auth bearer!
     // Copy the max size
     let _secure_cookies = config.secure_cookies;
@@ -795,6 +1137,9 @@ pub async fn create_server_core(
             config.tls_config.as_ref(),
             config.role,
             config.trust_x_forward_for,
+            config.rate_limit,
+            config.cors.as_ref(),
+            config.maximum_request_size_bytes,
             &cookie_key,
             jws_signer,
             status_ref,
@@ -807,16 +1152,52 @@ pub async fn create_server_core(
         Some(h)
     };
 
+    #[cfg(not(target_family = "windows"))]
+    let maybe_admin_socket_handle = match &config.adminbindpath {
+        Some(path) if !config_test => {
+            let h = admin::create_admin_socket(
+                path,
+                server_read_ref,
+                server_write_ref,
+                config.online_backup.clone(),
+                broadcast_tx.clone(),
+            )
+            .await?;
+            Some(h)
+        }
+        _ => {
+            debug!("Admin socket not requested, skipping");
+            None
+        }
+    };
+
     let mut handles = vec![interval_handle, delayed_handle];
 
+    #[cfg(not(target_family = "windows"))]
+    if let Some(admin_handle) = maybe_admin_socket_handle {
+        handles.push(admin_handle)
+    }
+
     if let Some(backup_handle) = maybe_backup_handle {
         handles.push(backup_handle)
     }
 
+    if let Some(repl_consumer_handle) = maybe_repl_consumer_handle {
+        handles.push(repl_consumer_handle)
+    }
+
     if let Some(ldap_handle) = maybe_ldap_acceptor_handle {
         handles.push(ldap_handle)
     }
 
+    if let Some(ldap_handle) = maybe_ldap_starttls_acceptor_handle {
+        handles.push(ldap_handle)
+    }
+
+    if let Some(repl_handle) = maybe_repl_acceptor_handle {
+        handles.push(repl_handle)
+    }
+
     if let Some(http_handle) = maybe_http_acceptor_handle {
         handles.push(http_handle)
     }
@@ -0,0 +1,453 @@
+//! The dedicated replication listener and the client that consumers use to reach it.
+//! Partners authenticate each other with mutual TLS, where each side must present a
+//! certificate that is pinned on a replication agreement. Messages are cbor encoded
+//! and framed with a length prefix.
+
+use std::fs;
+use std::io::{Error as IoError, ErrorKind};
+use std::marker::PhantomData;
+use std::net;
+use std::pin::Pin;
+use std::str::FromStr;
+
+use bytes::{Buf, BufMut, BytesMut};
+use futures_util::sink::SinkExt;
+use futures_util::stream::StreamExt;
+use http_types::Url;
+use kanidmd_lib::prelude::*;
+use kanidmd_lib::repl::agreement::ReplAgreement;
+use kanidmd_lib::repl::proto::{
+    ReplIncrementalContext, ReplProtoRequest, ReplProtoResponse, ReplRefreshContext,
+    ReplRefreshRequest, ReplRuvRange,
+};
+use openssl::hash::MessageDigest;
+use openssl::ssl::{Ssl, SslAcceptorBuilder, SslConnector, SslContext, SslRef};
+use openssl::x509::X509;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
+use tokio_openssl::SslStream;
+use tokio_util::codec::{Decoder, Encoder, Framed};
+
+use crate::actors::v1_read::QueryServerReadV1;
+use crate::actors::v1_write::QueryServerWriteV1;
+use crate::config::Configuration;
+use crate::CoreAction;
+
+/// The largest message we will accept from a partner. A full set of changes can be
+/// large, but we must not allow a partner to make us allocate without limit.
+const REPL_MAX_FRAME_BYTES: usize = 256 * 1024 * 1024;
+
+/// The length prefix of each frame.
+const REPL_HEADER_BYTES: usize = 4;
+
+pub(crate) struct ReplCodec<D, E> {
+    _d: PhantomData<D>,
+    _e: PhantomData<E>,
+}
+
+impl<D, E> ReplCodec<D, E> {
+    fn new() -> Self {
+        ReplCodec {
+            _d: PhantomData,
+            _e: PhantomData,
+        }
+    }
+}
+
+impl<D: DeserializeOwned, E> Decoder for ReplCodec<D, E> {
+    type Error = IoError;
+    type Item = D;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if src.len() < REPL_HEADER_BYTES {
+            return Ok(None);
+        }
+
+        let mut header = [0u8; REPL_HEADER_BYTES];
+        header.copy_from_slice(&src[..REPL_HEADER_BYTES]);
+        let length = u32::from_be_bytes(header) as usize;
+
+        if length > REPL_MAX_FRAME_BYTES {
+            error!(length, "Replication message exceeds the maximum frame size");
+            return Err(IoError::new(ErrorKind::InvalidData, "Frame too large"));
+        }
+
+        if src.len() < REPL_HEADER_BYTES + length {
+            src.reserve(REPL_HEADER_BYTES + length - src.len());
+            return Ok(None);
+        }
+
+        src.advance(REPL_HEADER_BYTES);
+        let data = src.split_to(length);
+
+        serde_cbor::from_slice(&data).map(Some).map_err(|e| {
+            error!(?e, "Replication message decoding error");
+            IoError::new(ErrorKind::InvalidData, "CBOR decode error")
+        })
+    }
+}
+
+impl<D, E: Serialize> Encoder<E> for ReplCodec<D, E> {
+    type Error = IoError;
+
+    fn encode(&mut self, msg: E, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let data = serde_cbor::to_vec(&msg).map_err(|e| {
+            error!(?e, "Replication message encoding error");
+            IoError::new(ErrorKind::InvalidData, "CBOR encode error")
+        })?;
+
+        if data.len() > REPL_MAX_FRAME_BYTES {
+            error!(
+                length = data.len(),
+                "Replication message exceeds the maximum frame size"
+            );
+            return Err(IoError::new(ErrorKind::InvalidData, "Frame too large"));
+        }
+
+        dst.reserve(REPL_HEADER_BYTES + data.len());
+        dst.put_u32(data.len() as u32);
+        dst.put_slice(&data);
+        Ok(())
+    }
+}
+
+type ReplServerCodec = ReplCodec<ReplProtoRequest, ReplProtoResponse>;
+type ReplClientCodec = ReplCodec<ReplProtoResponse, ReplProtoRequest>;
+
+/// The hex encoded sha256 fingerprint of the certificate the partner presented.
+fn peer_cert_sha256(ssl: &SslRef) -> Option<String> {
+    ssl.peer_certificate()
+        .and_then(|cert| cert.digest(MessageDigest::sha256()).ok())
+        .map(|digest| hex::encode(&*digest))
+}
+
+/// How a new server reaches this server's replication listener and recognises its
+/// certificate, as written in the join tokens this server creates.
+#[derive(Debug, Clone)]
+pub struct ReplJoinSupplier {
+    pub address: String,
+    pub cert_sha256: String,
+}
+
+impl ReplJoinSupplier {
+    /// The listener is usually bound to all addresses, so the address is made from the host
+    /// of our origin and the port of the listener.
+    pub(crate) fn new(config: &Configuration) -> Result<Option<Self>, ()> {
+        let (repladdress, tls_config) = match (&config.repladdress, &config.tls_config) {
+            (Some(repladdress), Some(tls_config)) => (repladdress, tls_config),
+            _ => return Ok(None),
+        };
+
+        let port = net::SocketAddr::from_str(repladdress)
+            .map(|addr| addr.port())
+            .map_err(|e| {
+                error!(
+                    "Could not parse replication server address {} -> {:?}",
+                    repladdress, e
+                );
+            })?;
+
+        let host = Url::parse(&config.origin)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+            .ok_or_else(|| {
+                error!("Could not find a host in origin {}", config.origin);
+            })?;
+
+        let cert_sha256 = fs::read(&tls_config.chain)
+            .ok()
+            .and_then(|pem| X509::from_pem(&pem).ok())
+            .and_then(|cert| cert.digest(MessageDigest::sha256()).ok())
+            .map(|digest| hex::encode(&*digest))
+            .ok_or_else(|| {
+                error!(
+                    "Could not read a certificate from tls_chain {}",
+                    tls_config.chain
+                );
+            })?;
+
+        Ok(Some(ReplJoinSupplier {
+            address: format!("repl://{}:{}", host, port),
+            cert_sha256,
+        }))
+    }
+}
+
+async fn repl_client_process(
+    tcpstream: TcpStream,
+    tls_ctx: SslContext,
+    client_address: net::SocketAddr,
+    qe_r_ref: &'static QueryServerReadV1,
+    qe_w_ref: &'static QueryServerWriteV1,
+) {
+    let mut tlsstream =
+        match Ssl::new(&tls_ctx).and_then(|tls_obj| SslStream::new(tls_obj, tcpstream)) {
+            Ok(ta) => ta,
+            Err(e) => {
+                error!("Replication TLS setup error -> {:?}", e);
+                return;
+            }
+        };
+
+    if let Err(e) = SslStream::accept(Pin::new(&mut tlsstream)).await {
+        error!("Replication TLS accept error -> {:?}", e);
+        return;
+    };
+
+    // The acceptor requires a client certificate, so this can only fail if the
+    // digest can't be computed.
+    let cert_sha256 = match peer_cert_sha256(tlsstream.ssl()) {
+        Some(fp) => fp,
+        None => {
+            security_info!(
+                client_ip = %client_address.ip(),
+                "Replication partner did not present a certificate"
+            );
+            return;
+        }
+    };
+
+    security_info!(
+        client_ip = %client_address.ip(),
+        client_port = %client_address.port(),
+        %cert_sha256,
+        "Replication partner connected"
+    );
+
+    let mut framed = Framed::new(tlsstream, ReplServerCodec::new());
+
+    while let Some(Ok(req)) = framed.next().await {
+        let eventid = sketching::tracing_forest::id();
+        let resp = match req {
+            ReplProtoRequest::V1Changes(ctx_ruv) => {
+                match qe_r_ref
+                    .handle_replsupplierchanges_pinned(cert_sha256.clone(), ctx_ruv, eventid)
+                    .await
+                {
+                    Ok(ctx) => ReplProtoResponse::V1Changes(ctx),
+                    Err(e) => ReplProtoResponse::V1Error(e),
+                }
+            }
+            ReplProtoRequest::V1Refresh(req) => {
+                match qe_r_ref
+                    .handle_replsupplierrefresh_pinned(cert_sha256.clone(), req, eventid)
+                    .await
+                {
+                    Ok(ctx) => ReplProtoResponse::V1Refresh(ctx),
+                    Err(e) => ReplProtoResponse::V1Error(e),
+                }
+            }
+            ReplProtoRequest::V1Join(secret) => {
+                match qe_w_ref
+                    .handle_repljoinaccept(cert_sha256.clone(), secret, eventid)
+                    .await
+                {
+                    Ok(()) => ReplProtoResponse::V1Join,
+                    Err(e) => ReplProtoResponse::V1Error(e),
+                }
+            }
+        };
+
+        if framed.send(resp).await.is_err() {
+            break;
+        }
+    }
+}
+
+async fn repl_acceptor(
+    listener: TcpListener,
+    tls_ctx: SslContext,
+    qe_r_ref: &'static QueryServerReadV1,
+    qe_w_ref: &'static QueryServerWriteV1,
+    mut rx: broadcast::Receiver<CoreAction>,
+) {
+    loop {
+        tokio::select! {
+            Ok(action) = rx.recv() => {
+                match action {
+                    CoreAction::Shutdown => break,
+                    CoreAction::ReloadTls => {}
+                }
+            }
+            accept_result = listener.accept() => {
+                match accept_result {
+                    Ok((tcpstream, client_socket_addr)) => {
+                        tokio::spawn(repl_client_process(tcpstream, tls_ctx.clone(), client_socket_addr, qe_r_ref, qe_w_ref));
+                    }
+                    Err(e) => {
+                        error!("Replication acceptor error, continuing -> {:?}", e);
+                    }
+                }
+            }
+        }
+    }
+    info!("Stopped ReplAcceptorActor");
+}
+
+pub(crate) async fn create_repl_server(
+    address: &str,
+    opt_tls_params: Option<SslAcceptorBuilder>,
+    qe_r_ref: &'static QueryServerReadV1,
+    qe_w_ref: &'static QueryServerWriteV1,
+    rx: broadcast::Receiver<CoreAction>,
+) -> Result<tokio::task::JoinHandle<()>, ()> {
+    let addr = net::SocketAddr::from_str(address).map_err(|e| {
+        error!(
+            "Could not parse replication server address {} -> {:?}",
+            address, e
+        );
+    })?;
+
+    let listener = TcpListener::bind(&addr).await.map_err(|e| {
+        error!(
+            "Could not bind to replication server address {} -> {:?}",
+            address, e
+        );
+    })?;
+
+    let repl_acceptor_handle = match opt_tls_params {
+        Some(tls_params) => {
+            info!("Starting replication interface repl://{} ...", address);
+            let tls_ctx = tls_params.build().into_context();
+            tokio::spawn(repl_acceptor(listener, tls_ctx, qe_r_ref, qe_w_ref, rx))
+        }
+        None => {
+            error!("The server won't run without TLS!");
+            return Err(());
+        }
+    };
+
+    info!("Created replication interface");
+    Ok(repl_acceptor_handle)
+}
+
+/// A consumer's connection to a supplier's replication listener.
+pub(crate) struct ReplClient {
+    framed: Framed<SslStream<TcpStream>, ReplClientCodec>,
+}
+
+impl ReplClient {
+    /// Connect to a supplier, which must present a certificate that is pinned by its
+    /// replication agreement.
+    pub(crate) async fn connect(
+        connector: &SslConnector,
+        supplier: &str,
+        agreement: &ReplAgreement,
+    ) -> Result<Self, OperationError> {
+        let address = supplier.trim_start_matches("repl://").trim_end_matches('/');
+        let host = address
+            .rsplit_once(':')
+            .map(|(host, _port)| host)
+            .unwrap_or(address)
+            .trim_start_matches('[')
+            .trim_end_matches(']');
+
+        let tcpstream = TcpStream::connect(address).await.map_err(|e| {
+            error!(?e, "Unable to connect to replication supplier {}", address);
+            OperationError::InvalidState
+        })?;
+
+        // The supplier is authenticated by its pinned certificate rather than by name.
+        let mut tlsstream = connector
+            .configure()
+            .and_then(|mut cfg| {
+                cfg.set_verify_hostname(false);
+                cfg.into_ssl(host)
+            })
+            .and_then(|tls_obj| SslStream::new(tls_obj, tcpstream))
+            .map_err(|e| {
+                error!(?e, "Replication TLS setup error");
+                OperationError::InvalidState
+            })?;
+
+        SslStream::connect(Pin::new(&mut tlsstream))
+            .await
+            .map_err(|e| {
+                error!(?e, "Replication TLS connect error");
+                OperationError::InvalidState
+            })?;
+
+        match peer_cert_sha256(tlsstream.ssl()) {
+            Some(fp) if agreement.is_pinned(&fp) => {}
+            fp => {
+                security_info!(
+                    cert_sha256 = ?fp,
+                    agreement = %agreement.name,
+                    "Replication supplier certificate is not pinned, disconnecting"
+                );
+                return Err(OperationError::AccessDenied);
+            }
+        }
+
+        Ok(ReplClient {
+            framed: Framed::new(tlsstream, ReplClientCodec::new()),
+        })
+    }
+
+    async fn request(
+        &mut self,
+        req: ReplProtoRequest,
+    ) -> Result<ReplProtoResponse, OperationError> {
+        self.framed.send(req).await.map_err(|e| {
+            error!(?e, "Unable to send request to replication supplier");
+            OperationError::InvalidState
+        })?;
+
+        match self.framed.next().await {
+            Some(Ok(ReplProtoResponse::V1Error(e))) => {
+                error!(?e, "Replication supplier returned an error");
+                Err(e)
+            }
+            Some(Ok(resp)) => Ok(resp),
+            Some(Err(e)) => {
+                error!(?e, "Replication supplier sent an invalid response");
+                Err(OperationError::InvalidState)
+            }
+            None => {
+                error!("Replication supplier closed the connection");
+                Err(OperationError::InvalidState)
+            }
+        }
+    }
+
+    /// Request the changes this consumer is missing.
+    pub(crate) async fn changes(
+        &mut self,
+        ctx_ruv: ReplRuvRange,
+    ) -> Result<ReplIncrementalContext, OperationError> {
+        match self.request(ReplProtoRequest::V1Changes(ctx_ruv)).await? {
+            ReplProtoResponse::V1Changes(ctx) => Ok(ctx),
+            _ => {
+                error!("Replication supplier sent an unexpected response");
+                Err(OperationError::InvalidState)
+            }
+        }
+    }
+
+    /// Present the secret of a join token, so that the supplier pins our certificate.
+    pub(crate) async fn join(&mut self, secret: String) -> Result<(), OperationError> {
+        match self.request(ReplProtoRequest::V1Join(secret)).await? {
+            ReplProtoResponse::V1Join => Ok(()),
+            _ => {
+                error!("Replication supplier sent an unexpected response");
+                Err(OperationError::InvalidState)
+            }
+        }
+    }
+
+    /// Request the next chunk of a full refresh.
+    pub(crate) async fn refresh(
+        &mut self,
+        req: ReplRefreshRequest,
+    ) -> Result<ReplRefreshContext, OperationError> {
+        match self.request(ReplProtoRequest::V1Refresh(req)).await? {
+            ReplProtoResponse::V1Refresh(ctx) => Ok(ctx),
+            _ => {
+                error!("Replication supplier sent an unexpected response");
+                Err(OperationError::InvalidState)
+            }
+        }
+    }
+}
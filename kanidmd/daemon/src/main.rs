@@ -22,19 +22,31 @@ use std::path::{Path, PathBuf};
 use std::process::exit;
 
 use clap::{Args, Parser, Subcommand};
-use kanidmd_core::config::{Configuration, OnlineBackup, ServerRole};
+use kanidmd_core::config::{
+    Configuration, Cors, OnlineBackup, RateLimit, ReplicationConsumer, ServerRole,
+};
+#[cfg(not(target_family = "windows"))]
+use kanidmd_core::{admin::AdminTaskRequest, admin_task_core, tail_core};
 use kanidmd_core::{
-    backup_server_core, create_server_core, dbscan_get_id2entry_core, dbscan_list_id2entry_core,
-    dbscan_list_index_analysis_core, dbscan_list_index_core, dbscan_list_indexes_core,
-    domain_rename_core, recover_account_core, reindex_server_core, restore_server_core,
-    vacuum_server_core, verify_server_core,
+    backup_server_core, create_server_core, dbscan_get_id2entry_core, dbscan_get_uuid_core,
+    dbscan_list_id2entry_core, dbscan_list_index_analysis_core, dbscan_list_index_core,
+    dbscan_list_indexes_core, domain_rename_core, recover_account_core, reindex_server_core,
+    restore_server_core, vacuum_server_core, verify_server_core,
 };
 #[cfg(not(target_family = "windows"))]
+use kanidmd_lib::prelude::Uuid;
 use kanidmd_lib::utils::file_permissions_readonly;
 use serde::Deserialize;
+use sketching::filter::LogFilter;
+use sketching::otel::{otel_layer, otel_shutdown};
+use sketching::security::SecurityEventLayer;
+use sketching::sink::LogSink;
+use sketching::slow::SlowOperationLog;
+use sketching::tail::TailLayer;
 use sketching::tracing_forest::traits::*;
 use sketching::tracing_forest::util::*;
 use sketching::tracing_forest::{self};
+use sketching::LogFormat;
 #[cfg(not(target_family = "windows"))] // not needed for windows builds
 use users::{get_current_gid, get_current_uid, get_effective_gid, get_effective_uid};
 #[cfg(target_family = "windows")] // for windows builds
@@ -42,18 +54,31 @@ use whoami;
 
 include!("./opt.rs");
 
+mod setup;
+
 #[derive(Debug, Deserialize)]
 struct ServerConfig {
     pub bindaddress: Option<String>,
     pub ldapbindaddress: Option<String>,
+    pub ldapstarttlsbindaddress: Option<String>,
+    pub replbindaddress: Option<String>,
+    pub adminbindpath: Option<String>,
     pub trust_x_forward_for: Option<bool>,
-    // pub threads: Option<usize>,
+    pub threads: Option<usize>,
     pub db_path: String,
     pub db_fs_type: Option<String>,
     pub db_arc_size: Option<usize>,
+    pub db_seal_key_path: Option<String>,
     pub tls_chain: Option<String>,
     pub tls_key: Option<String>,
     pub online_backup: Option<OnlineBackup>,
+    pub replication_consumer: Option<ReplicationConsumer>,
+    pub repl_join_token: Option<String>,
+    pub changelog_retention: Option<u64>,
+    pub audit_log_retention: Option<u64>,
+    pub rate_limit: Option<RateLimit>,
+    pub cors: Option<Cors>,
+    pub maximum_request_size_bytes: Option<usize>,
     pub domain: String,
     pub origin: String,
     #[serde(default)]
@@ -74,6 +99,44 @@ impl ServerConfig {
     }
 }
 
+/// The logging options of the config, which are needed before the logger starts.
+#[derive(Debug, Default, Deserialize)]
+struct LogConfig {
+    #[serde(default)]
+    log_format: LogFormat,
+    #[serde(default)]
+    log_sink: LogSink,
+    log_filter: Option<String>,
+    otel_endpoint: Option<String>,
+    security_log_path: Option<String>,
+    #[serde(default)]
+    slow_operation_log: SlowOperationLog,
+}
+
+impl LogConfig {
+    /// An unreadable config is ignored here, since that is reported when it is read in full.
+    fn new<P: AsRef<Path>>(config_path: P) -> Self {
+        let config = std::fs::read_to_string(config_path)
+            .ok()
+            .and_then(|contents| toml::from_str::<toml::Value>(contents.as_str()).ok());
+        match config {
+            Some(v) => v.try_into().unwrap_or_else(|e| {
+                eprintln!("Config Parse failure, invalid logging options {:?}", e);
+                std::process::exit(1);
+            }),
+            None => LogConfig::default(),
+        }
+    }
+
+    /// RUST_LOG takes precedence over the config, so that a single run can be debugged.
+    fn log_filter(&self) -> String {
+        std::env::var(EnvFilter::DEFAULT_ENV)
+            .ok()
+            .or_else(|| self.log_filter.clone())
+            .unwrap_or_else(|| "info".to_string())
+    }
+}
+
 impl KanidmdOpt {
     fn commonopt(&self) -> &CommonOpt {
         match self {
@@ -87,6 +150,10 @@ impl KanidmdOpt {
             }
             | KanidmdOpt::DbScan {
                 commands: DbScanOpt::ListIndexAnalysis(sopt),
+            }
+            | KanidmdOpt::ReloadTls(sopt)
+            | KanidmdOpt::Database {
+                commands: DbCommands::OnlineBackup(sopt),
             } => &sopt,
             KanidmdOpt::Database {
                 commands: DbCommands::Backup(bopt),
@@ -94,7 +161,10 @@ impl KanidmdOpt {
             KanidmdOpt::Database {
                 commands: DbCommands::Restore(ropt),
             } => &ropt.commonopts,
+            KanidmdOpt::Setup(sopt) => &sopt.commonopts,
             KanidmdOpt::RecoverAccount(ropt) => &ropt.commonopts,
+            KanidmdOpt::SetLogFilter(lopt) => &lopt.commonopts,
+            KanidmdOpt::Tail(topt) => &topt.commonopts,
             KanidmdOpt::DbScan {
                 commands: DbScanOpt::ListIndex(dopt),
             } => &dopt.commonopts,
@@ -102,9 +172,12 @@ impl KanidmdOpt {
             KanidmdOpt::DbScan {
                 commands: DbScanOpt::GetId2Entry(dopt),
             } => &dopt.commonopts,
+            KanidmdOpt::DbScan {
+                commands: DbScanOpt::GetUuid(dopt),
+            } => &dopt.commonopts,
             KanidmdOpt::DomainSettings {
-                commands: DomainSettingsCmds::DomainChange(sopt),
-            } => &sopt,
+                commands: DomainSettingsCmds::DomainChange(dopt),
+            } => &dopt.commonopts,
             KanidmdOpt::Database {
                 commands: DbCommands::Verify(sopt),
             }
@@ -166,16 +239,41 @@ fn get_user_details_windows() {
 
 #[tokio::main(flavor = "multi_thread")]
 async fn main() {
+    let log_config = LogConfig::new(&KanidmdParser::parse().commands.commonopt().config_path);
+
     tracing_forest::worker_task()
         .set_global(true)
         .set_tag(sketching::event_tagger)
         // Fall back to stderr
         .map_sender(|sender| sender.or_stderr())
+        .map_receiver(|_printer| {
+            log_config
+                .log_sink
+                .processor(log_config.log_format)
+                .unwrap_or_else(|e| {
+                    eprintln!("Failed to open log sink {:?} - {:?}", log_config.log_sink, e);
+                    std::process::exit(1);
+                })
+                .with_slow_operation_log(log_config.slow_operation_log.clone())
+        })
         .build_on(|subscriber| subscriber
-            .with(EnvFilter::try_from_default_env()
-                .or_else(|_| EnvFilter::try_new("info"))
-                .expect("Failed to init envfilter")
+            .with(LogFilter::new(&log_config.log_filter())
+                .or_else(|_| LogFilter::new("info"))
+                .expect("Failed to init log filter")
             )
+            .with(log_config.otel_endpoint.as_deref().map(|endpoint| {
+                otel_layer(endpoint, "kanidmd").unwrap_or_else(|e| {
+                    eprintln!("Failed to start OpenTelemetry export to {} - {:?}", endpoint, e);
+                    std::process::exit(1);
+                })
+            }))
+            .with(log_config.security_log_path.as_deref().map(|path| {
+                SecurityEventLayer::new(path).unwrap_or_else(|e| {
+                    eprintln!("Failed to open security log {} - {:?}", path, e);
+                    std::process::exit(1);
+                })
+            }))
+            .with(TailLayer::new(log_config.log_format))
         )
         .on(async {
             // Get information on the windows username
@@ -195,6 +293,13 @@ async fn main() {
                 exit(0);
             };
 
+            // The configuration of a new server is written before it is read as usual.
+            if let KanidmdOpt::Setup(sopt) = &opt.commands {
+                if setup::write_setup_config(&sopt.commonopts.config_path, sopt).is_err() {
+                    exit(1);
+                }
+            }
+
             let mut config = Configuration::new();
             // Check the permissions are OK.
             #[cfg(target_family = "unix")]
@@ -269,9 +374,11 @@ async fn main() {
             config.update_origin(&sconfig.origin.as_str());
             config.update_domain(&sconfig.domain.as_str());
             config.update_db_arc_size(sconfig.db_arc_size);
+            config.update_db_seal_key_path(&sconfig.db_seal_key_path);
             config.update_role(sconfig.role);
             config.update_output_mode(opt.commands.commonopt().output_mode.to_owned().into());
             config.update_trust_x_forward_for(sconfig.trust_x_forward_for);
+            config.update_adminbind(&sconfig.adminbindpath);
 
             /*
             // Apply any cli overrides, normally debug level.
@@ -293,7 +400,24 @@ async fn main() {
                     config.update_tls(&sconfig.tls_chain, &sconfig.tls_key);
                     config.update_bind(&sconfig.bindaddress);
                     config.update_ldapbind(&sconfig.ldapbindaddress);
+                    config.update_ldapstarttlsbind(&sconfig.ldapstarttlsbindaddress);
+                    config.update_replbind(&sconfig.replbindaddress);
                     config.update_online_backup(&sconfig.online_backup);
+                    config.update_replication_consumer(&sconfig.replication_consumer);
+                    config.update_repl_join_token(&sconfig.repl_join_token);
+                    config.update_changelog_retention(sconfig.changelog_retention);
+                    config.update_audit_log_retention(sconfig.audit_log_retention);
+                    config.update_rate_limit(&sconfig.rate_limit);
+                    config.update_cors(&sconfig.cors);
+                    config.update_maximum_request_size(sconfig.maximum_request_size_bytes);
+                    config.update_threads(sconfig.threads);
+
+                    if let Err(errors) = config.validate() {
+                        for e in errors {
+                            eprintln!("ERROR: Invalid configuration - {}", e);
+                        }
+                        std::process::exit(1);
+                    }
 
                     if let Some(i_str) = &(sconfig.tls_chain) {
                         let i_path = PathBuf::from(i_str.as_str());
@@ -354,7 +478,8 @@ async fn main() {
                                             let sigterm = tokio::signal::unix::SignalKind::hangup();
                                             tokio::signal::unix::signal(sigterm).unwrap().recv().await
                                         } => {
-                                            // Ignore
+                                            eprintln!("Signal received, reloading TLS certificates");
+                                            sctx.reload_tls();
                                         }
                                         Some(()) = async move {
                                             let sigterm = tokio::signal::unix::SignalKind::user_defined1();
@@ -418,6 +543,49 @@ async fn main() {
                     eprintln!("Running in db verification mode ...");
                     verify_server_core(&config).await;
                 }
+                KanidmdOpt::Database {
+                    commands: DbCommands::OnlineBackup(_copt),
+                } => {
+                    eprintln!("Requesting an online backup ...");
+                    #[cfg(not(target_family = "windows"))]
+                    admin_task_core(&config, AdminTaskRequest::OnlineBackup).await;
+                    #[cfg(target_family = "windows")]
+                    eprintln!("The admin socket is not supported on windows");
+                }
+                KanidmdOpt::ReloadTls(_copt) => {
+                    eprintln!("Requesting a reload of the TLS certificates ...");
+                    #[cfg(not(target_family = "windows"))]
+                    admin_task_core(&config, AdminTaskRequest::ReloadTls).await;
+                    #[cfg(target_family = "windows")]
+                    eprintln!("The admin socket is not supported on windows");
+                }
+                KanidmdOpt::SetLogFilter(_lopt) => {
+                    eprintln!("Requesting a change of the log filter ...");
+                    #[cfg(not(target_family = "windows"))]
+                    admin_task_core(
+                        &config,
+                        AdminTaskRequest::SetLogFilter {
+                            filter: _lopt.filter.clone(),
+                        },
+                    )
+                    .await;
+                    #[cfg(target_family = "windows")]
+                    eprintln!("The admin socket is not supported on windows");
+                }
+                KanidmdOpt::Tail(_topt) => {
+                    #[cfg(not(target_family = "windows"))]
+                    tail_core(&config, _topt.filter.clone()).await;
+                    #[cfg(target_family = "windows")]
+                    eprintln!("The admin socket is not supported on windows");
+                }
+                KanidmdOpt::Setup(sopt) => {
+                    eprintln!("Initialising the database ...");
+                    recover_account_core(&config, "admin").await;
+                    eprintln!(
+                        "Setup complete - the server can now be started with: kanidmd server -c {}",
+                        sopt.commonopts.config_path.display()
+                    );
+                }
                 KanidmdOpt::RecoverAccount(raopt) => {
                     eprintln!("Running account recovery ...");
                     recover_account_core(&config, &raopt.name).await;
@@ -458,11 +626,17 @@ async fn main() {
                     eprintln!("👀 db scan - get id2 entry - {}", dopt.id);
                     dbscan_get_id2entry_core(&config, dopt.id);
                 }
+                KanidmdOpt::DbScan {
+                    commands: DbScanOpt::GetUuid(dopt),
+                } => {
+                    eprintln!("👀 db scan - get uuid - {}", dopt.uuid);
+                    dbscan_get_uuid_core(&config, dopt.uuid);
+                }
                 KanidmdOpt::DomainSettings {
-                    commands: DomainSettingsCmds::DomainChange(_dopt),
+                    commands: DomainSettingsCmds::DomainChange(dopt),
                 } => {
                     eprintln!("Running in domain name change mode ... this may take a long time ...");
-                    domain_rename_core(&config).await;
+                    domain_rename_core(&config, dopt.assume_yes).await;
                 }
                 KanidmdOpt::Database {
                     commands: DbCommands::Vacuum(_copt),
@@ -476,4 +650,8 @@ async fn main() {
             }
         })
         .await;
+
+    if log_config.otel_endpoint.is_some() {
+        otel_shutdown();
+    }
 }
@@ -35,12 +35,67 @@ struct RecoverAccountOpt {
     commonopts: CommonOpt,
 }
 
+#[derive(Debug, Args)]
+struct SetLogFilterOpt {
+    #[clap(value_parser)]
+    /// The filter directives, such as "info,[be::search]=warn".
+    filter: String,
+    #[clap(flatten)]
+    commonopts: CommonOpt,
+}
+
+#[derive(Debug, Args)]
+struct TailOpt {
+    #[clap(short, long, default_value = "trace")]
+    /// The directives that select the events to follow, such as "[auth]" or "kanidmd_lib=debug".
+    filter: String,
+    #[clap(flatten)]
+    commonopts: CommonOpt,
+}
+
+#[derive(Debug, Args)]
+struct SetupOpt {
+    #[clap(long)]
+    /// The domain name of the server, such as idm.example.com
+    domain: Option<String>,
+    #[clap(long)]
+    /// The origin that clients connect to. Defaults to https://<domain>
+    origin: Option<String>,
+    #[clap(long)]
+    /// The address to listen on. Defaults to [::]:8443
+    bindaddress: Option<String>,
+    #[clap(long)]
+    /// The path of the database. Defaults to /var/lib/kanidm/kanidm.db
+    db_path: Option<String>,
+    #[clap(long)]
+    /// The path of the TLS certificate chain
+    tls_chain: Option<String>,
+    #[clap(long)]
+    /// The path of the TLS private key
+    tls_key: Option<String>,
+    #[clap(short = 'y', long)]
+    /// Use the defaults of the options that are not given, rather than asking for them
+    non_interactive: bool,
+    #[clap(flatten)]
+    commonopts: CommonOpt,
+}
+
+#[derive(Debug, Args)]
+struct DomainRenameOpt {
+    #[clap(short = 'y', long = "yes")]
+    /// Commit the rename without asking to confirm the changes.
+    assume_yes: bool,
+    #[clap(flatten)]
+    commonopts: CommonOpt,
+}
 
 #[derive(Debug, Subcommand)]
 enum DomainSettingsCmds {
     #[clap(name = "rename")]
-    /// Change the IDM domain name
-    DomainChange(CommonOpt),
+    /// Change the IDM domain name to the domain of the server configuration (offline). The
+    /// spns, and the oauth2 origins in the old domain, are changed in one transaction after the
+    /// changes and the sessions and passkeys they invalidate are confirmed.
+    DomainChange(DomainRenameOpt),
 }
 
 #[derive(Debug, Subcommand)]
@@ -51,11 +106,16 @@ enum DbCommands {
     #[clap(name = "backup")]
     /// Backup the database content (offline)
     Backup(BackupOpt),
+    #[clap(name = "online_backup")]
+    /// Request an online backup from the running server, in the path of its online_backup
+    /// configuration. Requires adminbindpath to be configured.
+    OnlineBackup(CommonOpt),
     #[clap(name = "restore")]
     /// Restore the database content (offline)
     Restore(RestoreOpt),
     #[clap(name = "verify")]
-    /// Verify database and entity consistency.
+    /// Verify database and entity consistency without changing the database (offline). Exits
+    /// non-zero if any inconsistency is found, which "-o json" reports as json.
     Verify(CommonOpt),
     #[clap(name = "reindex")]
     /// Reindex the database (offline)
@@ -90,6 +150,14 @@ struct DbScanGetId2Entry {
     commonopts: CommonOpt,
 }
 
+#[derive(Debug, Args)]
+struct DbScanGetUuid {
+    /// The uuid of the entry to display
+    uuid: Uuid,
+    #[clap(flatten)]
+    commonopts: CommonOpt,
+}
+
 #[derive(Debug, Subcommand)]
 enum DbScanOpt {
     #[clap(name = "list_all_indexes")]
@@ -107,6 +175,10 @@ enum DbScanOpt {
     #[clap(name = "get_id2entry")]
     /// View the data of a specific entry from id2entry
     GetId2Entry(DbScanGetId2Entry),
+    #[clap(name = "get_uuid")]
+    /// View the stored data of an entry by uuid, with the index keys and the replication changes
+    /// that refer to it
+    GetUuid(DbScanGetUuid),
     #[clap(name = "list_index_analysis")]
     /// List all content of index analysis
     ListIndexAnalysis(CommonOpt),
@@ -123,12 +195,29 @@ enum KanidmdOpt {
     #[clap(name = "server")]
     /// Start the IDM Server
     Server(CommonOpt),
+    #[clap(name = "setup")]
+    /// Set up a new server - write a starter configuration to the config path, initialise the
+    /// database and print the password of the admin account
+    Setup(SetupOpt),
     #[clap(name = "configtest")]
     /// Test the IDM Server configuration, without starting network listeners.
     ConfigTest(CommonOpt),
-    #[clap(name = "recover_account")]
-    /// Recover an account's password
+    #[clap(name = "recover_account", visible_aliases=&["recover-account"])]
+    /// Recover an account's password. If the server is running this is done through its
+    /// admin socket.
     RecoverAccount(RecoverAccountOpt),
+    #[clap(name = "reload_tls")]
+    /// Request the running server to reload its TLS certificates. Requires adminbindpath
+    /// to be configured.
+    ReloadTls(CommonOpt),
+    #[clap(name = "set_log_filter")]
+    /// Change the log filter of the running server until it restarts. Security events are
+    /// always logged. Requires adminbindpath to be configured.
+    SetLogFilter(SetLogFilterOpt),
+    #[clap(name = "tail")]
+    /// Follow the log of the running server. Only the events that pass its log filter can be
+    /// followed. Requires adminbindpath to be configured.
+    Tail(TailOpt),
     // #[clap(name = "reset_server_id")]
     // ResetServerId(CommonOpt),
     #[clap(name = "db_scan")]
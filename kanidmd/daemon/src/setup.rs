@@ -0,0 +1,191 @@
+//! The first run setup of a server. This asks for the settings that have no sensible default,
+//! or takes them from the command line for automation, and writes a starter configuration. The
+//! database is then initialised from that configuration by the account recovery of admin, which
+//! prints its password.
+
+use std::fs;
+use std::io::{self, BufRead, Write};
+#[cfg(target_family = "unix")]
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+use crate::SetupOpt;
+
+const DEFAULT_BINDADDRESS: &str = "[::]:8443";
+const DEFAULT_DB_PATH: &str = "/var/lib/kanidm/kanidm.db";
+
+struct SetupAnswers {
+    domain: String,
+    origin: String,
+    bindaddress: String,
+    db_path: String,
+    tls_chain: Option<String>,
+    tls_key: Option<String>,
+}
+
+/// Ask for a value on stdin, with the default if the answer is empty.
+fn ask(prompt: &str, default: Option<&str>) -> Result<String, ()> {
+    match default {
+        Some(d) if !d.is_empty() => eprint!("{} [{}]: ", prompt, d),
+        _ => eprint!("{}: ", prompt),
+    }
+    let _ = io::stderr().flush();
+
+    let mut answer = String::new();
+    io::stdin()
+        .lock()
+        .read_line(&mut answer)
+        .map_err(|e| eprintln!("Unable to read the answer - {:?}", e))?;
+    match (answer.trim(), default) {
+        ("", Some(d)) => Ok(d.to_string()),
+        ("", None) => {
+            eprintln!("A value is required");
+            ask(prompt, default)
+        }
+        (a, _) => Ok(a.to_string()),
+    }
+}
+
+/// The value from the command line, else the answer, else the default when not interactive.
+fn answer(
+    given: &Option<String>,
+    prompt: &str,
+    default: Option<&str>,
+    interactive: bool,
+) -> Result<Option<String>, ()> {
+    match (given, default, interactive) {
+        (Some(v), _, _) => Ok(Some(v.clone())),
+        (None, _, true) => ask(prompt, default).map(Some),
+        (None, d, false) => Ok(d.map(str::to_string)),
+    }
+}
+
+fn gather(sopt: &SetupOpt) -> Result<SetupAnswers, ()> {
+    let interactive = !sopt.non_interactive;
+
+    let domain = answer(
+        &sopt.domain,
+        "The domain name of the server, such as idm.example.com",
+        None,
+        interactive,
+    )?
+    .ok_or_else(|| eprintln!("--domain is required"))?;
+    let default_origin = format!("https://{}", domain);
+    let origin = answer(
+        &sopt.origin,
+        "The origin that clients connect to",
+        Some(&default_origin),
+        interactive,
+    )?
+    .unwrap_or(default_origin);
+    let bindaddress = answer(
+        &sopt.bindaddress,
+        "The address to listen on",
+        Some(DEFAULT_BINDADDRESS),
+        interactive,
+    )?
+    .unwrap_or_else(|| DEFAULT_BINDADDRESS.to_string());
+    let db_path = answer(
+        &sopt.db_path,
+        "The path of the database",
+        Some(DEFAULT_DB_PATH),
+        interactive,
+    )?
+    .unwrap_or_else(|| DEFAULT_DB_PATH.to_string());
+    // TLS is optional, so an empty answer leaves it out.
+    let tls_chain = answer(
+        &sopt.tls_chain,
+        "The path of the TLS certificate chain, or empty for none",
+        Some(""),
+        interactive,
+    )?
+    .filter(|p| !p.is_empty());
+    let tls_key = match tls_chain {
+        Some(_) => answer(
+            &sopt.tls_key,
+            "The path of the TLS private key",
+            None,
+            interactive,
+        )?,
+        None => sopt.tls_key.clone(),
+    };
+
+    Ok(SetupAnswers {
+        domain,
+        origin,
+        bindaddress,
+        db_path,
+        tls_chain,
+        tls_key,
+    })
+}
+
+fn starter_config(answers: &SetupAnswers) -> String {
+    let mut config = String::from(
+        "# Created by `kanidmd setup`. See examples/server.toml for all the options.\n",
+    );
+    config.push_str(&format!("bindaddress = {:?}\n", answers.bindaddress));
+    config.push_str(&format!("db_path = {:?}\n", answers.db_path));
+    match (&answers.tls_chain, &answers.tls_key) {
+        (Some(chain), Some(key)) => {
+            config.push_str(&format!("tls_chain = {:?}\n", chain));
+            config.push_str(&format!("tls_key = {:?}\n", key));
+        }
+        _ => config.push_str("# tls_chain = \"/data/chain.pem\"\n# tls_key = \"/data/key.pem\"\n"),
+    }
+    config.push_str(&format!("domain = {:?}\n", answers.domain));
+    config.push_str(&format!("origin = {:?}\n", answers.origin));
+    config
+}
+
+/// Write the starter configuration to `config_path`. Nothing is changed if the configuration or
+/// the database already exist.
+pub fn write_setup_config(config_path: &Path, sopt: &SetupOpt) -> Result<(), ()> {
+    if config_path.as_os_str().is_empty() {
+        eprintln!("The path to write the configuration to must be given with -c");
+        return Err(());
+    }
+    if config_path.exists() {
+        eprintln!(
+            "{} already exists - setup is only for a new server",
+            config_path.display()
+        );
+        return Err(());
+    }
+
+    let answers = gather(sopt)?;
+    if answers.tls_chain.is_some() != answers.tls_key.is_some() {
+        eprintln!("Both the TLS certificate chain and private key must be given");
+        return Err(());
+    }
+
+    let db_path = Path::new(&answers.db_path);
+    if db_path.exists() {
+        eprintln!(
+            "{} already exists - setup is only for a new server",
+            db_path.display()
+        );
+        return Err(());
+    }
+    if let Some(db_parent) = db_path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(db_parent).map_err(|e| {
+            eprintln!("Unable to create {} - {:?}", db_parent.display(), e);
+        })?;
+    }
+
+    fs::write(config_path, starter_config(&answers)).map_err(|e| {
+        eprintln!("Unable to write {} - {:?}", config_path.display(), e);
+    })?;
+    // The configuration should be readonly to the server.
+    #[cfg(target_family = "unix")]
+    fs::set_permissions(config_path, fs::Permissions::from_mode(0o440)).map_err(|e| {
+        eprintln!(
+            "Unable to set the permissions of {} - {:?}",
+            config_path.display(),
+            e
+        );
+    })?;
+
+    eprintln!("Wrote the configuration to {}", config_path.display());
+    Ok(())
+}
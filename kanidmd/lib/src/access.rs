@@ -22,7 +22,7 @@ use std::sync::Arc;
 
 use concread::arcache::{ARCache, ARCacheBuilder, ARCacheReadTxn};
 use concread::cowcell::*;
-use kanidm_proto::v1::{Filter as ProtoFilter, OperationError};
+use kanidm_proto::v1::{EntryChange, Filter as ProtoFilter, OperationError};
 use tracing::trace;
 use uuid::Uuid;
 
@@ -32,6 +32,7 @@ use crate::filter::{Filter, FilterValid, FilterValidResolved};
 use crate::identity::{AccessScope, IdentType, IdentityId};
 use crate::modify::Modify;
 use crate::prelude::*;
+use crate::security_event::SecurityEvent;
 
 const ACP_RESOLVE_FILTER_CACHE_MAX: usize = 2048;
 const ACP_RESOLVE_FILTER_CACHE_LOCAL: usize = 16;
@@ -391,6 +392,15 @@ pub struct AccessControls {
         ARCache<(IdentityId, Filter<FilterValid>), Filter<FilterValidResolved>>,
 }
 
+fn emit_access_denied(ident: &Identity, operation: EntryChange, reason: &'static str) {
+    SecurityEvent::AccessDenied {
+        identity: ident.get_uuid(),
+        operation,
+        reason,
+    }
+    .emit();
+}
+
 pub trait AccessControlsTransaction<'a> {
     fn get_search(&self) -> &Vec<AccessControlSearch>;
     fn get_create(&self) -> &Vec<AccessControlCreate>;
@@ -774,6 +784,7 @@ pub trait AccessControlsTransaction<'a> {
         match me.ident.access_scope() {
             AccessScope::IdentityOnly | AccessScope::ReadOnly | AccessScope::Synchronise => {
                 security_access!("denied ❌ - identity access scope is not permitted to modify");
+                emit_access_denied(&me.ident, EntryChange::Modify, "access_scope");
                 return Ok(false);
             }
             AccessScope::ReadWrite => {
@@ -920,6 +931,7 @@ pub trait AccessControlsTransaction<'a> {
             security_access!("allowed ✅");
         } else {
             security_access!("denied ❌");
+            emit_access_denied(&me.ident, EntryChange::Modify, "access_control");
         }
         Ok(r)
     }
@@ -952,6 +964,7 @@ pub trait AccessControlsTransaction<'a> {
         match me.ident.access_scope() {
             AccessScope::IdentityOnly | AccessScope::ReadOnly | AccessScope::Synchronise => {
                 security_access!("denied ❌ - identity access scope is not permitted to modify");
+                emit_access_denied(&me.ident, EntryChange::Modify, "access_scope");
                 return Ok(false);
             }
             AccessScope::ReadWrite => {
@@ -989,6 +1002,7 @@ pub trait AccessControlsTransaction<'a> {
         match ce.ident.access_scope() {
             AccessScope::IdentityOnly | AccessScope::ReadOnly | AccessScope::Synchronise => {
                 security_access!("denied ❌ - identity access scope is not permitted to create");
+                emit_access_denied(&ce.ident, EntryChange::Create, "access_scope");
                 return Ok(false);
             }
             AccessScope::ReadWrite => {
@@ -1099,6 +1113,7 @@ pub trait AccessControlsTransaction<'a> {
             security_access!("allowed ✅");
         } else {
             security_access!("denied ❌");
+            emit_access_denied(&ce.ident, EntryChange::Create, "access_control");
         }
 
         Ok(r)
@@ -1127,6 +1142,7 @@ pub trait AccessControlsTransaction<'a> {
         match de.ident.access_scope() {
             AccessScope::IdentityOnly | AccessScope::ReadOnly | AccessScope::Synchronise => {
                 security_access!("denied ❌ - identity access scope is not permitted to delete");
+                emit_access_denied(&de.ident, EntryChange::Delete, "access_scope");
                 return Ok(false);
             }
             AccessScope::ReadWrite => {
@@ -1198,6 +1214,7 @@ pub trait AccessControlsTransaction<'a> {
             security_access!("allowed ✅");
         } else {
             security_access!("denied ❌");
+            emit_access_denied(&de.ident, EntryChange::Delete, "access_control");
         }
         Ok(r)
     }
@@ -5,7 +5,9 @@ use serde::{Deserialize, Serialize};
 use smartstring::alias::String as AttrString;
 use uuid::Uuid;
 
-use crate::be::dbvalue::{DbValueEmailAddressV1, DbValuePhoneNumberV1, DbValueSetV2, DbValueV1};
+use crate::be::dbvalue::{
+    DbCidV1, DbValueEmailAddressV1, DbValuePhoneNumberV1, DbValueSetV2, DbValueV1,
+};
 use crate::prelude::OperationError;
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -16,6 +18,10 @@ pub struct DbEntryV1 {
 #[derive(Serialize, Deserialize, Debug)]
 pub struct DbEntryV2 {
     pub attrs: BTreeMap<AttrString, DbValueSetV2>,
+    /// The change that last modified each attribute. Older entries do not have this, in which
+    /// case every attribute is assumed to have changed at the entry's last_modified_cid.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub attr_cids: BTreeMap<AttrString, DbCidV1>,
 }
 
 // REMEMBER: If you add a new version here, you MUST
@@ -40,7 +46,7 @@ pub enum DbIdentSpn {
 }
 
 // This is actually what we store into the DB.
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct DbEntry {
     pub ent: DbEntryVers,
 }
@@ -423,7 +429,10 @@ impl DbEntry {
                 })
                 .collect::<Result<BTreeMap<_, _>, _>>()
                 .map(|attrs| DbEntry {
-                    ent: DbEntryVers::V2(DbEntryV2 { attrs }),
+                    ent: DbEntryVers::V2(DbEntryV2 {
+                        attrs,
+                        attr_cids: BTreeMap::new(),
+                    }),
                 })
         } else {
             Ok(self)
@@ -334,6 +334,21 @@ pub struct DbValuePhoneNumberV1 {
     pub p: bool,
 }
 
+/// A valueset sealed with the database seal key. The data is the sealed json of the valueset,
+/// followed by its gcm tag. The version is the format of the seal, which is authenticated
+/// along with the entry and attribute the valueset belongs to.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DbValueSealedV1 {
+    #[serde(rename = "v")]
+    pub version: u8,
+    #[serde(rename = "c")]
+    pub count: usize,
+    #[serde(rename = "n")]
+    pub nonce: Vec<u8>,
+    #[serde(rename = "d")]
+    pub data: Vec<u8>,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct DbValueAddressV1 {
     #[serde(rename = "f")]
@@ -350,6 +365,24 @@ pub struct DbValueAddressV1 {
     pub country: String,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+pub enum DbValueImageTypeV1 {
+    #[serde(rename = "p")]
+    Png,
+    #[serde(rename = "j")]
+    Jpeg,
+    #[serde(rename = "w")]
+    Webp,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DbValueImageV1 {
+    #[serde(rename = "t")]
+    pub filetype: DbValueImageTypeV1,
+    #[serde(rename = "c")]
+    pub contents: Vec<u8>,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct DbValueOauthScopeMapV1 {
     #[serde(rename = "u")]
@@ -412,6 +445,8 @@ pub enum DbValueOauth2Session {
         issued_at: String,
         #[serde(rename = "r")]
         rs_uuid: Uuid,
+        #[serde(rename = "d", default)]
+        delegated_from: Option<Uuid>,
     },
 }
 
@@ -532,6 +567,10 @@ pub enum DbValueSetV2 {
     PrivateBinary(Vec<Vec<u8>>),
     #[serde(rename = "PB")]
     PublicBinary(Vec<(String, Vec<u8>)>),
+    #[serde(rename = "BN")]
+    Binary(Vec<Vec<u8>>),
+    #[serde(rename = "IM")]
+    Image(Vec<DbValueImageV1>),
     #[serde(rename = "RS")]
     RestrictedString(Vec<String>),
     #[serde(rename = "IT")]
@@ -552,6 +591,8 @@ pub enum DbValueSetV2 {
     Oauth2Session(Vec<DbValueOauth2Session>),
     #[serde(rename = "UH")]
     UiHint(Vec<u16>),
+    #[serde(rename = "SE")]
+    Sealed(DbValueSealedV1),
 }
 
 impl DbValueSetV2 {
@@ -582,6 +623,8 @@ impl DbValueSetV2 {
             DbValueSetV2::OauthScopeMap(set) => set.len(),
             DbValueSetV2::PrivateBinary(set) => set.len(),
             DbValueSetV2::PublicBinary(set) => set.len(),
+            DbValueSetV2::Binary(set) => set.len(),
+            DbValueSetV2::Image(set) => set.len(),
             DbValueSetV2::RestrictedString(set) => set.len(),
             DbValueSetV2::IntentToken(set) => set.len(),
             DbValueSetV2::Passkey(set) => set.len(),
@@ -592,6 +635,7 @@ impl DbValueSetV2 {
             DbValueSetV2::JwsKeyEs256(set) => set.len(),
             DbValueSetV2::JwsKeyRs256(set) => set.len(),
             DbValueSetV2::UiHint(set) => set.len(),
+            DbValueSetV2::Sealed(sealed) => sealed.count,
         }
     }
 
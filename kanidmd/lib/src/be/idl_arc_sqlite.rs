@@ -1,4 +1,4 @@
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 use std::convert::TryInto;
 use std::ops::DerefMut;
 use std::sync::Arc;
@@ -9,10 +9,11 @@ use concread::cowcell::*;
 use hashbrown::HashMap;
 use idlset::v2::IDLBitRange;
 use idlset::AndNot;
-use kanidm_proto::v1::{ConsistencyError, OperationError};
+use kanidm_proto::v1::{AuditRecord, ConsistencyError, OperationError};
 use tracing::trace;
 use uuid::Uuid;
 
+use crate::be::dbentry::DbEntry;
 use crate::be::idl_sqlite::{
     IdlSqlite, IdlSqliteReadTransaction, IdlSqliteTransaction, IdlSqliteWriteTransaction,
 };
@@ -22,6 +23,7 @@ use crate::be::idxkey::{
 use crate::be::{BackendConfig, IdList, IdRawEntry};
 use crate::entry::{Entry, EntryCommitted, EntrySealed};
 use crate::prelude::*;
+use crate::repl::proto::ReplCidRange;
 use crate::value::{IndexType, Value};
 
 // use std::borrow::Borrow;
@@ -353,6 +355,14 @@ pub trait IdlArcSqliteTransaction {
 
     fn get_db_ts_max(&self) -> Result<Option<Duration>, OperationError>;
 
+    fn get_db_ruv(&self) -> Result<Option<BTreeMap<Uuid, ReplCidRange>>, OperationError>;
+
+    fn search_audit_log(
+        &self,
+        not_before: i64,
+        not_after: i64,
+    ) -> Result<Vec<AuditRecord>, OperationError>;
+
     fn verify(&self) -> Vec<Result<(), ConsistencyError>>;
 
     fn is_dirty(&self) -> bool;
@@ -415,6 +425,18 @@ impl<'a> IdlArcSqliteTransaction for IdlArcSqliteReadTransaction<'a> {
         self.db.get_db_ts_max()
     }
 
+    fn get_db_ruv(&self) -> Result<Option<BTreeMap<Uuid, ReplCidRange>>, OperationError> {
+        self.db.get_db_ruv()
+    }
+
+    fn search_audit_log(
+        &self,
+        not_before: i64,
+        not_after: i64,
+    ) -> Result<Vec<AuditRecord>, OperationError> {
+        self.db.search_audit_log(not_before, not_after)
+    }
+
     fn verify(&self) -> Vec<Result<(), ConsistencyError>> {
         verify!(self)
     }
@@ -504,6 +526,18 @@ impl<'a> IdlArcSqliteTransaction for IdlArcSqliteWriteTransaction<'a> {
         }
     }
 
+    fn get_db_ruv(&self) -> Result<Option<BTreeMap<Uuid, ReplCidRange>>, OperationError> {
+        self.db.get_db_ruv()
+    }
+
+    fn search_audit_log(
+        &self,
+        not_before: i64,
+        not_after: i64,
+    ) -> Result<Vec<AuditRecord>, OperationError> {
+        self.db.search_audit_log(not_before, not_after)
+    }
+
     fn verify(&self) -> Vec<Result<(), ConsistencyError>> {
         verify!(self)
     }
@@ -663,6 +697,26 @@ impl<'a> IdlArcSqliteWriteTransaction<'a> {
         })
     }
 
+    /// Set the attributes that schema marks as encrypted, which are sealed from now on. This is
+    /// false if the database is not sealed, and so they can't be.
+    pub fn set_sealed_attrs(&self, attrs: BTreeSet<AttrString>) -> bool {
+        match self.db.get_seal() {
+            Some(seal) => {
+                seal.set_sealed_attrs(attrs);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Seal the secret values of an entry that is to be written raw.
+    pub fn seal_dbentry(&self, dbe: DbEntry) -> Result<DbEntry, OperationError> {
+        match self.db.get_seal() {
+            Some(seal) => seal.seal_dbentry(dbe),
+            None => Ok(dbe),
+        }
+    }
+
     pub fn write_identries_raw<I>(&mut self, entries: I) -> Result<(), OperationError>
     where
         I: Iterator<Item = IdRawEntry>,
@@ -1090,6 +1144,21 @@ impl<'a> IdlArcSqliteWriteTransaction<'a> {
         self.db.set_db_ts_max(ts)
     }
 
+    pub fn write_db_ruv(
+        &self,
+        ranges: &BTreeMap<Uuid, ReplCidRange>,
+    ) -> Result<(), OperationError> {
+        self.db.write_db_ruv(ranges)
+    }
+
+    pub fn write_audit_records(&self, records: &[AuditRecord]) -> Result<(), OperationError> {
+        self.db.write_audit_records(records)
+    }
+
+    pub fn purge_audit_log(&self, before: i64) -> Result<usize, OperationError> {
+        self.db.purge_audit_log(before)
+    }
+
     pub(crate) fn get_db_index_version(&self) -> i64 {
         self.db.get_db_index_version()
     }
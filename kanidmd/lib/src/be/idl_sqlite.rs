@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::convert::{TryFrom, TryInto};
 use std::sync::Arc;
 use std::time::Duration;
@@ -5,16 +6,18 @@ use std::time::Duration;
 // use crate::valueset;
 use hashbrown::HashMap;
 use idlset::v2::IDLBitRange;
-use kanidm_proto::v1::{ConsistencyError, OperationError};
+use kanidm_proto::v1::{AuditRecord, ConsistencyError, OperationError};
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
 use rusqlite::{Connection, OpenFlags, OptionalExtension};
 use uuid::Uuid;
 
 use crate::be::dbentry::{DbEntry, DbIdentSpn};
+use crate::be::seal::DbSeal;
 use crate::be::{BackendConfig, IdList, IdRawEntry, IdxKey, IdxSlope};
 use crate::entry::{Entry, EntryCommitted, EntrySealed};
 use crate::prelude::*;
+use crate::repl::proto::ReplCidRange;
 use crate::value::{IndexType, Value};
 
 // use uuid::Uuid;
@@ -99,16 +102,19 @@ impl TryFrom<IdRawEntry> for IdSqliteEntry {
 #[derive(Clone)]
 pub struct IdlSqlite {
     pool: Pool<SqliteConnectionManager>,
+    seal: Option<Arc<DbSeal>>,
 }
 
 pub struct IdlSqliteReadTransaction {
     committed: bool,
     conn: r2d2::PooledConnection<SqliteConnectionManager>,
+    seal: Option<Arc<DbSeal>>,
 }
 
 pub struct IdlSqliteWriteTransaction {
     committed: bool,
     conn: r2d2::PooledConnection<SqliteConnectionManager>,
+    seal: Option<Arc<DbSeal>>,
 }
 
 pub trait IdlSqliteTransaction {
@@ -116,10 +122,13 @@ pub trait IdlSqliteTransaction {
 
     fn get_conn(&self) -> &r2d2::PooledConnection<r2d2_sqlite::SqliteConnectionManager>;
 
+    fn get_seal(&self) -> Option<&DbSeal>;
+
     fn get_identry(&self, idl: &IdList) -> Result<Vec<Arc<EntrySealedCommitted>>, OperationError> {
+        let seal = self.get_seal();
         self.get_identry_raw(idl)?
             .into_iter()
-            .map(|ide| ide.into_entry().map(Arc::new))
+            .map(|ide| ide.into_entry(seal).map(Arc::new))
             .collect()
     }
 
@@ -447,6 +456,46 @@ pub trait IdlSqliteTransaction {
         })
     }
 
+    fn get_db_ruv(&self) -> Result<Option<BTreeMap<Uuid, ReplCidRange>>, OperationError> {
+        let data: Option<Vec<u8>> = self
+            .get_conn()
+            .query_row("SELECT data FROM db_ruv WHERE id = 1", [], |row| row.get(0))
+            .optional()
+            .map_err(sqlite_error)?;
+
+        data.map(|d| serde_json::from_slice(d.as_slice()).map_err(serde_json_error))
+            .transpose()
+    }
+
+    /// The audit records between two times, as unix timestamps, in the order they were written.
+    fn search_audit_log(
+        &self,
+        not_before: i64,
+        not_after: i64,
+    ) -> Result<Vec<AuditRecord>, OperationError> {
+        let mut stmt = self
+            .get_conn()
+            .prepare(
+                "SELECT data FROM audit_log WHERE ts >= :not_before AND ts <= :not_after ORDER BY id ASC",
+            )
+            .map_err(sqlite_error)?;
+        let data: Vec<Vec<u8>> = stmt
+            .query_map(
+                named_params! {
+                    ":not_before": &not_before,
+                    ":not_after": &not_after,
+                },
+                |row| row.get(0),
+            )
+            .map_err(sqlite_error)?
+            .collect::<Result<_, _>>()
+            .map_err(sqlite_error)?;
+
+        data.iter()
+            .map(|d| serde_json::from_slice(d.as_slice()).map_err(serde_json_error))
+            .collect()
+    }
+
     #[instrument(level = "debug", name = "idl_sqlite::get_allids", skip_all)]
     fn get_allids(&self) -> Result<IDLBitRange, OperationError> {
         let mut stmt = self
@@ -567,6 +616,10 @@ impl IdlSqliteTransaction for IdlSqliteReadTransaction {
     fn get_conn(&self) -> &r2d2::PooledConnection<r2d2_sqlite::SqliteConnectionManager> {
         &self.conn
     }
+
+    fn get_seal(&self) -> Option<&DbSeal> {
+        self.seal.as_deref()
+    }
 }
 
 impl Drop for IdlSqliteReadTransaction {
@@ -585,7 +638,10 @@ impl Drop for IdlSqliteReadTransaction {
 }
 
 impl IdlSqliteReadTransaction {
-    pub fn new(conn: r2d2::PooledConnection<SqliteConnectionManager>) -> Self {
+    pub fn new(
+        conn: r2d2::PooledConnection<SqliteConnectionManager>,
+        seal: Option<Arc<DbSeal>>,
+    ) -> Self {
         // Start the transaction
         //
         // I'm happy for this to be an expect, because this is a huge failure
@@ -599,6 +655,7 @@ impl IdlSqliteReadTransaction {
         IdlSqliteReadTransaction {
             committed: false,
             conn,
+            seal,
         }
     }
 }
@@ -611,6 +668,10 @@ impl IdlSqliteTransaction for IdlSqliteWriteTransaction {
     fn get_conn(&self) -> &r2d2::PooledConnection<r2d2_sqlite::SqliteConnectionManager> {
         &self.conn
     }
+
+    fn get_seal(&self) -> Option<&DbSeal> {
+        self.seal.as_deref()
+    }
 }
 
 impl Drop for IdlSqliteWriteTransaction {
@@ -626,7 +687,10 @@ impl Drop for IdlSqliteWriteTransaction {
 }
 
 impl IdlSqliteWriteTransaction {
-    pub fn new(conn: r2d2::PooledConnection<SqliteConnectionManager>) -> Self {
+    pub fn new(
+        conn: r2d2::PooledConnection<SqliteConnectionManager>,
+        seal: Option<Arc<DbSeal>>,
+    ) -> Self {
         // Start the transaction
         #[allow(clippy::expect_used)]
         conn.execute("BEGIN EXCLUSIVE TRANSACTION", [])
@@ -634,6 +698,7 @@ impl IdlSqliteWriteTransaction {
         IdlSqliteWriteTransaction {
             committed: false,
             conn,
+            seal,
         }
     }
 
@@ -705,7 +770,10 @@ impl IdlSqliteWriteTransaction {
         &self,
         entry: &Entry<EntrySealed, EntryCommitted>,
     ) -> Result<(), OperationError> {
-        let dbe = entry.to_dbentry();
+        let dbe = match &self.seal {
+            Some(seal) => seal.seal_dbentry(entry.to_dbentry())?,
+            None => entry.to_dbentry(),
+        };
         let data = serde_json::to_vec(&dbe).map_err(serde_json_error)?;
 
         let raw_entries = std::iter::once(IdRawEntry {
@@ -1223,6 +1291,67 @@ impl IdlSqliteWriteTransaction {
             })
     }
 
+    pub fn write_db_ruv(
+        &self,
+        ranges: &BTreeMap<Uuid, ReplCidRange>,
+    ) -> Result<(), OperationError> {
+        let data = serde_json::to_vec(ranges).map_err(|e| {
+            admin_error!(immediate = true, ?e, "CRITICAL: Serde JSON Error");
+            eprintln!("CRITICAL: Serde JSON Error -> {:?}", e);
+            OperationError::SerdeJsonError
+        })?;
+
+        self.conn
+            .execute(
+                &format!(
+                    "INSERT OR REPLACE INTO {}.db_ruv (id, data) VALUES(:id, :ruv)",
+                    "main"
+                ),
+                named_params! {
+                    ":id": &1,
+                    ":ruv": &data,
+                },
+            )
+            .map(|_| ())
+            .map_err(|e| {
+                admin_error!(immediate = true, ?e, "CRITICAL: rusqlite error");
+                eprintln!("CRITICAL: rusqlite error {:?}", e);
+                OperationError::SqliteError
+            })
+    }
+
+    pub fn write_audit_records(&self, records: &[AuditRecord]) -> Result<(), OperationError> {
+        let mut stmt = self
+            .conn
+            .prepare(&format!(
+                "INSERT INTO {}.audit_log (ts, data) VALUES(:ts, :data)",
+                "main"
+            ))
+            .map_err(sqlite_error)?;
+
+        records.iter().try_for_each(|record| {
+            let data = serde_json::to_vec(record).map_err(serde_json_error)?;
+            stmt.execute(named_params! {
+                ":ts": &record.time.unix_timestamp(),
+                ":data": &data,
+            })
+            .map(|_| ())
+            .map_err(sqlite_error)
+        })
+    }
+
+    /// Remove the audit records from before a time, as a unix timestamp.
+    pub fn purge_audit_log(&self, before: i64) -> Result<usize, OperationError> {
+        self.conn
+            .execute(
+                &format!("DELETE FROM {}.audit_log WHERE ts < :before", "main"),
+                named_params! {
+                    ":before": &before,
+                },
+            )
+            .map_err(sqlite_error)
+    }
+
     // ===== inner helpers =====
     // Some of these are not self due to use in new()
     fn get_db_version_key(&self, key: &str) -> i64 {
@@ -1386,7 +1515,42 @@ impl IdlSqliteWriteTransaction {
             dbv_id2entry = 6;
             admin_info!(entry = %dbv_id2entry, "dbv_id2entry migrated (externalid2uuid)");
         }
-        //   * if v6 -> complete.
+        //   * if v6 -> add the replication update vector table.
+        if dbv_id2entry == 6 {
+            self.conn
+                .execute(
+                    &format!(
+                        "CREATE TABLE IF NOT EXISTS {}.db_ruv (
+                        id INTEGER PRIMARY KEY ASC,
+                        data BLOB NOT NULL
+                    )
+                    ",
+                        "main"
+                    ),
+                    [],
+                )
+                .map_err(sqlite_error)?;
+            dbv_id2entry = 7;
+            admin_info!(entry = %dbv_id2entry, "dbv_id2entry migrated (db_ruv)");
+        }
+        //   * if v7 -> add the audit log table.
+        if dbv_id2entry == 7 {
+            self.conn
+                .execute_batch(&format!(
+                    "CREATE TABLE IF NOT EXISTS {0}.audit_log (
+                        id INTEGER PRIMARY KEY AUTOINCREMENT,
+                        ts INTEGER NOT NULL,
+                        data BLOB NOT NULL
+                    );
+                    CREATE INDEX IF NOT EXISTS {0}.audit_log_ts ON audit_log (ts);
+                    ",
+                    "main"
+                ))
+                .map_err(sqlite_error)?;
+            dbv_id2entry = 8;
+            admin_info!(entry = %dbv_id2entry, "dbv_id2entry migrated (audit_log)");
+        }
+        //   * if v8 -> complete.
 
         self.set_db_version_key(DBV_ID2ENTRY, dbv_id2entry)
             .map_err(sqlite_error)?;
@@ -1408,7 +1572,11 @@ impl IdlSqlite {
         // immediately after is so that on db create the page size takes effect.
         //
         // Enable WAL mode, which is just faster and better for our needs.
-        let mut flags = OpenFlags::default();
+        let mut flags = if cfg.readonly {
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_URI
+        } else {
+            OpenFlags::default()
+        };
         // Open with multi thread flags and locking options.
         flags.insert(OpenFlags::SQLITE_OPEN_NO_MUTEX);
 
@@ -1480,9 +1648,14 @@ impl IdlSqlite {
 
         let fs_page_size = cfg.fstype as u32;
         let checkpoint_pages = cfg.fstype.checkpoint_pages();
+        let readonly = cfg.readonly;
 
         let manager = SqliteConnectionManager::file(cfg.path.as_str())
             .with_init(move |c| {
+                if readonly {
+                    // The journal mode and page size are kept from when the db was written.
+                    return c.execute_batch("PRAGMA query_only=ON;");
+                }
                 c.execute_batch(
                     format!(
                         "PRAGMA page_size={};
@@ -1505,7 +1678,14 @@ impl IdlSqlite {
             OperationError::SqliteError
         })?;
 
-        Ok(IdlSqlite { pool })
+        // An in memory database is lost with its seal, so it is always sealed.
+        let seal = match &cfg.seal_key_path {
+            Some(key_path) => Some(Arc::new(DbSeal::new(key_path)?)),
+            None if cfg.path.is_empty() => Some(Arc::new(DbSeal::ephemeral()?)),
+            None => None,
+        };
+
+        Ok(IdlSqlite { pool, seal })
     }
 
     pub(crate) fn get_allids_count(&self) -> Result<u64, OperationError> {
@@ -1526,7 +1706,7 @@ impl IdlSqlite {
             .pool
             .try_get()
             .expect("Unable to get connection from pool!!!");
-        IdlSqliteReadTransaction::new(conn)
+        IdlSqliteReadTransaction::new(conn, self.seal.clone())
     }
 
     pub fn write(&self) -> IdlSqliteWriteTransaction {
@@ -1535,7 +1715,7 @@ impl IdlSqlite {
             .pool
             .try_get()
             .expect("Unable to get connection from pool!!!");
-        IdlSqliteWriteTransaction::new(conn)
+        IdlSqliteWriteTransaction::new(conn, self.seal.clone())
     }
 }
 
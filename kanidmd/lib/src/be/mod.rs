@@ -5,6 +5,7 @@
 //! utilising indexes in the most effective way possible.
 
 use std::cell::UnsafeCell;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs;
 use std::ops::DerefMut;
 use std::sync::Arc;
@@ -14,17 +15,19 @@ use concread::cowcell::*;
 use hashbrown::{HashMap as Map, HashSet};
 use idlset::v2::IDLBitRange;
 use idlset::AndNot;
-use kanidm_proto::v1::{ConsistencyError, OperationError};
+use kanidm_proto::v1::{AuditRecord, ConsistencyError, OperationError};
 use smartstring::alias::String as AttrString;
 use tracing::{trace, trace_span};
 use uuid::Uuid;
 
 use crate::be::dbentry::{DbBackup, DbEntry};
+use crate::be::seal::DbSeal;
 use crate::entry::{Entry, EntryCommitted, EntryNew, EntrySealed};
 use crate::filter::{Filter, FilterPlan, FilterResolved, FilterValidResolved};
 use crate::identity::Limits;
 use crate::prelude::*;
 use crate::repl::cid::Cid;
+use crate::repl::proto::ReplCidRange;
 use crate::repl::ruv::{
     ReplicationUpdateVector, ReplicationUpdateVectorReadTransaction,
     ReplicationUpdateVectorTransaction, ReplicationUpdateVectorWriteTransaction,
@@ -36,6 +39,7 @@ pub mod dbvalue;
 mod idl_arc_sqlite;
 mod idl_sqlite;
 pub(crate) mod idxkey;
+mod seal;
 
 pub(crate) use self::idxkey::{IdxKey, IdxKeyRef, IdxKeyToRef, IdxSlope};
 use crate::be::idl_arc_sqlite::{
@@ -81,6 +85,8 @@ pub struct BackendConfig {
     fstype: FsType,
     // Cachesizes?
     arcsize: Option<usize>,
+    readonly: bool,
+    seal_key_path: Option<String>,
 }
 
 impl BackendConfig {
@@ -90,15 +96,34 @@ impl BackendConfig {
             path: path.to_string(),
             fstype,
             arcsize,
+            readonly: false,
+            seal_key_path: None,
         }
     }
 
+    /// Seal the secret values of the database with the key in this file, which is created if
+    /// it does not exist. The key must be kept with backups of the database, as they contain
+    /// the sealed values.
+    pub fn seal_key_path(mut self, path: &str) -> Self {
+        self.seal_key_path = Some(path.to_string());
+        self
+    }
+
+    /// Open an existing database without writing to it, such as to verify it. Only read
+    /// transactions may be used.
+    pub fn readonly(mut self) -> Self {
+        self.readonly = true;
+        self
+    }
+
     pub(crate) fn new_test() -> Self {
         BackendConfig {
             pool_size: 1,
             path: "".to_string(),
             fstype: FsType::Generic,
             arcsize: Some(1024),
+            readonly: false,
+            seal_key_path: None,
         }
     }
 }
@@ -134,6 +159,7 @@ pub struct BackendWriteTransaction<'a> {
     idxmeta: CowCellReadTxn<IdxMeta>,
     ruv: UnsafeCell<ReplicationUpdateVectorWriteTransaction<'a>>,
     idxmeta_wr: CowCellWriteTxn<'a, IdxMeta>,
+    sealed_attrs: Option<BTreeSet<AttrString>>,
 }
 
 impl IdRawEntry {
@@ -146,13 +172,20 @@ impl IdRawEntry {
             .map(|dbe| (self.id, dbe))
     }
 
-    fn into_entry(self) -> Result<Entry<EntrySealed, EntryCommitted>, OperationError> {
+    fn into_entry(
+        self,
+        seal: Option<&DbSeal>,
+    ) -> Result<Entry<EntrySealed, EntryCommitted>, OperationError> {
         let db_e = serde_json::from_slice(self.data.as_slice()).map_err(|e| {
             admin_error!(?e, id = %self.id, "Serde JSON Error");
             let raw_str = String::from_utf8_lossy(self.data.as_slice());
             debug!(raw = %raw_str);
             OperationError::SerdeJsonError
         })?;
+        let db_e = match seal {
+            Some(seal) => seal.unseal_dbentry(db_e)?,
+            None => db_e,
+        };
         // let id = u64::try_from(self.id).map_err(|_| OperationError::InvalidEntryId)?;
         Entry::from_dbentry(db_e, self.id).ok_or(OperationError::CorruptedEntry(self.id))
     }
@@ -219,6 +252,14 @@ pub trait BackendTransaction {
                     (IdList::AllIds, FilterPlan::SubUnindexed(attr.clone()))
                 }
             }
+            FilterResolved::Stw(attr, _subvalue, _idx) => {
+                // We have no process for indexing this right now.
+                (IdList::AllIds, FilterPlan::StwUnindexed(attr.clone()))
+            }
+            FilterResolved::Enw(attr, _subvalue, _idx) => {
+                // We have no process for indexing this right now.
+                (IdList::AllIds, FilterPlan::EnwUnindexed(attr.clone()))
+            }
             FilterResolved::Pres(attr, idx) => {
                 if idx.is_some() {
                     // Get the idl for this
@@ -235,6 +276,13 @@ pub trait BackendTransaction {
                 // We have no process for indexing this right now.
                 (IdList::AllIds, FilterPlan::LessThanUnindexed(attr.clone()))
             }
+            FilterResolved::GreaterThan(attr, _subvalue, _idx) => {
+                // We have no process for indexing this right now.
+                (
+                    IdList::AllIds,
+                    FilterPlan::GreaterThanUnindexed(attr.clone()),
+                )
+            }
             FilterResolved::Or(l, _) => {
                 // Importantly if this has no inner elements, this returns
                 // an empty list.
@@ -785,6 +833,54 @@ pub trait BackendTransaction {
         }
     }
 
+    /// Retrieve the entries with changes that are not within the ranges a replication
+    /// consumer already holds.
+    #[instrument(level = "debug", name = "be::retrieve_range", skip_all)]
+    fn retrieve_range(
+        &self,
+        ranges: &BTreeMap<Uuid, ReplCidRange>,
+    ) -> Result<Vec<Arc<EntrySealedCommitted>>, OperationError> {
+        let idl = self.get_ruv().range_to_idl(ranges);
+
+        if idl.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        self.get_idlayer()
+            .get_identry(&IdList::Indexed(idl))
+            .map_err(|e| {
+                admin_error!(?e, "get_identry failed");
+                e
+            })
+    }
+
+    /// Retrieve up to `limit` entries in uuid order, starting after the given uuid. This
+    /// includes tombstones and recycled entries, so that a consumer being refreshed holds
+    /// an exact copy of this database. The flag is true if no entries remain.
+    #[instrument(level = "debug", name = "be::retrieve_refresh_chunk", skip_all)]
+    fn retrieve_refresh_chunk(
+        &self,
+        after: Option<Uuid>,
+        limit: usize,
+    ) -> Result<(Vec<Arc<EntrySealedCommitted>>, bool), OperationError> {
+        let mut entries = self
+            .get_idlayer()
+            .get_identry(&IdList::AllIds)
+            .map_err(|e| {
+                admin_error!(?e, "get_identry failed");
+                e
+            })?;
+
+        if let Some(after) = after {
+            entries.retain(|e| e.get_uuid() > after);
+        }
+        entries.sort_unstable_by_key(|e| e.get_uuid());
+
+        let complete = entries.len() <= limit;
+        entries.truncate(limit);
+        Ok((entries, complete))
+    }
+
     fn verify_ruv(&self, results: &mut Vec<Result<(), ConsistencyError>>) {
         // The way we verify this is building a whole second RUV and then comparing it.
         let idl = IdList::AllIds;
@@ -862,6 +958,15 @@ pub trait BackendTransaction {
     fn uuid2rdn(&self, uuid: Uuid) -> Result<Option<String>, OperationError> {
         self.get_idlayer().uuid2rdn(uuid)
     }
+
+    /// The audit records between two unix timestamps, oldest first.
+    fn search_audit_log(
+        &self,
+        not_before: i64,
+        not_after: i64,
+    ) -> Result<Vec<AuditRecord>, OperationError> {
+        self.get_idlayer().search_audit_log(not_before, not_after)
+    }
 }
 
 impl<'a> BackendTransaction for BackendReadTransaction<'a> {
@@ -913,6 +1018,47 @@ impl<'a> BackendReadTransaction<'a> {
     pub fn get_id2entry(&self, id: u64) -> Result<(u64, String), OperationError> {
         self.get_idlayer().get_id2entry(id)
     }
+
+    /// The id of the entry with this uuid, from the uuid index.
+    pub fn get_uuid2id(&self, uuid: Uuid) -> Result<Option<u64>, OperationError> {
+        let uuid_s = uuid.as_hyphenated().to_string();
+        let idl = self
+            .get_idlayer()
+            .get_idl("uuid", IndexType::Equality, &uuid_s)?;
+        Ok(idl.and_then(|idl| (&idl).into_iter().next()))
+    }
+
+    /// The index keys that refer to the entry, as pairs of the index name and key. This reads
+    /// every index, so is only for inspecting the database.
+    pub fn list_index_refs(&self, id: u64) -> Result<Vec<(String, String)>, OperationError> {
+        let mut refs = Vec::new();
+        for index_name in self.list_indexes()? {
+            for (key, idl) in self.list_index_content(&index_name)? {
+                if idl.contains(id) {
+                    refs.push((index_name.clone(), key));
+                }
+            }
+        }
+        Ok(refs)
+    }
+
+    /// The changes in the replication update vector that include the entry, oldest first.
+    pub fn list_ruv_changes(&self, id: u64) -> Vec<Cid> {
+        self.get_ruv()
+            .ruv_snapshot()
+            .into_iter()
+            .filter(|(_, idl)| idl.contains(id))
+            .map(|(cid, _)| cid)
+            .collect()
+    }
+
+    pub fn get_db_s_uuid(&self) -> Result<Option<Uuid>, OperationError> {
+        self.get_idlayer().get_db_s_uuid()
+    }
+
+    pub fn get_db_d_uuid(&self) -> Result<Option<Uuid>, OperationError> {
+        self.get_idlayer().get_db_d_uuid()
+    }
 }
 
 impl<'a> BackendTransaction for BackendWriteTransaction<'a> {
@@ -1030,6 +1176,55 @@ impl<'a> BackendWriteTransaction<'a> {
             .try_for_each(|(pre, post)| self.entry_index(Some(pre.as_ref()), Some(post)))
     }
 
+    /// Write the entries received from a replication supplier. Unlike create and modify,
+    /// each entry carries the cid of the change on the supplier rather than the cid of
+    /// this transaction, and that is what the RUV records.
+    #[instrument(level = "debug", name = "be::incremental_apply", skip_all)]
+    pub fn incremental_apply(
+        &self,
+        update_entries: &[(Cid, Arc<EntrySealedCommitted>, EntrySealedCommitted)],
+        create_entries: Vec<(Cid, Entry<EntrySealed, EntryNew>)>,
+    ) -> Result<Vec<EntrySealedCommitted>, OperationError> {
+        let idlayer = self.get_idlayer();
+
+        let mut id_max = idlayer.get_id2entry_max_id()?;
+        let (create_cids, c_entries): (Vec<_>, Vec<_>) = create_entries
+            .into_iter()
+            .map(|(cid, e)| {
+                id_max += 1;
+                (cid, e.into_sealed_committed_id(id_max))
+            })
+            .unzip();
+
+        for (cid, e) in create_cids
+            .iter()
+            .zip(c_entries.iter())
+            .chain(update_entries.iter().map(|(cid, _, e)| (cid, e)))
+        {
+            let mut ruv_idl = IDLBitRange::new();
+            ruv_idl.insert_id(e.get_id());
+            self.get_ruv().insert_change(cid, ruv_idl)?;
+        }
+
+        idlayer.write_identries(
+            c_entries
+                .iter()
+                .chain(update_entries.iter().map(|(_, _, e)| e)),
+        )?;
+
+        idlayer.set_id2entry_max_id(id_max);
+
+        for e in c_entries.iter() {
+            self.entry_index(None, Some(e))?
+        }
+
+        update_entries
+            .iter()
+            .try_for_each(|(_, pre, post)| self.entry_index(Some(pre.as_ref()), Some(post)))?;
+
+        Ok(c_entries)
+    }
+
     #[instrument(level = "debug", name = "be::reap_tombstones", skip_all)]
     pub fn reap_tombstones(&self, cid: &Cid) -> Result<usize, OperationError> {
         // We plan to clear the RUV up to this cid. So we need to build an IDL
@@ -1131,6 +1326,14 @@ impl<'a> BackendWriteTransaction<'a> {
         Ok(())
     }
 
+    /// Set the attributes that schema marks as encrypted. They are sealed from when this
+    /// transaction commits, and values already written are sealed as their entry is next
+    /// written. Sealed values are always unsealed as they are read, so they remain readable if
+    /// an attribute is no longer marked.
+    pub fn update_sealed_attrs(&mut self, attrs: BTreeSet<AttrString>) {
+        self.sealed_attrs = Some(attrs);
+    }
+
     // Should take a mut index set, and then we write the whole thing back
     // in a single stripe.
     //
@@ -1401,14 +1604,16 @@ impl<'a> BackendWriteTransaction<'a> {
             e
         })?;
 
+        let total = entries.len();
         let mut count = 0;
+        limmediate_warning!("Reindexing {} entries ... ", total);
 
         entries
             .iter()
             .try_for_each(|e| {
                 count += 1;
                 if count % 2500 == 0 {
-                    limmediate_warning!("{}", count);
+                    limmediate_warning!("{}/{}", count, total);
                 } else if count % 250 == 0 {
                     limmediate_warning!(".");
                 }
@@ -1498,12 +1703,18 @@ impl<'a> BackendWriteTransaction<'a> {
             }
         };
 
+        // The restored entries are the source of truth for the changes we hold now.
+        idlayer.write_db_ruv(&BTreeMap::new())?;
+
         info!("Restoring {} entries ...", dbentries.len());
 
         // Migrate any v1 entries to v2 if needed.
         let dbentries = dbentries
             .into_iter()
-            .map(|dbe| dbe.convert_to_v2())
+            .map(|dbe| {
+                dbe.convert_to_v2()
+                    .and_then(|dbe| idlayer.seal_dbentry(dbe))
+            })
             .collect::<Result<Vec<_>, _>>()?;
 
         // Now, we setup all the entries with new ids.
@@ -1532,6 +1743,63 @@ impl<'a> BackendWriteTransaction<'a> {
         }
     }
 
+    /// Replace the content of this database with a complete copy of the entries held by a
+    /// replication supplier. The RUV is rebuilt from the new entries and the supplier's
+    /// ranges at the time of the copy. The caller must reindex once the schema of the new
+    /// entries is loaded.
+    #[instrument(level = "debug", name = "be::refresh", skip_all)]
+    pub fn refresh(
+        &self,
+        d_uuid: Uuid,
+        dbentries: Vec<DbEntry>,
+        ranges: &BTreeMap<Uuid, ReplCidRange>,
+    ) -> Result<(), OperationError> {
+        let idlayer = self.get_idlayer();
+
+        unsafe { idlayer.purge_id2entry() }.map_err(|e| {
+            admin_error!("purge_id2entry failed {:?}", e);
+            e
+        })?;
+
+        idlayer.write_db_d_uuid(d_uuid)?;
+        // The ranges we held before belong to our prior domain.
+        idlayer.write_db_ruv(&BTreeMap::new())?;
+
+        info!("Refreshing {} entries ...", dbentries.len());
+
+        // The supplier sends secrets unsealed, as its seal key is its own.
+        let dbentries = dbentries
+            .into_iter()
+            .map(|dbe| {
+                dbe.convert_to_v2()
+                    .and_then(|dbe| idlayer.seal_dbentry(dbe))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut id_max = 0;
+        let identries: Result<Vec<IdRawEntry>, _> = dbentries
+            .iter()
+            .map(|e| {
+                id_max += 1;
+                let data = serde_json::to_vec(&e).map_err(|_| OperationError::SerdeCborError)?;
+                Ok(IdRawEntry { id: id_max, data })
+            })
+            .collect();
+
+        idlayer.write_identries_raw(identries?.into_iter())?;
+
+        let entries = idlayer.get_identry(&IdList::AllIds).map_err(|e| {
+            admin_error!(?e, "get_identry failed");
+            e
+        })?;
+
+        self.get_ruv().rebuild(&entries)?;
+        self.get_ruv().merge_ranges(ranges);
+
+        info!("Refreshed {} entries", dbentries.len());
+        Ok(())
+    }
+
     #[instrument(level = "debug", name = "be::ruv_rebuild", skip_all)]
     pub fn ruv_rebuild(&mut self) -> Result<(), OperationError> {
         // Rebuild the ruv!
@@ -1549,21 +1817,43 @@ impl<'a> BackendWriteTransaction<'a> {
 
         self.get_ruv().rebuild(&entries)?;
 
+        // The ranges we have been told of by suppliers, or have since trimmed, can not be
+        // recovered from the entries, so merge them from the db.
+        if let Some(ranges) = self.get_idlayer().get_db_ruv()? {
+            self.get_ruv().merge_ranges(&ranges);
+        }
+
         Ok(())
     }
 
     pub fn commit(self) -> Result<(), OperationError> {
+        // Persist the ranges of changes we hold so they survive a restart.
+        let ranges = self.get_ruv().current_ruv_range();
+        self.get_idlayer().write_db_ruv(&ranges)?;
+
         let BackendWriteTransaction {
             idlayer,
             idxmeta: _,
             ruv,
             idxmeta_wr,
+            sealed_attrs,
         } = self;
 
         // Unwrap the Cell we have finished with it.
         let idlayer = idlayer.into_inner();
         let ruv = ruv.into_inner();
 
+        // The entries of this transaction are still to be written, so they are sealed with
+        // the attributes of its schema.
+        if let Some(sealed_attrs) = sealed_attrs {
+            let is_empty = sealed_attrs.is_empty();
+            if !idlayer.set_sealed_attrs(sealed_attrs) && !is_empty {
+                admin_warn!(
+                    "Schema marks attributes as encrypted, but the database is not sealed. Configure db_seal_key_path to encrypt them."
+                );
+            }
+        }
+
         idlayer.commit().map(|()| {
             ruv.commit();
             idxmeta_wr.commit();
@@ -1614,6 +1904,15 @@ impl<'a> BackendWriteTransaction<'a> {
         self.get_idlayer().set_db_ts_max(ts)
     }
 
+    pub fn write_audit_records(&self, records: &[AuditRecord]) -> Result<(), OperationError> {
+        self.get_idlayer().write_audit_records(records)
+    }
+
+    /// Remove the audit records from before a unix timestamp, returning how many there were.
+    pub fn purge_audit_log(&self, before: i64) -> Result<usize, OperationError> {
+        self.get_idlayer().purge_audit_log(before)
+    }
+
     pub fn get_db_ts_max(&self, ts: Duration) -> Result<Duration, OperationError> {
         // if none, return ts. If found, return it.
         match self.get_idlayer().get_db_ts_max()? {
@@ -1679,9 +1978,8 @@ impl Backend {
             })
             .collect();
 
-        // RUV-TODO
-        // Load the replication update vector here. For now we rebuild every startup
-        // from the database.
+        // The replication update vector is rebuilt from the entry changelogs at startup,
+        // and the persisted ranges are merged in during the rebuild.
         let ruv = Arc::new(ReplicationUpdateVector::default());
 
         // this has a ::memory() type, but will path == "" work?
@@ -1693,6 +1991,23 @@ impl Backend {
             idxmeta: Arc::new(CowCell::new(IdxMeta::new(idxkeys))),
         };
 
+        if be.cfg.readonly {
+            // The database must already be setup, and the ruv is only rebuilt in memory.
+            let mut idl_read = be.idlayer.read();
+            let entries = idl_read.get_identry(&IdList::AllIds)?;
+            let ranges = idl_read.get_db_ruv()?;
+            let mut ruv_write = be.ruv.write();
+            ruv_write.rebuild(&entries).map_err(|e| {
+                admin_error!(?e, "Failed to reload ruv");
+                e
+            })?;
+            if let Some(ranges) = ranges {
+                ruv_write.merge_ranges(&ranges);
+            }
+            ruv_write.commit();
+            return Ok(be);
+        }
+
         // Now complete our setup with a txn
         // In this case we can use an empty idx meta because we don't
         // access any parts of
@@ -1724,6 +2039,10 @@ impl Backend {
         self.cfg.pool_size
     }
 
+    pub fn is_readonly(&self) -> bool {
+        self.cfg.readonly
+    }
+
     pub fn try_quiesce(&self) {
         self.idlayer.try_quiesce();
     }
@@ -1742,6 +2061,7 @@ impl Backend {
             idxmeta: self.idxmeta.read(),
             ruv: UnsafeCell::new(self.ruv.write()),
             idxmeta_wr: self.idxmeta.write(),
+            sealed_attrs: None,
         }
     }
 
@@ -1780,8 +2100,8 @@ mod tests {
 
     use super::super::entry::{Entry, EntryInit, EntryNew};
     use super::{
-        Backend, BackendConfig, BackendTransaction, BackendWriteTransaction, DbBackup, IdList,
-        IdxKey, OperationError,
+        Backend, BackendConfig, BackendTransaction, BackendWriteTransaction, DbBackup, FsType,
+        IdList, IdxKey, OperationError,
     };
     use crate::identity::Limits;
     use crate::prelude::*;
@@ -3162,4 +3482,75 @@ mod tests {
             assert!(res == Err(OperationError::ResourceLimit));
         })
     }
+
+    #[test]
+    fn test_be_readonly() {
+        let _ = sketching::test_init();
+        let db_file_name = format!(
+            "{}/.readonly_test.db",
+            option_env!("OUT_DIR").unwrap_or("/tmp")
+        );
+        let _ = fs::remove_file(&db_file_name);
+
+        let cfg = BackendConfig::new(&db_file_name, 1, FsType::Generic, None);
+        // A database that doesn't exist yet can't be opened read only.
+        assert!(Backend::new(cfg.clone().readonly(), Vec::new(), false).is_err());
+
+        let s_uuid = {
+            let be = Backend::new(cfg.clone(), Vec::new(), false).expect("Failed to setup backend");
+            let mut be_txn = be.write();
+            let s_uuid = be_txn.get_db_s_uuid();
+            let mut e: Entry<EntryInit, EntryNew> = Entry::new();
+            e.add_ava("userid", Value::from("william"));
+            e.add_ava("uuid", Value::from("db237e8a-0079-4b8c-8a56-593b22aa44d1"));
+            let ve = unsafe { e.into_sealed_new() };
+            assert!(be_txn.create(&CID_ZERO, vec![ve]).is_ok());
+            assert!(be_txn.commit().is_ok());
+            s_uuid
+        };
+
+        let be = Backend::new(cfg.readonly(), Vec::new(), false)
+            .expect("Failed to open backend read only");
+        assert!(be.is_readonly());
+        let be_txn = be.read();
+        assert_eq!(be_txn.get_db_s_uuid(), Ok(Some(s_uuid)));
+        assert!(be_txn.get_id2entry(1).is_ok());
+        assert!(be_txn.verify().is_empty());
+        drop(be_txn);
+
+        let _ = fs::remove_file(&db_file_name);
+    }
+
+    #[test]
+    fn test_be_inspect_uuid() {
+        let _ = sketching::test_init();
+        let idxmeta = vec![IdxKey {
+            attr: AttrString::from("uuid"),
+            itype: IndexType::Equality,
+        }];
+        let be = Backend::new(BackendConfig::new_test(), idxmeta, false)
+            .expect("Failed to setup backend");
+        let uuid = uuid::uuid!("db237e8a-0079-4b8c-8a56-593b22aa44d1");
+
+        let mut be_txn = be.write();
+        let mut e: Entry<EntryInit, EntryNew> = Entry::new();
+        e.add_ava("userid", Value::from("william"));
+        e.add_ava("uuid", Value::Uuid(uuid));
+        let ve = unsafe { e.into_sealed_new() };
+        assert!(be_txn.create(&CID_ZERO, vec![ve]).is_ok());
+        assert!(be_txn.commit().is_ok());
+
+        let be_txn = be.read();
+        let id = be_txn
+            .get_uuid2id(uuid)
+            .expect("Failed to read uuid index")
+            .expect("No id for uuid");
+        assert_eq!(be_txn.get_uuid2id(Uuid::new_v4()), Ok(None));
+        let refs = be_txn.list_index_refs(id).expect("Failed to read indexes");
+        assert_eq!(
+            refs,
+            vec![("idx_eq_uuid".to_string(), uuid.as_hyphenated().to_string())]
+        );
+        assert_eq!(be_txn.list_ruv_changes(id), vec![CID_ZERO.clone()]);
+    }
 }
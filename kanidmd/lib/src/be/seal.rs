@@ -0,0 +1,395 @@
+//! Sealing of secret values at rest. Secrets such as radius secrets and credentials are
+//! encrypted with a key that is kept outside of the database before they are written, so that
+//! a copy of the database or a backup of it alone does not disclose them. They are unsealed as
+//! entries are read, so the rest of the server only ever sees the values themself.
+//!
+//! Schema may also mark other attributes as encrypted, such as those that hold personal
+//! information, and their values are sealed in the same way.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+use std::fs;
+use std::io::{ErrorKind, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::sync::{PoisonError, RwLock};
+
+use openssl::rand::rand_bytes;
+use openssl::symm::{decrypt_aead, encrypt_aead, Cipher};
+
+use crate::be::dbentry::{DbEntry, DbEntryVers};
+use crate::be::dbvalue::{DbValueSealedV1, DbValueSetV2};
+use crate::prelude::*;
+
+const SEAL_KEY_LEN: usize = 32;
+const SEAL_NONCE_LEN: usize = 12;
+const SEAL_TAG_LEN: usize = 16;
+// The valueset is sealed to the uuid of its entry and the name of its attribute, so that a
+// sealed value can't be moved to another entry or attribute.
+const SEAL_V1: u8 = 0x01;
+
+pub struct DbSeal {
+    key: [u8; SEAL_KEY_LEN],
+    // The attributes schema marks as encrypted. This is only changed as a write transaction
+    // commits, so it is consistent for the entries that transaction writes.
+    sealed_attrs: RwLock<BTreeSet<AttrString>>,
+}
+
+fn new_key() -> Result<[u8; SEAL_KEY_LEN], OperationError> {
+    let mut key = [0; SEAL_KEY_LEN];
+    rand_bytes(&mut key).map_err(|e| {
+        admin_error!(?e, "seal key generation error");
+        OperationError::CryptographyError
+    })?;
+    Ok(key)
+}
+
+/// The additional data a valueset of this entry and attribute is sealed with.
+fn seal_aad(version: u8, uuid: &Uuid, attr: &str) -> Vec<u8> {
+    let mut aad = Vec::with_capacity(1 + 16 + attr.len());
+    aad.push(version);
+    aad.extend_from_slice(uuid.as_bytes());
+    aad.extend_from_slice(attr.as_bytes());
+    aad
+}
+
+/// The uuid of the entry, which is only needed if it has values to seal or unseal.
+fn dbentry_uuid(attrs: &BTreeMap<AttrString, DbValueSetV2>) -> Option<Uuid> {
+    match attrs.get("uuid") {
+        Some(DbValueSetV2::Uuid(uuids)) if uuids.len() == 1 => Some(uuids[0]),
+        _ => None,
+    }
+}
+
+fn no_dbentry_uuid() -> OperationError {
+    admin_error!("Unable to seal or unseal values of an entry without a uuid");
+    OperationError::InvalidEntryState
+}
+
+impl DbSeal {
+    /// The seal of the key file, which is created if it does not exist. If the key is lost,
+    /// the values it sealed can't be recovered.
+    pub fn new(key_path: &str) -> Result<Self, OperationError> {
+        let key = match fs::read(key_path) {
+            Ok(key) => <[u8; SEAL_KEY_LEN]>::try_from(key.as_slice()).map_err(|_| {
+                admin_error!("Invalid database seal key in {}", key_path);
+                OperationError::InvalidDbState
+            })?,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                let key = new_key()?;
+                fs::OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .mode(0o600)
+                    .open(key_path)
+                    .and_then(|mut f| f.write_all(&key))
+                    .map_err(|e| {
+                        admin_error!(?e, "Unable to create database seal key {}", key_path);
+                        OperationError::FsError
+                    })?;
+                admin_info!("Created database seal key {}", key_path);
+                key
+            }
+            Err(e) => {
+                admin_error!(?e, "Unable to read database seal key {}", key_path);
+                return Err(OperationError::FsError);
+            }
+        };
+        Ok(DbSeal {
+            key,
+            sealed_attrs: RwLock::new(BTreeSet::new()),
+        })
+    }
+
+    /// A seal with a key only in memory, for a database that is lost with it.
+    pub fn ephemeral() -> Result<Self, OperationError> {
+        Ok(DbSeal {
+            key: new_key()?,
+            sealed_attrs: RwLock::new(BTreeSet::new()),
+        })
+    }
+
+    /// Set the attributes that schema marks as encrypted, which are sealed as well as secrets.
+    pub fn set_sealed_attrs(&self, attrs: BTreeSet<AttrString>) {
+        *self
+            .sealed_attrs
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = attrs;
+    }
+
+    /// If the values of this set are secrets that must be sealed.
+    fn is_secret(dbvs: &DbValueSetV2) -> bool {
+        matches!(
+            dbvs,
+            DbValueSetV2::SecretValue(_) | DbValueSetV2::Credential(_)
+        )
+    }
+
+    fn seal_valueset(
+        &self,
+        uuid: &Uuid,
+        attr: &str,
+        dbvs: &DbValueSetV2,
+    ) -> Result<DbValueSealedV1, OperationError> {
+        let data = serde_json::to_vec(dbvs).map_err(|e| {
+            admin_error!(?e, "Serde JSON Error");
+            OperationError::SerdeJsonError
+        })?;
+
+        let mut nonce = [0; SEAL_NONCE_LEN];
+        rand_bytes(&mut nonce).map_err(|e| {
+            admin_error!(?e, "seal nonce generation error");
+            OperationError::CryptographyError
+        })?;
+        let mut tag = [0; SEAL_TAG_LEN];
+        let mut ciphertext = encrypt_aead(
+            Cipher::aes_256_gcm(),
+            &self.key,
+            Some(&nonce),
+            &seal_aad(SEAL_V1, uuid, attr),
+            &data,
+            &mut tag,
+        )
+        .map_err(|e| {
+            admin_error!(?e, "seal error");
+            OperationError::CryptographyError
+        })?;
+        ciphertext.extend_from_slice(&tag);
+
+        Ok(DbValueSealedV1 {
+            version: SEAL_V1,
+            count: dbvs.len(),
+            nonce: nonce.to_vec(),
+            data: ciphertext,
+        })
+    }
+
+    fn unseal_valueset(
+        &self,
+        uuid: &Uuid,
+        attr: &str,
+        sealed: &DbValueSealedV1,
+    ) -> Result<DbValueSetV2, OperationError> {
+        if sealed.version != SEAL_V1 {
+            admin_error!(version = %sealed.version, "Unknown seal version of sealed value");
+            return Err(OperationError::InvalidValueState);
+        }
+        if sealed.data.len() < SEAL_TAG_LEN {
+            return Err(OperationError::InvalidValueState);
+        }
+        let (ciphertext, tag) = sealed.data.split_at(sealed.data.len() - SEAL_TAG_LEN);
+        let data = decrypt_aead(
+            Cipher::aes_256_gcm(),
+            &self.key,
+            Some(&sealed.nonce),
+            &seal_aad(sealed.version, uuid, attr),
+            ciphertext,
+            tag,
+        )
+        .map_err(|e| {
+            admin_error!(?e, "Unable to unseal value, is this the database seal key?");
+            OperationError::CryptographyError
+        })?;
+
+        serde_json::from_slice(&data).map_err(|e| {
+            admin_error!(?e, "Serde JSON Error");
+            OperationError::SerdeJsonError
+        })
+    }
+
+    /// Seal the secret values of an entry before it is written.
+    pub fn seal_dbentry(&self, dbe: DbEntry) -> Result<DbEntry, OperationError> {
+        let sealed_attrs = self
+            .sealed_attrs
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        match dbe.ent {
+            DbEntryVers::V2(mut v2) => {
+                let uuid = dbentry_uuid(&v2.attrs);
+                v2.attrs = v2
+                    .attrs
+                    .into_iter()
+                    .map(|(k, dbvs)| {
+                        let sealed = matches!(dbvs, DbValueSetV2::Sealed(_));
+                        if !sealed && (Self::is_secret(&dbvs) || sealed_attrs.contains(&k)) {
+                            let uuid = uuid.ok_or_else(no_dbentry_uuid)?;
+                            self.seal_valueset(&uuid, &k, &dbvs)
+                                .map(|sealed| (k, DbValueSetV2::Sealed(sealed)))
+                        } else {
+                            Ok((k, dbvs))
+                        }
+                    })
+                    .collect::<Result<BTreeMap<_, _>, _>>()?;
+                Ok(DbEntry {
+                    ent: DbEntryVers::V2(v2),
+                })
+            }
+            ent => Ok(DbEntry { ent }),
+        }
+    }
+
+    /// Unseal the secret values of an entry as it is read. Values that were written before
+    /// the database was sealed are returned as they are.
+    pub fn unseal_dbentry(&self, dbe: DbEntry) -> Result<DbEntry, OperationError> {
+        match dbe.ent {
+            DbEntryVers::V2(mut v2) => {
+                let uuid = dbentry_uuid(&v2.attrs);
+                v2.attrs = v2
+                    .attrs
+                    .into_iter()
+                    .map(|(k, dbvs)| match dbvs {
+                        DbValueSetV2::Sealed(sealed) => {
+                            let uuid = uuid.ok_or_else(no_dbentry_uuid)?;
+                            self.unseal_valueset(&uuid, &k, &sealed)
+                                .map(|dbvs| (k, dbvs))
+                        }
+                        dbvs => Ok((k, dbvs)),
+                    })
+                    .collect::<Result<BTreeMap<_, _>, _>>()?;
+                Ok(DbEntry {
+                    ent: DbEntryVers::V2(v2),
+                })
+            }
+            ent => Ok(DbEntry { ent }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::DbSeal;
+    use crate::be::dbentry::{DbEntry, DbEntryV2, DbEntryVers};
+    use crate::be::dbvalue::DbValueSetV2;
+    use crate::prelude::*;
+
+    fn dbentry(attrs: Vec<(&str, DbValueSetV2)>) -> DbEntry {
+        DbEntry {
+            ent: DbEntryVers::V2(DbEntryV2 {
+                attrs: attrs.into_iter().map(|(k, v)| (k.into(), v)).collect(),
+                attr_cids: BTreeMap::new(),
+            }),
+        }
+    }
+
+    fn dbentry_attrs(dbe: DbEntry) -> BTreeMap<AttrString, DbValueSetV2> {
+        match dbe.ent {
+            DbEntryVers::V2(v2) => v2.attrs,
+            _ => panic!("unexpected entry version"),
+        }
+    }
+
+    #[test]
+    fn test_be_seal_dbentry() {
+        let seal = DbSeal::ephemeral().expect("failed to create seal");
+
+        let dbe = dbentry(vec![
+            ("uuid", DbValueSetV2::Uuid(vec![Uuid::new_v4()])),
+            ("name", DbValueSetV2::Iname(vec!["testperson".to_string()])),
+            (
+                "radius_secret",
+                DbValueSetV2::SecretValue(vec!["very secret".to_string()]),
+            ),
+        ]);
+
+        let sealed = seal.seal_dbentry(dbe).expect("failed to seal");
+        let sealed_str = serde_json::to_string(&sealed).expect("failed to serialise");
+        // Only the secret is sealed.
+        assert!(!sealed_str.contains("very secret"));
+        assert!(sealed_str.contains("testperson"));
+
+        // Another key can't unseal it.
+        let sealed: DbEntry = serde_json::from_str(&sealed_str).expect("failed to deserialise");
+        let other = DbSeal::ephemeral().expect("failed to create seal");
+        assert!(other.unseal_dbentry(sealed).is_err());
+
+        let sealed: DbEntry = serde_json::from_str(&sealed_str).expect("failed to deserialise");
+        let unsealed = seal.unseal_dbentry(sealed).expect("failed to unseal");
+        match unsealed.ent {
+            DbEntryVers::V2(v2) => {
+                assert!(matches!(
+                    v2.attrs.get("radius_secret"),
+                    Some(DbValueSetV2::SecretValue(s)) if s == &vec!["very secret".to_string()]
+                ));
+            }
+            _ => panic!("unexpected entry version"),
+        }
+    }
+
+    #[test]
+    fn test_be_seal_dbentry_sealed_attrs() {
+        let seal = DbSeal::ephemeral().expect("failed to create seal");
+        seal.set_sealed_attrs(["legalname".into()].into_iter().collect());
+
+        let dbe = dbentry(vec![
+            ("uuid", DbValueSetV2::Uuid(vec![Uuid::new_v4()])),
+            ("name", DbValueSetV2::Iname(vec!["testperson".to_string()])),
+            (
+                "legalname",
+                DbValueSetV2::Utf8(vec!["Test Person".to_string()]),
+            ),
+        ]);
+
+        let sealed = seal.seal_dbentry(dbe).expect("failed to seal");
+        // A value that is already sealed is not sealed again.
+        let sealed = seal.seal_dbentry(sealed).expect("failed to seal");
+        let sealed_str = serde_json::to_string(&sealed).expect("failed to serialise");
+        assert!(!sealed_str.contains("Test Person"));
+        assert!(sealed_str.contains("testperson"));
+
+        // Values stay readable when the attribute is no longer marked.
+        seal.set_sealed_attrs(Default::default());
+        let unsealed = seal.unseal_dbentry(sealed).expect("failed to unseal");
+        match unsealed.ent {
+            DbEntryVers::V2(v2) => {
+                assert!(matches!(
+                    v2.attrs.get("legalname"),
+                    Some(DbValueSetV2::Utf8(s)) if s == &vec!["Test Person".to_string()]
+                ));
+            }
+            _ => panic!("unexpected entry version"),
+        }
+    }
+
+    #[test]
+    fn test_be_seal_dbentry_moved_value() {
+        let seal = DbSeal::ephemeral().expect("failed to create seal");
+        let uuid = Uuid::new_v4();
+
+        let dbe = dbentry(vec![
+            ("uuid", DbValueSetV2::Uuid(vec![uuid])),
+            (
+                "radius_secret",
+                DbValueSetV2::SecretValue(vec!["very secret".to_string()]),
+            ),
+        ]);
+        let sealed = seal.seal_dbentry(dbe).expect("failed to seal");
+        let sealed_str = serde_json::to_string(&sealed).expect("failed to serialise");
+
+        // A sealed value can't be unsealed in another attribute,
+        let mut attrs = dbentry_attrs(serde_json::from_str(&sealed_str).unwrap());
+        let value = attrs.remove("radius_secret").unwrap();
+        attrs.insert("legalname".into(), value);
+        let moved = DbEntry {
+            ent: DbEntryVers::V2(DbEntryV2 {
+                attrs,
+                attr_cids: BTreeMap::new(),
+            }),
+        };
+        assert!(seal.unseal_dbentry(moved).is_err());
+
+        // or in another entry.
+        let mut attrs = dbentry_attrs(serde_json::from_str(&sealed_str).unwrap());
+        attrs.insert("uuid".into(), DbValueSetV2::Uuid(vec![Uuid::new_v4()]));
+        let moved = DbEntry {
+            ent: DbEntryVers::V2(DbEntryV2 {
+                attrs,
+                attr_cids: BTreeMap::new(),
+            }),
+        };
+        assert!(seal.unseal_dbentry(moved).is_err());
+
+        let sealed: DbEntry = serde_json::from_str(&sealed_str).expect("failed to deserialise");
+        assert!(seal.unseal_dbentry(sealed).is_ok());
+    }
+}
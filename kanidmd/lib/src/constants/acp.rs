@@ -95,6 +95,7 @@ pub const JSON_IDM_SELF_ACP_READ_V1: &str = r#"{
             "spn",
             "displayname",
             "legalname",
+            "image",
             "class",
             "memberof",
             "radius_secret",
@@ -105,6 +106,7 @@ pub const JSON_IDM_SELF_ACP_READ_V1: &str = r#"{
             "account_valid_from",
             "primary_credential",
             "user_auth_token_session",
+            "oauth2_session",
             "passkeys",
             "devicekeys"
         ]
@@ -123,10 +125,10 @@ pub const JSON_IDM_SELF_ACP_WRITE_V1: &str = r#"{
             "{\"and\": [{\"eq\": [\"class\",\"person\"]}, {\"eq\": [\"class\",\"account\"]}, \"self\"]}"
         ],
         "acp_modify_removedattr": [
-            "name", "displayname", "legalname", "radius_secret", "primary_credential", "ssh_publickey", "unix_password", "passkeys", "devicekeys", "user_auth_token_session"
+            "name", "displayname", "legalname", "image", "radius_secret", "primary_credential", "ssh_publickey", "unix_password", "passkeys", "devicekeys", "user_auth_token_session", "oauth2_session"
         ],
         "acp_modify_presentattr": [
-            "name", "displayname", "legalname", "radius_secret", "primary_credential", "ssh_publickey", "unix_password", "passkeys", "devicekeys"
+            "name", "displayname", "legalname", "image", "radius_secret", "primary_credential", "ssh_publickey", "unix_password", "passkeys", "devicekeys"
         ]
     }
 }"#;
@@ -194,7 +196,7 @@ pub const JSON_IDM_ACP_PEOPLE_READ_PRIV_V1: &str = r#"{
             "{\"and\": [{\"eq\": [\"class\",\"person\"]}, {\"andnot\": {\"or\": [{\"eq\": [\"memberof\",\"00000000-0000-0000-0000-000000001000\"]}, {\"eq\": [\"class\", \"tombstone\"]}, {\"eq\": [\"class\", \"recycled\"]}]}}]}"
         ],
         "acp_search_attr": [
-            "name", "displayname", "legalname", "mail"
+            "name", "displayname", "legalname", "mail", "phone_number", "postal_address", "image"
         ]
     }
 }"#;
@@ -215,10 +217,10 @@ pub const JSON_IDM_ACP_PEOPLE_WRITE_PRIV_V1: &str = r#"{
             "{\"and\": [{\"eq\": [\"class\",\"person\"]}, {\"andnot\": {\"or\": [{\"eq\": [\"memberof\",\"00000000-0000-0000-0000-000000001000\"]}, {\"eq\": [\"class\", \"tombstone\"]}, {\"eq\": [\"class\", \"recycled\"]}]}}]}"
         ],
         "acp_modify_removedattr": [
-            "name", "displayname", "legalname", "mail"
+            "name", "displayname", "legalname", "mail", "phone_number", "postal_address", "image"
         ],
         "acp_modify_presentattr": [
-            "name", "displayname", "legalname", "mail"
+            "name", "displayname", "legalname", "mail", "phone_number", "postal_address", "image"
         ]
     }
 }"#;
@@ -249,7 +251,10 @@ pub const JSON_IDM_ACP_PEOPLE_MANAGE_PRIV_V1: &str = r#"{
             "devicekeys",
             "user_auth_token_session",
             "ssh_publickey",
-            "mail"
+            "mail",
+            "phone_number",
+            "postal_address",
+            "image"
         ],
         "acp_create_class": [
             "object", "person", "account"
@@ -301,10 +306,10 @@ pub const JSON_IDM_ACP_PEOPLE_EXTEND_PRIV_V1: &str = r#"{
             "{\"and\": [{\"eq\": [\"class\",\"account\"]}, {\"andnot\": {\"or\": [{\"eq\": [\"memberof\",\"00000000-0000-0000-0000-000000001000\"]}, {\"eq\": [\"class\", \"tombstone\"]}, {\"eq\": [\"class\", \"recycled\"]}]}}]}"
         ],
         "acp_modify_removedattr": [
-            "name", "displayname", "legalname", "mail"
+            "name", "displayname", "legalname", "mail", "phone_number", "postal_address", "image"
         ],
         "acp_modify_presentattr": [
-            "class", "name", "displayname", "legalname", "mail"
+            "class", "name", "displayname", "legalname", "mail", "phone_number", "postal_address", "image"
         ],
         "acp_modify_class": ["person"]
     }
@@ -327,7 +332,7 @@ pub const JSON_IDM_ACP_HP_PEOPLE_READ_PRIV_V1: &str = r#"{
             "{\"and\": [{\"eq\": [\"class\",\"person\"]}, {\"eq\": [\"memberof\",\"00000000-0000-0000-0000-000000001000\"]}, {\"andnot\": {\"or\": [{\"eq\": [\"class\", \"tombstone\"]}, {\"eq\": [\"class\", \"recycled\"]}]}}]}"
         ],
         "acp_search_attr": [
-            "name", "displayname", "legalname", "mail"
+            "name", "displayname", "legalname", "mail", "phone_number", "postal_address", "image"
         ]
     }
 }"#;
@@ -379,10 +384,10 @@ pub const JSON_IDM_ACP_HP_PEOPLE_WRITE_PRIV_V1: &str = r#"{
             "{\"and\": [{\"eq\": [\"class\",\"person\"]}, {\"eq\": [\"memberof\",\"00000000-0000-0000-0000-000000001000\"]}, {\"andnot\": {\"or\": [{\"eq\": [\"class\", \"tombstone\"]}, {\"eq\": [\"class\", \"recycled\"]}]}}]}"
         ],
         "acp_modify_removedattr": [
-            "name", "displayname", "legalname", "mail"
+            "name", "displayname", "legalname", "mail", "phone_number", "postal_address", "image"
         ],
         "acp_modify_presentattr": [
-            "name", "displayname", "legalname", "mail"
+            "name", "displayname", "legalname", "mail", "phone_number", "postal_address", "image"
         ]
     }
 }"#;
@@ -403,10 +408,10 @@ pub const JSON_IDM_ACP_HP_PEOPLE_EXTEND_PRIV_V1: &str = r#"{
             "{\"and\": [{\"eq\": [\"class\",\"account\"]}, {\"eq\": [\"memberof\",\"00000000-0000-0000-0000-000000001000\"]}, {\"andnot\": {\"or\": [{\"eq\": [\"class\", \"tombstone\"]}, {\"eq\": [\"class\", \"recycled\"]}]}}]}"
         ],
         "acp_modify_removedattr": [
-            "name", "displayname", "legalname", "mail"
+            "name", "displayname", "legalname", "mail", "phone_number", "postal_address", "image"
         ],
         "acp_modify_presentattr": [
-            "class", "name", "displayname", "legalname", "mail"
+            "class", "name", "displayname", "legalname", "mail", "phone_number", "postal_address", "image"
         ],
         "acp_modify_class": ["person"]
     }
@@ -459,7 +464,7 @@ pub const JSON_IDM_ACP_ACCOUNT_READ_PRIV_V1: &str = r#"{
             "{\"and\": [{\"eq\": [\"class\",\"account\"]}, {\"andnot\": {\"or\": [{\"eq\": [\"memberof\",\"00000000-0000-0000-0000-000000001000\"]}, {\"eq\": [\"class\", \"tombstone\"]}, {\"eq\": [\"class\", \"recycled\"]}]}}]}"
         ],
         "acp_search_attr": [
-            "class", "name", "spn", "uuid", "displayname", "ssh_publickey", "primary_credential", "memberof", "mail", "gidnumber", "account_expire", "account_valid_from", "passkeys", "devicekeys", "api_token_session", "user_auth_token_session"
+            "class", "name", "spn", "uuid", "displayname", "ssh_publickey", "primary_credential", "memberof", "mail", "gidnumber", "account_expire", "account_valid_from", "passkeys", "devicekeys", "api_token_session", "user_auth_token_session", "oauth2_session"
         ]
     }
 }"#;
@@ -480,10 +485,10 @@ pub const JSON_IDM_ACP_ACCOUNT_WRITE_PRIV_V1: &str = r#"{
             "{\"and\": [{\"eq\": [\"class\",\"account\"]}, {\"andnot\": {\"or\": [{\"eq\": [\"memberof\",\"00000000-0000-0000-0000-000000001000\"]}, {\"eq\": [\"class\", \"tombstone\"]}, {\"eq\": [\"class\", \"recycled\"]}]}}]}"
         ],
         "acp_modify_removedattr": [
-            "name", "displayname", "ssh_publickey", "primary_credential", "mail", "account_expire", "account_valid_from", "passkeys", "devicekeys", "api_token_session", "user_auth_token_session"
+            "name", "displayname", "ssh_publickey", "primary_credential", "mail", "account_expire", "account_valid_from", "passkeys", "devicekeys", "api_token_session", "user_auth_token_session", "oauth2_session", "host_join_token"
         ],
         "acp_modify_presentattr": [
-            "name", "displayname", "ssh_publickey", "primary_credential", "mail", "account_expire", "account_valid_from", "passkeys", "devicekeys", "api_token_session"
+            "name", "displayname", "ssh_publickey", "primary_credential", "mail", "account_expire", "account_valid_from", "passkeys", "devicekeys", "api_token_session", "host_join_token"
         ]
     }
 }"#;
@@ -518,7 +523,7 @@ pub const JSON_IDM_ACP_ACCOUNT_MANAGE_PRIV_V1: &str = r#"{
             "devicekeys"
         ],
         "acp_create_class": [
-            "object", "account", "service_account"
+            "object", "account", "service_account", "host"
         ]
     }
 }"#;
@@ -610,7 +615,7 @@ pub const JSON_IDM_ACP_HP_ACCOUNT_READ_PRIV_V1: &str = r#"{
             "{\"and\": [{\"eq\": [\"class\",\"account\"]}, {\"eq\": [\"memberof\",\"00000000-0000-0000-0000-000000001000\"]}, {\"andnot\": {\"or\": [{\"eq\": [\"class\", \"tombstone\"]}, {\"eq\": [\"class\", \"recycled\"]}]}}]}"
         ],
         "acp_search_attr": [
-            "class", "name", "spn", "uuid", "displayname", "ssh_publickey", "primary_credential", "memberof", "account_expire", "account_valid_from", "passkeys", "devicekeys", "api_token_session", "user_auth_token_session"
+            "class", "name", "spn", "uuid", "displayname", "ssh_publickey", "primary_credential", "memberof", "account_expire", "account_valid_from", "passkeys", "devicekeys", "api_token_session", "user_auth_token_session", "oauth2_session"
         ]
     }
 }"#;
@@ -631,10 +636,10 @@ pub const JSON_IDM_ACP_HP_ACCOUNT_WRITE_PRIV_V1: &str = r#"{
             "{\"and\": [{\"eq\": [\"class\",\"account\"]}, {\"eq\": [\"memberof\",\"00000000-0000-0000-0000-000000001000\"]}, {\"andnot\": {\"or\": [{\"eq\": [\"class\", \"tombstone\"]}, {\"eq\": [\"class\", \"recycled\"]}]}}]}"
         ],
         "acp_modify_removedattr": [
-            "name", "displayname", "ssh_publickey", "primary_credential", "account_expire", "account_valid_from", "passkeys", "devicekeys", "api_token_session", "user_auth_token_session"
+            "name", "displayname", "ssh_publickey", "primary_credential", "account_expire", "account_valid_from", "passkeys", "devicekeys", "api_token_session", "user_auth_token_session", "oauth2_session", "host_join_token"
         ],
         "acp_modify_presentattr": [
-            "name", "displayname", "ssh_publickey", "primary_credential", "account_expire", "account_valid_from", "passkeys", "devicekeys", "api_token_session"
+            "name", "displayname", "ssh_publickey", "primary_credential", "account_expire", "account_valid_from", "passkeys", "devicekeys", "api_token_session", "host_join_token"
         ]
     }
 }"#;
@@ -934,7 +939,7 @@ pub const JSON_IDM_ACP_HP_ACCOUNT_MANAGE_PRIV_V1: &str = r#"{
             "devicekeys"
         ],
         "acp_create_class": [
-            "object", "account", "service_account"
+            "object", "account", "service_account", "host"
         ]
     }
 }"#;
@@ -987,7 +992,11 @@ pub const JSON_IDM_ACP_DOMAIN_ADMIN_PRIV_V1: &str = r#"{
         ],
         "acp_search_attr": [
             "domain_display_name",
+            "domain_ldap_basedn",
+            "domain_ldap_rdn_attr",
+            "domain_ldap_attr_map",
             "domain_name",
+            "domain_read_only_replica",
             "domain_ssid",
             "domain_uuid",
             "es256_private_key_der",
@@ -997,12 +1006,20 @@ pub const JSON_IDM_ACP_DOMAIN_ADMIN_PRIV_V1: &str = r#"{
         ],
         "acp_modify_removedattr": [
             "domain_display_name",
+            "domain_ldap_basedn",
+            "domain_ldap_rdn_attr",
+            "domain_ldap_attr_map",
+            "domain_read_only_replica",
             "domain_ssid",
             "es256_private_key_der",
             "fernet_private_key_str"
         ],
         "acp_modify_presentattr": [
             "domain_display_name",
+            "domain_ldap_basedn",
+            "domain_ldap_rdn_attr",
+            "domain_ldap_attr_map",
+            "domain_read_only_replica",
             "domain_ssid"
         ]
     }
@@ -1061,7 +1078,7 @@ pub const JSON_IDM_ACP_ACCOUNT_UNIX_EXTEND_PRIV_V1: &str = r#"{
             "class", "name", "spn", "uuid", "description", "gidnumber", "loginshell", "unix_password"
         ],
         "acp_modify_removedattr": [
-            "loginshell", "gidnumber", "unix_password"
+            "class", "loginshell", "gidnumber", "unix_password"
         ],
         "acp_modify_presentattr": [
             "class", "loginshell", "gidnumber", "unix_password"
@@ -1090,7 +1107,7 @@ pub const JSON_IDM_ACP_GROUP_UNIX_EXTEND_PRIV_V1: &str = r#"{
             "class", "name", "spn", "uuid", "description", "member", "gidnumber"
         ],
         "acp_modify_removedattr": [
-            "gidnumber"
+            "class", "gidnumber"
         ],
         "acp_modify_presentattr": [
             "class", "gidnumber"
@@ -1120,7 +1137,7 @@ pub const JSON_IDM_HP_ACP_ACCOUNT_UNIX_EXTEND_PRIV_V1: &str = r#"{
             "class", "name", "spn", "uuid", "description", "gidnumber", "loginshell", "unix_password"
         ],
         "acp_modify_removedattr": [
-            "loginshell", "gidnumber", "unix_password"
+            "class", "loginshell", "gidnumber", "unix_password"
         ],
         "acp_modify_presentattr": [
             "class", "loginshell", "gidnumber", "unix_password"
@@ -1149,7 +1166,7 @@ pub const JSON_IDM_HP_ACP_GROUP_UNIX_EXTEND_PRIV_V1: &str = r#"{
             "class", "name", "spn", "uuid", "description", "member", "gidnumber"
         ],
         "acp_modify_removedattr": [
-            "gidnumber"
+            "class", "gidnumber"
         ],
         "acp_modify_presentattr": [
             "class", "gidnumber"
@@ -1192,7 +1209,13 @@ pub const JSON_IDM_HP_ACP_OAUTH2_MANAGE_PRIV_V1: &str = r#"{
             "oauth2_allow_insecure_client_disable_pkce",
             "rs256_private_key_der",
             "oauth2_jwt_legacy_crypto_enable",
-            "oauth2_prefer_short_username"
+            "oauth2_prefer_short_username",
+            "oauth2_access_token_expiry",
+            "oauth2_refresh_token_expiry",
+            "oauth2_session_outlive_parent",
+            "oauth2_jwt_sign_alg",
+            "oauth2_token_exchange_audience",
+            "image"
         ],
         "acp_modify_removedattr": [
             "description",
@@ -1208,7 +1231,13 @@ pub const JSON_IDM_HP_ACP_OAUTH2_MANAGE_PRIV_V1: &str = r#"{
             "oauth2_allow_insecure_client_disable_pkce",
             "rs256_private_key_der",
             "oauth2_jwt_legacy_crypto_enable",
-            "oauth2_prefer_short_username"
+            "oauth2_prefer_short_username",
+            "oauth2_access_token_expiry",
+            "oauth2_refresh_token_expiry",
+            "oauth2_session_outlive_parent",
+            "oauth2_jwt_sign_alg",
+            "oauth2_token_exchange_audience",
+            "image"
         ],
         "acp_modify_presentattr": [
             "description",
@@ -1220,7 +1249,13 @@ pub const JSON_IDM_HP_ACP_OAUTH2_MANAGE_PRIV_V1: &str = r#"{
             "oauth2_rs_scope_map",
             "oauth2_allow_insecure_client_disable_pkce",
             "oauth2_jwt_legacy_crypto_enable",
-            "oauth2_prefer_short_username"
+            "oauth2_prefer_short_username",
+            "oauth2_access_token_expiry",
+            "oauth2_refresh_token_expiry",
+            "oauth2_session_outlive_parent",
+            "oauth2_jwt_sign_alg",
+            "oauth2_token_exchange_audience",
+            "image"
         ],
         "acp_modify_class": [],
         "acp_create_attr": [
@@ -1234,9 +1269,15 @@ pub const JSON_IDM_HP_ACP_OAUTH2_MANAGE_PRIV_V1: &str = r#"{
             "oauth2_rs_scope_map",
             "oauth2_allow_insecure_client_disable_pkce",
             "oauth2_jwt_legacy_crypto_enable",
-            "oauth2_prefer_short_username"
-        ],
-        "acp_create_class": ["oauth2_resource_server", "oauth2_resource_server_basic", "object"]
+            "oauth2_prefer_short_username",
+            "oauth2_access_token_expiry",
+            "oauth2_refresh_token_expiry",
+            "oauth2_session_outlive_parent",
+            "oauth2_jwt_sign_alg",
+            "oauth2_token_exchange_audience",
+            "image"
+        ],
+        "acp_create_class": ["oauth2_resource_server", "oauth2_resource_server_basic", "oauth2_resource_server_public", "object"]
     }
 }"#;
 
@@ -1341,3 +1382,52 @@ pub const JSON_IDM_HP_ACP_SYNC_ACCOUNT_MANAGE_PRIV_V1: &str = r#"{
         "acp_create_class": ["sync_account", "object"]
     }
 }"#;
+
+pub const JSON_IDM_ACP_REPL_AGREEMENT_MANAGE_PRIV_V1: &str = r#"{
+    "attrs": {
+        "class": [
+            "object",
+            "access_control_profile",
+            "access_control_search",
+            "access_control_modify",
+            "access_control_delete",
+            "access_control_create"
+        ],
+        "name": ["idm_acp_repl_agreement_manage_priv"],
+        "uuid": ["00000000-0000-0000-0000-ffffff000046"],
+        "description": ["Builtin IDM Control for managing replication agreements and their pinned certificates"],
+        "acp_receiver": [],
+        "acp_receiver_group": ["00000000-0000-0000-0000-000000000019"],
+        "acp_targetscope": [
+            "{\"and\": [{\"eq\": [\"class\",\"repl_agreement\"]},{\"andnot\": {\"or\": [{\"eq\": [\"class\", \"tombstone\"]}, {\"eq\": [\"class\", \"recycled\"]}]}}]}"
+        ],
+        "acp_search_attr": [
+            "class",
+            "name",
+            "description",
+            "repl_partner_address",
+            "repl_partner_cert_sha256"
+        ],
+        "acp_modify_removedattr": [
+            "name",
+            "description",
+            "repl_partner_address",
+            "repl_partner_cert_sha256"
+        ],
+        "acp_modify_presentattr": [
+            "name",
+            "description",
+            "repl_partner_address",
+            "repl_partner_cert_sha256"
+        ],
+        "acp_modify_class": [],
+        "acp_create_attr": [
+            "class",
+            "name",
+            "description",
+            "repl_partner_address",
+            "repl_partner_cert_sha256"
+        ],
+        "acp_create_class": ["repl_agreement", "object"]
+    }
+}"#;
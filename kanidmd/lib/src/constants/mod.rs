@@ -39,6 +39,21 @@ pub const RECYCLEBIN_MAX_AGE: u64 = 300;
 /// In production we allow 1 week
 pub const RECYCLEBIN_MAX_AGE: u64 = 604_800;
 
+/// The page size of searches by sessions that are not privileged, when none is requested.
+pub const SEARCH_PAGE_SIZE_DEFAULT: usize = 64;
+/// The largest page size that a session that is not privileged may request.
+pub const SEARCH_PAGE_SIZE_MAX: usize = 128;
+
+/// The largest request body that the http api accepts, in bytes.
+pub const MAXIMUM_REQUEST_SIZE_DEFAULT: usize = 4 * 1024 * 1024;
+
+/// The largest binary value of an attribute that has no max_size in its schema, in bytes.
+pub const BINARY_MAX_SIZE_DEFAULT: u32 = 64 * 1024;
+/// The largest image of an attribute that has no max_size in its schema, in bytes.
+pub const IMAGE_MAX_SIZE_DEFAULT: u32 = 256 * 1024;
+/// The largest width and height of an image, in pixels.
+pub const IMAGE_MAX_DIMENSION: u32 = 1024;
+
 // 5 minute auth session window.
 pub const AUTH_SESSION_TIMEOUT: u64 = 300;
 // 5 minute mfa reg window
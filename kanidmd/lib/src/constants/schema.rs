@@ -261,6 +261,93 @@ pub const JSON_SCHEMA_ATTR_DOMAIN_DISPLAY_NAME: &str = r#"{
   }
 }"#;
 
+pub const JSON_SCHEMA_ATTR_DOMAIN_LDAP_BASEDN: &str = r#"{
+  "attrs": {
+    "class": [
+      "object",
+      "system",
+      "attributetype"
+    ],
+    "description": [
+      "The base DN that entries are presented under by the LDAP interface."
+    ],
+    "index": [],
+    "unique": [
+      "false"
+    ],
+    "multivalue": [
+      "false"
+    ],
+    "attributename": [
+      "domain_ldap_basedn"
+    ],
+    "syntax": [
+      "UTF8STRING_INSENSITIVE"
+    ],
+    "uuid": [
+      "00000000-0000-0000-0000-ffff00000132"
+    ]
+  }
+}"#;
+
+pub const JSON_SCHEMA_ATTR_DOMAIN_LDAP_RDN_ATTR: &str = r#"{
+  "attrs": {
+    "class": [
+      "object",
+      "system",
+      "attributetype"
+    ],
+    "description": [
+      "The attribute used to form an entry's RDN in the LDAP interface, optionally per class."
+    ],
+    "index": [],
+    "unique": [
+      "false"
+    ],
+    "multivalue": [
+      "true"
+    ],
+    "attributename": [
+      "domain_ldap_rdn_attr"
+    ],
+    "syntax": [
+      "UTF8STRING_INSENSITIVE"
+    ],
+    "uuid": [
+      "00000000-0000-0000-0000-ffff00000133"
+    ]
+  }
+}"#;
+
+pub const JSON_SCHEMA_ATTR_DOMAIN_LDAP_ATTR_MAP: &str = r#"{
+  "attrs": {
+    "class": [
+      "object",
+      "system",
+      "attributetype"
+    ],
+    "description": [
+      "Aliases of LDAP attribute names to Kanidm attributes, in the form ldapname:attribute."
+    ],
+    "index": [],
+    "unique": [
+      "false"
+    ],
+    "multivalue": [
+      "true"
+    ],
+    "attributename": [
+      "domain_ldap_attr_map"
+    ],
+    "syntax": [
+      "UTF8STRING_INSENSITIVE"
+    ],
+    "uuid": [
+      "00000000-0000-0000-0000-ffff00000134"
+    ]
+  }
+}"#;
+
 pub const JSON_SCHEMA_ATTR_DOMAIN_UUID: &str = r#"{
     "attrs": {
       "class": [
@@ -691,6 +778,153 @@ pub const JSON_SCHEMA_ATTR_OAUTH2_RS_ORIGIN_LANDING: &str = r#"{
     }
 }"#;
 
+pub const JSON_SCHEMA_ATTR_OAUTH2_ACCESS_TOKEN_EXPIRY: &str = r#"{
+    "attrs": {
+      "class": [
+        "object",
+        "system",
+        "attributetype"
+      ],
+      "description": [
+        "The number of seconds an oauth2 access token is valid for"
+      ],
+      "index": [],
+      "unique": [
+        "false"
+      ],
+      "multivalue": [
+        "false"
+      ],
+      "attributename": [
+        "oauth2_access_token_expiry"
+      ],
+      "syntax": [
+        "UINT32"
+      ],
+      "uuid": [
+        "00000000-0000-0000-0000-ffff00000127"
+      ]
+    }
+}"#;
+
+pub const JSON_SCHEMA_ATTR_OAUTH2_REFRESH_TOKEN_EXPIRY: &str = r#"{
+    "attrs": {
+      "class": [
+        "object",
+        "system",
+        "attributetype"
+      ],
+      "description": [
+        "The number of seconds an oauth2 session may be refreshed for. If absent, refresh tokens are not issued"
+      ],
+      "index": [],
+      "unique": [
+        "false"
+      ],
+      "multivalue": [
+        "false"
+      ],
+      "attributename": [
+        "oauth2_refresh_token_expiry"
+      ],
+      "syntax": [
+        "UINT32"
+      ],
+      "uuid": [
+        "00000000-0000-0000-0000-ffff00000128"
+      ]
+    }
+}"#;
+
+pub const JSON_SCHEMA_ATTR_OAUTH2_SESSION_OUTLIVE_PARENT: &str = r#"{
+    "attrs": {
+      "class": [
+        "object",
+        "system",
+        "attributetype"
+      ],
+      "description": [
+        "Allow oauth2 sessions to remain valid after the user auth session that created them ends"
+      ],
+      "index": [],
+      "unique": [
+        "false"
+      ],
+      "multivalue": [
+        "false"
+      ],
+      "attributename": [
+        "oauth2_session_outlive_parent"
+      ],
+      "syntax": [
+        "BOOLEAN"
+      ],
+      "uuid": [
+        "00000000-0000-0000-0000-ffff00000129"
+      ]
+    }
+}"#;
+
+pub const JSON_SCHEMA_ATTR_OAUTH2_JWT_SIGN_ALG: &str = r#"{
+    "attrs": {
+      "class": [
+        "object",
+        "system",
+        "attributetype"
+      ],
+      "description": [
+        "The algorithm used to sign id tokens issued to this client (es256, rs256, hs256)"
+      ],
+      "index": [],
+      "unique": [
+        "false"
+      ],
+      "multivalue": [
+        "false"
+      ],
+      "attributename": [
+        "oauth2_jwt_sign_alg"
+      ],
+      "syntax": [
+        "UTF8STRING_INSENSITIVE"
+      ],
+      "uuid": [
+        "00000000-0000-0000-0000-ffff00000130"
+      ]
+    }
+}"#;
+
+pub const JSON_SCHEMA_ATTR_OAUTH2_TOKEN_EXCHANGE_AUDIENCE: &str = r#"{
+    "attrs": {
+      "class": [
+        "object",
+        "system",
+        "attributetype"
+      ],
+      "description": [
+        "Resource servers that this resource server may exchange user access tokens for"
+      ],
+      "index": [
+        "EQUALITY"
+      ],
+      "unique": [
+        "false"
+      ],
+      "multivalue": [
+        "true"
+      ],
+      "attributename": [
+        "oauth2_token_exchange_audience"
+      ],
+      "syntax": [
+        "REFERENCE_UUID"
+      ],
+      "uuid": [
+        "00000000-0000-0000-0000-ffff00000131"
+      ]
+    }
+}"#;
+
 pub const JSON_SCHEMA_ATTR_OAUTH2_RS_SCOPE_MAP: &str = r#"{
     "attrs": {
       "class": [
@@ -1358,6 +1592,254 @@ pub const JSON_SCHEMA_ATTR_GRANT_UI_HINT: &str = r#"{
     }
 }"#;
 
+pub const JSON_SCHEMA_ATTR_REPL_PARTNER_ADDRESS: &str = r#"{
+    "attrs": {
+      "class": [
+        "object",
+        "system",
+        "attributetype"
+      ],
+      "description": [
+        "The address of a replication partner, in the form repl://host:port"
+      ],
+      "index": [
+        "EQUALITY"
+      ],
+      "unique": [
+        "false"
+      ],
+      "multivalue": [
+        "false"
+      ],
+      "attributename": [
+        "repl_partner_address"
+      ],
+      "syntax": [
+        "UTF8STRING_INSENSITIVE"
+      ],
+      "uuid": [
+        "00000000-0000-0000-0000-ffff00000138"
+      ]
+    }
+}"#;
+
+pub const JSON_SCHEMA_ATTR_REPL_PARTNER_CERT_SHA256: &str = r#"{
+    "attrs": {
+      "class": [
+        "object",
+        "system",
+        "attributetype"
+      ],
+      "description": [
+        "The hex encoded sha256 fingerprints of the certificates a replication partner may present"
+      ],
+      "index": [
+        "EQUALITY"
+      ],
+      "unique": [
+        "false"
+      ],
+      "multivalue": [
+        "true"
+      ],
+      "attributename": [
+        "repl_partner_cert_sha256"
+      ],
+      "syntax": [
+        "UTF8STRING_INSENSITIVE"
+      ],
+      "uuid": [
+        "00000000-0000-0000-0000-ffff00000139"
+      ]
+    }
+}"#;
+
+pub const JSON_SCHEMA_ATTR_DOMAIN_READ_ONLY_REPLICA: &str = r#"{
+    "attrs": {
+      "class": [
+        "object",
+        "system",
+        "attributetype"
+      ],
+      "description": [
+        "The server uuids of replicas that only accept changes from their replication suppliers"
+      ],
+      "index": [],
+      "unique": [
+        "false"
+      ],
+      "multivalue": [
+        "true"
+      ],
+      "attributename": [
+        "domain_read_only_replica"
+      ],
+      "syntax": [
+        "UUID"
+      ],
+      "uuid": [
+        "00000000-0000-0000-0000-ffff00000141"
+      ]
+    }
+}"#;
+
+pub const JSON_SCHEMA_ATTR_REPL_JOIN_TOKEN: &str = r#"{
+    "attrs": {
+      "class": [
+        "object",
+        "system",
+        "attributetype"
+      ],
+      "description": [
+        "The hex encoded sha256 of the one time secret a new server presents to join with a replication agreement"
+      ],
+      "index": [
+        "EQUALITY"
+      ],
+      "unique": [
+        "false"
+      ],
+      "multivalue": [
+        "false"
+      ],
+      "attributename": [
+        "repl_join_token"
+      ],
+      "syntax": [
+        "UTF8STRING_INSENSITIVE"
+      ],
+      "uuid": [
+        "00000000-0000-0000-0000-ffff00000142"
+      ]
+    }
+}"#;
+
+pub const JSON_SCHEMA_ATTR_HOST_JOIN_TOKEN: &str = r#"{
+    "attrs": {
+      "class": [
+        "object",
+        "system",
+        "attributetype"
+      ],
+      "description": [
+        "The hex encoded sha256 of the one time secret a host presents to enrol"
+      ],
+      "index": [
+        "EQUALITY"
+      ],
+      "unique": [
+        "false"
+      ],
+      "multivalue": [
+        "false"
+      ],
+      "attributename": [
+        "host_join_token"
+      ],
+      "syntax": [
+        "UTF8STRING_INSENSITIVE"
+      ],
+      "uuid": [
+        "00000000-0000-0000-0000-ffff00000143"
+      ]
+    }
+}"#;
+
+pub const JSON_SCHEMA_ATTR_PHONE_NUMBER: &str = r#"{
+    "attrs": {
+      "class": [
+        "object",
+        "system",
+        "attributetype"
+      ],
+      "description": [
+        "The phone numbers of this person, in E.164 form"
+      ],
+      "index": [
+        "EQUALITY"
+      ],
+      "unique": [
+        "false"
+      ],
+      "multivalue": [
+        "true"
+      ],
+      "sync_allowed": [
+        "true"
+      ],
+      "attributename": [
+        "phone_number"
+      ],
+      "syntax": [
+        "PHONE_NUMBER"
+      ],
+      "uuid": [
+        "00000000-0000-0000-0000-ffff00000145"
+      ]
+    }
+}"#;
+
+pub const JSON_SCHEMA_ATTR_POSTAL_ADDRESS: &str = r#"{
+    "attrs": {
+      "class": [
+        "object",
+        "system",
+        "attributetype"
+      ],
+      "description": [
+        "The postal addresses of this person, in the components of an OpenID Connect address claim"
+      ],
+      "index": [],
+      "unique": [
+        "false"
+      ],
+      "multivalue": [
+        "true"
+      ],
+      "sync_allowed": [
+        "true"
+      ],
+      "attributename": [
+        "postal_address"
+      ],
+      "syntax": [
+        "ADDRESS"
+      ],
+      "uuid": [
+        "00000000-0000-0000-0000-ffff00000146"
+      ]
+    }
+}"#;
+
+pub const JSON_SCHEMA_ATTR_IMAGE: &str = r#"{
+    "attrs": {
+      "class": [
+        "object",
+        "system",
+        "attributetype"
+      ],
+      "description": [
+        "A picture of a person, or the logo of an oauth2 resource server, as a png, jpeg or webp"
+      ],
+      "index": [],
+      "unique": [
+        "false"
+      ],
+      "multivalue": [
+        "false"
+      ],
+      "attributename": [
+        "image"
+      ],
+      "syntax": [
+        "IMAGE"
+      ],
+      "uuid": [
+        "00000000-0000-0000-0000-ffff00000148"
+      ]
+    }
+}"#;
+
 // === classes ===
 
 pub const JSON_SCHEMA_CLASS_PERSON: &str = r#"
@@ -1379,7 +1861,10 @@ pub const JSON_SCHEMA_CLASS_PERSON: &str = r#"
       ],
       "systemmay": [
         "mail",
-        "legalname"
+        "legalname",
+        "phone_number",
+        "postal_address",
+        "image"
       ],
       "systemmust": [
         "displayname",
@@ -1594,6 +2079,63 @@ pub const JSON_SCHEMA_CLASS_SYNC_ACCOUNT: &str = r#"
   }
 "#;
 
+pub const JSON_SCHEMA_CLASS_REPL_AGREEMENT: &str = r#"
+  {
+    "attrs": {
+      "class": [
+        "object",
+        "system",
+        "classtype"
+      ],
+      "description": [
+        "A replication agreement with a partner server"
+      ],
+      "classname": [
+        "repl_agreement"
+      ],
+      "systemmust": [
+        "name"
+      ],
+      "systemmay": [
+        "description",
+        "repl_partner_address",
+        "repl_partner_cert_sha256",
+        "repl_join_token"
+      ],
+      "uuid": [
+        "00000000-0000-0000-0000-ffff00000140"
+      ]
+    }
+  }
+"#;
+
+pub const JSON_SCHEMA_CLASS_HOST: &str = r#"
+  {
+    "attrs": {
+      "class": [
+        "object",
+        "system",
+        "classtype"
+      ],
+      "description": [
+        "A host that enrols to authenticate with its own credential"
+      ],
+      "classname": [
+        "host"
+      ],
+      "systemmay": [
+        "host_join_token"
+      ],
+      "systemsupplements": [
+        "service_account"
+      ],
+      "uuid": [
+        "00000000-0000-0000-0000-ffff00000144"
+      ]
+    }
+  }
+"#;
+
 // domain_info type
 //  domain_uuid
 //  domain_name <- should be the dns name?
@@ -1614,7 +2156,11 @@ pub const JSON_SCHEMA_CLASS_DOMAIN_INFO: &str = r#"
         "domain_info"
       ],
       "systemmay": [
-        "domain_ssid"
+        "domain_ssid",
+        "domain_ldap_basedn",
+        "domain_ldap_rdn_attr",
+        "domain_ldap_attr_map",
+        "domain_read_only_replica"
       ],
       "systemmust": [
         "name",
@@ -1743,7 +2289,13 @@ pub const JSON_SCHEMA_CLASS_OAUTH2_RS: &str = r#"
         "rs256_private_key_der",
         "oauth2_jwt_legacy_crypto_enable",
         "oauth2_prefer_short_username",
-        "oauth2_rs_origin_landing"
+        "oauth2_rs_origin_landing",
+        "oauth2_access_token_expiry",
+        "oauth2_refresh_token_expiry",
+        "oauth2_session_outlive_parent",
+        "oauth2_jwt_sign_alg",
+        "oauth2_token_exchange_audience",
+        "image"
       ],
       "systemmust": [
         "oauth2_rs_name",
@@ -1783,3 +2335,26 @@ pub const JSON_SCHEMA_CLASS_OAUTH2_RS_BASIC: &str = r#"
     }
   }
 "#;
+
+pub const JSON_SCHEMA_CLASS_OAUTH2_RS_PUBLIC: &str = r#"
+  {
+    "attrs": {
+      "class": [
+        "object",
+        "system",
+        "classtype"
+      ],
+      "description": [
+        "The class representing a configured public (native or browser) Oauth2 client without a client secret"
+      ],
+      "classname": [
+        "oauth2_resource_server_public"
+      ],
+      "systemmay": [],
+      "systemmust": [],
+      "uuid": [
+        "00000000-0000-0000-0000-ffff00000126"
+      ]
+    }
+  }
+"#;
@@ -219,6 +219,41 @@ pub const UUID_SCHEMA_ATTR_SYNC_PARENT_UUID: Uuid = uuid!("00000000-0000-0000-00
 pub const UUID_SCHEMA_CLASS_SYNC_OBJECT: Uuid = uuid!("00000000-0000-0000-0000-ffff00000123");
 pub const UUID_SCHEMA_ATTR_SYNC_CLASS: Uuid = uuid!("00000000-0000-0000-0000-ffff00000124");
 pub const UUID_SCHEMA_ATTR_SYNC_ALLOWED: Uuid = uuid!("00000000-0000-0000-0000-ffff00000125");
+pub const _UUID_SCHEMA_CLASS_OAUTH2_RS_PUBLIC: Uuid = uuid!("00000000-0000-0000-0000-ffff00000126");
+pub const _UUID_SCHEMA_ATTR_OAUTH2_ACCESS_TOKEN_EXPIRY: Uuid =
+    uuid!("00000000-0000-0000-0000-ffff00000127");
+pub const _UUID_SCHEMA_ATTR_OAUTH2_REFRESH_TOKEN_EXPIRY: Uuid =
+    uuid!("00000000-0000-0000-0000-ffff00000128");
+pub const _UUID_SCHEMA_ATTR_OAUTH2_SESSION_OUTLIVE_PARENT: Uuid =
+    uuid!("00000000-0000-0000-0000-ffff00000129");
+pub const _UUID_SCHEMA_ATTR_OAUTH2_JWT_SIGN_ALG: Uuid =
+    uuid!("00000000-0000-0000-0000-ffff00000130");
+pub const _UUID_SCHEMA_ATTR_OAUTH2_TOKEN_EXCHANGE_AUDIENCE: Uuid =
+    uuid!("00000000-0000-0000-0000-ffff00000131");
+pub const _UUID_SCHEMA_ATTR_DOMAIN_LDAP_BASEDN: Uuid =
+    uuid!("00000000-0000-0000-0000-ffff00000132");
+pub const _UUID_SCHEMA_ATTR_DOMAIN_LDAP_RDN_ATTR: Uuid =
+    uuid!("00000000-0000-0000-0000-ffff00000133");
+pub const _UUID_SCHEMA_ATTR_DOMAIN_LDAP_ATTR_MAP: Uuid =
+    uuid!("00000000-0000-0000-0000-ffff00000134");
+pub const UUID_SCHEMA_ATTR_UID: Uuid = uuid!("00000000-0000-0000-0000-ffff00000135");
+pub const UUID_SCHEMA_ATTR_GECOS: Uuid = uuid!("00000000-0000-0000-0000-ffff00000136");
+pub const UUID_SCHEMA_CLASS_CONFLICT: Uuid = uuid!("00000000-0000-0000-0000-ffff00000137");
+pub const _UUID_SCHEMA_ATTR_REPL_PARTNER_ADDRESS: Uuid =
+    uuid!("00000000-0000-0000-0000-ffff00000138");
+pub const _UUID_SCHEMA_ATTR_REPL_PARTNER_CERT_SHA256: Uuid =
+    uuid!("00000000-0000-0000-0000-ffff00000139");
+pub const _UUID_SCHEMA_CLASS_REPL_AGREEMENT: Uuid = uuid!("00000000-0000-0000-0000-ffff00000140");
+pub const _UUID_SCHEMA_ATTR_DOMAIN_READ_ONLY_REPLICA: Uuid =
+    uuid!("00000000-0000-0000-0000-ffff00000141");
+pub const _UUID_SCHEMA_ATTR_REPL_JOIN_TOKEN: Uuid = uuid!("00000000-0000-0000-0000-ffff00000142");
+pub const _UUID_SCHEMA_ATTR_HOST_JOIN_TOKEN: Uuid = uuid!("00000000-0000-0000-0000-ffff00000143");
+pub const _UUID_SCHEMA_CLASS_HOST: Uuid = uuid!("00000000-0000-0000-0000-ffff00000144");
+pub const _UUID_SCHEMA_ATTR_PHONE_NUMBER: Uuid = uuid!("00000000-0000-0000-0000-ffff00000145");
+pub const _UUID_SCHEMA_ATTR_POSTAL_ADDRESS: Uuid = uuid!("00000000-0000-0000-0000-ffff00000146");
+pub const UUID_SCHEMA_ATTR_MAX_SIZE: Uuid = uuid!("00000000-0000-0000-0000-ffff00000147");
+pub const _UUID_SCHEMA_ATTR_IMAGE: Uuid = uuid!("00000000-0000-0000-0000-ffff00000148");
+pub const UUID_SCHEMA_ATTR_ENCRYPTED: Uuid = uuid!("00000000-0000-0000-0000-ffff00000149");
 
 // System and domain infos
 // I'd like to strongly criticise william of the past for making poor choices about these allocations.
@@ -300,6 +335,8 @@ pub const _UUID_IDM_HP_ACP_SYNC_ACCOUNT_MANAGE_PRIV_V1: Uuid =
     uuid!("00000000-0000-0000-0000-ffffff000044");
 pub const UUID_IDM_ACP_ACCOUNT_MAIL_READ_PRIV_V1: Uuid =
     uuid!("00000000-0000-0000-0000-ffffff000045");
+pub const _UUID_IDM_ACP_REPL_AGREEMENT_MANAGE_PRIV_V1: Uuid =
+    uuid!("00000000-0000-0000-0000-ffffff000046");
 
 // End of system ranges
 pub const UUID_DOES_NOT_EXIST: Uuid = uuid!("00000000-0000-0000-0000-fffffffffffe");
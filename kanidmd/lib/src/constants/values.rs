@@ -16,6 +16,7 @@ lazy_static! {
     pub static ref PVCLASS_ACP: PartialValue = PartialValue::new_class("access_control_profile");
     pub static ref PVCLASS_ATTRIBUTETYPE: PartialValue = PartialValue::new_class("attributetype");
     pub static ref PVCLASS_CLASSTYPE: PartialValue = PartialValue::new_class("classtype");
+    pub static ref PVCLASS_CONFLICT: PartialValue = PartialValue::new_class("conflict");
     pub static ref PVCLASS_DOMAIN_INFO: PartialValue = PartialValue::new_class("domain_info");
     pub static ref PVCLASS_DYNGROUP: PartialValue = PartialValue::new_class("dyngroup");
     pub static ref PVCLASS_EXTENSIBLE: PartialValue = PartialValue::new_class("extensibleobject");
@@ -24,6 +25,8 @@ lazy_static! {
         PartialValue::new_class("oauth2_resource_server");
     pub static ref PVCLASS_OAUTH2_BASIC: PartialValue =
         PartialValue::new_class("oauth2_resource_server_basic");
+    pub static ref PVCLASS_OAUTH2_PUBLIC: PartialValue =
+        PartialValue::new_class("oauth2_resource_server_public");
     pub static ref PVCLASS_PERSON: PartialValue = PartialValue::new_class("person");
     pub static ref PVCLASS_POSIXACCOUNT: PartialValue = PartialValue::new_class("posixaccount");
     pub static ref PVCLASS_POSIXGROUP: PartialValue = PartialValue::new_class("posixgroup");
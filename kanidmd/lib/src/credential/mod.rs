@@ -13,6 +13,7 @@ use webauthn_rs::prelude::{AuthenticationResult, Passkey, SecurityKey};
 use webauthn_rs_core::proto::{Credential as WebauthnCredential, CredentialV3};
 
 use crate::be::dbvalue::{DbBackupCodeV1, DbCred, DbPasswordV1};
+use crate::redact::{redact_all, Redacted};
 
 pub mod policy;
 pub mod softlock;
@@ -70,11 +71,19 @@ enum Kdf {
     NT_MD4(Vec<u8>),
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, PartialEq)]
 pub struct Password {
     material: Kdf,
 }
 
+impl std::fmt::Debug for Password {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Password")
+            .field("material", &Redacted::new(&self.material))
+            .finish()
+    }
+}
+
 impl TryFrom<DbPasswordV1> for Password {
     type Error = ();
 
@@ -432,11 +441,19 @@ impl Password {
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, PartialEq, Eq)]
 pub struct BackupCodes {
     code_set: HashSet<String>,
 }
 
+impl std::fmt::Debug for BackupCodes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BackupCodes")
+            .field("code_set", &redact_all(self.code_set.iter()))
+            .finish()
+    }
+}
+
 impl TryFrom<DbBackupCodeV1> for BackupCodes {
     type Error = ();
 
@@ -8,6 +8,7 @@ use openssl::sign::Signer;
 use rand::prelude::*;
 
 use crate::be::dbvalue::{DbTotpAlgoV1, DbTotpV1};
+use crate::redact::Redacted;
 
 // This is 64 bits of entropy, as the examples in https://tools.ietf.org/html/rfc6238 show.
 const SECRET_SIZE_BYTES: usize = 8;
@@ -57,13 +58,23 @@ impl TotpAlgo {
 }
 
 /// <https://tools.ietf.org/html/rfc6238> which relies on <https://tools.ietf.org/html/rfc4226>
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Clone, PartialEq, Eq)]
 pub struct Totp {
     secret: Vec<u8>,
     pub(crate) step: u64,
     algo: TotpAlgo,
 }
 
+impl std::fmt::Debug for Totp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Totp")
+            .field("secret", &Redacted::new(&self.secret))
+            .field("step", &self.step)
+            .field("algo", &self.algo)
+            .finish()
+    }
+}
+
 impl TryFrom<DbTotpV1> for Totp {
     type Error = ();
 
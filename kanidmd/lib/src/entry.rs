@@ -32,8 +32,8 @@ use std::sync::Arc;
 use compact_jwt::JwsSigner;
 use hashbrown::HashMap;
 use kanidm_proto::v1::{
-    ConsistencyError, Entry as ProtoEntry, Filter as ProtoFilter, OperationError, SchemaError,
-    UiHint,
+    AttributeChange, ConsistencyError, Entry as ProtoEntry, Filter as ProtoFilter, OperationError,
+    SchemaError, UiHint,
 };
 use ldap3_proto::simple::{LdapPartialAttribute, LdapSearchResultEntry};
 use smartstring::alias::String as AttrString;
@@ -43,18 +43,20 @@ use uuid::Uuid;
 use webauthn_rs::prelude::{DeviceKey as DeviceKeyV4, Passkey as PasskeyV4};
 
 use crate::be::dbentry::{DbEntry, DbEntryV2, DbEntryVers};
-use crate::be::dbvalue::DbValueSetV2;
+use crate::be::dbvalue::{DbCidV1, DbValueSetV2};
 use crate::be::{IdxKey, IdxSlope};
 use crate::credential::Credential;
 use crate::filter::{Filter, FilterInvalid, FilterResolved, FilterValidResolved};
-use crate::ldap::ldap_vattr_map;
+use crate::ldap::{LdapAttrMap, LdapDnFormat};
 use crate::modify::{Modify, ModifyInvalid, ModifyList, ModifyValid};
 use crate::prelude::*;
 use crate::repl::cid::Cid;
 use crate::repl::entry::EntryChangelog;
+use crate::repl::proto::ReplEntryV1;
 use crate::schema::{SchemaAttribute, SchemaClass, SchemaTransaction};
 use crate::value::{
-    IndexType, IntentTokenState, Oauth2Session, PartialValue, Session, SyntaxType, Value,
+    ImageValue, IndexType, IntentTokenState, Oauth2Session, PartialValue, Session, SyntaxType,
+    Value,
 };
 use crate::valueset::{self, ValueSet};
 
@@ -165,6 +167,16 @@ pub struct EntrySealed {
 #[derive(Clone, Debug)]
 pub struct EntryReduced {
     uuid: Uuid,
+    // The change state isn't an attribute the client may be allowed to read, but its etag is
+    // always given so that clients can detect concurrent changes.
+    last_changed: Option<Cid>,
+    // The change that last modified each attribute the client may read.
+    attr_cids: BTreeMap<AttrString, Cid>,
+}
+
+/// The etag of an entry is a strong http entity tag of the change that last modified it.
+fn cid_etag(cid: &Cid) -> String {
+    format!("\"{}\"", cid)
 }
 
 pub type Eattrs = Map<AttrString, ValueSet>;
@@ -1042,6 +1054,20 @@ impl Entry<EntrySealed, EntryNew> {
     pub fn compare(&self, rhs: &Entry<EntrySealed, EntryNew>) -> bool {
         compare_attrs(&self.attrs, &rhs.attrs)
     }
+
+    /// Mark this entry from a replication supplier as conflicting with an entry we already
+    /// hold. It is placed in the recycle bin so that it no longer asserts its unique values.
+    pub(crate) fn into_conflict(mut self, cid: &Cid) -> Self {
+        let classes = [Value::new_class("conflict"), Value::new_class("recycled")];
+        self.valid
+            .eclog
+            .add_ava_iter(cid, "class", classes.iter().cloned());
+        self.valid.eclog.recycled(cid);
+        classes
+            .into_iter()
+            .for_each(|v| self.add_ava_int("class", v));
+        self
+    }
 }
 
 type IdxDiff<'a> =
@@ -1090,6 +1116,10 @@ impl Entry<EntrySealed, EntryCommitted> {
         // In the future this will do extra work to process uuid
         // into "attributes" suitable for dbentry storage.
 
+        // Most attributes were changed by the change that last modified the entry, and these
+        // are restored from it as the entry is read, so only the others are stored.
+        let last_changed = self.get_last_changed();
+
         // How will this work with replication?
         //
         // Alternately, we may have higher-level types that translate entry
@@ -1105,6 +1135,25 @@ impl Entry<EntrySealed, EntryCommitted> {
                         (k.clone(), dbvs)
                     })
                     .collect(),
+                attr_cids: self
+                    .valid
+                    .eclog
+                    .attr_cids()
+                    .iter()
+                    .filter(|(k, cid)| {
+                        !(self.attrs.contains_key(*k) && Some(*cid) == last_changed.as_ref())
+                    })
+                    .map(|(k, cid)| {
+                        (
+                            k.clone(),
+                            DbCidV1 {
+                                domain_id: cid.d_uuid,
+                                server_id: cid.s_uuid,
+                                timestamp: cid.ts,
+                            },
+                        )
+                    })
+                    .collect(),
             }),
         }
     }
@@ -1506,29 +1555,48 @@ impl Entry<EntrySealed, EntryCommitted> {
         }
     }
 
-    pub fn from_dbentry(db_e: DbEntry, id: u64) -> Option<Self> {
+    fn eattrs_from_dbentry(db_e: DbEntry) -> Option<(Eattrs, BTreeMap<AttrString, Cid>)> {
         // Convert attrs from db format to value
-        let r_attrs: Result<Eattrs, ()> = match db_e.ent {
+        let (attrs, attr_cids) = match db_e.ent {
             DbEntryVers::V1(_) => {
                 admin_error!("Db V1 entry should have been migrated!");
-                Err(())
+                return None;
             }
-            DbEntryVers::V2(v2) => v2
-                .attrs
-                .into_iter()
-                // Skip anything empty as new VS can't deal with it.
-                .filter(|(_k, vs)| !vs.is_empty())
-                .map(|(k, dbvs)| {
-                    valueset::from_db_valueset_v2(dbvs)
-                        .map(|vs: ValueSet| (k, vs))
-                        .map_err(|e| {
-                            admin_error!(?e, "from_dbentry failed");
-                        })
-                })
-                .collect(),
+            DbEntryVers::V2(v2) => (v2.attrs, v2.attr_cids),
         };
 
-        let attrs = r_attrs.ok()?;
+        let r_attrs: Result<Eattrs, ()> = attrs
+            .into_iter()
+            // Skip anything empty as new VS can't deal with it.
+            .filter(|(_k, vs)| !vs.is_empty())
+            .map(|(k, dbvs)| {
+                valueset::from_db_valueset_v2(dbvs)
+                    .map(|vs: ValueSet| (k, vs))
+                    .map_err(|e| {
+                        admin_error!(?e, "from_dbentry failed");
+                    })
+            })
+            .collect();
+
+        let attr_cids = attr_cids
+            .into_iter()
+            .map(|(k, dc)| {
+                (
+                    k,
+                    Cid {
+                        d_uuid: dc.domain_id,
+                        s_uuid: dc.server_id,
+                        ts: dc.timestamp,
+                    },
+                )
+            })
+            .collect();
+
+        r_attrs.ok().map(|attrs| (attrs, attr_cids))
+    }
+
+    pub fn from_dbentry(db_e: DbEntry, id: u64) -> Option<Self> {
+        let (attrs, attr_cids) = Self::eattrs_from_dbentry(db_e)?;
 
         let uuid = attrs.get("uuid").and_then(|vs| vs.to_uuid_single())?;
 
@@ -1547,7 +1615,8 @@ impl Entry<EntrySealed, EntryCommitted> {
             .and_then(|vs| vs.as_cid_set())
             .and_then(|set| set.iter().next().cloned())?;
 
-        let eclog = EntryChangelog::new_without_schema(cid, attrs.clone());
+        let mut eclog = EntryChangelog::new_without_schema(cid, attrs.clone());
+        eclog.restore_attr_cids(attr_cids);
 
         Some(Entry {
             valid: EntrySealed { uuid, eclog },
@@ -1556,6 +1625,112 @@ impl Entry<EntrySealed, EntryCommitted> {
         })
     }
 
+    /// Prepare this entry to be sent to a replication consumer.
+    pub(crate) fn to_repl_entry_v1(&self) -> Option<ReplEntryV1> {
+        let cid = self.get_last_changed()?;
+
+        Some(ReplEntryV1 {
+            uuid: self.valid.uuid,
+            cid,
+            entry: self.to_dbentry(),
+        })
+    }
+
+    /// Merge the content sent by a replication supplier into this entry. Each attribute is
+    /// taken from whichever side changed it most recently, so that replicas that apply the
+    /// same changes converge regardless of the order they receive them in. The merge is
+    /// recorded in the entry changelog as a change at the supplier's cid. Returns `None` if
+    /// the supplier has nothing newer than we hold.
+    pub(crate) fn merge_replicated(
+        &self,
+        cid: &Cid,
+        entry: &Entry<EntrySealed, EntryNew>,
+    ) -> Option<Self> {
+        let l_cids = self.valid.eclog.attr_cids();
+        let r_cids = entry.valid.eclog.attr_cids();
+
+        let allkeys: BTreeSet<&AttrString> = l_cids
+            .keys()
+            .chain(r_cids.keys())
+            .chain(self.attrs.keys())
+            .chain(entry.attrs.keys())
+            .filter(|k| k.as_str() != "last_modified_cid")
+            .collect();
+
+        let mut changed = false;
+        let mut attrs: Eattrs = Map::new();
+        let mut attr_cids: BTreeMap<AttrString, Cid> = BTreeMap::new();
+
+        for k in allkeys {
+            let (l_cid, r_cid) = (l_cids.get(k), r_cids.get(k));
+            // Ties are the same change, and cid ordering is total, so every replica picks
+            // the same side.
+            let (vs, a_cid) = if r_cid > l_cid {
+                changed = true;
+                (entry.attrs.get(k), r_cid)
+            } else {
+                (self.attrs.get(k), l_cid)
+            };
+            if let Some(vs) = vs {
+                attrs.insert(k.clone(), vs.clone());
+            }
+            if let Some(a_cid) = a_cid {
+                attr_cids.insert(k.clone(), a_cid.clone());
+            }
+        }
+
+        if !changed {
+            return None;
+        }
+
+        // The entry was last modified by the most recent change from either side.
+        let last_changed = std::cmp::max(self.get_last_changed(), entry.get_last_changed())
+            .unwrap_or_else(|| cid.clone());
+        attr_cids.insert(AttrString::from("last_modified_cid"), last_changed.clone());
+        attrs.insert(AttrString::from("last_modified_cid"), vs_cid![last_changed]);
+
+        let mut eclog = self.valid.eclog.clone();
+        eclog.replicated(cid, attrs.clone(), attr_cids);
+
+        Some(Entry {
+            valid: EntrySealed {
+                uuid: self.valid.uuid,
+                eclog,
+            },
+            state: self.state.clone(),
+            attrs,
+        })
+    }
+
+    /// Rebuild an entry that was sent by a replication supplier, retaining the
+    /// supplier's uuid and cid.
+    pub(crate) fn from_repl_entry_v1(
+        re: ReplEntryV1,
+    ) -> Result<(Cid, Entry<EntrySealed, EntryNew>), OperationError> {
+        let ReplEntryV1 { uuid, cid, entry } = re;
+
+        let (attrs, attr_cids) = Self::eattrs_from_dbentry(entry).ok_or_else(|| {
+            admin_error!(?uuid, "Unable to deserialise replicated entry");
+            OperationError::InvalidEntryState
+        })?;
+
+        if attrs.get("uuid").and_then(|vs| vs.to_uuid_single()) != Some(uuid) {
+            admin_error!(?uuid, "Replicated entry uuid does not match its content");
+            return Err(OperationError::InvalidEntryState);
+        }
+
+        let eclog = EntryChangelog::new_replicated(cid.clone(), attrs.clone(), attr_cids);
+
+        Ok((
+            cid,
+            Entry {
+                valid: EntrySealed { uuid, eclog },
+                state: EntryNew,
+                attrs,
+            },
+        ))
+    }
+
     /// # Safety
     /// This function bypasses the access control validation logic and should NOT
     /// be used without special care and attention to ensure that no private data
@@ -1566,6 +1741,8 @@ impl Entry<EntrySealed, EntryCommitted> {
         Entry {
             valid: EntryReduced {
                 uuid: self.valid.uuid,
+                last_changed: self.get_last_changed(),
+                attr_cids: self.valid.eclog.attr_cids().clone(),
             },
             state: self.state,
             attrs: self.attrs,
@@ -1591,8 +1768,20 @@ impl Entry<EntrySealed, EntryCommitted> {
             })
             .collect();
 
+        // Attributes that were removed still have the change that removed them.
+        let attr_cids = self
+            .valid
+            .eclog
+            .attr_cids()
+            .iter()
+            .filter(|(k, _)| allowed_attrs.contains(k.as_str()))
+            .map(|(k, cid)| (k.clone(), cid.clone()))
+            .collect();
+
         let valid = EntryReduced {
             uuid: self.valid.uuid,
+            last_changed: self.get_last_changed(),
+            attr_cids,
         };
         let state = self.state.clone();
 
@@ -1724,6 +1913,26 @@ impl Entry<EntryReduced, EntryCommitted> {
         self.valid.uuid
     }
 
+    /// The etag of this entry, which changes whenever the entry is modified.
+    pub fn get_etag(&self) -> Option<String> {
+        self.valid.last_changed.as_ref().map(cid_etag)
+    }
+
+    /// The change that last modified each attribute of this entry that the client may read,
+    /// including attributes that were removed.
+    pub fn get_attr_changes(&self) -> Vec<AttributeChange> {
+        self.valid
+            .attr_cids
+            .iter()
+            .map(|(k, cid)| AttributeChange {
+                attr: k.to_string(),
+                cid: cid.to_string(),
+                server_uuid: cid.s_uuid,
+                changed: OffsetDateTime::unix_epoch() + cid.ts,
+            })
+            .collect()
+    }
+
     /// Transform this reduced entry into a JSON protocol form that can be sent to clients.
     pub fn to_pe(&self, qs: &QueryServerReadTransaction) -> Result<ProtoEntry, OperationError> {
         // Turn values -> Strings.
@@ -1740,13 +1949,15 @@ impl Entry<EntryReduced, EntryCommitted> {
         &self,
         qs: &QueryServerReadTransaction,
         basedn: &str,
+        dn_format: &LdapDnFormat,
+        attr_map: &LdapAttrMap,
         // Did the client request all attributes?
         all_attrs: bool,
         // Did the ldap client request any sperific attribute names? If so,
         // we need to remap everything to match.
         l_attrs: &[String],
     ) -> Result<LdapSearchResultEntry, OperationError> {
-        let rdn = qs.uuid_to_rdn(self.get_uuid())?;
+        let rdn = dn_format.rdn(qs, self.get_uuid())?;
 
         let dn = format!("{},{}", rdn, basedn);
 
@@ -1758,7 +1969,7 @@ impl Entry<EntryReduced, EntryCommitted> {
             .attrs
             .iter()
             .map(|(k, vs)| {
-                qs.resolve_valueset_ldap(vs, basedn)
+                qs.resolve_valueset_ldap(vs, basedn, dn_format)
                     .map(|pvs| (k.as_str(), pvs))
             })
             .collect();
@@ -1774,14 +1985,14 @@ impl Entry<EntryReduced, EntryCommitted> {
                 .chain(
                     l_attrs
                         .iter()
-                        .map(|k| (k.as_str(), ldap_vattr_map(k.as_str()))),
+                        .map(|k| (k.as_str(), attr_map.map(k.as_str()))),
                 )
                 .collect()
         } else {
             // Just get the requested ones.
             l_attrs
                 .iter()
-                .map(|k| (k.as_str(), ldap_vattr_map(k.as_str())))
+                .map(|k| (k.as_str(), attr_map.map(k.as_str())))
                 .collect()
         };
 
@@ -1853,6 +2064,21 @@ impl<VALID, STATE> Entry<VALID, STATE> {
         let _ = self.attrs.insert(AttrString::from("last_modified_cid"), cv);
     }
 
+    /// The change identifier that last modified this entry.
+    pub(crate) fn get_last_changed(&self) -> Option<Cid> {
+        self.attrs
+            .get("last_modified_cid")
+            .and_then(|vs| vs.as_cid_set())
+            .and_then(|set| set.iter().next().cloned())
+    }
+
+    /// If this entry is unchanged since it was read with one of these etags. `*` matches any
+    /// entry, as for an http If-Match header.
+    pub fn etag_matches<'b>(&self, mut etags: impl Iterator<Item = &'b str>) -> bool {
+        let current = self.get_last_changed().as_ref().map(cid_etag);
+        etags.any(|etag| etag == "*" || Some(etag) == current.as_deref())
+    }
+
     #[inline(always)]
     /// Get an iterator over the current set of attribute names that this entry contains.
     pub fn get_ava_names(&self) -> impl Iterator<Item = &str> {
@@ -2062,6 +2288,10 @@ impl<VALID, STATE> Entry<VALID, STATE> {
         self.attrs.get(attr).and_then(|vs| vs.to_url_single())
     }
 
+    pub fn get_ava_single_image(&self, attr: &str) -> Option<&ImageValue> {
+        self.attrs.get(attr).and_then(|vs| vs.to_image_single())
+    }
+
     pub fn get_ava_single_uuid(&self, attr: &str) -> Option<Uuid> {
         self.attrs.get(attr).and_then(|vs| vs.to_uuid_single())
     }
@@ -2137,6 +2367,26 @@ impl<VALID, STATE> Entry<VALID, STATE> {
             .unwrap_or(false)
     }
 
+    #[inline(always)]
+    /// Assert if an attribute of this name is present, and one of it's values starts with
+    /// the following string, if possible to perform the comparison.
+    pub fn attribute_startswith(&self, attr: &str, subvalue: &PartialValue) -> bool {
+        self.attrs
+            .get(attr)
+            .map(|vset| vset.startswith(subvalue))
+            .unwrap_or(false)
+    }
+
+    #[inline(always)]
+    /// Assert if an attribute of this name is present, and one of it's values ends with
+    /// the following string, if possible to perform the comparison.
+    pub fn attribute_endswith(&self, attr: &str, subvalue: &PartialValue) -> bool {
+        self.attrs
+            .get(attr)
+            .map(|vset| vset.endswith(subvalue))
+            .unwrap_or(false)
+    }
+
     #[inline(always)]
     /// Assert if an attribute of this name is present, and one of it's values is less than
     /// the following partial value
@@ -2147,6 +2397,16 @@ impl<VALID, STATE> Entry<VALID, STATE> {
             .unwrap_or(false)
     }
 
+    #[inline(always)]
+    /// Assert if an attribute of this name is present, and one of it's values is greater than
+    /// the following partial value
+    pub fn attribute_greaterthan(&self, attr: &str, subvalue: &PartialValue) -> bool {
+        self.attrs
+            .get(attr)
+            .map(|vset| vset.greaterthan(subvalue))
+            .unwrap_or(false)
+    }
+
     // Since EntryValid/Invalid is just about class adherenece, not Value correctness, we
     // can now apply filters to invalid entries - why? Because even if they aren't class
     // valid, we still have strict typing checks between the filter -> entry to guarantee
@@ -2169,6 +2429,12 @@ impl<VALID, STATE> Entry<VALID, STATE> {
             FilterResolved::Sub(attr, subvalue, _) => {
                 self.attribute_substring(attr.as_str(), subvalue)
             }
+            FilterResolved::Stw(attr, subvalue, _) => {
+                self.attribute_startswith(attr.as_str(), subvalue)
+            }
+            FilterResolved::Enw(attr, subvalue, _) => {
+                self.attribute_endswith(attr.as_str(), subvalue)
+            }
             FilterResolved::Pres(attr, _) => {
                 // Given attr, is is present in the entry?
                 self.attribute_pres(attr.as_str())
@@ -2176,6 +2442,9 @@ impl<VALID, STATE> Entry<VALID, STATE> {
             FilterResolved::LessThan(attr, subvalue, _) => {
                 self.attribute_lessthan(attr.as_str(), subvalue)
             }
+            FilterResolved::GreaterThan(attr, subvalue, _) => {
+                self.attribute_greaterthan(attr.as_str(), subvalue)
+            }
             // Check with ftweedal about or filter zero len correctness.
             FilterResolved::Or(l, _) => l.iter().any(|f| self.entry_match_no_index_inner(f)),
             // Check with ftweedal about and filter zero len correctness.
@@ -2226,56 +2495,51 @@ impl<VALID, STATE> Entry<VALID, STATE> {
         )))
     }
 
-    /// Given this entry, generate a modification list that would "assert"
-    /// another entry is in the same/identical attribute state.
-    pub fn gen_modlist_assert(
-        &self,
-        schema: &dyn SchemaTransaction,
-    ) -> Result<ModifyList<ModifyInvalid>, SchemaError> {
-        // Create a modlist from this entry. We make this assuming we want the entry
-        // to have this one as a subset of values. This means if we have single
-        // values, we'll replace, if they are multivalue, we present them.
+    /// Generate the modification list that transforms this entry into the other, so that it
+    /// has the same attributes and values. Attributes the other does not have are purged, and
+    /// otherwise only the values that differ are removed or made present, so entries that are
+    /// already the same give an empty list.
+    ///
+    /// The uuid is never part of the list, as it is invalid to modify and the list is applied
+    /// to the entry with that uuid. The last modified cid is maintained by the server and is
+    /// ignored, as it is when entries are compared.
+    pub fn diff<V, S>(&self, other: &Entry<V, S>) -> ModifyList<ModifyInvalid> {
         let mut mods = ModifyList::new();
 
-        for (k, vs) in self.attrs.iter() {
-            // WHY?! We skip uuid here because it is INVALID for a UUID
-            // to be in a modlist, and the base.rs plugin will fail if it
-            // is there. This actually doesn't matter, because to apply the
-            // modlist in these situations we already know the entry MUST
-            // exist with that UUID, we only need to conform it's other
-            // attributes into the same state.
-            //
-            // In the future, if we make uuid a real entry type, then this
-            // check can "go away" because uuid will never exist as an ava.
-            //
-            // NOTE: Remove this check when uuid becomes a real attribute.
-            // UUID is now a real attribute, but it also has an ava for db_entry
-            // conversion - so what do? If we remove it here, we could have CSN issue with
-            // repl on uuid conflict, but it probably shouldn't be an ava either ...
-            // as a result, I think we need to keep this continue line to not cause issues.
-            if k == "uuid" {
-                continue;
-            }
-            // Get the schema attribute type out.
-            match schema.is_multivalue(k) {
-                Ok(r) => {
-                    if !r || k == "systemmust" || k == "systemmay" {
-                        // As this is single value, purge then present to maintain this
-                        // invariant. The other situation we purge is within schema with
-                        // the system types where we need to be able to express REMOVAL
-                        // of attributes, thus we need the purge.
-                        mods.push_mod(Modify::Purged(k.clone()));
-                    }
+        let allkeys: Set<&AttrString> = self
+            .attrs
+            .keys()
+            .chain(other.attrs.keys())
+            .filter(|k| k.as_str() != "uuid" && k.as_str() != "last_modified_cid")
+            .collect();
+
+        for k in allkeys {
+            match (self.attrs.get(k), other.attrs.get(k)) {
+                (Some(_), None) => mods.push_mod(Modify::Purged(k.clone())),
+                (None, Some(r)) => {
+                    r.to_value_iter()
+                        .for_each(|v| mods.push_mod(Modify::Present(k.clone(), v)));
                 }
-                // A schema error happened, fail the whole operation.
-                Err(e) => return Err(e),
-            }
-            for v in vs.to_value_iter() {
-                mods.push_mod(Modify::Present(k.clone(), v.clone()));
+                (Some(l), Some(r)) if l != r => {
+                    let l_values: Vec<_> = l.to_value_iter().collect();
+                    let r_values: Vec<_> = r.to_value_iter().collect();
+                    // Both iterators walk the same set, so each value is paired with its
+                    // own partial value.
+                    l_values
+                        .iter()
+                        .zip(l.to_partialvalue_iter())
+                        .filter(|(v, _)| !r_values.contains(v))
+                        .for_each(|(_, pv)| mods.push_mod(Modify::Removed(k.clone(), pv)));
+                    r_values
+                        .into_iter()
+                        .filter(|v| !l_values.contains(v))
+                        .for_each(|v| mods.push_mod(Modify::Present(k.clone(), v)));
+                }
+                _ => {}
             }
         }
 
-        Ok(mods)
+        mods
     }
 
     /// Determine if this entry is recycled or a tombstone, and map that to "None". This allows
@@ -2498,6 +2762,12 @@ impl From<&SchemaAttribute> for Entry<EntryInit, EntryNew> {
         if let Some(vs) = index_v {
             attrs.insert(AttrString::from("index"), vs);
         }
+        if s.encrypted {
+            attrs.insert(AttrString::from("encrypted"), ValueSetBool::new(true));
+        }
+        if let Some(max_size) = s.max_size {
+            attrs.insert(AttrString::from("max_size"), ValueSetUint32::new(max_size));
+        }
         attrs.insert(AttrString::from("syntax"), syntax_v);
         attrs.insert(
             AttrString::from("class"),
@@ -2559,7 +2829,7 @@ mod tests {
     use smartstring::alias::String as AttrString;
 
     use crate::be::{IdxKey, IdxSlope};
-    use crate::entry::{Entry, EntryInit, EntryInvalid, EntryNew};
+    use crate::entry::{compare_attrs, Entry, EntryInit, EntryInvalid, EntryNew};
     use crate::modify::{Modify, ModifyList};
     use crate::value::{IndexType, PartialValue, Value};
 
@@ -2720,6 +2990,31 @@ mod tests {
         assert!(e.attrs.get("attr").is_none());
     }
 
+    #[test]
+    fn test_entry_diff() {
+        let mut e1: Entry<EntryInvalid, EntryNew> = unsafe { Entry::new().into_invalid_new() };
+        e1.add_ava("userid", Value::from("william"));
+        e1.add_ava("class", Value::new_iutf8("test"));
+        e1.add_ava("class", Value::new_iutf8("removed"));
+        e1.add_ava("purged", Value::new_iutf8("value"));
+
+        let mut e2: Entry<EntryInit, EntryNew> = Entry::new();
+        e2.add_ava("userid", Value::from("claire"));
+        e2.add_ava("class", Value::new_iutf8("test"));
+        e2.add_ava("class", Value::new_iutf8("added"));
+        e2.add_ava("present", Value::new_iutf8("value"));
+
+        let modlist = e1.diff(&e2);
+        // The class that is in both is not touched.
+        assert!(modlist.len() == 6);
+
+        assert!(e1.apply_modlist(&unsafe { modlist.into_valid() }).is_ok());
+        assert!(compare_attrs(&e1.attrs, &e2.attrs));
+
+        // Entries that are the same have no differences.
+        assert!(e1.diff(&e2).is_empty());
+    }
+
     #[test]
     fn test_entry_idx_diff() {
         let mut e1: Entry<EntryInit, EntryNew> = Entry::new();
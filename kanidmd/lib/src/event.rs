@@ -58,8 +58,14 @@ impl SearchResult {
 
     // Consume self into a search response
     pub fn response(self) -> SearchResponse {
+        self.paged_response(None)
+    }
+
+    // Consume self into a page of a search response
+    pub fn paged_response(self, next_page_token: Option<String>) -> SearchResponse {
         SearchResponse {
             entries: self.entries,
+            next_page_token,
         }
     }
 
@@ -69,6 +75,65 @@ impl SearchResult {
     }
 }
 
+/// A page of search results. Pages are taken in uuid order, and the continuation token is the
+/// uuid of the last entry returned, so that the next page resumes after it. No state is held
+/// between pages, and entries that are added or removed between pages do not cause others to
+/// be skipped or repeated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchPage {
+    pub size: usize,
+    pub after: Option<Uuid>,
+}
+
+impl SearchPage {
+    /// Determine the page of a search requested by `ident`. Sessions that are not privileged,
+    /// including anonymous, may request pages no larger than [SEARCH_PAGE_SIZE_MAX]. If they
+    /// do not page the search, it is limited to [SEARCH_PAGE_SIZE_DEFAULT] results, so that a
+    /// larger result is refused rather than silently cut short.
+    pub fn from_request(
+        ident: &mut Identity,
+        page_size: Option<usize>,
+        page_token: Option<&str>,
+    ) -> Result<Option<Self>, OperationError> {
+        let privileged = ident.access_scope() == AccessScope::ReadWrite;
+
+        let after = page_token
+            .map(|token| {
+                Uuid::parse_str(token).map_err(|_| {
+                    request_error!("Search failure - invalid page token");
+                    OperationError::InvalidRequestState
+                })
+            })
+            .transpose()?;
+
+        match (page_size, after) {
+            (None, None) => {
+                if !privileged {
+                    ident.limits.search_max_results = ident
+                        .limits
+                        .search_max_results
+                        .min(SEARCH_PAGE_SIZE_DEFAULT);
+                }
+                Ok(None)
+            }
+            (size, after) => {
+                let size = size.unwrap_or(SEARCH_PAGE_SIZE_DEFAULT);
+                let size = if privileged {
+                    size
+                } else {
+                    size.min(SEARCH_PAGE_SIZE_MAX)
+                };
+                Ok(Some(SearchPage { size, after }))
+            }
+        }
+    }
+
+    /// The continuation token of the page that follows the entry `last`.
+    pub fn token(last: Uuid) -> String {
+        last.to_string()
+    }
+}
+
 #[derive(Debug)]
 pub struct SearchEvent {
     pub ident: Identity,
@@ -726,6 +791,8 @@ impl WhoamiResult {
 pub struct PurgeTombstoneEvent {
     pub ident: Identity,
     pub eventid: Uuid,
+    /// The minimum number of seconds of changes to retain in the changelog.
+    pub changelog_retention: u64,
 }
 
 impl Default for PurgeTombstoneEvent {
@@ -739,6 +806,14 @@ impl PurgeTombstoneEvent {
         PurgeTombstoneEvent {
             ident: Identity::from_internal(),
             eventid: Uuid::new_v4(),
+            changelog_retention: CHANGELOG_MAX_AGE,
+        }
+    }
+
+    pub fn new_with_retention(changelog_retention: u64) -> Self {
+        PurgeTombstoneEvent {
+            changelog_retention,
+            ..Self::new()
         }
     }
 }
@@ -764,6 +839,24 @@ impl PurgeRecycledEvent {
     }
 }
 
+#[derive(Debug)]
+pub struct PurgeAuditLogEvent {
+    pub ident: Identity,
+    pub eventid: Uuid,
+    /// The number of seconds that records are kept in the audit log.
+    pub retention: u64,
+}
+
+impl PurgeAuditLogEvent {
+    pub fn new(retention: u64) -> Self {
+        PurgeAuditLogEvent {
+            ident: Identity::from_internal(),
+            eventid: Uuid::new_v4(),
+            retention,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct OnlineBackupEvent {
     pub ident: Identity,
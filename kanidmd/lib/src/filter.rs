@@ -19,7 +19,7 @@ use hashbrown::HashMap;
 #[cfg(test)]
 use hashbrown::HashSet;
 use kanidm_proto::v1::{Filter as ProtoFilter, OperationError, SchemaError};
-use ldap3_proto::proto::{LdapFilter, LdapSubstringFilter};
+use ldap3_proto::proto::{LdapFilter, LdapMatchingRuleAssertion, LdapSubstringFilter};
 // use smartstring::alias::String as AttrString;
 use serde::Deserialize;
 use uuid::Uuid;
@@ -33,6 +33,11 @@ use crate::value::{IndexType, PartialValue};
 
 const FILTER_DEPTH_MAX: usize = 16;
 
+// The ldap extensible matching rules that we can express as equality.
+const LDAP_MATCHING_RULE_CASE_IGNORE: &str = "2.5.13.2";
+const LDAP_MATCHING_RULE_CASE_EXACT: &str = "2.5.13.5";
+const LDAP_MATCHING_RULE_IN_CHAIN: &str = "1.2.840.113556.1.4.1941";
+
 // Default filter is safe, ignores all hidden types!
 
 // This is &Value so we can lazy const then clone, but perhaps we can reconsider
@@ -57,6 +62,21 @@ pub fn f_lt(a: &str, v: PartialValue) -> FC {
     FC::LessThan(a, v)
 }
 
+#[allow(dead_code)]
+pub fn f_gt(a: &str, v: PartialValue) -> FC {
+    FC::GreaterThan(a, v)
+}
+
+#[allow(dead_code)]
+pub fn f_lte(a: &str, v: PartialValue) -> FC {
+    FC::Or(vec![FC::LessThan(a, v.clone()), FC::Eq(a, v)])
+}
+
+#[allow(dead_code)]
+pub fn f_gte(a: &str, v: PartialValue) -> FC {
+    FC::Or(vec![FC::GreaterThan(a, v.clone()), FC::Eq(a, v)])
+}
+
 #[allow(dead_code)]
 pub fn f_or(vs: Vec<FC>) -> FC {
     FC::Or(vs)
@@ -111,8 +131,11 @@ pub fn f_spn_name(id: &str) -> FC<'static> {
 pub enum FC<'a> {
     Eq(&'a str, PartialValue),
     Sub(&'a str, PartialValue),
+    Stw(&'a str, PartialValue),
+    Enw(&'a str, PartialValue),
     Pres(&'a str),
     LessThan(&'a str, PartialValue),
+    GreaterThan(&'a str, PartialValue),
     Or(Vec<FC<'a>>),
     And(Vec<FC<'a>>),
     Inclusion(Vec<FC<'a>>),
@@ -127,8 +150,11 @@ enum FilterComp {
     // This is attr - value
     Eq(AttrString, PartialValue),
     Sub(AttrString, PartialValue),
+    Stw(AttrString, PartialValue),
+    Enw(AttrString, PartialValue),
     Pres(AttrString),
     LessThan(AttrString, PartialValue),
+    GreaterThan(AttrString, PartialValue),
     Or(Vec<FilterComp>),
     And(Vec<FilterComp>),
     Inclusion(Vec<FilterComp>),
@@ -153,8 +179,11 @@ pub enum FilterResolved {
     // This is attr - value - indexed slope factor
     Eq(AttrString, PartialValue, Option<NonZeroU8>),
     Sub(AttrString, PartialValue, Option<NonZeroU8>),
+    Stw(AttrString, PartialValue, Option<NonZeroU8>),
+    Enw(AttrString, PartialValue, Option<NonZeroU8>),
     Pres(AttrString, Option<NonZeroU8>),
     LessThan(AttrString, PartialValue, Option<NonZeroU8>),
+    GreaterThan(AttrString, PartialValue, Option<NonZeroU8>),
     Or(Vec<FilterResolved>, Option<NonZeroU8>),
     And(Vec<FilterResolved>, Option<NonZeroU8>),
     // All terms must have 1 or more items, or the inclusion is false!
@@ -186,10 +215,13 @@ pub enum FilterPlan {
     SubIndexed(AttrString, String),
     SubUnindexed(AttrString),
     SubCorrupt(AttrString),
+    StwUnindexed(AttrString),
+    EnwUnindexed(AttrString),
     PresIndexed(AttrString),
     PresUnindexed(AttrString),
     PresCorrupt(AttrString),
     LessThanUnindexed(AttrString),
+    GreaterThanUnindexed(AttrString),
     OrUnindexed(Vec<FilterPlan>),
     OrIndexed(Vec<FilterPlan>),
     OrPartial(Vec<FilterPlan>),
@@ -542,8 +574,11 @@ impl FilterComp {
         match fc {
             FC::Eq(a, v) => FilterComp::Eq(AttrString::from(a), v),
             FC::Sub(a, v) => FilterComp::Sub(AttrString::from(a), v),
+            FC::Stw(a, v) => FilterComp::Stw(AttrString::from(a), v),
+            FC::Enw(a, v) => FilterComp::Enw(AttrString::from(a), v),
             FC::Pres(a) => FilterComp::Pres(AttrString::from(a)),
             FC::LessThan(a, v) => FilterComp::LessThan(AttrString::from(a), v),
+            FC::GreaterThan(a, v) => FilterComp::GreaterThan(AttrString::from(a), v),
             FC::Or(v) => FilterComp::Or(v.into_iter().map(FilterComp::new).collect()),
             FC::And(v) => FilterComp::And(v.into_iter().map(FilterComp::new).collect()),
             FC::Inclusion(v) => FilterComp::Inclusion(v.into_iter().map(FilterComp::new).collect()),
@@ -552,6 +587,41 @@ impl FilterComp {
         }
     }
 
+    fn new_gte(a: AttrString, v: PartialValue) -> Self {
+        FilterComp::Or(vec![
+            FilterComp::GreaterThan(a.clone(), v.clone()),
+            FilterComp::Eq(a, v),
+        ])
+    }
+
+    fn new_lte(a: AttrString, v: PartialValue) -> Self {
+        FilterComp::Or(vec![
+            FilterComp::LessThan(a.clone(), v.clone()),
+            FilterComp::Eq(a, v),
+        ])
+    }
+
+    /// A term that never matches. Ldap evaluates a comparison that the attribute
+    /// can not perform as undefined, which excludes the entry rather than failing
+    /// the whole search.
+    fn new_undefined() -> Self {
+        FilterComp::AndNot(Box::new(FilterComp::Pres(AttrString::from("class"))))
+    }
+
+    /// True when the attribute is known to schema, but its syntax has no ordering.
+    fn ldap_attr_unordered(a: &AttrString, qs: &QueryServerReadTransaction) -> bool {
+        qs.get_schema()
+            .get_attributes()
+            .get(a)
+            .map(|schema_a| {
+                !matches!(
+                    schema_a.syntax,
+                    SyntaxType::Uint32 | SyntaxType::DateTime | SyntaxType::Cid
+                )
+            })
+            .unwrap_or(false)
+    }
+
     fn new_ignore_hidden(fc: FilterComp) -> Self {
         FilterComp::And(vec![
             FilterComp::AndNot(Box::new(FilterComp::Or(vec![
@@ -583,13 +653,13 @@ impl FilterComp {
             FilterComp::Eq(attr, _) => {
                 r_set.insert(attr.as_str());
             }
-            FilterComp::Sub(attr, _) => {
+            FilterComp::Sub(attr, _) | FilterComp::Stw(attr, _) | FilterComp::Enw(attr, _) => {
                 r_set.insert(attr.as_str());
             }
             FilterComp::Pres(attr) => {
                 r_set.insert(attr.as_str());
             }
-            FilterComp::LessThan(attr, _) => {
+            FilterComp::LessThan(attr, _) | FilterComp::GreaterThan(attr, _) => {
                 r_set.insert(attr.as_str());
             }
             FilterComp::Or(vs) => vs.iter().for_each(|f| f.get_attr_set(r_set)),
@@ -645,6 +715,36 @@ impl FilterComp {
                     None => Err(SchemaError::InvalidAttribute(attr_norm.to_string())),
                 }
             }
+            FilterComp::Stw(attr, value) => {
+                // Validate/normalise the attr name.
+                let attr_norm = schema.normalise_attr_name(attr);
+                // Now check it exists
+                match schema_attributes.get(&attr_norm) {
+                    Some(schema_a) => {
+                        schema_a
+                            .validate_partialvalue(attr_norm.as_str(), value)
+                            // Okay, it worked, transform to a filter component
+                            .map(|_| FilterComp::Stw(attr_norm, value.clone()))
+                        // On error, pass the error back out.
+                    }
+                    None => Err(SchemaError::InvalidAttribute(attr_norm.to_string())),
+                }
+            }
+            FilterComp::Enw(attr, value) => {
+                // Validate/normalise the attr name.
+                let attr_norm = schema.normalise_attr_name(attr);
+                // Now check it exists
+                match schema_attributes.get(&attr_norm) {
+                    Some(schema_a) => {
+                        schema_a
+                            .validate_partialvalue(attr_norm.as_str(), value)
+                            // Okay, it worked, transform to a filter component
+                            .map(|_| FilterComp::Enw(attr_norm, value.clone()))
+                        // On error, pass the error back out.
+                    }
+                    None => Err(SchemaError::InvalidAttribute(attr_norm.to_string())),
+                }
+            }
             FilterComp::Pres(attr) => {
                 let attr_norm = schema.normalise_attr_name(attr);
                 // Now check it exists
@@ -662,6 +762,13 @@ impl FilterComp {
                 // Now check it exists
                 match schema_attributes.get(&attr_norm) {
                     Some(schema_a) => {
+                        // Only some syntaxes have an order that values can be compared by.
+                        if !matches!(
+                            schema_a.syntax,
+                            SyntaxType::Uint32 | SyntaxType::DateTime | SyntaxType::Cid
+                        ) {
+                            return Err(SchemaError::InvalidAttributeSyntax(attr_norm.to_string()));
+                        }
                         schema_a
                             .validate_partialvalue(attr_norm.as_str(), value)
                             // Okay, it worked, transform to a filter component
@@ -671,6 +778,28 @@ impl FilterComp {
                     None => Err(SchemaError::InvalidAttribute(attr_norm.to_string())),
                 }
             }
+            FilterComp::GreaterThan(attr, value) => {
+                // Validate/normalise the attr name.
+                let attr_norm = schema.normalise_attr_name(attr);
+                // Now check it exists
+                match schema_attributes.get(&attr_norm) {
+                    Some(schema_a) => {
+                        // Only some syntaxes have an order that values can be compared by.
+                        if !matches!(
+                            schema_a.syntax,
+                            SyntaxType::Uint32 | SyntaxType::DateTime | SyntaxType::Cid
+                        ) {
+                            return Err(SchemaError::InvalidAttributeSyntax(attr_norm.to_string()));
+                        }
+                        schema_a
+                            .validate_partialvalue(attr_norm.as_str(), value)
+                            // Okay, it worked, transform to a filter component
+                            .map(|_| FilterComp::GreaterThan(attr_norm, value.clone()))
+                        // On error, pass the error back out.
+                    }
+                    None => Err(SchemaError::InvalidAttribute(attr_norm.to_string())),
+                }
+            }
             FilterComp::Or(filters) => {
                 // If all filters are okay, return Ok(Filter::Or())
                 // If any is invalid, return the error.
@@ -745,6 +874,22 @@ impl FilterComp {
                 let nk = qs.get_schema().normalise_attr_name(a);
                 FilterComp::Pres(nk)
             }
+            ProtoFilter::Gte(a, v) => {
+                *elems = (*elems)
+                    .checked_sub(2)
+                    .ok_or(OperationError::ResourceLimit)?;
+                let nk = qs.get_schema().normalise_attr_name(a);
+                let v = qs.clone_partialvalue(nk.as_str(), v)?;
+                FilterComp::new_gte(nk, v)
+            }
+            ProtoFilter::Lte(a, v) => {
+                *elems = (*elems)
+                    .checked_sub(2)
+                    .ok_or(OperationError::ResourceLimit)?;
+                let nk = qs.get_schema().normalise_attr_name(a);
+                let v = qs.clone_partialvalue(nk.as_str(), v)?;
+                FilterComp::new_lte(nk, v)
+            }
             ProtoFilter::Or(l) => {
                 *elems = (*elems)
                     .checked_sub(l.len())
@@ -797,6 +942,22 @@ impl FilterComp {
                 let nk = qs.get_schema().normalise_attr_name(a);
                 FilterComp::Pres(nk)
             }
+            ProtoFilter::Gte(a, v) => {
+                *elems = (*elems)
+                    .checked_sub(2)
+                    .ok_or(OperationError::ResourceLimit)?;
+                let nk = qs.get_schema().normalise_attr_name(a);
+                let v = qs.clone_partialvalue(nk.as_str(), v)?;
+                FilterComp::new_gte(nk, v)
+            }
+            ProtoFilter::Lte(a, v) => {
+                *elems = (*elems)
+                    .checked_sub(2)
+                    .ok_or(OperationError::ResourceLimit)?;
+                let nk = qs.get_schema().normalise_attr_name(a);
+                let v = qs.clone_partialvalue(nk.as_str(), v)?;
+                FilterComp::new_lte(nk, v)
+            }
             ProtoFilter::Or(l) => {
                 *elems = (*elems)
                     .checked_sub(l.len())
@@ -870,32 +1031,122 @@ impl FilterComp {
             }
             LdapFilter::Present(a) => FilterComp::Pres(ldap_attr_filter_map(a)),
             LdapFilter::Substring(
-                _a,
+                a,
                 LdapSubstringFilter {
-                    initial: _,
-                    any: _,
-                    final_: _,
+                    initial,
+                    any,
+                    final_,
                 },
             ) => {
-                // let a = ldap_attr_filter_map(a);
-                admin_error!("Unable to convert ldapsubstringfilter to sub filter");
-                return Err(OperationError::FilterGeneration);
+                // The initial and final components are anchored to the start and end of
+                // the value. The any components become contains terms, so the order of
+                // them is not enforced, and this may match more than the ldap substring would.
+                //
+                // The anchored terms can't be indexed, so each is paired with a contains term
+                // that the substring index can answer, and the anchored term then only has to
+                // test the remaining candidates.
+                let a = ldap_attr_filter_map(a);
+                let mut subs = Vec::with_capacity(any.len() + 4);
+
+                if let Some(v) = initial.as_ref().filter(|v| !v.is_empty()) {
+                    let v = qs.clone_partialvalue(a.as_str(), v)?;
+                    subs.push(FilterComp::Sub(a.clone(), v.clone()));
+                    subs.push(FilterComp::Stw(a.clone(), v));
+                }
+                for v in any.iter().filter(|v| !v.is_empty()) {
+                    let v = qs.clone_partialvalue(a.as_str(), v)?;
+                    subs.push(FilterComp::Sub(a.clone(), v));
+                }
+                if let Some(v) = final_.as_ref().filter(|v| !v.is_empty()) {
+                    let v = qs.clone_partialvalue(a.as_str(), v)?;
+                    subs.push(FilterComp::Sub(a.clone(), v.clone()));
+                    subs.push(FilterComp::Enw(a.clone(), v));
+                }
+
+                if subs.is_empty() {
+                    admin_error!("Unable to convert empty ldapsubstringfilter to sub filter");
+                    return Err(OperationError::FilterGeneration);
+                }
+
+                *elems = (*elems)
+                    .checked_sub(subs.len())
+                    .ok_or(OperationError::ResourceLimit)?;
+
+                FilterComp::And(subs)
             }
-            LdapFilter::GreaterOrEqual(_, _) => {
-                admin_error!("Unsupported filter operation - greater or equal");
-                return Err(OperationError::FilterGeneration);
+            LdapFilter::GreaterOrEqual(a, v) => {
+                *elems = (*elems)
+                    .checked_sub(2)
+                    .ok_or(OperationError::ResourceLimit)?;
+                let a = ldap_attr_filter_map(a);
+                if Self::ldap_attr_unordered(&a, qs) {
+                    Self::new_undefined()
+                } else {
+                    let v = qs.clone_partialvalue(a.as_str(), v)?;
+                    FilterComp::new_gte(a, v)
+                }
             }
-            LdapFilter::LessOrEqual(_, _) => {
-                admin_error!("Unsupported filter operation - less or equal");
-                return Err(OperationError::FilterGeneration);
+            LdapFilter::LessOrEqual(a, v) => {
+                *elems = (*elems)
+                    .checked_sub(2)
+                    .ok_or(OperationError::ResourceLimit)?;
+                let a = ldap_attr_filter_map(a);
+                if Self::ldap_attr_unordered(&a, qs) {
+                    Self::new_undefined()
+                } else {
+                    let v = qs.clone_partialvalue(a.as_str(), v)?;
+                    FilterComp::new_lte(a, v)
+                }
             }
-            LdapFilter::Approx(_, _) => {
-                admin_error!("Unsupported filter operation - approximate");
-                return Err(OperationError::FilterGeneration);
+            LdapFilter::Approx(a, v) => {
+                // Approximate matching is implementation defined, so we use equality.
+                let a = ldap_attr_filter_map(a);
+                let v = qs.clone_partialvalue(a.as_str(), v)?;
+                FilterComp::Eq(a, v)
             }
-            LdapFilter::Extensible(_) => {
-                admin_error!("Unsupported filter operation - extensible");
-                return Err(OperationError::FilterGeneration);
+            LdapFilter::Extensible(LdapMatchingRuleAssertion {
+                matching_rule,
+                type_,
+                match_value,
+                dn_attributes,
+            }) => {
+                // We support the subset of extensible matches that reduce to equality on
+                // a named attribute. Matching against dn components is not supported.
+                let a = match (type_, dn_attributes) {
+                    (Some(a), false) => ldap_attr_filter_map(a),
+                    _ => {
+                        admin_error!("Unsupported filter operation - extensible without attribute");
+                        return Err(OperationError::FilterGeneration);
+                    }
+                };
+
+                match matching_rule.as_deref() {
+                    // No rule, caseIgnoreMatch and caseExactMatch. The case sensitivity is
+                    // determined by the attribute syntax.
+                    None
+                    | Some(LDAP_MATCHING_RULE_CASE_IGNORE)
+                    | Some(LDAP_MATCHING_RULE_CASE_EXACT) => {}
+                    // memberof is already resolved transitively, so the in chain rule is
+                    // equality. member only holds direct members, so it can't be chained.
+                    Some(LDAP_MATCHING_RULE_IN_CHAIN) if a.as_str() == "memberof" => {}
+                    Some(LDAP_MATCHING_RULE_IN_CHAIN) => {
+                        admin_error!(
+                            ?a,
+                            "Unsupported filter operation - in chain rule on attribute other than memberof"
+                        );
+                        return Err(OperationError::FilterGeneration);
+                    }
+                    Some(rule) => {
+                        admin_error!(
+                            ?rule,
+                            "Unsupported filter operation - extensible matching rule"
+                        );
+                        return Err(OperationError::FilterGeneration);
+                    }
+                }
+
+                let v = qs.clone_partialvalue(a.as_str(), match_value)?;
+                FilterComp::Eq(a, v)
             }
         })
     }
@@ -937,13 +1188,18 @@ impl PartialEq for FilterResolved {
     fn eq(&self, rhs: &FilterResolved) -> bool {
         match (self, rhs) {
             (FilterResolved::Eq(a1, v1, _), FilterResolved::Eq(a2, v2, _)) => a1 == a2 && v1 == v2,
-            (FilterResolved::Sub(a1, v1, _), FilterResolved::Sub(a2, v2, _)) => {
+            (FilterResolved::Sub(a1, v1, _), FilterResolved::Sub(a2, v2, _))
+            | (FilterResolved::Stw(a1, v1, _), FilterResolved::Stw(a2, v2, _))
+            | (FilterResolved::Enw(a1, v1, _), FilterResolved::Enw(a2, v2, _)) => {
                 a1 == a2 && v1 == v2
             }
             (FilterResolved::Pres(a1, _), FilterResolved::Pres(a2, _)) => a1 == a2,
             (FilterResolved::LessThan(a1, v1, _), FilterResolved::LessThan(a2, v2, _)) => {
                 a1 == a2 && v1 == v2
             }
+            (FilterResolved::GreaterThan(a1, v1, _), FilterResolved::GreaterThan(a2, v2, _)) => {
+                a1 == a2 && v1 == v2
+            }
             (FilterResolved::And(vs1, _), FilterResolved::And(vs2, _)) => vs1 == vs2,
             (FilterResolved::Or(vs1, _), FilterResolved::Or(vs2, _)) => vs1 == vs2,
             (FilterResolved::Inclusion(vs1, _), FilterResolved::Inclusion(vs2, _)) => vs1 == vs2,
@@ -982,12 +1238,16 @@ impl Ord for FilterResolved {
             match (self, rhs) {
                 (FilterResolved::Eq(a1, v1, _), FilterResolved::Eq(a2, v2, _))
                 | (FilterResolved::Sub(a1, v1, _), FilterResolved::Sub(a2, v2, _))
-                | (FilterResolved::LessThan(a1, v1, _), FilterResolved::LessThan(a2, v2, _)) => {
-                    match a1.cmp(a2) {
-                        Ordering::Equal => v1.cmp(v2),
-                        o => o,
-                    }
-                }
+                | (FilterResolved::Stw(a1, v1, _), FilterResolved::Stw(a2, v2, _))
+                | (FilterResolved::Enw(a1, v1, _), FilterResolved::Enw(a2, v2, _))
+                | (FilterResolved::LessThan(a1, v1, _), FilterResolved::LessThan(a2, v2, _))
+                | (
+                    FilterResolved::GreaterThan(a1, v1, _),
+                    FilterResolved::GreaterThan(a2, v2, _),
+                ) => match a1.cmp(a2) {
+                    Ordering::Equal => v1.cmp(v2),
+                    o => o,
+                },
                 (FilterResolved::Pres(a1, _), FilterResolved::Pres(a2, _)) => a1.cmp(a2),
                 // Now sort these into the generally "best" order.
                 (FilterResolved::Eq(_, _, _), _) => Ordering::Less,
@@ -996,8 +1256,14 @@ impl Ord for FilterResolved {
                 (_, FilterResolved::Pres(_, _)) => Ordering::Greater,
                 (FilterResolved::LessThan(_, _, _), _) => Ordering::Less,
                 (_, FilterResolved::LessThan(_, _, _)) => Ordering::Greater,
+                (FilterResolved::GreaterThan(_, _, _), _) => Ordering::Less,
+                (_, FilterResolved::GreaterThan(_, _, _)) => Ordering::Greater,
                 (FilterResolved::Sub(_, _, _), _) => Ordering::Less,
                 (_, FilterResolved::Sub(_, _, _)) => Ordering::Greater,
+                (FilterResolved::Stw(_, _, _), _) => Ordering::Less,
+                (_, FilterResolved::Stw(_, _, _)) => Ordering::Greater,
+                (FilterResolved::Enw(_, _, _), _) => Ordering::Less,
+                (_, FilterResolved::Enw(_, _, _)) => Ordering::Greater,
                 // They can't be re-arranged, they don't move!
                 (_, _) => Ordering::Equal,
             }
@@ -1023,6 +1289,8 @@ impl FilterResolved {
                 // let idx = NonZeroU8::new(idx as u8);
                 FilterResolved::Sub(a, v, None)
             }
+            FilterComp::Stw(a, v) => FilterResolved::Stw(a, v, None),
+            FilterComp::Enw(a, v) => FilterResolved::Enw(a, v, None),
             FilterComp::Pres(a) => {
                 let idx = idxmeta.contains(&(&a, &IndexType::Presence));
                 let idx = NonZeroU8::new(idx as u8);
@@ -1034,6 +1302,11 @@ impl FilterResolved {
                 let idx = None;
                 FilterResolved::LessThan(a, v, idx)
             }
+            FilterComp::GreaterThan(a, v) => {
+                // TODO: For now, don't emit ordering indexes.
+                let idx = None;
+                FilterResolved::GreaterThan(a, v, idx)
+            }
             FilterComp::Or(vs) => FilterResolved::Or(
                 vs.into_iter()
                     .map(|v| FilterResolved::from_invalid(v, idxmeta))
@@ -1097,6 +1370,9 @@ impl FilterResolved {
                     .and_then(NonZeroU8::new);
                 Some(FilterResolved::Sub(a, v, idx))
             }
+            // There is no index that can answer these yet.
+            FilterComp::Stw(a, v) => Some(FilterResolved::Stw(a, v, None)),
+            FilterComp::Enw(a, v) => Some(FilterResolved::Enw(a, v, None)),
             FilterComp::Pres(a) => {
                 let idxkref = IdxKeyRef::new(&a, &IndexType::Presence);
                 let idx = idxmeta
@@ -1109,6 +1385,7 @@ impl FilterResolved {
                 // let idx = idxmeta.contains(&(&a, &IndexType::SubString));
                 Some(FilterResolved::LessThan(a, v, None))
             }
+            FilterComp::GreaterThan(a, v) => Some(FilterResolved::GreaterThan(a, v, None)),
             // We set the compound filters slope factor to "None" here, because when we do
             // optimise we'll actually fill in the correct slope factors after we sort those
             // inner terms in a more optimial way.
@@ -1166,8 +1443,11 @@ impl FilterResolved {
                 )
             }),
             FilterComp::Sub(a, v) => Some(FilterResolved::Sub(a, v, None)),
+            FilterComp::Stw(a, v) => Some(FilterResolved::Stw(a, v, None)),
+            FilterComp::Enw(a, v) => Some(FilterResolved::Enw(a, v, None)),
             FilterComp::Pres(a) => Some(FilterResolved::Pres(a, None)),
             FilterComp::LessThan(a, v) => Some(FilterResolved::LessThan(a, v, None)),
+            FilterComp::GreaterThan(a, v) => Some(FilterResolved::GreaterThan(a, v, None)),
             FilterComp::Or(vs) => {
                 let fi: Option<Vec<_>> = vs
                     .into_iter()
@@ -1330,8 +1610,11 @@ impl FilterResolved {
         match self {
             FilterResolved::Eq(_, _, sf)
             | FilterResolved::Sub(_, _, sf)
+            | FilterResolved::Stw(_, _, sf)
+            | FilterResolved::Enw(_, _, sf)
             | FilterResolved::Pres(_, sf)
             | FilterResolved::LessThan(_, _, sf)
+            | FilterResolved::GreaterThan(_, _, sf)
             | FilterResolved::Or(_, sf)
             | FilterResolved::And(_, sf)
             | FilterResolved::Inclusion(_, sf)
@@ -1347,6 +1630,7 @@ mod tests {
     use std::time::Duration;
 
     use kanidm_proto::v1::Filter as ProtoFilter;
+    use ldap3_proto::proto::{LdapMatchingRuleAssertion, LdapSubstringFilter};
     use ldap3_proto::simple::LdapFilter;
 
     use crate::event::{CreateEvent, DeleteEvent};
@@ -1559,6 +1843,37 @@ mod tests {
         assert_eq!(f_t2a == f_t2c, false);
     }
 
+    #[test]
+    fn test_startswith_endswith_entry_filter() {
+        let e: Entry<EntrySealed, EntryNew> = unsafe {
+            Entry::unsafe_from_entry_str(
+                r#"{
+            "attrs": {
+                "userid": ["xxabyy"],
+                "uuid": ["db237e8a-0079-4b8c-8a56-593b22aa44d1"]
+            }
+        }"#,
+            )
+            .into_sealed_new()
+        };
+
+        let f_t1a = unsafe { filter_resolved!(FC::Stw("userid", PartialValue::new_iutf8("xx"))) };
+        assert!(e.entry_match_no_index(&f_t1a));
+
+        let f_t1b = unsafe { filter_resolved!(FC::Enw("userid", PartialValue::new_iutf8("yy"))) };
+        assert!(e.entry_match_no_index(&f_t1b));
+
+        // Neither is satisfied by a match in the middle of the value.
+        let f_t2a = unsafe { filter_resolved!(FC::Stw("userid", PartialValue::new_iutf8("ab"))) };
+        assert!(!e.entry_match_no_index(&f_t2a));
+
+        let f_t2b = unsafe { filter_resolved!(FC::Enw("userid", PartialValue::new_iutf8("ab"))) };
+        assert!(!e.entry_match_no_index(&f_t2b));
+
+        let f_t2c = unsafe { filter_resolved!(f_sub("userid", PartialValue::new_iutf8("ab"))) };
+        assert!(e.entry_match_no_index(&f_t2c));
+    }
+
     #[test]
     fn test_lessthan_entry_filter() {
         let e: Entry<EntrySealed, EntryNew> = unsafe {
@@ -1584,6 +1899,79 @@ mod tests {
         assert!(e.entry_match_no_index(&f_t1c) == true);
     }
 
+    #[test]
+    fn test_range_entry_filter() {
+        let e: Entry<EntrySealed, EntryNew> = unsafe {
+            Entry::unsafe_from_entry_str(
+                r#"{
+            "attrs": {
+                "userid": ["william"],
+                "uuid": ["db237e8a-0079-4b8c-8a56-593b22aa44d1"],
+                "gidnumber": ["1000"]
+            }
+        }"#,
+            )
+            .into_sealed_new()
+        };
+
+        let f_t1a = unsafe { filter_resolved!(f_gte("gidnumber", PartialValue::new_uint32(500))) };
+        assert!(e.entry_match_no_index(&f_t1a));
+
+        let f_t1b = unsafe { filter_resolved!(f_gte("gidnumber", PartialValue::new_uint32(1000))) };
+        assert!(e.entry_match_no_index(&f_t1b));
+
+        let f_t1c = unsafe { filter_resolved!(f_gte("gidnumber", PartialValue::new_uint32(1001))) };
+        assert!(!e.entry_match_no_index(&f_t1c));
+
+        let f_t2a = unsafe { filter_resolved!(f_lte("gidnumber", PartialValue::new_uint32(500))) };
+        assert!(!e.entry_match_no_index(&f_t2a));
+
+        let f_t2b = unsafe { filter_resolved!(f_lte("gidnumber", PartialValue::new_uint32(1000))) };
+        assert!(e.entry_match_no_index(&f_t2b));
+
+        // An entry without the attribute is in neither range.
+        let f_t3 = unsafe { filter_resolved!(f_gte("uidnumber", PartialValue::new_uint32(0))) };
+        assert!(!e.entry_match_no_index(&f_t3));
+    }
+
+    #[test]
+    fn test_range_entry_filter_multivalue() {
+        // Ranges match if any value is within them, in both directions.
+        let e: Entry<EntrySealed, EntryNew> = unsafe {
+            Entry::unsafe_from_entry_str(
+                r#"{
+            "attrs": {
+                "userid": ["william"],
+                "uuid": ["db237e8a-0079-4b8c-8a56-593b22aa44d1"],
+                "gidnumber": ["1", "10"]
+            }
+        }"#,
+            )
+            .into_sealed_new()
+        };
+
+        let f_t1a = unsafe { filter_resolved!(f_gte("gidnumber", PartialValue::new_uint32(5))) };
+        assert!(e.entry_match_no_index(&f_t1a));
+
+        let f_t1b = unsafe { filter_resolved!(f_gte("gidnumber", PartialValue::new_uint32(10))) };
+        assert!(e.entry_match_no_index(&f_t1b));
+
+        let f_t1c = unsafe { filter_resolved!(f_gte("gidnumber", PartialValue::new_uint32(11))) };
+        assert!(!e.entry_match_no_index(&f_t1c));
+
+        let f_t2a = unsafe { filter_resolved!(f_lte("gidnumber", PartialValue::new_uint32(5))) };
+        assert!(e.entry_match_no_index(&f_t2a));
+
+        let f_t2b = unsafe { filter_resolved!(f_lte("gidnumber", PartialValue::new_uint32(1))) };
+        assert!(e.entry_match_no_index(&f_t2b));
+
+        let f_t2c = unsafe { filter_resolved!(f_lte("gidnumber", PartialValue::new_uint32(0))) };
+        assert!(!e.entry_match_no_index(&f_t2c));
+
+        let f_t3 = unsafe { filter_resolved!(f_gt("gidnumber", PartialValue::new_uint32(1))) };
+        assert!(e.entry_match_no_index(&f_t3));
+    }
+
     #[test]
     fn test_or_entry_filter() {
         let e: Entry<EntrySealed, EntryNew> = unsafe {
@@ -1973,4 +2361,119 @@ mod tests {
         let res = Filter::from_rw(&ev, &inv_proto, &wr_txn);
         assert!(res == Err(OperationError::ResourceLimit));
     }
+
+    #[qs_test]
+    async fn test_filter_ldap_translation(server: &QueryServer) {
+        let r_txn = server.read().await;
+        let ev = Identity::from_internal();
+
+        // Substrings are anchored at the start and end, and attribute names are mapped.
+        let ldap = LdapFilter::Substring(
+            "cn".to_string(),
+            LdapSubstringFilter {
+                initial: Some("test".to_string()),
+                any: vec!["per".to_string()],
+                final_: Some("son".to_string()),
+            },
+        );
+        let res = Filter::from_ldap_ro(&ev, &ldap, &r_txn);
+        assert!(
+            res == Ok(filter_all!(f_and!([
+                f_sub("name", PartialValue::new_iname("test")),
+                FC::Stw("name", PartialValue::new_iname("test")),
+                f_sub("name", PartialValue::new_iname("per")),
+                f_sub("name", PartialValue::new_iname("son")),
+                FC::Enw("name", PartialValue::new_iname("son"))
+            ])))
+        );
+
+        let ldap = LdapFilter::Substring(
+            "name".to_string(),
+            LdapSubstringFilter {
+                initial: None,
+                any: vec![],
+                final_: None,
+            },
+        );
+        assert!(Filter::from_ldap_ro(&ev, &ldap, &r_txn) == Err(OperationError::FilterGeneration));
+
+        // Ordering
+        let ldap = LdapFilter::LessOrEqual("uidnumber".to_string(), "2000".to_string());
+        let res = Filter::from_ldap_ro(&ev, &ldap, &r_txn);
+        assert!(
+            res == Ok(filter_all!(f_or!([
+                f_lt("gidnumber", PartialValue::new_uint32(2000)),
+                f_eq("gidnumber", PartialValue::new_uint32(2000))
+            ])))
+        );
+
+        let ldap = LdapFilter::GreaterOrEqual("gidnumber".to_string(), "2000".to_string());
+        let res = Filter::from_ldap_ro(&ev, &ldap, &r_txn);
+        assert!(
+            res == Ok(filter_all!(f_or!([
+                f_gt("gidnumber", PartialValue::new_uint32(2000)),
+                f_eq("gidnumber", PartialValue::new_uint32(2000))
+            ])))
+        );
+
+        // Ordering on an attribute without an order is undefined, and matches nothing.
+        let ldap = LdapFilter::GreaterOrEqual("cn".to_string(), "x".to_string());
+        let res = Filter::from_ldap_ro(&ev, &ldap, &r_txn);
+        assert!(res == Ok(filter_all!(f_andnot(f_pres("class")))));
+
+        // Extensible matches that reduce to equality.
+        let ldap = LdapFilter::Extensible(LdapMatchingRuleAssertion {
+            matching_rule: Some("2.5.13.2".to_string()),
+            type_: Some("name".to_string()),
+            match_value: "testperson".to_string(),
+            dn_attributes: false,
+        });
+        let res = Filter::from_ldap_ro(&ev, &ldap, &r_txn);
+        assert!(
+            res == Ok(filter_all!(f_eq(
+                "name",
+                PartialValue::new_iname("testperson")
+            )))
+        );
+
+        // Unsupported rules and dn attribute matching are rejected.
+        let ldap = LdapFilter::Extensible(LdapMatchingRuleAssertion {
+            matching_rule: Some("2.5.13.15".to_string()),
+            type_: Some("name".to_string()),
+            match_value: "testperson".to_string(),
+            dn_attributes: false,
+        });
+        assert!(Filter::from_ldap_ro(&ev, &ldap, &r_txn) == Err(OperationError::FilterGeneration));
+
+        let ldap = LdapFilter::Extensible(LdapMatchingRuleAssertion {
+            matching_rule: None,
+            type_: Some("name".to_string()),
+            match_value: "testperson".to_string(),
+            dn_attributes: true,
+        });
+        assert!(Filter::from_ldap_ro(&ev, &ldap, &r_txn) == Err(OperationError::FilterGeneration));
+
+        // The in chain rule is only equality on memberof, as member holds direct members only.
+        let ldap = LdapFilter::Extensible(LdapMatchingRuleAssertion {
+            matching_rule: Some("1.2.840.113556.1.4.1941".to_string()),
+            type_: Some("memberof".to_string()),
+            match_value: "idm_admins".to_string(),
+            dn_attributes: false,
+        });
+        let res = Filter::from_ldap_ro(&ev, &ldap, &r_txn);
+        assert!(
+            res == Ok(filter_all!(f_eq(
+                "memberof",
+                PartialValue::new_refer(UUID_IDM_ADMINS)
+            )))
+        );
+
+        let ldap = LdapFilter::Extensible(LdapMatchingRuleAssertion {
+            matching_rule: Some("1.2.840.113556.1.4.1941".to_string()),
+            type_: Some("member".to_string()),
+            match_value: "idm_admins".to_string(),
+            dn_attributes: false,
+        });
+        assert!(Filter::from_ldap_ro(&ev, &ldap, &r_txn) == Err(OperationError::FilterGeneration));
+    }
 }
@@ -2,8 +2,8 @@ use std::collections::{BTreeMap, BTreeSet};
 use std::time::Duration;
 
 use kanidm_proto::v1::{
-    AuthType, BackupCodesView, CredentialStatus, OperationError, UatPurpose, UatStatus, UiHint,
-    UserAuthToken,
+    AuthType, BackupCodesView, CredentialStatus, Oauth2SessionStatus, OperationError, UatPurpose,
+    UatStatus, UiHint, UserAuthToken,
 };
 use time::OffsetDateTime;
 use uuid::Uuid;
@@ -22,6 +22,7 @@ use crate::idm::server::{IdmServerProxyReadTransaction, IdmServerProxyWriteTrans
 use crate::modify::{ModifyInvalid, ModifyList};
 use crate::prelude::*;
 use crate::schema::SchemaTransaction;
+use crate::security_event::SecurityEvent;
 use crate::value::{IntentTokenState, PartialValue, Value};
 
 macro_rules! try_from_entry {
@@ -506,6 +507,15 @@ impl DestroySessionTokenEvent {
     }
 }
 
+pub struct DestroyOauth2SessionEvent {
+    // Who initiated this?
+    pub ident: Identity,
+    // Who is it targetting?
+    pub target: Uuid,
+    // Which oauth2 session id.
+    pub session_id: Uuid,
+}
+
 impl<'a> IdmServerProxyWriteTransaction<'a> {
     pub fn account_destroy_session_token(
         &mut self,
@@ -533,12 +543,60 @@ impl<'a> IdmServerProxyWriteTransaction<'a> {
                 // Provide the event to impersonate
                 &dte.ident,
             )
+            .map(|()| {
+                SecurityEvent::SessionRevoked {
+                    account: dte.target,
+                    session: dte.token_id,
+                    by: dte.ident.get_uuid(),
+                }
+                .emit()
+            })
             .map_err(|e| {
                 admin_error!("Failed to destroy user auth token {:?}", e);
                 e
             })
     }
 
+    pub fn account_destroy_oauth2_session(
+        &mut self,
+        dte: &DestroyOauth2SessionEvent,
+    ) -> Result<(), OperationError> {
+        // Delete the attribute with uuid.
+        let modlist = ModifyList::new_list(vec![Modify::Removed(
+            AttrString::from("oauth2_session"),
+            PartialValue::Refer(dte.session_id),
+        )]);
+
+        self.qs_write
+            .impersonate_modify(
+                // Filter as executed
+                &filter!(f_and!([
+                    f_eq("uuid", PartialValue::Uuid(dte.target)),
+                    f_eq("oauth2_session", PartialValue::Refer(dte.session_id))
+                ])),
+                // Filter as intended (acp)
+                &filter_all!(f_and!([
+                    f_eq("uuid", PartialValue::Uuid(dte.target)),
+                    f_eq("oauth2_session", PartialValue::Refer(dte.session_id))
+                ])),
+                &modlist,
+                // Provide the event to impersonate
+                &dte.ident,
+            )
+            .map(|()| {
+                SecurityEvent::SessionRevoked {
+                    account: dte.target,
+                    session: dte.session_id,
+                    by: dte.ident.get_uuid(),
+                }
+                .emit()
+            })
+            .map_err(|e| {
+                admin_error!("Failed to destroy oauth2 session {:?}", e);
+                e
+            })
+    }
+
     pub fn service_account_into_person(
         &mut self,
         ident: &Identity,
@@ -617,6 +675,13 @@ pub struct ListUserAuthTokenEvent {
     pub target: Uuid,
 }
 
+pub struct ListOauth2SessionEvent {
+    // Who initiated this?
+    pub ident: Identity,
+    // Who is it targetting?
+    pub target: Uuid,
+}
+
 impl<'a> IdmServerProxyReadTransaction<'a> {
     pub fn account_list_user_auth_tokens(
         &self,
@@ -652,6 +717,7 @@ impl<'a> IdmServerProxyReadTransaction<'a> {
                                             .map(|purpose| UatStatus {
                                                 account_id,
                                                 session_id: *u,
+                                                label: s.label.clone(),
                                                 expiry: s.expiry,
                                                 issued_at: s.issued_at,
                                                 purpose,
@@ -672,12 +738,80 @@ impl<'a> IdmServerProxyReadTransaction<'a> {
             Err(e) => Err(e),
         }
     }
+
+    pub fn account_list_oauth2_sessions(
+        &self,
+        lte: &ListOauth2SessionEvent,
+    ) -> Result<Vec<Oauth2SessionStatus>, OperationError> {
+        // Make an event from the request
+        let srch = match SearchEvent::from_target_uuid_request(
+            lte.ident.clone(),
+            lte.target,
+            &self.qs_read,
+        ) {
+            Ok(s) => s,
+            Err(e) => {
+                admin_error!("Failed to begin account list oauth2 sessions: {:?}", e);
+                return Err(e);
+            }
+        };
+
+        let entry = match self.qs_read.search_ext(&srch)?.pop() {
+            Some(e) => e,
+            None => return Ok(Vec::new()),
+        };
+
+        let account_id = entry.get_uuid();
+        let o2_sessions = match entry.get_ava_as_oauth2session_map("oauth2_session") {
+            Some(s) => s,
+            None => return Ok(Vec::new()),
+        };
+
+        // ⚠️  Safety Notes - We perform an internal search here to resolve the names of
+        // the resource servers that issued these sessions, which the ident may not be
+        // able to read. This is safe as we only output the name of the resource server,
+        // and only for sessions the ident is already able to read.
+        let f = filter!(f_and!([
+            f_eq("class", PVCLASS_OAUTH2_RS.clone()),
+            f_or(
+                o2_sessions
+                    .values()
+                    .map(|s| f_eq("uuid", PartialValue::Uuid(s.rs_uuid)))
+                    .collect()
+            )
+        ]));
+
+        let rs_names: BTreeMap<Uuid, String> = self
+            .qs_read
+            .internal_search(f)?
+            .iter()
+            .filter_map(|e| {
+                e.get_ava_single_iname("oauth2_rs_name")
+                    .map(|name| (e.get_uuid(), name.to_string()))
+            })
+            .collect();
+
+        Ok(o2_sessions
+            .iter()
+            .map(|(u, s)| Oauth2SessionStatus {
+                account_id,
+                session_id: *u,
+                parent_session_id: s.parent,
+                rs_uuid: s.rs_uuid,
+                rs_name: rs_names.get(&s.rs_uuid).cloned(),
+                expiry: s.expiry,
+                issued_at: s.issued_at,
+            })
+            .collect())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::event::{CreateEvent, ModifyEvent};
+    use crate::idm::account::{DestroyOauth2SessionEvent, ListOauth2SessionEvent};
     use crate::prelude::*;
+    use crate::value::Oauth2Session;
     use async_std::task;
     use kanidm_proto::v1::{AuthType, UiHint};
 
@@ -779,4 +913,93 @@ mod tests {
             assert!(idms_prox_write.commit().is_ok());
         })
     }
+
+    #[test]
+    fn test_idm_account_oauth2_session_list_and_destroy() {
+        run_idm_test!(|_qs: &QueryServer,
+                       idms: &IdmServer,
+                       _idms_delayed: &mut IdmServerDelayed| {
+            let ct = duration_from_epoch_now();
+            let mut idms_prox_write = task::block_on(idms.proxy_write(ct.clone()));
+
+            let target_uuid = Uuid::new_v4();
+            let rs_uuid = Uuid::new_v4();
+
+            let e1 = entry_init!(
+                ("class", Value::new_class("object")),
+                ("class", Value::new_class("account")),
+                ("class", Value::new_class("person")),
+                ("name", Value::new_iname("testaccount")),
+                ("uuid", Value::new_uuid(target_uuid)),
+                ("description", Value::new_utf8s("testaccount")),
+                ("displayname", Value::new_utf8s("Test Account"))
+            );
+
+            let e2 = entry_init!(
+                ("class", Value::new_class("object")),
+                ("class", Value::new_class("oauth2_resource_server")),
+                ("class", Value::new_class("oauth2_resource_server_basic")),
+                ("uuid", Value::new_uuid(rs_uuid)),
+                ("oauth2_rs_name", Value::new_iname("test_resource_server")),
+                ("displayname", Value::new_utf8s("test_resource_server")),
+                (
+                    "oauth2_rs_origin",
+                    Value::new_url_s("https://demo.example.com").unwrap()
+                )
+            );
+
+            let ce = CreateEvent::new_internal(vec![e1, e2]);
+            assert!(idms_prox_write.qs_write.create(&ce).is_ok());
+
+            let session_id = Uuid::new_v4();
+            let session = Value::Oauth2Session(
+                session_id,
+                Oauth2Session {
+                    parent: Uuid::new_v4(),
+                    expiry: None,
+                    issued_at: time::OffsetDateTime::unix_epoch() + ct,
+                    rs_uuid,
+                    delegated_from: None,
+                },
+            );
+            assert!(idms_prox_write
+                .qs_write
+                .internal_modify(
+                    &filter!(f_eq("uuid", PartialValue::new_uuid(target_uuid))),
+                    &ModifyList::new_append("oauth2_session", session),
+                )
+                .is_ok());
+            assert!(idms_prox_write.commit().is_ok());
+
+            // List the session, which shows the issuing rs.
+            let idms_prox_read = task::block_on(idms.proxy_read());
+            let lte = ListOauth2SessionEvent {
+                ident: Identity::from_internal(),
+                target: target_uuid,
+            };
+            let sessions = idms_prox_read
+                .account_list_oauth2_sessions(&lte)
+                .expect("Failed to list oauth2 sessions");
+            assert!(sessions.len() == 1);
+            assert!(sessions[0].session_id == session_id);
+            assert!(sessions[0].rs_name.as_deref() == Some("test_resource_server"));
+            drop(idms_prox_read);
+
+            // Destroy it.
+            let mut idms_prox_write = task::block_on(idms.proxy_write(ct.clone()));
+            let dte = DestroyOauth2SessionEvent {
+                ident: Identity::from_internal(),
+                target: target_uuid,
+                session_id,
+            };
+            assert!(idms_prox_write.account_destroy_oauth2_session(&dte).is_ok());
+            assert!(idms_prox_write.commit().is_ok());
+
+            let idms_prox_read = task::block_on(idms.proxy_read());
+            assert!(idms_prox_read
+                .account_list_oauth2_sessions(&lte)
+                .expect("Failed to list oauth2 sessions")
+                .is_empty());
+        })
+    }
 }
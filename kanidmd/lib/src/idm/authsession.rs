@@ -31,6 +31,7 @@ use crate::idm::delayed::{
 };
 use crate::idm::AuthState;
 use crate::prelude::*;
+use crate::security_event::SecurityEvent;
 
 // Each CredHandler takes one or more credentials and determines if the
 // handlers requirements can be 100% fufilled. This is where MFA or other
@@ -836,6 +837,7 @@ impl AuthSession {
                     }
                     CredState::Denied(reason) => {
                         security_info!(%reason, "Credentials denied");
+                        self.emit_auth_failure(reason);
                         (
                             Some(AuthSessionState::Denied(reason)),
                             Ok(AuthState::Denied(reason.to_string())),
@@ -866,11 +868,28 @@ impl AuthSession {
 
     /// End the session, defaulting to a denied.
     pub fn end_session(&mut self, reason: &'static str) -> Result<AuthState, OperationError> {
+        self.emit_auth_failure(reason);
         let mut next_state = AuthSessionState::Denied(reason);
         std::mem::swap(&mut self.state, &mut next_state);
         Ok(AuthState::Denied(reason.to_string()))
     }
 
+    fn emit_auth_failure(&self, reason: &str) {
+        SecurityEvent::AuthFailure {
+            account: self.account.uuid,
+            spn: self.account.spn.clone(),
+            reason: reason.to_string(),
+        }
+        .emit();
+    }
+
+    pub(crate) fn emit_account_locked(&self) {
+        SecurityEvent::AccountLocked {
+            account: self.account.uuid,
+        }
+        .emit();
+    }
+
     fn valid_auth_mechs(&self) -> Vec<AuthMech> {
         match &self.state {
             AuthSessionState::Success
@@ -21,6 +21,7 @@ use crate::credential::{BackupCodes, Credential};
 use crate::idm::account::Account;
 use crate::idm::server::{IdmServerCredUpdateTransaction, IdmServerProxyWriteTransaction};
 use crate::prelude::*;
+use crate::security_event::SecurityEvent;
 use crate::utils::{backup_code_from_random, readable_password_from_random, uuid_from_duration};
 use crate::value::IntentTokenState;
 
@@ -811,6 +812,12 @@ impl<'a> IdmServerProxyWriteTransaction<'a> {
                 &filter!(f_eq("uuid", PartialValue::new_uuid(session.account.uuid))),
                 &modlist,
             )
+            .map(|()| {
+                SecurityEvent::CredentialChanged {
+                    account: session.account.uuid,
+                }
+                .emit()
+            })
             .map_err(|e| {
                 request_error!(error = ?e);
                 e
@@ -81,4 +81,6 @@ pub struct Oauth2SessionRecord {
     pub issued_at: OffsetDateTime,
     // Which rs is this related to?
     pub rs_uuid: Uuid,
+    // The oauth2 session this was derived from by token exchange.
+    pub delegated_from: Option<Uuid>,
 }
@@ -0,0 +1,205 @@
+//! Enrolment of hosts. A host is a service account with the host class. An administrator
+//! creates a one time enrolment token for it, which the host exchanges for an api token. The
+//! api token is the credential of that host alone, so a host can be revoked without affecting
+//! any other.
+
+use std::time::Duration;
+
+use openssl::sha;
+
+use crate::idm::server::IdmServerProxyWriteTransaction;
+use crate::idm::serviceaccount::GenerateApiTokenEvent;
+use crate::prelude::*;
+use crate::utils::password_from_random;
+
+/// The label of the api token of an enrolled host.
+pub const HOST_CREDENTIAL_LABEL: &str = "host enrolment";
+
+fn enrol_secret_sha256(secret: &str) -> String {
+    hex::encode(sha::sha256(secret.as_bytes()))
+}
+
+impl<'a> IdmServerProxyWriteTransaction<'a> {
+    /// Create the one time token that enrols a host, replacing any earlier token that was not
+    /// used. The token is only stored as a hash, so it can't be recovered if it is lost, and a
+    /// new one must be created.
+    pub fn host_enrol_token_create(
+        &mut self,
+        ident: &Identity,
+        target: Uuid,
+    ) -> Result<String, OperationError> {
+        let secret = password_from_random();
+
+        let modlist = ModifyList::new_list(vec![
+            m_purge("host_join_token"),
+            m_pres(
+                "host_join_token",
+                &Value::new_iutf8(&enrol_secret_sha256(&secret)),
+            ),
+        ]);
+
+        self.qs_write
+            .impersonate_modify(
+                // Filter as executed
+                &filter!(f_and!([
+                    f_eq("uuid", PartialValue::new_uuid(target)),
+                    f_eq("class", PartialValue::new_class("host"))
+                ])),
+                // Filter as intended (acp)
+                &filter_all!(f_and!([
+                    f_eq("uuid", PartialValue::new_uuid(target)),
+                    f_eq("class", PartialValue::new_class("host"))
+                ])),
+                &modlist,
+                ident,
+            )
+            .map(|_| secret)
+            .map_err(|e| {
+                admin_error!("Failed to create host enrolment token {:?}", e);
+                e
+            })
+    }
+
+    /// Enrol the host of a one time enrolment token, and return its new credential. Any
+    /// credential the host held before is revoked, as the host is enrolled again.
+    pub fn host_enrol(&mut self, token: &str, ct: Duration) -> Result<String, OperationError> {
+        let filter = filter!(f_and!([
+            f_eq("class", PartialValue::new_class("host")),
+            f_eq(
+                "host_join_token",
+                PartialValue::new_iutf8(&enrol_secret_sha256(token.trim()))
+            ),
+        ]));
+
+        let waiting = self.qs_write.internal_search(filter)?;
+        let target = match waiting.first() {
+            Some(e) if waiting.len() == 1 => e.get_uuid(),
+            _ => {
+                security_info!("Host enrolment token does not match a host");
+                return Err(OperationError::AccessDenied);
+            }
+        };
+
+        // The token is only valid once.
+        let modlist = ModifyList::new_list(vec![
+            m_purge("host_join_token"),
+            m_purge("api_token_session"),
+        ]);
+        self.qs_write.internal_modify(
+            &filter!(f_eq("uuid", PartialValue::new_uuid(target))),
+            &modlist,
+        )?;
+
+        let gte = GenerateApiTokenEvent {
+            ident: Identity::from_internal(),
+            target,
+            label: HOST_CREDENTIAL_LABEL.to_string(),
+            expiry: None,
+            read_write: false,
+        };
+
+        self.service_account_generate_api_token(&gte, ct)
+            .map(|token| {
+                security_info!(uuid = %target, "Host enrolled");
+                token
+            })
+    }
+
+    /// Revoke the credential of a host, and any enrolment token it was not yet enrolled with.
+    pub fn host_credential_revoke(
+        &mut self,
+        ident: &Identity,
+        target: Uuid,
+    ) -> Result<(), OperationError> {
+        let modlist = ModifyList::new_list(vec![
+            m_purge("host_join_token"),
+            m_purge("api_token_session"),
+        ]);
+
+        self.qs_write
+            .impersonate_modify(
+                // Filter as executed
+                &filter!(f_and!([
+                    f_eq("uuid", PartialValue::new_uuid(target)),
+                    f_eq("class", PartialValue::new_class("host"))
+                ])),
+                // Filter as intended (acp)
+                &filter_all!(f_and!([
+                    f_eq("uuid", PartialValue::new_uuid(target)),
+                    f_eq("class", PartialValue::new_class("host"))
+                ])),
+                &modlist,
+                ident,
+            )
+            .map_err(|e| {
+                admin_error!("Failed to revoke host credential {:?}", e);
+                e
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::event::CreateEvent;
+    use crate::idm::server::IdmServerTransaction;
+    use crate::prelude::*;
+
+    const TEST_CURRENT_TIME: u64 = 6000;
+
+    #[idm_test]
+    async fn test_idm_host_enrol(idms: &IdmServer, _idms_delayed: &mut IdmServerDelayed) {
+        let ct = Duration::from_secs(TEST_CURRENT_TIME);
+        let past_grc = Duration::from_secs(TEST_CURRENT_TIME + 1) + GRACE_WINDOW;
+        let mut idms_prox_write = idms.proxy_write(ct).await;
+
+        let host_uuid = Uuid::new_v4();
+
+        let e1 = entry_init!(
+            ("class", Value::new_class("object")),
+            ("class", Value::new_class("account")),
+            ("class", Value::new_class("service_account")),
+            ("class", Value::new_class("host")),
+            ("name", Value::new_iname("test_host")),
+            ("uuid", Value::new_uuid(host_uuid)),
+            ("displayname", Value::new_utf8s("test_host"))
+        );
+
+        let ce = CreateEvent::new_internal(vec![e1]);
+        assert!(idms_prox_write.qs_write.create(&ce).is_ok());
+
+        let ident = Identity::from_internal();
+        let enrol_token = idms_prox_write
+            .host_enrol_token_create(&ident, host_uuid)
+            .expect("Failed to create enrol token");
+
+        // A token that was not created is refused.
+        assert!(idms_prox_write.host_enrol("invalid", ct) == Err(OperationError::AccessDenied));
+
+        let credential = idms_prox_write
+            .host_enrol(&enrol_token, ct)
+            .expect("Failed to enrol host");
+
+        let host_ident = idms_prox_write
+            .validate_and_parse_token_to_ident(Some(&credential), ct)
+            .expect("Unable to verify host credential");
+        assert!(host_ident.get_uuid() == Some(host_uuid));
+
+        // The token can only be used once.
+        assert!(idms_prox_write.host_enrol(&enrol_token, ct) == Err(OperationError::AccessDenied));
+
+        // Once revoked, the credential is refused after the grace window.
+        assert!(idms_prox_write
+            .host_credential_revoke(&ident, host_uuid)
+            .is_ok());
+        assert!(
+            idms_prox_write
+                .validate_and_parse_token_to_ident(Some(&credential), past_grc)
+                .expect_err("Should not succeed")
+                == OperationError::SessionExpired
+        );
+
+        assert!(idms_prox_write.commit().is_ok());
+    }
+}
@@ -16,10 +16,13 @@ use compact_jwt::{JwsSigner, OidcClaims, OidcSubject};
 use concread::cowcell::*;
 use fernet::Fernet;
 use hashbrown::HashMap;
+use kanidm_proto::internal::Oauth2Consent;
 pub use kanidm_proto::oauth2::{
     AccessTokenIntrospectRequest, AccessTokenIntrospectResponse, AccessTokenRequest,
-    AccessTokenResponse, AuthorisationRequest, CodeChallengeMethod, ErrorResponse,
-    OidcDiscoveryResponse, TokenRevokeRequest,
+    AccessTokenResponse, AuthorisationRequest, AuthorisationRequestOrPushed,
+    AuthorisationRequestReference, CodeChallengeMethod, ErrorResponse, GrantTypeReq,
+    OidcDiscoveryResponse, PushedAuthorisationRequest, PushedAuthorisationResponse,
+    TokenRevokeRequest,
 };
 use kanidm_proto::oauth2::{
     ClaimType, DisplayValue, GrantType, IdTokenSignAlg, ResponseMode, ResponseType, SubjectType,
@@ -31,7 +34,7 @@ use serde::{Deserialize, Serialize};
 use time::OffsetDateTime;
 use tokio::sync::mpsc::UnboundedSender as Sender;
 use tracing::trace;
-use url::{Origin, Url};
+use url::{Host, Origin, Url};
 
 use crate::identity::IdentityId;
 use crate::idm::delayed::{DelayedAction, Oauth2ConsentGrant, Oauth2SessionRecord};
@@ -39,6 +42,7 @@ use crate::idm::server::{
     IdmServerProxyReadTransaction, IdmServerProxyWriteTransaction, IdmServerTransaction,
 };
 use crate::prelude::*;
+use crate::security_event::SecurityEvent;
 use crate::value::OAUTHSCOPE_RE;
 
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
@@ -61,6 +65,10 @@ pub enum Oauth2Error {
     InsufficientScope,
     // from https://datatracker.ietf.org/doc/html/rfc7009#section-2.2.1
     UnsupportedTokenType,
+    // from https://datatracker.ietf.org/doc/html/rfc6749#section-5.2
+    InvalidGrant,
+    // from https://datatracker.ietf.org/doc/html/rfc8693#section-2.2.2
+    InvalidTarget,
 }
 
 impl std::fmt::Display for Oauth2Error {
@@ -79,10 +87,21 @@ impl std::fmt::Display for Oauth2Error {
             Oauth2Error::InvalidToken => "invalid_token",
             Oauth2Error::InsufficientScope => "insufficient_scope",
             Oauth2Error::UnsupportedTokenType => "unsupported_token_type",
+            Oauth2Error::InvalidGrant => "invalid_grant",
+            Oauth2Error::InvalidTarget => "invalid_target",
         })
     }
 }
 
+// The prefix of request_uri values issued by the pushed authorisation request endpoint.
+// See https://datatracker.ietf.org/doc/html/rfc9126#section-2.2
+const PUSHED_AUTHORISATION_URI_PREFIX: &str = "urn:ietf:params:oauth:request_uri:";
+// How long a pushed authorisation request may be referenced for, in seconds.
+const PUSHED_AUTHORISATION_EXPIRY: u32 = 60;
+// The only token type we accept and issue in token exchanges.
+// See https://datatracker.ietf.org/doc/html/rfc8693#section-3
+const TOKEN_TYPE_ACCESS_TOKEN: &str = "urn:ietf:params:oauth:token-type:access_token";
+
 // == internal state formats that we encrypt and send.
 
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
@@ -133,10 +152,13 @@ enum Oauth2TokenType {
         auth_time: Option<i64>,
     },
     Refresh {
+        scopes: Vec<String>,
         parent_session_id: Uuid,
         session_id: Uuid,
+        auth_type: AuthType,
         expiry: time::OffsetDateTime,
         uuid: Uuid,
+        iat: i64,
     },
 }
 
@@ -158,6 +180,8 @@ pub enum AuthoriseResponse {
     ConsentRequested {
         // A pretty-name of the client
         client_name: String,
+        // The logo of the client as a data url, if it has one.
+        client_image: Option<String>,
         // A list of scopes requested / to be issued.
         scopes: Vec<String>,
         // Extra PII that may be requested
@@ -180,6 +204,53 @@ pub struct AuthorisePermitSuccess {
     pub code: String,
 }
 
+#[derive(Clone)]
+pub enum OauthRSType {
+    Basic {
+        authz_secret: String,
+        // Some clients, especially openid ones don't do pkce. SIGH.
+        // Can we enforce nonce in this case?
+        enable_pkce: bool,
+    },
+    // Native and browser applications can't hold a secret, so they MUST use
+    // pkce, and may only redirect to the exact uri that was configured.
+    Public {
+        redirect_uri: Url,
+    },
+}
+
+impl OauthRSType {
+    fn enable_pkce(&self) -> bool {
+        match self {
+            OauthRSType::Basic { enable_pkce, .. } => *enable_pkce,
+            OauthRSType::Public { .. } => true,
+        }
+    }
+
+    /// Check the client supplied secret (if any) against the configured client type.
+    fn authenticate(&self, secret: Option<&str>) -> Result<(), Oauth2Error> {
+        match (self, secret) {
+            (OauthRSType::Basic { authz_secret, .. }, Some(secret)) => {
+                if authz_secret == secret {
+                    Ok(())
+                } else {
+                    security_info!("Invalid oauth2 client_id secret");
+                    Err(Oauth2Error::AuthenticationRequired)
+                }
+            }
+            (OauthRSType::Basic { .. }, None) => {
+                security_info!("Invalid oauth2 authentication - no client secret was provided");
+                Err(Oauth2Error::AuthenticationRequired)
+            }
+            (OauthRSType::Public { .. }, None) => Ok(()),
+            (OauthRSType::Public { .. }, Some(_)) => {
+                security_info!("Invalid oauth2 authentication - public clients must not provide a client secret");
+                Err(Oauth2Error::AuthenticationRequired)
+            }
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct Oauth2RS {
     name: String,
@@ -189,24 +260,142 @@ pub struct Oauth2RS {
     origin_https: bool,
     scope_maps: BTreeMap<Uuid, BTreeSet<String>>,
     sup_scope_maps: BTreeMap<Uuid, BTreeSet<String>>,
-    // Client Auth Type (basic is all we support for now.
-    authz_secret: String,
+    // Client Auth Type
+    type_: OauthRSType,
     // Our internal exchange encryption material for this rs.
     token_fernet: Fernet,
     jws_signer: JwsSigner,
     // jws_validator: JwsValidator,
-    // Some clients, especially openid ones don't do pkce. SIGH.
-    // Can we enforce nonce in this case?
-    enable_pkce: bool,
     // For oidc we also need our issuer url.
     iss: Url,
     // For discovery we need to build and keep a number of values.
     authorization_endpoint: Url,
     token_endpoint: Url,
+    par_endpoint: Url,
     userinfo_endpoint: Url,
     jwks_uri: Url,
     scopes_supported: Vec<String>,
     prefer_short_username: bool,
+    // Token lifetimes, in seconds.
+    access_token_expiry: u32,
+    // If none, refresh tokens are not issued to this rs.
+    refresh_token_expiry: Option<u32>,
+    // May the oauth2 session continue after the parent uat session ends?
+    session_outlive_parent: bool,
+    // The resource servers that this rs may exchange user tokens for.
+    token_exchange_audience: BTreeSet<Uuid>,
+    // The logo of this rs as a data url, to show on the consent page.
+    image: Option<String>,
+}
+
+impl Oauth2RS {
+    /// Check that the redirect uri of a request is permitted for this rs.
+    fn check_redirect_uri(&self, redirect_uri_req: &Url) -> Result<(), Oauth2Error> {
+        if let OauthRSType::Public { redirect_uri } = &self.type_ {
+            // Public clients may only return to the exact uri they registered. The only
+            // exception is loopback where the port is allocated by the os at runtime, so
+            // we ignore it as per RFC8252 7.3.
+            if !redirect_uri_exact_match(redirect_uri, redirect_uri_req) {
+                admin_warn!(
+                    "Invalid oauth2 redirect_uri (must exactly match {}) - got {}",
+                    redirect_uri,
+                    redirect_uri_req
+                );
+                return Err(Oauth2Error::InvalidOrigin);
+            }
+        } else {
+            // redirect_uri must be part of the client_id origin.
+            if redirect_uri_req.origin() != self.origin {
+                admin_warn!(
+                    origin = ?self.origin,
+                    "Invalid oauth2 redirect_uri (must be related to origin {:?}) - got {:?}",
+                    self.origin,
+                    redirect_uri_req.origin()
+                );
+                return Err(Oauth2Error::InvalidOrigin);
+            }
+
+            if self.origin_https && redirect_uri_req.scheme() != "https" {
+                admin_warn!(
+                    origin = ?self.origin,
+                    "Invalid oauth2 redirect_uri (must be https for secure origin) - got {:?}", redirect_uri_req.scheme()
+                );
+                return Err(Oauth2Error::InvalidOrigin);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn generate_access_token_response(
+        &self,
+        scopes: Vec<String>,
+        uuid: Uuid,
+        auth_type: AuthType,
+        parent_session_id: Uuid,
+        session_id: Uuid,
+        (expiry, expires_in): (OffsetDateTime, u32),
+        session_expiry: OffsetDateTime,
+        ct: Duration,
+    ) -> Result<AccessTokenResponse, Oauth2Error> {
+        let iat = ct.as_secs() as i64;
+
+        let scope = if scopes.is_empty() {
+            None
+        } else {
+            Some(scopes.join(" "))
+        };
+
+        // Refresh tokens are valid for as long as the session is.
+        let refresh_token = if self.refresh_token_expiry.is_some() {
+            let refresh_token_raw = Oauth2TokenType::Refresh {
+                scopes: scopes.clone(),
+                parent_session_id,
+                session_id,
+                auth_type: auth_type.clone(),
+                expiry: session_expiry,
+                uuid,
+                iat,
+            };
+            Some(self.encrypt_token(&refresh_token_raw, ct)?)
+        } else {
+            None
+        };
+
+        let access_token_raw = Oauth2TokenType::Access {
+            scopes,
+            parent_session_id,
+            session_id,
+            auth_type,
+            expiry,
+            uuid,
+            iat,
+            nbf: iat,
+            auth_time: None,
+        };
+
+        let access_token = self.encrypt_token(&access_token_raw, ct)?;
+
+        Ok(AccessTokenResponse {
+            access_token,
+            token_type: "bearer".to_string(),
+            expires_in,
+            refresh_token,
+            scope,
+            id_token: None,
+            issued_token_type: None,
+        })
+    }
+
+    fn encrypt_token(&self, token: &Oauth2TokenType, ct: Duration) -> Result<String, Oauth2Error> {
+        let token_data = serde_json::to_vec(token).map_err(|e| {
+            admin_error!(err = ?e, "Unable to encode token data");
+            Oauth2Error::ServerError(OperationError::SerdeJsonError)
+        })?;
+
+        Ok(self.token_fernet.encrypt_at_time(&token_data, ct.as_secs()))
+    }
 }
 
 impl std::fmt::Debug for Oauth2RS {
@@ -288,158 +477,226 @@ impl<'a> Oauth2ResourceServersWriteTransaction<'a> {
                 if !ent.attribute_equality("class", &PVCLASS_OAUTH2_RS) {
                     admin_error!("Missing class oauth2_resource_server");
                     // Check we have oauth2_resource_server class
-                    Err(OperationError::InvalidEntryState)
-                } else if ent.attribute_equality("class", &PVCLASS_OAUTH2_BASIC) {
-                    // If we have oauth2_resource_server_basic
-                    // Now we know we can load the attrs.
-                    trace!("name");
-                    let name = ent
-                        .get_ava_single_iname("oauth2_rs_name")
-                        .map(str::to_string)
-                        .ok_or(OperationError::InvalidValueState)?;
-                    trace!("displayname");
-                    let displayname = ent
-                        .get_ava_single_utf8("displayname")
-                        .map(str::to_string)
-                        .ok_or(OperationError::InvalidValueState)?;
-                    trace!("origin");
-                    let (origin, origin_https) = ent
-                        .get_ava_single_url("oauth2_rs_origin")
-                        .map(|url| (url.origin(), url.scheme() == "https"))
-                        .ok_or(OperationError::InvalidValueState)?;
-
-                    let landing_valid = ent
-                        .get_ava_single_url("oauth2_rs_origin_landing")
-                        .map(|url| url.origin() == origin).
-                        unwrap_or(true);
+                    return Err(OperationError::InvalidEntryState);
+                }
 
-                    if !landing_valid {
-                        warn!("{} has a landing page that is not part of origin. May be invalid.", name);
-                    }
+                trace!("name");
+                let name = ent
+                    .get_ava_single_iname("oauth2_rs_name")
+                    .map(str::to_string)
+                    .ok_or(OperationError::InvalidValueState)?;
+                trace!("displayname");
+                let displayname = ent
+                    .get_ava_single_utf8("displayname")
+                    .map(str::to_string)
+                    .ok_or(OperationError::InvalidValueState)?;
+                trace!("origin");
+                let origin_url = ent
+                    .get_ava_single_url("oauth2_rs_origin")
+                    .cloned()
+                    .ok_or(OperationError::InvalidValueState)?;
+                let (origin, origin_https) = (origin_url.origin(), origin_url.scheme() == "https");
+
+                let landing_valid = ent
+                    .get_ava_single_url("oauth2_rs_origin_landing")
+                    .map(|url| url.origin() == origin).
+                    unwrap_or(true);
+
+                if !landing_valid {
+                    warn!("{} has a landing page that is not part of origin. May be invalid.", name);
+                }
 
+                let type_ = if ent.attribute_equality("class", &PVCLASS_OAUTH2_BASIC) {
+                    // If we have oauth2_resource_server_basic
                     trace!("authz_secret");
                     let authz_secret = ent
                         .get_ava_single_secret("oauth2_rs_basic_secret")
                         .map(str::to_string)
                         .ok_or(OperationError::InvalidValueState)?;
-                    trace!("token_key");
-                    let token_fernet = ent
-                        .get_ava_single_secret("oauth2_rs_token_key")
-                        .ok_or(OperationError::InvalidValueState)
-                        .and_then(|key| {
-                            Fernet::new(key).ok_or(OperationError::CryptographyError)
-                        })?;
-
-                    trace!("scope_maps");
-                    let scope_maps = ent
-                        .get_ava_as_oauthscopemaps("oauth2_rs_scope_map")
-                        .cloned()
-                        .unwrap_or_default();
-
-                    trace!("sup_scope_maps");
-                    let sup_scope_maps = ent
-                        .get_ava_as_oauthscopemaps("oauth2_rs_sup_scope_map")
-                        .cloned()
-                        .unwrap_or_default();
-
-                    trace!("oauth2_jwt_legacy_crypto_enable");
-                    let jws_signer = if ent.get_ava_single_bool("oauth2_jwt_legacy_crypto_enable").unwrap_or(false) {
-                        trace!("rs256_private_key_der");
+
+                    let enable_pkce = ent
+                        .get_ava_single_bool("oauth2_allow_insecure_client_disable_pkce")
+                        .map(|e| !e)
+                        .unwrap_or(true);
+
+                    OauthRSType::Basic { authz_secret, enable_pkce }
+                } else if ent.attribute_equality("class", &PVCLASS_OAUTH2_PUBLIC) {
+                    // Public clients redirect to exactly the uri they were configured with.
+                    if origin_url.scheme() == "http" && !is_loopback_url(&origin_url) {
+                        admin_error!("{} is a public client with an insecure http redirect uri that is not loopback", name);
+                        return Err(OperationError::InvalidValueState);
+                    }
+                    OauthRSType::Public { redirect_uri: origin_url }
+                } else {
+                    admin_error!("Missing class oauth2_resource_server_basic or oauth2_resource_server_public");
+                    return Err(OperationError::InvalidEntryState);
+                };
+
+                trace!("token_key");
+                let token_fernet = ent
+                    .get_ava_single_secret("oauth2_rs_token_key")
+                    .ok_or(OperationError::InvalidValueState)
+                    .and_then(|key| {
+                        Fernet::new(key).ok_or(OperationError::CryptographyError)
+                    })?;
+
+                trace!("scope_maps");
+                let scope_maps = ent
+                    .get_ava_as_oauthscopemaps("oauth2_rs_scope_map")
+                    .cloned()
+                    .unwrap_or_default();
+
+                trace!("sup_scope_maps");
+                let sup_scope_maps = ent
+                    .get_ava_as_oauthscopemaps("oauth2_rs_sup_scope_map")
+                    .cloned()
+                    .unwrap_or_default();
+
+                trace!("oauth2_jwt_sign_alg");
+                let legacy_crypto = ent
+                    .get_ava_single_bool("oauth2_jwt_legacy_crypto_enable")
+                    .unwrap_or(false);
+                // Without an explicit choice, legacy crypto implies rs256.
+                let sign_alg = ent
+                    .get_ava_single_iutf8("oauth2_jwt_sign_alg")
+                    .unwrap_or(if legacy_crypto { "rs256" } else { "es256" });
+
+                let jws_signer = match sign_alg {
+                    "es256" => {
+                        trace!("es256_private_key_der");
                         ent
-                            .get_ava_single_private_binary("rs256_private_key_der")
+                            .get_ava_single_private_binary("es256_private_key_der")
                             .ok_or(OperationError::InvalidValueState)
                             .and_then(|key_der| {
-                                JwsSigner::from_rs256_der(key_der).map_err(|e| {
-                                    admin_error!(err = ?e, "Unable to load Legacy RS256 JwsSigner from DER");
+                                JwsSigner::from_es256_der(key_der).map_err(|e| {
+                                    admin_error!(err = ?e, "Unable to load ES256 JwsSigner from DER");
                                     OperationError::CryptographyError
                                 })
                             })?
-                    } else {
-                        trace!("es256_private_key_der");
+                    }
+                    "rs256" => {
+                        trace!("rs256_private_key_der");
                         ent
-                            .get_ava_single_private_binary("es256_private_key_der")
+                            .get_ava_single_private_binary("rs256_private_key_der")
                             .ok_or(OperationError::InvalidValueState)
                             .and_then(|key_der| {
-                                JwsSigner::from_es256_der(key_der).map_err(|e| {
-                                    admin_error!(err = ?e, "Unable to load ES256 JwsSigner from DER");
+                                JwsSigner::from_rs256_der(key_der).map_err(|e| {
+                                    admin_error!(err = ?e, "Unable to load RS256 JwsSigner from DER");
                                     OperationError::CryptographyError
                                 })
                             })?
-                    };
+                    }
+                    "hs256" => match &type_ {
+                        // hs256 is keyed by the client secret, so only clients that
+                        // hold one can use it.
+                        OauthRSType::Basic { authz_secret, .. } => {
+                            JwsSigner::from_hs256_raw(authz_secret.as_bytes()).map_err(|e| {
+                                admin_error!(err = ?e, "Unable to load HS256 JwsSigner from client secret");
+                                OperationError::CryptographyError
+                            })?
+                        }
+                        OauthRSType::Public { .. } => {
+                            admin_error!("{} is a public client and can not use hs256", name);
+                            return Err(OperationError::InvalidValueState);
+                        }
+                    },
+                    other => {
+                        admin_error!("{} has an unsupported oauth2_jwt_sign_alg {}", name, other);
+                        return Err(OperationError::InvalidValueState);
+                    }
+                };
 
-                    /*
-                    let jws_validator = jws_signer.get_validator().map_err(|e| {
-                        admin_error!(err = ?e, "Unable to load JwsValidator from JwsSigner");
-                        OperationError::CryptographyError
-                    })?;
-                    */
+                /*
+                let jws_validator = jws_signer.get_validator().map_err(|e| {
+                    admin_error!(err = ?e, "Unable to load JwsValidator from JwsSigner");
+                    OperationError::CryptographyError
+                })?;
+                */
 
-                    let enable_pkce = ent
-                        .get_ava_single_bool("oauth2_allow_insecure_client_disable_pkce")
-                        .map(|e| !e)
-                        .unwrap_or(true);
+                let prefer_short_username = ent
+                    .get_ava_single_bool("oauth2_prefer_short_username")
+                    .unwrap_or(false);
 
-                    let prefer_short_username = ent
-                        .get_ava_single_bool("oauth2_prefer_short_username")
-                        .unwrap_or(false);
+                let access_token_expiry = ent
+                    .get_ava_single_uint32("oauth2_access_token_expiry")
+                    .unwrap_or(OAUTH2_ACCESS_TOKEN_EXPIRY);
 
-                    let mut authorization_endpoint = self.inner.origin.clone();
-                    authorization_endpoint.set_path("/ui/oauth2");
+                let refresh_token_expiry = ent
+                    .get_ava_single_uint32("oauth2_refresh_token_expiry");
 
-                    let mut token_endpoint = self.inner.origin.clone();
-                    token_endpoint.set_path("/oauth2/token");
+                let session_outlive_parent = ent
+                    .get_ava_single_bool("oauth2_session_outlive_parent")
+                    .unwrap_or(false);
 
-                    let mut userinfo_endpoint = self.inner.origin.clone();
-                    userinfo_endpoint.set_path(&format!("/oauth2/openid/{}/userinfo", name));
+                let token_exchange_audience = ent
+                    .get_ava_refer("oauth2_token_exchange_audience")
+                    .cloned()
+                    .unwrap_or_default();
 
-                    let mut jwks_uri = self.inner.origin.clone();
-                    jwks_uri.set_path(&format!("/oauth2/openid/{}/public_key.jwk", name));
+                let image = ent.get_ava_single_image("image").map(|i| i.to_data_url());
 
-                    let mut iss = self.inner.origin.clone();
-                    iss.set_path(&format!("/oauth2/openid/{}", name));
+                let mut authorization_endpoint = self.inner.origin.clone();
+                authorization_endpoint.set_path("/ui/oauth2");
 
-                    let scopes_supported: BTreeSet<String> =
-                    scope_maps
-                        .values()
-                        .flat_map(|bts| bts.iter())
+                let mut token_endpoint = self.inner.origin.clone();
+                token_endpoint.set_path("/oauth2/token");
 
-                        .chain(
-                            sup_scope_maps
-                                .values()
-                                .flat_map(|bts| bts.iter())
-                        )
+                let mut par_endpoint = self.inner.origin.clone();
+                par_endpoint.set_path("/oauth2/authorise/par");
 
-                        .cloned()
-                        .collect();
-                    let scopes_supported: Vec<_> = scopes_supported.into_iter().collect();
+                let mut userinfo_endpoint = self.inner.origin.clone();
+                userinfo_endpoint.set_path(&format!("/oauth2/openid/{}/userinfo", name));
 
-                    let client_id = name.clone();
-                    let rscfg = Oauth2RS {
-                        name,
-                        displayname,
-                        uuid,
-                        origin,
-                        origin_https,
-                        scope_maps,
-                        sup_scope_maps,
-                        authz_secret,
-                        token_fernet,
-                        jws_signer,
-                        // jws_validator,
-                        enable_pkce,
-                        iss,
-                        authorization_endpoint,
-                        token_endpoint,
-                        userinfo_endpoint,
-                        jwks_uri,
-                        scopes_supported,
-                        prefer_short_username,
-                    };
+                let mut jwks_uri = self.inner.origin.clone();
+                jwks_uri.set_path(&format!("/oauth2/openid/{}/public_key.jwk", name));
 
-                    Ok((client_id, rscfg))
-                } else {
-                    Err(OperationError::InvalidEntryState)
-                }
+                let mut iss = self.inner.origin.clone();
+                iss.set_path(&format!("/oauth2/openid/{}", name));
+
+                let scopes_supported: BTreeSet<String> =
+                scope_maps
+                    .values()
+                    .flat_map(|bts| bts.iter())
+
+                    .chain(
+                        sup_scope_maps
+                            .values()
+                            .flat_map(|bts| bts.iter())
+                    )
+
+                    .cloned()
+                    .collect();
+                let scopes_supported: Vec<_> = scopes_supported.into_iter().collect();
+
+                let client_id = name.clone();
+                let rscfg = Oauth2RS {
+                    name,
+                    displayname,
+                    uuid,
+                    origin,
+                    origin_https,
+                    scope_maps,
+                    sup_scope_maps,
+                    type_,
+                    token_fernet,
+                    jws_signer,
+                    // jws_validator,
+                    iss,
+                    authorization_endpoint,
+                    token_endpoint,
+                    par_endpoint,
+                    userinfo_endpoint,
+                    jwks_uri,
+                    scopes_supported,
+                    prefer_short_username,
+                    access_token_expiry,
+                    refresh_token_expiry,
+                    session_outlive_parent,
+                    token_exchange_audience,
+                    image,
+                };
+
+                Ok((client_id, rscfg))
             })
             .collect();
 
@@ -472,10 +729,7 @@ impl<'a> IdmServerProxyWriteTransaction<'a> {
         })?;
 
         // check the secret.
-        if o2rs.authz_secret != secret {
-            security_info!("Invalid oauth2 client_id secret");
-            return Err(Oauth2Error::AuthenticationRequired);
-        }
+        o2rs.type_.authenticate(Some(&secret))?;
         // We are authenticated! Yay! Now we can actually check things ...
 
         // Can we deserialise the token?
@@ -533,6 +787,14 @@ impl<'a> IdmServerProxyWriteTransaction<'a> {
                         &filter!(f_eq("uuid", PartialValue::new_uuid(uuid))),
                         &modlist,
                     )
+                    .map(|()| {
+                        SecurityEvent::SessionRevoked {
+                            account: uuid,
+                            session: session_id,
+                            by: None,
+                        }
+                        .emit()
+                    })
                     .map_err(|e| {
                         admin_error!("Failed to modify - revoke oauth2 session {:?}", e);
                         Oauth2Error::ServerError(e)
@@ -540,9 +802,190 @@ impl<'a> IdmServerProxyWriteTransaction<'a> {
             }
         }
     }
+
+    /// Revoke the consent an identity has granted to a resource server. This also
+    /// removes all oauth2 sessions the identity has with that resource server.
+    pub fn oauth2_consent_revoke(
+        &mut self,
+        ident: &Identity,
+        client_id: &str,
+    ) -> Result<(), OperationError> {
+        let target = ident.get_uuid().ok_or_else(|| {
+            admin_error!("Invalid identity - no uuid present");
+            OperationError::InvalidState
+        })?;
+
+        let o2rs = self.oauth2rs.inner.rs_set.get(client_id).ok_or_else(|| {
+            admin_warn!("Invalid oauth2 client_id");
+            OperationError::NoMatchingEntries
+        })?;
+
+        // ⚠️  Safety Notes - We perform an internal modify here which bypasses access
+        // controls, as users may not be able to write to their consent or session
+        // attributes. This is safe as the target is derived from the ident, so a user
+        // can only ever revoke their own consent.
+        //
+        // Removing the rs uuid from the oauth2 sessions removes every session related
+        // to this rs.
+        let modlist = ModifyList::new_list(vec![
+            Modify::Removed(
+                AttrString::from("oauth2_consent_scope_map"),
+                PartialValue::Refer(o2rs.uuid),
+            ),
+            Modify::Removed(
+                AttrString::from("oauth2_session"),
+                PartialValue::Refer(o2rs.uuid),
+            ),
+        ]);
+
+        security_info!(?target, rs_uuid = ?o2rs.uuid, "Revoking oauth2 consent");
+
+        self.qs_write
+            .internal_modify(
+                &filter!(f_eq("uuid", PartialValue::new_uuid(target))),
+                &modlist,
+            )
+            .map_err(|e| {
+                admin_error!("Failed to modify - revoke oauth2 consent {:?}", e);
+                e
+            })
+    }
 }
 
 impl Oauth2ResourceServersReadTransaction {
+    pub fn oauth2_consent_list(
+        &self,
+        ident: &Identity,
+    ) -> Result<Vec<Oauth2Consent>, OperationError> {
+        // Consents to a resource server that no longer exists are ignored, these are
+        // removed by referential integrity.
+        let consents = self
+            .inner
+            .rs_set
+            .values()
+            .filter_map(|o2rs| {
+                ident
+                    .get_oauth2_consent_scopes(o2rs.uuid)
+                    .map(|scopes| Oauth2Consent {
+                        name: o2rs.name.clone(),
+                        display_name: o2rs.displayname.clone(),
+                        scopes: scopes.iter().cloned().collect(),
+                    })
+            })
+            .collect::<Vec<_>>();
+
+        trace!(?consents);
+
+        Ok(consents)
+    }
+
+    pub fn check_oauth2_pushed_authorisation(
+        &self,
+        client_authz: Option<&str>,
+        par_req: &PushedAuthorisationRequest,
+        ct: Duration,
+    ) -> Result<PushedAuthorisationResponse, Oauth2Error> {
+        let auth_req = &par_req.auth_req;
+
+        let (client_id, secret) = if let Some(client_authz) = client_authz {
+            let (client_id, secret) = parse_basic_authz(client_authz)?;
+            (client_id, Some(secret))
+        } else {
+            // Public clients only identify themselves by client_id in the post data.
+            (auth_req.client_id.clone(), par_req.client_secret.clone())
+        };
+
+        // A client may only push requests on behalf of itself.
+        if client_id != auth_req.client_id {
+            security_info!(
+                "Invalid pushed authorisation request - client_id does not match the authenticated client"
+            );
+            return Err(Oauth2Error::InvalidRequest);
+        }
+
+        let o2rs = self.inner.rs_set.get(&client_id).ok_or_else(|| {
+            admin_warn!("Invalid oauth2 client_id");
+            Oauth2Error::AuthenticationRequired
+        })?;
+
+        o2rs.type_.authenticate(secret.as_deref())?;
+
+        // RFC 9126 2.1 - a pushed request must not itself refer to a pushed request.
+        if auth_req.unknown_keys.contains_key("request_uri") {
+            admin_warn!("Invalid pushed authorisation request - must not contain request_uri");
+            return Err(Oauth2Error::InvalidRequest);
+        }
+
+        // We can only validate the parts of the request that don't depend on the user here. The
+        // request is checked in full when it is referenced from the authorisation endpoint.
+        if auth_req.response_type != "code" {
+            admin_warn!("Invalid oauth2 response_type (should be 'code')");
+            return Err(Oauth2Error::UnsupportedResponseType);
+        }
+
+        o2rs.check_redirect_uri(&auth_req.redirect_uri)?;
+
+        let par_data = serde_json::to_vec(auth_req).map_err(|e| {
+            admin_error!(err = ?e, "Unable to encode pushed authorisation request");
+            Oauth2Error::ServerError(OperationError::SerdeJsonError)
+        })?;
+
+        // Encrypted with the rs key, so that only this rs may reference it.
+        let par_token = o2rs.token_fernet.encrypt_at_time(&par_data, ct.as_secs());
+
+        Ok(PushedAuthorisationResponse {
+            request_uri: format!("{}{}", PUSHED_AUTHORISATION_URI_PREFIX, par_token),
+            expires_in: PUSHED_AUTHORISATION_EXPIRY,
+        })
+    }
+
+    pub fn check_oauth2_pushed_authorisation_reference(
+        &self,
+        auth_ref: &AuthorisationRequestReference,
+        ct: Duration,
+    ) -> Result<AuthorisationRequest, Oauth2Error> {
+        let o2rs = self.inner.rs_set.get(&auth_ref.client_id).ok_or_else(|| {
+            admin_warn!(
+                "Invalid oauth2 client_id ({}) Have you configured the oauth2 resource server?",
+                &auth_ref.client_id
+            );
+            Oauth2Error::InvalidClientId
+        })?;
+
+        let par_token = auth_ref
+            .request_uri
+            .strip_prefix(PUSHED_AUTHORISATION_URI_PREFIX)
+            .ok_or_else(|| {
+                admin_warn!("Invalid oauth2 request_uri - not issued by this server");
+                Oauth2Error::InvalidRequest
+            })?;
+
+        let auth_req: AuthorisationRequest = o2rs
+            .token_fernet
+            .decrypt_at_time(
+                par_token,
+                Some(PUSHED_AUTHORISATION_EXPIRY as u64),
+                ct.as_secs(),
+            )
+            .map_err(|_| {
+                admin_error!("Failed to decrypt pushed authorisation request");
+                Oauth2Error::InvalidRequest
+            })
+            .and_then(|data| {
+                serde_json::from_slice(&data).map_err(|e| {
+                    admin_error!(err = ?e, "Failed to deserialise pushed authorisation request");
+                    Oauth2Error::InvalidRequest
+                })
+            })?;
+
+        if auth_req.client_id != auth_ref.client_id {
+            security_info!("pushed authorisation request client_id does not match the reference");
+            return Err(Oauth2Error::InvalidRequest);
+        }
+
+        Ok(auth_req)
+    }
+
     pub fn check_oauth2_authorisation(
         &self,
         ident: &Identity,
@@ -579,27 +1022,21 @@ impl Oauth2ResourceServersReadTransaction {
             Oauth2Error::InvalidClientId
         })?;
 
-        // redirect_uri must be part of the client_id origin.
-        if auth_req.redirect_uri.origin() != o2rs.origin {
-            admin_warn!(
-                origin = ?o2rs.origin,
-                "Invalid oauth2 redirect_uri (must be related to origin {:?}) - got {:?}",
-                o2rs.origin,
-                auth_req.redirect_uri.origin()
-            );
-            return Err(Oauth2Error::InvalidOrigin);
-        }
+        o2rs.check_redirect_uri(&auth_req.redirect_uri)?;
 
-        if o2rs.origin_https && auth_req.redirect_uri.scheme() != "https" {
-            admin_warn!(
-                origin = ?o2rs.origin,
-                "Invalid oauth2 redirect_uri (must be https for secure origin) - got {:?}", auth_req.redirect_uri.scheme()
+        // Only members of a group in the scope map may begin authorisation to this rs. Everyone
+        // else is turned away before we process anything further about the request.
+        if !o2rs.scope_maps.keys().any(|u| ident.is_memberof(*u)) {
+            security_access!(
+                %ident,
+                rs = %o2rs.name,
+                "denied ❌ - identity is not a member of any group in the resource server scope map"
             );
-            return Err(Oauth2Error::InvalidOrigin);
+            return Err(Oauth2Error::AccessDenied);
         }
 
         let code_challenge = if let Some(pkce_request) = &auth_req.pkce_request {
-            if !o2rs.enable_pkce {
+            if !o2rs.type_.enable_pkce() {
                 security_info!(?o2rs.name, "Insecure rs configuration - pkce is not enforced, but rs is requesting it!");
             }
             // CodeChallengeMethod must be S256
@@ -608,7 +1045,7 @@ impl Oauth2ResourceServersReadTransaction {
                 return Err(Oauth2Error::InvalidRequest);
             }
             Some(pkce_request.code_challenge.clone())
-        } else if o2rs.enable_pkce {
+        } else if o2rs.type_.enable_pkce() {
             security_error!(?o2rs.name, "No PKCE code challenge was provided with client in enforced PKCE mode.");
             return Err(Oauth2Error::InvalidRequest);
         } else {
@@ -728,9 +1165,11 @@ impl Oauth2ResourceServersReadTransaction {
             .chain(req_scopes.into_iter())
             .collect();
 
+        // If the user previously consented to the same or a greater set of scopes, we
+        // do not need to ask again.
         let consent_previously_granted =
             if let Some(consent_scopes) = ident.get_oauth2_consent_scopes(o2rs.uuid) {
-                granted_scopes.eq(consent_scopes)
+                granted_scopes.is_subset(consent_scopes)
             } else {
                 false
             };
@@ -816,6 +1255,7 @@ impl Oauth2ResourceServersReadTransaction {
 
             Ok(AuthoriseResponse::ConsentRequested {
                 client_name: o2rs.displayname.clone(),
+                client_image: o2rs.image.clone(),
                 scopes: granted_scopes.into_iter().collect(),
                 pii_scopes,
                 consent_token,
@@ -958,16 +1398,19 @@ impl Oauth2ResourceServersReadTransaction {
 
     pub fn check_oauth2_token_exchange(
         &self,
+        idms: &IdmServerProxyReadTransaction<'_>,
         client_authz: Option<&str>,
         token_req: &AccessTokenRequest,
         ct: Duration,
         async_tx: &Sender<DelayedAction>,
     ) -> Result<AccessTokenResponse, Oauth2Error> {
         let (client_id, secret) = if let Some(client_authz) = client_authz {
-            parse_basic_authz(client_authz)?
+            let (client_id, secret) = parse_basic_authz(client_authz)?;
+            (client_id, Some(secret))
         } else {
+            // Public clients only identify themselves by client_id in the post data.
             match (&token_req.client_id, &token_req.client_secret) {
-                (Some(a), Some(b)) => (a.clone(), b.clone()),
+                (Some(a), b) => (a.clone(), b.clone()),
                 _ => {
                     security_info!(
                         "Invalid oauth2 authentication - no basic auth or missing auth post data"
@@ -984,27 +1427,67 @@ impl Oauth2ResourceServersReadTransaction {
         })?;
 
         // check the secret.
-        if o2rs.authz_secret != secret {
-            security_info!("Invalid oauth2 client_id secret");
-            return Err(Oauth2Error::AuthenticationRequired);
-        }
+        o2rs.type_.authenticate(secret.as_deref())?;
 
         // We are authenticated! Yay! Now we can actually check things ...
-
-        // TODO: add refresh token grant type.
-        //  If it's a refresh token grant, are the consent permissions the same?
-        if token_req.grant_type == "authorization_code" {
-            self.check_oauth2_token_exchange_authorization_code(o2rs, token_req, ct, async_tx)
-        } else {
-            admin_warn!("Invalid oauth2 grant_type (should be 'authorization_code')");
-            return Err(Oauth2Error::InvalidRequest);
+        match &token_req.grant_type {
+            GrantTypeReq::AuthorizationCode {
+                code,
+                redirect_uri,
+                code_verifier,
+            } => self.check_oauth2_token_exchange_authorization_code(
+                o2rs,
+                code,
+                redirect_uri,
+                code_verifier.as_deref(),
+                ct,
+                async_tx,
+            ),
+            GrantTypeReq::RefreshToken {
+                refresh_token,
+                scope,
+            } => self.check_oauth2_token_refresh(idms, o2rs, refresh_token, scope.as_deref(), ct),
+            GrantTypeReq::TokenExchange {
+                subject_token,
+                subject_token_type,
+                requested_token_type,
+                audience,
+                scope,
+            } => {
+                if subject_token_type != TOKEN_TYPE_ACCESS_TOKEN
+                    || requested_token_type
+                        .as_deref()
+                        .map(|t| t != TOKEN_TYPE_ACCESS_TOKEN)
+                        .unwrap_or(false)
+                {
+                    admin_warn!("Invalid token exchange - only access tokens may be exchanged");
+                    return Err(Oauth2Error::InvalidRequest);
+                }
+
+                let audience = audience.as_deref().ok_or_else(|| {
+                    admin_warn!("Invalid token exchange - an audience is required");
+                    Oauth2Error::InvalidTarget
+                })?;
+
+                self.check_oauth2_token_exchange_delegation(
+                    idms,
+                    o2rs,
+                    subject_token,
+                    audience,
+                    scope.as_deref(),
+                    ct,
+                    async_tx,
+                )
+            }
         }
     }
 
     fn check_oauth2_token_exchange_authorization_code(
         &self,
         o2rs: &Oauth2RS,
-        token_req: &AccessTokenRequest,
+        token_req_code: &str,
+        token_req_redirect: &Url,
+        token_req_code_verifier: Option<&str>,
         ct: Duration,
         async_tx: &Sender<DelayedAction>,
     ) -> Result<AccessTokenResponse, Oauth2Error> {
@@ -1013,7 +1496,7 @@ impl Oauth2ResourceServersReadTransaction {
 
         let code_xchg: TokenExchangeCode = o2rs
             .token_fernet
-            .decrypt_at_time(&token_req.code, Some(60), ct.as_secs())
+            .decrypt_at_time(token_req_code, Some(60), ct.as_secs())
             .map_err(|_| {
                 admin_error!("Failed to decrypt token exchange request");
                 Oauth2Error::InvalidRequest
@@ -1030,8 +1513,7 @@ impl Oauth2ResourceServersReadTransaction {
         // a short validity period. The client controlled value is in token_req.code_verifier
         if let Some(code_challenge) = code_xchg.code_challenge {
             // Validate the code_verifier
-            let code_verifier = token_req.code_verifier
-                    .as_deref()
+            let code_verifier = token_req_code_verifier
                     .ok_or_else(|| {
                         security_info!("PKCE code verification failed - code challenge is present, but not verifier was provided");
                         Oauth2Error::InvalidRequest
@@ -1046,12 +1528,12 @@ impl Oauth2ResourceServersReadTransaction {
                 );
                 return Err(Oauth2Error::InvalidRequest);
             }
-        } else if o2rs.enable_pkce {
+        } else if o2rs.type_.enable_pkce() {
             security_info!(
                 "PKCE code verification failed - no code challenge present in PKCE enforced mode"
             );
             return Err(Oauth2Error::InvalidRequest);
-        } else if token_req.code_verifier.is_some() {
+        } else if token_req_code_verifier.is_some() {
             security_info!(
                 "PKCE code verification failed - a code verifier is present, but no code challenge in exchange"
             );
@@ -1059,7 +1541,7 @@ impl Oauth2ResourceServersReadTransaction {
         }
 
         // Validate the redirect_uri is the same as the original.
-        if token_req.redirect_uri != code_xchg.redirect_uri {
+        if token_req_redirect != &code_xchg.redirect_uri {
             security_info!("Invalid oauth2 redirect_uri (differs from original request uri)");
             return Err(Oauth2Error::InvalidOrigin);
         }
@@ -1071,32 +1553,33 @@ impl Oauth2ResourceServersReadTransaction {
 
         let iat = ct.as_secs() as i64;
 
-        // TODO: Make configurable from auth policy!
-        let (expiry, expires_in) = if let Some(expiry) = code_xchg.uat.expiry {
-            if expiry > odt_ct {
-                // Becomes a duration.
-                (expiry, (expiry - odt_ct).whole_seconds() as u32)
-            } else {
+        if let Some(uat_expiry) = code_xchg.uat.expiry {
+            if uat_expiry <= odt_ct {
                 security_info!(
                     "User Auth Token has expired before we could publish the oauth2 response"
                 );
                 return Err(Oauth2Error::AccessDenied);
             }
-        } else {
-            security_info!("User Auth Token has no expiry, setting to refresh window");
-            (
-                odt_ct + Duration::from_secs(OAUTH2_ACCESS_TOKEN_EXPIRY as u64),
-                OAUTH2_ACCESS_TOKEN_EXPIRY,
-            )
-        };
-        // let expiry = odt_ct + Duration::from_secs(expires_in as u64);
+        }
 
-        let scope = if code_xchg.scopes.is_empty() {
+        // Unless this rs allows it, the oauth2 session may not outlive the uat.
+        let parent_expiry = if o2rs.session_outlive_parent {
             None
         } else {
-            Some(code_xchg.scopes.join(" "))
+            code_xchg.uat.expiry
         };
 
+        // The session lasts as long as refresh tokens may be used, else only as long
+        // as the access token.
+        let session_expiry = token_expiry(
+            odt_ct,
+            o2rs.refresh_token_expiry
+                .unwrap_or(o2rs.access_token_expiry),
+            parent_expiry,
+        );
+
+        let (expiry, expires_in) = token_expiry(odt_ct, o2rs.access_token_expiry, parent_expiry);
+
         let scope_set: BTreeSet<String> = code_xchg.scopes.iter().cloned().collect();
 
         let id_token = if scope_set.contains("openid") {
@@ -1120,7 +1603,7 @@ impl Oauth2ResourceServersReadTransaction {
             };
 
             let (email, email_verified) = if scope_set.contains("email") {
-                if let Some(mp) = code_xchg.uat.mail_primary {
+                if let Some(mp) = code_xchg.uat.mail_primary.clone() {
                     (Some(mp), Some(true))
                 } else {
                     (None, None)
@@ -1134,7 +1617,6 @@ impl Oauth2ResourceServersReadTransaction {
             // amr == auth method
             let amr = Some(vec![code_xchg.uat.auth_type.to_string()]);
 
-            // TODO: Make configurable from auth policy!
             let exp = iat + (expires_in as i64);
 
             let iss = o2rs.iss.clone();
@@ -1183,53 +1665,314 @@ impl Oauth2ResourceServersReadTransaction {
         let session_id = Uuid::new_v4();
         let parent_session_id = code_xchg.uat.session_id;
 
-        // We need to record this into the record? Delayed action?
+        let mut access_token_response = o2rs.generate_access_token_response(
+            code_xchg.scopes,
+            code_xchg.uat.uuid,
+            code_xchg.uat.auth_type,
+            parent_session_id,
+            session_id,
+            (expiry, expires_in),
+            session_expiry.0,
+            ct,
+        )?;
+        access_token_response.id_token = id_token;
+
+        async_tx
+            .send(DelayedAction::Oauth2SessionRecord(Oauth2SessionRecord {
+                target_uuid: code_xchg.uat.uuid,
+                parent_session_id,
+                session_id,
+                expiry: Some(session_expiry.0),
+                issued_at: odt_ct,
+                rs_uuid: o2rs.uuid,
+                delegated_from: None,
+            }))
+            .map_err(|e| {
+                admin_error!(err = ?e, "Unable to submit oauth2 session record");
+                Oauth2Error::ServerError(OperationError::InvalidState)
+            })?;
+
+        Ok(access_token_response)
+    }
 
-        let access_token_raw = Oauth2TokenType::Access {
-            scopes: code_xchg.scopes,
+    fn check_oauth2_token_refresh(
+        &self,
+        idms: &IdmServerProxyReadTransaction<'_>,
+        o2rs: &Oauth2RS,
+        refresh_token: &str,
+        req_scopes: Option<&str>,
+        ct: Duration,
+    ) -> Result<AccessTokenResponse, Oauth2Error> {
+        if o2rs.refresh_token_expiry.is_none() {
+            admin_warn!("Refresh token grant requested, but refresh tokens are not enabled for this resource server");
+            return Err(Oauth2Error::UnauthorizedClient);
+        }
+
+        let token: Oauth2TokenType = o2rs
+            .token_fernet
+            .decrypt(refresh_token)
+            .map_err(|_| {
+                admin_error!("Failed to decrypt refresh token");
+                Oauth2Error::InvalidGrant
+            })
+            .and_then(|data| {
+                serde_json::from_slice(&data).map_err(|e| {
+                    admin_error!("Failed to deserialise refresh token - {:?}", e);
+                    Oauth2Error::InvalidGrant
+                })
+            })?;
+
+        let (scopes, parent_session_id, session_id, auth_type, expiry, uuid, iat) = match token {
+            Oauth2TokenType::Refresh {
+                scopes,
+                parent_session_id,
+                session_id,
+                auth_type,
+                expiry,
+                uuid,
+                iat,
+            } => (
+                scopes,
+                parent_session_id,
+                session_id,
+                auth_type,
+                expiry,
+                uuid,
+                iat,
+            ),
+            Oauth2TokenType::Access { .. } => {
+                security_info!("An access token was presented as a refresh token");
+                return Err(Oauth2Error::InvalidGrant);
+            }
+        };
+
+        // Has this refresh token expired?
+        let odt_ct = OffsetDateTime::unix_epoch() + ct;
+        if expiry <= odt_ct {
+            security_info!(?uuid, "refresh token has expired");
+            return Err(Oauth2Error::InvalidGrant);
+        }
+
+        // Is the user expired, or the oauth2 session invalid?
+        let valid = idms
+            .check_oauth2_account_uuid_valid(
+                uuid,
+                session_id,
+                parent_session_id,
+                o2rs.session_outlive_parent,
+                iat,
+                ct,
+            )
+            .map_err(|_| admin_error!("Account is not valid"));
+
+        if !matches!(valid, Ok(Some(_))) {
+            security_info!(?uuid, "refresh token has account not valid");
+            return Err(Oauth2Error::InvalidGrant);
+        }
+
+        // The requested scopes may only narrow what was originally granted.
+        let scopes = if let Some(req_scopes) = req_scopes {
+            let req_scopes: Vec<String> = req_scopes
+                .split_ascii_whitespace()
+                .map(str::to_string)
+                .collect();
+            if !req_scopes.iter().all(|s| scopes.contains(s)) {
+                security_info!("Refresh token request scopes exceed those originally granted");
+                return Err(Oauth2Error::InvalidScope);
+            }
+            req_scopes
+        } else {
+            scopes
+        };
+
+        // Refreshed access tokens never outlive the refresh token, which is itself bound
+        // to the parent session as required. The session expiry is not extended.
+        let access_expiry = token_expiry(odt_ct, o2rs.access_token_expiry, Some(expiry));
+
+        o2rs.generate_access_token_response(
+            scopes,
+            uuid,
+            auth_type,
             parent_session_id,
             session_id,
-            auth_type: code_xchg.uat.auth_type,
+            access_expiry,
             expiry,
-            uuid: code_xchg.uat.uuid,
-            iat,
-            nbf: iat,
-            auth_time: None,
-        };
+            ct,
+        )
+    }
 
-        let access_token_data = serde_json::to_vec(&access_token_raw).map_err(|e| {
-            admin_error!(err = ?e, "Unable to encode consent data");
-            Oauth2Error::ServerError(OperationError::SerdeJsonError)
+    #[allow(clippy::too_many_arguments)]
+    fn check_oauth2_token_exchange_delegation(
+        &self,
+        idms: &IdmServerProxyReadTransaction<'_>,
+        o2rs: &Oauth2RS,
+        subject_token: &str,
+        audience: &str,
+        req_scopes: Option<&str>,
+        ct: Duration,
+        async_tx: &Sender<DelayedAction>,
+    ) -> Result<AccessTokenResponse, Oauth2Error> {
+        // Is the requesting rs allowed to delegate to the audience?
+        let target_rs = self.inner.rs_set.get(audience).ok_or_else(|| {
+            admin_warn!("Invalid token exchange - no such audience {}", audience);
+            Oauth2Error::InvalidTarget
         })?;
 
-        let access_token = o2rs
+        if !o2rs.token_exchange_audience.contains(&target_rs.uuid) {
+            security_info!(
+                rs = %o2rs.name,
+                audience = %target_rs.name,
+                "Invalid token exchange - resource server may not exchange tokens for this audience"
+            );
+            return Err(Oauth2Error::InvalidTarget);
+        }
+
+        // The subject token must be an access token issued to the requesting rs.
+        let token: Oauth2TokenType = o2rs
             .token_fernet
-            .encrypt_at_time(&access_token_data, ct.as_secs());
+            .decrypt(subject_token)
+            .map_err(|_| {
+                admin_error!("Failed to decrypt token exchange subject token");
+                Oauth2Error::InvalidGrant
+            })
+            .and_then(|data| {
+                serde_json::from_slice(&data).map_err(|e| {
+                    admin_error!("Failed to deserialise subject token - {:?}", e);
+                    Oauth2Error::InvalidGrant
+                })
+            })?;
+
+        let (parent_session_id, delegated_from, auth_type, expiry, uuid, iat) = match token {
+            Oauth2TokenType::Access {
+                parent_session_id,
+                session_id,
+                auth_type,
+                expiry,
+                uuid,
+                iat,
+                ..
+            } => (parent_session_id, session_id, auth_type, expiry, uuid, iat),
+            Oauth2TokenType::Refresh { .. } => {
+                security_info!("A refresh token was presented as a token exchange subject token");
+                return Err(Oauth2Error::InvalidGrant);
+            }
+        };
+
+        let odt_ct = OffsetDateTime::unix_epoch() + ct;
+        if expiry <= odt_ct {
+            security_info!(?uuid, "subject token has expired");
+            return Err(Oauth2Error::InvalidGrant);
+        }
+
+        let valid = idms
+            .check_oauth2_account_uuid_valid(
+                uuid,
+                delegated_from,
+                parent_session_id,
+                o2rs.session_outlive_parent,
+                iat,
+                ct,
+            )
+            .map_err(|_| admin_error!("Account is not valid"));
+
+        if !matches!(valid, Ok(Some(_))) {
+            security_info!(?uuid, "subject token has account not valid");
+            return Err(Oauth2Error::InvalidGrant);
+        }
+
+        // Scopes at the audience are determined by the account's membership, exactly as
+        // if the account had authorised to the audience directly.
+        let memberof = idms
+            .get_qs_txn()
+            .internal_search_uuid(&uuid)
+            .map(|entry| entry.get_ava_refer("memberof").cloned().unwrap_or_default())
+            .map_err(|e| {
+                admin_error!(?e, "Unable to retrieve account memberships");
+                Oauth2Error::ServerError(e)
+            })?;
+
+        let avail_scopes: BTreeSet<String> = target_rs
+            .scope_maps
+            .iter()
+            .filter(|(u, _)| memberof.contains(u))
+            .flat_map(|(_, m)| m.iter())
+            .cloned()
+            .collect();
 
-        let refresh_token = None;
+        if !target_rs.scope_maps.keys().any(|u| memberof.contains(u)) {
+            security_access!(
+                ?uuid,
+                rs = %target_rs.name,
+                "denied ❌ - account is not a member of any group in the audience scope map"
+            );
+            return Err(Oauth2Error::AccessDenied);
+        }
+
+        let req_scopes: BTreeSet<String> = if let Some(req_scopes) = req_scopes {
+            let req_scopes: BTreeSet<String> = req_scopes
+                .split_ascii_whitespace()
+                .map(str::to_string)
+                .collect();
+            if !req_scopes.is_subset(&avail_scopes) {
+                security_info!(
+                    requested_scopes = ?req_scopes,
+                    available_scopes = ?avail_scopes,
+                    "Token exchange requested scopes are not available at the audience"
+                );
+                return Err(Oauth2Error::InvalidScope);
+            }
+            req_scopes
+        } else {
+            avail_scopes
+        };
+
+        let scopes: Vec<String> = target_rs
+            .sup_scope_maps
+            .iter()
+            .filter(|(u, _)| memberof.contains(u))
+            .flat_map(|(_, m)| m.iter())
+            .cloned()
+            .chain(req_scopes.into_iter())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+
+        // The derived token may never outlive the token it was exchanged for.
+        let (access_expiry, expires_in) =
+            token_expiry(odt_ct, target_rs.access_token_expiry, Some(expiry));
+
+        let session_id = Uuid::new_v4();
+
+        let mut access_token_response = target_rs.generate_access_token_response(
+            scopes,
+            uuid,
+            auth_type,
+            parent_session_id,
+            session_id,
+            (access_expiry, expires_in),
+            access_expiry,
+            ct,
+        )?;
+        // Derived tokens are not refreshable - the delegating rs exchanges again instead.
+        access_token_response.refresh_token = None;
+        access_token_response.issued_token_type = Some(TOKEN_TYPE_ACCESS_TOKEN.to_string());
 
         async_tx
             .send(DelayedAction::Oauth2SessionRecord(Oauth2SessionRecord {
-                target_uuid: code_xchg.uat.uuid,
+                target_uuid: uuid,
                 parent_session_id,
                 session_id,
-                expiry: Some(expiry),
+                expiry: Some(access_expiry),
                 issued_at: odt_ct,
-                rs_uuid: o2rs.uuid,
+                rs_uuid: target_rs.uuid,
+                delegated_from: Some(delegated_from),
             }))
             .map_err(|e| {
                 admin_error!(err = ?e, "Unable to submit oauth2 session record");
                 Oauth2Error::ServerError(OperationError::InvalidState)
             })?;
 
-        Ok(AccessTokenResponse {
-            access_token,
-            token_type: "bearer".to_string(),
-            expires_in,
-            refresh_token,
-            scope,
-            id_token,
-        })
+        Ok(access_token_response)
     }
 
     pub fn check_oauth2_token_introspect(
@@ -1248,10 +1991,7 @@ impl Oauth2ResourceServersReadTransaction {
         })?;
 
         // check the secret.
-        if o2rs.authz_secret != secret {
-            security_info!("Invalid oauth2 client_id secret");
-            return Err(Oauth2Error::AuthenticationRequired);
-        }
+        o2rs.type_.authenticate(Some(&secret))?;
         // We are authenticated! Yay! Now we can actually check things ...
 
         let token: Oauth2TokenType = o2rs
@@ -1290,7 +2030,14 @@ impl Oauth2ResourceServersReadTransaction {
 
                 // Is the user expired, or the oauth2 session invalid?
                 let valid = idms
-                    .check_oauth2_account_uuid_valid(uuid, session_id, parent_session_id, iat, ct)
+                    .check_oauth2_account_uuid_valid(
+                        uuid,
+                        session_id,
+                        parent_session_id,
+                        o2rs.session_outlive_parent,
+                        iat,
+                        ct,
+                    )
                     .map_err(|_| admin_error!("Account is not valid"));
 
                 let account = match valid {
@@ -1382,7 +2129,14 @@ impl Oauth2ResourceServersReadTransaction {
 
                 // Is the user expired, or the oauth2 session invalid?
                 let valid = idms
-                    .check_oauth2_account_uuid_valid(uuid, session_id, parent_session_id, iat, ct)
+                    .check_oauth2_account_uuid_valid(
+                        uuid,
+                        session_id,
+                        parent_session_id,
+                        o2rs.session_outlive_parent,
+                        iat,
+                        ct,
+                    )
                     .map_err(|_| admin_error!("Account is not valid"));
 
                 let account = match valid {
@@ -1469,23 +2223,29 @@ impl Oauth2ResourceServersReadTransaction {
         let scopes_supported = Some(o2rs.scopes_supported.clone());
         let response_types_supported = vec![ResponseType::Code];
         let response_modes_supported = vec![ResponseMode::Query];
-        let grant_types_supported = vec![GrantType::AuthorisationCode];
+        let mut grant_types_supported = vec![GrantType::AuthorisationCode];
+        if o2rs.refresh_token_expiry.is_some() {
+            grant_types_supported.push(GrantType::RefreshToken);
+        }
+        if !o2rs.token_exchange_audience.is_empty() {
+            grant_types_supported.push(GrantType::TokenExchange);
+        }
         let subject_types_supported = vec![SubjectType::Public];
 
         let id_token_signing_alg_values_supported = match &o2rs.jws_signer {
             JwsSigner::ES256 { .. } => vec![IdTokenSignAlg::ES256],
             JwsSigner::RS256 { .. } => vec![IdTokenSignAlg::RS256],
-            JwsSigner::HS256 { .. } => {
-                admin_warn!("Invalid oauth2 configuration - HS256 is not supported!");
-                vec![]
-            }
+            JwsSigner::HS256 { .. } => vec![IdTokenSignAlg::HS256],
         };
 
         let userinfo_signing_alg_values_supported = None;
-        let token_endpoint_auth_methods_supported = vec![
-            TokenEndpointAuthMethod::ClientSecretBasic,
-            TokenEndpointAuthMethod::ClientSecretPost,
-        ];
+        let token_endpoint_auth_methods_supported = match &o2rs.type_ {
+            OauthRSType::Basic { .. } => vec![
+                TokenEndpointAuthMethod::ClientSecretBasic,
+                TokenEndpointAuthMethod::ClientSecretPost,
+            ],
+            OauthRSType::Public { .. } => vec![TokenEndpointAuthMethod::None],
+        };
         let display_values_supported = Some(vec![DisplayValue::Page]);
         let claim_types_supported = vec![ClaimType::Normal];
         // What claims can we offer?
@@ -1529,6 +2289,8 @@ impl Oauth2ResourceServersReadTransaction {
             require_request_uri_registration: false,
             op_policy_uri: None,
             op_tos_uri: None,
+            pushed_authorization_request_endpoint: Some(o2rs.par_endpoint.clone()),
+            require_pushed_authorization_requests: false,
         })
     }
 
@@ -1540,6 +2302,11 @@ impl Oauth2ResourceServersReadTransaction {
             OperationError::NoMatchingEntries
         })?;
 
+        // hs256 is symmetric and keyed by the client secret - there is no public key.
+        if matches!(o2rs.jws_signer, JwsSigner::HS256 { .. }) {
+            return Ok(JwkKeySet { keys: Vec::new() });
+        }
+
         o2rs.jws_signer
             .public_key_as_jwk()
             .map_err(|e| {
@@ -1550,6 +2317,40 @@ impl Oauth2ResourceServersReadTransaction {
     }
 }
 
+fn is_loopback_url(url: &Url) -> bool {
+    match url.host() {
+        Some(Host::Domain(domain)) => domain == "localhost",
+        Some(Host::Ipv4(addr)) => addr.is_loopback(),
+        Some(Host::Ipv6(addr)) => addr.is_loopback(),
+        None => false,
+    }
+}
+
+fn redirect_uri_exact_match(configured: &Url, requested: &Url) -> bool {
+    if is_loopback_url(configured) && is_loopback_url(requested) {
+        configured.scheme() == requested.scheme()
+            && configured.host() == requested.host()
+            && configured.path() == requested.path()
+            && configured.query() == requested.query()
+    } else {
+        configured == requested
+    }
+}
+
+/// The expiry of a token issued at `odt_ct` that is valid for `ttl` seconds, limited
+/// by `bound` if present. Returns the expiry and the number of seconds until it.
+fn token_expiry(
+    odt_ct: OffsetDateTime,
+    ttl: u32,
+    bound: Option<OffsetDateTime>,
+) -> (OffsetDateTime, u32) {
+    let expiry = odt_ct + Duration::from_secs(ttl as u64);
+    match bound {
+        Some(bound) if bound < expiry => (bound, (bound - odt_ct).whole_seconds() as u32),
+        _ => (expiry, ttl),
+    }
+}
+
 fn parse_basic_authz(client_authz: &str) -> Result<(String, String), Oauth2Error> {
     // Check the client_authz
     let authz = base64::decode(&client_authz)
@@ -1587,14 +2388,17 @@ mod tests {
     use std::time::Duration;
 
     use base64urlsafedata::Base64UrlSafeData;
-    use compact_jwt::{JwaAlg, Jwk, JwkUse, JwsValidator, OidcSubject, OidcUnverified};
+    use compact_jwt::{JwaAlg, Jwk, JwkUse, JwsSigner, JwsValidator, OidcSubject, OidcUnverified};
     use kanidm_proto::oauth2::*;
     use kanidm_proto::v1::{AuthType, UserAuthToken};
     use openssl::sha;
 
     use crate::event::{CreateEvent, DeleteEvent, ModifyEvent};
-    use crate::idm::delayed::DelayedAction;
-    use crate::idm::oauth2::{AuthoriseResponse, Oauth2Error};
+    use crate::idm::delayed::{DelayedAction, Oauth2ConsentGrant, Oauth2SessionRecord};
+    use crate::idm::oauth2::{
+        AuthoriseResponse, Oauth2Error, PUSHED_AUTHORISATION_EXPIRY,
+        PUSHED_AUTHORISATION_URI_PREFIX, TOKEN_TYPE_ACCESS_TOKEN,
+    };
     use crate::idm::server::{IdmServer, IdmServerTransaction};
     use crate::prelude::*;
 
@@ -1732,6 +2536,53 @@ mod tests {
         (secret, uat, ident, uuid)
     }
 
+    fn setup_oauth2_resource_server_public(
+        idms: &IdmServer,
+        ct: Duration,
+        redirect_uri: &str,
+    ) -> (UserAuthToken, Identity, Uuid) {
+        let mut idms_prox_write = task::block_on(idms.proxy_write(ct));
+
+        let uuid = Uuid::new_v4();
+
+        let e: Entry<EntryInit, EntryNew> = entry_init!(
+            ("class", Value::new_class("object")),
+            ("class", Value::new_class("oauth2_resource_server")),
+            ("class", Value::new_class("oauth2_resource_server_public")),
+            ("uuid", Value::new_uuid(uuid)),
+            ("oauth2_rs_name", Value::new_iname("test_public_client")),
+            ("displayname", Value::new_utf8s("test_public_client")),
+            ("oauth2_rs_origin", Value::new_url_s(redirect_uri).unwrap()),
+            (
+                "oauth2_rs_scope_map",
+                Value::new_oauthscopemap(UUID_IDM_ALL_ACCOUNTS, btreeset!["openid".to_string()])
+                    .expect("invalid oauthscope")
+            )
+        );
+        let ce = CreateEvent::new_internal(vec![e]);
+        assert!(idms_prox_write.qs_write.create(&ce).is_ok());
+
+        let account = idms_prox_write
+            .target_to_account(&UUID_ADMIN)
+            .expect("account must exist");
+        let session_id = uuid::Uuid::new_v4();
+        let uat = account
+            .to_userauthtoken(
+                session_id,
+                ct,
+                AuthType::PasswordMfa,
+                Some(AUTH_SESSION_EXPIRY),
+            )
+            .expect("Unable to create uat");
+        let ident = idms_prox_write
+            .process_uat_to_identity(&uat, ct)
+            .expect("Unable to process uat");
+
+        idms_prox_write.commit().expect("failed to commit");
+
+        (uat, ident, uuid)
+    }
+
     fn setup_idm_admin(
         idms: &IdmServer,
         ct: Duration,
@@ -1754,6 +2605,178 @@ mod tests {
         (uat, ident)
     }
 
+    #[test]
+    fn test_idm_oauth2_pushed_authorisation_request() {
+        run_idm_test!(|_qs: &QueryServer,
+                       idms: &IdmServer,
+                       _idms_delayed: &mut IdmServerDelayed| {
+            let ct = Duration::from_secs(TEST_CURRENT_TIME);
+            let (secret, uat, ident, _) =
+                setup_oauth2_resource_server(idms, ct, true, false, false);
+            let client_authz = Some(base64::encode(format!("test_resource_server:{}", secret)));
+
+            let idms_prox_read = task::block_on(idms.proxy_read());
+
+            let (_code_verifier, code_challenge) = create_code_verifier!("Whar Garble");
+
+            let par_req = PushedAuthorisationRequest {
+                auth_req: AuthorisationRequest {
+                    response_type: "code".to_string(),
+                    client_id: "test_resource_server".to_string(),
+                    state: "123".to_string(),
+                    pkce_request: Some(PkceRequest {
+                        code_challenge: Base64UrlSafeData(code_challenge),
+                        code_challenge_method: CodeChallengeMethod::S256,
+                    }),
+                    redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+                    scope: "openid".to_string(),
+                    nonce: Some("abcdef".to_string()),
+                    oidc_ext: Default::default(),
+                    unknown_keys: Default::default(),
+                },
+                client_secret: None,
+            };
+
+            // Pushing requires client authentication.
+            assert!(
+                idms_prox_read
+                    .check_oauth2_pushed_authorisation(
+                        Some(&base64::encode("test_resource_server:12345")),
+                        &par_req,
+                        ct
+                    )
+                    .unwrap_err()
+                    == Oauth2Error::AuthenticationRequired
+            );
+            assert!(
+                idms_prox_read
+                    .check_oauth2_pushed_authorisation(None, &par_req, ct)
+                    .unwrap_err()
+                    == Oauth2Error::AuthenticationRequired
+            );
+
+            let par_resp = idms_prox_read
+                .check_oauth2_pushed_authorisation(client_authz.as_deref(), &par_req, ct)
+                .expect("Failed to push authorisation request");
+            assert!(par_resp
+                .request_uri
+                .starts_with(PUSHED_AUTHORISATION_URI_PREFIX));
+            assert!(par_resp.expires_in == PUSHED_AUTHORISATION_EXPIRY);
+
+            let auth_ref = AuthorisationRequestReference {
+                client_id: "test_resource_server".to_string(),
+                request_uri: par_resp.request_uri.clone(),
+            };
+
+            // The reference resolves to the pushed request, which can then be authorised.
+            let auth_req = idms_prox_read
+                .check_oauth2_pushed_authorisation_reference(&auth_ref, ct)
+                .expect("Failed to resolve pushed authorisation request");
+            assert!(auth_req.state == "123");
+
+            let consent_request = idms_prox_read
+                .check_oauth2_authorisation(&ident, &uat, &auth_req, ct)
+                .expect("Oauth2 authorisation failed");
+            assert!(matches!(
+                consent_request,
+                AuthoriseResponse::ConsentRequested { .. }
+            ));
+
+            // References expire.
+            assert!(
+                idms_prox_read
+                    .check_oauth2_pushed_authorisation_reference(
+                        &auth_ref,
+                        ct + Duration::from_secs(PUSHED_AUTHORISATION_EXPIRY as u64 + 1)
+                    )
+                    .unwrap_err()
+                    == Oauth2Error::InvalidRequest
+            );
+
+            // Only request uris we issued are accepted.
+            let bad_ref = AuthorisationRequestReference {
+                client_id: "test_resource_server".to_string(),
+                request_uri: "https://demo.example.com/request.jwt".to_string(),
+            };
+            assert!(
+                idms_prox_read
+                    .check_oauth2_pushed_authorisation_reference(&bad_ref, ct)
+                    .unwrap_err()
+                    == Oauth2Error::InvalidRequest
+            );
+
+            // A pushed request may not carry a request_uri itself.
+            let mut nested_par_req = par_req;
+            nested_par_req.auth_req.unknown_keys.insert(
+                "request_uri".to_string(),
+                serde_json::Value::String(par_resp.request_uri),
+            );
+            assert!(
+                idms_prox_read
+                    .check_oauth2_pushed_authorisation(client_authz.as_deref(), &nested_par_req, ct)
+                    .unwrap_err()
+                    == Oauth2Error::InvalidRequest
+            );
+        })
+    }
+
+    #[test]
+    fn test_idm_oauth2_authorisation_requires_scope_map_membership() {
+        run_idm_test!(|_qs: &QueryServer,
+                       idms: &IdmServer,
+                       _idms_delayed: &mut IdmServerDelayed| {
+            let ct = Duration::from_secs(TEST_CURRENT_TIME);
+            let (_secret, uat, ident, rs_uuid) =
+                setup_oauth2_resource_server(idms, ct, true, false, false);
+            let (idm_admin_uat, idm_admin_ident) = setup_idm_admin(idms, ct, AuthType::PasswordMfa);
+
+            // Limit the resource server to system admins only.
+            let mut idms_prox_write = task::block_on(idms.proxy_write(ct));
+            let me_scope = unsafe {
+                ModifyEvent::new_internal_invalid(
+                    filter!(f_eq("uuid", PartialValue::new_uuid(rs_uuid))),
+                    ModifyList::new_list(vec![Modify::Removed(
+                        AttrString::from("oauth2_rs_scope_map"),
+                        PartialValue::Refer(UUID_IDM_ALL_ACCOUNTS),
+                    )]),
+                )
+            };
+            assert!(idms_prox_write.qs_write.modify(&me_scope).is_ok());
+            assert!(idms_prox_write.commit().is_ok());
+
+            let idms_prox_read = task::block_on(idms.proxy_read());
+
+            // A non-member is denied even before the rest of the request is validated,
+            // so the missing pkce challenge is never reached.
+            let auth_req = AuthorisationRequest {
+                response_type: "code".to_string(),
+                client_id: "test_resource_server".to_string(),
+                state: "123".to_string(),
+                pkce_request: None,
+                redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+                scope: "read".to_string(),
+                nonce: None,
+                oidc_ext: Default::default(),
+                unknown_keys: Default::default(),
+            };
+
+            assert!(
+                idms_prox_read
+                    .check_oauth2_authorisation(&idm_admin_ident, &idm_admin_uat, &auth_req, ct)
+                    .unwrap_err()
+                    == Oauth2Error::AccessDenied
+            );
+
+            // Members continue through the normal validation.
+            assert!(
+                idms_prox_read
+                    .check_oauth2_authorisation(&ident, &uat, &auth_req, ct)
+                    .unwrap_err()
+                    == Oauth2Error::InvalidRequest
+            );
+        })
+    }
+
     #[test]
     fn test_idm_oauth2_basic_function() {
         run_idm_test!(
@@ -1799,13 +2822,14 @@ mod tests {
                 // == Submit the token exchange code.
 
                 let token_req = AccessTokenRequest {
-                    grant_type: "authorization_code".to_string(),
-                    code: permit_success.code,
-                    redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+                    grant_type: GrantTypeReq::AuthorizationCode {
+                        code: permit_success.code,
+                        redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+                        // From the first step.
+                        code_verifier,
+                    },
                     client_id: Some("test_resource_server".to_string()),
                     client_secret: Some(secret),
-                    // From the first step.
-                    code_verifier,
                 };
 
                 let token_response = idms_prox_read
@@ -2124,13 +3148,14 @@ mod tests {
                 // Invalid token exchange
                 //  * invalid client_authz (not base64)
                 let token_req = AccessTokenRequest {
-                    grant_type: "authorization_code".to_string(),
-                    code: permit_success.code.clone(),
-                    redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+                    grant_type: GrantTypeReq::AuthorizationCode {
+                        code: permit_success.code.clone(),
+                        redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+                        // From the first step.
+                        code_verifier: code_verifier.clone(),
+                    },
                     client_id: None,
                     client_secret: None,
-                    // From the first step.
-                    code_verifier: code_verifier.clone(),
                 };
 
                 assert!(
@@ -2194,30 +3219,22 @@ mod tests {
                         == Oauth2Error::AccessDenied
                 );
 
-                //  * incorrect grant_type
-                let token_req = AccessTokenRequest {
-                    grant_type: "INCORRECT GRANT TYPE".to_string(),
-                    code: permit_success.code.clone(),
-                    redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
-                    client_id: None,
-                    client_secret: None,
-                    code_verifier: code_verifier.clone(),
-                };
-                assert!(
-                    idms_prox_read
-                        .check_oauth2_token_exchange(client_authz.as_deref(), &token_req, ct)
-                        .unwrap_err()
-                        == Oauth2Error::InvalidRequest
-                );
+                //  * incorrect grant_type - this is now rejected as the request is parsed.
+                assert!(serde_json::from_str::<AccessTokenRequest>(
+                    r#"{"grant_type": "INCORRECT GRANT TYPE", "code": "abcd", "redirect_uri": "https://demo.example.com/oauth2/result"}"#
+                )
+                .is_err());
 
                 //  * Incorrect redirect uri
                 let token_req = AccessTokenRequest {
-                    grant_type: "authorization_code".to_string(),
-                    code: permit_success.code.clone(),
-                    redirect_uri: Url::parse("https://totes.not.sus.org/oauth2/result").unwrap(),
+                    grant_type: GrantTypeReq::AuthorizationCode {
+                        code: permit_success.code.clone(),
+                        redirect_uri: Url::parse("https://totes.not.sus.org/oauth2/result")
+                            .unwrap(),
+                        code_verifier,
+                    },
                     client_id: None,
                     client_secret: None,
-                    code_verifier,
                 };
                 assert!(
                     idms_prox_read
@@ -2228,12 +3245,13 @@ mod tests {
 
                 //  * code verifier incorrect
                 let token_req = AccessTokenRequest {
-                    grant_type: "authorization_code".to_string(),
-                    code: permit_success.code,
-                    redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+                    grant_type: GrantTypeReq::AuthorizationCode {
+                        code: permit_success.code,
+                        redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+                        code_verifier: Some("12345".to_string()),
+                    },
                     client_id: None,
                     client_secret: None,
-                    code_verifier: Some("12345".to_string()),
                 };
                 assert!(
                     idms_prox_read
@@ -2282,12 +3300,13 @@ mod tests {
                 }
 
                 let token_req = AccessTokenRequest {
-                    grant_type: "authorization_code".to_string(),
-                    code: permit_success.code.clone(),
-                    redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+                    grant_type: GrantTypeReq::AuthorizationCode {
+                        code: permit_success.code.clone(),
+                        redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+                        code_verifier,
+                    },
                     client_id: None,
                     client_secret: None,
-                    code_verifier,
                 };
                 let oauth2_token = idms_prox_read
                     .check_oauth2_token_exchange(client_authz.as_deref(), &token_req, ct)
@@ -2353,6 +3372,192 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_idm_oauth2_refresh_token() {
+        run_idm_test!(
+            |_qs: &QueryServer, idms: &IdmServer, idms_delayed: &mut IdmServerDelayed| {
+                let ct = Duration::from_secs(TEST_CURRENT_TIME);
+                let (secret, uat, ident, rs_uuid) =
+                    setup_oauth2_resource_server(idms, ct, true, false, false);
+                let client_authz = Some(base64::encode(format!("test_resource_server:{}", secret)));
+
+                // Configure short access tokens, and allow refresh.
+                let mut idms_prox_write = task::block_on(idms.proxy_write(ct));
+                let me_ttl = unsafe {
+                    ModifyEvent::new_internal_invalid(
+                        filter!(f_eq("uuid", PartialValue::new_uuid(rs_uuid))),
+                        ModifyList::new_list(vec![
+                            Modify::Present(
+                                AttrString::from("oauth2_access_token_expiry"),
+                                Value::new_uint32(300),
+                            ),
+                            Modify::Present(
+                                AttrString::from("oauth2_refresh_token_expiry"),
+                                Value::new_uint32(86400),
+                            ),
+                        ]),
+                    )
+                };
+                assert!(idms_prox_write.qs_write.modify(&me_ttl).is_ok());
+                assert!(idms_prox_write.commit().is_ok());
+
+                let idms_prox_read = task::block_on(idms.proxy_read());
+
+                let (code_verifier, code_challenge) = create_code_verifier!("Whar Garble");
+                let consent_request =
+                    good_authorisation_request!(idms_prox_read, &ident, &uat, ct, code_challenge);
+
+                let consent_token =
+                    if let AuthoriseResponse::ConsentRequested { consent_token, .. } =
+                        consent_request
+                    {
+                        consent_token
+                    } else {
+                        unreachable!();
+                    };
+
+                let permit_success = idms_prox_read
+                    .check_oauth2_authorise_permit(&ident, &uat, &consent_token, ct)
+                    .expect("Failed to perform oauth2 permit");
+
+                match idms_delayed.async_rx.blocking_recv() {
+                    Some(DelayedAction::Oauth2ConsentGrant(_)) => {}
+                    _ => assert!(false),
+                }
+
+                let token_req = AccessTokenRequest {
+                    grant_type: GrantTypeReq::AuthorizationCode {
+                        code: permit_success.code.clone(),
+                        redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+                        code_verifier,
+                    },
+                    client_id: None,
+                    client_secret: None,
+                };
+                let oauth2_token = idms_prox_read
+                    .check_oauth2_token_exchange(client_authz.as_deref(), &token_req, ct)
+                    .expect("Unable to exchange for oauth2 token");
+
+                // The session is bound to the uat, so it expires with it.
+                match idms_delayed.async_rx.blocking_recv() {
+                    Some(DelayedAction::Oauth2SessionRecord(osr)) => {
+                        assert!(osr.expiry == uat.expiry);
+                    }
+                    _ => assert!(false),
+                }
+
+                assert!(oauth2_token.expires_in == 300);
+                let refresh_token = oauth2_token
+                    .refresh_token
+                    .clone()
+                    .expect("No refresh token issued");
+
+                // Refresh the access token after it has expired.
+                let refresh_ct = ct + Duration::from_secs(400);
+                let refresh_req = AccessTokenRequest {
+                    grant_type: GrantTypeReq::RefreshToken {
+                        refresh_token: refresh_token.clone(),
+                        scope: None,
+                    },
+                    client_id: None,
+                    client_secret: None,
+                };
+                let refreshed_token = idms_prox_read
+                    .check_oauth2_token_exchange(client_authz.as_deref(), &refresh_req, refresh_ct)
+                    .expect("Unable to refresh oauth2 token");
+                assert!(refreshed_token.expires_in == 300);
+                assert!(refreshed_token.scope.as_deref() == Some("openid supplement"));
+
+                let intr_request = AccessTokenIntrospectRequest {
+                    token: refreshed_token.access_token.clone(),
+                    token_type_hint: None,
+                };
+                let intr_response = idms_prox_read
+                    .check_oauth2_token_introspect(
+                        client_authz.as_deref().unwrap(),
+                        &intr_request,
+                        refresh_ct,
+                    )
+                    .expect("Failed to inspect token");
+                assert!(intr_response.active);
+
+                // Scopes may be narrowed, but not widened.
+                let refresh_req = AccessTokenRequest {
+                    grant_type: GrantTypeReq::RefreshToken {
+                        refresh_token: refresh_token.clone(),
+                        scope: Some("openid".to_string()),
+                    },
+                    client_id: None,
+                    client_secret: None,
+                };
+                let narrowed_token = idms_prox_read
+                    .check_oauth2_token_exchange(client_authz.as_deref(), &refresh_req, refresh_ct)
+                    .expect("Unable to refresh oauth2 token");
+                assert!(narrowed_token.scope.as_deref() == Some("openid"));
+
+                let refresh_req = AccessTokenRequest {
+                    grant_type: GrantTypeReq::RefreshToken {
+                        refresh_token: refresh_token.clone(),
+                        scope: Some("openid read".to_string()),
+                    },
+                    client_id: None,
+                    client_secret: None,
+                };
+                assert!(
+                    idms_prox_read
+                        .check_oauth2_token_exchange(
+                            client_authz.as_deref(),
+                            &refresh_req,
+                            refresh_ct
+                        )
+                        .unwrap_err()
+                        == Oauth2Error::InvalidScope
+                );
+
+                // An access token is not a refresh token.
+                let refresh_req = AccessTokenRequest {
+                    grant_type: GrantTypeReq::RefreshToken {
+                        refresh_token: oauth2_token.access_token.clone(),
+                        scope: None,
+                    },
+                    client_id: None,
+                    client_secret: None,
+                };
+                assert!(
+                    idms_prox_read
+                        .check_oauth2_token_exchange(
+                            client_authz.as_deref(),
+                            &refresh_req,
+                            refresh_ct
+                        )
+                        .unwrap_err()
+                        == Oauth2Error::InvalidGrant
+                );
+
+                // Once the parent session has expired, refresh is denied.
+                let refresh_req = AccessTokenRequest {
+                    grant_type: GrantTypeReq::RefreshToken {
+                        refresh_token,
+                        scope: None,
+                    },
+                    client_id: None,
+                    client_secret: None,
+                };
+                let expired_ct = ct + Duration::from_secs(AUTH_SESSION_EXPIRY + 1);
+                assert!(
+                    idms_prox_read
+                        .check_oauth2_token_exchange(
+                            client_authz.as_deref(),
+                            &refresh_req,
+                            expired_ct
+                        )
+                        .unwrap_err()
+                        == Oauth2Error::InvalidGrant
+                );
+            }
+        )
+    }
+
     #[test]
     fn test_idm_oauth2_token_revoke() {
         run_idm_test!(
@@ -2391,12 +3596,13 @@ mod tests {
                 }
 
                 let token_req = AccessTokenRequest {
-                    grant_type: "authorization_code".to_string(),
-                    code: permit_success.code.clone(),
-                    redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+                    grant_type: GrantTypeReq::AuthorizationCode {
+                        code: permit_success.code.clone(),
+                        redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+                        code_verifier,
+                    },
                     client_id: None,
                     client_secret: None,
-                    code_verifier,
                 };
                 let oauth2_token = idms_prox_read
                     .check_oauth2_token_exchange(client_authz.as_deref(), &token_req, ct)
@@ -2528,6 +3734,267 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_idm_oauth2_token_exchange_delegation() {
+        run_idm_test!(
+            |_qs: &QueryServer, idms: &IdmServer, idms_delayed: &mut IdmServerDelayed| {
+                let ct = Duration::from_secs(TEST_CURRENT_TIME);
+                let (secret, uat, ident, rs_uuid) =
+                    setup_oauth2_resource_server(idms, ct, true, false, false);
+                let client_authz = Some(base64::encode(format!("test_resource_server:{}", secret)));
+
+                // Setup a downstream resource server, and allow the first rs to delegate to it.
+                let mut idms_prox_write = task::block_on(idms.proxy_write(ct));
+                let rs_b_uuid = Uuid::new_v4();
+                let e: Entry<EntryInit, EntryNew> = entry_init!(
+                    ("class", Value::new_class("object")),
+                    ("class", Value::new_class("oauth2_resource_server")),
+                    ("class", Value::new_class("oauth2_resource_server_basic")),
+                    ("uuid", Value::new_uuid(rs_b_uuid)),
+                    ("oauth2_rs_name", Value::new_iname("test_resource_server_b")),
+                    ("displayname", Value::new_utf8s("test_resource_server_b")),
+                    (
+                        "oauth2_rs_origin",
+                        Value::new_url_s("https://downstream.example.com").unwrap()
+                    ),
+                    (
+                        "oauth2_rs_scope_map",
+                        Value::new_oauthscopemap(
+                            UUID_IDM_ALL_ACCOUNTS,
+                            btreeset!["openid".to_string(), "email".to_string()]
+                        )
+                        .expect("invalid oauthscope")
+                    )
+                );
+                let ce = CreateEvent::new_internal(vec![e]);
+                assert!(idms_prox_write.qs_write.create(&ce).is_ok());
+
+                let secret_b = idms_prox_write
+                    .qs_write
+                    .internal_search_uuid(&rs_b_uuid)
+                    .expect("Failed to retrieve oauth2 resource entry")
+                    .get_ava_single_secret("oauth2_rs_basic_secret")
+                    .map(str::to_string)
+                    .expect("No oauth2_rs_basic_secret found");
+                let client_authz_b = Some(base64::encode(format!(
+                    "test_resource_server_b:{}",
+                    secret_b
+                )));
+
+                let me = unsafe {
+                    ModifyEvent::new_internal_invalid(
+                        filter!(f_eq("uuid", PartialValue::new_uuid(rs_uuid))),
+                        ModifyList::new_list(vec![
+                            Modify::Present(
+                                AttrString::from("oauth2_token_exchange_audience"),
+                                Value::new_refer(rs_b_uuid),
+                            ),
+                            Modify::Present(
+                                AttrString::from("oauth2_refresh_token_expiry"),
+                                Value::new_uint32(86400),
+                            ),
+                        ]),
+                    )
+                };
+                assert!(idms_prox_write.qs_write.modify(&me).is_ok());
+                assert!(idms_prox_write.commit().is_ok());
+
+                // Get a token for the first rs.
+                let idms_prox_read = task::block_on(idms.proxy_read());
+                let (code_verifier, code_challenge) = create_code_verifier!("Whar Garble");
+                let consent_request =
+                    good_authorisation_request!(idms_prox_read, &ident, &uat, ct, code_challenge);
+
+                let consent_token =
+                    if let AuthoriseResponse::ConsentRequested { consent_token, .. } =
+                        consent_request
+                    {
+                        consent_token
+                    } else {
+                        unreachable!();
+                    };
+
+                let permit_success = idms_prox_read
+                    .check_oauth2_authorise_permit(&ident, &uat, &consent_token, ct)
+                    .expect("Failed to perform oauth2 permit");
+
+                match idms_delayed.async_rx.blocking_recv() {
+                    Some(DelayedAction::Oauth2ConsentGrant(_)) => {}
+                    _ => assert!(false),
+                }
+
+                let token_req = AccessTokenRequest {
+                    grant_type: GrantTypeReq::AuthorizationCode {
+                        code: permit_success.code.clone(),
+                        redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+                        code_verifier,
+                    },
+                    client_id: None,
+                    client_secret: None,
+                };
+                let oauth2_token = idms_prox_read
+                    .check_oauth2_token_exchange(client_authz.as_deref(), &token_req, ct)
+                    .expect("Unable to exchange for oauth2 token");
+
+                let root_osr = match idms_delayed.async_rx.blocking_recv() {
+                    Some(DelayedAction::Oauth2SessionRecord(osr)) => osr,
+                    _ => unreachable!(),
+                };
+
+                // The discovery document advertises the token exchange grant.
+                let discovery = idms_prox_read
+                    .oauth2_openid_discovery("test_resource_server")
+                    .expect("Failed to get discovery");
+                assert!(discovery
+                    .grant_types_supported
+                    .contains(&GrantType::TokenExchange));
+
+                // Exchange the token for one at the downstream rs.
+                let exchange_req =
+                    |subject_token: &str, audience: &str, scope: Option<&str>| AccessTokenRequest {
+                        grant_type: GrantTypeReq::TokenExchange {
+                            subject_token: subject_token.to_string(),
+                            subject_token_type: TOKEN_TYPE_ACCESS_TOKEN.to_string(),
+                            requested_token_type: None,
+                            audience: Some(audience.to_string()),
+                            scope: scope.map(str::to_string),
+                        },
+                        client_id: None,
+                        client_secret: None,
+                    };
+
+                let delegated_token = idms_prox_read
+                    .check_oauth2_token_exchange(
+                        client_authz.as_deref(),
+                        &exchange_req(&oauth2_token.access_token, "test_resource_server_b", None),
+                        ct,
+                    )
+                    .expect("Unable to exchange for delegated token");
+
+                assert!(delegated_token.refresh_token.is_none());
+                assert!(delegated_token.scope.as_deref() == Some("email openid"));
+                assert!(
+                    delegated_token.issued_token_type.as_deref() == Some(TOKEN_TYPE_ACCESS_TOKEN)
+                );
+
+                let delegated_osr = match idms_delayed.async_rx.blocking_recv() {
+                    Some(DelayedAction::Oauth2SessionRecord(osr)) => osr,
+                    _ => unreachable!(),
+                };
+                assert!(delegated_osr.rs_uuid == rs_b_uuid);
+                assert!(delegated_osr.parent_session_id == root_osr.parent_session_id);
+                assert!(delegated_osr.delegated_from == Some(root_osr.session_id));
+
+                // Scopes at the downstream rs may be narrowed, but not widened.
+                let narrowed_token = idms_prox_read
+                    .check_oauth2_token_exchange(
+                        client_authz.as_deref(),
+                        &exchange_req(
+                            &oauth2_token.access_token,
+                            "test_resource_server_b",
+                            Some("openid"),
+                        ),
+                        ct,
+                    )
+                    .expect("Unable to exchange for delegated token");
+                assert!(narrowed_token.scope.as_deref() == Some("openid"));
+                match idms_delayed.async_rx.blocking_recv() {
+                    Some(DelayedAction::Oauth2SessionRecord(_)) => {}
+                    _ => assert!(false),
+                }
+
+                assert!(
+                    idms_prox_read.check_oauth2_token_exchange(
+                        client_authz.as_deref(),
+                        &exchange_req(
+                            &oauth2_token.access_token,
+                            "test_resource_server_b",
+                            Some("read"),
+                        ),
+                        ct,
+                    ) == Err(Oauth2Error::InvalidScope)
+                );
+
+                // An audience that is not configured is rejected.
+                assert!(
+                    idms_prox_read.check_oauth2_token_exchange(
+                        client_authz.as_deref(),
+                        &exchange_req(&oauth2_token.access_token, "test_resource_server", None),
+                        ct,
+                    ) == Err(Oauth2Error::InvalidTarget)
+                );
+
+                // The downstream rs may not exchange back, since it has no audience configured.
+                assert!(
+                    idms_prox_read.check_oauth2_token_exchange(
+                        client_authz_b.as_deref(),
+                        &exchange_req(&delegated_token.access_token, "test_resource_server", None),
+                        ct,
+                    ) == Err(Oauth2Error::InvalidTarget)
+                );
+
+                // Refresh tokens can not be exchanged.
+                let refresh_token = oauth2_token
+                    .refresh_token
+                    .as_deref()
+                    .expect("No refresh token issued");
+                assert!(
+                    idms_prox_read.check_oauth2_token_exchange(
+                        client_authz.as_deref(),
+                        &exchange_req(refresh_token, "test_resource_server_b", None),
+                        ct,
+                    ) == Err(Oauth2Error::InvalidGrant)
+                );
+
+                // The downstream rs can introspect the delegated token.
+                let intr_request = AccessTokenIntrospectRequest {
+                    token: delegated_token.access_token.clone(),
+                    token_type_hint: None,
+                };
+                let intr_response = idms_prox_read
+                    .check_oauth2_token_introspect(
+                        client_authz_b.as_deref().unwrap(),
+                        &intr_request,
+                        ct,
+                    )
+                    .expect("Failed to inspect token");
+                assert!(intr_response.active);
+                assert!(intr_response.client_id.as_deref() == Some("test_resource_server_b"));
+                drop(idms_prox_read);
+
+                // Record the sessions, then revoke the root token.
+                let mut idms_prox_write = task::block_on(idms.proxy_write(ct));
+                assert!(idms_prox_write
+                    .process_oauth2sessionrecord(&root_osr)
+                    .is_ok());
+                assert!(idms_prox_write
+                    .process_oauth2sessionrecord(&delegated_osr)
+                    .is_ok());
+                let revoke_request = TokenRevokeRequest {
+                    token: oauth2_token.access_token.clone(),
+                    token_type_hint: None,
+                };
+                assert!(idms_prox_write
+                    .oauth2_token_revoke(client_authz.as_deref().unwrap(), &revoke_request, ct)
+                    .is_ok());
+                assert!(idms_prox_write.commit().is_ok());
+
+                // After the grace window the delegated token is invalid, since the session
+                // it was derived from is gone.
+                let ct = ct + GRACE_WINDOW;
+                let idms_prox_read = task::block_on(idms.proxy_read());
+                let intr_response = idms_prox_read
+                    .check_oauth2_token_introspect(
+                        client_authz_b.as_deref().unwrap(),
+                        &intr_request,
+                        ct,
+                    )
+                    .expect("Failed to inspect token");
+                assert!(!intr_response.active);
+            }
+        )
+    }
+
     #[test]
     fn test_idm_oauth2_session_cleanup_post_rs_delete() {
         run_idm_test!(
@@ -2566,12 +4033,13 @@ mod tests {
                 }
 
                 let token_req = AccessTokenRequest {
-                    grant_type: "authorization_code".to_string(),
-                    code: permit_success.code.clone(),
-                    redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+                    grant_type: GrantTypeReq::AuthorizationCode {
+                        code: permit_success.code.clone(),
+                        redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+                        code_verifier,
+                    },
                     client_id: None,
                     client_secret: None,
-                    code_verifier,
                 };
                 let _oauth2_token = idms_prox_read
                     .check_oauth2_token_exchange(client_authz.as_deref(), &token_req, ct)
@@ -2898,13 +4366,14 @@ mod tests {
 
                 // == Submit the token exchange code.
                 let token_req = AccessTokenRequest {
-                    grant_type: "authorization_code".to_string(),
-                    code: permit_success.code,
-                    redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+                    grant_type: GrantTypeReq::AuthorizationCode {
+                        code: permit_success.code,
+                        redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+                        // From the first step.
+                        code_verifier,
+                    },
                     client_id: None,
                     client_secret: None,
-                    // From the first step.
-                    code_verifier,
                 };
 
                 let token_response = idms_prox_read
@@ -3030,13 +4499,14 @@ mod tests {
 
                 // == Submit the token exchange code.
                 let token_req = AccessTokenRequest {
-                    grant_type: "authorization_code".to_string(),
-                    code: permit_success.code,
-                    redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+                    grant_type: GrantTypeReq::AuthorizationCode {
+                        code: permit_success.code,
+                        redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+                        // From the first step.
+                        code_verifier,
+                    },
                     client_id: None,
                     client_secret: None,
-                    // From the first step.
-                    code_verifier,
                 };
 
                 let token_response = idms_prox_read
@@ -3183,13 +4653,14 @@ mod tests {
 
                 // == Submit the token exchange code.
                 let token_req = AccessTokenRequest {
-                    grant_type: "authorization_code".to_string(),
-                    code: permit_success.code,
-                    redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+                    grant_type: GrantTypeReq::AuthorizationCode {
+                        code: permit_success.code,
+                        redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+                        // From the first step.
+                        code_verifier,
+                    },
                     client_id: Some("test_resource_server".to_string()),
                     client_secret: Some(secret),
-                    // From the first step.
-                    code_verifier,
                 };
 
                 let token_response = idms_prox_read
@@ -3223,6 +4694,144 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_idm_oauth2_openid_sign_alg_selection() {
+        run_idm_test!(
+            |_qs: &QueryServer, idms: &IdmServer, idms_delayed: &mut IdmServerDelayed| {
+                let ct = Duration::from_secs(TEST_CURRENT_TIME);
+                let (secret, uat, ident, rs_uuid) =
+                    setup_oauth2_resource_server(idms, ct, false, false, false);
+
+                // An unknown algorithm is rejected when the config is reloaded.
+                let mut idms_prox_write = task::block_on(idms.proxy_write(ct));
+                let me_bad_alg = unsafe {
+                    ModifyEvent::new_internal_invalid(
+                        filter!(f_eq("uuid", PartialValue::new_uuid(rs_uuid))),
+                        ModifyList::new_purge_and_set(
+                            "oauth2_jwt_sign_alg",
+                            Value::new_iutf8("none"),
+                        ),
+                    )
+                };
+                assert!(idms_prox_write.qs_write.modify(&me_bad_alg).is_ok());
+                assert!(idms_prox_write.commit().is_err());
+
+                // rs256 can be selected without enabling legacy crypto.
+                let mut idms_prox_write = task::block_on(idms.proxy_write(ct));
+                let me_rs256 = unsafe {
+                    ModifyEvent::new_internal_invalid(
+                        filter!(f_eq("uuid", PartialValue::new_uuid(rs_uuid))),
+                        ModifyList::new_purge_and_set(
+                            "oauth2_jwt_sign_alg",
+                            Value::new_iutf8("rs256"),
+                        ),
+                    )
+                };
+                assert!(idms_prox_write.qs_write.modify(&me_rs256).is_ok());
+                assert!(idms_prox_write.commit().is_ok());
+
+                let idms_prox_read = task::block_on(idms.proxy_read());
+                let discovery = idms_prox_read
+                    .oauth2_openid_discovery("test_resource_server")
+                    .expect("Failed to get discovery");
+                assert!(
+                    discovery.id_token_signing_alg_values_supported == vec![IdTokenSignAlg::RS256]
+                );
+                let jwkset = idms_prox_read
+                    .oauth2_openid_publickey("test_resource_server")
+                    .expect("Failed to get public key");
+                assert!(matches!(jwkset.keys.as_slice(), [Jwk::RSA { .. }]));
+                drop(idms_prox_read);
+
+                // hs256 is keyed from the client secret.
+                let mut idms_prox_write = task::block_on(idms.proxy_write(ct));
+                let me_hs256 = unsafe {
+                    ModifyEvent::new_internal_invalid(
+                        filter!(f_eq("uuid", PartialValue::new_uuid(rs_uuid))),
+                        ModifyList::new_purge_and_set(
+                            "oauth2_jwt_sign_alg",
+                            Value::new_iutf8("hs256"),
+                        ),
+                    )
+                };
+                assert!(idms_prox_write.qs_write.modify(&me_hs256).is_ok());
+                assert!(idms_prox_write.commit().is_ok());
+
+                let idms_prox_read = task::block_on(idms.proxy_read());
+                let discovery = idms_prox_read
+                    .oauth2_openid_discovery("test_resource_server")
+                    .expect("Failed to get discovery");
+                assert!(
+                    discovery.id_token_signing_alg_values_supported == vec![IdTokenSignAlg::HS256]
+                );
+                // The secret must never be published.
+                let jwkset = idms_prox_read
+                    .oauth2_openid_publickey("test_resource_server")
+                    .expect("Failed to get public key");
+                assert!(jwkset.keys.is_empty());
+
+                let (code_verifier, code_challenge) = create_code_verifier!("Whar Garble");
+
+                let consent_request =
+                    good_authorisation_request!(idms_prox_read, &ident, &uat, ct, code_challenge);
+
+                let consent_token =
+                    if let AuthoriseResponse::ConsentRequested { consent_token, .. } =
+                        consent_request
+                    {
+                        consent_token
+                    } else {
+                        unreachable!();
+                    };
+
+                let permit_success = idms_prox_read
+                    .check_oauth2_authorise_permit(&ident, &uat, &consent_token, ct)
+                    .expect("Failed to perform oauth2 permit");
+
+                match idms_delayed.async_rx.blocking_recv() {
+                    Some(DelayedAction::Oauth2ConsentGrant(_)) => {}
+                    _ => assert!(false),
+                }
+
+                let token_req = AccessTokenRequest {
+                    grant_type: GrantTypeReq::AuthorizationCode {
+                        code: permit_success.code,
+                        redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+                        code_verifier,
+                    },
+                    client_id: Some("test_resource_server".to_string()),
+                    client_secret: Some(secret.clone()),
+                };
+
+                let token_response = idms_prox_read
+                    .check_oauth2_token_exchange(None, &token_req, ct)
+                    .expect("Failed to perform oauth2 token exchange");
+
+                match idms_delayed.async_rx.blocking_recv() {
+                    Some(DelayedAction::Oauth2SessionRecord(_)) => {}
+                    _ => assert!(false),
+                }
+
+                let id_token = token_response.id_token.expect("No id_token in response!");
+
+                let jws_validator = JwsSigner::from_hs256_raw(secret.as_bytes())
+                    .and_then(|signer| signer.get_validator())
+                    .expect("failed to build validator");
+
+                let oidc_unverified =
+                    OidcUnverified::from_str(&id_token).expect("Failed to parse id_token");
+
+                let iat = ct.as_secs() as i64;
+
+                let oidc = oidc_unverified
+                    .validate(&jws_validator, iat)
+                    .expect("Failed to verify oidc");
+
+                assert!(oidc.sub == OidcSubject::U(UUID_ADMIN));
+            }
+        )
+    }
+
     #[test]
     fn test_idm_oauth2_consent_granted_and_changed_workflow() {
         run_idm_test!(
@@ -3424,6 +5033,90 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_idm_oauth2_consent_lesser_scopes_list_and_revoke() {
+        run_idm_test!(|_qs: &QueryServer,
+                       idms: &IdmServer,
+                       _idms_delayed: &mut IdmServerDelayed| {
+            let ct = Duration::from_secs(TEST_CURRENT_TIME);
+            let (_secret, uat, _ident, rs_uuid) =
+                setup_oauth2_resource_server(idms, ct, true, false, false);
+
+            // Consent to a greater set of scopes than will be requested, and
+            // record a session with the rs.
+            let session_id = Uuid::new_v4();
+            let mut idms_prox_write = task::block_on(idms.proxy_write(ct));
+            let o2cg = Oauth2ConsentGrant {
+                target_uuid: uat.uuid,
+                oauth2_rs_uuid: rs_uuid,
+                scopes: vec![
+                    "email".to_string(),
+                    "openid".to_string(),
+                    "supplement".to_string(),
+                ],
+            };
+            assert!(idms_prox_write.process_oauth2consentgrant(&o2cg).is_ok());
+            let osr = Oauth2SessionRecord {
+                target_uuid: uat.uuid,
+                parent_session_id: uat.session_id,
+                session_id,
+                expiry: uat.expiry,
+                issued_at: time::OffsetDateTime::unix_epoch() + ct,
+                rs_uuid,
+                delegated_from: None,
+            };
+            assert!(idms_prox_write.process_oauth2sessionrecord(&osr).is_ok());
+            assert!(idms_prox_write.commit().is_ok());
+
+            let idms_prox_read = task::block_on(idms.proxy_read());
+            let ident = idms_prox_read
+                .process_uat_to_identity(&uat, ct)
+                .expect("Unable to process uat");
+
+            // A lesser set of scopes was previously consented to, so no prompt.
+            let (_code_verifier, code_challenge) = create_code_verifier!("Whar Garble");
+            let consent_request =
+                good_authorisation_request!(idms_prox_read, &ident, &uat, ct, code_challenge);
+            assert!(matches!(consent_request, AuthoriseResponse::Permitted(_)));
+
+            let consents = idms_prox_read
+                .oauth2_consent_list(&ident)
+                .expect("Failed to list consents");
+            assert!(consents.len() == 1);
+            assert!(consents[0].name == "test_resource_server");
+            assert!(consents[0].scopes.contains(&"email".to_string()));
+            drop(idms_prox_read);
+
+            // Revoke, which also removes the session.
+            let mut idms_prox_write = task::block_on(idms.proxy_write(ct));
+            assert!(idms_prox_write
+                .oauth2_consent_revoke(&ident, "test_resource_server")
+                .is_ok());
+            assert!(
+                idms_prox_write.oauth2_consent_revoke(&ident, "invalid_resource_server")
+                    == Err(OperationError::NoMatchingEntries)
+            );
+            let entry = idms_prox_write
+                .qs_write
+                .internal_search_uuid(&uat.uuid)
+                .expect("Failed to search");
+            assert!(
+                !entry.attribute_equality("oauth2_session", &PartialValue::new_refer(session_id))
+            );
+            assert!(idms_prox_write.commit().is_ok());
+
+            let idms_prox_read = task::block_on(idms.proxy_read());
+            let ident = idms_prox_read
+                .process_uat_to_identity(&uat, ct)
+                .expect("Unable to process uat");
+            assert!(ident.get_oauth2_consent_scopes(rs_uuid).is_none());
+            assert!(idms_prox_read
+                .oauth2_consent_list(&ident)
+                .expect("Failed to list consents")
+                .is_empty());
+        })
+    }
+
     #[test]
     fn test_idm_oauth2_consent_granted_refint_cleanup_on_delete() {
         run_idm_test!(
@@ -3576,13 +5269,14 @@ mod tests {
                 // This exchange failed because we submitted a verifier when the code exchange
                 // has NO code challenge present.
                 let token_req = AccessTokenRequest {
-                    grant_type: "authorization_code".to_string(),
-                    code: permit_success.code,
-                    redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+                    grant_type: GrantTypeReq::AuthorizationCode {
+                        code: permit_success.code,
+                        redirect_uri: Url::parse("https://demo.example.com/oauth2/result").unwrap(),
+                        // Note the code verifier is set to "something else"
+                        code_verifier,
+                    },
                     client_id: Some("test_resource_server".to_string()),
                     client_secret: Some(secret),
-                    // Note the code verifier is set to "something else"
-                    code_verifier,
                 };
 
                 // Assert the exchange fails.
@@ -3665,13 +5359,14 @@ mod tests {
                 // == Submit the token exchange code.
                 // NOTE the url is http again
                 let token_req = AccessTokenRequest {
-                    grant_type: "authorization_code".to_string(),
-                    code: permit_success.code,
-                    redirect_uri: Url::parse("http://demo.example.com/oauth2/result").unwrap(),
+                    grant_type: GrantTypeReq::AuthorizationCode {
+                        code: permit_success.code,
+                        redirect_uri: Url::parse("http://demo.example.com/oauth2/result").unwrap(),
+                        // Note the code verifier is set to "something else"
+                        code_verifier,
+                    },
                     client_id: Some("test_resource_server".to_string()),
                     client_secret: Some(secret),
-                    // Note the code verifier is set to "something else"
-                    code_verifier,
                 };
 
                 // Assert the exchange fails.
@@ -3682,4 +5377,148 @@ mod tests {
             }
         )
     }
+
+    #[test]
+    fn test_idm_oauth2_public_client() {
+        run_idm_test!(
+            |_qs: &QueryServer, idms: &IdmServer, idms_delayed: &mut IdmServerDelayed| {
+                let ct = Duration::from_secs(TEST_CURRENT_TIME);
+                let (uat, ident, _) = setup_oauth2_resource_server_public(
+                    idms,
+                    ct,
+                    "http://127.0.0.1:8080/oauth2/result",
+                );
+
+                let idms_prox_read = task::block_on(idms.proxy_read());
+
+                let (code_verifier, code_challenge) = create_code_verifier!("Whar Garble");
+
+                // Public clients always require pkce.
+                let auth_req = AuthorisationRequest {
+                    response_type: "code".to_string(),
+                    client_id: "test_public_client".to_string(),
+                    state: "123".to_string(),
+                    pkce_request: None,
+                    redirect_uri: Url::parse("http://127.0.0.1:8080/oauth2/result").unwrap(),
+                    scope: "openid".to_string(),
+                    nonce: None,
+                    oidc_ext: Default::default(),
+                    unknown_keys: Default::default(),
+                };
+
+                assert!(
+                    idms_prox_read
+                        .check_oauth2_authorisation(&ident, &uat, &auth_req, ct)
+                        .unwrap_err()
+                        == Oauth2Error::InvalidRequest
+                );
+
+                // The redirect must be exact, other than the loopback port.
+                let auth_req = AuthorisationRequest {
+                    response_type: "code".to_string(),
+                    client_id: "test_public_client".to_string(),
+                    state: "123".to_string(),
+                    pkce_request: Some(PkceRequest {
+                        code_challenge: Base64UrlSafeData(code_challenge.clone()),
+                        code_challenge_method: CodeChallengeMethod::S256,
+                    }),
+                    redirect_uri: Url::parse("http://127.0.0.1:8080/oauth2/other").unwrap(),
+                    scope: "openid".to_string(),
+                    nonce: None,
+                    oidc_ext: Default::default(),
+                    unknown_keys: Default::default(),
+                };
+
+                assert!(
+                    idms_prox_read
+                        .check_oauth2_authorisation(&ident, &uat, &auth_req, ct)
+                        .unwrap_err()
+                        == Oauth2Error::InvalidOrigin
+                );
+
+                let auth_req = AuthorisationRequest {
+                    response_type: "code".to_string(),
+                    client_id: "test_public_client".to_string(),
+                    state: "123".to_string(),
+                    pkce_request: Some(PkceRequest {
+                        code_challenge: Base64UrlSafeData(code_challenge),
+                        code_challenge_method: CodeChallengeMethod::S256,
+                    }),
+                    redirect_uri: Url::parse("http://127.0.0.1:49152/oauth2/result").unwrap(),
+                    scope: "openid".to_string(),
+                    nonce: None,
+                    oidc_ext: Default::default(),
+                    unknown_keys: Default::default(),
+                };
+
+                let consent_token =
+                    if let AuthoriseResponse::ConsentRequested { consent_token, .. } =
+                        idms_prox_read
+                            .check_oauth2_authorisation(&ident, &uat, &auth_req, ct)
+                            .expect("Oauth2 authorisation failed")
+                    {
+                        consent_token
+                    } else {
+                        unreachable!();
+                    };
+
+                let permit_success = idms_prox_read
+                    .check_oauth2_authorise_permit(&ident, &uat, &consent_token, ct)
+                    .expect("Failed to perform oauth2 permit");
+
+                match idms_delayed.async_rx.blocking_recv() {
+                    Some(DelayedAction::Oauth2ConsentGrant(_)) => {}
+                    _ => assert!(false),
+                }
+
+                // A public client can not present a secret.
+                let token_req = AccessTokenRequest {
+                    grant_type: GrantTypeReq::AuthorizationCode {
+                        code: permit_success.code.clone(),
+                        redirect_uri: Url::parse("http://127.0.0.1:49152/oauth2/result").unwrap(),
+                        code_verifier: code_verifier.clone(),
+                    },
+                    client_id: Some("test_public_client".to_string()),
+                    client_secret: Some("12345".to_string()),
+                };
+
+                assert!(
+                    idms_prox_read
+                        .check_oauth2_token_exchange(None, &token_req, ct)
+                        .unwrap_err()
+                        == Oauth2Error::AuthenticationRequired
+                );
+
+                let token_req = AccessTokenRequest {
+                    grant_type: GrantTypeReq::AuthorizationCode {
+                        code: permit_success.code,
+                        redirect_uri: Url::parse("http://127.0.0.1:49152/oauth2/result").unwrap(),
+                        code_verifier,
+                    },
+                    client_id: Some("test_public_client".to_string()),
+                    client_secret: None,
+                };
+
+                let token_response = idms_prox_read
+                    .check_oauth2_token_exchange(None, &token_req, ct)
+                    .expect("Failed to perform oauth2 token exchange");
+
+                match idms_delayed.async_rx.blocking_recv() {
+                    Some(DelayedAction::Oauth2SessionRecord(_)) => {}
+                    _ => assert!(false),
+                }
+
+                assert!(token_response.token_type == "bearer");
+
+                let discovery = idms_prox_read
+                    .oauth2_openid_discovery("test_public_client")
+                    .expect("Failed to get discovery");
+
+                assert!(
+                    discovery.token_endpoint_auth_methods_supported
+                        == vec![TokenEndpointAuthMethod::None]
+                );
+            }
+        )
+    }
 }
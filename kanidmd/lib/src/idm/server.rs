@@ -11,6 +11,7 @@ use concread::hashmap::HashMap;
 use concread::CowCell;
 use fernet::Fernet;
 use hashbrown::HashSet;
+use kanidm_proto::internal::Oauth2Consent;
 use kanidm_proto::v1::{
     ApiToken, BackupCodesView, CredentialStatus, PasswordFeedback, RadiusAuthToken, UatPurpose,
     UnixGroupToken, UnixUserToken, UserAuthToken,
@@ -27,6 +28,7 @@ use webauthn_rs::prelude::{Webauthn, WebauthnBuilder};
 use super::event::ReadBackupCodeEvent;
 use crate::credential::policy::CryptoPolicy;
 use crate::credential::softlock::CredSoftLock;
+use crate::credential::CredentialType;
 use crate::identity::{AccessScope, IdentType, IdentUser, Limits};
 use crate::idm::account::Account;
 use crate::idm::authsession::AuthSession;
@@ -45,9 +47,10 @@ use crate::idm::event::{
 };
 use crate::idm::oauth2::{
     AccessTokenIntrospectRequest, AccessTokenIntrospectResponse, AccessTokenRequest,
-    AccessTokenResponse, AuthorisationRequest, AuthorisePermitSuccess, AuthoriseResponse,
-    JwkKeySet, Oauth2Error, Oauth2ResourceServers, Oauth2ResourceServersReadTransaction,
-    Oauth2ResourceServersWriteTransaction, OidcDiscoveryResponse, OidcToken,
+    AccessTokenResponse, AuthorisationRequest, AuthorisationRequestReference,
+    AuthorisePermitSuccess, AuthoriseResponse, JwkKeySet, Oauth2Error, Oauth2ResourceServers,
+    Oauth2ResourceServersReadTransaction, Oauth2ResourceServersWriteTransaction,
+    OidcDiscoveryResponse, OidcToken, PushedAuthorisationRequest, PushedAuthorisationResponse,
 };
 use crate::idm::radius::RadiusAccount;
 use crate::idm::scim::{ScimSyncToken, SyncAccount};
@@ -56,6 +59,8 @@ use crate::idm::unix::{UnixGroup, UnixUserAccount};
 use crate::idm::AuthState;
 use crate::ldap::{LdapBoundToken, LdapSession};
 use crate::prelude::*;
+use crate::security_event::SecurityEvent;
+use crate::server::EntryChanges;
 use crate::utils::{password_from_random, readable_password_from_random, uuid_from_duration, Sid};
 use crate::value::{Oauth2Session, Session};
 
@@ -286,6 +291,11 @@ impl IdmServer {
         }
     }
 
+    /// Subscribe to the entries changed by each transaction as it is committed.
+    pub fn subscribe_changes(&self) -> tokio::sync::broadcast::Receiver<EntryChanges> {
+        self.qs.subscribe_changes()
+    }
+
     #[instrument(level = "debug", skip_all)]
     pub async fn proxy_write(&self, ts: Duration) -> IdmServerProxyWriteTransaction<'_> {
         let qs_write = self.qs.write(ts).await;
@@ -578,6 +588,7 @@ pub trait IdmServerTransaction<'a> {
         uuid: Uuid,
         session_id: Uuid,
         parent_session_id: Uuid,
+        outlive_parent: bool,
         iat: i64,
         ct: Duration,
     ) -> Result<Option<Account>, OperationError> {
@@ -601,14 +612,28 @@ pub trait IdmServerTransaction<'a> {
             // We are past the grace window. Enforce session presence.
             // We enforce both sessions are present in case of inconsistency
             // that may occur with replication.
+            // A session derived by token exchange is only valid while every session
+            // in its delegation chain still exists.
             let oauth2_session_valid = entry
                 .get_ava_as_oauth2session_map("oauth2_session")
-                .map(|map| map.get(&session_id).is_some())
-                .unwrap_or(false);
-            let uat_session_valid = entry
-                .get_ava_as_session_map("user_auth_token_session")
-                .map(|map| map.get(&parent_session_id).is_some())
+                .map(|map| {
+                    let mut next = Some(session_id);
+                    while let Some(id) = next {
+                        match map.get(&id) {
+                            Some(session) => next = session.delegated_from,
+                            None => return false,
+                        }
+                    }
+                    true
+                })
                 .unwrap_or(false);
+            // If the rs allows the oauth2 session to outlive the parent, we don't require
+            // the parent to be present.
+            let uat_session_valid = outlive_parent
+                || entry
+                    .get_ava_as_session_map("user_auth_token_session")
+                    .map(|map| map.get(&parent_session_id).is_some())
+                    .unwrap_or(false);
 
             if oauth2_session_valid && uat_session_valid {
                 security_info!("A valid session value exists for this token");
@@ -732,34 +757,23 @@ pub trait IdmServerTransaction<'a> {
     ) -> Result<Identity, OperationError> {
         match session {
             LdapSession::UnixBind(uuid) => {
-                let anon_entry = self
-                    .get_qs_txn()
-                    .internal_search_uuid(&UUID_ANONYMOUS)
-                    .map_err(|e| {
-                        admin_error!("Failed to validate ldap session -> {:?}", e);
-                        e
-                    })?;
-
-                let entry = if uuid == &UUID_ANONYMOUS {
-                    anon_entry.clone()
-                } else {
-                    self.get_qs_txn().internal_search_uuid(uuid).map_err(|e| {
-                        admin_error!("Failed to start auth ldap -> {:?}", e);
-                        e
-                    })?
-                };
+                let entry = self.get_qs_txn().internal_search_uuid(uuid).map_err(|e| {
+                    admin_error!("Failed to validate ldap session -> {:?}", e);
+                    e
+                })?;
 
                 if Account::check_within_valid_time(
                     ct,
                     entry.get_ava_single_datetime("account_valid_from").as_ref(),
                     entry.get_ava_single_datetime("account_expire").as_ref(),
                 ) {
-                    // Good to go
+                    // Good to go. The bound account reads with its own access, but an ldap
+                    // bind can never be used to write.
                     let limits = Limits::default();
                     let session_id = Uuid::new_v4();
 
                     Ok(Identity {
-                        origin: IdentType::User(IdentUser { entry: anon_entry }),
+                        origin: IdentType::User(IdentUser { entry }),
                         session_id,
                         scope: AccessScope::ReadOnly,
                         limits,
@@ -880,6 +894,21 @@ impl<'a> IdmServerTransaction<'a> for IdmServerAuthTransaction<'a> {
     }
 }
 
+fn emit_unix_auth_failure(account: &UnixUserAccount, is_valid: bool) {
+    SecurityEvent::AuthFailure {
+        account: account.uuid,
+        spn: account.spn.clone(),
+        reason: "incorrect password".to_string(),
+    }
+    .emit();
+    if !is_valid {
+        SecurityEvent::AccountLocked {
+            account: account.uuid,
+        }
+        .emit();
+    }
+}
+
 impl<'a> IdmServerAuthTransaction<'a> {
     #[cfg(test)]
     pub fn is_sessionid_present(&self, sessionid: &Uuid) -> bool {
@@ -1159,6 +1188,9 @@ impl<'a> IdmServerAuthTransaction<'a> {
                                 // Update it.
                                 if let Some(ref mut slock) = maybe_slock {
                                     slock.record_failure(ct);
+                                    if !slock.is_valid() {
+                                        auth_session.emit_account_locked();
+                                    }
                                 }
                             };
                             aus
@@ -1251,6 +1283,7 @@ impl<'a> IdmServerAuthTransaction<'a> {
                     if res.is_none() {
                         // Update it.
                         slock.record_failure(ct);
+                        emit_unix_auth_failure(&account, slock.is_valid());
                     };
                     res
                 })
@@ -1335,7 +1368,27 @@ impl<'a> IdmServerAuthTransaction<'a> {
                 return Ok(None);
             }
 
-            let maybe_slock_ref = match account.unix_cred_uuid_and_policy() {
+            // Binds are checked against the unix credential. If the account has none, a
+            // primary credential that is password only may be used instead. An mfa primary
+            // credential can never be satisfied by a simple bind.
+            let primary_pw_cred = if account.unix_cred_uuid_and_policy().is_none() {
+                account_entry
+                    .get_ava_single_credential("primary_credential")
+                    .filter(|cred| {
+                        matches!(
+                            cred.type_,
+                            CredentialType::Password(_) | CredentialType::GeneratedPassword(_)
+                        )
+                    })
+            } else {
+                None
+            };
+
+            let cred_uuid_and_policy = account
+                .unix_cred_uuid_and_policy()
+                .or_else(|| primary_pw_cred.map(|cred| (cred.uuid, cred.softlock_policy())));
+
+            let maybe_slock_ref = match cred_uuid_and_policy {
                 Some((cred_uuid, policy)) => {
                     let softlock_read = self.softlocks.read();
                     let slock_ref = match softlock_read.get(&cred_uuid) {
@@ -1374,10 +1427,31 @@ impl<'a> IdmServerAuthTransaction<'a> {
             };
 
             if let Some(mut slock) = maybe_valid {
-                if account
-                    .verify_unix_credential(lae.cleartext.as_str(), &self.async_tx, ct)?
-                    .is_some()
-                {
+                let verified = match primary_pw_cred {
+                    Some(cred) => {
+                        let pw = cred.password_ref()?;
+                        let verified = pw.verify(lae.cleartext.as_str())?;
+                        if verified && pw.requires_upgrade() {
+                            self.async_tx
+                                .send(DelayedAction::PwUpgrade(PasswordUpgrade {
+                                    target_uuid: account.uuid,
+                                    existing_password: lae.cleartext.clone(),
+                                }))
+                                .map_err(|_| {
+                                    admin_error!(
+                                        "failed to queue delayed action - password upgrade"
+                                    );
+                                    OperationError::InvalidState
+                                })?;
+                        }
+                        verified
+                    }
+                    None => account
+                        .verify_unix_credential(lae.cleartext.as_str(), &self.async_tx, ct)?
+                        .is_some(),
+                };
+
+                if verified {
                     let session_id = Uuid::new_v4();
                     security_info!(
                         "Starting session {} for {} {}",
@@ -1394,6 +1468,7 @@ impl<'a> IdmServerAuthTransaction<'a> {
                 } else {
                     // PW failure, update softlock.
                     slock.record_failure(ct);
+                    emit_unix_auth_failure(&account, slock.is_valid());
                     Ok(None)
                 }
             } else {
@@ -1404,6 +1479,93 @@ impl<'a> IdmServerAuthTransaction<'a> {
         }
     }
 
+    /// An ldap session can not write, except that a password modify which carries the account's
+    /// current password re-authenticates it, allowing the account to update its own credentials.
+    /// The current password is checked under the same softlock as a bind, so that a session can
+    /// not be used to guess it.
+    #[instrument(level = "debug", skip_all)]
+    pub async fn validate_ldap_password_modify_session(
+        &mut self,
+        session: &LdapSession,
+        target: Option<Uuid>,
+        old_password: Option<&str>,
+        ct: Duration,
+    ) -> Result<(Identity, Uuid), OperationError> {
+        let mut ident = self.validate_ldap_session(session, ct)?;
+
+        let self_uuid = ident.get_uuid().ok_or(OperationError::InvalidState)?;
+        if self_uuid == UUID_ANONYMOUS {
+            security_info!("Anonymous may not modify passwords");
+            return Err(OperationError::AccessDenied);
+        }
+        let target = target.unwrap_or(self_uuid);
+
+        if ident.access_scope() == AccessScope::ReadWrite {
+            return Ok((ident, target));
+        }
+
+        let old_password = match old_password {
+            Some(pw) if target == self_uuid => pw,
+            _ => {
+                security_info!(
+                    "Password modify of a read only ldap session requires the current password"
+                );
+                return Err(OperationError::AccessDenied);
+            }
+        };
+
+        // As with bind, only a password only credential can be satisfied here.
+        let entry = self.qs_read.internal_search_uuid(&target)?;
+        let cred = match entry
+            .get_ava_single_credential("primary_credential")
+            .filter(|cred| {
+                matches!(
+                    cred.type_,
+                    CredentialType::Password(_) | CredentialType::GeneratedPassword(_)
+                )
+            }) {
+            Some(cred) => cred,
+            None => {
+                security_info!("Password modify requires a password only primary credential");
+                return Err(OperationError::NotAuthenticated);
+            }
+        };
+
+        let slock_ref = {
+            let softlock_read = self.softlocks.read();
+            match softlock_read.get(&cred.uuid) {
+                Some(slock_ref) => slock_ref.clone(),
+                None => {
+                    let _session_ticket = self.session_ticket.acquire().await;
+                    let mut softlock_write = self.softlocks.write();
+                    let slock = Arc::new(Mutex::new(CredSoftLock::new(cred.softlock_policy())));
+                    softlock_write.insert(cred.uuid, slock.clone());
+                    softlock_write.commit();
+                    slock
+                }
+            }
+        };
+
+        let mut slock = slock_ref.lock().await;
+        // Apply the current time.
+        slock.apply_time_step(ct);
+        if !slock.is_valid() {
+            security_info!("Account is softlocked.");
+            return Err(OperationError::NotAuthenticated);
+        }
+
+        if cred.verify_password(old_password)? {
+            security_info!("Password modify re-authenticated ldap session");
+            ident.scope = AccessScope::ReadWrite;
+            Ok((ident, target))
+        } else {
+            // PW failure, update softlock.
+            slock.record_failure(ct);
+            security_info!("Password modify current password is incorrect");
+            Err(OperationError::NotAuthenticated)
+        }
+    }
+
     pub fn commit(self) -> Result<(), OperationError> {
         /*
         lperf_trace_segment!("idm::server::IdmServerAuthTransaction::commit", || {
@@ -1516,6 +1678,25 @@ impl<'a> IdmServerProxyReadTransaction<'a> {
         account.to_backupcodesview()
     }
 
+    pub fn check_oauth2_pushed_authorisation(
+        &self,
+        client_authz: Option<&str>,
+        par_req: &PushedAuthorisationRequest,
+        ct: Duration,
+    ) -> Result<PushedAuthorisationResponse, Oauth2Error> {
+        self.oauth2rs
+            .check_oauth2_pushed_authorisation(client_authz, par_req, ct)
+    }
+
+    pub fn check_oauth2_pushed_authorisation_reference(
+        &self,
+        auth_ref: &AuthorisationRequestReference,
+        ct: Duration,
+    ) -> Result<AuthorisationRequest, Oauth2Error> {
+        self.oauth2rs
+            .check_oauth2_pushed_authorisation_reference(auth_ref, ct)
+    }
+
     pub fn check_oauth2_authorisation(
         &self,
         ident: &Identity,
@@ -1556,7 +1737,7 @@ impl<'a> IdmServerProxyReadTransaction<'a> {
         ct: Duration,
     ) -> Result<AccessTokenResponse, Oauth2Error> {
         self.oauth2rs
-            .check_oauth2_token_exchange(client_authz, token_req, ct, &self.async_tx)
+            .check_oauth2_token_exchange(self, client_authz, token_req, ct, &self.async_tx)
     }
 
     pub fn check_oauth2_token_introspect(
@@ -1579,6 +1760,13 @@ impl<'a> IdmServerProxyReadTransaction<'a> {
             .oauth2_openid_userinfo(self, client_id, client_authz, ct)
     }
 
+    pub fn oauth2_consent_list(
+        &self,
+        ident: &Identity,
+    ) -> Result<Vec<Oauth2Consent>, OperationError> {
+        self.oauth2rs.oauth2_consent_list(ident)
+    }
+
     pub fn oauth2_openid_discovery(
         &self,
         client_id: &str,
@@ -2199,6 +2387,7 @@ impl<'a> IdmServerProxyWriteTransaction<'a> {
                 expiry: osr.expiry,
                 issued_at: osr.issued_at,
                 rs_uuid: osr.rs_uuid,
+                delegated_from: osr.delegated_from,
             },
         );
 
@@ -2331,6 +2520,7 @@ mod tests {
     };
     use crate::idm::server::{IdmServer, IdmServerTransaction};
     use crate::idm::AuthState;
+    use crate::ldap::LdapSession;
     use crate::modify::{Modify, ModifyList};
     use crate::prelude::*;
     use crate::utils::duration_from_epoch_now;
@@ -3686,6 +3876,51 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_idm_ldap_password_modify_softlocking() {
+        run_idm_test!(
+            |qs: &QueryServer, idms: &IdmServer, _idms_delayed: &mut IdmServerDelayed| {
+                task::block_on(init_admin_w_password(qs, TEST_PASSWORD))
+                    .expect("Failed to setup admin account");
+
+                let ct = Duration::from_secs(TEST_CURRENT_TIME);
+                let session = LdapSession::UnixBind(UUID_ADMIN);
+                let mut idms_auth = idms.auth();
+
+                let r = task::block_on(idms_auth.validate_ldap_password_modify_session(
+                    &session,
+                    None,
+                    Some(TEST_PASSWORD_INC),
+                    ct,
+                ));
+                assert!(r.err() == Some(OperationError::NotAuthenticated));
+
+                // Now if we immediately try again, should fail at same time due to SL
+                let r = task::block_on(idms_auth.validate_ldap_password_modify_session(
+                    &session,
+                    None,
+                    Some(TEST_PASSWORD),
+                    ct,
+                ));
+                assert!(r.err() == Some(OperationError::NotAuthenticated));
+
+                // And then later, works because of SL lifting.
+                let (ident, target) =
+                    task::block_on(idms_auth.validate_ldap_password_modify_session(
+                        &session,
+                        None,
+                        Some(TEST_PASSWORD),
+                        ct + Duration::from_secs(2),
+                    ))
+                    .expect("Failed to re-authenticate ldap session");
+                assert!(target == UUID_ADMIN);
+                assert!(ident.access_scope() == AccessScope::ReadWrite);
+
+                assert!(idms_auth.commit().is_ok());
+            }
+        )
+    }
+
     #[test]
     fn test_idm_jwt_uat_expiry() {
         run_idm_test!(
@@ -1,20 +1,26 @@
 //! LDAP specific operations handling components. This is where LDAP operations
 //! are sent to for processing.
 
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 use std::iter;
 
 use async_std::task;
 use kanidm_proto::v1::{ApiToken, OperationError, UserAuthToken};
+use ldap3_proto::proto::{
+    LdapCompareRequest, LdapControl, LdapExtendedRequest, LdapExtendedResponse, LdapModifyRequest,
+    LdapModifyType, LdapOp, LdapPasswordModifyRequest, LdapResult,
+};
 use ldap3_proto::simple::*;
 use regex::Regex;
 use tracing::trace;
 use uuid::Uuid;
 
-use crate::event::SearchEvent;
+use crate::event::{SearchEvent, SearchPage};
+use crate::idm::credupdatesession::InitCredentialUpdateEvent;
 use crate::idm::event::{LdapAuthEvent, LdapTokenAuthEvent};
 use crate::idm::server::{IdmServer, IdmServerTransaction};
 use crate::prelude::*;
+use crate::schema::{SchemaAttribute, SchemaClass, SchemaTransaction};
 
 // Clippy doesn't like Bind here. But proto needs unboxed ldapmsg,
 // and ldapboundtoken is moved. Really, it's not too bad, every message here is pretty sucky.
@@ -30,8 +36,8 @@ pub enum LdapResponseState {
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum LdapSession {
-    // Maps through and provides anon read, but allows us to check the validity
-    // of the account still.
+    // A simple bind of an account, or anonymous. This provides read only access
+    // as the bound account, and allows us to check the validity of the account still.
     UnixBind(Uuid),
     UserAuthToken(UserAuthToken),
     ApiToken(ApiToken),
@@ -51,9 +57,211 @@ pub struct LdapBoundToken {
     pub effective_session: LdapSession,
 }
 
+/// An RFC 2696 simple paged results request. The cookie we issue is opaque to the client, and
+/// holds the uuid of the last entry returned so that the next page resumes after it. No state
+/// is held between pages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct LdapPagedRequest(SearchPage);
+
+impl LdapPagedRequest {
+    fn from_controls(ctrl: &[LdapControl]) -> Result<Option<Self>, OperationError> {
+        ctrl.iter()
+            .find_map(|c| match c {
+                LdapControl::SimplePagedResults { size, cookie } => Some((size, cookie)),
+                _ => None,
+            })
+            .map(|(size, cookie)| {
+                let size = usize::try_from(*size).map_err(|_| {
+                    request_error!(?size, "LDAP Search failure - invalid page size");
+                    OperationError::InvalidRequestState
+                })?;
+                let after = if cookie.is_empty() {
+                    None
+                } else {
+                    Some(Uuid::parse_str(cookie).map_err(|_| {
+                        request_error!("LDAP Search failure - invalid paged results cookie");
+                        OperationError::InvalidRequestState
+                    })?)
+                };
+                Ok(LdapPagedRequest(SearchPage { size, after }))
+            })
+            .transpose()
+    }
+}
+
+/// The attribute that an entry's RDN is formed from when it is presented as a DN.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LdapRdnAttr {
+    Spn,
+    Name,
+    Uid,
+    Cn,
+    Uuid,
+}
+
+impl LdapRdnAttr {
+    fn from_str(input: &str) -> Option<Self> {
+        match input {
+            "spn" => Some(LdapRdnAttr::Spn),
+            "name" => Some(LdapRdnAttr::Name),
+            "uid" => Some(LdapRdnAttr::Uid),
+            "cn" => Some(LdapRdnAttr::Cn),
+            "uuid" => Some(LdapRdnAttr::Uuid),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            LdapRdnAttr::Spn => "spn",
+            LdapRdnAttr::Name => "name",
+            LdapRdnAttr::Uid => "uid",
+            LdapRdnAttr::Cn => "cn",
+            LdapRdnAttr::Uuid => "uuid",
+        }
+    }
+}
+
+/// How entries are presented as DNs. This is configured by `domain_ldap_rdn_attr` where
+/// each value is either a default rdn attribute, or `class:attr` for entries of that class.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LdapDnFormat {
+    default: LdapRdnAttr,
+    class_rdn: Vec<(String, LdapRdnAttr)>,
+}
+
+impl Default for LdapDnFormat {
+    fn default() -> Self {
+        LdapDnFormat {
+            default: LdapRdnAttr::Spn,
+            class_rdn: Vec::new(),
+        }
+    }
+}
+
+impl LdapDnFormat {
+    pub(crate) fn parse<'a, I>(values: I) -> Result<Self, OperationError>
+    where
+        I: Iterator<Item = &'a str>,
+    {
+        let mut fmt = LdapDnFormat::default();
+        let mut default = None;
+        for v in values {
+            let invalid = || {
+                OperationError::InvalidAttribute(format!(
+                    "domain_ldap_rdn_attr {} is not a valid rdn attribute",
+                    v
+                ))
+            };
+            match v.split_once(':') {
+                Some((class, attr)) if !class.is_empty() => {
+                    let attr = LdapRdnAttr::from_str(attr).ok_or_else(invalid)?;
+                    fmt.class_rdn.push((class.to_string(), attr));
+                }
+                Some(_) => return Err(invalid()),
+                None => {
+                    let attr = LdapRdnAttr::from_str(v).ok_or_else(invalid)?;
+                    // Only one default may be set, else the presented dn is ambiguous.
+                    if default.replace(attr).is_some() {
+                        return Err(OperationError::InvalidAttribute(
+                            "domain_ldap_rdn_attr may only have one default rdn attribute"
+                                .to_string(),
+                        ));
+                    }
+                }
+            }
+        }
+        if let Some(d) = default {
+            fmt.default = d;
+        }
+        Ok(fmt)
+    }
+
+    /// Generate the RDN of the entry with this uuid.
+    pub(crate) fn rdn<'a, T>(&self, qs: &T, uuid: Uuid) -> Result<String, OperationError>
+    where
+        T: QueryServerTransaction<'a> + ?Sized,
+    {
+        // The default format is maintained in the uuid2rdn index.
+        if *self == LdapDnFormat::default() {
+            return qs.uuid_to_rdn(uuid);
+        }
+
+        let e = match qs.internal_search_uuid(&uuid) {
+            Ok(e) => e,
+            Err(_) => return qs.uuid_to_rdn(uuid),
+        };
+
+        let rdn_attr = self
+            .class_rdn
+            .iter()
+            .find(|(class, _)| e.attribute_equality("class", &PartialValue::new_class(class)))
+            .map(|(_, attr)| *attr)
+            .unwrap_or(self.default);
+
+        let value = match rdn_attr {
+            LdapRdnAttr::Spn => e.get_ava_single_proto_string("spn"),
+            LdapRdnAttr::Name | LdapRdnAttr::Uid | LdapRdnAttr::Cn => {
+                e.get_ava_single_iname("name").map(str::to_string)
+            }
+            LdapRdnAttr::Uuid => None,
+        };
+
+        Ok(match value {
+            Some(v) => format!("{}={}", rdn_attr.as_str(), v),
+            None => format!("uuid={}", uuid.as_hyphenated()),
+        })
+    }
+}
+
+/// Administrator configured aliases of ldap attribute names to kanidm attributes from
+/// `domain_ldap_attr_map`. These take precedence over the builtin virtual attributes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LdapAttrMap {
+    aliases: BTreeMap<String, String>,
+}
+
+impl LdapAttrMap {
+    pub(crate) fn parse<'a, I>(values: I) -> Result<Self, OperationError>
+    where
+        I: Iterator<Item = &'a str>,
+    {
+        let aliases = values
+            .map(|v| match v.split_once(':') {
+                Some((l, k)) if !l.is_empty() && !k.is_empty() => {
+                    Ok((l.to_lowercase(), k.to_lowercase()))
+                }
+                _ => Err(OperationError::InvalidAttribute(format!(
+                    "domain_ldap_attr_map {} is not in the form ldapname:attribute",
+                    v
+                ))),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(LdapAttrMap { aliases })
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.aliases.iter().map(|(l, k)| (l.as_str(), k.as_str()))
+    }
+
+    /// Map a lowercase ldap attribute name to the kanidm attribute it is sourced from.
+    pub(crate) fn map<'a>(&'a self, input: &'a str) -> &'a str {
+        self.aliases
+            .get(input)
+            .map(|k| k.as_str())
+            .unwrap_or_else(|| ldap_vattr_map(input))
+    }
+
+    fn map_filter_attr(&self, input: &str) -> String {
+        self.map(input.to_lowercase().as_str()).to_string()
+    }
+}
+
 pub struct LdapServer {
     rootdse: LdapSearchResultEntry,
     basedn: String,
+    dn_format: LdapDnFormat,
+    attr_map: LdapAttrMap,
     dnre: Regex,
     binddnre: Regex,
 }
@@ -73,13 +281,41 @@ impl LdapServer {
             .map(|s| s.to_string())
             .ok_or(OperationError::InvalidEntryState)?;
 
-        let basedn = ldap_domain_to_dc(domain_name.as_str());
-
-        let dnre = Regex::new(format!("^((?P<attr>[^=]+)=(?P<val>[^=]+),)?{}$", basedn).as_str())
-            .map_err(|_| OperationError::InvalidEntryState)?;
-
-        let binddnre = Regex::new(format!("^(([^=,]+)=)?(?P<val>[^=,]+)(,{})?$", basedn).as_str())
-            .map_err(|_| OperationError::InvalidEntryState)?;
+        // An administrator may override the basedn, otherwise it's derived from the domain name.
+        let basedn = domain_entry
+            .get_ava_single_iutf8("domain_ldap_basedn")
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| ldap_domain_to_dc(domain_name.as_str()));
+
+        let dn_format = domain_entry
+            .get_ava_iter_iutf8("domain_ldap_rdn_attr")
+            .map(LdapDnFormat::parse)
+            .transpose()?
+            .unwrap_or_default();
+
+        let attr_map = domain_entry
+            .get_ava_iter_iutf8("domain_ldap_attr_map")
+            .map(LdapAttrMap::parse)
+            .transpose()?
+            .unwrap_or_default();
+
+        let dnre = Regex::new(
+            format!(
+                "^((?P<attr>[^=]+)=(?P<val>[^=]+),)?{}$",
+                regex::escape(basedn.as_str())
+            )
+            .as_str(),
+        )
+        .map_err(|_| OperationError::InvalidEntryState)?;
+
+        let binddnre = Regex::new(
+            format!(
+                "^(([^=,]+)=)?(?P<val>[^=,]+)(,{})?$",
+                regex::escape(basedn.as_str())
+            )
+            .as_str(),
+        )
+        .map_err(|_| OperationError::InvalidEntryState)?;
 
         let rootdse = LdapSearchResultEntry {
             dn: "".to_string(),
@@ -100,9 +336,24 @@ impl LdapServer {
                     atype: "supportedLDAPVersion".to_string(),
                     vals: vec!["3".as_bytes().to_vec()],
                 },
+                LdapPartialAttribute {
+                    atype: "namingContexts".to_string(),
+                    vals: vec![basedn.as_bytes().to_vec()],
+                },
+                LdapPartialAttribute {
+                    atype: "subschemaSubentry".to_string(),
+                    vals: vec![LDAP_SCHEMA_DN.as_bytes().to_vec()],
+                },
                 LdapPartialAttribute {
                     atype: "supportedExtension".to_string(),
-                    vals: vec!["1.3.6.1.4.1.4203.1.11.3".as_bytes().to_vec()],
+                    vals: vec![
+                        "1.3.6.1.4.1.4203.1.11.3".as_bytes().to_vec(),
+                        LDAP_PASSWORD_MODIFY_OID.as_bytes().to_vec(),
+                    ],
+                },
+                LdapPartialAttribute {
+                    atype: "supportedControl".to_string(),
+                    vals: vec![LDAP_CONTROL_PAGED_RESULTS.as_bytes().to_vec()],
                 },
                 LdapPartialAttribute {
                     atype: "supportedFeatures".to_string(),
@@ -118,17 +369,29 @@ impl LdapServer {
         Ok(LdapServer {
             rootdse,
             basedn,
+            dn_format,
+            attr_map,
             dnre,
             binddnre,
         })
     }
 
-    #[instrument(level = "debug", skip_all)]
     async fn do_search(
         &self,
         idms: &IdmServer,
         sr: &SearchRequest,
         uat: &LdapBoundToken,
+    ) -> Result<Vec<LdapMsg>, OperationError> {
+        self.do_search_ext(idms, sr, uat, None).await
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn do_search_ext(
+        &self,
+        idms: &IdmServer,
+        sr: &SearchRequest,
+        uat: &LdapBoundToken,
+        paged: Option<LdapPagedRequest>,
         // eventid: &Uuid,
     ) -> Result<Vec<LdapMsg>, OperationError> {
         admin_info!("Attempt LDAP Search for {}", uat.spn);
@@ -136,7 +399,19 @@ impl LdapServer {
         if sr.base.is_empty() && sr.scope == LdapSearchScope::Base {
             admin_info!("LDAP Search success - RootDSE");
             Ok(vec![
-                sr.gen_result_entry(self.rootdse.clone()),
+                sr.gen_result_entry(ldap_filter_requested_attrs(self.rootdse.clone(), &sr.attrs)),
+                sr.gen_success(),
+            ])
+        } else if sr.base.eq_ignore_ascii_case(LDAP_SCHEMA_DN) {
+            // The subschema subentry is only addressable as a base object.
+            if sr.scope != LdapSearchScope::Base {
+                return Ok(vec![sr.gen_success()]);
+            }
+            let idm_read = idms.proxy_read().await;
+            let subentry = ldap_schema_subentry(idm_read.qs_read.get_schema());
+            admin_info!("LDAP Search success - Schema Subentry");
+            Ok(vec![
+                sr.gen_result_entry(ldap_filter_requested_attrs(subentry, &sr.attrs)),
                 sr.gen_success(),
             ])
         } else {
@@ -147,7 +422,7 @@ impl LdapServer {
 
             let (opt_attr, opt_value) = match self.dnre.captures(sr.base.as_str()) {
                 Some(caps) => (
-                    caps.name("attr").map(|v| v.as_str().to_string()),
+                    caps.name("attr").map(|v| ldap_rdn_attr_map(v.as_str())),
                     caps.name("val").map(|v| v.as_str().to_string()),
                 ),
                 None => {
@@ -222,8 +497,16 @@ impl LdapServer {
 
             // We need to retain this to know what the client requested.
             let (k_attrs, l_attrs) = if all_op_attrs {
-                // We need all attrs, and we do a full v_attr map.
-                (None, ldap_all_vattrs())
+                // We need all attrs, and we do a full v_attr map, including any aliases.
+                let mut l_attrs = ldap_all_vattrs();
+                l_attrs.extend(
+                    self.attr_map
+                        .iter()
+                        .map(|(l, _)| l.to_string())
+                        .filter(|l| !l_attrs.contains(l))
+                        .collect::<Vec<_>>(),
+                );
+                (None, l_attrs)
             } else if all_attrs {
                 (None, Vec::new())
             } else {
@@ -249,7 +532,7 @@ impl LdapServer {
                         if a == "entrydn" || a == "dn" {
                             None
                         } else {
-                            Some(AttrString::from(ldap_vattr_map(a)))
+                            Some(AttrString::from(self.attr_map.map(a)))
                         }
                     })
                     .collect();
@@ -267,7 +550,7 @@ impl LdapServer {
             // join the filter, with ext_filter
             let lfilter = match ext_filter {
                 Some(ext) => LdapFilter::And(vec![
-                    sr.filter.clone(),
+                    self.ldap_filter_map(&sr.filter),
                     ext,
                     LdapFilter::Not(Box::new(LdapFilter::Or(vec![
                         LdapFilter::Equality("class".to_string(), "classtype".to_string()),
@@ -279,7 +562,7 @@ impl LdapServer {
                     ]))),
                 ]),
                 None => LdapFilter::And(vec![
-                    sr.filter.clone(),
+                    self.ldap_filter_map(&sr.filter),
                     LdapFilter::Not(Box::new(LdapFilter::Or(vec![
                         LdapFilter::Equality("class".to_string(), "classtype".to_string()),
                         LdapFilter::Equality("class".to_string(), "attributetype".to_string()),
@@ -309,10 +592,27 @@ impl LdapServer {
                         e
                     })?;
 
-            let res = idm_read.qs_read.search_ext(&se).map_err(|e| {
-                admin_error!("search failure {:?}", e);
-                e
-            })?;
+            let mut done = sr.gen_success();
+
+            let res = match paged {
+                Some(LdapPagedRequest(page)) => {
+                    let (res, next) =
+                        idm_read.qs_read.search_ext_page(&se, &page).map_err(|e| {
+                            admin_error!("search failure {:?}", e);
+                            e
+                        })?;
+                    // An empty cookie signals that there are no more pages. A size of zero is
+                    // the client abandoning the search.
+                    let cookie = next.map(SearchPage::token).unwrap_or_default();
+                    admin_info!(after = ?page.after, size = %page.size, "LDAP Search Paged Results");
+                    done.ctrl = vec![LdapControl::SimplePagedResults { size: 0, cookie }];
+                    res
+                }
+                None => idm_read.qs_read.search_ext(&se).map_err(|e| {
+                    admin_error!("search failure {:?}", e);
+                    e
+                })?,
+            };
 
             // These have already been fully reduced (access controls applied),
             // so we can just transform the values and open palm slam them into
@@ -320,11 +620,18 @@ impl LdapServer {
             let lres: Result<Vec<_>, _> = res
                 .into_iter()
                 .map(|e| {
-                    e.to_ldap(&idm_read.qs_read, self.basedn.as_str(), all_attrs, &l_attrs)
-                        // if okay, wrap in a ldap msg.
-                        .map(|r| sr.gen_result_entry(r))
+                    e.to_ldap(
+                        &idm_read.qs_read,
+                        self.basedn.as_str(),
+                        &self.dn_format,
+                        &self.attr_map,
+                        all_attrs,
+                        &l_attrs,
+                    )
+                    // if okay, wrap in a ldap msg.
+                    .map(|r| sr.gen_result_entry(r))
                 })
-                .chain(iter::once(Ok(sr.gen_success())))
+                .chain(iter::once(Ok(done)))
                 .collect();
 
             let lres = lres.map_err(|e| {
@@ -341,6 +648,440 @@ impl LdapServer {
         }
     }
 
+    /// Rewrite a client filter to use kanidm attribute names. Legacy clients such as sssd
+    /// compare reference attributes (memberOf) to dns, so these are reduced to their rdn value.
+    fn ldap_filter_map(&self, f: &LdapFilter) -> LdapFilter {
+        let map_value = |v: &String| {
+            self.dnre
+                .captures(v.as_str())
+                .filter(|caps| caps.name("attr").is_some())
+                .and_then(|caps| caps.name("val"))
+                .map(|val| val.as_str().to_string())
+                .unwrap_or_else(|| v.clone())
+        };
+
+        match f {
+            LdapFilter::And(l) => {
+                LdapFilter::And(l.iter().map(|f| self.ldap_filter_map(f)).collect())
+            }
+            LdapFilter::Or(l) => {
+                LdapFilter::Or(l.iter().map(|f| self.ldap_filter_map(f)).collect())
+            }
+            LdapFilter::Not(f) => LdapFilter::Not(Box::new(self.ldap_filter_map(f))),
+            LdapFilter::Equality(a, v) => {
+                LdapFilter::Equality(self.attr_map.map_filter_attr(a), map_value(v))
+            }
+            LdapFilter::Present(a) => LdapFilter::Present(self.attr_map.map_filter_attr(a)),
+            LdapFilter::Substring(a, s) => {
+                LdapFilter::Substring(self.attr_map.map_filter_attr(a), s.clone())
+            }
+            LdapFilter::GreaterOrEqual(a, v) => {
+                LdapFilter::GreaterOrEqual(self.attr_map.map_filter_attr(a), v.clone())
+            }
+            LdapFilter::LessOrEqual(a, v) => {
+                LdapFilter::LessOrEqual(self.attr_map.map_filter_attr(a), v.clone())
+            }
+            LdapFilter::Approx(a, v) => {
+                LdapFilter::Approx(self.attr_map.map_filter_attr(a), map_value(v))
+            }
+            LdapFilter::Extensible(mra) => {
+                let mut mra = mra.clone();
+                mra.type_ = mra.type_.map(|a| self.attr_map.map_filter_attr(&a));
+                mra.match_value = map_value(&mra.match_value);
+                LdapFilter::Extensible(mra)
+            }
+        }
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    async fn do_compare(
+        &self,
+        idms: &IdmServer,
+        cr: &LdapCompareRequest,
+        uat: &LdapBoundToken,
+    ) -> Result<LdapResultCode, OperationError> {
+        admin_info!("Attempt LDAP Compare for {}", uat.spn);
+
+        // The target must be an entry beneath our basedn.
+        let (attr, val) = self
+            .dnre
+            .captures(cr.dn.as_str())
+            .and_then(|caps| {
+                Some((
+                    ldap_rdn_attr_map(caps.name("attr")?.as_str()),
+                    caps.name("val")?.as_str().to_string(),
+                ))
+            })
+            .ok_or_else(|| {
+                request_error!("LDAP Compare failure - invalid dn");
+                OperationError::InvalidRequestState
+            })?;
+
+        let assertion = std::str::from_utf8(cr.val.as_slice()).map_err(|_| {
+            request_error!("LDAP Compare failure - invalid assertion value");
+            OperationError::InvalidRequestState
+        })?;
+        // References are presented as dns, so allow them to be compared in that form.
+        let assertion = self
+            .dnre
+            .captures(assertion)
+            .and_then(|caps| caps.name("val"))
+            .map(|v| v.as_str())
+            .unwrap_or(assertion);
+
+        let k_attr = AttrString::from(self.attr_map.map_filter_attr(cr.atype.as_str()));
+
+        let ct = duration_from_epoch_now();
+        let idm_read = idms.proxy_read().await;
+
+        let pv = idm_read
+            .qs_read
+            .clone_partialvalue(k_attr.as_str(), assertion)?;
+
+        let ident = idm_read
+            .validate_ldap_session(&uat.effective_session, ct)
+            .map_err(|e| {
+                admin_error!("Invalid identity: {:?}", e);
+                e
+            })?;
+
+        // Search for the target as the bound identity so that the entry is reduced to what
+        // they may read - an attribute they can't read never compares as true.
+        let mut k_attrs = BTreeSet::new();
+        k_attrs.insert(k_attr.clone());
+        let se = SearchEvent::new_ext_impersonate_uuid(
+            &idm_read.qs_read,
+            ident,
+            &LdapFilter::Equality(attr, val),
+            Some(k_attrs),
+        )
+        .map_err(|e| {
+            admin_error!("failed to create search event -> {:?}", e);
+            e
+        })?;
+
+        let res = idm_read.qs_read.search_ext(&se).map_err(|e| {
+            admin_error!("search failure {:?}", e);
+            e
+        })?;
+
+        if res.is_empty() {
+            admin_info!("LDAP Compare - no such object");
+            Ok(LdapResultCode::NoSuchObject)
+        } else if res
+            .iter()
+            .any(|e| e.attribute_equality(k_attr.as_str(), &pv))
+        {
+            admin_info!("LDAP Compare success - true");
+            Ok(LdapResultCode::CompareTrue)
+        } else {
+            admin_info!("LDAP Compare success - false");
+            Ok(LdapResultCode::CompareFalse)
+        }
+    }
+
+    /// Compare is not one of the simple server operations, so it is handled from the
+    /// raw message.
+    pub async fn do_compare_op(
+        &self,
+        idms: &IdmServer,
+        msgid: i32,
+        cr: &LdapCompareRequest,
+        uat: Option<LdapBoundToken>,
+    ) -> Result<LdapResponseState, OperationError> {
+        let gen_result = |code: LdapResultCode, message: String| LdapMsg {
+            msgid,
+            op: LdapOp::CompareResult(LdapResult {
+                code,
+                matcheddn: "".to_string(),
+                message,
+                referral: vec![],
+            }),
+            ctrl: vec![],
+        };
+
+        let (lbt, bound) = match uat {
+            Some(u) => (u, false),
+            None => {
+                // Compare can occur without a bind, so bind first.
+                match self.do_bind(idms, "", "").await {
+                    Ok(Some(lbt)) => (lbt, true),
+                    Ok(None) => {
+                        return Ok(LdapResponseState::Respond(gen_result(
+                            LdapResultCode::InvalidCredentials,
+                            "".to_string(),
+                        )))
+                    }
+                    Err(e) => {
+                        let (rc, msg) = operationerr_to_ldapresultcode(e);
+                        return Ok(LdapResponseState::Respond(gen_result(rc, msg)));
+                    }
+                }
+            }
+        };
+
+        let msg = match self.do_compare(idms, cr, &lbt).await {
+            Ok(rc) => gen_result(rc, "".to_string()),
+            Err(e) => {
+                let (rc, msg) = operationerr_to_ldapresultcode(e);
+                gen_result(rc, msg)
+            }
+        };
+
+        if bound {
+            Ok(LdapResponseState::Bind(lbt, msg))
+        } else {
+            Ok(LdapResponseState::Respond(msg))
+        }
+    }
+
+    /// Change a password through the credential update subsystem, so that the same access
+    /// controls and password quality policy apply as to any other credential update.
+    #[instrument(level = "debug", skip_all)]
+    async fn do_password_modify(
+        &self,
+        idms: &IdmServer,
+        target_dn: Option<&str>,
+        old_password: Option<&str>,
+        new_password: &str,
+        uat: &LdapBoundToken,
+    ) -> Result<(), OperationError> {
+        admin_info!("Attempt LDAP Password Modify for {}", uat.spn);
+        let ct = duration_from_epoch_now();
+
+        let (ident, target) = {
+            let mut idm_auth = idms.auth_async().await;
+
+            let target = target_dn
+                .map(|dn| {
+                    let val = self
+                        .binddnre
+                        .captures(dn)
+                        .and_then(|caps| caps.name("val"))
+                        .map(|v| v.as_str())
+                        .ok_or_else(|| {
+                            request_error!("LDAP Password Modify failure - invalid dn");
+                            OperationError::InvalidRequestState
+                        })?;
+                    idm_auth.qs_read.name_to_uuid(val)
+                })
+                .transpose()?;
+
+            let r = idm_auth
+                .validate_ldap_password_modify_session(
+                    &uat.effective_session,
+                    target,
+                    old_password,
+                    ct,
+                )
+                .await?;
+            idm_auth.commit()?;
+            r
+        };
+
+        let cust = {
+            let mut idms_prox_write = idms.proxy_write(ct).await;
+            let (cust, _) = idms_prox_write
+                .init_credential_update(&InitCredentialUpdateEvent::new(ident, target), ct)?;
+            idms_prox_write.commit()?;
+            cust
+        };
+
+        let set_result = {
+            let cutxn = idms.cred_update_transaction_async().await;
+            cutxn
+                .credential_primary_set_password(&cust, ct, new_password)
+                .map(|_| ())
+        };
+
+        let mut idms_prox_write = idms.proxy_write(ct).await;
+        match set_result {
+            Ok(()) => {
+                idms_prox_write.commit_credential_update(&cust, ct)?;
+                idms_prox_write.commit()?;
+                admin_info!("LDAP Password Modify success");
+                Ok(())
+            }
+            Err(e) => {
+                if let Err(ce) = idms_prox_write
+                    .cancel_credential_update(&cust, ct)
+                    .and_then(|_| idms_prox_write.commit())
+                {
+                    admin_error!(?ce, "Failed to cancel credential update");
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Password modify (rfc3062) is not one of the simple server operations, so it is
+    /// handled from the raw message.
+    pub async fn do_password_modify_op(
+        &self,
+        idms: &IdmServer,
+        msgid: i32,
+        er: &LdapExtendedRequest,
+        uat: Option<LdapBoundToken>,
+    ) -> Result<LdapResponseState, OperationError> {
+        let gen_result = |code: LdapResultCode, message: String| {
+            LdapResponseState::Respond(LdapMsg {
+                msgid,
+                op: LdapOp::ExtendedResponse(LdapExtendedResponse {
+                    res: LdapResult {
+                        code,
+                        matcheddn: "".to_string(),
+                        message,
+                        referral: vec![],
+                    },
+                    name: None,
+                    value: None,
+                }),
+                ctrl: vec![],
+            })
+        };
+
+        let uat = match uat {
+            Some(u) => u,
+            None => {
+                return Ok(gen_result(
+                    LdapResultCode::UnwillingToPerform,
+                    "Unbound Connection".to_string(),
+                ))
+            }
+        };
+
+        let pmr = match LdapPasswordModifyRequest::try_from(er) {
+            Ok(pmr) => pmr,
+            Err(_) => {
+                return Ok(gen_result(
+                    LdapResultCode::ProtocolError,
+                    "Invalid password modify request".to_string(),
+                ))
+            }
+        };
+
+        // We don't generate passwords on behalf of the client.
+        let new_password = match pmr.new_password.as_deref() {
+            Some(pw) => pw,
+            None => {
+                return Ok(gen_result(
+                    LdapResultCode::UnwillingToPerform,
+                    "A new password must be provided".to_string(),
+                ))
+            }
+        };
+
+        match self
+            .do_password_modify(
+                idms,
+                pmr.user_identity.as_deref(),
+                pmr.old_password.as_deref(),
+                new_password,
+                &uat,
+            )
+            .await
+        {
+            Ok(()) => Ok(gen_result(LdapResultCode::Success, "".to_string())),
+            Err(e) => {
+                let (rc, msg) = operationerr_to_ldapresultcode(e);
+                Ok(gen_result(rc, msg))
+            }
+        }
+    }
+
+    /// Writes are not supported through ldap, other than an account changing the password
+    /// of itself with userPassword. The current password may be provided as a delete of the
+    /// old value, as some clients do.
+    pub async fn do_modify_op(
+        &self,
+        idms: &IdmServer,
+        msgid: i32,
+        mr: &LdapModifyRequest,
+        uat: Option<LdapBoundToken>,
+    ) -> Result<LdapResponseState, OperationError> {
+        let gen_result = |code: LdapResultCode, message: String| {
+            LdapResponseState::Respond(LdapMsg {
+                msgid,
+                op: LdapOp::ModifyResponse(LdapResult {
+                    code,
+                    matcheddn: "".to_string(),
+                    message,
+                    referral: vec![],
+                }),
+                ctrl: vec![],
+            })
+        };
+
+        let uat = match uat {
+            Some(u) => u,
+            None => {
+                return Ok(gen_result(
+                    LdapResultCode::UnwillingToPerform,
+                    "Unbound Connection".to_string(),
+                ))
+            }
+        };
+
+        let mut old_password = None;
+        let mut new_password = None;
+        for m in mr.changes.iter() {
+            if !m.modification.atype.eq_ignore_ascii_case("userpassword") {
+                return Ok(gen_result(
+                    LdapResultCode::UnwillingToPerform,
+                    "Only userPassword may be modified".to_string(),
+                ));
+            }
+            let value = match m.modification.vals.as_slice() {
+                [v] => match String::from_utf8(v.clone()) {
+                    Ok(s) => s,
+                    Err(_) => {
+                        return Ok(gen_result(
+                            LdapResultCode::InvalidAttributeSyntax,
+                            "userPassword must be a utf8 string".to_string(),
+                        ))
+                    }
+                },
+                _ => {
+                    return Ok(gen_result(
+                        LdapResultCode::ConstraintViolation,
+                        "userPassword must have exactly one value".to_string(),
+                    ))
+                }
+            };
+            match m.operation {
+                LdapModifyType::Delete => old_password = Some(value),
+                LdapModifyType::Add | LdapModifyType::Replace => new_password = Some(value),
+            }
+        }
+
+        let new_password = match new_password {
+            Some(pw) => pw,
+            None => {
+                return Ok(gen_result(
+                    LdapResultCode::UnwillingToPerform,
+                    "A new userPassword must be provided".to_string(),
+                ))
+            }
+        };
+
+        match self
+            .do_password_modify(
+                idms,
+                Some(mr.dn.as_str()),
+                old_password.as_deref(),
+                new_password.as_str(),
+                &uat,
+            )
+            .await
+        {
+            Ok(()) => Ok(gen_result(LdapResultCode::Success, "".to_string())),
+            Err(e) => {
+                let (rc, msg) = operationerr_to_ldapresultcode(e);
+                Ok(gen_result(rc, msg))
+            }
+        }
+    }
+
     async fn do_bind(
         &self,
         idms: &IdmServer,
@@ -431,6 +1172,7 @@ impl LdapServer {
         &self,
         idms: &IdmServer,
         server_op: ServerOps,
+        ctrl: &[LdapControl],
         uat: Option<LdapBoundToken>,
         eventid: &Uuid,
     ) -> Result<LdapResponseState, OperationError> {
@@ -446,39 +1188,49 @@ impl LdapServer {
                     let (rc, msg) = operationerr_to_ldapresultcode(e);
                     Ok(LdapResponseState::Respond(sbr.gen_error(rc, msg)))
                 }),
-            ServerOps::Search(sr) => match uat {
-                Some(u) => self
-                    .do_search(idms, &sr, &u)
-                    .await
-                    .map(LdapResponseState::MultiPartResponse)
-                    .or_else(|e| {
+            ServerOps::Search(sr) => {
+                let paged = match LdapPagedRequest::from_controls(ctrl) {
+                    Ok(paged) => paged,
+                    Err(e) => {
                         let (rc, msg) = operationerr_to_ldapresultcode(e);
-                        Ok(LdapResponseState::Respond(sr.gen_error(rc, msg)))
-                    }),
-                None => {
-                    // Search can occur without a bind, so bind first.
-                    let lbt = match self.do_bind(idms, "", "").await {
-                        Ok(Some(lbt)) => lbt,
-                        Ok(None) => {
-                            return Ok(LdapResponseState::Respond(
-                                sr.gen_error(LdapResultCode::InvalidCredentials, "".to_string()),
-                            ))
-                        }
-                        Err(e) => {
-                            let (rc, msg) = operationerr_to_ldapresultcode(e);
-                            return Ok(LdapResponseState::Respond(sr.gen_error(rc, msg)));
-                        }
-                    };
-                    // If okay, do the search.
-                    self.do_search(idms, &sr, &lbt)
+                        return Ok(LdapResponseState::Respond(sr.gen_error(rc, msg)));
+                    }
+                };
+                match uat {
+                    Some(u) => self
+                        .do_search_ext(idms, &sr, &u, paged)
                         .await
-                        .map(|r| LdapResponseState::BindMultiPartResponse(lbt, r))
+                        .map(LdapResponseState::MultiPartResponse)
                         .or_else(|e| {
                             let (rc, msg) = operationerr_to_ldapresultcode(e);
                             Ok(LdapResponseState::Respond(sr.gen_error(rc, msg)))
-                        })
+                        }),
+                    None => {
+                        // Search can occur without a bind, so bind first.
+                        let lbt = match self.do_bind(idms, "", "").await {
+                            Ok(Some(lbt)) => lbt,
+                            Ok(None) => {
+                                return Ok(LdapResponseState::Respond(sr.gen_error(
+                                    LdapResultCode::InvalidCredentials,
+                                    "".to_string(),
+                                )))
+                            }
+                            Err(e) => {
+                                let (rc, msg) = operationerr_to_ldapresultcode(e);
+                                return Ok(LdapResponseState::Respond(sr.gen_error(rc, msg)));
+                            }
+                        };
+                        // If okay, do the search.
+                        self.do_search_ext(idms, &sr, &lbt, paged)
+                            .await
+                            .map(|r| LdapResponseState::BindMultiPartResponse(lbt, r))
+                            .or_else(|e| {
+                                let (rc, msg) = operationerr_to_ldapresultcode(e);
+                                Ok(LdapResponseState::Respond(sr.gen_error(rc, msg)))
+                            })
+                    }
                 }
-            },
+            }
             ServerOps::Unbind(_) => {
                 // No need to notify on unbind (per rfc4511)
                 Ok(LdapResponseState::Unbind)
@@ -493,32 +1245,250 @@ impl LdapServer {
             },
         } // end match server op
     }
-}
 
-fn ldap_domain_to_dc(input: &str) -> String {
-    let mut output: String = String::new();
-    input.split('.').for_each(|dc| {
-        output.push_str("dc=");
-        output.push_str(dc);
-        #[allow(clippy::single_char_pattern, clippy::single_char_add_str)]
-        output.push_str(",");
-    });
-    // Remove the last ','
-    output.pop();
-    output
+    /// Format entries as an LDIF (RFC 2849) document, with the dns and attributes that an ldap
+    /// search for all attributes would return.
+    pub fn to_ldif(
+        &self,
+        qs: &QueryServerReadTransaction,
+        entries: &[Entry<EntryReduced, EntryCommitted>],
+    ) -> Result<String, OperationError> {
+        let mut ldif = String::from("version: 1\n");
+        for e in entries {
+            let le = e.to_ldap(
+                qs,
+                self.basedn.as_str(),
+                &self.dn_format,
+                &self.attr_map,
+                true,
+                &[],
+            )?;
+            ldif.push('\n');
+            ldif_record(&mut ldif, &le);
+        }
+        Ok(ldif)
+    }
 }
 
-fn operationerr_to_ldapresultcode(e: OperationError) -> (LdapResultCode, String) {
-    match e {
-        OperationError::InvalidRequestState => {
-            (LdapResultCode::ConstraintViolation, "".to_string())
+const LDAP_CONTROL_PAGED_RESULTS: &str = "1.2.840.113556.1.4.319";
+// https://www.rfc-editor.org/rfc/rfc3062
+pub const LDAP_PASSWORD_MODIFY_OID: &str = "1.3.6.1.4.1.4203.1.11.1";
+const LDAP_SCHEMA_DN: &str = "cn=schema";
+
+/// LDIF lines longer than this are folded.
+const LDIF_LINE_LENGTH: usize = 76;
+
+/// Append an LDIF line of an attribute value, which is base64 encoded if it isn't a
+/// SAFE-STRING, or ends in a space.
+fn ldif_line(ldif: &mut String, attr: &str, value: &[u8]) {
+    let safe = !matches!(value.first(), Some(b' ' | b':' | b'<'))
+        && value.last() != Some(&b' ')
+        && value
+            .iter()
+            .all(|b| b.is_ascii() && !matches!(b, 0 | b'\n' | b'\r'));
+    let line = if safe {
+        format!("{}: {}", attr, String::from_utf8_lossy(value))
+    } else {
+        format!("{}:: {}", attr, base64::encode(value))
+    };
+
+    // The line is ascii, so it can be split anywhere. Continuations begin with a space.
+    let mut rest = line.as_str();
+    let mut width = LDIF_LINE_LENGTH;
+    while !rest.is_empty() {
+        let (chunk, tail) = rest.split_at(width.min(rest.len()));
+        if width != LDIF_LINE_LENGTH {
+            ldif.push(' ');
         }
-        OperationError::InvalidAttributeName(s) | OperationError::InvalidAttribute(s) => {
+        ldif.push_str(chunk);
+        ldif.push('\n');
+        rest = tail;
+        width = LDIF_LINE_LENGTH - 1;
+    }
+}
+
+fn ldif_record(ldif: &mut String, entry: &LdapSearchResultEntry) {
+    ldif_line(ldif, "dn", entry.dn.as_bytes());
+    for attr in entry.attributes.iter() {
+        for v in attr.vals.iter() {
+            ldif_line(ldif, attr.atype.as_str(), v);
+        }
+    }
+}
+
+/// Limit a generated entry (rootdse, schema) to the attributes the client requested.
+fn ldap_filter_requested_attrs(
+    mut entry: LdapSearchResultEntry,
+    attrs: &[String],
+) -> LdapSearchResultEntry {
+    if !(attrs.is_empty() || attrs.iter().any(|a| a == "*" || a == "+")) {
+        entry
+            .attributes
+            .retain(|pa| attrs.iter().any(|a| a.eq_ignore_ascii_case(&pa.atype)));
+    }
+    entry
+}
+
+/// Kanidm schema has no assigned OIDs, so we derive them from the schema uuid (X.667).
+fn ldap_schema_oid(u: Uuid) -> String {
+    format!("2.25.{}", u.as_u128())
+}
+
+fn ldap_schema_qdstring(input: &str) -> String {
+    format!("'{}'", input.replace('\\', "\\5C").replace('\'', "\\27"))
+}
+
+fn ldap_schema_oids(names: &[AttrString]) -> Option<String> {
+    match names {
+        [] => None,
+        [n] => Some(n.to_string()),
+        ns => Some(format!(
+            "( {} )",
+            ns.iter()
+                .map(|n| n.as_str())
+                .collect::<Vec<_>>()
+                .join(" $ ")
+        )),
+    }
+}
+
+fn ldap_syntax_type(syntax: &SyntaxType) -> (&'static str, &'static str) {
+    // (equality matching rule, ldap syntax oid)
+    match syntax {
+        SyntaxType::Utf8String => ("caseExactMatch", "1.3.6.1.4.1.1466.115.121.1.15"),
+        SyntaxType::Utf8StringInsensitive
+        | SyntaxType::Utf8StringIname
+        | SyntaxType::SecurityPrincipalName
+        | SyntaxType::EmailAddress
+        | SyntaxType::OauthScope => ("caseIgnoreMatch", "1.3.6.1.4.1.1466.115.121.1.15"),
+        SyntaxType::Url | SyntaxType::NsUniqueId | SyntaxType::SshKey => {
+            ("caseExactMatch", "1.3.6.1.4.1.1466.115.121.1.15")
+        }
+        SyntaxType::Uuid => ("uuidMatch", "1.3.6.1.1.16.1"),
+        SyntaxType::ReferenceUuid | SyntaxType::OauthScopeMap => {
+            ("distinguishedNameMatch", "1.3.6.1.4.1.1466.115.121.1.12")
+        }
+        SyntaxType::Boolean => ("booleanMatch", "1.3.6.1.4.1.1466.115.121.1.7"),
+        SyntaxType::Uint32 | SyntaxType::SyntaxId | SyntaxType::IndexId => {
+            ("integerMatch", "1.3.6.1.4.1.1466.115.121.1.27")
+        }
+        SyntaxType::DateTime => ("generalizedTimeMatch", "1.3.6.1.4.1.1466.115.121.1.24"),
+        _ => ("octetStringMatch", "1.3.6.1.4.1.1466.115.121.1.40"),
+    }
+}
+
+fn ldap_schema_attribute_type(a: &SchemaAttribute) -> String {
+    let (equality, syntax) = ldap_syntax_type(&a.syntax);
+    let mut s = format!(
+        "( {} NAME {} DESC {} EQUALITY {} SYNTAX {}",
+        ldap_schema_oid(a.uuid),
+        ldap_schema_qdstring(a.name.as_str()),
+        ldap_schema_qdstring(a.description.as_str()),
+        equality,
+        syntax
+    );
+    if !a.multivalue {
+        s.push_str(" SINGLE-VALUE");
+    }
+    s.push_str(" )");
+    s
+}
+
+fn ldap_schema_object_class(c: &SchemaClass) -> String {
+    let must: Vec<_> = c.systemmust.iter().chain(c.must.iter()).cloned().collect();
+    let may: Vec<_> = c.systemmay.iter().chain(c.may.iter()).cloned().collect();
+
+    // Kanidm classes are freely composed, so they are all presented as auxiliary.
+    let mut s = format!(
+        "( {} NAME {} DESC {} SUP top AUXILIARY",
+        ldap_schema_oid(c.uuid),
+        ldap_schema_qdstring(c.name.as_str()),
+        ldap_schema_qdstring(c.description.as_str())
+    );
+    if let Some(must) = ldap_schema_oids(&must) {
+        s.push_str(" MUST ");
+        s.push_str(&must);
+    }
+    if let Some(may) = ldap_schema_oids(&may) {
+        s.push_str(" MAY ");
+        s.push_str(&may);
+    }
+    s.push_str(" )");
+    s
+}
+
+/// Generate the RFC 4512 subschema subentry from the live schema.
+fn ldap_schema_subentry<S: SchemaTransaction>(schema: &S) -> LdapSearchResultEntry {
+    let mut attrs: Vec<_> = schema.get_attributes().values().collect();
+    attrs.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+    let mut classes: Vec<_> = schema.get_classes().values().collect();
+    classes.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+
+    LdapSearchResultEntry {
+        dn: LDAP_SCHEMA_DN.to_string(),
+        attributes: vec![
+            LdapPartialAttribute {
+                atype: "objectClass".to_string(),
+                vals: vec![
+                    "top".as_bytes().to_vec(),
+                    "subschema".as_bytes().to_vec(),
+                    "subentry".as_bytes().to_vec(),
+                ],
+            },
+            LdapPartialAttribute {
+                atype: "cn".to_string(),
+                vals: vec!["schema".as_bytes().to_vec()],
+            },
+            LdapPartialAttribute {
+                atype: "attributeTypes".to_string(),
+                vals: attrs
+                    .into_iter()
+                    .map(|a| ldap_schema_attribute_type(a).into_bytes())
+                    .collect(),
+            },
+            LdapPartialAttribute {
+                atype: "objectClasses".to_string(),
+                vals: classes
+                    .into_iter()
+                    .map(|c| ldap_schema_object_class(c).into_bytes())
+                    .collect(),
+            },
+        ],
+    }
+}
+
+fn ldap_domain_to_dc(input: &str) -> String {
+    let mut output: String = String::new();
+    input.split('.').for_each(|dc| {
+        output.push_str("dc=");
+        output.push_str(dc);
+        #[allow(clippy::single_char_pattern, clippy::single_char_add_str)]
+        output.push_str(",");
+    });
+    // Remove the last ','
+    output.pop();
+    output
+}
+
+fn operationerr_to_ldapresultcode(e: OperationError) -> (LdapResultCode, String) {
+    match e {
+        OperationError::InvalidRequestState => {
+            (LdapResultCode::ConstraintViolation, "".to_string())
+        }
+        OperationError::InvalidAttributeName(s) | OperationError::InvalidAttribute(s) => {
             (LdapResultCode::InvalidAttributeSyntax, s)
         }
         OperationError::SchemaViolation(se) => {
             (LdapResultCode::UnwillingToPerform, format!("{:?}", se))
         }
+        OperationError::NotAuthenticated => (LdapResultCode::InvalidCredentials, "".to_string()),
+        OperationError::AccessDenied | OperationError::NotAuthorised => {
+            (LdapResultCode::UnwillingToPerform, format!("{:?}", e))
+        }
+        OperationError::PasswordQuality(feedback) => (
+            LdapResultCode::ConstraintViolation,
+            format!("{:?}", feedback),
+        ),
         e => (LdapResultCode::Other, format!("{:?}", e)),
     }
 }
@@ -535,6 +1505,8 @@ pub(crate) fn ldap_all_vattrs() -> Vec<String> {
         "sshpublickey".to_string(),
         "cn".to_string(),
         "uidnumber".to_string(),
+        "uid".to_string(),
+        "gecos".to_string(),
     ]
 }
 
@@ -555,10 +1527,22 @@ pub(crate) fn ldap_vattr_map(input: &str) -> &str {
         "sshpublickey" => "ssh_publickey",
         "cn" => "name",
         "uidnumber" => "gidnumber",
+        "uid" => "name",
+        "gecos" => "displayname",
         a => a,
     }
 }
 
+/// Map the attribute of an incoming rdn to the attribute it was formed from, as the rdn
+/// attribute may be one of the configured dn formats.
+fn ldap_rdn_attr_map(input: &str) -> String {
+    let input = input.to_lowercase();
+    match input.as_str() {
+        "uid" | "cn" => "name".to_string(),
+        _ => input,
+    }
+}
+
 #[inline]
 pub(crate) fn ldap_attr_filter_map(input: &str) -> AttrString {
     AttrString::from(ldap_vattr_map(&input.to_lowercase()))
@@ -573,16 +1557,61 @@ mod tests {
     use compact_jwt::{Jws, JwsUnverified};
     use hashbrown::HashSet;
     use kanidm_proto::v1::ApiToken;
-    use ldap3_proto::proto::{LdapFilter, LdapOp, LdapSearchScope};
+    use ldap3_proto::proto::{
+        LdapCompareRequest, LdapControl, LdapFilter, LdapMatchingRuleAssertion, LdapOp,
+        LdapSearchScope, LdapSubstringFilter,
+    };
     use ldap3_proto::simple::*;
 
+    use crate::credential::policy::CryptoPolicy;
+    use crate::credential::Credential;
     use crate::event::{CreateEvent, ModifyEvent};
     use crate::idm::event::UnixPasswordChangeEvent;
     use crate::idm::serviceaccount::GenerateApiTokenEvent;
-    use crate::ldap::{LdapServer, LdapSession};
+    use crate::ldap::{
+        LdapBoundToken, LdapPagedRequest, LdapResponseState, LdapServer, LdapSession,
+    };
 
     const TEST_PASSWORD: &'static str = "ntaoeuntnaoeuhraohuercahu😍";
 
+    #[test]
+    fn test_ldif_record() {
+        let entry = LdapSearchResultEntry {
+            dn: "spn=testperson1@example.com,dc=example,dc=com".to_string(),
+            attributes: vec![
+                LdapPartialAttribute {
+                    atype: "name".to_string(),
+                    vals: vec![b"testperson1".to_vec()],
+                },
+                LdapPartialAttribute {
+                    atype: "displayname".to_string(),
+                    vals: vec!["Test 😍".as_bytes().to_vec(), b" leading".to_vec()],
+                },
+                LdapPartialAttribute {
+                    atype: "description".to_string(),
+                    vals: vec![vec![b'a'; 80]],
+                },
+            ],
+        };
+        let mut ldif = String::new();
+        super::ldif_record(&mut ldif, &entry);
+
+        let mut lines = ldif.lines();
+        assert_eq!(
+            lines.next(),
+            Some("dn: spn=testperson1@example.com,dc=example,dc=com")
+        );
+        assert_eq!(lines.next(), Some("name: testperson1"));
+        assert_eq!(lines.next(), Some("displayname:: VGVzdCDwn5iN"));
+        assert_eq!(lines.next(), Some("displayname:: IGxlYWRpbmc="));
+        // The long value is folded, and continues on a line that begins with a space.
+        let first = lines.next().expect("Missing line");
+        assert_eq!(first.len(), 76);
+        assert_eq!(first, format!("description: {}", "a".repeat(63)));
+        assert_eq!(lines.next(), Some(format!(" {}", "a".repeat(17)).as_str()));
+        assert_eq!(lines.next(), None);
+    }
+
     #[test]
     fn test_ldap_simple_bind() {
         run_idm_test!(
@@ -754,6 +1783,92 @@ mod tests {
         }};
     }
 
+    #[test]
+    fn test_ldap_bind_primary_credential_and_self_access() {
+        run_idm_test!(
+            |_qs: &QueryServer, idms: &IdmServer, _idms_delayed: &IdmServerDelayed| {
+                let ldaps = LdapServer::new(idms).expect("failed to start ldap");
+
+                let tuuid = uuid::uuid!("cc8e95b4-c24f-4d68-ba54-8bed76f63930");
+
+                // A posix account with a password only primary credential, and no unix password.
+                {
+                    let p = CryptoPolicy::minimum();
+                    let cred = Credential::new_password_only(&p, TEST_PASSWORD).unwrap();
+                    let e1 = entry_init!(
+                        ("class", Value::new_class("object")),
+                        ("class", Value::new_class("person")),
+                        ("class", Value::new_class("account")),
+                        ("class", Value::new_class("posixaccount")),
+                        ("name", Value::new_iname("testperson1")),
+                        ("uuid", Value::new_uuid(tuuid)),
+                        ("description", Value::new_utf8s("testperson1")),
+                        ("displayname", Value::new_utf8s("testperson1")),
+                        ("legalname", Value::new_utf8s("Test Person One")),
+                        ("gidnumber", Value::new_uint32(12345678)),
+                        ("primary_credential", Value::new_credential("primary", cred))
+                    );
+
+                    let mut server_txn =
+                        task::block_on(idms.proxy_write(duration_from_epoch_now()));
+                    let ce = CreateEvent::new_internal(vec![e1]);
+                    assert!(server_txn
+                        .qs_write
+                        .create(&ce)
+                        .and_then(|_| server_txn.commit())
+                        .is_ok());
+                }
+
+                let sr = SearchRequest {
+                    msgid: 1,
+                    base: "dc=example,dc=com".to_string(),
+                    scope: LdapSearchScope::Subtree,
+                    filter: LdapFilter::Equality("name".to_string(), "testperson1".to_string()),
+                    attrs: vec!["name".to_string(), "legalname".to_string()],
+                };
+
+                // Anonymous can not see the legalname.
+                let anon_t = task::block_on(ldaps.do_bind(idms, "", ""))
+                    .unwrap()
+                    .unwrap();
+                let r1 = task::block_on(ldaps.do_search(idms, &sr, &anon_t)).unwrap();
+                assert!(r1.len() == 2);
+                match &r1[0].op {
+                    LdapOp::SearchResultEntry(lsre) => {
+                        assert!(!lsre.attributes.iter().any(|a| a.atype == "legalname"));
+                    }
+                    _ => assert!(false),
+                };
+
+                // Bind with the primary credential password.
+                let person_t = task::block_on(ldaps.do_bind(idms, "testperson1", TEST_PASSWORD))
+                    .unwrap()
+                    .unwrap();
+                assert!(person_t.effective_session == LdapSession::UnixBind(tuuid));
+
+                // The bound account reads with its own access.
+                let r1 = task::block_on(ldaps.do_search(idms, &sr, &person_t)).unwrap();
+                assert!(r1.len() == 2);
+                match &r1[0].op {
+                    LdapOp::SearchResultEntry(lsre) => {
+                        assert_entry_contains!(
+                            lsre,
+                            "spn=testperson1@example.com,dc=example,dc=com",
+                            ("name", "testperson1"),
+                            ("legalname", "Test Person One")
+                        );
+                    }
+                    _ => assert!(false),
+                };
+
+                // Bad password.
+                assert!(task::block_on(ldaps.do_bind(idms, "testperson1", "test"))
+                    .unwrap()
+                    .is_none());
+            }
+        )
+    }
+
     #[test]
     fn test_ldap_virtual_attribute_generation() {
         run_idm_test!(
@@ -1046,4 +2161,817 @@ mod tests {
             }
         )
     }
+
+    #[test]
+    fn test_ldap_configured_basedn() {
+        run_idm_test!(
+            |_qs: &QueryServer, idms: &IdmServer, _idms_delayed: &IdmServerDelayed| {
+                // Setup a user, and override the basedn.
+                {
+                    let e1 = entry_init!(
+                        ("class", Value::new_class("object")),
+                        ("class", Value::new_class("person")),
+                        ("class", Value::new_class("account")),
+                        ("name", Value::new_iname("testperson1")),
+                        (
+                            "uuid",
+                            Value::new_uuids("cc8e95b4-c24f-4d68-ba54-8bed76f63930").expect("uuid")
+                        ),
+                        ("description", Value::new_utf8s("testperson1")),
+                        ("displayname", Value::new_utf8s("testperson1"))
+                    );
+
+                    let mut server_txn =
+                        task::block_on(idms.proxy_write(duration_from_epoch_now()));
+                    let ce = CreateEvent::new_internal(vec![e1]);
+                    assert!(server_txn.qs_write.create(&ce).is_ok());
+                    assert!(server_txn
+                        .qs_write
+                        .internal_modify_uuid(
+                            UUID_DOMAIN_INFO,
+                            &ModifyList::new_purge_and_set(
+                                "domain_ldap_basedn",
+                                Value::new_iutf8("o=kanidm")
+                            )
+                        )
+                        .is_ok());
+                    assert!(server_txn.commit().is_ok());
+                }
+
+                let ldaps = LdapServer::new(idms).expect("failed to start ldap");
+
+                // The rootdse advertises the configured basedn.
+                assert!(ldaps.rootdse.attributes.iter().any(|a| {
+                    a.atype == "defaultnamingcontext"
+                        && a.vals == vec!["o=kanidm".as_bytes().to_vec()]
+                }));
+
+                let anon_t = task::block_on(ldaps.do_bind(idms, "", ""))
+                    .unwrap()
+                    .unwrap();
+
+                let sr = SearchRequest {
+                    msgid: 1,
+                    base: "o=kanidm".to_string(),
+                    scope: LdapSearchScope::Subtree,
+                    filter: LdapFilter::Equality("name".to_string(), "testperson1".to_string()),
+                    attrs: vec!["name".to_string()],
+                };
+                let r1 = task::block_on(ldaps.do_search(idms, &sr, &anon_t)).unwrap();
+                assert!(r1.len() == 2);
+                match &r1[0].op {
+                    LdapOp::SearchResultEntry(lsre) => {
+                        assert_entry_contains!(
+                            lsre,
+                            "spn=testperson1@example.com,o=kanidm",
+                            ("name", "testperson1")
+                        );
+                    }
+                    _ => assert!(false),
+                };
+
+                // The basedn derived from the domain name is no longer served.
+                let sr = SearchRequest {
+                    base: "dc=example,dc=com".to_string(),
+                    ..sr
+                };
+                assert!(task::block_on(ldaps.do_search(idms, &sr, &anon_t)).is_err());
+            }
+        )
+    }
+
+    #[test]
+    fn test_ldap_simple_paged_results() {
+        run_idm_test!(
+            |_qs: &QueryServer, idms: &IdmServer, _idms_delayed: &IdmServerDelayed| {
+                let ldaps = LdapServer::new(idms).expect("failed to start ldap");
+
+                // Setup a set of groups to page over.
+                {
+                    let entries: Vec<_> = (0..5)
+                        .map(|i| {
+                            entry_init!(
+                                ("class", Value::new_class("object")),
+                                ("class", Value::new_class("group")),
+                                ("name", Value::new_iname(&format!("pagedgroup{}", i)))
+                            )
+                        })
+                        .collect();
+
+                    let mut server_txn =
+                        task::block_on(idms.proxy_write(duration_from_epoch_now()));
+                    let ce = CreateEvent::new_internal(entries);
+                    assert!(server_txn
+                        .qs_write
+                        .create(&ce)
+                        .and_then(|_| server_txn.commit())
+                        .is_ok());
+                }
+
+                let anon_t = task::block_on(ldaps.do_bind(idms, "", ""))
+                    .unwrap()
+                    .unwrap();
+
+                let sr = SearchRequest {
+                    msgid: 1,
+                    base: "dc=example,dc=com".to_string(),
+                    scope: LdapSearchScope::Subtree,
+                    filter: LdapFilter::Substring(
+                        "name".to_string(),
+                        LdapSubstringFilter {
+                            initial: Some("pagedgroup".to_string()),
+                            any: vec![],
+                            final_: None,
+                        },
+                    ),
+                    attrs: vec!["name".to_string()],
+                };
+
+                let page = |cookie: String| {
+                    let paged =
+                        LdapPagedRequest::from_controls(&[LdapControl::SimplePagedResults {
+                            size: 2,
+                            cookie,
+                        }])
+                        .expect("invalid paged control");
+                    let mut r = task::block_on(ldaps.do_search_ext(idms, &sr, &anon_t, paged))
+                        .expect("paged search failed");
+                    let done = r.pop().expect("no done message");
+                    let next = match done.ctrl.as_slice() {
+                        [LdapControl::SimplePagedResults { size: 0, cookie }] => cookie.clone(),
+                        _ => panic!("paged results control missing"),
+                    };
+                    let names: Vec<_> = r
+                        .into_iter()
+                        .map(|m| match m.op {
+                            LdapOp::SearchResultEntry(lsre) => lsre
+                                .attributes
+                                .into_iter()
+                                .find(|a| a.atype == "name")
+                                .and_then(|a| a.vals.into_iter().next())
+                                .and_then(|v| String::from_utf8(v).ok())
+                                .expect("name missing"),
+                            _ => panic!("unexpected ldap op"),
+                        })
+                        .collect();
+                    (names, next)
+                };
+
+                // Walk the pages until the server returns an empty cookie.
+                let mut seen = Vec::new();
+                let mut cookie = String::new();
+                let mut pages = 0;
+                loop {
+                    let (names, next) = page(cookie);
+                    assert!(names.len() <= 2);
+                    seen.extend(names);
+                    pages += 1;
+                    if next.is_empty() {
+                        break;
+                    }
+                    cookie = next;
+                }
+
+                assert!(pages == 3);
+                let order = seen.clone();
+                seen.sort_unstable();
+                seen.dedup();
+                assert!(seen.len() == 5);
+
+                // Removing an entry of an earlier page does not skip an entry of the next.
+                let (names, cookie) = page(String::new());
+                assert!(names == order[..2].to_vec());
+                {
+                    let filt = filter!(f_eq("name", PartialValue::new_iname(&names[0])));
+                    let mut server_txn =
+                        task::block_on(idms.proxy_write(duration_from_epoch_now()));
+                    assert!(server_txn
+                        .qs_write
+                        .internal_delete(&filt)
+                        .and_then(|_| server_txn.commit())
+                        .is_ok());
+                }
+                let (names, _) = page(cookie);
+                assert!(names == order[2..4].to_vec());
+
+                // A malformed cookie is rejected.
+                assert!(
+                    LdapPagedRequest::from_controls(&[LdapControl::SimplePagedResults {
+                        size: 2,
+                        cookie: "invalid".to_string(),
+                    }])
+                    .is_err()
+                );
+            }
+        )
+    }
+
+    #[test]
+    fn test_ldap_rootdse_and_schema_subentry() {
+        run_idm_test!(
+            |_qs: &QueryServer, idms: &IdmServer, _idms_delayed: &IdmServerDelayed| {
+                let ldaps = LdapServer::new(idms).expect("failed to start ldap");
+
+                let anon_t = task::block_on(ldaps.do_bind(idms, "", ""))
+                    .unwrap()
+                    .unwrap();
+
+                // Clients probe the rootdse for specific attributes.
+                let sr = SearchRequest {
+                    msgid: 1,
+                    base: "".to_string(),
+                    scope: LdapSearchScope::Base,
+                    filter: LdapFilter::Present("objectClass".to_string()),
+                    attrs: vec![
+                        "namingContexts".to_string(),
+                        "supportedcontrol".to_string(),
+                        "subschemaSubentry".to_string(),
+                    ],
+                };
+                let r1 = task::block_on(ldaps.do_search(idms, &sr, &anon_t)).unwrap();
+                assert!(r1.len() == 2);
+                match &r1[0].op {
+                    LdapOp::SearchResultEntry(lsre) => {
+                        assert!(lsre.attributes.len() == 3);
+                        assert_entry_contains!(
+                            lsre,
+                            "",
+                            ("namingContexts", "dc=example,dc=com"),
+                            ("supportedControl", "1.2.840.113556.1.4.319"),
+                            ("subschemaSubentry", "cn=schema")
+                        );
+                    }
+                    _ => assert!(false),
+                };
+
+                // The schema subentry is generated from the live schema.
+                let sr = SearchRequest {
+                    msgid: 1,
+                    base: "cn=schema".to_string(),
+                    scope: LdapSearchScope::Base,
+                    filter: LdapFilter::Equality(
+                        "objectClass".to_string(),
+                        "subschema".to_string(),
+                    ),
+                    attrs: vec!["+".to_string()],
+                };
+                let r1 = task::block_on(ldaps.do_search(idms, &sr, &anon_t)).unwrap();
+                assert!(r1.len() == 2);
+                match &r1[0].op {
+                    LdapOp::SearchResultEntry(lsre) => {
+                        assert!(lsre.dn == "cn=schema");
+                        let attribute_types = lsre
+                            .attributes
+                            .iter()
+                            .find(|a| a.atype == "attributeTypes")
+                            .expect("no attributeTypes");
+                        assert!(attribute_types.vals.iter().any(|v| {
+                            let v = std::str::from_utf8(v).unwrap();
+                            v.contains("NAME 'displayname'")
+                                && v.contains("EQUALITY caseExactMatch")
+                                && v.contains("SINGLE-VALUE")
+                        }));
+                        let object_classes = lsre
+                            .attributes
+                            .iter()
+                            .find(|a| a.atype == "objectClasses")
+                            .expect("no objectClasses");
+                        assert!(object_classes.vals.iter().any(|v| {
+                            let v = std::str::from_utf8(v).unwrap();
+                            v.starts_with("( 2.25.") && v.contains("NAME 'person'")
+                        }));
+                    }
+                    _ => assert!(false),
+                };
+
+                // It is not part of any subtree.
+                let sr = SearchRequest {
+                    scope: LdapSearchScope::Subtree,
+                    ..sr
+                };
+                let r1 = task::block_on(ldaps.do_search(idms, &sr, &anon_t)).unwrap();
+                assert!(r1.len() == 1);
+            }
+        )
+    }
+
+    #[test]
+    fn test_ldap_compare() {
+        run_idm_test!(
+            |_qs: &QueryServer, idms: &IdmServer, _idms_delayed: &IdmServerDelayed| {
+                let ldaps = LdapServer::new(idms).expect("failed to start ldap");
+
+                // Setup a user and a group they are a member of.
+                {
+                    let e1 = entry_init!(
+                        ("class", Value::new_class("object")),
+                        ("class", Value::new_class("person")),
+                        ("class", Value::new_class("account")),
+                        ("name", Value::new_iname("testperson1")),
+                        (
+                            "uuid",
+                            Value::new_uuids("cc8e95b4-c24f-4d68-ba54-8bed76f63930").expect("uuid")
+                        ),
+                        ("description", Value::new_utf8s("testperson1")),
+                        ("displayname", Value::new_utf8s("testperson1"))
+                    );
+                    let e2 = entry_init!(
+                        ("class", Value::new_class("object")),
+                        ("class", Value::new_class("group")),
+                        ("name", Value::new_iname("testgroup1")),
+                        (
+                            "member",
+                            Value::new_refer_s("cc8e95b4-c24f-4d68-ba54-8bed76f63930")
+                                .expect("uuid")
+                        )
+                    );
+
+                    let mut server_txn =
+                        task::block_on(idms.proxy_write(duration_from_epoch_now()));
+                    let ce = CreateEvent::new_internal(vec![e1, e2]);
+                    assert!(server_txn
+                        .qs_write
+                        .create(&ce)
+                        .and_then(|_| server_txn.commit())
+                        .is_ok());
+                }
+
+                let anon_t = task::block_on(ldaps.do_bind(idms, "", ""))
+                    .unwrap()
+                    .unwrap();
+
+                let compare = |dn: &str, atype: &str, val: &str| {
+                    let cr = LdapCompareRequest {
+                        dn: dn.to_string(),
+                        atype: atype.to_string(),
+                        val: val.as_bytes().to_vec(),
+                    };
+                    match task::block_on(ldaps.do_compare_op(idms, 1, &cr, Some(anon_t.clone()))) {
+                        Ok(LdapResponseState::Respond(LdapMsg {
+                            op: LdapOp::CompareResult(res),
+                            ..
+                        })) => res.code,
+                        _ => panic!("unexpected compare response"),
+                    }
+                };
+
+                // Membership can be tested with either a dn or a name.
+                assert!(
+                    compare(
+                        "spn=testgroup1@example.com,dc=example,dc=com",
+                        "member",
+                        "spn=testperson1@example.com,dc=example,dc=com"
+                    ) == LdapResultCode::CompareTrue
+                );
+                assert!(
+                    compare(
+                        "spn=testgroup1@example.com,dc=example,dc=com",
+                        "member",
+                        "testperson1"
+                    ) == LdapResultCode::CompareTrue
+                );
+                assert!(
+                    compare(
+                        "spn=testgroup1@example.com,dc=example,dc=com",
+                        "member",
+                        "spn=admin@example.com,dc=example,dc=com"
+                    ) == LdapResultCode::CompareFalse
+                );
+                assert!(
+                    compare(
+                        "spn=testperson1@example.com,dc=example,dc=com",
+                        "displayname",
+                        "testperson1"
+                    ) == LdapResultCode::CompareTrue
+                );
+
+                // An entry that doesn't exist can't be compared.
+                assert!(
+                    compare(
+                        "spn=nosuchgroup@example.com,dc=example,dc=com",
+                        "member",
+                        "testperson1"
+                    ) == LdapResultCode::NoSuchObject
+                );
+            }
+        )
+    }
+
+    #[test]
+    fn test_ldap_configured_dn_format() {
+        run_idm_test!(
+            |_qs: &QueryServer, idms: &IdmServer, _idms_delayed: &IdmServerDelayed| {
+                // Setup a user in a group, and present people by uid and groups by cn.
+                {
+                    let e1 = entry_init!(
+                        ("class", Value::new_class("object")),
+                        ("class", Value::new_class("person")),
+                        ("class", Value::new_class("account")),
+                        ("name", Value::new_iname("testperson1")),
+                        (
+                            "uuid",
+                            Value::new_uuids("cc8e95b4-c24f-4d68-ba54-8bed76f63930").expect("uuid")
+                        ),
+                        ("description", Value::new_utf8s("testperson1")),
+                        ("displayname", Value::new_utf8s("testperson1"))
+                    );
+                    let e2 = entry_init!(
+                        ("class", Value::new_class("object")),
+                        ("class", Value::new_class("group")),
+                        ("name", Value::new_iname("testgroup1")),
+                        (
+                            "member",
+                            Value::new_refer_s("cc8e95b4-c24f-4d68-ba54-8bed76f63930")
+                                .expect("uuid")
+                        )
+                    );
+
+                    let mut server_txn =
+                        task::block_on(idms.proxy_write(duration_from_epoch_now()));
+                    let ce = CreateEvent::new_internal(vec![e1, e2]);
+                    assert!(server_txn.qs_write.create(&ce).is_ok());
+                    assert!(server_txn
+                        .qs_write
+                        .internal_modify_uuid(
+                            UUID_DOMAIN_INFO,
+                            &ModifyList::new_list(vec![
+                                m_pres("domain_ldap_rdn_attr", &Value::new_iutf8("uid")),
+                                m_pres("domain_ldap_rdn_attr", &Value::new_iutf8("group:cn")),
+                            ])
+                        )
+                        .is_ok());
+                    assert!(server_txn.commit().is_ok());
+                }
+
+                let ldaps = LdapServer::new(idms).expect("failed to start ldap");
+
+                let anon_t = task::block_on(ldaps.do_bind(idms, "", ""))
+                    .unwrap()
+                    .unwrap();
+
+                // Incoming dns in the configured format are mapped back to the entry.
+                let sr = SearchRequest {
+                    msgid: 1,
+                    base: "uid=testperson1,dc=example,dc=com".to_string(),
+                    scope: LdapSearchScope::Base,
+                    filter: LdapFilter::Present("objectClass".to_string()),
+                    attrs: vec!["name".to_string(), "memberof".to_string()],
+                };
+                let r1 = task::block_on(ldaps.do_search(idms, &sr, &anon_t)).unwrap();
+                assert!(r1.len() == 2);
+                match &r1[0].op {
+                    LdapOp::SearchResultEntry(lsre) => {
+                        assert_entry_contains!(
+                            lsre,
+                            "uid=testperson1,dc=example,dc=com",
+                            ("name", "testperson1"),
+                            ("memberof", "cn=testgroup1,dc=example,dc=com")
+                        );
+                    }
+                    _ => assert!(false),
+                };
+
+                let sr = SearchRequest {
+                    base: "cn=testgroup1,dc=example,dc=com".to_string(),
+                    attrs: vec!["member".to_string()],
+                    ..sr
+                };
+                let r1 = task::block_on(ldaps.do_search(idms, &sr, &anon_t)).unwrap();
+                assert!(r1.len() == 2);
+                match &r1[0].op {
+                    LdapOp::SearchResultEntry(lsre) => {
+                        assert_entry_contains!(
+                            lsre,
+                            "cn=testgroup1,dc=example,dc=com",
+                            ("member", "uid=testperson1,dc=example,dc=com")
+                        );
+                    }
+                    _ => assert!(false),
+                };
+            }
+        )
+    }
+
+    #[test]
+    fn test_ldap_password_modify() {
+        run_idm_test!(
+            |_qs: &QueryServer, idms: &IdmServer, _idms_delayed: &IdmServerDelayed| {
+                let ldaps = LdapServer::new(idms).expect("failed to start ldap");
+
+                let tuuid = uuid::uuid!("cc8e95b4-c24f-4d68-ba54-8bed76f63930");
+                let new_password = "dn3XwJ7k tuaoe cahu ntaoeu 🦀 tnhs";
+
+                {
+                    let p = CryptoPolicy::minimum();
+                    let cred = Credential::new_password_only(&p, TEST_PASSWORD).unwrap();
+                    let e1 = entry_init!(
+                        ("class", Value::new_class("object")),
+                        ("class", Value::new_class("person")),
+                        ("class", Value::new_class("account")),
+                        ("name", Value::new_iname("testperson1")),
+                        ("uuid", Value::new_uuid(tuuid)),
+                        ("description", Value::new_utf8s("testperson1")),
+                        ("displayname", Value::new_utf8s("testperson1")),
+                        ("primary_credential", Value::new_credential("primary", cred))
+                    );
+
+                    let mut server_txn =
+                        task::block_on(idms.proxy_write(duration_from_epoch_now()));
+                    let ce = CreateEvent::new_internal(vec![e1]);
+                    assert!(server_txn
+                        .qs_write
+                        .create(&ce)
+                        .and_then(|_| server_txn.commit())
+                        .is_ok());
+                }
+
+                let modify = |uat: &LdapBoundToken, old: Option<&str>, new: &str| {
+                    let mut changes = Vec::new();
+                    if let Some(old) = old {
+                        changes.push(LdapModify {
+                            operation: LdapModifyType::Delete,
+                            modification: LdapPartialAttribute {
+                                atype: "userPassword".to_string(),
+                                vals: vec![old.as_bytes().to_vec()],
+                            },
+                        });
+                    }
+                    changes.push(LdapModify {
+                        operation: LdapModifyType::Add,
+                        modification: LdapPartialAttribute {
+                            atype: "userPassword".to_string(),
+                            vals: vec![new.as_bytes().to_vec()],
+                        },
+                    });
+                    let mr = LdapModifyRequest {
+                        dn: "spn=testperson1@example.com,dc=example,dc=com".to_string(),
+                        changes,
+                    };
+                    match task::block_on(ldaps.do_modify_op(idms, 1, &mr, Some(uat.clone()))) {
+                        Ok(LdapResponseState::Respond(LdapMsg {
+                            op: LdapOp::ModifyResponse(res),
+                            ..
+                        })) => res.code,
+                        _ => panic!("unexpected modify response"),
+                    }
+                };
+
+                let anon_t = task::block_on(ldaps.do_bind(idms, "", ""))
+                    .unwrap()
+                    .unwrap();
+                let person_t = task::block_on(ldaps.do_bind(idms, "testperson1", TEST_PASSWORD))
+                    .unwrap()
+                    .unwrap();
+
+                // Anonymous can't change the password, even knowing the current one.
+                assert!(
+                    modify(&anon_t, Some(TEST_PASSWORD), new_password) != LdapResultCode::Success
+                );
+                // The bound session is read only, so the current password is required.
+                assert!(modify(&person_t, None, new_password) != LdapResultCode::Success);
+                // Password quality policy applies.
+                assert!(
+                    modify(&person_t, Some(TEST_PASSWORD), "password")
+                        == LdapResultCode::ConstraintViolation
+                );
+
+                assert!(
+                    modify(&person_t, Some(TEST_PASSWORD), new_password) == LdapResultCode::Success
+                );
+
+                assert!(
+                    task::block_on(ldaps.do_bind(idms, "testperson1", TEST_PASSWORD))
+                        .unwrap()
+                        .is_none()
+                );
+                assert!(
+                    task::block_on(ldaps.do_bind(idms, "testperson1", new_password))
+                        .unwrap()
+                        .is_some()
+                );
+
+                // A wrong current password softlocks the credential, so even the correct one
+                // is refused until the lock lifts.
+                assert!(
+                    modify(&person_t, Some("wrong password"), TEST_PASSWORD)
+                        == LdapResultCode::InvalidCredentials
+                );
+                assert!(
+                    modify(&person_t, Some(new_password), TEST_PASSWORD)
+                        == LdapResultCode::InvalidCredentials
+                );
+                assert!(
+                    task::block_on(ldaps.do_bind(idms, "testperson1", new_password))
+                        .unwrap()
+                        .is_none()
+                );
+            }
+        )
+    }
+
+    #[test]
+    fn test_ldap_attribute_aliases() {
+        run_idm_test!(
+            |_qs: &QueryServer, idms: &IdmServer, _idms_delayed: &IdmServerDelayed| {
+                // Setup a user in a group, and alias fullname to displayname.
+                {
+                    let e1 = entry_init!(
+                        ("class", Value::new_class("object")),
+                        ("class", Value::new_class("person")),
+                        ("class", Value::new_class("account")),
+                        ("name", Value::new_iname("testperson1")),
+                        (
+                            "uuid",
+                            Value::new_uuids("cc8e95b4-c24f-4d68-ba54-8bed76f63930").expect("uuid")
+                        ),
+                        ("description", Value::new_utf8s("testperson1")),
+                        ("displayname", Value::new_utf8s("Test Person"))
+                    );
+                    let e2 = entry_init!(
+                        ("class", Value::new_class("object")),
+                        ("class", Value::new_class("group")),
+                        ("name", Value::new_iname("testgroup1")),
+                        (
+                            "member",
+                            Value::new_refer_s("cc8e95b4-c24f-4d68-ba54-8bed76f63930")
+                                .expect("uuid")
+                        )
+                    );
+
+                    let mut server_txn =
+                        task::block_on(idms.proxy_write(duration_from_epoch_now()));
+                    let ce = CreateEvent::new_internal(vec![e1, e2]);
+                    assert!(server_txn.qs_write.create(&ce).is_ok());
+                    assert!(server_txn
+                        .qs_write
+                        .internal_modify_uuid(
+                            UUID_DOMAIN_INFO,
+                            &ModifyList::new_purge_and_set(
+                                "domain_ldap_attr_map",
+                                Value::new_iutf8("fullname:displayname")
+                            )
+                        )
+                        .is_ok());
+                    assert!(server_txn.commit().is_ok());
+                }
+
+                let ldaps = LdapServer::new(idms).expect("failed to start ldap");
+
+                let anon_t = task::block_on(ldaps.do_bind(idms, "", ""))
+                    .unwrap()
+                    .unwrap();
+
+                // Builtin and configured aliases can be used in filters and requested, and
+                // memberOf can be filtered by dn.
+                let sr = SearchRequest {
+                    msgid: 1,
+                    base: "dc=example,dc=com".to_string(),
+                    scope: LdapSearchScope::Subtree,
+                    filter: LdapFilter::And(vec![
+                        LdapFilter::Equality("uid".to_string(), "testperson1".to_string()),
+                        LdapFilter::Equality(
+                            "memberOf".to_string(),
+                            "spn=testgroup1@example.com,dc=example,dc=com".to_string(),
+                        ),
+                        LdapFilter::Present("fullName".to_string()),
+                    ]),
+                    attrs: vec![
+                        "uid".to_string(),
+                        "gecos".to_string(),
+                        "fullname".to_string(),
+                        "entryuuid".to_string(),
+                    ],
+                };
+                let r1 = task::block_on(ldaps.do_search(idms, &sr, &anon_t)).unwrap();
+                assert!(r1.len() == 2);
+                match &r1[0].op {
+                    LdapOp::SearchResultEntry(lsre) => {
+                        assert_entry_contains!(
+                            lsre,
+                            "spn=testperson1@example.com,dc=example,dc=com",
+                            ("uid", "testperson1"),
+                            ("gecos", "Test Person"),
+                            ("fullname", "Test Person"),
+                            ("entryuuid", "cc8e95b4-c24f-4d68-ba54-8bed76f63930")
+                        );
+                    }
+                    _ => assert!(false),
+                };
+
+                // A member of no groups is not returned.
+                let sr = SearchRequest {
+                    filter: LdapFilter::Equality(
+                        "memberof".to_string(),
+                        "spn=idm_admins@example.com,dc=example,dc=com".to_string(),
+                    ),
+                    ..sr
+                };
+                let r1 = task::block_on(ldaps.do_search(idms, &sr, &anon_t)).unwrap();
+                assert!(r1.iter().all(|m| !matches!(
+                    &m.op,
+                    LdapOp::SearchResultEntry(lsre) if lsre.dn.starts_with("spn=testperson1")
+                )));
+            }
+        )
+    }
+
+    #[test]
+    fn test_ldap_extensible_in_chain() {
+        run_idm_test!(
+            |_qs: &QueryServer, idms: &IdmServer, _idms_delayed: &IdmServerDelayed| {
+                // Setup a user in a group that is nested in another group.
+                {
+                    let e1 = entry_init!(
+                        ("class", Value::new_class("object")),
+                        ("class", Value::new_class("person")),
+                        ("class", Value::new_class("account")),
+                        ("name", Value::new_iname("testperson1")),
+                        (
+                            "uuid",
+                            Value::new_uuids("cc8e95b4-c24f-4d68-ba54-8bed76f63930").expect("uuid")
+                        ),
+                        ("description", Value::new_utf8s("testperson1")),
+                        ("displayname", Value::new_utf8s("Test Person"))
+                    );
+                    let e2 = entry_init!(
+                        ("class", Value::new_class("object")),
+                        ("class", Value::new_class("group")),
+                        ("name", Value::new_iname("testgroup_inner")),
+                        (
+                            "uuid",
+                            Value::new_uuids("5d4e1b44-1c4b-4c7d-8a5c-0a6b3b7a1f01").expect("uuid")
+                        ),
+                        (
+                            "member",
+                            Value::new_refer_s("cc8e95b4-c24f-4d68-ba54-8bed76f63930")
+                                .expect("uuid")
+                        )
+                    );
+                    let e3 = entry_init!(
+                        ("class", Value::new_class("object")),
+                        ("class", Value::new_class("group")),
+                        ("name", Value::new_iname("testgroup_outer")),
+                        (
+                            "member",
+                            Value::new_refer_s("5d4e1b44-1c4b-4c7d-8a5c-0a6b3b7a1f01")
+                                .expect("uuid")
+                        )
+                    );
+
+                    let mut server_txn =
+                        task::block_on(idms.proxy_write(duration_from_epoch_now()));
+                    let ce = CreateEvent::new_internal(vec![e1, e2, e3]);
+                    assert!(server_txn
+                        .qs_write
+                        .create(&ce)
+                        .and_then(|_| server_txn.commit())
+                        .is_ok());
+                }
+
+                let ldaps = LdapServer::new(idms).expect("failed to start ldap");
+
+                let anon_t = task::block_on(ldaps.do_bind(idms, "", ""))
+                    .unwrap()
+                    .unwrap();
+
+                let in_chain = |attr: &str| {
+                    LdapFilter::Extensible(LdapMatchingRuleAssertion {
+                        matching_rule: Some("1.2.840.113556.1.4.1941".to_string()),
+                        type_: Some(attr.to_string()),
+                        match_value: "spn=testgroup_outer@example.com,dc=example,dc=com"
+                            .to_string(),
+                        dn_attributes: false,
+                    })
+                };
+
+                // Members of the nested group are members of the outer group in chain.
+                let sr = SearchRequest {
+                    msgid: 1,
+                    base: "dc=example,dc=com".to_string(),
+                    scope: LdapSearchScope::Subtree,
+                    filter: in_chain("memberOf"),
+                    attrs: vec!["name".to_string()],
+                };
+                let r1 = task::block_on(ldaps.do_search(idms, &sr, &anon_t)).unwrap();
+                let dns: Vec<_> = r1
+                    .iter()
+                    .filter_map(|m| match &m.op {
+                        LdapOp::SearchResultEntry(lsre) => Some(lsre.dn.as_str()),
+                        _ => None,
+                    })
+                    .collect();
+                assert!(dns.len() == 2);
+                assert!(dns.contains(&"spn=testperson1@example.com,dc=example,dc=com"));
+                assert!(dns.contains(&"spn=testgroup_inner@example.com,dc=example,dc=com"));
+
+                // The direct members are not the members in chain, so this is refused rather
+                // than silently excluding the nested members.
+                let sr = SearchRequest {
+                    filter: LdapFilter::Not(Box::new(in_chain("member"))),
+                    ..sr
+                };
+                assert!(task::block_on(ldaps.do_search(idms, &sr, &anon_t)).is_err());
+            }
+        )
+    }
 }
@@ -41,6 +41,7 @@ pub mod filter;
 pub mod identity;
 pub mod ldap;
 pub mod modify;
+pub mod redact;
 pub mod utils;
 pub mod value;
 pub mod valueset;
@@ -48,8 +49,9 @@ pub mod valueset;
 mod plugins;
 mod access;
 pub mod idm;
-mod repl;
+pub mod repl;
 pub mod schema;
+pub mod security_event;
 pub mod server;
 pub mod status;
 pub mod testkit;
@@ -74,8 +76,8 @@ pub mod prelude {
         EntrySealedCommitted, EntrySealedNew, EntryTuple, EntryValid,
     };
     pub use crate::filter::{
-        f_and, f_andnot, f_eq, f_id, f_inc, f_lt, f_or, f_pres, f_self, f_spn_name, f_sub, Filter,
-        FilterInvalid, FC,
+        f_and, f_andnot, f_eq, f_gt, f_gte, f_id, f_inc, f_lt, f_lte, f_or, f_pres, f_self,
+        f_spn_name, f_sub, Filter, FilterInvalid, FC,
     };
     pub use crate::identity::{AccessScope, IdentType, Identity, IdentityId};
     pub use crate::idm::server::{IdmServer, IdmServerDelayed};
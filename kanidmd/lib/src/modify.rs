@@ -122,6 +122,13 @@ impl ModifyList<ModifyInvalid> {
         self.mods.push(modify)
     }
 
+    pub fn retain<F>(&mut self, f: F)
+    where
+        F: FnMut(&Modify) -> bool,
+    {
+        self.mods.retain(f)
+    }
+
     pub fn from(
         ml: &ProtoModifyList,
         qs: &QueryServerWriteTransaction,
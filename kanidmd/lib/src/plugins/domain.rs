@@ -8,11 +8,24 @@ use std::iter::once;
 
 use compact_jwt::JwsSigner;
 use kanidm_proto::v1::OperationError;
+use regex::Regex;
 use tracing::trace;
 
 use crate::event::{CreateEvent, ModifyEvent};
+use crate::ldap::{LdapAttrMap, LdapDnFormat};
 use crate::plugins::Plugin;
 use crate::prelude::*;
+use crate::schema::SchemaTransaction;
+
+lazy_static! {
+    // A basedn is a sequence of simple attr=value rdns. This is strict so that it can
+    // be safely embedded into the ldap dn matching expressions.
+    static ref DOMAIN_LDAP_BASEDN_RE: Regex = {
+        #[allow(clippy::expect_used)]
+        Regex::new(r"^[a-z]+=[a-z0-9_\-]+(,[a-z]+=[a-z0-9_\-]+)*$")
+            .expect("Invalid domain ldap basedn regex found")
+    };
+}
 
 pub struct Domain {}
 
@@ -77,6 +90,53 @@ impl Domain {
                     e.set_ava("domain_display_name", once(domain_display_name));
                 }
 
+                if let Some(basedn) = e.get_ava_single_iutf8("domain_ldap_basedn") {
+                    if !DOMAIN_LDAP_BASEDN_RE.is_match(basedn) {
+                        admin_error!(?basedn, "plugin_domain: invalid domain_ldap_basedn");
+                        return Err(OperationError::InvalidAttribute(format!(
+                            "domain_ldap_basedn {} is not a valid basedn",
+                            basedn
+                        )));
+                    }
+                }
+
+                if let Some(rdn_attrs) = e.get_ava_iter_iutf8("domain_ldap_rdn_attr") {
+                    LdapDnFormat::parse(rdn_attrs).map_err(|err| {
+                        admin_error!(?err, "plugin_domain: invalid domain_ldap_rdn_attr");
+                        err
+                    })?;
+                }
+
+                if let Some(attr_map) = e.get_ava_iter_iutf8("domain_ldap_attr_map") {
+                    let attr_map = LdapAttrMap::parse(attr_map).map_err(|err| {
+                        admin_error!(?err, "plugin_domain: invalid domain_ldap_attr_map");
+                        err
+                    })?;
+                    let schema_attrs = qs.get_schema().get_attributes();
+                    for (ldap_attr, attr) in attr_map.iter() {
+                        // An alias may not shadow a real attribute, but may replace one of the
+                        // builtin ldap phantoms.
+                        if schema_attrs
+                            .get(ldap_attr)
+                            .map(|sa| !sa.phantom)
+                            .unwrap_or(false)
+                        {
+                            admin_error!(?ldap_attr, "plugin_domain: domain_ldap_attr_map shadows an attribute");
+                            return Err(OperationError::InvalidAttribute(format!(
+                                "domain_ldap_attr_map {} is an existing attribute",
+                                ldap_attr
+                            )));
+                        }
+                        if !schema_attrs.contains_key(attr) {
+                            admin_error!(?attr, "plugin_domain: domain_ldap_attr_map target does not exist");
+                            return Err(OperationError::InvalidAttribute(format!(
+                                "domain_ldap_attr_map {} is not an attribute",
+                                attr
+                            )));
+                        }
+                    }
+                }
+
                 if !e.attribute_pres("fernet_private_key_str") {
                     security_info!("regenerating domain token encryption key");
                     let k = fernet::Fernet::generate_key();
@@ -119,4 +179,88 @@ mod tests {
 
         assert!(e_dom.attribute_equality("domain_uuid", &PartialValue::new_uuid(u_dom)));
     }
+
+    #[qs_test]
+    async fn test_domain_ldap_basedn_validation(server: &QueryServer) {
+        let mut server_txn = server.write(duration_from_epoch_now()).await;
+
+        let set_basedn = |server_txn: &mut QueryServerWriteTransaction, basedn: &str| {
+            server_txn.internal_modify_uuid(
+                UUID_DOMAIN_INFO,
+                &ModifyList::new_purge_and_set("domain_ldap_basedn", Value::new_iutf8(basedn)),
+            )
+        };
+
+        assert!(set_basedn(&mut server_txn, "o=kanidm").is_ok());
+        assert!(set_basedn(&mut server_txn, "ou=idm,dc=example-corp,dc=com").is_ok());
+
+        assert!(set_basedn(&mut server_txn, "").is_err());
+        assert!(set_basedn(&mut server_txn, "dc=example,").is_err());
+        assert!(set_basedn(&mut server_txn, "dc=example,dc=(.*)").is_err());
+        assert!(set_basedn(&mut server_txn, "dc=example,dc=com=org").is_err());
+
+        assert!(server_txn.commit().is_ok());
+    }
+
+    #[qs_test]
+    async fn test_domain_ldap_rdn_attr_validation(server: &QueryServer) {
+        let mut server_txn = server.write(duration_from_epoch_now()).await;
+
+        let set_rdn_attr = |server_txn: &mut QueryServerWriteTransaction, rdn_attrs: &[&str]| {
+            server_txn.internal_modify_uuid(
+                UUID_DOMAIN_INFO,
+                &ModifyList::new_list(
+                    std::iter::once(m_purge("domain_ldap_rdn_attr"))
+                        .chain(
+                            rdn_attrs
+                                .iter()
+                                .map(|v| m_pres("domain_ldap_rdn_attr", &Value::new_iutf8(v))),
+                        )
+                        .collect(),
+                ),
+            )
+        };
+
+        assert!(set_rdn_attr(&mut server_txn, &["uid"]).is_ok());
+        assert!(set_rdn_attr(&mut server_txn, &["uid", "group:cn"]).is_ok());
+        assert!(set_rdn_attr(&mut server_txn, &["person:spn", "group:uuid"]).is_ok());
+
+        assert!(set_rdn_attr(&mut server_txn, &["mail"]).is_err());
+        assert!(set_rdn_attr(&mut server_txn, &[":cn"]).is_err());
+        assert!(set_rdn_attr(&mut server_txn, &["group:"]).is_err());
+        assert!(set_rdn_attr(&mut server_txn, &["uid", "cn"]).is_err());
+
+        assert!(server_txn.commit().is_ok());
+    }
+
+    #[qs_test]
+    async fn test_domain_ldap_attr_map_validation(server: &QueryServer) {
+        let mut server_txn = server.write(duration_from_epoch_now()).await;
+
+        let set_attr_map = |server_txn: &mut QueryServerWriteTransaction, attr_map: &[&str]| {
+            server_txn.internal_modify_uuid(
+                UUID_DOMAIN_INFO,
+                &ModifyList::new_list(
+                    std::iter::once(m_purge("domain_ldap_attr_map"))
+                        .chain(
+                            attr_map
+                                .iter()
+                                .map(|v| m_pres("domain_ldap_attr_map", &Value::new_iutf8(v))),
+                        )
+                        .collect(),
+                ),
+            )
+        };
+
+        assert!(set_attr_map(&mut server_txn, &["mailprimary:mail"]).is_ok());
+        assert!(set_attr_map(&mut server_txn, &["uid:spn", "gecos:legalname"]).is_ok());
+
+        assert!(set_attr_map(&mut server_txn, &["mail"]).is_err());
+        assert!(set_attr_map(&mut server_txn, &[":mail"]).is_err());
+        assert!(set_attr_map(&mut server_txn, &["mailprimary:"]).is_err());
+        assert!(set_attr_map(&mut server_txn, &["name:spn"]).is_err());
+        assert!(set_attr_map(&mut server_txn, &["mailprimary:nonexistant"]).is_err());
+
+        assert!(server_txn.commit().is_ok());
+    }
 }
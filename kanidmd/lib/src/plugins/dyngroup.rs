@@ -153,7 +153,7 @@ impl DynGroup {
         // so we don't need to reference them.
 
         //
-        let resolve_filter_cache = qs.get_resolve_filter_cache();
+        let mut resolve_filter_cache = qs.get_resolve_filter_cache();
 
         let mut pre_candidates = Vec::with_capacity(dyn_groups.insts.len() + cand.len());
         let mut candidates = Vec::with_capacity(dyn_groups.insts.len() + cand.len());
@@ -164,7 +164,9 @@ impl DynGroup {
             let dg_filter_valid = dg_filter
                 .validate(qs.get_schema())
                 .map_err(OperationError::SchemaViolation)
-                .and_then(|f| f.resolve(&ident_internal, None, Some(resolve_filter_cache)))?;
+                .and_then(|f| {
+                    f.resolve(&ident_internal, None, resolve_filter_cache.as_deref_mut())
+                })?;
 
             let matches: Vec<_> = entries
                 .iter()
@@ -238,7 +240,7 @@ impl DynGroup {
         let mut affected_uuids = Vec::with_capacity(cand.len());
 
         let ident_internal = Identity::from_internal();
-        let resolve_filter_cache = qs.get_resolve_filter_cache();
+        let mut resolve_filter_cache = qs.get_resolve_filter_cache();
 
         // Probably should be filter here instead.
         let (_, pre_entries): (Vec<&Arc<Entry<_, _>>>, Vec<_>) = pre_cand
@@ -283,7 +285,9 @@ impl DynGroup {
             let dg_filter_valid = dg_filter
                 .validate(qs.get_schema())
                 .map_err(OperationError::SchemaViolation)
-                .and_then(|f| f.resolve(&ident_internal, None, Some(resolve_filter_cache)))?;
+                .and_then(|f| {
+                    f.resolve(&ident_internal, None, resolve_filter_cache.as_deref_mut())
+                })?;
 
             let matches: Vec<_> = pre_entries
                 .iter()
@@ -45,12 +45,16 @@ impl JwsKeygen {
         cand: &mut Vec<Entry<EntryInvalid, T>>,
     ) -> Result<(), OperationError> {
         cand.iter_mut().try_for_each(|e| {
-        if e.attribute_equality("class", &PVCLASS_OAUTH2_BASIC) {
-            if !e.attribute_pres("oauth2_rs_basic_secret") {
+        if e.attribute_equality("class", &PVCLASS_OAUTH2_BASIC) &&
+            !e.attribute_pres("oauth2_rs_basic_secret") {
                 security_info!("regenerating oauth2 basic secret");
                 let v = Value::SecretValue(password_from_random());
                 e.add_ava("oauth2_rs_basic_secret", v);
-            }
+        }
+
+        if e.attribute_equality("class", &PVCLASS_OAUTH2_BASIC) ||
+           e.attribute_equality("class", &PVCLASS_OAUTH2_PUBLIC)
+        {
             if !e.attribute_pres("oauth2_rs_token_key") {
                 security_info!("regenerating oauth2 token key");
                 let k = fernet::Fernet::generate_key();
@@ -68,9 +72,11 @@ impl JwsKeygen {
                 let v = Value::new_privatebinary(&der);
                 e.add_ava("es256_private_key_der", v);
             }
-            if e.get_ava_single_bool("oauth2_jwt_legacy_crypto_enable").unwrap_or(false) {
+            if e.get_ava_single_bool("oauth2_jwt_legacy_crypto_enable").unwrap_or(false) ||
+               e.get_ava_single_iutf8("oauth2_jwt_sign_alg") == Some("rs256")
+            {
                 if !e.attribute_pres("rs256_private_key_der") {
-                    security_info!("regenerating oauth2 legacy rs256 private key");
+                    security_info!("regenerating oauth2 rs256 private key");
                     let der = JwsSigner::generate_legacy_rs256()
                         .and_then(|jws| jws.private_key_to_der())
                         .map_err(|e| {
@@ -147,6 +153,79 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_pre_create_oauth2_public_secrets() {
+        let preload: Vec<Entry<EntryInit, EntryNew>> = Vec::new();
+
+        let uuid = Uuid::new_v4();
+        let e: Entry<EntryInit, EntryNew> = entry_init!(
+            ("class", Value::new_class("object")),
+            ("class", Value::new_class("oauth2_resource_server")),
+            ("class", Value::new_class("oauth2_resource_server_public")),
+            ("uuid", Value::new_uuid(uuid)),
+            ("displayname", Value::new_utf8s("test_public_client")),
+            ("oauth2_rs_name", Value::new_iname("test_public_client")),
+            (
+                "oauth2_rs_origin",
+                Value::new_url_s("http://127.0.0.1/oauth2/result").unwrap()
+            )
+        );
+
+        let create = vec![e];
+
+        run_create_test!(
+            Ok(()),
+            preload,
+            create,
+            None,
+            |qs: &QueryServerWriteTransaction| {
+                let e = qs
+                    .internal_search_uuid(&uuid)
+                    .expect("failed to get oauth2 config");
+                // Public clients never have a secret.
+                assert!(!e.attribute_pres("oauth2_rs_basic_secret"));
+                assert!(e.attribute_pres("oauth2_rs_token_key"));
+                assert!(e.attribute_pres("es256_private_key_der"));
+            }
+        );
+    }
+
+    #[test]
+    fn test_pre_create_oauth2_rs256_sign_alg() {
+        let preload: Vec<Entry<EntryInit, EntryNew>> = Vec::new();
+
+        let uuid = Uuid::new_v4();
+        let e: Entry<EntryInit, EntryNew> = entry_init!(
+            ("class", Value::new_class("object")),
+            ("class", Value::new_class("oauth2_resource_server")),
+            ("class", Value::new_class("oauth2_resource_server_basic")),
+            ("uuid", Value::new_uuid(uuid)),
+            ("displayname", Value::new_utf8s("test_resource_server")),
+            ("oauth2_rs_name", Value::new_iname("test_resource_server")),
+            (
+                "oauth2_rs_origin",
+                Value::new_url_s("https://demo.example.com").unwrap()
+            ),
+            ("oauth2_jwt_sign_alg", Value::new_iutf8("rs256"))
+        );
+
+        let create = vec![e];
+
+        run_create_test!(
+            Ok(()),
+            preload,
+            create,
+            None,
+            |qs: &QueryServerWriteTransaction| {
+                let e = qs
+                    .internal_search_uuid(&uuid)
+                    .expect("failed to get oauth2 config");
+                assert!(e.attribute_pres("es256_private_key_der"));
+                assert!(e.attribute_pres("rs256_private_key_der"));
+            }
+        );
+    }
+
     #[test]
     fn test_modify_oauth2_secrets_regenerate() {
         let uuid = Uuid::new_v4();
@@ -829,6 +829,7 @@ mod tests {
                         expiry: None,
                         issued_at,
                         rs_uuid,
+                        delegated_from: None,
                     },
                 )
             ),
@@ -6,6 +6,12 @@
 //!
 //! This plugin is also responsible for invaliding old sessions that are past
 //! their expiry.
+//!
+//! An oauth2 resource server may opt out of parent session enforcement with
+//! `oauth2_session_outlive_parent`, in which case only expiry applies.
+//!
+//! Oauth2 sessions derived by token exchange are removed when the session they
+//! were delegated from is removed.
 
 use crate::event::ModifyEvent;
 use crate::plugins::Plugin;
@@ -47,6 +53,26 @@ impl SessionConsistency {
         let curtime = qs.get_curtime();
         let curtime_odt = OffsetDateTime::unix_epoch() + curtime;
 
+        // Only lookup which resource servers allow sessions to outlive their parent
+        // if we have oauth2 sessions to check.
+        let outlive_parent: BTreeSet<Uuid> = if cand
+            .iter()
+            .any(|e| e.get_ava_set("oauth2_session").is_some())
+        {
+            qs.internal_search(filter!(f_and!([
+                f_eq("class", PVCLASS_OAUTH2_RS.clone()),
+                f_eq(
+                    "oauth2_session_outlive_parent",
+                    PartialValue::new_bool(true)
+                )
+            ])))?
+            .iter()
+            .map(|e| e.get_uuid())
+            .collect()
+        } else {
+            BTreeSet::new()
+        };
+
         // We need to assert a number of properties. We must do these *in order*.
         cand.iter_mut().try_for_each(|entry| {
             // * If a UAT is past it's expiry, remove it.
@@ -82,7 +108,10 @@ impl SessionConsistency {
                         }
                         _ => {
                             // Okay, now check the issued / grace time for parent enforcement.
-                            if session.issued_at + GRACE_WINDOW <= curtime_odt {
+                            if outlive_parent.contains(&session.rs_uuid) {
+                                // This rs allows the session to outlive the parent.
+                                None
+                            } else if session.issued_at + GRACE_WINDOW <= curtime_odt {
                                 if sessions.map(|s| s.contains_key(&session.parent)).unwrap_or(false) {
                                     // The parent exists, go ahead
                                     None
@@ -106,6 +135,33 @@ impl SessionConsistency {
                 entry.remove_avas("oauth2_session", oauth2_remove);
             }
 
+            // * If an oauth2 session is past the grace window, and was delegated from a session that
+            //   no longer exists, remove it. Delegation may be chained, so repeat until no more
+            //   sessions are removed.
+            loop {
+                let orphaned: BTreeSet<_> = entry.get_ava_as_oauth2session_map("oauth2_session").map(|oauth2_sessions| {
+                    oauth2_sessions.iter().filter_map(|(o2_session_id, session)| {
+                        match session.delegated_from {
+                            Some(delegated_from)
+                                if session.issued_at + GRACE_WINDOW <= curtime_odt
+                                    && !oauth2_sessions.contains_key(&delegated_from) =>
+                            {
+                                info!(%o2_session_id, "Removing orphaned delegated oauth2 session");
+                                Some(PartialValue::Refer(*o2_session_id))
+                            }
+                            _ => None,
+                        }
+                    })
+                    .collect()
+                })
+                .unwrap_or_default();
+
+                if orphaned.is_empty() {
+                    break;
+                }
+                entry.remove_avas("oauth2_session", &orphaned);
+            }
+
             Ok(())
         })
     }
@@ -285,6 +341,7 @@ mod tests {
                         expiry,
                         issued_at,
                         rs_uuid,
+                        delegated_from: None,
                     },
                 )
             ),
@@ -419,6 +476,7 @@ mod tests {
                         expiry: None,
                         issued_at,
                         rs_uuid,
+                        delegated_from: None,
                     },
                 )
             ),
@@ -482,6 +540,111 @@ mod tests {
         assert!(server_txn.commit().is_ok());
     }
 
+    // test removal of an oauth2 session removes the sessions delegated from it.
+    #[qs_test]
+    async fn test_session_consistency_oauth2_removed_by_delegation(server: &QueryServer) {
+        let curtime = duration_from_epoch_now();
+        let curtime_odt = OffsetDateTime::unix_epoch() + curtime;
+        let exp_curtime = curtime + GRACE_WINDOW;
+
+        // Create a user
+        let mut server_txn = server.write(curtime).await;
+
+        let tuuid = uuid!("cc8e95b4-c24f-4d68-ba54-8bed76f63930");
+        let rs_uuid = Uuid::new_v4();
+
+        let e1 = entry_init!(
+            ("class", Value::new_class("object")),
+            ("class", Value::new_class("person")),
+            ("class", Value::new_class("account")),
+            ("name", Value::new_iname("testperson1")),
+            ("uuid", Value::new_uuid(tuuid)),
+            ("description", Value::new_utf8s("testperson1")),
+            ("displayname", Value::new_utf8s("testperson1"))
+        );
+
+        let ce = CreateEvent::new_internal(vec![e1]);
+        assert!(server_txn.create(&ce).is_ok());
+
+        // A root oauth2 session, and a chain of two sessions delegated from it.
+        let parent = Uuid::new_v4();
+        let pv_parent_id = PartialValue::new_refer(parent);
+        let root_id = Uuid::new_v4();
+        let pv_root_id = PartialValue::new_refer(root_id);
+        let delegated_id = Uuid::new_v4();
+        let pv_delegated_id = PartialValue::new_refer(delegated_id);
+        let chained_id = Uuid::new_v4();
+        let pv_chained_id = PartialValue::new_refer(chained_id);
+        let issued_at = curtime_odt;
+
+        let o2_session = |delegated_from| Oauth2Session {
+            parent,
+            expiry: None,
+            issued_at,
+            rs_uuid,
+            delegated_from,
+        };
+
+        let modlist = modlist!([
+            Modify::Present(
+                "oauth2_session".into(),
+                Value::Oauth2Session(root_id, o2_session(None))
+            ),
+            Modify::Present(
+                "oauth2_session".into(),
+                Value::Oauth2Session(delegated_id, o2_session(Some(root_id)))
+            ),
+            Modify::Present(
+                "oauth2_session".into(),
+                Value::Oauth2Session(chained_id, o2_session(Some(delegated_id)))
+            ),
+            Modify::Present(
+                "user_auth_token_session".into(),
+                Value::Session(
+                    parent,
+                    Session {
+                        label: "label".to_string(),
+                        expiry: None,
+                        issued_at,
+                        issued_by: IdentityId::User(tuuid),
+                        scope: AccessScope::IdentityOnly,
+                    },
+                )
+            ),
+        ]);
+
+        server_txn
+            .internal_modify(
+                &filter!(f_eq("uuid", PartialValue::new_uuid(tuuid))),
+                &modlist,
+            )
+            .expect("Failed to modify user");
+
+        assert!(server_txn.commit().is_ok());
+        let mut server_txn = server.write(exp_curtime).await;
+
+        // Remove the root session.
+        let modlist = ModifyList::new_remove("oauth2_session", pv_root_id.clone());
+
+        server_txn
+            .internal_modify(
+                &filter!(f_eq("uuid", PartialValue::new_uuid(tuuid))),
+                &modlist,
+            )
+            .expect("Failed to modify user");
+
+        let entry = server_txn.internal_search_uuid(&tuuid).expect("failed");
+
+        // The uat is untouched
+        assert!(entry.attribute_equality("user_auth_token_session", &pv_parent_id));
+        // But the whole delegation chain is removed.
+        assert!(!entry.attribute_equality("oauth2_session", &pv_root_id));
+        assert!(!entry.attribute_equality("oauth2_session", &pv_delegated_id));
+        assert!(!entry.attribute_equality("oauth2_session", &pv_chained_id));
+
+        assert!(server_txn.commit().is_ok());
+    }
+
     // Test if an oauth2 session exists, the grace window passes and it's UAT doesn't exist.
     #[qs_test]
     async fn test_session_consistency_oauth2_grace_window_past(server: &QueryServer) {
@@ -546,6 +709,7 @@ mod tests {
                 expiry: None,
                 issued_at,
                 rs_uuid,
+                delegated_from: None,
             },
         );
 
@@ -590,4 +754,115 @@ mod tests {
 
         assert!(server_txn.commit().is_ok());
     }
+
+    // Test that an oauth2 session is retained past the grace window without it's UAT if the
+    // resource server allows the session to outlive the parent.
+    #[qs_test]
+    async fn test_session_consistency_oauth2_outlive_parent(server: &QueryServer) {
+        let curtime = duration_from_epoch_now();
+        let curtime_odt = OffsetDateTime::unix_epoch() + curtime;
+
+        // Set exp to gracewindow.
+        let exp_curtime = curtime + GRACE_WINDOW;
+        // let exp_curtime_odt = OffsetDateTime::unix_epoch() + exp_curtime;
+
+        // Create a user
+        let mut server_txn = server.write(curtime).await;
+
+        let tuuid = uuid!("cc8e95b4-c24f-4d68-ba54-8bed76f63930");
+        let rs_uuid = Uuid::new_v4();
+
+        let e1 = entry_init!(
+            ("class", Value::new_class("object")),
+            ("class", Value::new_class("person")),
+            ("class", Value::new_class("account")),
+            ("name", Value::new_iname("testperson1")),
+            ("uuid", Value::new_uuid(tuuid)),
+            ("description", Value::new_utf8s("testperson1")),
+            ("displayname", Value::new_utf8s("testperson1"))
+        );
+
+        let e2 = entry_init!(
+            ("class", Value::new_class("object")),
+            ("class", Value::new_class("oauth2_resource_server")),
+            ("class", Value::new_class("oauth2_resource_server_basic")),
+            ("uuid", Value::new_uuid(rs_uuid)),
+            ("oauth2_rs_name", Value::new_iname("test_resource_server")),
+            ("displayname", Value::new_utf8s("test_resource_server")),
+            (
+                "oauth2_rs_origin",
+                Value::new_url_s("https://demo.example.com").unwrap()
+            ),
+            // System admins
+            (
+                "oauth2_rs_scope_map",
+                Value::new_oauthscopemap(UUID_IDM_ALL_ACCOUNTS, btreeset!["openid".to_string()])
+                    .expect("invalid oauthscope")
+            ),
+            ("oauth2_session_outlive_parent", Value::new_bool(true))
+        );
+
+        let ce = CreateEvent::new_internal(vec![e1, e2]);
+        assert!(server_txn.create(&ce).is_ok());
+
+        // Create a fake session.
+        let session_id = Uuid::new_v4();
+        let pv_session_id = PartialValue::new_refer(session_id);
+
+        let parent = Uuid::new_v4();
+        let issued_at = curtime_odt;
+
+        let session = Value::Oauth2Session(
+            session_id,
+            Oauth2Session {
+                parent,
+                // Note we set the exp to None so we are asserting the session is retained
+                // only due to the outlive parent setting.
+                expiry: None,
+                issued_at,
+                rs_uuid,
+                delegated_from: None,
+            },
+        );
+
+        // Mod the user
+        let modlist = ModifyList::new_append("oauth2_session", session);
+
+        server_txn
+            .internal_modify(
+                &filter!(f_eq("uuid", PartialValue::new_uuid(tuuid))),
+                &modlist,
+            )
+            .expect("Failed to modify user");
+
+        // Still there
+
+        let entry = server_txn.internal_search_uuid(&tuuid).expect("failed");
+
+        assert!(entry.attribute_equality("oauth2_session", &pv_session_id));
+
+        assert!(server_txn.commit().is_ok());
+
+        // Note the exp_curtime now is past the gracewindow, but the rs permits the
+        // session to outlive the parent.
+        let mut server_txn = server.write(exp_curtime).await;
+
+        // Mod again - anything will do.
+        let modlist =
+            ModifyList::new_purge_and_set("description", Value::new_utf8s("test person 1 change"));
+
+        server_txn
+            .internal_modify(
+                &filter!(f_eq("uuid", PartialValue::new_uuid(tuuid))),
+                &modlist,
+            )
+            .expect("Failed to modify user");
+
+        // Session retained.
+        let entry = server_txn.internal_search_uuid(&tuuid).expect("failed");
+
+        assert!(entry.attribute_equality("oauth2_session", &pv_session_id));
+
+        assert!(server_txn.commit().is_ok());
+    }
 }
@@ -0,0 +1,69 @@
+//! Redaction of sensitive values from logs. The debug output of credentials, secrets, sessions,
+//! intent tokens and keys is replaced by a [Redacted] placeholder in their own `Debug`
+//! implementations, so that no log of an entry, value or modification can contain them, however
+//! the caller formats it.
+//!
+//! The placeholder is a hash of the value, keyed with a secret of this process. Two placeholders
+//! in the log of one server can be compared to see if the values are the same, but they can not
+//! be reversed or compared to a guess of the value.
+
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+
+lazy_static! {
+    static ref REDACT_KEY: RandomState = RandomState::new();
+}
+
+/// The placeholder of a sensitive value in debug output.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Redacted(u64);
+
+impl Redacted {
+    pub fn new<T: fmt::Debug + ?Sized>(value: &T) -> Self {
+        let mut hasher = REDACT_KEY.build_hasher();
+        hasher.write(format!("{:?}", value).as_bytes());
+        Redacted(hasher.finish())
+    }
+}
+
+impl fmt::Debug for Redacted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<redacted:{:016x}>", self.0)
+    }
+}
+
+impl fmt::Display for Redacted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// The placeholders of a set of sensitive values.
+pub fn redact_all<T: fmt::Debug, I: IntoIterator<Item = T>>(values: I) -> Vec<Redacted> {
+    values.into_iter().map(|v| Redacted::new(&v)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Redacted;
+    use crate::prelude::*;
+
+    #[test]
+    fn test_redacted_value_debug() {
+        let secret = "a3d6f91b-secret";
+        let r = Redacted::new(secret);
+        assert_eq!(r, Redacted::new(secret));
+        assert_ne!(r, Redacted::new("another"));
+        assert!(!format!("{:?}", r).contains(secret));
+
+        let v = Value::new_secret_str(secret);
+        assert!(!format!("{:?}", v).contains(secret));
+        let vs = ValueSetSecret::new(secret.to_string());
+        assert!(!format!("{:?}", vs).contains(secret));
+        let pv = PartialValue::IntentToken(secret.to_string());
+        assert!(!format!("{:?}", pv).contains(secret));
+        // Values that are not sensitive are still shown.
+        assert!(format!("{:?}", Value::new_utf8s(secret)).contains(secret));
+    }
+}
@@ -0,0 +1,146 @@
+//! Replication agreements describe the partners this server will exchange changes with.
+//! Each agreement pins the certificates a partner may present on the replication
+//! listener, so that only known servers can supply or consume changes.
+
+use std::collections::BTreeSet;
+
+use crate::prelude::*;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplAgreement {
+    pub uuid: Uuid,
+    pub name: String,
+    /// The address of the partner, if this server consumes changes from it.
+    pub partner_address: Option<String>,
+    /// The normalised sha256 fingerprints of the certificates the partner may present.
+    pub cert_sha256: BTreeSet<String>,
+}
+
+/// Fingerprints may be written with or without separating colons and in either case.
+pub fn normalise_cert_sha256(fp: &str) -> String {
+    fp.chars()
+        .filter(|c| *c != ':')
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+impl ReplAgreement {
+    fn try_from_entry(e: &EntrySealedCommitted) -> Option<Self> {
+        let name = e.get_ava_single_iname("name")?.to_string();
+        let partner_address = e
+            .get_ava_single_iutf8("repl_partner_address")
+            .map(str::to_string);
+        let cert_sha256 = e
+            .get_ava_iter_iutf8("repl_partner_cert_sha256")
+            .map(|i| i.map(normalise_cert_sha256).collect())
+            .unwrap_or_default();
+
+        Some(ReplAgreement {
+            uuid: e.get_uuid(),
+            name,
+            partner_address,
+            cert_sha256,
+        })
+    }
+
+    pub fn is_pinned(&self, fp: &str) -> bool {
+        self.cert_sha256.contains(&normalise_cert_sha256(fp))
+    }
+}
+
+fn search_repl_agreements<'a, T: QueryServerTransaction<'a>>(
+    qs: &T,
+) -> Result<Vec<ReplAgreement>, OperationError> {
+    let filt = filter!(f_eq("class", PartialValue::new_class("repl_agreement")));
+    qs.internal_search(filt).map(|entries| {
+        entries
+            .iter()
+            .filter_map(|e| {
+                let agreement = ReplAgreement::try_from_entry(e);
+                if agreement.is_none() {
+                    admin_warn!(uuid = ?e.get_uuid(), "Ignoring invalid replication agreement");
+                }
+                agreement
+            })
+            .collect()
+    })
+}
+
+impl<'a> QueryServerReadTransaction<'a> {
+    pub fn get_repl_agreements(&self) -> Result<Vec<ReplAgreement>, OperationError> {
+        search_repl_agreements(self)
+    }
+
+    /// Find the agreement of the partner that presented a certificate with this fingerprint.
+    pub fn get_repl_agreement_by_cert(
+        &self,
+        fp: &str,
+    ) -> Result<Option<ReplAgreement>, OperationError> {
+        self.get_repl_agreements()
+            .map(|agreements| agreements.into_iter().find(|a| a.is_pinned(fp)))
+    }
+
+    /// Find the agreement for a partner that this server consumes changes from.
+    pub fn get_repl_agreement_by_address(
+        &self,
+        address: &str,
+    ) -> Result<Option<ReplAgreement>, OperationError> {
+        let address = address.to_lowercase();
+        self.get_repl_agreements().map(|agreements| {
+            agreements
+                .into_iter()
+                .find(|a| a.partner_address.as_deref() == Some(address.as_str()))
+        })
+    }
+}
+
+impl<'a> QueryServerWriteTransaction<'a> {
+    pub fn get_repl_agreements(&self) -> Result<Vec<ReplAgreement>, OperationError> {
+        search_repl_agreements(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::normalise_cert_sha256;
+    use crate::prelude::*;
+
+    #[qs_test]
+    async fn test_repl_agreement_pin_lookup(server: &QueryServer) {
+        let mut server_txn = server.write(duration_from_epoch_now()).await;
+
+        let e1 = entry_init!(
+            ("class", Value::new_class("object")),
+            ("class", Value::new_class("repl_agreement")),
+            ("name", Value::new_iname("replica_b")),
+            (
+                "repl_partner_address",
+                Value::new_iutf8("repl://B.example.com:8444")
+            ),
+            ("repl_partner_cert_sha256", Value::new_iutf8("AB:CD:EF:01"))
+        );
+
+        assert!(server_txn.internal_create(vec![e1]).is_ok());
+        assert!(server_txn.commit().is_ok());
+
+        let server_txn = server.read().await;
+
+        let agreement = server_txn
+            .get_repl_agreement_by_cert("abcdef01")
+            .unwrap()
+            .unwrap();
+        assert!(agreement.name == "replica_b");
+        assert!(agreement.is_pinned("AB:CD:EF:01"));
+
+        assert!(server_txn
+            .get_repl_agreement_by_cert("abcdef02")
+            .unwrap()
+            .is_none());
+
+        let agreement = server_txn
+            .get_repl_agreement_by_address("repl://b.example.com:8444")
+            .unwrap()
+            .unwrap();
+        assert!(agreement.is_pinned(&normalise_cert_sha256("ab:cd:ef:01")));
+    }
+}
@@ -0,0 +1,125 @@
+//! Conflict entries are created when two replicas create entries with the same unique values.
+//! The later creation is placed in the recycle bin with the class `conflict`, where it remains
+//! until an administrator decides what to do with it.
+
+use kanidm_proto::v1::ReplConflictResolution;
+
+use crate::event::ReviveRecycledEvent;
+use crate::prelude::*;
+
+impl<'a> QueryServerWriteTransaction<'a> {
+    /// Resolve a conflict entry, either by returning it to life or by permanently deleting it.
+    /// An entry can only be kept once the entry it conflicts with no longer has the same
+    /// unique values.
+    #[instrument(level = "info", skip(self))]
+    pub fn repl_conflict_resolve(
+        &mut self,
+        target: Uuid,
+        resolution: ReplConflictResolution,
+    ) -> Result<(), OperationError> {
+        let filt = filter_all!(f_and!([
+            f_eq("uuid", PartialValue::new_uuid(target)),
+            f_eq("class", PVCLASS_CONFLICT.clone()),
+            f_eq("class", PVCLASS_RECYCLED.clone()),
+        ]));
+
+        let conflicts = self.internal_search(filt)?;
+        if conflicts.is_empty() {
+            admin_error!(?target, "No conflict entry exists with this uuid");
+            return Err(OperationError::NoMatchingEntries);
+        }
+
+        match resolution {
+            ReplConflictResolution::Keep => {
+                let filt = filter!(f_eq("uuid", PartialValue::new_uuid(target)));
+                let re = ReviveRecycledEvent::from_parts(Identity::from_internal(), &filt, self)?;
+                self.revive_recycled(&re)?;
+
+                let modlist = ModifyList::new_remove("class", PVCLASS_CONFLICT.clone());
+                self.internal_modify(&filt, &modlist)?;
+                admin_info!(?target, "Conflict entry kept");
+            }
+            ReplConflictResolution::Discard => {
+                self.tombstone_recycled(&conflicts)?;
+                admin_info!(?target, "Conflict entry discarded");
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use kanidm_proto::v1::ReplConflictResolution;
+
+    use crate::prelude::*;
+
+    #[qs_test]
+    async fn test_repl_conflict_resolve(server: &QueryServer) {
+        let mut server_txn = server.write(duration_from_epoch_now()).await;
+
+        let uuid_a = Uuid::new_v4();
+        let uuid_b = Uuid::new_v4();
+        let uuid_c = Uuid::new_v4();
+        let entries = [
+            (uuid_a, "testperson1"),
+            (uuid_b, "testperson2"),
+            (uuid_c, "testperson3"),
+        ]
+        .iter()
+        .map(|(u, name)| {
+            entry_init!(
+                ("class", Value::new_class("object")),
+                ("class", Value::new_class("person")),
+                ("name", Value::new_iname(name)),
+                ("uuid", Value::new_uuid(*u)),
+                ("description", Value::new_utf8s("testperson")),
+                ("displayname", Value::new_utf8s("testperson"))
+            )
+        })
+        .collect();
+        assert!(server_txn.internal_create(entries).is_ok());
+
+        // Mark these as conflicts as a consumer would.
+        let filt = filter!(f_or!([
+            f_eq("uuid", PartialValue::new_uuid(uuid_a)),
+            f_eq("uuid", PartialValue::new_uuid(uuid_b)),
+        ]));
+        let modlist = ModifyList::new_append("class", Value::new_class("conflict"));
+        assert!(server_txn.internal_modify(&filt, &modlist).is_ok());
+        assert!(server_txn.internal_delete(&filt).is_ok());
+
+        // Only conflict entries can be resolved.
+        assert!(
+            server_txn.repl_conflict_resolve(uuid_c, ReplConflictResolution::Discard)
+                == Err(OperationError::NoMatchingEntries)
+        );
+
+        // A kept entry is live again, and is no longer a conflict.
+        assert!(server_txn
+            .repl_conflict_resolve(uuid_a, ReplConflictResolution::Keep)
+            .is_ok());
+        let e_a = server_txn.internal_search_uuid(&uuid_a).unwrap();
+        assert!(!e_a.attribute_equality("class", &PVCLASS_CONFLICT));
+
+        // A discarded entry is a tombstone.
+        assert!(server_txn
+            .repl_conflict_resolve(uuid_b, ReplConflictResolution::Discard)
+            .is_ok());
+        let e_b = server_txn
+            .internal_search(filter_all!(f_eq("uuid", PartialValue::new_uuid(uuid_b))))
+            .unwrap();
+        assert!(e_b.len() == 1);
+        assert!(e_b[0].attribute_equality("class", &PVCLASS_TOMBSTONE));
+
+        // Neither can be resolved again.
+        assert!(server_txn
+            .repl_conflict_resolve(uuid_a, ReplConflictResolution::Keep)
+            .is_err());
+        assert!(server_txn
+            .repl_conflict_resolve(uuid_b, ReplConflictResolution::Keep)
+            .is_err());
+
+        assert!(server_txn.commit().is_ok());
+    }
+}
@@ -0,0 +1,687 @@
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+use kanidm_proto::v1::EntryChange;
+
+use super::proto::{ReplIncrementalContext, ReplRuvRange};
+use crate::be::BackendTransaction;
+use crate::prelude::*;
+use crate::repl::cid::Cid;
+use crate::repl::entry::EntryChangelog;
+use crate::repl::ruv::{ruv_lag, ReplicationUpdateVectorTransaction};
+use crate::schema::SchemaTransaction;
+
+impl<'a> QueryServerReadTransaction<'a> {
+    /// The ranges of changes this server holds, to be sent to a supplier so that it can
+    /// determine what we are missing.
+    pub fn consumer_get_ruv_range(&self) -> ReplRuvRange {
+        ReplRuvRange {
+            domain_uuid: self.get_domain_uuid(),
+            ranges: self.get_be_txn().get_ruv().current_ruv_range(),
+        }
+    }
+}
+
+impl<'a> QueryServerWriteTransaction<'a> {
+    /// Apply the changes sent by a replication supplier. Entries are written as they exist on
+    /// the supplier, retaining their uuid and cid. An entry is only replaced if the supplier's
+    /// copy was changed more recently than ours.
+    #[instrument(level = "info", skip_all)]
+    pub fn consumer_apply_changes(
+        &mut self,
+        ctx: ReplIncrementalContext,
+    ) -> Result<(), OperationError> {
+        let (domain_uuid, ranges, entries) = match ctx {
+            ReplIncrementalContext::DomainMismatch => {
+                admin_error!("Replication supplier is not a member of this domain");
+                return Err(OperationError::ReplDomainUuidMismatch);
+            }
+            ReplIncrementalContext::NoChangesAvailable => {
+                debug!("No changes available from replication supplier");
+                return Ok(());
+            }
+            ReplIncrementalContext::V1 {
+                domain_uuid,
+                ranges,
+                entries,
+            } => (domain_uuid, ranges, entries),
+        };
+
+        if domain_uuid != self.get_domain_uuid() {
+            admin_error!(
+                supplier_domain_uuid = ?domain_uuid,
+                "Replication supplier is not a member of this domain"
+            );
+            return Err(OperationError::ReplDomainUuidMismatch);
+        }
+
+        let lag = ruv_lag(&self.get_be_txn().get_ruv().current_ruv_range(), &ranges);
+        admin_info!(?lag, "Replication consumer lag behind supplier");
+
+        let entries = entries
+            .into_iter()
+            .map(EntrySealedCommitted::from_repl_entry_v1)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let entries = self.consumer_resolve_conflicts(entries)?;
+
+        // Schema is applied ahead of data, so that data changes can use attributes and
+        // classes that were added on the supplier in the same set of changes.
+        let (schema_entries, data_entries): (Vec<_>, Vec<_>) =
+            entries.into_iter().partition(|(_, e)| is_schema_entry(e));
+
+        self.consumer_apply_entries(schema_entries)?;
+        if self.changed_schema.get() {
+            self.reload_schema()?;
+        }
+
+        // If the supplier sent data that uses schema we don't hold, applying it would
+        // corrupt our content. Nothing is applied, and our ruv is not advanced, so the
+        // changes are supplied again once the supplier sends the schema.
+        self.consumer_check_schema(&data_entries)?;
+        self.consumer_apply_entries(data_entries)?;
+
+        // We now hold every change the supplier does, even if some were already applied.
+        self.get_be_txn().get_ruv().merge_ranges(&ranges);
+
+        Ok(())
+    }
+
+    /// Refuse entries that have attributes or classes that are not in our schema.
+    fn consumer_check_schema(
+        &self,
+        entries: &[(Cid, EntrySealedNew)],
+    ) -> Result<(), OperationError> {
+        let schema = self.get_schema();
+        let attributes = schema.get_attributes();
+        let classes = schema.get_classes();
+
+        for (_, entry) in entries {
+            let unknown_attrs: Vec<&str> = entry
+                .get_ava_names()
+                .filter(|a| !attributes.contains_key(*a))
+                .collect();
+            let unknown_classes: Vec<&str> = entry
+                .get_ava_as_iutf8_iter("class")
+                .into_iter()
+                .flatten()
+                .filter(|c| !classes.contains_key(*c))
+                .collect();
+
+            if !unknown_attrs.is_empty() || !unknown_classes.is_empty() {
+                admin_error!(
+                    uuid = ?entry.get_uuid(),
+                    ?unknown_attrs,
+                    ?unknown_classes,
+                    "Replicated entry uses schema this server does not hold, refusing changes"
+                );
+                return Err(OperationError::ReplSchemaUnknown);
+            }
+        }
+        Ok(())
+    }
+
+    fn consumer_apply_entries(
+        &mut self,
+        entries: Vec<(Cid, EntrySealedNew)>,
+    ) -> Result<(), OperationError> {
+        let mut create_entries = Vec::new();
+        let mut update_entries = Vec::new();
+        let mut ts_max = Duration::ZERO;
+
+        for (cid, entry) in entries {
+            let filt = filter_all!(f_eq("uuid", PartialValue::new_uuid(entry.get_uuid())));
+            match self.internal_search(filt)?.pop() {
+                Some(pre) => match pre.merge_replicated(&cid, &entry) {
+                    Some(post) => {
+                        ts_max = std::cmp::max(ts_max, cid.ts);
+                        update_entries.push((cid, pre, post));
+                    }
+                    None => {
+                        trace!(uuid = ?entry.get_uuid(), "Local entry is newer, skipping");
+                    }
+                },
+                None => {
+                    ts_max = std::cmp::max(ts_max, cid.ts);
+                    create_entries.push((cid, entry));
+                }
+            }
+        }
+
+        if create_entries.is_empty() && update_entries.is_empty() {
+            debug!("All replicated changes are already applied");
+            return Ok(());
+        }
+
+        admin_info!(
+            created = create_entries.len(),
+            updated = update_entries.len(),
+            "Applying changes from replication supplier"
+        );
+
+        let c_entries = self
+            .get_be_txn()
+            .incremental_apply(&update_entries, create_entries)
+            .map_err(|e| {
+                admin_error!("betxn incremental apply failure {:?}", e);
+                e
+            })?;
+
+        // Our next change must be ordered after every change we now hold, including one
+        // that was made at the same time as ours.
+        self.cid = Cid::new_lamport(self.cid.d_uuid, self.cid.s_uuid, self.cid.ts, &ts_max);
+
+        // Both the prior and replicated states are checked, as the supplier may have changed
+        // an entry's class.
+        let applied: Vec<&EntrySealedCommitted> = c_entries
+            .iter()
+            .chain(
+                update_entries
+                    .iter()
+                    .flat_map(|(_, pre, post)| [pre.as_ref(), post]),
+            )
+            .collect();
+
+        // Flag if schema or acp requires reload.
+        if !self.changed_schema.get() {
+            self.changed_schema
+                .set(applied.iter().any(|e| is_schema_entry(*e)))
+        }
+        if !self.changed_acp.get() {
+            self.changed_acp.set(
+                applied
+                    .iter()
+                    .any(|e| e.attribute_equality("class", &PVCLASS_ACP)),
+            )
+        }
+        if !self.changed_oauth2.get() {
+            self.changed_oauth2.set(
+                applied
+                    .iter()
+                    .any(|e| e.attribute_equality("class", &PVCLASS_OAUTH2_RS)),
+            )
+        }
+        if !self.changed_domain.get() {
+            self.changed_domain.set(
+                applied
+                    .iter()
+                    .any(|e| e.attribute_equality("uuid", &PVUUID_DOMAIN_INFO)),
+            )
+        }
+
+        let cu = self.changed_uuid.as_ptr();
+        unsafe {
+            (*cu).extend(applied.iter().map(|e| e.get_uuid()));
+        }
+        // Entries that are created or deleted by a partner are modified from our view, since
+        // we apply their full state.
+        self.record_entry_changes(EntryChange::Modify, applied.iter().map(|e| e.get_uuid()));
+        trace!(
+            schema_reload = ?self.changed_schema,
+            acp_reload = ?self.changed_acp,
+            oauth2_reload = ?self.changed_oauth2,
+            domain_reload = ?self.changed_domain,
+        );
+
+        Ok(())
+    }
+
+    /// An entry created on a supplier may share a unique value, such as a name, with an entry
+    /// that was created here before we saw it. This can not be resolved by merging attributes,
+    /// so the entry that was created last becomes a conflict entry in the recycle bin, where
+    /// an administrator can review it. Every replica orders the creations the same way, and so
+    /// chooses the same entry.
+    fn consumer_resolve_conflicts(
+        &mut self,
+        entries: Vec<(Cid, EntrySealedNew)>,
+    ) -> Result<Vec<(Cid, EntrySealedNew)>, OperationError> {
+        let uniqueattrs = self.get_schema().get_attributes_unique().clone();
+
+        let mut local_conflicts = BTreeSet::new();
+        let mut resolved = Vec::with_capacity(entries.len());
+
+        for (cid, entry) in entries {
+            // Only live entries we do not yet hold can conflict on creation.
+            let is_new_live = !entry.attribute_equality("class", &PVCLASS_RECYCLED)
+                && !entry.attribute_equality("class", &PVCLASS_TOMBSTONE)
+                && !self.internal_exists(filter_all!(f_eq(
+                    "uuid",
+                    PartialValue::new_uuid(entry.get_uuid())
+                )))?;
+
+            if !is_new_live {
+                resolved.push((cid, entry));
+                continue;
+            }
+
+            let conflict_filt: Vec<_> = uniqueattrs
+                .iter()
+                .flat_map(|attr| {
+                    entry
+                        .get_ava_set(attr.as_str())
+                        .into_iter()
+                        .flat_map(|vs| vs.to_partialvalue_iter())
+                        .map(move |pv| f_eq(attr.as_str(), pv))
+                })
+                .collect();
+
+            if conflict_filt.is_empty() {
+                resolved.push((cid, entry));
+                continue;
+            }
+
+            let conflicts = self.internal_search(filter!(f_and(vec![
+                f_or(conflict_filt),
+                f_andnot(f_eq("uuid", PartialValue::new_uuid(entry.get_uuid()))),
+            ])))?;
+
+            let entry_order = creation_order(entry.get_changelog(), entry.get_uuid());
+            let mut entry_loses = false;
+            for local in conflicts.iter() {
+                if creation_order(local.get_changelog(), local.get_uuid()) < entry_order {
+                    entry_loses = true;
+                } else {
+                    local_conflicts.insert(local.get_uuid());
+                }
+            }
+
+            if entry_loses {
+                admin_warn!(
+                    uuid = ?entry.get_uuid(),
+                    "Replicated entry conflicts with an existing entry, marking as conflict"
+                );
+                resolved.push((cid.clone(), entry.into_conflict(&cid)));
+            } else {
+                resolved.push((cid, entry));
+            }
+        }
+
+        if !local_conflicts.is_empty() {
+            admin_warn!(
+                ?local_conflicts,
+                "Local entries conflict with replicated entries, marking as conflict"
+            );
+            let filt = filter!(f_or(
+                local_conflicts
+                    .iter()
+                    .map(|u| f_eq("uuid", PartialValue::new_uuid(*u)))
+                    .collect()
+            ));
+            let modlist = ModifyList::new_append("class", Value::new_class("conflict"));
+            self.internal_modify(&filt, &modlist)?;
+            self.internal_delete(&filt)?;
+        }
+
+        Ok(resolved)
+    }
+}
+
+pub(super) fn is_schema_entry<VALID, STATE>(e: &Entry<VALID, STATE>) -> bool {
+    e.attribute_equality("class", &PVCLASS_CLASSTYPE)
+        || e.attribute_equality("class", &PVCLASS_ATTRIBUTETYPE)
+}
+
+/// The order in which entries were created, as determined by the change that set their uuid.
+/// Entries that were created at the same time are ordered by their uuid.
+fn creation_order(eclog: &EntryChangelog, uuid: Uuid) -> (Option<Cid>, Uuid) {
+    (eclog.attr_cids().get("uuid").cloned(), uuid)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, BTreeSet};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use crate::prelude::*;
+    use crate::repl::proto::{ReplCidRange, ReplIncrementalContext, ReplRuvRange};
+    use crate::testkit::setup_test;
+
+    /// Supply the changes `from` holds that `to` does not. The test servers are separate
+    /// domains that were initialised independently, so the changes in `base` are treated as
+    /// already replicated, and the domain uuids are substituted.
+    async fn repl_supply(
+        from: &QueryServer,
+        to: &QueryServer,
+        base: &BTreeMap<Uuid, ReplCidRange>,
+    ) -> ReplIncrementalContext {
+        let mut ctx_ruv = to.read().await.consumer_get_ruv_range();
+        base.iter().for_each(|(s_uuid, range)| {
+            ctx_ruv
+                .ranges
+                .entry(*s_uuid)
+                .or_insert_with(|| range.clone());
+        });
+
+        let from_txn = from.read().await;
+        ctx_ruv.domain_uuid = from_txn.get_domain_uuid();
+        let ctx = from_txn
+            .supplier_provide_changes(&ctx_ruv)
+            .expect("Failed to supply changes");
+        drop(from_txn);
+
+        match ctx {
+            ReplIncrementalContext::V1 {
+                domain_uuid: _,
+                ranges,
+                entries,
+            } => ReplIncrementalContext::V1 {
+                domain_uuid: to.read().await.get_domain_uuid(),
+                ranges,
+                entries,
+            },
+            ctx => ctx,
+        }
+    }
+
+    async fn repl_incremental(
+        from: &QueryServer,
+        to: &QueryServer,
+        base: &BTreeMap<Uuid, ReplCidRange>,
+    ) {
+        let ctx = repl_supply(from, to, base).await;
+        let mut to_txn = to.write(duration_from_epoch_now()).await;
+        assert!(to_txn.consumer_apply_changes(ctx).is_ok());
+        assert!(to_txn.commit().is_ok());
+    }
+
+    async fn get_entry(server: &QueryServer, uuid: Uuid) -> Arc<EntrySealedCommitted> {
+        let server_txn = server.read().await;
+        server_txn
+            .internal_search(filter_all!(f_eq("uuid", PartialValue::new_uuid(uuid))))
+            .expect("Failed to search")
+            .pop()
+            .expect("Entry not found")
+    }
+
+    fn assert_entries_converged(a: &EntrySealedCommitted, b: &EntrySealedCommitted) {
+        let names: BTreeSet<&str> = a.get_ava_names().chain(b.get_ava_names()).collect();
+        for name in names {
+            assert!(a.get_ava_set(name) == b.get_ava_set(name));
+        }
+    }
+
+    #[qs_test]
+    async fn test_repl_incremental_supply_and_consume(server: &QueryServer) {
+        let server_txn = server.read().await;
+        let ctx_ruv = server_txn.consumer_get_ruv_range();
+
+        // A consumer that holds everything gets nothing.
+        assert!(matches!(
+            server_txn.supplier_provide_changes(&ctx_ruv),
+            Ok(ReplIncrementalContext::NoChangesAvailable)
+        ));
+
+        // A consumer from another domain is refused.
+        let foreign_ruv = ReplRuvRange {
+            domain_uuid: Uuid::new_v4(),
+            ..ctx_ruv.clone()
+        };
+        assert!(matches!(
+            server_txn.supplier_provide_changes(&foreign_ruv),
+            Ok(ReplIncrementalContext::DomainMismatch)
+        ));
+        drop(server_txn);
+
+        // Make a change after the consumer's ruv.
+        let t_uuid = Uuid::new_v4();
+        let mut server_txn = server.write(duration_from_epoch_now()).await;
+        assert!(server_txn
+            .internal_create(vec![entry_init!(
+                ("class", Value::new_class("object")),
+                ("class", Value::new_class("person")),
+                ("name", Value::new_iname("testperson1")),
+                ("uuid", Value::new_uuid(t_uuid)),
+                ("description", Value::new_utf8s("testperson1")),
+                ("displayname", Value::new_utf8s("testperson1"))
+            )])
+            .is_ok());
+        assert!(server_txn.commit().is_ok());
+
+        // The changed entry is supplied, with its cid.
+        let server_txn = server.read().await;
+        let ctx = server_txn
+            .supplier_provide_changes(&ctx_ruv)
+            .expect("Failed to supply changes");
+        match &ctx {
+            ReplIncrementalContext::V1 { entries, .. } => {
+                assert!(entries
+                    .iter()
+                    .any(|e| e.uuid == t_uuid && e.cid.ts > Duration::ZERO));
+            }
+            _ => assert!(false),
+        }
+        drop(server_txn);
+
+        // Applying changes we already hold is a no-op.
+        let mut server_txn = server.write(duration_from_epoch_now()).await;
+        assert!(server_txn.consumer_apply_changes(ctx).is_ok());
+        assert!(server_txn
+            .consumer_apply_changes(ReplIncrementalContext::DomainMismatch)
+            .is_err());
+        assert!(server_txn.commit().is_ok());
+    }
+
+    #[qs_test]
+    async fn test_repl_conflict_resolution_converges(server_a: &QueryServer) {
+        let server_b = setup_test().await;
+        server_b
+            .initialise_helper(duration_from_epoch_now())
+            .await
+            .expect("init failed!");
+
+        let mut base = server_a.read().await.consumer_get_ruv_range().ranges;
+        base.extend(server_b.read().await.consumer_get_ruv_range().ranges);
+
+        let person = |name: &str, uuid: Uuid| {
+            entry_init!(
+                ("class", Value::new_class("object")),
+                ("class", Value::new_class("person")),
+                ("name", Value::new_iname(name)),
+                ("uuid", Value::new_uuid(uuid)),
+                ("description", Value::new_utf8s(name)),
+                ("displayname", Value::new_utf8s(name))
+            )
+        };
+
+        // An entry that both replicas hold.
+        let shared_uuid = Uuid::new_v4();
+        let mut server_txn = server_a.write(duration_from_epoch_now()).await;
+        assert!(server_txn
+            .internal_create(vec![person("testshared", shared_uuid)])
+            .is_ok());
+        assert!(server_txn.commit().is_ok());
+        repl_incremental(server_a, &server_b, &base).await;
+
+        // Both replicas create an entry with the same name. A's is created first.
+        let a_uuid = Uuid::new_v4();
+        let mut server_txn = server_a.write(duration_from_epoch_now()).await;
+        assert!(server_txn
+            .internal_create(vec![person("testperson1", a_uuid)])
+            .is_ok());
+        assert!(server_txn.commit().is_ok());
+
+        let b_uuid = Uuid::new_v4();
+        let mut server_txn = server_b.write(duration_from_epoch_now()).await;
+        assert!(server_txn
+            .internal_create(vec![person("testperson1", b_uuid)])
+            .is_ok());
+        assert!(server_txn.commit().is_ok());
+
+        // Both replicas change different attributes of the shared entry. B's change is
+        // older, but must not be lost.
+        let mut server_txn = server_b.write(duration_from_epoch_now()).await;
+        assert!(server_txn
+            .internal_modify_uuid(
+                shared_uuid,
+                &ModifyList::new_purge_and_set("description", Value::new_utf8s("changed on b"))
+            )
+            .is_ok());
+        assert!(server_txn.commit().is_ok());
+
+        let mut server_txn = server_a.write(duration_from_epoch_now()).await;
+        assert!(server_txn
+            .internal_modify_uuid(
+                shared_uuid,
+                &ModifyList::new_purge_and_set("displayname", Value::new_utf8s("changed on a"))
+            )
+            .is_ok());
+        assert!(server_txn.commit().is_ok());
+
+        repl_incremental(server_a, &server_b, &base).await;
+        repl_incremental(&server_b, server_a, &base).await;
+        repl_incremental(server_a, &server_b, &base).await;
+
+        for uuid in [shared_uuid, a_uuid, b_uuid] {
+            let e_a = get_entry(server_a, uuid).await;
+            let e_b = get_entry(&server_b, uuid).await;
+            assert_entries_converged(&e_a, &e_b);
+        }
+
+        // Both attribute changes to the shared entry are retained.
+        let shared = get_entry(server_a, shared_uuid).await;
+        assert!(shared.attribute_equality("description", &PartialValue::new_utf8s("changed on b")));
+        assert!(shared.attribute_equality("displayname", &PartialValue::new_utf8s("changed on a")));
+
+        // The later creation is the conflict.
+        let e_a = get_entry(server_a, a_uuid).await;
+        assert!(!e_a.attribute_equality("class", &PVCLASS_CONFLICT));
+        let e_b = get_entry(server_a, b_uuid).await;
+        assert!(e_b.attribute_equality("class", &PVCLASS_CONFLICT));
+        assert!(e_b.attribute_equality("class", &PVCLASS_RECYCLED));
+
+        assert!(server_b.verify().await.is_empty());
+    }
+
+    #[qs_test]
+    async fn test_repl_consume_equal_timestamp(server_a: &QueryServer) {
+        let server_b = setup_test().await;
+        server_b
+            .initialise_helper(duration_from_epoch_now())
+            .await
+            .expect("init failed!");
+
+        let mut base = server_a.read().await.consumer_get_ruv_range().ranges;
+        base.extend(server_b.read().await.consumer_get_ruv_range().ranges);
+
+        // Both replicas write at exactly the same time.
+        let ct = duration_from_epoch_now() + Duration::from_secs(60);
+
+        let mut server_txn = server_b.write(ct).await;
+        assert!(server_txn
+            .internal_create(vec![entry_init!(
+                ("class", Value::new_class("object")),
+                ("class", Value::new_class("person")),
+                ("name", Value::new_iname("testperson1")),
+                ("uuid", Value::new_uuid(Uuid::new_v4())),
+                ("description", Value::new_utf8s("testperson1")),
+                ("displayname", Value::new_utf8s("testperson1"))
+            )])
+            .is_ok());
+        let b_cid = server_txn.cid.clone();
+        assert!(server_txn.commit().is_ok());
+
+        let ctx = repl_supply(&server_b, server_a, &base).await;
+        let mut server_txn = server_a.write(ct).await;
+        assert!(server_txn.cid.ts == ct);
+        assert!(server_txn.consumer_apply_changes(ctx).is_ok());
+        // Our next change is ordered after the replicated one, regardless of server uuids.
+        assert!(server_txn.cid.ts > b_cid.ts);
+        assert!(server_txn.cid > b_cid);
+        assert!(server_txn.commit().is_ok());
+    }
+
+    #[qs_test]
+    async fn test_repl_schema_ahead_of_data(server_a: &QueryServer) {
+        let server_b = setup_test().await;
+        server_b
+            .initialise_helper(duration_from_epoch_now())
+            .await
+            .expect("init failed!");
+
+        let mut base = server_a.read().await.consumer_get_ruv_range().ranges;
+        base.extend(server_b.read().await.consumer_get_ruv_range().ranges);
+
+        // A custom attribute, and an entry that uses it, are added on A.
+        let attr_uuid = Uuid::new_v4();
+        let t_uuid = Uuid::new_v4();
+        let mut server_txn = server_a.write(duration_from_epoch_now()).await;
+        assert!(server_txn
+            .internal_create(vec![entry_init!(
+                ("class", Value::new_class("object")),
+                ("class", Value::new_class("attributetype")),
+                ("uuid", Value::new_uuid(attr_uuid)),
+                ("attributename", Value::new_iutf8("testattr")),
+                ("description", Value::new_utf8s("Test Attribute")),
+                ("multivalue", Value::new_bool(false)),
+                ("unique", Value::new_bool(false)),
+                ("syntax", Value::new_syntaxs("UTF8STRING").expect("syntax"))
+            )])
+            .is_ok());
+        assert!(server_txn.commit().is_ok());
+
+        let mut server_txn = server_a.write(duration_from_epoch_now()).await;
+        assert!(server_txn
+            .internal_create(vec![entry_init!(
+                ("class", Value::new_class("object")),
+                ("class", Value::new_class("extensibleobject")),
+                ("uuid", Value::new_uuid(t_uuid)),
+                ("testattr", Value::new_utf8s("test"))
+            )])
+            .is_ok());
+        assert!(server_txn.commit().is_ok());
+
+        // Schema is supplied first.
+        let mut ctx_ruv = server_b.read().await.consumer_get_ruv_range();
+        base.iter().for_each(|(s_uuid, range)| {
+            ctx_ruv
+                .ranges
+                .entry(*s_uuid)
+                .or_insert_with(|| range.clone());
+        });
+        let server_txn = server_a.read().await;
+        ctx_ruv.domain_uuid = server_txn.get_domain_uuid();
+        let ctx = server_txn
+            .supplier_provide_changes(&ctx_ruv)
+            .expect("Failed to supply changes");
+        drop(server_txn);
+
+        let (ranges, entries) = match ctx {
+            ReplIncrementalContext::V1 {
+                ranges, entries, ..
+            } => (ranges, entries),
+            _ => {
+                assert!(false);
+                return;
+            }
+        };
+        let position = |uuid: Uuid| {
+            entries
+                .iter()
+                .position(|e| e.uuid == uuid)
+                .expect("Entry not supplied")
+        };
+        assert!(position(attr_uuid) < position(t_uuid));
+
+        // Data that uses schema the consumer does not hold is refused, and nothing applied.
+        let domain_uuid = server_b.read().await.get_domain_uuid();
+        let data_only: Vec<_> = entries.into_iter().filter(|e| e.uuid == t_uuid).collect();
+        let mut server_txn = server_b.write(duration_from_epoch_now()).await;
+        assert!(
+            server_txn.consumer_apply_changes(ReplIncrementalContext::V1 {
+                domain_uuid,
+                ranges,
+                entries: data_only,
+            }) == Err(OperationError::ReplSchemaUnknown)
+        );
+        drop(server_txn);
+        assert!(server_b.read().await.internal_search_uuid(&t_uuid).is_err());
+
+        // With the schema, both are applied in the same set of changes.
+        repl_incremental(server_a, &server_b, &base).await;
+        let e = get_entry(&server_b, t_uuid).await;
+        assert!(e.attribute_equality("testattr", &PartialValue::new_utf8s("test")));
+
+        assert!(server_b.verify().await.is_empty());
+    }
+}
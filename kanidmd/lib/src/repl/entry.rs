@@ -24,6 +24,9 @@ pub struct EntryChangelog {
     /// X have NOT been replayed and applied!
     anchors: BTreeMap<Cid, State>,
     changes: BTreeMap<Cid, Change>,
+    /// The cid that last changed each attribute, including attributes that were removed. This
+    /// is not trimmed with the changes, and is what replication conflicts are resolved by.
+    attr_cids: BTreeMap<AttrString, Cid>,
 }
 
 /*
@@ -70,6 +73,7 @@ enum Transition {
     Recycle,
     Revive,
     Tombstone(Eattrs),
+    Replicated(Eattrs),
 }
 
 impl fmt::Display for Transition {
@@ -83,11 +87,29 @@ impl fmt::Display for Transition {
             Transition::Recycle => write!(f, "Recycle"),
             Transition::Revive => write!(f, "Revive"),
             Transition::Tombstone(_) => write!(f, "Tombstone"),
+            Transition::Replicated(_) => write!(f, "Replicated"),
         }
     }
 }
 
 impl State {
+    fn from_eattrs(attrs: Eattrs) -> Self {
+        let class = attrs.get("class");
+        if class
+            .map(|c| c.contains(&PVCLASS_TOMBSTONE as &PartialValue))
+            .unwrap_or(false)
+        {
+            State::Tombstone(attrs)
+        } else if class
+            .map(|c| c.contains(&PVCLASS_RECYCLED as &PartialValue))
+            .unwrap_or(false)
+        {
+            State::Recycled(attrs)
+        } else {
+            State::Live(attrs)
+        }
+    }
+
     fn apply_change(self, change: &Change) -> Result<Self, Self> {
         let mut state = self;
         for transition in change.s.iter() {
@@ -171,6 +193,12 @@ impl State {
                     trace!("Recycled + Tombstone -> Tombstone");
                     state = State::Tombstone(attrs.clone());
                 }
+                // A supplier sends the whole entry, which replaces whatever we held.
+                (_, Transition::Replicated(attrs)) => {
+                    let new_state = State::from_eattrs(attrs.clone());
+                    trace!("{} + Replicated -> {}", state, new_state);
+                    state = new_state;
+                }
 
                 // ==============================
                 // Invalid States
@@ -204,6 +232,7 @@ impl EntryChangelog {
     pub fn new(cid: Cid, attrs: Eattrs, _schema: &dyn SchemaTransaction) -> Self {
         // I think we need to reduce the attrs based on what is / is not replicated.?
 
+        let attr_cids = attrs.keys().map(|k| (k.clone(), cid.clone())).collect();
         let anchors = btreemap![(cid.clone(), State::NonExistent)];
         let changes = btreemap![(
             cid,
@@ -212,7 +241,11 @@ impl EntryChangelog {
             }
         )];
 
-        EntryChangelog { anchors, changes }
+        EntryChangelog {
+            anchors,
+            changes,
+            attr_cids,
+        }
     }
 
     // TODO: work out if the below comment about uncommenting is still valid
@@ -220,6 +253,8 @@ impl EntryChangelog {
     pub fn new_without_schema(cid: Cid, attrs: Eattrs) -> Self {
         // I think we need to reduce the attrs based on what is / is not replicated.?
 
+        let attr_cids = attrs.keys().map(|k| (k.clone(), cid.clone())).collect();
+
         // We need to pick a state that reflects the current state WRT to tombstone
         // or recycled!
         let class = attrs.get("class");
@@ -248,13 +283,78 @@ impl EntryChangelog {
             )
         };
 
-        EntryChangelog { anchors, changes }
+        EntryChangelog {
+            anchors,
+            changes,
+            attr_cids,
+        }
+    }
+
+    /// Create the changelog of an entry received from a replication supplier. The entry
+    /// may be in any state, so unlike a create this is anchored as non-existent and then
+    /// replaced by the supplied content. Any attribute the supplier did not send a cid for
+    /// is assumed to have changed at this cid.
+    pub fn new_replicated(
+        cid: Cid,
+        attrs: Eattrs,
+        mut attr_cids: BTreeMap<AttrString, Cid>,
+    ) -> Self {
+        attrs.keys().for_each(|k| {
+            if !attr_cids.contains_key(k) {
+                attr_cids.insert(k.clone(), cid.clone());
+            }
+        });
+
+        let anchors = btreemap![(cid.clone(), State::NonExistent)];
+        let changes = btreemap![(
+            cid,
+            Change {
+                s: vec![Transition::Replicated(attrs)]
+            }
+        )];
+
+        EntryChangelog {
+            anchors,
+            changes,
+            attr_cids,
+        }
+    }
+
+    /// The cid that last changed each attribute of this entry.
+    pub fn attr_cids(&self) -> &BTreeMap<AttrString, Cid> {
+        &self.attr_cids
+    }
+
+    /// Restore the attribute cids that were persisted with this entry, replacing those that
+    /// were assumed when the changelog was created.
+    pub fn restore_attr_cids(&mut self, attr_cids: BTreeMap<AttrString, Cid>) {
+        self.attr_cids.extend(attr_cids.into_iter());
+    }
+
+    fn touch_attr(&mut self, cid: &Cid, attr: &str) {
+        self.attr_cids.insert(AttrString::from(attr), cid.clone());
+    }
+
+    pub fn replicated(&mut self, cid: &Cid, attrs: Eattrs, attr_cids: BTreeMap<AttrString, Cid>) {
+        self.attr_cids = attr_cids;
+
+        if !self.changes.contains_key(cid) {
+            self.changes.insert(cid.clone(), Change { s: Vec::new() });
+        }
+
+        #[allow(clippy::expect_used)]
+        let change = self
+            .changes
+            .get_mut(cid)
+            .expect("Memory corruption, change must exist");
+        change.s.push(Transition::Replicated(attrs));
     }
 
     pub fn add_ava_iter<T>(&mut self, cid: &Cid, attr: &str, viter: T)
     where
         T: IntoIterator<Item = Value>,
     {
+        self.touch_attr(cid, attr);
         if !self.changes.contains_key(cid) {
             self.changes.insert(cid.clone(), Change { s: Vec::new() });
         }
@@ -275,6 +375,7 @@ impl EntryChangelog {
     where
         T: IntoIterator<Item = PartialValue>,
     {
+        self.touch_attr(cid, attr);
         if !self.changes.contains_key(cid) {
             self.changes.insert(cid.clone(), Change { s: Vec::new() });
         }
@@ -309,6 +410,7 @@ impl EntryChangelog {
     }
 
     pub fn purge_ava(&mut self, cid: &Cid, attr: &str) {
+        self.touch_attr(cid, attr);
         if !self.changes.contains_key(cid) {
             self.changes.insert(cid.clone(), Change { s: Vec::new() });
         }
@@ -350,6 +452,12 @@ impl EntryChangelog {
     }
 
     pub fn tombstone(&mut self, cid: &Cid, attrs: Eattrs) {
+        // Every attribute is either retained or removed by this change.
+        self.attr_cids
+            .values_mut()
+            .for_each(|a_cid| *a_cid = cid.clone());
+        attrs.keys().for_each(|k| self.touch_attr(cid, k));
+
         if !self.changes.contains_key(cid) {
             self.changes.insert(cid.clone(), Change { s: Vec::new() });
         }
@@ -584,5 +692,16 @@ mod tests {
                 s: vec![Transition::Create(Eattrs::new())]
             })
             .is_err());
+
+        assert!(State::NonExistent
+            .apply_change(&Change {
+                s: vec![Transition::Replicated(Eattrs::new())]
+            })
+            .is_ok());
+        assert!(State::Tombstone(Eattrs::new())
+            .apply_change(&Change {
+                s: vec![Transition::Replicated(Eattrs::new())]
+            })
+            .is_ok());
     }
 }
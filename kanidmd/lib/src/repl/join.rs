@@ -0,0 +1,263 @@
+//! Enrolment of a new replica with a one time join token. An administrator creates the token
+//! on a supplier, which creates a replication agreement that is waiting for its partner. A new
+//! server started with the token connects to the supplier, pins the supplier's certificate from
+//! the token and presents the token's secret. The supplier then pins the new server's
+//! certificate on the agreement and discards the secret, after which the new server is
+//! initialised with a full refresh.
+
+use openssl::sha;
+use serde::{Deserialize, Serialize};
+
+use super::agreement::{normalise_cert_sha256, ReplAgreement};
+use super::refresh::ReplRefreshCollector;
+use crate::prelude::*;
+use crate::utils::password_from_random;
+
+/// Everything a new server needs to trust a supplier, and to be trusted by it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ReplJoinToken {
+    /// The name of the supplier's agreement with the new server.
+    pub name: String,
+    /// The address of the supplier's replication listener, in the form repl://host:port
+    pub supplier: String,
+    /// The hex encoded sha256 fingerprint of the supplier's certificate.
+    pub cert_sha256: String,
+    /// The secret the supplier accepts once to pin the new server's certificate.
+    pub secret: String,
+}
+
+impl ReplJoinToken {
+    pub fn encode(&self) -> Result<String, OperationError> {
+        serde_json::to_vec(self)
+            .map(|data| base64::encode_config(data, base64::URL_SAFE_NO_PAD))
+            .map_err(|e| {
+                admin_error!(?e, "Unable to encode replication join token");
+                OperationError::SerdeJsonError
+            })
+    }
+
+    pub fn decode(token: &str) -> Result<Self, OperationError> {
+        let data = base64::decode_config(token.trim(), base64::URL_SAFE_NO_PAD).map_err(|e| {
+            admin_error!(?e, "Replication join token is not valid base64");
+            OperationError::InvalidState
+        })?;
+        serde_json::from_slice(&data).map_err(|e| {
+            admin_error!(?e, "Replication join token is not valid");
+            OperationError::SerdeJsonError
+        })
+    }
+
+    /// The agreement this server holds with the supplier once it has joined.
+    pub fn supplier_agreement(&self) -> ReplAgreement {
+        ReplAgreement {
+            uuid: UUID_DOES_NOT_EXIST,
+            name: format!("{}_supplier", self.name),
+            partner_address: Some(self.supplier.to_lowercase()),
+            cert_sha256: std::iter::once(normalise_cert_sha256(&self.cert_sha256)).collect(),
+        }
+    }
+}
+
+fn join_secret_sha256(secret: &str) -> String {
+    hex::encode(sha::sha256(secret.as_bytes()))
+}
+
+impl<'a> QueryServerWriteTransaction<'a> {
+    /// Create an agreement that is waiting for a new server to join, and return the secret
+    /// that the new server must present. The secret is only stored as a hash, so it can't be
+    /// recovered if it is lost, and the agreement must be deleted and the token created again.
+    #[instrument(level = "info", skip(self))]
+    pub fn repl_join_token_create(&mut self, name: &str) -> Result<String, OperationError> {
+        let secret = password_from_random();
+
+        let e = entry_init!(
+            ("class", Value::new_class("object")),
+            ("class", Value::new_class("repl_agreement")),
+            ("name", Value::new_iname(name)),
+            (
+                "repl_join_token",
+                Value::new_iutf8(&join_secret_sha256(&secret))
+            )
+        );
+
+        self.internal_create(vec![e]).map(|_| {
+            admin_info!(%name, "Created replication agreement waiting for a new server to join");
+            secret
+        })
+    }
+
+    /// Accept a new server that presented the secret of a join token, by pinning the
+    /// certificate it presented on the agreement that is waiting for it. If the certificate
+    /// is already pinned this is a retry of a join that was already accepted.
+    #[instrument(level = "info", skip_all)]
+    pub fn supplier_accept_join(
+        &mut self,
+        secret: &str,
+        cert_sha256: &str,
+    ) -> Result<ReplAgreement, OperationError> {
+        if let Some(agreement) = self
+            .get_repl_agreements()?
+            .into_iter()
+            .find(|a| a.is_pinned(cert_sha256))
+        {
+            admin_info!(agreement = %agreement.name, "Replication partner has already joined");
+            return Ok(agreement);
+        }
+
+        let filt = filter!(f_and!([
+            f_eq("class", PartialValue::new_class("repl_agreement")),
+            f_eq(
+                "repl_join_token",
+                PartialValue::new_iutf8(&join_secret_sha256(secret))
+            ),
+        ]));
+
+        let waiting = self.internal_search(filt)?;
+        let target = match waiting.first() {
+            Some(e) if waiting.len() == 1 => e.get_uuid(),
+            _ => {
+                security_info!("Replication join secret does not match a waiting agreement");
+                return Err(OperationError::AccessDenied);
+            }
+        };
+
+        // The secret is only valid once.
+        let modlist = ModifyList::new_list(vec![
+            m_purge("repl_join_token"),
+            m_pres(
+                "repl_partner_cert_sha256",
+                &Value::new_iutf8(&normalise_cert_sha256(cert_sha256)),
+            ),
+        ]);
+        self.internal_modify(
+            &filter!(f_eq("uuid", PartialValue::new_uuid(target))),
+            &modlist,
+        )?;
+
+        self.get_repl_agreements()?
+            .into_iter()
+            .find(|a| a.uuid == target)
+            .map(|agreement| {
+                security_info!(
+                    agreement = %agreement.name,
+                    %cert_sha256,
+                    "Replication partner joined"
+                );
+                agreement
+            })
+            .ok_or(OperationError::InvalidState)
+    }
+
+    /// Initialise this server from the supplier of a join token, and create the agreement
+    /// this server uses to consume changes from it. The agreement must be created after the
+    /// refresh, since the refresh replaces all content of this server.
+    #[instrument(level = "info", skip_all)]
+    pub fn consumer_complete_join(
+        &mut self,
+        refresh: ReplRefreshCollector,
+        token: &ReplJoinToken,
+    ) -> Result<(), OperationError> {
+        self.consumer_apply_refresh(refresh)?;
+
+        let agreement = token.supplier_agreement();
+        let mut e = entry_init!(
+            ("class", Value::new_class("object")),
+            ("class", Value::new_class("repl_agreement")),
+            ("name", Value::new_iname(&agreement.name))
+        );
+        if let Some(address) = &agreement.partner_address {
+            e.add_ava("repl_partner_address", Value::new_iutf8(address));
+        }
+        agreement.cert_sha256.iter().for_each(|fp| {
+            e.add_ava("repl_partner_cert_sha256", Value::new_iutf8(fp));
+        });
+
+        self.internal_create(vec![e]).map(|_| {
+            admin_info!(
+                agreement = %agreement.name,
+                supplier = %token.supplier,
+                "Joined replication supplier"
+            );
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReplJoinToken;
+    use crate::prelude::*;
+    use crate::repl::refresh::ReplRefreshCollector;
+    use crate::testkit::setup_test;
+
+    #[qs_test]
+    async fn test_repl_join_token(server_a: &QueryServer) {
+        let server_b = setup_test().await;
+        server_b
+            .initialise_helper(duration_from_epoch_now())
+            .await
+            .expect("init failed!");
+
+        let mut server_txn = server_a.write(duration_from_epoch_now()).await;
+        let secret = server_txn
+            .repl_join_token_create("replica_b")
+            .expect("Failed to create join token");
+        assert!(server_txn.commit().is_ok());
+
+        let token = ReplJoinToken {
+            name: "replica_b".to_string(),
+            supplier: "repl://A.example.com:8444".to_string(),
+            cert_sha256: "AB:CD:EF:01".to_string(),
+            secret,
+        };
+        let encoded = token.encode().expect("Failed to encode join token");
+        assert!(ReplJoinToken::decode(&encoded) == Ok(token.clone()));
+
+        // An unknown secret is refused.
+        let mut server_txn = server_a.write(duration_from_epoch_now()).await;
+        assert!(
+            server_txn.supplier_accept_join("invalid", "abcdef02")
+                == Err(OperationError::AccessDenied)
+        );
+
+        // The secret pins the certificate of the new server.
+        let agreement = server_txn
+            .supplier_accept_join(&token.secret, "abcdef02")
+            .expect("Failed to accept join");
+        assert!(agreement.name == "replica_b");
+        assert!(agreement.is_pinned("AB:CD:EF:02"));
+
+        // A retry by the same server is accepted, but the secret can't be used again.
+        assert!(server_txn
+            .supplier_accept_join(&token.secret, "abcdef02")
+            .is_ok());
+        assert!(
+            server_txn.supplier_accept_join(&token.secret, "abcdef03")
+                == Err(OperationError::AccessDenied)
+        );
+        assert!(server_txn.commit().is_ok());
+
+        // The new server is initialised from the supplier, and can then consume from it.
+        let mut refresh = ReplRefreshCollector::new();
+        while !refresh.is_complete() {
+            let ctx = server_a
+                .read()
+                .await
+                .supplier_provide_refresh(&refresh.next_request())
+                .expect("Failed to supply refresh");
+            assert!(refresh.add_chunk(ctx).is_ok());
+        }
+
+        let mut server_txn = server_b.write(duration_from_epoch_now()).await;
+        assert!(server_txn.consumer_complete_join(refresh, &token).is_ok());
+        assert!(server_txn.commit().is_ok());
+
+        let server_txn = server_b.read().await;
+        assert!(server_txn.get_domain_uuid() == server_a.read().await.get_domain_uuid());
+        let agreement = server_txn
+            .get_repl_agreement_by_address("repl://a.example.com:8444")
+            .unwrap()
+            .expect("No agreement with supplier");
+        assert!(agreement.name == "replica_b_supplier");
+        assert!(agreement.is_pinned("abcdef01"));
+    }
+}
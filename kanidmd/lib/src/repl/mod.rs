@@ -1,3 +1,11 @@
+pub mod agreement;
 pub mod cid;
+pub mod conflict;
+pub mod consumer;
 pub mod entry;
+pub mod join;
+pub mod proto;
+pub mod refresh;
 pub mod ruv;
+pub mod status;
+pub mod supplier;
@@ -0,0 +1,88 @@
+//! The types exchanged between a replication consumer and supplier. A consumer sends
+//! the range of changes it holds from each server, and the supplier responds with the
+//! entries that have changed since then.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use kanidm_proto::v1::OperationError;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::cid::Cid;
+use crate::be::dbentry::DbEntry;
+
+/// The range of changes from a single server that are known to a replica.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct ReplCidRange {
+    pub ts_min: Duration,
+    pub ts_max: Duration,
+}
+
+/// Sent by a consumer to request the changes it has not yet seen.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct ReplRuvRange {
+    pub domain_uuid: Uuid,
+    /// The range of changes held by the consumer, keyed by the uuid of the originating server.
+    pub ranges: BTreeMap<Uuid, ReplCidRange>,
+}
+
+/// An entry as it exists on the supplier, along with the change id that last modified it.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReplEntryV1 {
+    pub uuid: Uuid,
+    pub cid: Cid,
+    pub entry: DbEntry,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum ReplIncrementalContext {
+    /// The consumer is not part of the same domain as the supplier.
+    DomainMismatch,
+    /// The consumer already holds every change the supplier has.
+    NoChangesAvailable,
+    V1 {
+        domain_uuid: Uuid,
+        /// The range of changes held by the supplier after these entries are applied.
+        ranges: BTreeMap<Uuid, ReplCidRange>,
+        entries: Vec<ReplEntryV1>,
+    },
+}
+
+/// Sent by a consumer to request a complete copy of the supplier's entries. Entries are
+/// supplied in uuid order, so an interrupted refresh resumes after the last uuid received.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct ReplRefreshRequest {
+    pub after: Option<Uuid>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum ReplRefreshContext {
+    V1 {
+        domain_uuid: Uuid,
+        /// The range of changes held by the supplier when this chunk was read.
+        ranges: BTreeMap<Uuid, ReplCidRange>,
+        entries: Vec<ReplEntryV1>,
+        /// True if no entries remain after this chunk.
+        complete: bool,
+    },
+}
+
+/// A request sent by a consumer over the dedicated replication listener. Each protocol
+/// version is a distinct variant so that partners can reject versions they don't support.
+#[derive(Serialize, Deserialize, Debug)]
+pub enum ReplProtoRequest {
+    V1Changes(ReplRuvRange),
+    V1Refresh(ReplRefreshRequest),
+    /// Sent by a new server with the secret of its join token.
+    V1Join(String),
+}
+
+/// A response sent by a supplier over the dedicated replication listener.
+#[derive(Serialize, Deserialize, Debug)]
+pub enum ReplProtoResponse {
+    V1Changes(ReplIncrementalContext),
+    V1Refresh(ReplRefreshContext),
+    V1Join,
+    V1Error(OperationError),
+}
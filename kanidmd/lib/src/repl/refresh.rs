@@ -0,0 +1,305 @@
+//! A full refresh initialises a new replica with a complete copy of a supplier's entries.
+//! The copy is supplied in chunks so that a refresh of a large database can be streamed and
+//! resumed if it is interrupted. Once every chunk is received the consumer replaces its
+//! content in a single transaction, and then continues with incremental replication from
+//! the supplier's RUV at the start of the copy.
+
+use std::collections::BTreeMap;
+
+use super::proto::{ReplCidRange, ReplEntryV1, ReplRefreshContext, ReplRefreshRequest};
+use crate::be::BackendTransaction;
+use crate::prelude::*;
+use crate::repl::ruv::ReplicationUpdateVectorTransaction;
+
+/// The number of entries sent in each chunk of a refresh.
+const REPL_REFRESH_CHUNK_SIZE: usize = 1000;
+
+impl<'a> QueryServerReadTransaction<'a> {
+    /// Supply the next chunk of a complete copy of this server's entries.
+    #[instrument(level = "info", skip_all)]
+    pub fn supplier_provide_refresh(
+        &self,
+        req: &ReplRefreshRequest,
+    ) -> Result<ReplRefreshContext, OperationError> {
+        let be_txn = self.get_be_txn();
+        let ranges = be_txn.get_ruv().current_ruv_range();
+
+        let (entries, complete) =
+            be_txn.retrieve_refresh_chunk(req.after, REPL_REFRESH_CHUNK_SIZE)?;
+
+        let entries = entries
+            .iter()
+            .map(|e| {
+                e.to_repl_entry_v1().ok_or_else(|| {
+                    admin_error!(uuid = ?e.get_uuid(), "Entry is missing last_modified_cid");
+                    OperationError::InvalidEntryState
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        admin_info!(
+            entries = entries.len(),
+            after = ?req.after,
+            complete,
+            "Supplying refresh to replication consumer"
+        );
+
+        Ok(ReplRefreshContext::V1 {
+            domain_uuid: self.get_domain_uuid(),
+            ranges,
+            entries,
+            complete,
+        })
+    }
+}
+
+/// Collects the chunks of a refresh as they are received from a supplier.
+#[derive(Debug, Default)]
+pub struct ReplRefreshCollector {
+    /// The supplier's domain and RUV when the first chunk was read. Changes made on the
+    /// supplier while the copy is in progress are after this RUV, so they are supplied
+    /// again by incremental replication.
+    snapshot: Option<(Uuid, BTreeMap<Uuid, ReplCidRange>)>,
+    after: Option<Uuid>,
+    entries: Vec<ReplEntryV1>,
+    complete: bool,
+}
+
+impl ReplRefreshCollector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The request for the next chunk, resuming after the last entry received.
+    pub fn next_request(&self) -> ReplRefreshRequest {
+        ReplRefreshRequest { after: self.after }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn add_chunk(&mut self, ctx: ReplRefreshContext) -> Result<(), OperationError> {
+        let ReplRefreshContext::V1 {
+            domain_uuid,
+            ranges,
+            entries,
+            complete,
+        } = ctx;
+
+        match &self.snapshot {
+            Some((snapshot_domain_uuid, _)) if *snapshot_domain_uuid != domain_uuid => {
+                admin_error!(
+                    supplier_domain_uuid = ?domain_uuid,
+                    "Replication supplier changed domain during a refresh"
+                );
+                return Err(OperationError::ReplDomainUuidMismatch);
+            }
+            Some(_) => {}
+            None => self.snapshot = Some((domain_uuid, ranges)),
+        }
+
+        // Entries must continue strictly after the last one we hold, otherwise the
+        // supplier has not resumed where we asked.
+        let mut after = self.after;
+        for e in entries.iter() {
+            if after.map(|a| e.uuid <= a).unwrap_or(false) {
+                admin_error!(uuid = ?e.uuid, "Replication supplier sent a refresh out of order");
+                return Err(OperationError::InvalidState);
+            }
+            after = Some(e.uuid);
+        }
+
+        self.after = after;
+        self.entries.extend(entries);
+        self.complete = complete;
+        Ok(())
+    }
+}
+
+impl<'a> QueryServerWriteTransaction<'a> {
+    /// Replace all content of this server with a complete copy from a supplier. After this
+    /// commits, this server is a member of the supplier's domain and holds every change the
+    /// supplier did at the start of the copy.
+    #[instrument(level = "info", skip_all)]
+    pub fn consumer_apply_refresh(
+        &mut self,
+        refresh: ReplRefreshCollector,
+    ) -> Result<(), OperationError> {
+        let ReplRefreshCollector {
+            snapshot,
+            entries,
+            complete,
+            ..
+        } = refresh;
+
+        let (domain_uuid, ranges) = match snapshot {
+            Some(snapshot) if complete => snapshot,
+            _ => {
+                admin_error!("Refusing to apply an incomplete refresh");
+                return Err(OperationError::InvalidState);
+            }
+        };
+
+        admin_warn!(
+            ?domain_uuid,
+            entries = entries.len(),
+            "Replacing all content with a refresh from replication supplier"
+        );
+
+        let dbentries = entries.into_iter().map(|re| re.entry).collect();
+
+        self.get_be_txn()
+            .refresh(domain_uuid, dbentries, &ranges)
+            .map_err(|e| {
+                admin_error!("betxn refresh failure {:?}", e);
+                e
+            })?;
+
+        // Loading the schema we were just sent requires the indexes of the current schema,
+        // and then the indexes must be rebuilt for any attributes the new schema indexes.
+        self.get_be_txn().reindex()?;
+        self.reload_schema()?;
+        self.get_be_txn().reindex()?;
+
+        // The domain info is reloaded from the database at commit.
+        self.cid.d_uuid = domain_uuid;
+
+        // Our next change must be ordered after every change we now hold.
+        if let Some(ts_max) = ranges.values().map(|r| r.ts_max).max() {
+            self.cid.ts = std::cmp::max(self.cid.ts, ts_max);
+        }
+
+        // Everything changed.
+        self.changed_schema.set(true);
+        self.changed_acp.set(true);
+        self.changed_oauth2.set(true);
+        self.changed_domain.set(true);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReplRefreshCollector;
+    use crate::prelude::*;
+    use crate::repl::proto::{ReplIncrementalContext, ReplRefreshContext};
+    use crate::testkit::setup_test;
+
+    #[qs_test]
+    async fn test_repl_refresh_initialises_consumer(server_a: &QueryServer) {
+        let server_b = setup_test().await;
+        server_b
+            .initialise_helper(duration_from_epoch_now())
+            .await
+            .expect("init failed!");
+
+        let t_uuid = Uuid::new_v4();
+        let mut server_txn = server_a.write(duration_from_epoch_now()).await;
+        assert!(server_txn
+            .internal_create(vec![entry_init!(
+                ("class", Value::new_class("object")),
+                ("class", Value::new_class("person")),
+                ("name", Value::new_iname("testperson1")),
+                ("uuid", Value::new_uuid(t_uuid)),
+                ("description", Value::new_utf8s("testperson1")),
+                ("displayname", Value::new_utf8s("testperson1"))
+            )])
+            .is_ok());
+        assert!(server_txn.commit().is_ok());
+
+        // The consumer is not a member of the supplier's domain, so can't be incrementally
+        // replicated.
+        let ctx_ruv = server_b.read().await.consumer_get_ruv_range();
+        assert!(matches!(
+            server_a.read().await.supplier_provide_changes(&ctx_ruv),
+            Ok(ReplIncrementalContext::DomainMismatch)
+        ));
+
+        // Collect the refresh, resuming after each chunk.
+        let mut refresh = ReplRefreshCollector::new();
+        while !refresh.is_complete() {
+            let ctx = server_a
+                .read()
+                .await
+                .supplier_provide_refresh(&refresh.next_request())
+                .expect("Failed to supply refresh");
+            assert!(refresh.add_chunk(ctx).is_ok());
+        }
+        assert!(!refresh.is_empty());
+
+        // A chunk from another domain is refused.
+        let mut foreign = ReplRefreshCollector::new();
+        assert!(foreign
+            .add_chunk(ReplRefreshContext::V1 {
+                domain_uuid: Uuid::new_v4(),
+                ranges: Default::default(),
+                entries: Vec::new(),
+                complete: false,
+            })
+            .is_ok());
+        assert!(foreign
+            .add_chunk(
+                server_a
+                    .read()
+                    .await
+                    .supplier_provide_refresh(&foreign.next_request())
+                    .expect("Failed to supply refresh")
+            )
+            .is_err());
+
+        let mut server_txn = server_b.write(duration_from_epoch_now()).await;
+        assert!(server_txn
+            .consumer_apply_refresh(ReplRefreshCollector::new())
+            .is_err());
+        assert!(server_txn.consumer_apply_refresh(refresh).is_ok());
+        assert!(server_txn.commit().is_ok());
+
+        // The consumer is now in the supplier's domain and holds its entries.
+        let server_txn = server_b.read().await;
+        assert!(server_txn.get_domain_uuid() == server_a.read().await.get_domain_uuid());
+        assert!(server_txn.internal_search_uuid(&t_uuid).is_ok());
+        drop(server_txn);
+
+        // And it continues with incremental replication.
+        let mut server_txn = server_a.write(duration_from_epoch_now()).await;
+        let modlist = ModifyList::new_purge_and_set("displayname", Value::new_utf8s("changed"));
+        assert!(server_txn
+            .internal_modify(
+                &filter!(f_eq("uuid", PartialValue::new_uuid(t_uuid))),
+                &modlist
+            )
+            .is_ok());
+        assert!(server_txn.commit().is_ok());
+
+        let ctx_ruv = server_b.read().await.consumer_get_ruv_range();
+        let ctx = server_a
+            .read()
+            .await
+            .supplier_provide_changes(&ctx_ruv)
+            .expect("Failed to supply changes");
+        assert!(matches!(ctx, ReplIncrementalContext::V1 { .. }));
+
+        let mut server_txn = server_b.write(duration_from_epoch_now()).await;
+        assert!(server_txn.consumer_apply_changes(ctx).is_ok());
+        assert!(server_txn.commit().is_ok());
+
+        let server_txn = server_b.read().await;
+        let e = server_txn
+            .internal_search_uuid(&t_uuid)
+            .expect("Entry not found");
+        assert!(e.get_ava_single_utf8("displayname") == Some("changed"));
+        drop(server_txn);
+
+        assert!(server_b.verify().await.is_empty());
+    }
+}
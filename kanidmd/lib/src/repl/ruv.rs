@@ -2,6 +2,7 @@ use std::cmp::Ordering;
 use std::collections::BTreeMap;
 use std::ops::Bound::*;
 use std::sync::Arc;
+use std::time::Duration;
 
 use concread::bptree::{BptreeMap, BptreeMapReadTxn, BptreeMapWriteTxn};
 use idlset::v2::IDLBitRange;
@@ -9,18 +10,23 @@ use kanidm_proto::v1::ConsistencyError;
 
 use crate::prelude::*;
 use crate::repl::cid::Cid;
+use crate::repl::proto::ReplCidRange;
 
 pub struct ReplicationUpdateVector {
     // This sorts by time. Should we look up by IDL or by UUID?
     // I think IDL, because when we need to actually do the look ups we'll need
     // to send this list to the BE to get the affected entries.
     data: BptreeMap<Cid, IDLBitRange>,
+    // The range of changes we hold from each server. This is persisted, so that we still
+    // know the most recent change from a server after its changes have been trimmed.
+    ranges: BptreeMap<Uuid, ReplCidRange>,
 }
 
 impl Default for ReplicationUpdateVector {
     fn default() -> Self {
         let data: BptreeMap<Cid, IDLBitRange> = BptreeMap::new();
-        ReplicationUpdateVector { data }
+        let ranges: BptreeMap<Uuid, ReplCidRange> = BptreeMap::new();
+        ReplicationUpdateVector { data, ranges }
     }
 }
 
@@ -28,27 +34,38 @@ impl ReplicationUpdateVector {
     pub fn write(&self) -> ReplicationUpdateVectorWriteTransaction<'_> {
         ReplicationUpdateVectorWriteTransaction {
             data: self.data.write(),
+            ranges: self.ranges.write(),
         }
     }
 
     pub fn read(&self) -> ReplicationUpdateVectorReadTransaction<'_> {
         ReplicationUpdateVectorReadTransaction {
             data: self.data.read(),
+            ranges: self.ranges.read(),
         }
     }
 }
 
 pub struct ReplicationUpdateVectorWriteTransaction<'a> {
     data: BptreeMapWriteTxn<'a, Cid, IDLBitRange>,
+    ranges: BptreeMapWriteTxn<'a, Uuid, ReplCidRange>,
 }
 
 pub struct ReplicationUpdateVectorReadTransaction<'a> {
     data: BptreeMapReadTxn<'a, Cid, IDLBitRange>,
+    ranges: BptreeMapReadTxn<'a, Uuid, ReplCidRange>,
 }
 
 pub trait ReplicationUpdateVectorTransaction {
     fn ruv_snapshot(&self) -> BTreeMap<Cid, IDLBitRange>;
 
+    /// The range of changes held from each server, keyed by the originating server uuid.
+    fn current_ruv_range(&self) -> BTreeMap<Uuid, ReplCidRange>;
+
+    /// The ids of all entries with changes that are not within the ranges a consumer
+    /// already holds.
+    fn range_to_idl(&self, ctx_ranges: &BTreeMap<Uuid, ReplCidRange>) -> IDLBitRange;
+
     fn verify(
         &self,
         entries: &[Arc<EntrySealedCommitted>],
@@ -134,6 +151,65 @@ pub trait ReplicationUpdateVectorTransaction {
     }
 }
 
+fn ruv_range_from_iter<'b, I>(iter: I) -> BTreeMap<Uuid, ReplCidRange>
+where
+    I: Iterator<Item = &'b Cid>,
+{
+    let mut ranges: BTreeMap<Uuid, ReplCidRange> = BTreeMap::new();
+    // The cids are in time order, so the first we see of a server is its min.
+    for cid in iter {
+        if let Some(range) = ranges.get_mut(&cid.s_uuid) {
+            range.ts_max = cid.ts;
+        } else {
+            ranges.insert(
+                cid.s_uuid,
+                ReplCidRange {
+                    ts_min: cid.ts,
+                    ts_max: cid.ts,
+                },
+            );
+        }
+    }
+    ranges
+}
+
+/// How far behind `supplier` the changes in `consumer` are, for each server the supplier holds
+/// changes from. A server that the consumer has never seen a change from lags by the whole range
+/// the supplier holds.
+pub fn ruv_lag(
+    consumer: &BTreeMap<Uuid, ReplCidRange>,
+    supplier: &BTreeMap<Uuid, ReplCidRange>,
+) -> BTreeMap<Uuid, Duration> {
+    supplier
+        .iter()
+        .map(|(s_uuid, s_range)| {
+            let lag = match consumer.get(s_uuid) {
+                Some(c_range) => s_range.ts_max.saturating_sub(c_range.ts_max),
+                None => s_range.ts_max.saturating_sub(s_range.ts_min),
+            };
+            (*s_uuid, lag)
+        })
+        .collect()
+}
+
+fn range_to_idl_from_iter<'b, I>(iter: I, ctx_ranges: &BTreeMap<Uuid, ReplCidRange>) -> IDLBitRange
+where
+    I: Iterator<Item = (&'b Cid, &'b IDLBitRange)>,
+{
+    let mut idl = IDLBitRange::new();
+    iter.filter(|(cid, _)| {
+        ctx_ranges
+            .get(&cid.s_uuid)
+            .map(|range| cid.ts > range.ts_max)
+            // The consumer has never seen a change from this server.
+            .unwrap_or(true)
+    })
+    .for_each(|(_, ex_idl)| {
+        idl = ex_idl as &_ | &idl;
+    });
+    idl
+}
+
 impl<'a> ReplicationUpdateVectorTransaction for ReplicationUpdateVectorWriteTransaction<'a> {
     fn ruv_snapshot(&self) -> BTreeMap<Cid, IDLBitRange> {
         self.data
@@ -141,6 +217,14 @@ impl<'a> ReplicationUpdateVectorTransaction for ReplicationUpdateVectorWriteTran
             .map(|(k, v)| (k.clone(), v.clone()))
             .collect()
     }
+
+    fn current_ruv_range(&self) -> BTreeMap<Uuid, ReplCidRange> {
+        self.ranges.iter().map(|(k, v)| (*k, v.clone())).collect()
+    }
+
+    fn range_to_idl(&self, ctx_ranges: &BTreeMap<Uuid, ReplCidRange>) -> IDLBitRange {
+        range_to_idl_from_iter(self.data.iter(), ctx_ranges)
+    }
 }
 
 impl<'a> ReplicationUpdateVectorTransaction for ReplicationUpdateVectorReadTransaction<'a> {
@@ -150,6 +234,14 @@ impl<'a> ReplicationUpdateVectorTransaction for ReplicationUpdateVectorReadTrans
             .map(|(k, v)| (k.clone(), v.clone()))
             .collect()
     }
+
+    fn current_ruv_range(&self) -> BTreeMap<Uuid, ReplCidRange> {
+        self.ranges.iter().map(|(k, v)| (*k, v.clone())).collect()
+    }
+
+    fn range_to_idl(&self, ctx_ranges: &BTreeMap<Uuid, ReplCidRange>) -> IDLBitRange {
+        range_to_idl_from_iter(self.data.iter(), ctx_ranges)
+    }
 }
 
 impl<'a> ReplicationUpdateVectorWriteTransaction<'a> {
@@ -182,9 +274,14 @@ impl<'a> ReplicationUpdateVectorWriteTransaction<'a> {
             idl.maybe_compress();
         });
 
+        let rebuild_ranges = ruv_range_from_iter(rebuild_ruv.keys());
+
         self.data.clear();
         self.data.extend(rebuild_ruv.into_iter());
 
+        self.ranges.clear();
+        self.ranges.extend(rebuild_ranges.into_iter());
+
         Ok(())
     }
 
@@ -197,9 +294,35 @@ impl<'a> ReplicationUpdateVectorWriteTransaction<'a> {
         } else {
             self.data.insert(cid.clone(), idl);
         }
+
+        if let Some(range) = self.ranges.get_mut(&cid.s_uuid) {
+            range.ts_min = std::cmp::min(range.ts_min, cid.ts);
+            range.ts_max = std::cmp::max(range.ts_max, cid.ts);
+        } else {
+            self.ranges.insert(
+                cid.s_uuid,
+                ReplCidRange {
+                    ts_min: cid.ts,
+                    ts_max: cid.ts,
+                },
+            );
+        }
         Ok(())
     }
 
+    /// Merge ranges of changes into this RUV, such as those persisted in the backend, or
+    /// those held by a replication supplier once its changes have been applied.
+    pub fn merge_ranges(&mut self, ranges: &BTreeMap<Uuid, ReplCidRange>) {
+        for (s_uuid, other) in ranges.iter() {
+            if let Some(range) = self.ranges.get_mut(s_uuid) {
+                range.ts_min = std::cmp::min(range.ts_min, other.ts_min);
+                range.ts_max = std::cmp::max(range.ts_max, other.ts_max);
+            } else {
+                self.ranges.insert(*s_uuid, other.clone());
+            }
+        }
+    }
+
     pub fn ruv_idls(&self) -> IDLBitRange {
         let mut idl = IDLBitRange::new();
         self.data.iter().for_each(|(_cid, ex_idl)| {
@@ -236,10 +359,118 @@ impl<'a> ReplicationUpdateVectorWriteTransaction<'a> {
         // that are affected.
         self.data.split_off_lt(cid);
 
+        // We still hold the most recent change from each server, but nothing before the trim
+        // point remains.
+        let trimmed: Vec<(Uuid, ReplCidRange)> = self
+            .ranges
+            .iter()
+            .filter(|(_, range)| range.ts_min < cid.ts)
+            .map(|(s_uuid, range)| {
+                (
+                    *s_uuid,
+                    ReplCidRange {
+                        ts_min: std::cmp::min(cid.ts, range.ts_max),
+                        ts_max: range.ts_max,
+                    },
+                )
+            })
+            .collect();
+        self.ranges.extend(trimmed.into_iter());
+
         Ok(idl)
     }
 
     pub fn commit(self) {
         self.data.commit();
+        self.ranges.commit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::time::Duration;
+
+    use idlset::v2::IDLBitRange;
+    use uuid::Uuid;
+
+    use super::{ruv_lag, ReplicationUpdateVector, ReplicationUpdateVectorTransaction};
+    use crate::repl::cid::Cid;
+    use crate::repl::proto::ReplCidRange;
+
+    #[test]
+    fn test_ruv_ranges_and_lag() {
+        let d_uuid = Uuid::new_v4();
+        let s_uuid_a = Uuid::new_v4();
+        let s_uuid_b = Uuid::new_v4();
+
+        let ruv = ReplicationUpdateVector::default();
+        let mut ruv_txn = ruv.write();
+
+        let mut idl = IDLBitRange::new();
+        idl.insert_id(1);
+        ruv_txn
+            .insert_change(
+                &Cid::new(d_uuid, s_uuid_a, Duration::from_secs(1)),
+                idl.clone(),
+            )
+            .unwrap();
+        ruv_txn
+            .insert_change(
+                &Cid::new(d_uuid, s_uuid_a, Duration::from_secs(5)),
+                idl.clone(),
+            )
+            .unwrap();
+
+        let ranges = ruv_txn.current_ruv_range();
+        assert!(
+            ranges.get(&s_uuid_a)
+                == Some(&ReplCidRange {
+                    ts_min: Duration::from_secs(1),
+                    ts_max: Duration::from_secs(5),
+                })
+        );
+        assert!(!ranges.contains_key(&s_uuid_b));
+
+        // A supplier holds later changes from a, and changes from b we have never seen.
+        let mut supplier = BTreeMap::new();
+        supplier.insert(
+            s_uuid_a,
+            ReplCidRange {
+                ts_min: Duration::from_secs(1),
+                ts_max: Duration::from_secs(8),
+            },
+        );
+        supplier.insert(
+            s_uuid_b,
+            ReplCidRange {
+                ts_min: Duration::from_secs(2),
+                ts_max: Duration::from_secs(6),
+            },
+        );
+
+        let lag = ruv_lag(&ranges, &supplier);
+        assert!(lag.get(&s_uuid_a) == Some(&Duration::from_secs(3)));
+        assert!(lag.get(&s_uuid_b) == Some(&Duration::from_secs(4)));
+
+        // Once merged we are no longer behind.
+        ruv_txn.merge_ranges(&supplier);
+        let lag = ruv_lag(&ruv_txn.current_ruv_range(), &supplier);
+        assert!(lag.values().all(|l| *l == Duration::ZERO));
+
+        // Trimming our changes retains the most recent change we know of.
+        ruv_txn
+            .trim_up_to(&Cid::new(d_uuid, s_uuid_a, Duration::from_secs(10)))
+            .unwrap();
+        assert!(ruv_txn.ruv_snapshot().is_empty());
+        let ranges = ruv_txn.current_ruv_range();
+        assert!(
+            ranges.get(&s_uuid_a)
+                == Some(&ReplCidRange {
+                    ts_min: Duration::from_secs(8),
+                    ts_max: Duration::from_secs(8),
+                })
+        );
+        ruv_txn.commit();
     }
 }
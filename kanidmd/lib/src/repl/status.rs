@@ -0,0 +1,406 @@
+//! The state of replication with each partner, as observed by this server. This is held in
+//! memory and is reset when the server restarts, so that recording an exchange of changes
+//! never needs a write to the database.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use concread::cowcell::CowCell;
+use kanidm_proto::v1::{ReplAgreementStatus, ReplRangeStatus, ReplTopologyStatus};
+use time::OffsetDateTime;
+
+use super::proto::ReplCidRange;
+use crate::be::BackendTransaction;
+use crate::prelude::*;
+use crate::repl::agreement::ReplAgreement;
+use crate::repl::ruv::ReplicationUpdateVectorTransaction;
+
+#[derive(Debug, Clone, Default)]
+pub struct ReplPartnerStatus {
+    /// When changes were last exchanged with this partner.
+    pub last_sync: Option<Duration>,
+    /// The range of changes the partner held at the last exchange.
+    pub ranges: BTreeMap<Uuid, ReplCidRange>,
+    /// The number of entries applied from the partner at the last exchange, and how long
+    /// applying them took.
+    pub last_applied: Option<(u64, Duration)>,
+    /// The last failure to exchange changes with the partner, and when it occurred.
+    pub last_error: Option<(Duration, String)>,
+    /// If replication with the partner has failed in a way that won't recover without an
+    /// administrator, such as the partner belonging to another domain.
+    pub fatal: bool,
+}
+
+/// If a failure to exchange changes will recur until an administrator intervenes.
+fn is_fatal_error(err: &OperationError) -> bool {
+    matches!(
+        err,
+        OperationError::ReplDomainUuidMismatch | OperationError::ReplInvalidRUVState
+    )
+}
+
+pub struct ReplStatus {
+    s_uuid: Uuid,
+    /// Keyed by the uuid of the replication agreement with the partner.
+    partners: CowCell<BTreeMap<Uuid, ReplPartnerStatus>>,
+}
+
+impl ReplStatus {
+    pub fn new(s_uuid: Uuid) -> Self {
+        ReplStatus {
+            s_uuid,
+            partners: CowCell::new(BTreeMap::new()),
+        }
+    }
+
+    /// Record an exchange of changes with the partner of an agreement. The partner's ranges
+    /// are only known if changes were sent in the exchange.
+    pub fn record_sync(
+        &self,
+        agreement: Uuid,
+        ranges: Option<BTreeMap<Uuid, ReplCidRange>>,
+        ct: Duration,
+    ) {
+        self.update(agreement, |status| {
+            status.last_sync = Some(ct);
+            status.fatal = false;
+            if let Some(ranges) = ranges {
+                status.ranges = ranges;
+            }
+        })
+    }
+
+    /// Record that entries from the partner of an agreement were applied.
+    pub fn record_applied(&self, agreement: Uuid, changes: u64, elapsed: Duration) {
+        self.update(agreement, |status| {
+            status.last_applied = Some((changes, elapsed));
+        })
+    }
+
+    /// Record a failure to exchange changes with the partner of an agreement.
+    pub fn record_error(&self, agreement: Uuid, err: &OperationError, ct: Duration) {
+        self.update(agreement, |status| {
+            status.last_error = Some((ct, format!("{:?}", err)));
+            status.fatal = is_fatal_error(err);
+        })
+    }
+
+    fn update<F>(&self, agreement: Uuid, f: F)
+    where
+        F: FnOnce(&mut ReplPartnerStatus),
+    {
+        let mut partners = self.partners.write();
+        f(partners.get_mut().entry(agreement).or_default());
+        partners.commit();
+    }
+
+    pub fn get(&self, agreement: Uuid) -> Option<ReplPartnerStatus> {
+        self.partners.read().get(&agreement).cloned()
+    }
+
+    /// The agreements whose replication has failed in a way that won't recover by itself.
+    pub fn fatal_agreements(&self) -> Vec<Uuid> {
+        self.partners
+            .read()
+            .iter()
+            .filter(|(_, status)| status.fatal)
+            .map(|(agreement, _)| *agreement)
+            .collect()
+    }
+}
+
+fn to_range_status(ranges: &BTreeMap<Uuid, ReplCidRange>) -> Vec<ReplRangeStatus> {
+    ranges
+        .iter()
+        .map(|(s_uuid, range)| ReplRangeStatus {
+            server_uuid: *s_uuid,
+            first_change: OffsetDateTime::unix_epoch() + range.ts_min,
+            last_change: OffsetDateTime::unix_epoch() + range.ts_max,
+        })
+        .collect()
+}
+
+/// How long the oldest change that the partner has not received has been waiting. This is
+/// measured from the last change the partner holds from each server, so when changes are
+/// infrequent it overstates the lag rather than hiding it.
+fn partner_lag(
+    ranges: &BTreeMap<Uuid, ReplCidRange>,
+    partner_ranges: &BTreeMap<Uuid, ReplCidRange>,
+    ct: Duration,
+) -> Duration {
+    ranges
+        .iter()
+        .filter_map(|(s_uuid, range)| {
+            let held = partner_ranges
+                .get(s_uuid)
+                .map(|partner_range| partner_range.ts_max)
+                .unwrap_or(range.ts_min);
+            if held < range.ts_max {
+                Some(ct.saturating_sub(held))
+            } else {
+                None
+            }
+        })
+        .max()
+        .unwrap_or_default()
+}
+
+impl<'a> QueryServerReadTransaction<'a> {
+    fn repl_agreement_status(&self, agreement: ReplAgreement, ct: Duration) -> ReplAgreementStatus {
+        let status = self
+            .get_repl_status()
+            .get(agreement.uuid)
+            .unwrap_or_default();
+        let ruv = self.get_be_txn().get_ruv();
+
+        // Until we have exchanged changes we don't know what the partner holds.
+        let (pending_changes, lag_secs) = match status.last_sync {
+            Some(_) => (
+                Some(ruv.range_to_idl(&status.ranges).len() as u64),
+                Some(partner_lag(&ruv.current_ruv_range(), &status.ranges, ct).as_secs()),
+            ),
+            None => (None, None),
+        };
+
+        let changes_per_sec = status
+            .last_applied
+            .filter(|(_, elapsed)| !elapsed.is_zero())
+            .map(|(changes, elapsed)| changes as f64 / elapsed.as_secs_f64());
+
+        let (last_error_time, last_error) = match status.last_error {
+            Some((ts, err)) => (Some(OffsetDateTime::unix_epoch() + ts), Some(err)),
+            None => (None, None),
+        };
+
+        ReplAgreementStatus {
+            uuid: agreement.uuid,
+            name: agreement.name,
+            partner_address: agreement.partner_address,
+            partner_ruv: to_range_status(&status.ranges),
+            last_sync: status.last_sync.map(|ts| OffsetDateTime::unix_epoch() + ts),
+            pending_changes,
+            lag_secs,
+            changes_per_sec,
+            last_error,
+            last_error_time,
+        }
+    }
+
+    /// Report the agreements of this server, and the state of replication with each partner.
+    #[instrument(level = "debug", skip_all)]
+    pub fn repl_topology_status(&self, ct: Duration) -> Result<ReplTopologyStatus, OperationError> {
+        let agreements = self
+            .get_repl_agreements()?
+            .into_iter()
+            .map(|agreement| self.repl_agreement_status(agreement, ct))
+            .collect();
+
+        Ok(ReplTopologyStatus {
+            domain_uuid: self.get_domain_uuid(),
+            server_uuid: self.get_repl_status().s_uuid,
+            ruv: to_range_status(&self.get_be_txn().get_ruv().current_ruv_range()),
+            agreements,
+        })
+    }
+
+    /// Emit the metrics of an agreement as an event, so that alerting can fire when the
+    /// partner falls behind.
+    pub fn repl_emit_metrics(&self, agreement: ReplAgreement, ct: Duration) {
+        let status = self.repl_agreement_status(agreement, ct);
+        admin_info!(
+            agreement = %status.name,
+            agreement_uuid = %status.uuid,
+            lag_secs = ?status.lag_secs,
+            pending_changes = ?status.pending_changes,
+            changes_per_sec = ?status.changes_per_sec,
+            last_error = ?status.last_error,
+            "Replication metrics"
+        );
+    }
+}
+
+impl<'a> QueryServerWriteTransaction<'a> {
+    /// The latest time before which every replication partner holds all of our changes.
+    /// Changes after this may still need to be supplied to a partner, so their tombstones
+    /// and changelogs must be retained. This is `None` if there are no partners.
+    pub(crate) fn repl_partner_min_ts(&self) -> Result<Option<Duration>, OperationError> {
+        let agreements = self.get_repl_agreements()?;
+        if agreements.is_empty() {
+            return Ok(None);
+        }
+
+        let repl_status = self.get_repl_status();
+        let ranges = self.get_be_txn().get_ruv().current_ruv_range();
+
+        let min_ts = agreements
+            .iter()
+            .flat_map(|agreement| {
+                // If we have not exchanged changes with a partner since we started we can't
+                // know what it holds, so nothing can be released.
+                let partner_ranges = repl_status
+                    .get(agreement.uuid)
+                    .filter(|status| status.last_sync.is_some())
+                    .map(|status| status.ranges)
+                    .unwrap_or_default();
+                ranges.keys().map(move |s_uuid| {
+                    partner_ranges
+                        .get(s_uuid)
+                        .map(|range| range.ts_max)
+                        .unwrap_or_default()
+                })
+            })
+            .min()
+            .unwrap_or_default();
+
+        Ok(Some(min_ts))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::prelude::*;
+
+    #[qs_test]
+    async fn test_repl_topology_status(server: &QueryServer) {
+        let mut server_txn = server.write(duration_from_epoch_now()).await;
+        let a_uuid = Uuid::new_v4();
+        assert!(server_txn
+            .internal_create(vec![entry_init!(
+                ("class", Value::new_class("object")),
+                ("class", Value::new_class("repl_agreement")),
+                ("name", Value::new_iname("replica_b")),
+                ("uuid", Value::new_uuid(a_uuid)),
+                ("repl_partner_cert_sha256", Value::new_iutf8("abcdef01"))
+            )])
+            .is_ok());
+        assert!(server_txn.commit().is_ok());
+
+        // Nothing has been exchanged with the partner yet.
+        let ct = duration_from_epoch_now();
+        let server_txn = server.read().await;
+        let status = server_txn.repl_topology_status(ct).unwrap();
+        assert!(status.domain_uuid == server_txn.get_domain_uuid());
+        assert!(!status.ruv.is_empty());
+        assert!(status.agreements.len() == 1);
+        assert!(status.agreements[0].name == "replica_b");
+        assert!(status.agreements[0].last_sync.is_none());
+        assert!(status.agreements[0].pending_changes.is_none());
+        assert!(status.agreements[0].lag_secs.is_none());
+
+        // A partner that holds none of our changes has every entry pending, and is behind
+        // by the age of our oldest change.
+        server_txn
+            .get_repl_status()
+            .record_sync(a_uuid, Some(Default::default()), ct);
+        let status = server_txn
+            .repl_topology_status(ct + Duration::from_secs(60))
+            .unwrap();
+        assert!(status.agreements[0].last_sync.is_some());
+        assert!(status.agreements[0].pending_changes.unwrap() > 0);
+        assert!(status.agreements[0].lag_secs.unwrap() >= 60);
+
+        // And a partner that holds all of our changes has nothing pending.
+        let ranges = server_txn.consumer_get_ruv_range().ranges;
+        server_txn
+            .get_repl_status()
+            .record_sync(a_uuid, Some(ranges), ct);
+        let status = server_txn
+            .repl_topology_status(ct + Duration::from_secs(60))
+            .unwrap();
+        assert!(status.agreements[0].pending_changes == Some(0));
+        assert!(status.agreements[0].lag_secs == Some(0));
+        assert!(status.agreements[0].partner_ruv == status.ruv);
+
+        // The rate and failures of applying changes are reported.
+        server_txn
+            .get_repl_status()
+            .record_applied(a_uuid, 10, Duration::from_secs(2));
+        server_txn
+            .get_repl_status()
+            .record_error(a_uuid, &OperationError::InvalidState, ct);
+        let status = server_txn.repl_topology_status(ct).unwrap();
+        assert!(status.agreements[0].changes_per_sec == Some(5.0));
+        assert!(status.agreements[0].last_error.as_deref() == Some("InvalidState"));
+        assert!(status.agreements[0].last_error_time.is_some());
+        assert!(server_txn.get_repl_status().fatal_agreements().is_empty());
+
+        // A partner in another domain won't recover until the next successful exchange.
+        server_txn.get_repl_status().record_error(
+            a_uuid,
+            &OperationError::ReplDomainUuidMismatch,
+            ct,
+        );
+        assert!(server_txn.get_repl_status().fatal_agreements() == vec![a_uuid]);
+        assert!(server_txn.ready_check().is_err());
+        server_txn.get_repl_status().record_sync(a_uuid, None, ct);
+        assert!(server_txn.get_repl_status().fatal_agreements().is_empty());
+        assert!(server_txn.ready_check().is_ok());
+    }
+
+    #[qs_test]
+    async fn test_repl_tombstone_purge_waits_for_partners(server: &QueryServer) {
+        let time_p1 = duration_from_epoch_now();
+        let time_p2 = time_p1 + Duration::from_secs(RECYCLEBIN_MAX_AGE * 2);
+        let time_p3 = time_p2 + Duration::from_secs(CHANGELOG_MAX_AGE * 2);
+
+        let a_uuid = Uuid::new_v4();
+        let t_uuid = Uuid::new_v4();
+        let t_filt = filter_all!(f_eq("uuid", PartialValue::new_uuid(t_uuid)));
+
+        let mut server_txn = server.write(time_p1).await;
+        assert!(server_txn
+            .internal_create(vec![
+                entry_init!(
+                    ("class", Value::new_class("object")),
+                    ("class", Value::new_class("repl_agreement")),
+                    ("name", Value::new_iname("replica_b")),
+                    ("uuid", Value::new_uuid(a_uuid)),
+                    ("repl_partner_cert_sha256", Value::new_iutf8("abcdef01"))
+                ),
+                entry_init!(
+                    ("class", Value::new_class("object")),
+                    ("class", Value::new_class("person")),
+                    ("name", Value::new_iname("testperson1")),
+                    ("uuid", Value::new_uuid(t_uuid)),
+                    ("description", Value::new_utf8s("testperson1")),
+                    ("displayname", Value::new_utf8s("testperson1"))
+                )
+            ])
+            .is_ok());
+        assert!(server_txn.internal_delete(&t_filt).is_ok());
+        assert!(server_txn.commit().is_ok());
+
+        let server_txn = server.write(time_p2).await;
+        assert!(server_txn.purge_recycled().is_ok());
+        assert!(server_txn.commit().is_ok());
+
+        // The partner has not been seen, so it may not hold the tombstone yet.
+        let server_txn = server.write(time_p3).await;
+        assert!(server_txn.purge_tombstones().is_ok());
+        assert!(server_txn.internal_search(t_filt.clone()).unwrap().len() == 1);
+        assert!(server_txn.commit().is_ok());
+
+        // Once the partner holds all of our changes, the tombstone can be purged.
+        let server_txn = server.read().await;
+        let ranges = server_txn.consumer_get_ruv_range().ranges;
+        server_txn
+            .get_repl_status()
+            .record_sync(a_uuid, Some(ranges), time_p3);
+        drop(server_txn);
+
+        // A longer retention keeps the tombstone even though the partner holds it.
+        let server_txn = server.write(time_p3).await;
+        assert!(server_txn
+            .purge_tombstones_retain(CHANGELOG_MAX_AGE * 4)
+            .is_ok());
+        assert!(server_txn.internal_search(t_filt.clone()).unwrap().len() == 1);
+        assert!(server_txn.commit().is_ok());
+
+        let server_txn = server.write(time_p3).await;
+        assert!(server_txn.purge_tombstones().is_ok());
+        assert!(server_txn.internal_search(t_filt).unwrap().is_empty());
+        assert!(server_txn.commit().is_ok());
+    }
+}
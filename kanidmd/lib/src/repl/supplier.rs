@@ -0,0 +1,63 @@
+use super::consumer::is_schema_entry;
+use super::proto::{ReplIncrementalContext, ReplRuvRange};
+use crate::be::BackendTransaction;
+use crate::prelude::*;
+use crate::repl::ruv::{ruv_lag, ReplicationUpdateVectorTransaction};
+
+impl<'a> QueryServerReadTransaction<'a> {
+    /// Determine the entries that a consumer needs to become consistent with this server,
+    /// given the ranges of changes that the consumer already holds.
+    #[instrument(level = "info", skip_all)]
+    pub fn supplier_provide_changes(
+        &self,
+        ctx_ruv: &ReplRuvRange,
+    ) -> Result<ReplIncrementalContext, OperationError> {
+        let domain_uuid = self.get_domain_uuid();
+
+        if ctx_ruv.domain_uuid != domain_uuid {
+            admin_warn!(
+                consumer_domain_uuid = ?ctx_ruv.domain_uuid,
+                ?domain_uuid,
+                "Replication consumer is not a member of this domain"
+            );
+            return Ok(ReplIncrementalContext::DomainMismatch);
+        }
+
+        let be_txn = self.get_be_txn();
+        let ranges = be_txn.get_ruv().current_ruv_range();
+
+        let lag = ruv_lag(&ctx_ruv.ranges, &ranges);
+        debug!(?lag, "Replication consumer lag");
+
+        let mut entries = be_txn.retrieve_range(&ctx_ruv.ranges)?;
+
+        if entries.is_empty() {
+            debug!("Replication consumer is up to date");
+            return Ok(ReplIncrementalContext::NoChangesAvailable);
+        }
+
+        // Schema is supplied ahead of the data that may depend on it.
+        entries.sort_by_key(|e| !is_schema_entry(e.as_ref()));
+
+        let entries = entries
+            .iter()
+            .map(|e| {
+                e.to_repl_entry_v1().ok_or_else(|| {
+                    admin_error!(uuid = ?e.get_uuid(), "Entry is missing last_modified_cid");
+                    OperationError::InvalidEntryState
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        admin_info!(
+            entries = entries.len(),
+            "Supplying changes to replication consumer"
+        );
+
+        Ok(ReplIncrementalContext::V1 {
+            domain_uuid,
+            ranges,
+            entries,
+        })
+    }
+}
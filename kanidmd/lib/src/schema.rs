@@ -91,6 +91,11 @@ pub struct SchemaAttribute {
     pub phantom: bool,
     pub sync_allowed: bool,
     pub index: Vec<IndexType>,
+    /// If the values of this attribute are encrypted at rest by the database seal key. An
+    /// encrypted attribute can't be indexed, as its index keys would disclose its values.
+    pub encrypted: bool,
+    /// The largest size of a value of this attribute, in bytes, for syntaxes that are bounded.
+    pub max_size: Option<u32>,
     pub syntax: SyntaxType,
 }
 
@@ -147,6 +152,8 @@ impl SchemaAttribute {
             admin_error!("invalid index - {}", name);
             OperationError::InvalidSchemaState("invalid index".to_string())
         })?;
+        let encrypted = value.get_ava_single_bool("encrypted").unwrap_or(false);
+        let max_size = value.get_ava_single_uint32("max_size");
         // syntax type
         let syntax = value.get_ava_single_syntax("syntax").ok_or_else(|| {
             admin_error!("missing syntax - {}", name);
@@ -162,6 +169,8 @@ impl SchemaAttribute {
             phantom,
             sync_allowed,
             index,
+            encrypted,
+            max_size,
             syntax,
         })
     }
@@ -203,6 +212,10 @@ impl SchemaAttribute {
             SyntaxType::JwsKeyEs256 => matches!(v, PartialValue::Iutf8(_)),
             SyntaxType::JwsKeyRs256 => matches!(v, PartialValue::Iutf8(_)),
             SyntaxType::UiHint => matches!(v, PartialValue::UiHint(_)),
+            SyntaxType::Address => matches!(v, PartialValue::Address(_)),
+            SyntaxType::PhoneNumber => matches!(v, PartialValue::PhoneNumber(_)),
+            SyntaxType::Binary => matches!(v, PartialValue::Binary(_)),
+            SyntaxType::Image => matches!(v, PartialValue::Image(_)),
         };
         if r {
             Ok(())
@@ -250,6 +263,10 @@ impl SchemaAttribute {
                 SyntaxType::JwsKeyEs256 => matches!(v, Value::JwsKeyEs256(_)),
                 SyntaxType::JwsKeyRs256 => matches!(v, Value::JwsKeyRs256(_)),
                 SyntaxType::UiHint => matches!(v, Value::UiHint(_)),
+                SyntaxType::Address => matches!(v, Value::Address(_)),
+                SyntaxType::PhoneNumber => matches!(v, Value::PhoneNumber(_, _)),
+                SyntaxType::Binary => matches!(v, Value::Binary(_)),
+                SyntaxType::Image => matches!(v, Value::Image(_)),
             };
         if r {
             Ok(())
@@ -480,6 +497,14 @@ pub trait SchemaTransaction {
                     }
                 })
         }); // end for
+
+        attribute_snapshot.values().for_each(|attr| {
+            if attr.encrypted && !attr.index.is_empty() {
+                res.push(Err(ConsistencyError::SchemaAttributeEncryptedIndexed(
+                    attr.name.to_string(),
+                )))
+            }
+        });
         res
     }
 
@@ -652,6 +677,14 @@ impl<'a> SchemaWriteTransaction<'a> {
             .collect()
     }
 
+    pub fn reload_sealed_attrs(&self) -> BTreeSet<AttrString> {
+        self.get_attributes()
+            .values()
+            .filter(|a| a.encrypted)
+            .map(|a| a.name.clone())
+            .collect()
+    }
+
     #[instrument(level = "debug", name = "schema::generate_in_memory", skip_all)]
     pub fn generate_in_memory(&mut self) -> Result<(), OperationError> {
         //
@@ -670,6 +703,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                 phantom: false,
                 sync_allowed: false,
                 index: vec![IndexType::Equality, IndexType::Presence],
+                encrypted: false,
+                max_size: None,
                 syntax: SyntaxType::Utf8StringInsensitive,
             },
         );
@@ -686,6 +721,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                 phantom: false,
                 sync_allowed: false,
                 index: vec![IndexType::Equality, IndexType::Presence],
+                encrypted: false,
+                max_size: None,
                 syntax: SyntaxType::Uuid,
             },
         );
@@ -702,6 +739,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                 phantom: false,
                 sync_allowed: false,
                 index: vec![],
+                encrypted: false,
+                max_size: None,
                 syntax: SyntaxType::Cid,
             },
         );
@@ -716,6 +755,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                 phantom: false,
                 sync_allowed: true,
                 index: vec![IndexType::Equality, IndexType::Presence],
+                encrypted: false,
+                max_size: None,
                 syntax: SyntaxType::Utf8StringIname,
             },
         );
@@ -732,6 +773,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                 phantom: false,
                 sync_allowed: false,
                 index: vec![IndexType::Equality],
+                encrypted: false,
+                max_size: None,
                 syntax: SyntaxType::SecurityPrincipalName,
             },
         );
@@ -746,6 +789,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                 phantom: false,
                 sync_allowed: false,
                 index: vec![IndexType::Equality],
+                encrypted: false,
+                max_size: None,
                 syntax: SyntaxType::Utf8StringInsensitive,
             },
         );
@@ -760,6 +805,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                 phantom: false,
                 sync_allowed: false,
                 index: vec![IndexType::Equality],
+                encrypted: false,
+                max_size: None,
                 syntax: SyntaxType::Utf8StringInsensitive,
             },
         );
@@ -774,6 +821,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                 phantom: false,
                 sync_allowed: true,
                 index: vec![],
+                encrypted: false,
+                max_size: None,
                 syntax: SyntaxType::Utf8String,
             },
         );
@@ -786,6 +835,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                 phantom: false,
                 sync_allowed: false,
                 index: vec![],
+                encrypted: false,
+                max_size: None,
                 syntax: SyntaxType::Boolean,
             });
         self.attributes.insert(AttrString::from("phantom"), SchemaAttribute {
@@ -797,6 +848,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                 phantom: false,
                 sync_allowed: false,
                 index: vec![],
+                encrypted: false,
+                max_size: None,
                 syntax: SyntaxType::Boolean,
             });
         self.attributes.insert(AttrString::from("sync_allowed"), SchemaAttribute {
@@ -808,6 +861,34 @@ impl<'a> SchemaWriteTransaction<'a> {
                 phantom: false,
                 sync_allowed: false,
                 index: vec![],
+                encrypted: false,
+                max_size: None,
+                syntax: SyntaxType::Boolean,
+            });
+        self.attributes.insert(AttrString::from("max_size"), SchemaAttribute {
+                name: AttrString::from("max_size"),
+                uuid: UUID_SCHEMA_ATTR_MAX_SIZE,
+                description: String::from("The largest size in bytes of a value of this attribute, for syntaxes that are bounded such as binary."),
+                multivalue: false,
+                unique: false,
+                phantom: false,
+                sync_allowed: false,
+                index: vec![],
+                encrypted: false,
+                max_size: None,
+                syntax: SyntaxType::Uint32,
+            });
+        self.attributes.insert(AttrString::from("encrypted"), SchemaAttribute {
+                name: AttrString::from("encrypted"),
+                uuid: UUID_SCHEMA_ATTR_ENCRYPTED,
+                description: String::from("If true, values of this attribute are encrypted at rest by the database seal key. An encrypted attribute can not be indexed."),
+                multivalue: false,
+                unique: false,
+                phantom: false,
+                sync_allowed: false,
+                index: vec![],
+                encrypted: false,
+                max_size: None,
                 syntax: SyntaxType::Boolean,
             });
         self.attributes.insert(
@@ -823,6 +904,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                 phantom: false,
                 sync_allowed: false,
                 index: vec![],
+                encrypted: false,
+                max_size: None,
                 syntax: SyntaxType::Boolean,
             },
         );
@@ -839,6 +922,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                 phantom: false,
                 sync_allowed: false,
                 index: vec![],
+                encrypted: false,
+                max_size: None,
                 syntax: SyntaxType::IndexId,
             },
         );
@@ -855,6 +940,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                 phantom: false,
                 sync_allowed: false,
                 index: vec![IndexType::Equality],
+                encrypted: false,
+                max_size: None,
                 syntax: SyntaxType::SyntaxId,
             },
         );
@@ -871,6 +958,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                 phantom: false,
                 sync_allowed: false,
                 index: vec![],
+                encrypted: false,
+                max_size: None,
                 syntax: SyntaxType::Utf8StringInsensitive,
             },
         );
@@ -887,6 +976,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                 phantom: false,
                 sync_allowed: false,
                 index: vec![],
+                encrypted: false,
+                max_size: None,
                 syntax: SyntaxType::Utf8StringInsensitive,
             },
         );
@@ -903,6 +994,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                 phantom: false,
                 sync_allowed: false,
                 index: vec![],
+                encrypted: false,
+                max_size: None,
                 syntax: SyntaxType::Utf8StringInsensitive,
             },
         );
@@ -919,6 +1012,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                 phantom: false,
                 sync_allowed: false,
                 index: vec![],
+                encrypted: false,
+                max_size: None,
                 syntax: SyntaxType::Utf8StringInsensitive,
             },
         );
@@ -935,6 +1030,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                     phantom: false,
                 sync_allowed: false,
                     index: vec![],
+                    encrypted: false,
+                    max_size: None,
                     syntax: SyntaxType::Utf8StringInsensitive,
                 },
             );
@@ -951,6 +1048,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                     phantom: false,
                 sync_allowed: false,
                     index: vec![],
+                    encrypted: false,
+                    max_size: None,
                     syntax: SyntaxType::Utf8StringInsensitive,
                 },
             );
@@ -967,6 +1066,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                 phantom: false,
                 sync_allowed: false,
                 index: vec![],
+                encrypted: false,
+                max_size: None,
                 syntax: SyntaxType::Utf8StringInsensitive,
             },
         );
@@ -983,6 +1084,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                     phantom: false,
                 sync_allowed: false,
                     index: vec![],
+                    encrypted: false,
+                    max_size: None,
                     syntax: SyntaxType::Utf8StringInsensitive,
                 },
             );
@@ -1000,6 +1103,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                     phantom: false,
                 sync_allowed: false,
                     index: vec![IndexType::Equality],
+                    encrypted: false,
+                    max_size: None,
                     syntax: SyntaxType::Boolean,
                 },
             );
@@ -1017,6 +1122,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                 phantom: false,
                 sync_allowed: false,
                 index: vec![IndexType::Equality, IndexType::SubString],
+                encrypted: false,
+                max_size: None,
                 syntax: SyntaxType::JsonFilter,
             },
         );
@@ -1033,6 +1140,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                 phantom: false,
                 sync_allowed: false,
                 index: vec![IndexType::Equality],
+                encrypted: false,
+                max_size: None,
                 syntax: SyntaxType::ReferenceUuid,
             },
         );
@@ -1050,6 +1159,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                 phantom: false,
                 sync_allowed: false,
                 index: vec![IndexType::Equality, IndexType::SubString],
+                encrypted: false,
+                max_size: None,
                 syntax: SyntaxType::JsonFilter,
             },
         );
@@ -1066,6 +1177,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                 phantom: false,
                 sync_allowed: false,
                 index: vec![IndexType::Equality],
+                encrypted: false,
+                max_size: None,
                 syntax: SyntaxType::Utf8StringInsensitive,
             },
         );
@@ -1080,6 +1193,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                 phantom: false,
                 sync_allowed: false,
                 index: vec![IndexType::Equality],
+                encrypted: false,
+                max_size: None,
                 syntax: SyntaxType::Utf8StringInsensitive,
             },
         );
@@ -1096,6 +1211,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                 phantom: false,
                 sync_allowed: false,
                 index: vec![IndexType::Equality],
+                encrypted: false,
+                max_size: None,
                 syntax: SyntaxType::Utf8StringInsensitive,
             },
         );
@@ -1113,6 +1230,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                 phantom: false,
                 sync_allowed: false,
                 index: vec![IndexType::Equality],
+                encrypted: false,
+                max_size: None,
                 syntax: SyntaxType::Utf8StringInsensitive,
             },
         );
@@ -1129,6 +1248,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                 phantom: false,
                 sync_allowed: false,
                 index: vec![IndexType::Equality],
+                encrypted: false,
+                max_size: None,
                 syntax: SyntaxType::Utf8StringInsensitive,
             },
         );
@@ -1143,6 +1264,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                     phantom: false,
                 sync_allowed: false,
                     index: vec![IndexType::Equality],
+                    encrypted: false,
+                    max_size: None,
                     syntax: SyntaxType::Utf8StringInsensitive,
                 },
             );
@@ -1158,6 +1281,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                 phantom: false,
                 sync_allowed: false,
                 index: vec![IndexType::Equality],
+                encrypted: false,
+                max_size: None,
                 syntax: SyntaxType::ReferenceUuid,
             },
         );
@@ -1172,6 +1297,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                 phantom: false,
                 sync_allowed: false,
                 index: vec![IndexType::Equality],
+                encrypted: false,
+                max_size: None,
                 syntax: SyntaxType::ReferenceUuid,
             },
         );
@@ -1186,6 +1313,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                 phantom: false,
                 sync_allowed: true,
                 index: vec![IndexType::Equality],
+                encrypted: false,
+                max_size: None,
                 syntax: SyntaxType::ReferenceUuid,
             },
         );
@@ -1203,6 +1332,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                 phantom: false,
                 sync_allowed: false,
                 index: vec![],
+                encrypted: false,
+                max_size: None,
                 syntax: SyntaxType::Uint32,
             },
         );
@@ -1218,6 +1349,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                 phantom: false,
                 sync_allowed: false,
                 index: vec![IndexType::Equality],
+                encrypted: false,
+                max_size: None,
                 syntax: SyntaxType::Utf8StringIname,
             },
         );
@@ -1234,6 +1367,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                 phantom: true,
                 sync_allowed: false,
                 index: vec![],
+                encrypted: false,
+                max_size: None,
                 syntax: SyntaxType::Utf8StringInsensitive,
             },
         );
@@ -1250,6 +1385,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                 phantom: true,
                 sync_allowed: false,
                 index: vec![],
+                encrypted: false,
+                max_size: None,
                 syntax: SyntaxType::Utf8StringInsensitive,
             },
         );
@@ -1268,6 +1405,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                 phantom: false,
                 sync_allowed: false,
                 index: vec![IndexType::Equality],
+                encrypted: false,
+                max_size: None,
                 syntax: SyntaxType::Utf8StringInsensitive,
             },
         );
@@ -1284,6 +1423,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                 phantom: false,
                 sync_allowed: false,
                 index: vec![IndexType::Equality],
+                encrypted: false,
+                max_size: None,
                 syntax: SyntaxType::ReferenceUuid,
             },
         );
@@ -1298,6 +1439,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                 phantom: false,
                 sync_allowed: false,
                 index: vec![],
+                encrypted: false,
+                max_size: None,
                 syntax: SyntaxType::Utf8StringInsensitive,
             },
         );
@@ -1313,6 +1456,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                 phantom: true,
                 sync_allowed: true,
                 index: vec![],
+                encrypted: false,
+                max_size: None,
                 syntax: SyntaxType::Utf8String,
             },
         );
@@ -1329,6 +1474,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                 phantom: true,
                 sync_allowed: false,
                 index: vec![],
+                encrypted: false,
+                max_size: None,
                 syntax: SyntaxType::Utf8StringInsensitive,
             },
         );
@@ -1343,6 +1490,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                 phantom: true,
                 sync_allowed: false,
                 index: vec![],
+                encrypted: false,
+                max_size: None,
                 syntax: SyntaxType::Utf8StringInsensitive,
             },
         );
@@ -1357,6 +1506,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                 phantom: true,
                 sync_allowed: false,
                 index: vec![],
+                encrypted: false,
+                max_size: None,
                 syntax: SyntaxType::Uuid,
             },
         );
@@ -1371,6 +1522,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                 phantom: true,
                 sync_allowed: false,
                 index: vec![],
+                encrypted: false,
+                max_size: None,
                 syntax: SyntaxType::Utf8StringInsensitive,
             },
         );
@@ -1385,6 +1538,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                 phantom: true,
                 sync_allowed: false,
                 index: vec![],
+                encrypted: false,
+                max_size: None,
                 syntax: SyntaxType::Utf8StringIname,
             },
         );
@@ -1399,6 +1554,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                 phantom: true,
                 sync_allowed: false,
                 index: vec![],
+                encrypted: false,
+                max_size: None,
                 syntax: SyntaxType::SshKey,
             },
         );
@@ -1413,6 +1570,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                 phantom: true,
                 sync_allowed: false,
                 index: vec![],
+                encrypted: false,
+                max_size: None,
                 syntax: SyntaxType::SshKey,
             },
         );
@@ -1427,6 +1586,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                 phantom: true,
                 sync_allowed: false,
                 index: vec![],
+                encrypted: false,
+                max_size: None,
                 syntax: SyntaxType::EmailAddress,
             },
         );
@@ -1441,6 +1602,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                 phantom: true,
                 sync_allowed: false,
                 index: vec![],
+                encrypted: false,
+                max_size: None,
                 syntax: SyntaxType::EmailAddress,
             },
         );
@@ -1455,9 +1618,43 @@ impl<'a> SchemaWriteTransaction<'a> {
                 phantom: true,
                 sync_allowed: false,
                 index: vec![],
+                encrypted: false,
+                max_size: None,
                 syntax: SyntaxType::Uint32,
             },
         );
+        self.attributes.insert(
+            AttrString::from("uid"),
+            SchemaAttribute {
+                name: AttrString::from("uid"),
+                uuid: UUID_SCHEMA_ATTR_UID,
+                description: String::from("An LDAP Compatible uid"),
+                multivalue: false,
+                unique: false,
+                phantom: true,
+                sync_allowed: false,
+                index: vec![],
+                encrypted: false,
+                max_size: None,
+                syntax: SyntaxType::Utf8StringIname,
+            },
+        );
+        self.attributes.insert(
+            AttrString::from("gecos"),
+            SchemaAttribute {
+                name: AttrString::from("gecos"),
+                uuid: UUID_SCHEMA_ATTR_GECOS,
+                description: String::from("An LDAP Compatible gecos"),
+                multivalue: false,
+                unique: false,
+                phantom: true,
+                sync_allowed: false,
+                index: vec![],
+                encrypted: false,
+                max_size: None,
+                syntax: SyntaxType::Utf8String,
+            },
+        );
         // end LDAP masking phantoms
 
         self.classes.insert(
@@ -1470,6 +1667,8 @@ impl<'a> SchemaWriteTransaction<'a> {
                     AttrString::from("phantom"),
                     AttrString::from("sync_allowed"),
                     AttrString::from("index"),
+                    AttrString::from("encrypted"),
+                    AttrString::from("max_size"),
                 ],
                 systemmust: vec![
                     AttrString::from("class"),
@@ -1573,6 +1772,15 @@ impl<'a> SchemaWriteTransaction<'a> {
                     .. Default::default()
                 },
             );
+        self.classes.insert(
+            AttrString::from("conflict"),
+            SchemaClass {
+                name: AttrString::from("conflict"),
+                uuid: UUID_SCHEMA_CLASS_CONFLICT,
+                description: String::from("An object that was created on two replicas with the same unique values. The later creation is placed in the recycle bin as a conflict."),
+                ..Default::default()
+            },
+        );
         // sysinfo
         self.classes.insert(
             AttrString::from("system_info"),
@@ -2052,6 +2260,8 @@ mod tests {
             uuid: Uuid::new_v4(),
             description: String::from(""),
             index: vec![IndexType::Equality],
+            encrypted: false,
+            max_size: None,
             syntax: SyntaxType::Utf8StringInsensitive,
             ..Default::default()
         };
@@ -2077,6 +2287,8 @@ mod tests {
             description: String::from(""),
             multivalue: true,
             index: vec![IndexType::Equality],
+            encrypted: false,
+            max_size: None,
             syntax: SyntaxType::Utf8String,
             ..Default::default()
         };
@@ -2092,6 +2304,8 @@ mod tests {
             description: String::from(""),
             multivalue: true,
             index: vec![IndexType::Equality],
+            encrypted: false,
+            max_size: None,
             syntax: SyntaxType::Boolean,
             ..Default::default()
         };
@@ -2123,6 +2337,8 @@ mod tests {
             uuid: Uuid::new_v4(),
             description: String::from(""),
             index: vec![IndexType::Equality],
+            encrypted: false,
+            max_size: None,
             syntax: SyntaxType::SyntaxId,
             ..Default::default()
         };
@@ -2144,6 +2360,8 @@ mod tests {
             uuid: Uuid::new_v4(),
             description: String::from(""),
             index: vec![IndexType::Equality],
+            encrypted: false,
+            max_size: None,
             syntax: SyntaxType::IndexId,
             ..Default::default()
         };
@@ -2453,6 +2671,14 @@ mod tests {
             f_insense.validate(&schema),
             Ok(unsafe { filter_valid!(f_eq("class", PartialValue::new_class("attributetype"))) })
         );
+        // Only attributes with an ordered syntax can be compared.
+        let f_lt_insense = filter_all!(f_lt("name", PartialValue::new_iname("zzzz")));
+        assert_eq!(
+            f_lt_insense.validate(&schema),
+            Err(SchemaError::InvalidAttributeSyntax("name".to_string()))
+        );
+        let f_gte = filter_all!(f_gte("max_size", PartialValue::new_uint32(5)));
+        assert!(f_gte.validate(&schema).is_ok());
         // Test the recursive structures validate
         let f_or_empty = filter_all!(f_or!([]));
         assert_eq!(f_or_empty.validate(&schema), Err(SchemaError::EmptyFilter));
@@ -0,0 +1,97 @@
+//! Security events, such as failed authentications and denied changes, with a stable schema
+//! for SIEM ingestion. They are a stream of their own, separate from the debugging events of
+//! the log, and are always emitted. Fields may be added to an event, but an event is never
+//! changed in a way that breaks a reader of its [SECURITY_EVENT_VERSION].
+
+use kanidm_proto::v1::EntryChange;
+use serde::Serialize;
+use sketching::security::SECURITY_EVENT_TARGET;
+use time::OffsetDateTime;
+
+use crate::prelude::*;
+
+pub const SECURITY_EVENT_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum SecurityEvent {
+    /// An authentication session was denied.
+    AuthFailure {
+        account: Uuid,
+        spn: String,
+        reason: String,
+    },
+    /// A credential of the account was locked after too many failed authentications.
+    AccountLocked { account: Uuid },
+    /// Access controls denied a change.
+    AccessDenied {
+        identity: Option<Uuid>,
+        operation: EntryChange,
+        reason: &'static str,
+    },
+    /// The credentials of the account were changed.
+    CredentialChanged { account: Uuid },
+    /// A session of the account was revoked.
+    SessionRevoked {
+        account: Uuid,
+        session: Uuid,
+        /// The identity that revoked it, if it was not revoked by an oauth2 client.
+        by: Option<Uuid>,
+    },
+    /// An api request was refused as its source, or its session, exceeded the rate limit.
+    RateLimited {
+        /// The source address, or its network for IPv6.
+        source: String,
+        path: String,
+        limit: &'static str,
+    },
+}
+
+#[derive(Serialize)]
+struct SecurityEventRecord<'a> {
+    version: u32,
+    time: String,
+    #[serde(flatten)]
+    event: &'a SecurityEvent,
+}
+
+impl SecurityEvent {
+    fn to_json(&self, at: OffsetDateTime) -> Result<String, serde_json::Error> {
+        serde_json::to_string(&SecurityEventRecord {
+            version: SECURITY_EVENT_VERSION,
+            time: at.format(time::Format::Rfc3339),
+            event: self,
+        })
+    }
+
+    pub fn emit(self) {
+        match self.to_json(OffsetDateTime::now_utc()) {
+            Ok(json) => tracing::info!(target: SECURITY_EVENT_TARGET, security_event = %json),
+            Err(e) => admin_error!(?e, event = ?self, "Failed to serialise security event"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use kanidm_proto::v1::EntryChange;
+    use time::OffsetDateTime;
+
+    use super::SecurityEvent;
+    use crate::prelude::*;
+
+    #[test]
+    fn test_security_event_schema() {
+        let json = SecurityEvent::AccessDenied {
+            identity: Some(UUID_ADMIN),
+            operation: EntryChange::Delete,
+            reason: "access_scope",
+        }
+        .to_json(OffsetDateTime::unix_epoch())
+        .expect("failed");
+        assert_eq!(
+            json,
+            r#"{"version":1,"time":"1970-01-01T00:00:00+00:00","event":"access_denied","identity":"00000000-0000-0000-0000-000000000000","operation":"delete","reason":"access_scope"}"#
+        );
+    }
+}
@@ -0,0 +1,231 @@
+//! The audit log keeps the changes that accounts make, or attempt, so that an administrator
+//! can find who changed an entry and when. Changes made by the server itself are not kept.
+//! It is disabled unless the server enables it.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, PoisonError};
+
+use kanidm_proto::v1::{AuditOutcome, AuditRecord, AuditSearchRequest, EntryChange};
+use time::OffsetDateTime;
+
+use super::{QueryServer, QueryServerReadTransaction, QueryServerWriteTransaction};
+use crate::prelude::*;
+
+#[derive(Default)]
+pub(crate) struct AuditLog {
+    enabled: AtomicBool,
+    /// The transaction of a failed change is not committed, so its record waits here for the
+    /// next transaction that is.
+    failed: Mutex<Vec<AuditRecord>>,
+}
+
+impl AuditLog {
+    fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    fn push_failed(&self, record: AuditRecord) {
+        self.failed
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(record)
+    }
+
+    fn take_failed(&self) -> Vec<AuditRecord> {
+        std::mem::take(&mut *self.failed.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+fn audit_record_matches(req: &AuditSearchRequest, record: &AuditRecord) -> bool {
+    req.operation.iter().all(|op| *op == record.operation)
+        && req.identity.iter().all(|u| *u == record.identity)
+        && req.target.iter().all(|u| record.targets.contains(u))
+        && req.outcome.iter().all(|o| *o == record.outcome)
+}
+
+impl QueryServer {
+    /// Keep the changes made by accounts in the audit log.
+    pub fn enable_audit_log(&self) {
+        self.audit_log.enabled.store(true, Ordering::Relaxed);
+    }
+}
+
+impl<'a> QueryServerReadTransaction<'a> {
+    /// Search the audit log, oldest record first. The caller must check that the identity
+    /// may read it.
+    #[instrument(level = "debug", skip_all)]
+    pub fn search_audit_log(
+        &self,
+        req: &AuditSearchRequest,
+    ) -> Result<Vec<AuditRecord>, OperationError> {
+        let not_before = req.not_before.map_or(i64::MIN, |t| t.unix_timestamp());
+        let not_after = req.not_after.map_or(i64::MAX, |t| t.unix_timestamp());
+        let mut records = self.be_txn.search_audit_log(not_before, not_after)?;
+        records.retain(|record| audit_record_matches(req, record));
+        Ok(records)
+    }
+}
+
+impl<'a> QueryServerWriteTransaction<'a> {
+    /// Apply a change, and record it in the audit log if it was made by an account.
+    pub(crate) fn audited<F>(
+        &mut self,
+        operation: EntryChange,
+        ident: &Identity,
+        f: F,
+    ) -> Result<(), OperationError>
+    where
+        F: FnOnce(&mut Self) -> Result<(), OperationError>,
+    {
+        let identity = match ident.get_uuid() {
+            Some(uuid) if self.audit_log.is_enabled() => uuid,
+            _ => return f(self),
+        };
+
+        // Only the entries changed by this change are its targets.
+        let outer_targets = self.audit_targets.take();
+        let res = f(self);
+        let targets = self.audit_targets.replace(outer_targets);
+
+        let time = OffsetDateTime::unix_epoch() + self.curtime;
+        match &res {
+            Ok(()) => {
+                let mut records = self.audit_records.take();
+                records.push(AuditRecord {
+                    time,
+                    operation,
+                    identity,
+                    targets: targets.into_iter().collect(),
+                    outcome: AuditOutcome::Success,
+                    error: None,
+                });
+                self.audit_records.set(records);
+            }
+            Err(e) => self.audit_log.push_failed(AuditRecord {
+                time,
+                operation,
+                identity,
+                targets: Vec::new(),
+                outcome: AuditOutcome::Failure,
+                error: Some(e.to_string()),
+            }),
+        }
+        res
+    }
+
+    /// Write the records of this transaction, and of the failed changes before it.
+    pub(crate) fn write_audit_log(&mut self) -> Result<(), OperationError> {
+        let mut records = self.audit_log.take_failed();
+        records.append(self.audit_records.get_mut());
+        if records.is_empty() {
+            return Ok(());
+        }
+        records.sort_by_key(|record| record.time);
+        self.be_txn.write_audit_records(&records)
+    }
+
+    /// Remove the records of the audit log that are older than `retention` seconds.
+    #[instrument(level = "debug", skip_all)]
+    pub fn purge_audit_log(&self, retention: u64) -> Result<(), OperationError> {
+        let before = self.curtime.as_secs().saturating_sub(retention);
+        let purged = self
+            .be_txn
+            .purge_audit_log(i64::try_from(before).unwrap_or(i64::MAX))?;
+        admin_info!(%purged, "Purged audit log records");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use kanidm_proto::v1::{AuditOutcome, AuditSearchRequest, EntryChange};
+
+    use crate::event::CreateEvent;
+    use crate::prelude::*;
+
+    #[qs_test]
+    async fn test_audit_log(server: &QueryServer) {
+        server.enable_audit_log();
+        let ct = duration_from_epoch_now();
+        let group_uuid = Uuid::new_v4();
+
+        let mut server_txn = server.write(ct).await;
+        let admin = server_txn
+            .internal_search_uuid(&UUID_ADMIN)
+            .expect("failed");
+        let ident = Identity::from_impersonate_entry_readwrite(admin);
+
+        // A change made for an account is recorded with the entries it changed.
+        let e_group = entry_init!(
+            ("class", Value::new_class("object")),
+            ("class", Value::new_class("group")),
+            ("name", Value::new_iname("audit_group")),
+            ("uuid", Value::new_uuid(group_uuid))
+        );
+        let res = server_txn.audited(EntryChange::Create, &ident, |qs| {
+            qs.create(&CreateEvent::new_internal(vec![e_group]))
+        });
+        assert!(res.is_ok());
+        // Changes made by the server itself are not recorded.
+        let e_internal = entry_init!(
+            ("class", Value::new_class("object")),
+            ("class", Value::new_class("group")),
+            ("name", Value::new_iname("audit_internal"))
+        );
+        assert!(server_txn
+            .create(&CreateEvent::new_internal(vec![e_internal]))
+            .is_ok());
+        assert!(server_txn.commit().is_ok());
+
+        // A failed change is recorded once a later transaction commits.
+        let mut server_txn = server.write(ct + Duration::from_secs(10)).await;
+        let ce = CreateEvent::new_impersonate_identity(ident, Vec::new());
+        assert_eq!(server_txn.create(&ce), Err(OperationError::EmptyRequest));
+        drop(server_txn);
+        let server_txn = server.write(ct + Duration::from_secs(20)).await;
+        assert!(server_txn.commit().is_ok());
+
+        let server_txn = server.read().await;
+        let all = server_txn
+            .search_audit_log(&AuditSearchRequest::default())
+            .expect("failed");
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[0].operation, EntryChange::Create);
+        assert_eq!(all[0].identity, UUID_ADMIN);
+        assert!(all[0].targets.contains(&group_uuid));
+        assert_eq!(all[0].outcome, AuditOutcome::Success);
+        assert_eq!(all[1].outcome, AuditOutcome::Failure);
+        assert!(all[1].targets.is_empty());
+        assert!(all[1].error.is_some());
+
+        let by_target = server_txn
+            .search_audit_log(&AuditSearchRequest {
+                target: Some(group_uuid),
+                ..Default::default()
+            })
+            .expect("failed");
+        assert_eq!(by_target, all[..1]);
+
+        let failures = server_txn
+            .search_audit_log(&AuditSearchRequest {
+                outcome: Some(AuditOutcome::Failure),
+                not_before: Some(all[1].time),
+                ..Default::default()
+            })
+            .expect("failed");
+        assert_eq!(failures, all[1..]);
+        drop(server_txn);
+
+        // Records older than the retention are purged.
+        let mut server_txn = server.write(ct + Duration::from_secs(30)).await;
+        assert!(server_txn.purge_audit_log(25).is_ok());
+        assert!(server_txn.commit().is_ok());
+        let server_txn = server.read().await;
+        let remaining = server_txn
+            .search_audit_log(&AuditSearchRequest::default())
+            .expect("failed");
+        assert_eq!(remaining, all[1..]);
+    }
+}
@@ -0,0 +1,155 @@
+use super::QueryServerWriteTransaction;
+use crate::event::{CreateEvent, DeleteEvent, ModifyEvent};
+use crate::prelude::*;
+use kanidm_proto::v1::{BatchOperation, BatchOperationResult, BatchRequest};
+
+impl<'a> QueryServerWriteTransaction<'a> {
+    /// Apply the operations of a batch in order. Each operation is resolved after the ones
+    /// before it are applied, so an operation can refer to an entry that an earlier one created.
+    ///
+    /// This stops at the first operation that fails, and the operations after it are not
+    /// attempted. The caller must only commit the transaction if this returns true, which is
+    /// when every operation succeeded.
+    #[instrument(level = "debug", skip_all)]
+    pub fn batch(
+        &mut self,
+        ident: &Identity,
+        req: &BatchRequest,
+    ) -> Result<(bool, Vec<BatchOperationResult>), OperationError> {
+        if req.operations.is_empty() {
+            request_error!("empty batch request");
+            return Err(OperationError::EmptyRequest);
+        }
+
+        let mut success = true;
+        let results = req
+            .operations
+            .iter()
+            .map(|op| {
+                if !success {
+                    return BatchOperationResult::NotAttempted;
+                }
+                let res = match op {
+                    BatchOperation::Create(req) => {
+                        CreateEvent::from_message(ident.clone(), req, self)
+                            .and_then(|ce| self.create(&ce))
+                    }
+                    BatchOperation::Modify(req) => {
+                        ModifyEvent::from_message(ident.clone(), req, self)
+                            .and_then(|me| self.modify(&me))
+                    }
+                    BatchOperation::Delete(req) => {
+                        DeleteEvent::from_message(ident.clone(), req, self)
+                            .and_then(|de| self.delete(&de))
+                    }
+                };
+                match res {
+                    Ok(()) => BatchOperationResult::Success,
+                    Err(e) => {
+                        admin_warn!(?e, "Batch operation failed, not attempting the rest");
+                        success = false;
+                        BatchOperationResult::Failed(e)
+                    }
+                }
+            })
+            .collect();
+
+        Ok((success, results))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use kanidm_proto::v1::{
+        BatchOperation, BatchOperationResult, BatchRequest, CreateRequest, DeleteRequest,
+        Entry as ProtoEntry, Filter as ProtoFilter, Modify as ProtoModify,
+        ModifyList as ProtoModifyList, ModifyRequest,
+    };
+
+    fn proto_group(name: &str) -> ProtoEntry {
+        let mut e = ProtoEntry::default();
+        e.attrs.insert(
+            "class".to_string(),
+            vec!["group".to_string(), "object".to_string()],
+        );
+        e.attrs.insert("name".to_string(), vec![name.to_string()]);
+        e
+    }
+
+    fn eq_name(name: &str) -> ProtoFilter {
+        ProtoFilter::Eq("name".to_string(), name.to_string())
+    }
+
+    #[qs_test]
+    async fn test_batch_in_order(server: &QueryServer) {
+        let mut server_txn = server.write(duration_from_epoch_now()).await;
+        let ident = Identity::from_internal();
+
+        // The modify refers to the group created before it.
+        let req = BatchRequest::new(vec![
+            BatchOperation::Create(CreateRequest::new(vec![
+                proto_group("batch_a"),
+                proto_group("batch_b"),
+            ])),
+            BatchOperation::Modify(ModifyRequest::new(
+                eq_name("batch_a"),
+                ProtoModifyList::new_list(vec![ProtoModify::Present(
+                    "member".to_string(),
+                    "batch_b".to_string(),
+                )]),
+            )),
+        ]);
+        let (success, results) = server_txn.batch(&ident, &req).expect("Batch failed");
+        assert!(success);
+        assert_eq!(
+            results,
+            vec![BatchOperationResult::Success, BatchOperationResult::Success]
+        );
+
+        let group_a = server_txn
+            .internal_search(filter!(f_eq("name", PartialValue::new_iname("batch_a"))))
+            .expect("Search failed")
+            .pop()
+            .expect("No group");
+        let group_b = server_txn
+            .internal_search(filter!(f_eq("name", PartialValue::new_iname("batch_b"))))
+            .expect("Search failed")
+            .pop()
+            .expect("No group");
+        assert!(group_a.attribute_equality("member", &PartialValue::new_refer(group_b.get_uuid())));
+        assert!(server_txn.commit().is_ok());
+    }
+
+    #[qs_test]
+    async fn test_batch_stops_at_failure(server: &QueryServer) {
+        let mut server_txn = server.write(duration_from_epoch_now()).await;
+        let ident = Identity::from_internal();
+
+        // The second create conflicts with the first, so the delete after it is not attempted.
+        let req = BatchRequest::new(vec![
+            BatchOperation::Create(CreateRequest::new(vec![proto_group("batch_c")])),
+            BatchOperation::Create(CreateRequest::new(vec![proto_group("batch_c")])),
+            BatchOperation::Delete(DeleteRequest::new(eq_name("batch_c"))),
+        ]);
+        let (success, results) = server_txn.batch(&ident, &req).expect("Batch failed");
+        assert!(!success);
+        assert_eq!(results[0], BatchOperationResult::Success);
+        assert!(matches!(results[1], BatchOperationResult::Failed(_)));
+        assert_eq!(results[2], BatchOperationResult::NotAttempted);
+
+        // Only the first create was applied.
+        assert_eq!(
+            server_txn
+                .internal_search(filter!(f_eq("name", PartialValue::new_iname("batch_c"))))
+                .expect("Search failed")
+                .len(),
+            1
+        );
+
+        assert!(matches!(
+            server_txn.batch(&ident, &BatchRequest::new(Vec::new())),
+            Err(OperationError::EmptyRequest)
+        ));
+    }
+}
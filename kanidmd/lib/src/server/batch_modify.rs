@@ -4,6 +4,7 @@ use crate::prelude::*;
 use crate::access::AccessControlsTransaction;
 use crate::server::Plugins;
 use hashbrown::HashMap;
+use kanidm_proto::v1::EntryChange;
 
 pub type ModSetValid = HashMap<Uuid, ModifyList<ModifyValid>>;
 
@@ -29,6 +30,12 @@ impl<'a> QueryServerWriteTransaction<'a> {
     /// the same modlists are used.
     #[instrument(level = "debug", skip_all)]
     pub fn batch_modify(&mut self, me: &BatchModifyEvent) -> Result<(), OperationError> {
+        self.audited(EntryChange::Modify, &me.ident, |qs| {
+            qs.batch_modify_inner(me)
+        })
+    }
+
+    fn batch_modify_inner(&mut self, me: &BatchModifyEvent) -> Result<(), OperationError> {
         // ⚠️  =========
         // Effectively this is the same as modify but instead of apply modlist
         // we do it by uuid.
@@ -40,6 +47,8 @@ impl<'a> QueryServerWriteTransaction<'a> {
             security_info!(name = %me.ident, "batch modify initiator");
         }
 
+        self.check_client_write_allowed(&me.ident)?;
+
         // Validate input.
 
         // Is the modlist non zero?
@@ -221,6 +230,7 @@ impl<'a> QueryServerWriteTransaction<'a> {
                     .chain(pre_candidates.iter().map(|e| e.get_uuid())),
             );
         }
+        self.record_entry_changes(EntryChange::Modify, norm_cand.iter().map(|e| e.get_uuid()));
 
         trace!(
             schema_reload = ?self.changed_schema,
@@ -4,6 +4,7 @@
 // This is really only used for long lived, high level types that need clone
 // that otherwise can't be cloned. Think Mutex.
 use std::cell::Cell;
+use std::collections::{BTreeMap, BTreeSet};
 use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Duration;
@@ -11,8 +12,10 @@ use std::time::Duration;
 use concread::arcache::{ARCache, ARCacheBuilder, ARCacheReadTxn};
 use concread::cowcell::*;
 use hashbrown::{HashMap, HashSet};
-use kanidm_proto::v1::{ConsistencyError, SchemaError, UiHint};
-use tokio::sync::{Semaphore, SemaphorePermit};
+use kanidm_proto::v1::{
+    AuditRecord, ConsistencyError, EntryChange, EntryChangeNotification, SchemaError, UiHint,
+};
+use tokio::sync::{broadcast, Semaphore, SemaphorePermit};
 use tracing::trace;
 
 use crate::access::{
@@ -24,20 +27,27 @@ use crate::be::{Backend, BackendReadTransaction, BackendTransaction, BackendWrit
 // We use so many, we just import them all ...
 use crate::event::{
     CreateEvent, DeleteEvent, ExistsEvent, ModifyEvent, ReviveRecycledEvent, SearchEvent,
+    SearchPage,
 };
 use crate::filter::{Filter, FilterInvalid, FilterValid, FilterValidResolved};
 use crate::identity::IdentityId;
+use crate::ldap::LdapDnFormat;
 use crate::modify::{Modify, ModifyInvalid, ModifyList, ModifyValid};
 use crate::plugins::dyngroup::{DynGroup, DynGroupCache};
 use crate::plugins::Plugins;
 use crate::prelude::*;
 use crate::repl::cid::Cid;
+use crate::repl::status::ReplStatus;
 use crate::schema::{
     Schema, SchemaAttribute, SchemaClass, SchemaReadTransaction, SchemaTransaction,
     SchemaWriteTransaction,
 };
 use crate::valueset::uuid_to_proto_string;
 
+use self::audit::AuditLog;
+
+pub mod audit;
+pub mod batch;
 pub mod batch_modify;
 pub mod create;
 pub mod delete;
@@ -46,6 +56,11 @@ pub mod search;
 
 const RESOLVE_FILTER_CACHE_MAX: usize = 4096;
 const RESOLVE_FILTER_CACHE_LOCAL: usize = 0;
+/// The number of committed transactions that a subscriber to changes may fall behind by.
+const CHANGE_NOTIFY_QUEUE: usize = 256;
+
+/// The entries changed by a committed transaction.
+pub type EntryChanges = Arc<[EntryChangeNotification]>;
 
 #[derive(Debug, Clone, PartialOrd, PartialEq, Eq)]
 enum ServerPhase {
@@ -59,6 +74,49 @@ struct DomainInfo {
     d_uuid: Uuid,
     d_name: String,
     d_display: String,
+    /// True if this server is a read only replica of the domain.
+    d_read_only: bool,
+}
+
+/// The origin of an oauth2 resource server that is in the renamed domain, so is moved with it.
+#[derive(Debug, Clone)]
+pub struct OriginRewrite {
+    pub rs_name: String,
+    pub uuid: Uuid,
+    pub attr: &'static str,
+    pub old: Url,
+    pub new: Url,
+}
+
+/// The changes of a domain rename, so that an administrator can review them before the rename
+/// is committed.
+#[derive(Debug, Clone, Default)]
+pub struct DomainRenamePlan {
+    pub old_domain_name: String,
+    pub new_domain_name: String,
+    /// The entries with an spn, which is regenerated in the new domain.
+    pub spn_entries: usize,
+    pub origins: Vec<OriginRewrite>,
+    /// Sessions and api tokens carry the spn of the old domain.
+    pub sessions: usize,
+    pub api_tokens: usize,
+    /// Passkeys and device keys are registered to the domain as their relying party, so they
+    /// can not be used after the rename.
+    pub passkeys: usize,
+}
+
+/// The url with its host moved from the old domain to the new one, if it is in the old domain.
+fn rename_url_domain(url: &Url, old_domain_name: &str, new_domain_name: &str) -> Option<Url> {
+    let host = url.host_str()?;
+    let new_host = if host == old_domain_name {
+        new_domain_name.to_string()
+    } else {
+        let prefix = host.strip_suffix(old_domain_name)?.strip_suffix('.')?;
+        format!("{}.{}", prefix, new_domain_name)
+    };
+    let mut new_url = url.clone();
+    new_url.set_host(Some(&new_host)).ok()?;
+    Some(new_url)
 }
 
 #[derive(Clone)]
@@ -74,6 +132,9 @@ pub struct QueryServer {
     resolve_filter_cache:
         Arc<ARCache<(IdentityId, Filter<FilterValid>), Filter<FilterValidResolved>>>,
     dyngroup_cache: Arc<CowCell<DynGroupCache>>,
+    repl_status: Arc<ReplStatus>,
+    change_tx: broadcast::Sender<EntryChanges>,
+    audit_log: Arc<AuditLog>,
 }
 
 pub struct QueryServerReadTransaction<'a> {
@@ -87,6 +148,7 @@ pub struct QueryServerReadTransaction<'a> {
     resolve_filter_cache: Cell<
         ARCacheReadTxn<'a, (IdentityId, Filter<FilterValid>), Filter<FilterValidResolved>, ()>,
     >,
+    repl_status: &'a ReplStatus,
 }
 
 unsafe impl<'a> Sync for QueryServerReadTransaction<'a> {}
@@ -98,25 +160,37 @@ pub struct QueryServerWriteTransaction<'a> {
     phase: CowCellWriteTxn<'a, ServerPhase>,
     d_info: CowCellWriteTxn<'a, DomainInfo>,
     curtime: Duration,
-    cid: Cid,
+    pub(crate) cid: Cid,
     be_txn: BackendWriteTransaction<'a>,
     schema: SchemaWriteTransaction<'a>,
     accesscontrols: AccessControlsWriteTransaction<'a>,
     // We store a set of flags that indicate we need a reload of
     // schema or acp, which is tested by checking the classes of the
     // changing content.
-    changed_schema: Cell<bool>,
-    changed_acp: Cell<bool>,
-    changed_oauth2: Cell<bool>,
-    changed_domain: Cell<bool>,
+    pub(crate) changed_schema: Cell<bool>,
+    pub(crate) changed_acp: Cell<bool>,
+    pub(crate) changed_oauth2: Cell<bool>,
+    pub(crate) changed_domain: Cell<bool>,
     // Store the list of changed uuids for other invalidation needs?
-    changed_uuid: Cell<HashSet<Uuid>>,
+    pub(crate) changed_uuid: Cell<HashSet<Uuid>>,
+    // The changes to notify subscribers of once the transaction commits.
+    changed_entries: Cell<BTreeMap<Uuid, EntryChange>>,
     _db_ticket: SemaphorePermit<'a>,
     _write_ticket: SemaphorePermit<'a>,
     resolve_filter_cache: Cell<
         ARCacheReadTxn<'a, (IdentityId, Filter<FilterValid>), Filter<FilterValidResolved>, ()>,
     >,
+    // The shared cache of resolved filters, which is cleared when a change to schema commits.
+    resolve_filter_cache_clear:
+        &'a ARCache<(IdentityId, Filter<FilterValid>), Filter<FilterValidResolved>>,
     dyngroup_cache: Cell<CowCellWriteTxn<'a, DynGroupCache>>,
+    repl_status: &'a ReplStatus,
+    change_tx: &'a broadcast::Sender<EntryChanges>,
+    audit_log: &'a AuditLog,
+    // The audit records of the changes in this transaction, and the entries changed by the
+    // change being applied.
+    audit_records: Cell<Vec<AuditRecord>>,
+    audit_targets: Cell<BTreeSet<Uuid>>,
 }
 
 pub(crate) struct ModifyPartial<'a> {
@@ -153,10 +227,13 @@ pub trait QueryServerTransaction<'a> {
 
     fn get_domain_display_name(&self) -> &str;
 
+    /// The cache of resolved filters, if it is consistent with the schema of this transaction.
     #[allow(clippy::mut_from_ref)]
     fn get_resolve_filter_cache(
         &self,
-    ) -> &mut ARCacheReadTxn<'a, (IdentityId, Filter<FilterValid>), Filter<FilterValidResolved>, ()>;
+    ) -> Option<
+        &mut ARCacheReadTxn<'a, (IdentityId, Filter<FilterValid>), Filter<FilterValidResolved>, ()>,
+    >;
 
     /// Conduct a search and apply access controls to yield a set of entries that
     /// have been reduced to the set of user visible avas. Note that if you provide
@@ -190,6 +267,44 @@ pub trait QueryServerTransaction<'a> {
         // This now returns the reduced vec.
     }
 
+    /// As [`fn search_ext`], for a page of the entries in uuid order. The page is taken before
+    /// the attributes of the entries are reduced, so only the entries of the page are
+    /// processed. If more entries follow the page, the uuid of its last entry is returned to
+    /// continue from.
+    ///
+    /// [`fn search_ext`]: trait.QueryServerTransaction.html#method.search_ext
+    #[instrument(level = "debug", skip_all)]
+    fn search_ext_page(
+        &self,
+        se: &SearchEvent,
+        page: &SearchPage,
+    ) -> Result<(Vec<Entry<EntryReduced, EntryCommitted>>, Option<Uuid>), OperationError> {
+        let mut entries = self.search(se)?;
+
+        // Resume after the last entry of the previous page.
+        if let Some(after) = page.after {
+            entries.retain(|e| e.get_uuid() > after);
+        }
+        entries.sort_unstable_by_key(|e| e.get_uuid());
+
+        let more = entries.len() > page.size;
+        entries.truncate(page.size);
+        let next = if more {
+            entries.last().map(|e| e.get_uuid())
+        } else {
+            None
+        };
+
+        let access = self.get_accesscontrols();
+        access
+            .search_filter_entry_attributes(se, entries)
+            .map(|entries| (entries, next))
+            .map_err(|e| {
+                admin_error!(?e, "Failed to filter entry attributes");
+                e
+            })
+    }
+
     #[instrument(level = "debug", skip_all)]
     fn search(&self, se: &SearchEvent) -> Result<Vec<Arc<EntrySealedCommitted>>, OperationError> {
         if se.ident.is_internal() {
@@ -215,7 +330,7 @@ pub trait QueryServerTransaction<'a> {
         // Now resolve all references and indexes.
         let vfr = se
             .filter
-            .resolve(&se.ident, Some(idxmeta), Some(resolve_filter_cache))
+            .resolve(&se.ident, Some(idxmeta), resolve_filter_cache)
             .map_err(|e| {
                 admin_error!(?e, "search filter resolve failure");
                 e
@@ -253,7 +368,7 @@ pub trait QueryServerTransaction<'a> {
 
         let vfr = ee
             .filter
-            .resolve(&ee.ident, Some(idxmeta), Some(resolve_filter_cache))
+            .resolve(&ee.ident, Some(idxmeta), resolve_filter_cache)
             .map_err(|e| {
                 admin_error!(?e, "Failed to resolve filter");
                 e
@@ -505,6 +620,14 @@ pub trait QueryServerTransaction<'a> {
                     SyntaxType::UiHint => UiHint::from_str(value)
                         .map(Value::UiHint)
                         .map_err(|()| OperationError::InvalidAttribute("Invalid uihint syntax".to_string())),
+                    SyntaxType::Address => Value::new_address_s(value)
+                        .ok_or_else(|| OperationError::InvalidAttribute("Invalid Address (json address claim) syntax".to_string())),
+                    SyntaxType::PhoneNumber => Value::new_phonenumber_s(value)
+                        .ok_or_else(|| OperationError::InvalidAttribute("Invalid Phone Number (E.164) syntax".to_string())),
+                    SyntaxType::Binary => Value::new_binary_base64(value)
+                        .ok_or_else(|| OperationError::InvalidAttribute("Invalid Binary (base64) syntax".to_string())),
+                    SyntaxType::Image => Value::new_image_s(value)
+                        .ok_or_else(|| OperationError::InvalidAttribute("Invalid Image (png, jpeg or webp, base64) syntax".to_string())),
                 }
             }
             None => {
@@ -604,6 +727,20 @@ pub trait QueryServerTransaction<'a> {
                         .map_err(|()| {
                             OperationError::InvalidAttribute("Invalid uihint syntax".to_string())
                         }),
+                    SyntaxType::Address => Ok(PartialValue::new_address(value)),
+                    SyntaxType::PhoneNumber => {
+                        PartialValue::new_phonenumber_s(value).ok_or_else(|| {
+                            OperationError::InvalidAttribute(
+                                "Invalid Phone Number (E.164) syntax".to_string(),
+                            )
+                        })
+                    }
+                    SyntaxType::Binary => PartialValue::new_binary_base64(value).ok_or_else(|| {
+                        OperationError::InvalidAttribute(
+                            "Invalid Binary (base64) syntax".to_string(),
+                        )
+                    }),
+                    SyntaxType::Image => Ok(PartialValue::new_image_s(value)),
                 }
             }
             None => {
@@ -652,13 +789,14 @@ pub trait QueryServerTransaction<'a> {
         &self,
         value: &ValueSet,
         basedn: &str,
+        dn_format: &LdapDnFormat,
     ) -> Result<Vec<Vec<u8>>, OperationError> {
         if let Some(r_set) = value.as_refer_set() {
             let v: Result<Vec<_>, _> = r_set
                 .iter()
                 .copied()
                 .map(|ur| {
-                    let rdn = self.uuid_to_rdn(ur)?;
+                    let rdn = dn_format.rdn(self, ur)?;
                     Ok(format!("{},{}", rdn, basedn).into_bytes())
                 })
                 .collect();
@@ -756,17 +894,20 @@ impl<'a> QueryServerTransaction<'a> for QueryServerReadTransaction<'a> {
 
     fn get_resolve_filter_cache(
         &self,
-    ) -> &mut ARCacheReadTxn<'a, (IdentityId, Filter<FilterValid>), Filter<FilterValidResolved>, ()>
-    {
+    ) -> Option<
+        &mut ARCacheReadTxn<'a, (IdentityId, Filter<FilterValid>), Filter<FilterValidResolved>, ()>,
+    > {
         unsafe {
             let mptr = self.resolve_filter_cache.as_ptr();
-            &mut (*mptr)
-                as &mut ARCacheReadTxn<
-                    'a,
-                    (IdentityId, Filter<FilterValid>),
-                    Filter<FilterValidResolved>,
-                    (),
-                >
+            Some(
+                &mut (*mptr)
+                    as &mut ARCacheReadTxn<
+                        'a,
+                        (IdentityId, Filter<FilterValid>),
+                        Filter<FilterValidResolved>,
+                        (),
+                    >,
+            )
         }
     }
 
@@ -784,6 +925,11 @@ impl<'a> QueryServerTransaction<'a> for QueryServerReadTransaction<'a> {
 }
 
 impl<'a> QueryServerReadTransaction<'a> {
+    /// The state of replication with each partner. This is not part of the transaction.
+    pub fn get_repl_status(&self) -> &'a ReplStatus {
+        self.repl_status
+    }
+
     // Verify the data content of the server is as expected. This will probably
     // call various functions for validation, including possibly plugin
     // verifications.
@@ -863,17 +1009,25 @@ impl<'a> QueryServerTransaction<'a> for QueryServerWriteTransaction<'a> {
 
     fn get_resolve_filter_cache(
         &self,
-    ) -> &mut ARCacheReadTxn<'a, (IdentityId, Filter<FilterValid>), Filter<FilterValidResolved>, ()>
-    {
+    ) -> Option<
+        &mut ARCacheReadTxn<'a, (IdentityId, Filter<FilterValid>), Filter<FilterValidResolved>, ()>,
+    > {
+        // Once schema has changed in this transaction the cached filters may have been
+        // resolved against indexes that no longer exist, so they are bypassed.
+        if self.changed_schema.get() {
+            return None;
+        }
         unsafe {
             let mptr = self.resolve_filter_cache.as_ptr();
-            &mut (*mptr)
-                as &mut ARCacheReadTxn<
-                    'a,
-                    (IdentityId, Filter<FilterValid>),
-                    Filter<FilterValidResolved>,
-                    (),
-                >
+            Some(
+                &mut (*mptr)
+                    as &mut ARCacheReadTxn<
+                        'a,
+                        (IdentityId, Filter<FilterValid>),
+                        Filter<FilterValidResolved>,
+                        (),
+                    >,
+            )
         }
     }
 
@@ -893,7 +1047,20 @@ impl<'a> QueryServerTransaction<'a> for QueryServerWriteTransaction<'a> {
 
 impl QueryServer {
     pub fn new(be: Backend, schema: Schema, domain_name: String) -> Self {
-        let (s_uuid, d_uuid) = {
+        let (s_uuid, d_uuid) = if be.is_readonly() {
+            // Missing uuids can't be stored, so they are only generated for this run.
+            let rd = be.read();
+            (
+                rd.get_db_s_uuid()
+                    .ok()
+                    .flatten()
+                    .unwrap_or_else(Uuid::new_v4),
+                rd.get_db_d_uuid()
+                    .ok()
+                    .flatten()
+                    .unwrap_or_else(Uuid::new_v4),
+            )
+        } else {
             let wr = be.write();
             let res = (wr.get_db_s_uuid(), wr.get_db_d_uuid());
             #[allow(clippy::expect_used)]
@@ -904,7 +1071,7 @@ impl QueryServer {
 
         let pool_size = be.get_pool_size();
 
-        debug!("Server UUID -> {:?}", s_uuid);
+        info!("Server UUID -> {:?}", s_uuid);
         debug!("Domain UUID -> {:?}", d_uuid);
         debug!("Domain Name -> {:?}", domain_name);
 
@@ -914,6 +1081,8 @@ impl QueryServer {
             // we set the domain_display_name to the configuration file's domain_name
             // here because the database is not started, so we cannot pull it from there.
             d_display: domain_name,
+            // This is loaded from the domain info entry once the database is started.
+            d_read_only: false,
         }));
 
         let dyngroup_cache = Arc::new(CowCell::new(DynGroupCache::default()));
@@ -940,9 +1109,17 @@ impl QueryServer {
                     .expect("Failed to build resolve_filter_cache"),
             ),
             dyngroup_cache,
+            repl_status: Arc::new(ReplStatus::new(s_uuid)),
+            change_tx: broadcast::channel(CHANGE_NOTIFY_QUEUE).0,
+            audit_log: Arc::new(AuditLog::default()),
         }
     }
 
+    /// Subscribe to the entries changed by each transaction as it is committed.
+    pub fn subscribe_changes(&self) -> broadcast::Receiver<EntryChanges> {
+        self.change_tx.subscribe()
+    }
+
     pub fn try_quiesce(&self) {
         self.be.try_quiesce();
         self.accesscontrols.try_quiesce();
@@ -965,6 +1142,7 @@ impl QueryServer {
             accesscontrols: self.accesscontrols.read(),
             _db_ticket: db_ticket,
             resolve_filter_cache: Cell::new(self.resolve_filter_cache.read()),
+            repl_status: &self.repl_status,
         }
     }
 
@@ -993,7 +1171,7 @@ impl QueryServer {
         let ts_max = be_txn
             .get_db_ts_max(curtime)
             .expect("Unable to get db_ts_max");
-        let cid = Cid::new_lamport(self.s_uuid, d_info.d_uuid, curtime, &ts_max);
+        let cid = Cid::new_lamport(d_info.d_uuid, self.s_uuid, curtime, &ts_max);
 
         QueryServerWriteTransaction {
             // I think this is *not* needed, because commit is mut self which should
@@ -1015,10 +1193,17 @@ impl QueryServer {
             changed_oauth2: Cell::new(false),
             changed_domain: Cell::new(false),
             changed_uuid: Cell::new(HashSet::new()),
+            changed_entries: Cell::new(BTreeMap::new()),
             _db_ticket: db_ticket,
             _write_ticket: write_ticket,
             resolve_filter_cache: Cell::new(self.resolve_filter_cache.read()),
+            resolve_filter_cache_clear: &self.resolve_filter_cache,
             dyngroup_cache: Cell::new(self.dyngroup_cache.write()),
+            repl_status: &self.repl_status,
+            change_tx: &self.change_tx,
+            audit_log: &self.audit_log,
+            audit_records: Cell::new(Vec::new()),
+            audit_targets: Cell::new(BTreeSet::new()),
         }
     }
 
@@ -1127,6 +1312,9 @@ impl QueryServer {
 
         let mut ts_write_3 = self.write(ts).await;
         ts_write_3.initialise_idm().and_then(|_| {
+            // Our role is only held in the database, so it must be loaded before we
+            // accept any writes.
+            ts_write_3.reload_domain_read_only()?;
             ts_write_3.set_phase(ServerPhase::Running);
             ts_write_3.commit()
         })?;
@@ -1146,8 +1334,17 @@ impl<'a> QueryServerWriteTransaction<'a> {
         self.curtime
     }
 
+    /// The state of replication with each partner. This is not part of the transaction.
+    pub fn get_repl_status(&self) -> &'a ReplStatus {
+        self.repl_status
+    }
+
     #[instrument(level = "debug", skip_all)]
     pub fn create(&mut self, ce: &CreateEvent) -> Result<(), OperationError> {
+        self.audited(EntryChange::Create, &ce.ident, |qs| qs.create_inner(ce))
+    }
+
+    fn create_inner(&mut self, ce: &CreateEvent) -> Result<(), OperationError> {
         // The create event is a raw, read only representation of the request
         // that was made to us, including information about the identity
         // performing the request.
@@ -1155,13 +1352,14 @@ impl<'a> QueryServerWriteTransaction<'a> {
             security_info!(name = %ce.ident, "create initiator");
         }
 
+        self.check_client_write_allowed(&ce.ident)?;
+
         if ce.entries.is_empty() {
             request_error!("create: empty create request");
             return Err(OperationError::EmptyRequest);
         }
 
-        // TODO #67: Do we need limits on number of creates, or do we constraint
-        // based on request size in the frontend?
+        // The number of creates is bounded by the request size limit of the frontend.
 
         // Copy the entries to a writeable form, this involves assigning a
         // change id so we can track what's happening.
@@ -1282,6 +1480,10 @@ impl<'a> QueryServerWriteTransaction<'a> {
         unsafe {
             (*cu).extend(commit_cand.iter().map(|e| e.get_uuid()));
         }
+        self.record_entry_changes(
+            EntryChange::Create,
+            commit_cand.iter().map(|e| e.get_uuid()),
+        );
         trace!(
             schema_reload = ?self.changed_schema,
             acp_reload = ?self.changed_acp,
@@ -1299,9 +1501,13 @@ impl<'a> QueryServerWriteTransaction<'a> {
         Ok(())
     }
 
-    #[allow(clippy::cognitive_complexity)]
     #[instrument(level = "debug", skip_all)]
     pub fn delete(&mut self, de: &DeleteEvent) -> Result<(), OperationError> {
+        self.audited(EntryChange::Delete, &de.ident, |qs| qs.delete_inner(de))
+    }
+
+    #[allow(clippy::cognitive_complexity)]
+    fn delete_inner(&mut self, de: &DeleteEvent) -> Result<(), OperationError> {
         // Do you have access to view all the set members? Reduce based on your
         // read permissions and attrs
         // THIS IS PRETTY COMPLEX SEE THE DESIGN DOC
@@ -1312,6 +1518,8 @@ impl<'a> QueryServerWriteTransaction<'a> {
             security_info!(name = %de.ident, "delete initiator");
         }
 
+        self.check_client_write_allowed(&de.ident)?;
+
         // Now, delete only what you can see
         let pre_candidates = self
             .impersonate_search_valid(de.filter.clone(), de.filter_orig.clone(), &de.ident)
@@ -1424,6 +1632,7 @@ impl<'a> QueryServerWriteTransaction<'a> {
         unsafe {
             (*cu).extend(del_cand.iter().map(|e| e.get_uuid()));
         }
+        self.record_entry_changes(EntryChange::Delete, del_cand.iter().map(|e| e.get_uuid()));
 
         trace!(
             schema_reload = ?self.changed_schema,
@@ -1443,12 +1652,32 @@ impl<'a> QueryServerWriteTransaction<'a> {
 
     #[instrument(level = "debug", skip_all)]
     pub fn purge_tombstones(&self) -> Result<(), OperationError> {
+        self.purge_tombstones_retain(CHANGELOG_MAX_AGE)
+    }
+
+    /// Purge tombstones and trim the changelog of changes older than `changelog_retention`
+    /// seconds. Changes that a replication partner may still need are retained regardless
+    /// of their age.
+    #[instrument(level = "debug", skip_all)]
+    pub fn purge_tombstones_retain(&self, changelog_retention: u64) -> Result<(), OperationError> {
         // purge everything that is a tombstone.
-        let cid = self.cid.sub_secs(CHANGELOG_MAX_AGE).map_err(|e| {
+        let mut cid = self.cid.sub_secs(changelog_retention).map_err(|e| {
             admin_error!("Unable to generate search cid {:?}", e);
             e
         })?;
 
+        // A tombstone must be retained until every replication partner holds it, else the
+        // delete is never replicated to them.
+        if let Some(partner_ts) = self.repl_partner_min_ts()? {
+            if partner_ts < cid.ts {
+                admin_warn!(
+                    ?partner_ts,
+                    "Changelog trim is limited to changes held by all replication partners"
+                );
+                cid.ts = partner_ts;
+            }
+        }
+
         // Delete them - this is a TRUE delete, no going back now!
         self.be_txn
             .reap_tombstones(&cid)
@@ -1479,6 +1708,21 @@ impl<'a> QueryServerWriteTransaction<'a> {
             return Ok(());
         }
 
+        self.tombstone_recycled(&rc)
+            .map(|_| {
+                admin_info!("Purge recycled operation success");
+            })
+            .map_err(|e| {
+                admin_error!("Purge recycled operation failed, {:?}", e);
+                e
+            })
+    }
+
+    /// Convert these recycled entries to tombstones, ending the ability to revive them.
+    pub(crate) fn tombstone_recycled(
+        &self,
+        rc: &[Arc<EntrySealedCommitted>],
+    ) -> Result<(), OperationError> {
         // Modify them to strip all avas except uuid
         let tombstone_cand: Result<Vec<_>, _> = rc
             .iter()
@@ -1498,14 +1742,11 @@ impl<'a> QueryServerWriteTransaction<'a> {
 
         // Backend Modify
         self.be_txn
-            .modify(&self.cid, &rc, &tombstone_cand)
+            .modify(&self.cid, rc, &tombstone_cand)
             .map_err(|e| {
-                admin_error!("Purge recycled operation failed (backend), {:?}", e);
+                admin_error!("Tombstone recycled operation failed (backend), {:?}", e);
                 e
             })
-            .map(|_| {
-                admin_info!("Purge recycled operation success");
-            })
     }
 
     #[instrument(level = "debug", skip_all)]
@@ -1519,6 +1760,8 @@ impl<'a> QueryServerWriteTransaction<'a> {
             security_info!(name = %re.ident, "revive initiator");
         }
 
+        self.check_client_write_allowed(&re.ident)?;
+
         // Get the list of pre_candidates, using impersonate search.
         let pre_candidates =
             self.impersonate_search_valid(re.filter.clone(), re.filter.clone(), &re.ident)?;
@@ -1743,6 +1986,8 @@ impl<'a> QueryServerWriteTransaction<'a> {
             security_info!(name = %me.ident, "modify initiator");
         }
 
+        self.check_client_write_allowed(&me.ident)?;
+
         // Validate input.
 
         // Is the modlist non zero?
@@ -1929,6 +2174,7 @@ impl<'a> QueryServerWriteTransaction<'a> {
                     .chain(pre_candidates.iter().map(|e| e.get_uuid())),
             );
         }
+        self.record_entry_changes(EntryChange::Modify, norm_cand.iter().map(|e| e.get_uuid()));
 
         trace!(
             schema_reload = ?self.changed_schema,
@@ -1948,12 +2194,40 @@ impl<'a> QueryServerWriteTransaction<'a> {
 
     #[instrument(level = "debug", skip_all)]
     pub fn modify(&mut self, me: &ModifyEvent) -> Result<(), OperationError> {
-        let mp = unsafe { self.modify_pre_apply(me)? };
-        if let Some(mp) = mp {
-            self.modify_apply(mp)
-        } else {
-            // No action to apply, the pre-apply said nothing to be done.
+        self.audited(EntryChange::Modify, &me.ident, |qs| {
+            let mp = unsafe { qs.modify_pre_apply(me)? };
+            if let Some(mp) = mp {
+                qs.modify_apply(mp)
+            } else {
+                // No action to apply, the pre-apply said nothing to be done.
+                Ok(())
+            }
+        })
+    }
+
+    /// Check that the entries matching a filter are unchanged since the identity read them,
+    /// given the etags the identity read them with. This must be called in the transaction
+    /// that changes the entries, so that no other change can be made between the check and
+    /// the change.
+    #[instrument(level = "debug", skip_all)]
+    pub fn check_etags(
+        &mut self,
+        ident: &Identity,
+        filter: &Filter<FilterInvalid>,
+        etags: &[String],
+    ) -> Result<(), OperationError> {
+        let entries = self.impersonate_search(filter.clone(), filter.clone(), ident)?;
+        if entries.is_empty() {
+            return Err(OperationError::NoMatchingEntries);
+        }
+        if entries
+            .iter()
+            .all(|e| e.etag_matches(etags.iter().map(|s| s.as_str())))
+        {
             Ok(())
+        } else {
+            request_warn!("Entry was changed after it was read, refusing the change");
+            Err(OperationError::EntryChanged)
         }
     }
 
@@ -2082,6 +2356,7 @@ impl<'a> QueryServerWriteTransaction<'a> {
                     .chain(pre_candidates.iter().map(|e| e.get_uuid())),
             );
         }
+        self.record_entry_changes(EntryChange::Modify, norm_cand.iter().map(|e| e.get_uuid()));
         trace!(
             schema_reload = ?self.changed_schema,
             acp_reload = ?self.changed_acp,
@@ -2490,9 +2765,6 @@ impl<'a> QueryServerWriteTransaction<'a> {
         // If not exist, create from Entry B
         //
         // This will extra classes an attributes alone!
-        //
-        // NOTE: gen modlist IS schema aware and will handle multivalue
-        // correctly!
         trace!("internal_migrate_or_create operating on {:?}", e.get_uuid());
 
         let filt = match e.filter_from_attrs(&[AttrString::from("uuid")]) {
@@ -2508,15 +2780,28 @@ impl<'a> QueryServerWriteTransaction<'a> {
             // It does not exist. Create it.
             self.internal_create(vec![e])
         } else if results.len() == 1 {
-            // If the thing is subset, pass
-            match e.gen_modlist_assert(&self.schema) {
-                Ok(modlist) => {
-                    // Apply to &results[0]
-                    trace!("Generated modlist -> {:?}", modlist);
-                    self.internal_modify(&filt, &modlist)
+            let mut modlist = results[0].diff(&e);
+            // Only the attributes of the migrated entry are asserted, so we keep the extended
+            // attributes and the extra values of multivalue attributes. The system types of
+            // schema are the exception, as they must be able to express the REMOVAL of
+            // attributes.
+            let schema = &self.schema;
+            modlist.retain(|m| match m {
+                Modify::Purged(_) => false,
+                Modify::Removed(k, _) => {
+                    k == "systemmust"
+                        || k == "systemmay"
+                        || !schema.is_multivalue(k).unwrap_or(false)
                 }
-                Err(e) => Err(OperationError::SchemaViolation(e)),
+                _ => true,
+            });
+
+            // If the thing is subset, pass
+            if modlist.is_empty() {
+                return Ok(());
             }
+            trace!("Generated modlist -> {:?}", modlist);
+            self.internal_modify(&filt, &modlist)
         } else {
             admin_error!(
                 "Invalid Result Set - Expected One Entry for {:?} - {:?}",
@@ -2527,21 +2812,15 @@ impl<'a> QueryServerWriteTransaction<'a> {
         }
     }
 
-    /*
-    pub fn internal_assert_or_create_str(
-        &mut self,
-        e_str: &str,
-    ) -> Result<(), OperationError> {
-        let res = audit_segment!( || Entry::from_proto_entry_str( e_str, self)
-            .and_then(
-                |e: Entry<EntryInit, EntryNew>| self.internal_assert_or_create( e)
-            ));
-        ltrace!( "internal_assert_or_create_str -> result {:?}", res);
+    #[instrument(level = "debug", skip_all)]
+    pub fn internal_assert_or_create_str(&mut self, e_str: &str) -> Result<(), OperationError> {
+        let res = Entry::from_proto_entry_str(e_str, self)
+            .and_then(|e: Entry<EntryInit, EntryNew>| self.internal_assert_or_create(e));
+        trace!(?res);
         debug_assert!(res.is_ok());
         res
     }
 
-    // Should this take a be_txn?
     pub fn internal_assert_or_create(
         &mut self,
         e: Entry<EntryInit, EntryNew>,
@@ -2549,47 +2828,39 @@ impl<'a> QueryServerWriteTransaction<'a> {
         // If exists, ensure the object is exactly as provided
         // else, if not exists, create it. IE no extra or excess
         // attributes and classes.
-
-        ltrace!(
-
-            "internal_assert_or_create operating on {:?}",
-            e.get_uuid()
-        );
+        trace!("internal_assert_or_create operating on {:?}", e.get_uuid());
 
         // Create a filter from the entry for assertion.
-        let filt = match e.filter_from_attrs(&[String::from("uuid")]) {
+        let filt = match e.filter_from_attrs(&[AttrString::from("uuid")]) {
             Some(f) => f,
             None => return Err(OperationError::FilterGeneration),
         };
 
         // Does it exist? we use search here, not exists, so that if the entry does exist
-        // we can compare it is identical, which avoids a delete/create cycle that would
+        // we only modify what differs, which avoids a delete/create cycle that would
         // trigger csn/repl each time we start up.
-        match self.internal_search( filt.clone()) {
-            Ok(results) => {
-                if results.is_empty() {
-                    // It does not exist. Create it.
-                    self.internal_create( vec![e])
-                } else if results.len() == 1 {
-                    // it exists. To guarantee content exactly as is, we compare if it's identical.
-                    if !e.compare(&results[0]) {
-                        self.internal_delete( filt)
-                            .and_then(|_| self.internal_create( vec![e]))
-                    } else {
-                        // No action required
-                        Ok(())
-                    }
-                } else {
-                    Err(OperationError::InvalidDbState)
-                }
-            }
-            Err(er) => {
-                // An error occured. pass it back up.
-                Err(er)
+        let results = self.internal_search(filt.clone())?;
+
+        if results.is_empty() {
+            // It does not exist. Create it.
+            self.internal_create(vec![e])
+        } else if results.len() == 1 {
+            let modlist = results[0].diff(&e);
+            if modlist.is_empty() {
+                // No action required
+                return Ok(());
             }
+            trace!("Generated modlist -> {:?}", modlist);
+            self.internal_modify(&filt, &modlist)
+        } else {
+            admin_error!(
+                "Invalid Result Set - Expected One Entry for {:?} - {:?}",
+                filt,
+                results
+            );
+            Err(OperationError::InvalidDbState)
         }
     }
-    */
 
     #[instrument(level = "info", skip_all)]
     pub fn initialise_schema_core(&mut self) -> Result<(), OperationError> {
@@ -2627,6 +2898,9 @@ impl<'a> QueryServerWriteTransaction<'a> {
             JSON_SCHEMA_ATTR_RADIUS_SECRET,
             JSON_SCHEMA_ATTR_DOMAIN_NAME,
             JSON_SCHEMA_ATTR_DOMAIN_DISPLAY_NAME,
+            JSON_SCHEMA_ATTR_DOMAIN_LDAP_BASEDN,
+            JSON_SCHEMA_ATTR_DOMAIN_LDAP_RDN_ATTR,
+            JSON_SCHEMA_ATTR_DOMAIN_LDAP_ATTR_MAP,
             JSON_SCHEMA_ATTR_DOMAIN_UUID,
             JSON_SCHEMA_ATTR_DOMAIN_SSID,
             JSON_SCHEMA_ATTR_DOMAIN_TOKEN_KEY,
@@ -2663,6 +2937,19 @@ impl<'a> QueryServerWriteTransaction<'a> {
             JSON_SCHEMA_ATTR_SYNC_COOKIE,
             JSON_SCHEMA_ATTR_GRANT_UI_HINT,
             JSON_SCHEMA_ATTR_OAUTH2_RS_ORIGIN_LANDING,
+            JSON_SCHEMA_ATTR_OAUTH2_ACCESS_TOKEN_EXPIRY,
+            JSON_SCHEMA_ATTR_OAUTH2_REFRESH_TOKEN_EXPIRY,
+            JSON_SCHEMA_ATTR_OAUTH2_SESSION_OUTLIVE_PARENT,
+            JSON_SCHEMA_ATTR_OAUTH2_JWT_SIGN_ALG,
+            JSON_SCHEMA_ATTR_OAUTH2_TOKEN_EXCHANGE_AUDIENCE,
+            JSON_SCHEMA_ATTR_REPL_PARTNER_ADDRESS,
+            JSON_SCHEMA_ATTR_REPL_PARTNER_CERT_SHA256,
+            JSON_SCHEMA_ATTR_DOMAIN_READ_ONLY_REPLICA,
+            JSON_SCHEMA_ATTR_REPL_JOIN_TOKEN,
+            JSON_SCHEMA_ATTR_HOST_JOIN_TOKEN,
+            JSON_SCHEMA_ATTR_PHONE_NUMBER,
+            JSON_SCHEMA_ATTR_POSTAL_ADDRESS,
+            JSON_SCHEMA_ATTR_IMAGE,
             JSON_SCHEMA_CLASS_PERSON,
             JSON_SCHEMA_CLASS_ORGPERSON,
             JSON_SCHEMA_CLASS_GROUP,
@@ -2675,7 +2962,10 @@ impl<'a> QueryServerWriteTransaction<'a> {
             JSON_SCHEMA_CLASS_SYSTEM_CONFIG,
             JSON_SCHEMA_CLASS_OAUTH2_RS,
             JSON_SCHEMA_CLASS_OAUTH2_RS_BASIC,
+            JSON_SCHEMA_CLASS_OAUTH2_RS_PUBLIC,
             JSON_SCHEMA_CLASS_SYNC_ACCOUNT,
+            JSON_SCHEMA_CLASS_REPL_AGREEMENT,
+            JSON_SCHEMA_CLASS_HOST,
         ];
 
         let r = idm_schema
@@ -2816,6 +3106,7 @@ impl<'a> QueryServerWriteTransaction<'a> {
             JSON_IDM_HP_ACP_SERVICE_ACCOUNT_INTO_PERSON_MIGRATE_V1,
             JSON_IDM_ACP_OAUTH2_READ_PRIV_V1,
             JSON_IDM_HP_ACP_SYNC_ACCOUNT_MANAGE_PRIV_V1,
+            JSON_IDM_ACP_REPL_AGREEMENT_MANAGE_PRIV_V1,
         ];
 
         let res: Result<(), _> = idm_entries
@@ -2853,7 +3144,7 @@ impl<'a> QueryServerWriteTransaction<'a> {
     }
 
     #[instrument(level = "debug", name = "reload_schema", skip(self))]
-    fn reload_schema(&mut self) -> Result<(), OperationError> {
+    pub(crate) fn reload_schema(&mut self) -> Result<(), OperationError> {
         // supply entries to the writable schema to reload from.
         // find all attributes.
         let filt = filter!(f_eq("class", PVCLASS_ATTRIBUTETYPE.clone()));
@@ -2899,6 +3190,8 @@ impl<'a> QueryServerWriteTransaction<'a> {
         if valid_r.is_empty() {
             // Now use this to reload the backend idxmeta
             trace!("Reloading idxmeta ...");
+            self.be_txn
+                .update_sealed_attrs(self.schema.reload_sealed_attrs());
             self.be_txn
                 .update_idxmeta(self.schema.reload_idxmeta())
                 .map_err(|e| {
@@ -3044,6 +3337,17 @@ impl<'a> QueryServerWriteTransaction<'a> {
         })
     }
 
+    /// True if this server is listed as a read only replica on the domain info entry.
+    fn get_db_domain_read_only_replica(&self) -> Result<bool, OperationError> {
+        let s_uuid = PartialValue::new_uuid(self.cid.s_uuid);
+        self.internal_search_uuid(&UUID_DOMAIN_INFO)
+            .map(|e| e.attribute_equality("domain_read_only_replica", &s_uuid))
+            .map_err(|e| {
+                admin_error!(?e, "Error getting domain read only replicas");
+                e
+            })
+    }
+
     fn get_db_domain_display_name(&self) -> Result<String, OperationError> {
         self.internal_search_uuid(&UUID_DOMAIN_INFO)
             .and_then(|e| {
@@ -3063,7 +3367,10 @@ impl<'a> QueryServerWriteTransaction<'a> {
     fn reload_domain_info(&mut self) -> Result<(), OperationError> {
         let domain_name = self.get_db_domain_name()?;
         let display_name = self.get_db_domain_display_name()?;
+        // This only differs if we were refreshed from a replication supplier.
+        let domain_uuid = self.be_txn.get_db_d_uuid();
         let mut_d_info = self.d_info.get_mut();
+        mut_d_info.d_uuid = domain_uuid;
         if mut_d_info.d_name != domain_name {
             admin_warn!(
                 "Using domain name from the database {} - was {} in memory",
@@ -3076,9 +3383,34 @@ impl<'a> QueryServerWriteTransaction<'a> {
             mut_d_info.d_name = domain_name;
         }
         mut_d_info.d_display = display_name;
+        self.reload_domain_read_only()
+    }
+
+    /// Pulls this server's read only replica role from the database.
+    fn reload_domain_read_only(&mut self) -> Result<(), OperationError> {
+        let read_only = self.get_db_domain_read_only_replica()?;
+        let mut_d_info = self.d_info.get_mut();
+        if mut_d_info.d_read_only != read_only {
+            admin_warn!(
+                read_only,
+                "This server's read only replica role has changed"
+            );
+            mut_d_info.d_read_only = read_only;
+        }
         Ok(())
     }
 
+    /// A read only replica applies the changes it receives from its replication suppliers,
+    /// but rejects any write that originates from a client.
+    fn check_client_write_allowed(&self, ident: &Identity) -> Result<(), OperationError> {
+        if self.d_info.d_read_only && !ident.is_internal() {
+            security_info!(name = %ident, "Rejecting client write to a read only replica");
+            Err(OperationError::ReplReadOnlyReplica)
+        } else {
+            Ok(())
+        }
+    }
+
     /// Initiate a domain display name change process. This isn't particularly scary
     /// because it's just a wibbly human-facing thing, not used for secure
     /// activities (yet)
@@ -3092,13 +3424,92 @@ impl<'a> QueryServerWriteTransaction<'a> {
         self.internal_modify(&filt, &modl)
     }
 
+    /// The changes that renaming the domain to `new_domain_name` would make.
+    pub fn domain_rename_plan(
+        &self,
+        new_domain_name: &str,
+    ) -> Result<DomainRenamePlan, OperationError> {
+        let old_domain_name = self.get_db_domain_name()?;
+
+        let spn_entries = self.internal_search(filter!(f_pres("spn")))?.len();
+
+        let mut origins = Vec::new();
+        let rs_set = self.internal_search(filter!(f_eq("class", PVCLASS_OAUTH2_RS.clone())))?;
+        for rs in rs_set.iter() {
+            let rs_name = rs
+                .get_ava_single_iname("oauth2_rs_name")
+                .unwrap_or_default();
+            for attr in ["oauth2_rs_origin", "oauth2_rs_origin_landing"] {
+                let rewrite = rs.get_ava_single_url(attr).and_then(|old| {
+                    rename_url_domain(old, &old_domain_name, new_domain_name).map(|new| {
+                        OriginRewrite {
+                            rs_name: rs_name.to_string(),
+                            uuid: rs.get_uuid(),
+                            attr,
+                            old: old.clone(),
+                            new,
+                        }
+                    })
+                });
+                origins.extend(rewrite);
+            }
+        }
+
+        let count = |entries: &[Arc<EntrySealedCommitted>], attrs: &[&str]| -> usize {
+            entries
+                .iter()
+                .flat_map(|e| attrs.iter().filter_map(|attr| e.get_ava_set(attr)))
+                .map(|vs| vs.len())
+                .sum()
+        };
+        let session_attrs = ["user_auth_token_session", "oauth2_session"];
+        let token_attrs = ["api_token_session", "sync_token_session"];
+        let key_attrs = ["passkeys", "devicekeys"];
+        let holders = self.internal_search(filter!(f_or(
+            session_attrs
+                .iter()
+                .chain(token_attrs.iter())
+                .chain(key_attrs.iter())
+                .copied()
+                .map(f_pres)
+                .collect()
+        )))?;
+
+        Ok(DomainRenamePlan {
+            old_domain_name,
+            new_domain_name: new_domain_name.to_string(),
+            spn_entries,
+            origins,
+            sessions: count(&holders, &session_attrs),
+            api_tokens: count(&holders, &token_attrs),
+            passkeys: count(&holders, &key_attrs),
+        })
+    }
+
     /// Initiate a domain rename process. This is generally an internal function but it's
-    /// exposed to the cli for admins to be able to initiate the process.
-    pub fn domain_rename(&mut self, new_domain_name: &str) -> Result<(), OperationError> {
+    /// exposed to the cli for admins to be able to initiate the process. The origins of
+    /// oauth2 resource servers in the old domain are moved to the new one, and the spns are
+    /// regenerated, in this transaction. The changes are returned to be reviewed before commit.
+    pub fn domain_rename(
+        &mut self,
+        new_domain_name: &str,
+    ) -> Result<DomainRenamePlan, OperationError> {
         // We can't use the d_info struct here, because this has the database version of the domain
         // name, not the in memory (config) version. We need to accept the domain's
         // new name from the caller so we can change this.
-        unsafe { self.domain_rename_inner(new_domain_name) }
+        let plan = self.domain_rename_plan(new_domain_name)?;
+
+        if !plan.origins.is_empty() {
+            self.internal_batch_modify(plan.origins.iter().map(|o| {
+                (
+                    o.uuid,
+                    ModifyList::new_purge_and_set(o.attr, Value::new_url(o.new.clone())),
+                )
+            }))?;
+        }
+
+        unsafe { self.domain_rename_inner(new_domain_name) }?;
+        Ok(plan)
     }
 
     /// # Safety
@@ -3136,6 +3547,29 @@ impl<'a> QueryServerWriteTransaction<'a> {
         self.be_txn.upgrade_reindex(v)
     }
 
+    /// Record changes to entries to notify subscribers of. An entry that is created, or
+    /// deleted, in this transaction is not reported as modified as well.
+    pub(crate) fn record_entry_changes<I>(&self, change: EntryChange, uuids: I)
+    where
+        I: Iterator<Item = Uuid>,
+    {
+        let mut changed = self.changed_entries.take();
+        let mut targets = self.audit_targets.take();
+        uuids.for_each(|uuid| {
+            match change {
+                EntryChange::Modify => {
+                    changed.entry(uuid).or_insert(change);
+                }
+                EntryChange::Create | EntryChange::Delete => {
+                    changed.insert(uuid, change);
+                }
+            }
+            targets.insert(uuid);
+        });
+        self.changed_entries.set(changed);
+        self.audit_targets.set(targets);
+    }
+
     pub fn get_changed_uuids(&self) -> &HashSet<Uuid> {
         unsafe { &(*self.changed_uuid.as_ptr()) }
     }
@@ -3178,6 +3612,10 @@ impl<'a> QueryServerWriteTransaction<'a> {
             self.reload_domain_info()?;
         }
 
+        self.write_audit_log()?;
+
+        let changed_schema = self.changed_schema.get();
+
         // Now destructure the transaction ready to reset it.
         let QueryServerWriteTransaction {
             committed,
@@ -3188,6 +3626,9 @@ impl<'a> QueryServerWriteTransaction<'a> {
             accesscontrols,
             cid,
             dyngroup_cache,
+            changed_entries,
+            change_tx,
+            resolve_filter_cache_clear,
             ..
         } = self;
         debug_assert!(!committed);
@@ -3208,6 +3649,27 @@ impl<'a> QueryServerWriteTransaction<'a> {
                 .map(|_| dyngroup_cache.into_inner().commit())
                 .and_then(|_| accesscontrols.commit())
                 .and_then(|_| be_txn.commit())
+                .map(|_| {
+                    // Filters that were resolved against the old schema may use indexes
+                    // that no longer exist, or miss those that now do.
+                    if changed_schema {
+                        let mut rcache = resolve_filter_cache_clear.write();
+                        rcache.clear();
+                        rcache.commit();
+                    }
+                })
+                .map(|_| {
+                    // Subscribers are only told of changes once they are visible to readers.
+                    let changed = changed_entries.into_inner();
+                    if !changed.is_empty() && change_tx.receiver_count() > 0 {
+                        let changes: Vec<_> = changed
+                            .into_iter()
+                            .map(|(uuid, change)| EntryChangeNotification { uuid, change })
+                            .collect();
+                        // This can only fail if every subscriber has since gone.
+                        let _ = change_tx.send(changes.into());
+                    }
+                })
         } else {
             Err(OperationError::ConsistencyError(r))
         }
@@ -3220,13 +3682,196 @@ mod tests {
     use std::sync::Arc;
     use std::time::Duration;
 
-    use kanidm_proto::v1::SchemaError;
+    use kanidm_proto::v1::{EntryChange, EntryChangeNotification, SchemaError};
 
     use crate::credential::policy::CryptoPolicy;
     use crate::credential::Credential;
-    use crate::event::{CreateEvent, DeleteEvent, ModifyEvent, ReviveRecycledEvent, SearchEvent};
+    use crate::event::{
+        CreateEvent, DeleteEvent, ModifyEvent, ReviveRecycledEvent, SearchEvent, SearchPage,
+    };
     use crate::prelude::*;
 
+    #[qs_test]
+    async fn test_qs_subscribe_changes(server: &QueryServer) {
+        let mut rx = server.subscribe_changes();
+        let t_uuid = Uuid::new_v4();
+        let t_filt = filter!(f_eq("uuid", PartialValue::new_uuid(t_uuid)));
+        let t_change = |change| EntryChangeNotification {
+            uuid: t_uuid,
+            change,
+        };
+
+        let mut server_txn = server.write(duration_from_epoch_now()).await;
+        assert!(server_txn
+            .internal_create(vec![entry_init!(
+                ("class", Value::new_class("object")),
+                ("class", Value::new_class("person")),
+                ("name", Value::new_iname("testperson1")),
+                ("uuid", Value::new_uuid(t_uuid)),
+                ("description", Value::new_utf8s("testperson1")),
+                ("displayname", Value::new_utf8s("testperson1"))
+            )])
+            .is_ok());
+        // Nothing is sent until the transaction commits.
+        assert!(rx.try_recv().is_err());
+        assert!(server_txn.commit().is_ok());
+
+        let changes = rx.try_recv().expect("No changes sent");
+        assert!(changes.contains(&t_change(EntryChange::Create)));
+
+        // A modified entry that is then deleted is only reported as deleted.
+        let mut server_txn = server.write(duration_from_epoch_now()).await;
+        assert!(server_txn
+            .internal_modify(
+                &t_filt,
+                &ModifyList::new_purge_and_set("description", Value::new_utf8s("changed"))
+            )
+            .is_ok());
+        assert!(server_txn.internal_delete(&t_filt).is_ok());
+        assert!(server_txn.commit().is_ok());
+
+        let changes = rx.try_recv().expect("No changes sent");
+        assert!(changes.contains(&t_change(EntryChange::Delete)));
+        assert!(!changes.contains(&t_change(EntryChange::Modify)));
+
+        // Changes that are not committed are never sent.
+        let mut server_txn = server.write(duration_from_epoch_now()).await;
+        assert!(server_txn
+            .internal_modify(
+                &filter!(f_eq("name", PartialValue::new_iname("admin"))),
+                &ModifyList::new_purge_and_set("description", Value::new_utf8s("changed"))
+            )
+            .is_ok());
+        drop(server_txn);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[qs_test]
+    async fn test_qs_migrate_or_create(server: &QueryServer) {
+        let t_uuid = Uuid::new_v4();
+        let t_entry = |description| {
+            entry_init!(
+                ("class", Value::new_class("object")),
+                ("class", Value::new_class("person")),
+                ("name", Value::new_iname("testperson1")),
+                ("uuid", Value::new_uuid(t_uuid)),
+                ("description", Value::new_utf8s(description)),
+                ("displayname", Value::new_utf8s("testperson1"))
+            )
+        };
+
+        let mut server_txn = server.write(duration_from_epoch_now()).await;
+        assert!(server_txn
+            .internal_migrate_or_create(t_entry("testperson1"))
+            .is_ok());
+        let last_changed = server_txn
+            .internal_search_uuid(&t_uuid)
+            .expect("failed")
+            .get_last_changed();
+        assert!(server_txn.commit().is_ok());
+
+        // Migrating an entry that is already in that state does not modify it.
+        let mut server_txn = server.write(duration_from_epoch_now()).await;
+        assert!(server_txn
+            .internal_migrate_or_create(t_entry("testperson1"))
+            .is_ok());
+        let e = server_txn.internal_search_uuid(&t_uuid).expect("failed");
+        assert!(e.get_last_changed() == last_changed);
+
+        // Attributes that the migration does not define are kept.
+        assert!(server_txn
+            .internal_modify(
+                &filter!(f_eq("uuid", PartialValue::new_uuid(t_uuid))),
+                &ModifyList::new_purge_and_set("legalname", Value::new_utf8s("Test Person"))
+            )
+            .is_ok());
+        assert!(server_txn
+            .internal_migrate_or_create(t_entry("changed"))
+            .is_ok());
+        let e = server_txn.internal_search_uuid(&t_uuid).expect("failed");
+        assert!(e.attribute_equality("description", &PartialValue::new_utf8s("changed")));
+        assert!(!e.attribute_equality("description", &PartialValue::new_utf8s("testperson1")));
+        assert!(e.attribute_equality("legalname", &PartialValue::new_utf8s("Test Person")));
+        assert!(server_txn.commit().is_ok());
+    }
+
+    #[qs_test]
+    async fn test_qs_search_ext_page(server: &QueryServer) {
+        let mut server_txn = server.write(duration_from_epoch_now()).await;
+        let uuids = [
+            uuid::uuid!("cc8e95b4-c24f-4d68-ba54-8bed76f63931"),
+            uuid::uuid!("cc8e95b4-c24f-4d68-ba54-8bed76f63932"),
+            uuid::uuid!("cc8e95b4-c24f-4d68-ba54-8bed76f63933"),
+        ];
+        let entries = uuids
+            .iter()
+            .enumerate()
+            .map(|(i, u)| {
+                let name = format!("testperson{}", i);
+                entry_init!(
+                    ("class", Value::new_class("object")),
+                    ("class", Value::new_class("person")),
+                    ("name", Value::new_iname(&name)),
+                    ("uuid", Value::new_uuid(*u)),
+                    ("description", Value::new_utf8s(&name)),
+                    ("displayname", Value::new_utf8s(&name))
+                )
+            })
+            .collect();
+        assert!(server_txn
+            .internal_create(entries)
+            .and_then(|_| server_txn.commit())
+            .is_ok());
+
+        let search = |server_txn: &QueryServerReadTransaction, page: SearchPage| {
+            let admin = server_txn
+                .internal_search_uuid(&UUID_ADMIN)
+                .expect("failed");
+            let filt = filter!(f_or(
+                uuids
+                    .iter()
+                    .map(|u| f_eq("uuid", PartialValue::new_uuid(*u)))
+                    .collect()
+            ));
+            let se = unsafe { SearchEvent::new_impersonate_entry(admin, filt) };
+            let (entries, next) = server_txn
+                .search_ext_page(&se, &page)
+                .expect("search failure");
+            let entries: Vec<_> = entries.iter().map(|e| e.get_uuid()).collect();
+            (entries, next)
+        };
+
+        let server_txn = server.read().await;
+        let (page_1, next) = search(
+            &server_txn,
+            SearchPage {
+                size: 2,
+                after: None,
+            },
+        );
+        assert!(page_1 == uuids[..2].to_vec());
+        assert!(next == Some(uuids[1]));
+        drop(server_txn);
+
+        // An entry of the previous page being removed does not skip an entry of the next.
+        let mut server_txn = server.write(duration_from_epoch_now()).await;
+        assert!(server_txn
+            .internal_delete(&filter!(f_eq("uuid", PartialValue::new_uuid(uuids[0]))))
+            .and_then(|_| server_txn.commit())
+            .is_ok());
+
+        let server_txn = server.read().await;
+        let (page_2, next) = search(
+            &server_txn,
+            SearchPage {
+                size: 2,
+                after: next,
+            },
+        );
+        assert!(page_2 == uuids[2..].to_vec());
+        assert!(next.is_none());
+    }
+
     #[qs_test]
     async fn test_create_user(server: &QueryServer) {
         let mut server_txn = server.write(duration_from_epoch_now()).await;
@@ -3425,6 +4070,101 @@ mod tests {
         assert!(server_txn.commit().is_ok());
     }
 
+    #[qs_test]
+    async fn test_qs_attr_changes(server: &QueryServer) {
+        let t_uuid = Uuid::new_v4();
+        let mut server_txn = server.write(duration_from_epoch_now()).await;
+        assert!(server_txn
+            .internal_create(vec![entry_init!(
+                ("class", Value::new_class("object")),
+                ("class", Value::new_class("person")),
+                ("name", Value::new_iname("testperson1")),
+                ("uuid", Value::new_uuid(t_uuid)),
+                ("description", Value::new_utf8s("testperson1")),
+                ("displayname", Value::new_utf8s("testperson1"))
+            )])
+            .is_ok());
+        assert!(server_txn.commit().is_ok());
+
+        let mut server_txn = server.write(duration_from_epoch_now()).await;
+        assert!(server_txn
+            .internal_modify(
+                &filter!(f_eq("uuid", PartialValue::new_uuid(t_uuid))),
+                &ModifyList::new_purge_and_set("description", Value::new_utf8s("changed"))
+            )
+            .is_ok());
+        assert!(server_txn.commit().is_ok());
+
+        let server_txn = server.read().await;
+        let e = server_txn.internal_search_uuid(&t_uuid).expect("failed");
+        let allowed = ["name", "description"].into_iter().collect();
+        let changes = e.reduce_attributes(&allowed).get_attr_changes();
+        // Only the attributes that may be read are given.
+        assert!(changes.len() == 2);
+        let changed = |attr| {
+            changes
+                .iter()
+                .find(|c| c.attr == attr)
+                .map(|c| c.cid.clone())
+                .expect("No change for attribute")
+        };
+        assert!(changed("description") != changed("name"));
+        assert!(e.get_last_changed().map(|cid| cid.to_string()) == Some(changed("description")));
+    }
+
+    #[qs_test]
+    async fn test_check_etags(server: &QueryServer) {
+        let ident = Identity::from_internal();
+        let t_uuid = Uuid::new_v4();
+        let filter = filter!(f_eq("uuid", PartialValue::Uuid(t_uuid)));
+        let etag_of = |server_txn: &QueryServerWriteTransaction| {
+            let e = server_txn
+                .internal_search_uuid(&t_uuid)
+                .expect("Failed to get entry");
+            let e = unsafe { e.as_ref().clone().into_reduced() };
+            e.get_etag().expect("No etag")
+        };
+
+        let mut server_txn = server.write(duration_from_epoch_now()).await;
+        assert!(server_txn
+            .internal_create(vec![entry_init!(
+                ("class", Value::new_class("object")),
+                ("uuid", Value::Uuid(t_uuid))
+            ),])
+            .is_ok());
+        let etag = etag_of(&server_txn);
+        assert!(server_txn
+            .check_etags(&ident, &filter, &[etag.clone()])
+            .is_ok());
+        assert!(server_txn
+            .check_etags(&ident, &filter, &["*".to_string()])
+            .is_ok());
+        assert!(server_txn.commit().is_ok());
+
+        // Once the entry is modified, the etag it was read with is stale.
+        let mut server_txn = server.write(duration_from_epoch_now()).await;
+        assert!(server_txn
+            .internal_modify_uuid(
+                t_uuid,
+                &ModifyList::new_purge_and_set("description", Value::new_utf8s("changed"))
+            )
+            .is_ok());
+        assert!(server_txn.commit().is_ok());
+
+        let mut server_txn = server.write(duration_from_epoch_now()).await;
+        assert_ne!(etag_of(&server_txn), etag);
+        assert_eq!(
+            server_txn.check_etags(&ident, &filter, &[etag]),
+            Err(OperationError::EntryChanged)
+        );
+
+        let filter = filter!(f_eq("uuid", PartialValue::Uuid(Uuid::new_v4())));
+        assert_eq!(
+            server_txn.check_etags(&ident, &filter, &["*".to_string()]),
+            Err(OperationError::NoMatchingEntries)
+        );
+    }
+
     #[qs_test]
     async fn test_modify_assert(server: &QueryServer) {
         let mut server_txn = server.write(duration_from_epoch_now()).await;
@@ -4664,4 +5404,134 @@ mod tests {
         );
         assert!(server_txn.commit().is_ok());
     }
+
+    #[qs_test]
+    async fn test_read_only_replica_rejects_client_writes(server: &QueryServer) {
+        let mut server_txn = server.write(duration_from_epoch_now()).await;
+        let s_uuid = server_txn.cid.s_uuid;
+        let modl =
+            ModifyList::new_purge_and_set("domain_read_only_replica", Value::new_uuid(s_uuid));
+        assert!(server_txn
+            .internal_modify(
+                &filter_all!(f_eq("uuid", PVUUID_DOMAIN_INFO.clone())),
+                &modl
+            )
+            .is_ok());
+        assert!(server_txn.commit().is_ok());
+
+        let mut server_txn = server.write(duration_from_epoch_now()).await;
+        let admin = server_txn
+            .internal_search_uuid(&UUID_ADMIN)
+            .expect("failed");
+
+        let e = entry_init!(
+            ("class", Value::new_class("object")),
+            ("class", Value::new_class("person")),
+            ("name", Value::new_iname("testperson1")),
+            ("description", Value::new_utf8s("testperson1")),
+            ("displayname", Value::new_utf8s("testperson1"))
+        );
+
+        // A client can't write to a read only replica, even with access to do so.
+        let ce = CreateEvent::new_impersonate_identity(
+            Identity::from_impersonate_entry_readwrite(admin.clone()),
+            vec![e.clone()],
+        );
+        assert!(server_txn.create(&ce) == Err(OperationError::ReplReadOnlyReplica));
+
+        // But internal and replicated changes are still applied.
+        assert!(server_txn.internal_create(vec![e]).is_ok());
+
+        let filt = filter!(f_eq("name", PartialValue::new_iname("testperson1")));
+        let de = unsafe { DeleteEvent::new_impersonate_entry(admin, filt.clone()) };
+        assert!(server_txn.delete(&de) == Err(OperationError::ReplReadOnlyReplica));
+        assert!(server_txn.internal_delete(&filt).is_ok());
+        assert!(server_txn.commit().is_ok());
+
+        // Once removed from the read only replicas, client writes are accepted again.
+        let mut server_txn = server.write(duration_from_epoch_now()).await;
+        let modl = ModifyList::new_purge("domain_read_only_replica");
+        assert!(server_txn
+            .internal_modify(
+                &filter_all!(f_eq("uuid", PVUUID_DOMAIN_INFO.clone())),
+                &modl
+            )
+            .is_ok());
+        assert!(server_txn.commit().is_ok());
+
+        let mut server_txn = server.write(duration_from_epoch_now()).await;
+        let admin = server_txn
+            .internal_search_uuid(&UUID_ADMIN)
+            .expect("failed");
+        let ce = CreateEvent::new_impersonate_identity(
+            Identity::from_impersonate_entry_readwrite(admin),
+            vec![entry_init!(
+                ("class", Value::new_class("object")),
+                ("class", Value::new_class("person")),
+                ("name", Value::new_iname("testperson2")),
+                ("description", Value::new_utf8s("testperson2")),
+                ("displayname", Value::new_utf8s("testperson2"))
+            )],
+        );
+        assert!(server_txn.create(&ce).is_ok());
+        assert!(server_txn.commit().is_ok());
+    }
+
+    #[qs_test]
+    async fn test_qs_domain_rename_plan(server: &QueryServer) {
+        let mut server_txn = server.write(duration_from_epoch_now()).await;
+        let old_domain_name = server_txn.get_db_domain_name().expect("no domain name");
+        let rs_uuid = Uuid::new_v4();
+        let other_uuid = Uuid::new_v4();
+        let rs = |uuid, name: &str, origin: &str| {
+            entry_init!(
+                ("class", Value::new_class("object")),
+                ("class", Value::new_class("oauth2_resource_server")),
+                ("class", Value::new_class("oauth2_resource_server_public")),
+                ("uuid", Value::new_uuid(uuid)),
+                ("oauth2_rs_name", Value::new_iname(name)),
+                ("displayname", Value::new_utf8s(name)),
+                (
+                    "oauth2_rs_origin",
+                    Value::new_url_s(origin).expect("invalid url")
+                )
+            )
+        };
+        assert!(server_txn
+            .internal_create(vec![
+                rs(
+                    rs_uuid,
+                    "test_rs",
+                    &format!("https://app.{}/oauth2", old_domain_name)
+                ),
+                // Only hosts in the domain move, not those that end with its name.
+                rs(
+                    other_uuid,
+                    "test_other",
+                    &format!("https://app.bad{}/", old_domain_name)
+                ),
+            ])
+            .is_ok());
+
+        let plan = server_txn
+            .domain_rename("renamed.example.net")
+            .expect("rename failed");
+        assert_eq!(plan.old_domain_name, old_domain_name);
+        assert!(plan.spn_entries > 0);
+        assert_eq!(plan.origins.len(), 1);
+        assert_eq!(plan.origins[0].uuid, rs_uuid);
+
+        let e = server_txn
+            .internal_search_uuid(&rs_uuid)
+            .expect("rs missing");
+        assert_eq!(
+            e.get_ava_single_url("oauth2_rs_origin").map(Url::as_str),
+            Some("https://app.renamed.example.net/oauth2")
+        );
+        assert_eq!(
+            server_txn.get_db_domain_name().expect("no domain name"),
+            "renamed.example.net"
+        );
+        assert!(server_txn.commit().is_ok());
+    }
 }
@@ -3,6 +3,7 @@
 use uuid::Uuid;
 
 use crate::prelude::*;
+use crate::schema::SchemaTransaction;
 
 pub struct StatusRequestEvent {
     pub eventid: Uuid,
@@ -23,3 +24,35 @@ impl StatusActor {
         true
     }
 }
+
+impl<'a> QueryServerReadTransaction<'a> {
+    /// Check that this server can serve requests. Unlike the status of the process, this fails
+    /// if the backend can't be read, the schema is inconsistent, or replication has failed in
+    /// a way that needs an administrator.
+    #[instrument(level = "debug", skip_all)]
+    pub fn ready_check(&self) -> Result<(), OperationError> {
+        self.internal_search_uuid(&UUID_DOMAIN_INFO).map_err(|e| {
+            admin_error!(?e, "Not ready, unable to read from the backend");
+            e
+        })?;
+
+        let schema_errs: Vec<_> = self
+            .get_schema()
+            .validate()
+            .into_iter()
+            .filter(Result::is_err)
+            .collect();
+        if !schema_errs.is_empty() {
+            admin_error!(?schema_errs, "Not ready, the schema is inconsistent");
+            return Err(OperationError::ConsistencyError(schema_errs));
+        }
+
+        let fatal = self.get_repl_status().fatal_agreements();
+        if !fatal.is_empty() {
+            admin_error!(agreements = ?fatal, "Not ready, replication requires an administrator");
+            return Err(OperationError::InvalidState);
+        }
+
+        Ok(())
+    }
+}
@@ -23,8 +23,10 @@ use uuid::Uuid;
 use webauthn_rs::prelude::{DeviceKey as DeviceKeyV4, Passkey as PasskeyV4};
 
 use crate::be::dbentry::DbIdentSpn;
+use crate::constants::IMAGE_MAX_DIMENSION;
 use crate::credential::Credential;
 use crate::identity::{AccessScope, IdentityId};
+use crate::redact::Redacted;
 use crate::repl::cid::Cid;
 
 lazy_static! {
@@ -60,9 +62,34 @@ lazy_static! {
         Regex::new("^[0-9a-zA-Z_]+$").expect("Invalid oauthscope regex found")
         // Must not contain whitespace.
     };
+    pub static ref PHONENUMBER_RE: Regex = {
+        #[allow(clippy::expect_used)]
+        Regex::new("^\\+[1-9][0-9]{1,14}$").expect("Invalid phonenumber regex found")
+        // E.164, a country code and subscriber number of at most 15 digits.
+    };
 }
 
-#[derive(Debug, Clone, PartialOrd, Ord, Eq, PartialEq, Hash)]
+/// Normalise a phone number to E.164, removing the separators people write it with. A leading
+/// 00 is the international prefix, and is replaced by +. Numbers without a country code can't
+/// be normalised, and are rejected.
+pub fn normalise_phonenumber(s: &str) -> Option<String> {
+    let n: String = s
+        .chars()
+        .filter(|c| !(c.is_whitespace() || matches!(c, '-' | '.' | '(' | ')' | '/')))
+        .collect();
+    let n = match n.strip_prefix("00") {
+        Some(rest) => format!("+{}", rest),
+        None => n,
+    };
+    if PHONENUMBER_RE.is_match(&n) {
+        Some(n)
+    } else {
+        None
+    }
+}
+
+#[derive(Debug, Clone, PartialOrd, Ord, Eq, PartialEq, Hash, Default, Deserialize, Serialize)]
+#[serde(default)]
 // https://openid.net/specs/openid-connect-core-1_0.html#AddressClaim
 pub struct Address {
     pub formatted: String,
@@ -74,6 +101,249 @@ pub struct Address {
     pub country: String,
 }
 
+impl Address {
+    /// Parse an address from the json of an OpenID Connect address claim. If the formatted
+    /// address is not given, it is composed from the components, one per line.
+    pub fn from_json_str(s: &str) -> Option<Self> {
+        let mut a: Address = serde_json::from_str(s)
+            .map_err(|e| {
+                trace!(?e, ?s);
+            })
+            .ok()?;
+        a.country = a.country.trim().to_uppercase();
+        if a.formatted.trim().is_empty() {
+            a.formatted = [
+                a.street_address.as_str(),
+                a.locality.as_str(),
+                a.region.as_str(),
+                a.postal_code.as_str(),
+                a.country.as_str(),
+            ]
+            .iter()
+            .map(|c| c.trim())
+            .filter(|c| !c.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n");
+        }
+        if a.validate() {
+            Some(a)
+        } else {
+            None
+        }
+    }
+
+    /// An address must have a formatted form, and the country, when given, must be an
+    /// ISO 3166-1 alpha-2 code.
+    pub fn validate(&self) -> bool {
+        !self.formatted.trim().is_empty()
+            && (self.country.is_empty()
+                || (self.country.len() == 2
+                    && self.country.chars().all(|c| c.is_ascii_uppercase())))
+    }
+
+    #[allow(clippy::expect_used)]
+    pub fn to_json_string(&self) -> String {
+        serde_json::to_string(self).expect("An address value was corrupted during run-time")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialOrd, Ord, Eq, PartialEq, Hash)]
+pub enum ImageType {
+    Png,
+    Jpeg,
+    Webp,
+}
+
+impl ImageType {
+    pub fn as_mime_str(&self) -> &'static str {
+        match self {
+            ImageType::Png => "image/png",
+            ImageType::Jpeg => "image/jpeg",
+            ImageType::Webp => "image/webp",
+        }
+    }
+}
+
+fn be_u16(b: &[u8], i: usize) -> Option<u32> {
+    b.get(i..i + 2)
+        .map(|s| u32::from(s[0]) << 8 | u32::from(s[1]))
+}
+
+fn le_u16(b: &[u8], i: usize) -> Option<u32> {
+    b.get(i..i + 2)
+        .map(|s| u32::from(s[1]) << 8 | u32::from(s[0]))
+}
+
+fn le_u24(b: &[u8], i: usize) -> Option<u32> {
+    b.get(i..i + 3)
+        .map(|s| u32::from(s[2]) << 16 | u32::from(s[1]) << 8 | u32::from(s[0]))
+}
+
+/// The width and height of a png, from its header chunk.
+fn png_dimensions(b: &[u8]) -> Option<(u32, u32)> {
+    if b.get(12..16)? != b"IHDR" {
+        return None;
+    }
+    let w = b.get(16..20)?;
+    let h = b.get(20..24)?;
+    Some((
+        u32::from_be_bytes([w[0], w[1], w[2], w[3]]),
+        u32::from_be_bytes([h[0], h[1], h[2], h[3]]),
+    ))
+}
+
+/// The width and height of a jpeg, from the first start of frame segment.
+fn jpeg_dimensions(b: &[u8]) -> Option<(u32, u32)> {
+    let mut i = 2;
+    loop {
+        // Segments start with a marker, which may be padded with fill bytes.
+        if *b.get(i)? != 0xff {
+            return None;
+        }
+        while *b.get(i)? == 0xff {
+            i += 1;
+        }
+        let marker = *b.get(i)?;
+        i += 1;
+        match marker {
+            // Markers that stand alone, without a length.
+            0x01 | 0xd0..=0xd7 => continue,
+            // Start of scan or end of image, without a frame.
+            0xda | 0xd9 => return None,
+            // Start of frame, except for DHT, JPG and DAC which share the range.
+            0xc0..=0xcf if !matches!(marker, 0xc4 | 0xc8 | 0xcc) => {
+                let h = be_u16(b, i + 3)?;
+                let w = be_u16(b, i + 5)?;
+                return Some((w, h));
+            }
+            _ => {
+                let len = be_u16(b, i)? as usize;
+                if len < 2 {
+                    return None;
+                }
+                i += len;
+            }
+        }
+    }
+}
+
+/// The width and height of a webp, from the header of its lossy, lossless or extended form.
+fn webp_dimensions(b: &[u8]) -> Option<(u32, u32)> {
+    match b.get(12..16)? {
+        b"VP8 " => {
+            if b.get(23..26)? != [0x9d, 0x01, 0x2a] {
+                return None;
+            }
+            Some((le_u16(b, 26)? & 0x3fff, le_u16(b, 28)? & 0x3fff))
+        }
+        b"VP8L" => {
+            if *b.get(20)? != 0x2f {
+                return None;
+            }
+            let s = b.get(21..25)?;
+            let w = 1 + (u32::from(s[0]) | (u32::from(s[1]) & 0x3f) << 8);
+            let h =
+                1 + (u32::from(s[1]) >> 6 | u32::from(s[2]) << 2 | (u32::from(s[3]) & 0x0f) << 10);
+            Some((w, h))
+        }
+        b"VP8X" => Some((1 + le_u24(b, 24)?, 1 + le_u24(b, 27)?)),
+        _ => None,
+    }
+}
+
+/// An image, such as the picture of a person or the logo of an oauth2 resource server. The
+/// format is found from the contents, which must be a png, jpeg or webp of at most
+/// [IMAGE_MAX_DIMENSION] pixels wide and high.
+#[derive(Clone, PartialOrd, Ord, Eq, PartialEq, Hash)]
+pub struct ImageValue {
+    pub filetype: ImageType,
+    pub contents: Vec<u8>,
+}
+
+impl fmt::Debug for ImageValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ImageValue")
+            .field("filetype", &self.filetype)
+            .field("len", &self.contents.len())
+            .finish()
+    }
+}
+
+impl ImageValue {
+    pub fn from_bytes(contents: Vec<u8>) -> Option<Self> {
+        let filetype = if contents.starts_with(b"\x89PNG\r\n\x1a\n") {
+            ImageType::Png
+        } else if contents.starts_with(&[0xff, 0xd8, 0xff]) {
+            ImageType::Jpeg
+        } else if contents.starts_with(b"RIFF") && contents.get(8..12) == Some(b"WEBP") {
+            ImageType::Webp
+        } else {
+            trace!("unknown image type");
+            return None;
+        };
+        let i = ImageValue { filetype, contents };
+        if i.validate() {
+            Some(i)
+        } else {
+            None
+        }
+    }
+
+    /// Parse an image from base64, or from a base64 data url as it is returned.
+    pub fn from_base64(s: &str) -> Option<Self> {
+        let s = s.trim();
+        let s = match s.strip_prefix("data:") {
+            Some(url) => url.split_once(";base64,")?.1,
+            None => s,
+        };
+        base64::decode(s)
+            .map_err(|e| {
+                trace!(?e, "invalid image base64");
+            })
+            .ok()
+            .and_then(ImageValue::from_bytes)
+    }
+
+    pub fn dimensions(&self) -> Option<(u32, u32)> {
+        match self.filetype {
+            ImageType::Png => png_dimensions(&self.contents),
+            ImageType::Jpeg => jpeg_dimensions(&self.contents),
+            ImageType::Webp => webp_dimensions(&self.contents),
+        }
+    }
+
+    /// An image must have a header that can be read, with dimensions that are within the limit.
+    pub fn validate(&self) -> bool {
+        match self.dimensions() {
+            Some((w, h)) => {
+                let valid = w > 0 && h > 0 && w <= IMAGE_MAX_DIMENSION && h <= IMAGE_MAX_DIMENSION;
+                if !valid {
+                    warn!(w, h, "image dimensions exceed {}", IMAGE_MAX_DIMENSION);
+                }
+                valid
+            }
+            None => {
+                warn!(filetype = ?self.filetype, "invalid image header");
+                false
+            }
+        }
+    }
+
+    /// The sha256 of the contents, which identifies the image in filters and modifications.
+    pub fn hash(&self) -> String {
+        hex::encode(openssl::sha::sha256(&self.contents))
+    }
+
+    /// The image as a data url, which can be shown by a browser as is.
+    pub fn to_data_url(&self) -> String {
+        format!(
+            "data:{};base64,{}",
+            self.filetype.as_mime_str(),
+            base64::encode(&self.contents)
+        )
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum IntentTokenState {
     Valid {
@@ -198,6 +468,10 @@ pub enum SyntaxType {
     JwsKeyRs256 = 27,
     Oauth2Session = 28,
     UiHint = 29,
+    Address = 30,
+    PhoneNumber = 31,
+    Binary = 32,
+    Image = 33,
 }
 
 impl TryFrom<&str> for SyntaxType {
@@ -237,6 +511,10 @@ impl TryFrom<&str> for SyntaxType {
             "JWS_KEY_RS256" => Ok(SyntaxType::JwsKeyRs256),
             "OAUTH2SESSION" => Ok(SyntaxType::Oauth2Session),
             "UIHINT" => Ok(SyntaxType::UiHint),
+            "ADDRESS" => Ok(SyntaxType::Address),
+            "PHONE_NUMBER" => Ok(SyntaxType::PhoneNumber),
+            "BINARY" => Ok(SyntaxType::Binary),
+            "IMAGE" => Ok(SyntaxType::Image),
             _ => Err(()),
         }
     }
@@ -275,6 +553,10 @@ impl fmt::Display for SyntaxType {
             SyntaxType::JwsKeyRs256 => "JWS_KEY_RS256",
             SyntaxType::Oauth2Session => "OAUTH2SESSION",
             SyntaxType::UiHint => "UIHINT",
+            SyntaxType::Address => "ADDRESS",
+            SyntaxType::PhoneNumber => "PHONE_NUMBER",
+            SyntaxType::Binary => "BINARY",
+            SyntaxType::Image => "IMAGE",
         })
     }
 }
@@ -285,7 +567,7 @@ impl fmt::Display for SyntaxType {
 /// A partialValue is typically used when you need to match against a value, but without
 /// requiring all of it's data or expression. This is common in Filters or other direct
 /// lookups and requests.
-#[derive(Hash, Debug, Clone, Eq, Ord, PartialOrd, PartialEq, Deserialize, Serialize)]
+#[derive(Hash, Clone, Eq, Ord, PartialOrd, PartialEq, Deserialize, Serialize)]
 pub enum PartialValue {
     Utf8(String),
     Iutf8(String),
@@ -316,6 +598,9 @@ pub enum PartialValue {
     // OauthScopeMap(Uuid),
     PrivateBinary,
     PublicBinary(String),
+    Binary(Vec<u8>),
+    // The hash of the image contents.
+    Image(String),
     // Enumeration(String),
     // Float64(f64),
     RestrictedString(String),
@@ -329,6 +614,54 @@ pub enum PartialValue {
     Session(Uuid),
 }
 
+impl fmt::Debug for PartialValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PartialValue::Utf8(s) => f.debug_tuple("Utf8").field(s).finish(),
+            PartialValue::Iutf8(s) => f.debug_tuple("Iutf8").field(s).finish(),
+            PartialValue::Iname(s) => f.debug_tuple("Iname").field(s).finish(),
+            PartialValue::Uuid(u) => f.debug_tuple("Uuid").field(u).finish(),
+            PartialValue::Bool(b) => f.debug_tuple("Bool").field(b).finish(),
+            PartialValue::Syntax(s) => f.debug_tuple("Syntax").field(s).finish(),
+            PartialValue::Index(i) => f.debug_tuple("Index").field(i).finish(),
+            PartialValue::Refer(u) => f.debug_tuple("Refer").field(u).finish(),
+            PartialValue::JsonFilt(filt) => f.debug_tuple("JsonFilt").field(filt).finish(),
+            PartialValue::Cred(tag) => f.debug_tuple("Cred").field(tag).finish(),
+            PartialValue::SshKey(tag) => f.debug_tuple("SshKey").field(tag).finish(),
+            PartialValue::SecretValue => f.write_str("SecretValue"),
+            PartialValue::Spn(n, r) => f.debug_tuple("Spn").field(n).field(r).finish(),
+            PartialValue::Uint32(i) => f.debug_tuple("Uint32").field(i).finish(),
+            PartialValue::Cid(c) => f.debug_tuple("Cid").field(c).finish(),
+            PartialValue::Nsuniqueid(s) => f.debug_tuple("Nsuniqueid").field(s).finish(),
+            PartialValue::DateTime(t) => f.debug_tuple("DateTime").field(t).finish(),
+            PartialValue::EmailAddress(s) => f.debug_tuple("EmailAddress").field(s).finish(),
+            PartialValue::PhoneNumber(s) => f.debug_tuple("PhoneNumber").field(s).finish(),
+            PartialValue::Address(s) => f.debug_tuple("Address").field(s).finish(),
+            PartialValue::Url(u) => f.debug_tuple("Url").field(u).finish(),
+            PartialValue::OauthScope(s) => f.debug_tuple("OauthScope").field(s).finish(),
+            PartialValue::PrivateBinary => f.write_str("PrivateBinary"),
+            PartialValue::PublicBinary(tag) => f.debug_tuple("PublicBinary").field(tag).finish(),
+            PartialValue::Binary(b) => f.debug_tuple("Binary").field(b).finish(),
+            PartialValue::Image(h) => f.debug_tuple("Image").field(h).finish(),
+            PartialValue::RestrictedString(s) => {
+                f.debug_tuple("RestrictedString").field(s).finish()
+            }
+            // The id of an intent token is what the user presents to redeem it.
+            PartialValue::IntentToken(id) => f
+                .debug_tuple("IntentToken")
+                .field(&Redacted::new(id))
+                .finish(),
+            PartialValue::UiHint(h) => f.debug_tuple("UiHint").field(h).finish(),
+            PartialValue::Passkey(u) => f.debug_tuple("Passkey").field(u).finish(),
+            PartialValue::DeviceKey(u) => f.debug_tuple("DeviceKey").field(u).finish(),
+            PartialValue::TrustedDeviceEnrollment(u) => {
+                f.debug_tuple("TrustedDeviceEnrollment").field(u).finish()
+            }
+            PartialValue::Session(u) => f.debug_tuple("Session").field(u).finish(),
+        }
+    }
+}
+
 impl From<SyntaxType> for PartialValue {
     fn from(s: SyntaxType) -> Self {
         PartialValue::Syntax(s)
@@ -588,12 +921,17 @@ impl PartialValue {
         matches!(self, PartialValue::EmailAddress(_))
     }
 
-    pub fn new_phonenumber_s(s: &str) -> Self {
-        PartialValue::PhoneNumber(s.to_string())
+    pub fn new_phonenumber_s(s: &str) -> Option<Self> {
+        normalise_phonenumber(s).map(PartialValue::PhoneNumber)
     }
 
+    /// An address is matched on its formatted form. The json of an address is accepted too, so
+    /// that an address read from an entry can be given back to remove it.
     pub fn new_address(s: &str) -> Self {
-        PartialValue::Address(s.to_string())
+        match Address::from_json_str(s) {
+            Some(a) => PartialValue::Address(a.formatted),
+            None => PartialValue::Address(s.to_string()),
+        }
     }
 
     pub fn new_url_s(s: &str) -> Option<Self> {
@@ -637,6 +975,19 @@ impl PartialValue {
         PartialValue::PublicBinary(s.to_string())
     }
 
+    pub fn new_binary_base64(s: &str) -> Option<Self> {
+        base64::decode(s).map(PartialValue::Binary).ok()
+    }
+
+    /// An image is matched by the hash of its contents. The image itself is accepted too, so
+    /// that an image read from an entry can be given back to remove it.
+    pub fn new_image_s(s: &str) -> Self {
+        match ImageValue::from_base64(s) {
+            Some(i) => PartialValue::Image(i.hash()),
+            None => PartialValue::Image(s.trim().to_lowercase()),
+        }
+    }
+
     pub fn new_restrictedstring_s(s: &str) -> Self {
         PartialValue::RestrictedString(s.to_string())
     }
@@ -706,6 +1057,8 @@ impl PartialValue {
             PartialValue::OauthScope(u) => u.to_string(),
             PartialValue::Address(a) => a.to_string(),
             PartialValue::PhoneNumber(a) => a.to_string(),
+            PartialValue::Binary(b) => base64::encode(b),
+            PartialValue::Image(h) => h.clone(),
             PartialValue::IntentToken(u) => u.clone(),
             PartialValue::TrustedDeviceEnrollment(u) => u.as_hyphenated().to_string(),
             PartialValue::Session(u) => u.as_hyphenated().to_string(),
@@ -734,6 +1087,8 @@ pub struct Oauth2Session {
     pub expiry: Option<OffsetDateTime>,
     pub issued_at: OffsetDateTime,
     pub rs_uuid: Uuid,
+    // The oauth2 session this session was derived from by token exchange, if any.
+    pub delegated_from: Option<Uuid>,
 }
 
 /// A value is a complete unit of data for an attribute. It is made up of a PartialValue, which is
@@ -742,7 +1097,7 @@ pub struct Oauth2Session {
 ///
 /// This type is used when you need the "full data" of an attribute. Typically this is in a create
 /// or modification operation where you are applying a set of complete values into an entry.
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub enum Value {
     Utf8(String),
     // Case insensitive string
@@ -771,6 +1126,8 @@ pub enum Value {
     OauthScopeMap(Uuid, BTreeSet<String>),
     PrivateBinary(Vec<u8>),
     PublicBinary(String, Vec<u8>),
+    Binary(Vec<u8>),
+    Image(ImageValue),
     // Enumeration(String),
     // Float64(f64),
     RestrictedString(String),
@@ -787,6 +1144,94 @@ pub enum Value {
     UiHint(UiHint),
 }
 
+/// The secrets of credentials, sessions, intent tokens and keys are [Redacted], while their
+/// tags and uuids are shown.
+impl fmt::Debug for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Utf8(s) => f.debug_tuple("Utf8").field(s).finish(),
+            Value::Iutf8(s) => f.debug_tuple("Iutf8").field(s).finish(),
+            Value::Iname(s) => f.debug_tuple("Iname").field(s).finish(),
+            Value::Uuid(u) => f.debug_tuple("Uuid").field(u).finish(),
+            Value::Bool(b) => f.debug_tuple("Bool").field(b).finish(),
+            Value::Syntax(s) => f.debug_tuple("Syntax").field(s).finish(),
+            Value::Index(i) => f.debug_tuple("Index").field(i).finish(),
+            Value::Refer(u) => f.debug_tuple("Refer").field(u).finish(),
+            Value::JsonFilt(filt) => f.debug_tuple("JsonFilt").field(filt).finish(),
+            Value::Cred(tag, cred) => f
+                .debug_tuple("Cred")
+                .field(tag)
+                .field(&Redacted::new(cred))
+                .finish(),
+            Value::SshKey(tag, key) => f.debug_tuple("SshKey").field(tag).field(key).finish(),
+            Value::SecretValue(s) => f
+                .debug_tuple("SecretValue")
+                .field(&Redacted::new(s))
+                .finish(),
+            Value::Spn(n, r) => f.debug_tuple("Spn").field(n).field(r).finish(),
+            Value::Uint32(i) => f.debug_tuple("Uint32").field(i).finish(),
+            Value::Cid(c) => f.debug_tuple("Cid").field(c).finish(),
+            Value::Nsuniqueid(s) => f.debug_tuple("Nsuniqueid").field(s).finish(),
+            Value::DateTime(t) => f.debug_tuple("DateTime").field(t).finish(),
+            Value::EmailAddress(s, p) => f.debug_tuple("EmailAddress").field(s).field(p).finish(),
+            Value::PhoneNumber(s, p) => f.debug_tuple("PhoneNumber").field(s).field(p).finish(),
+            Value::Address(a) => f.debug_tuple("Address").field(a).finish(),
+            Value::Url(u) => f.debug_tuple("Url").field(u).finish(),
+            Value::OauthScope(s) => f.debug_tuple("OauthScope").field(s).finish(),
+            Value::OauthScopeMap(u, m) => f.debug_tuple("OauthScopeMap").field(u).field(m).finish(),
+            Value::PrivateBinary(b) => f
+                .debug_tuple("PrivateBinary")
+                .field(&Redacted::new(b))
+                .finish(),
+            Value::PublicBinary(tag, b) => {
+                f.debug_tuple("PublicBinary").field(tag).field(b).finish()
+            }
+            Value::Binary(b) => f.debug_tuple("Binary").field(b).finish(),
+            Value::Image(i) => f.debug_tuple("Image").field(i).finish(),
+            Value::RestrictedString(s) => f.debug_tuple("RestrictedString").field(s).finish(),
+            Value::IntentToken(id, state) => f
+                .debug_tuple("IntentToken")
+                .field(&Redacted::new(id))
+                .field(state)
+                .finish(),
+            Value::Passkey(u, tag, pk) => f
+                .debug_tuple("Passkey")
+                .field(u)
+                .field(tag)
+                .field(&Redacted::new(pk))
+                .finish(),
+            Value::DeviceKey(u, tag, dk) => f
+                .debug_tuple("DeviceKey")
+                .field(u)
+                .field(tag)
+                .field(&Redacted::new(dk))
+                .finish(),
+            Value::TrustedDeviceEnrollment(u) => {
+                f.debug_tuple("TrustedDeviceEnrollment").field(u).finish()
+            }
+            Value::Session(u, s) => f
+                .debug_tuple("Session")
+                .field(u)
+                .field(&Redacted::new(s))
+                .finish(),
+            Value::Oauth2Session(u, s) => f
+                .debug_tuple("Oauth2Session")
+                .field(u)
+                .field(&Redacted::new(s))
+                .finish(),
+            Value::JwsKeyEs256(k) => f
+                .debug_tuple("JwsKeyEs256")
+                .field(&Redacted::new(k))
+                .finish(),
+            Value::JwsKeyRs256(k) => f
+                .debug_tuple("JwsKeyRs256")
+                .field(&Redacted::new(k))
+                .finish(),
+            Value::UiHint(h) => f.debug_tuple("UiHint").field(h).finish(),
+        }
+    }
+}
+
 impl PartialEq for Value {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
@@ -822,9 +1267,14 @@ impl PartialEq for Value {
             (Value::Url(a), Value::Url(b)) => a.eq(b),
             // OauthScopeMap
             (Value::OauthScopeMap(a, c), Value::OauthScopeMap(b, d)) => a.eq(b) && c.eq(d),
-
-            (Value::Address(_), Value::Address(_))
-            | (Value::PrivateBinary(_), Value::PrivateBinary(_))
+            // Address
+            (Value::Address(a), Value::Address(b)) => a.eq(b),
+            // Binary
+            (Value::Binary(a), Value::Binary(b)) => a.eq(b),
+            // Image
+            (Value::Image(a), Value::Image(b)) => a.eq(b),
+
+            (Value::PrivateBinary(_), Value::PrivateBinary(_))
             | (Value::SecretValue(_), Value::SecretValue(_)) => false,
             // Specifically related to migrations, we allow the invalid comparison.
             (Value::Iutf8(_), Value::Iname(_)) | (Value::Iname(_), Value::Iutf8(_)) => false,
@@ -1207,14 +1657,26 @@ impl Value {
         matches!(&self, Value::EmailAddress(_, _))
     }
 
-    pub fn new_phonenumber_s(s: &str) -> Self {
-        Value::PhoneNumber(s.to_string(), false)
+    pub fn new_phonenumber_s(s: &str) -> Option<Self> {
+        normalise_phonenumber(s).map(|n| Value::PhoneNumber(n, false))
+    }
+
+    pub fn new_phonenumber_primary_s(s: &str) -> Option<Self> {
+        normalise_phonenumber(s).map(|n| Value::PhoneNumber(n, true))
+    }
+
+    pub fn is_phonenumber(&self) -> bool {
+        matches!(&self, Value::PhoneNumber(_, _))
     }
 
     pub fn new_address(a: Address) -> Self {
         Value::Address(a)
     }
 
+    pub fn new_address_s(s: &str) -> Option<Self> {
+        Address::from_json_str(s).map(Value::Address)
+    }
+
     pub fn new_url_s(s: &str) -> Option<Self> {
         Url::parse(s).ok().map(Value::Url)
     }
@@ -1276,6 +1738,42 @@ impl Value {
         Value::PublicBinary(tag, der)
     }
 
+    pub fn new_binary(b: Vec<u8>) -> Self {
+        Value::Binary(b)
+    }
+
+    /// Binary values are given and returned as base64.
+    pub fn new_binary_base64(s: &str) -> Option<Self> {
+        base64::decode(s).map(Value::Binary).ok()
+    }
+
+    pub fn to_binary(&self) -> Option<&[u8]> {
+        match &self {
+            Value::Binary(b) => Some(b.as_slice()),
+            _ => None,
+        }
+    }
+
+    pub fn is_binary(&self) -> bool {
+        matches!(&self, Value::Binary(_))
+    }
+
+    pub fn new_image(i: ImageValue) -> Self {
+        Value::Image(i)
+    }
+
+    /// Images are given as base64, or as the base64 data url that they are returned as.
+    pub fn new_image_s(s: &str) -> Option<Self> {
+        ImageValue::from_base64(s).map(Value::Image)
+    }
+
+    pub fn to_image(&self) -> Option<&ImageValue> {
+        match &self {
+            Value::Image(i) => Some(i),
+            _ => None,
+        }
+    }
+
     pub fn new_restrictedstring(s: String) -> Self {
         Value::RestrictedString(s)
     }
@@ -1548,6 +2046,9 @@ impl Value {
             Value::Nsuniqueid(s) => NSUNIQUEID_RE.is_match(s),
             Value::DateTime(odt) => odt.offset() == time::UtcOffset::UTC,
             Value::EmailAddress(mail, _) => validator::validate_email(mail.as_str()),
+            Value::PhoneNumber(n, _) => PHONENUMBER_RE.is_match(n),
+            Value::Address(a) => a.validate(),
+            Value::Image(i) => i.validate(),
             // PartialValue::Url validated through parsing.
             Value::OauthScope(s) => OAUTHSCOPE_RE.is_match(s),
             Value::OauthScopeMap(_, m) => m.iter().all(|s| OAUTHSCOPE_RE.is_match(s)),
@@ -1820,6 +2321,8 @@ mod tests {
             ),
             multivalue: false,
             index: vec![IndexType::Equality, IndexType::SubString],
+            encrypted: false,
+            max_size: None,
             syntax: SyntaxType::JSON_FILTER,
         };
 
@@ -1853,6 +2356,8 @@ mod tests {
             description: String::from("The universal unique id of the object"),
             multivalue: false,
             index: vec![IndexType::Equality],
+            encrypted: false,
+            max_size: None,
             syntax: SyntaxType::UUID,
         };
         let u1 = String::from("936DA01F9ABD4d9d80C702AF85C822A8");
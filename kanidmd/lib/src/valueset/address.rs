@@ -5,7 +5,7 @@ use smolset::SmolSet;
 use crate::be::dbvalue::DbValueAddressV1;
 use crate::prelude::*;
 use crate::schema::SchemaAttribute;
-use crate::value::Address;
+use crate::value::{Address, PHONENUMBER_RE};
 use crate::valueset::{DbValueSetV2, ValueSet};
 
 #[derive(Debug, Clone)]
@@ -78,8 +78,11 @@ impl ValueSetT for ValueSetAddress {
 
     fn remove(&mut self, pv: &PartialValue) -> bool {
         match pv {
-            PartialValue::Address(_) => {
-                unreachable!()
+            PartialValue::Address(formatted) => {
+                match self.set.iter().find(|a| &a.formatted == formatted).cloned() {
+                    Some(a) => self.set.remove(&a),
+                    None => false,
+                }
             }
             _ => {
                 debug_assert!(false);
@@ -90,9 +93,7 @@ impl ValueSetT for ValueSetAddress {
 
     fn contains(&self, pv: &PartialValue) -> bool {
         match pv {
-            PartialValue::Address(_) => {
-                unreachable!()
-            }
+            PartialValue::Address(formatted) => self.set.iter().any(|a| &a.formatted == formatted),
             _ => false,
         }
     }
@@ -101,29 +102,41 @@ impl ValueSetT for ValueSetAddress {
         false
     }
 
+    fn startswith(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
+    fn endswith(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
     fn lessthan(&self, _pv: &PartialValue) -> bool {
         false
     }
 
+    fn greaterthan(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
     fn len(&self) -> usize {
         self.set.len()
     }
 
     fn generate_idx_eq_keys(&self) -> Vec<String> {
-        unreachable!();
-        // self.set.iter().map(|b| b.to_string()).collect()
+        self.set.iter().map(|a| a.formatted.clone()).collect()
     }
 
     fn syntax(&self) -> SyntaxType {
-        unreachable!();
+        SyntaxType::Address
     }
 
     fn validate(&self, _schema_attr: &SchemaAttribute) -> bool {
-        true
+        self.set.iter().all(|a| a.validate())
     }
 
     fn to_proto_string_clone_iter(&self) -> Box<dyn Iterator<Item = String> + '_> {
-        Box::new(self.set.iter().map(|a| a.formatted.clone()))
+        // The json keeps the components, so that they are not lost to clients.
+        Box::new(self.set.iter().map(|a| a.to_json_string()))
     }
 
     fn to_db_valueset_v2(&self) -> DbValueSetV2 {
@@ -294,10 +307,22 @@ impl ValueSetT for ValueSetEmailAddress {
         false
     }
 
+    fn startswith(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
+    fn endswith(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
     fn lessthan(&self, _pv: &PartialValue) -> bool {
         false
     }
 
+    fn greaterthan(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
     fn len(&self) -> usize {
         self.set.len()
     }
@@ -376,18 +401,168 @@ impl ValueSetT for ValueSetEmailAddress {
     }
 }
 
-/*
 #[derive(Debug, Clone)]
 pub struct ValueSetPhoneNumber {
     primary: String,
     set: BTreeSet<String>,
 }
-*/
+
+impl ValueSetPhoneNumber {
+    pub fn new(primary: String) -> Box<Self> {
+        let mut set = BTreeSet::new();
+        set.insert(primary.clone());
+        Box::new(ValueSetPhoneNumber { primary, set })
+    }
+
+    pub fn push(&mut self, n: String, primary: bool) -> bool {
+        if primary {
+            self.primary = n.clone();
+        }
+        self.set.insert(n)
+    }
+
+    pub fn from_dbvs2(primary: String, data: Vec<String>) -> Result<ValueSet, OperationError> {
+        let set: BTreeSet<_> = data.into_iter().collect();
+
+        if set.contains(&primary) {
+            Ok(Box::new(ValueSetPhoneNumber { primary, set }))
+        } else {
+            Err(OperationError::InvalidValueState)
+        }
+    }
+}
+
+impl ValueSetT for ValueSetPhoneNumber {
+    fn insert_checked(&mut self, value: Value) -> Result<bool, OperationError> {
+        match value {
+            Value::PhoneNumber(n, p) => {
+                // if the set was empty, we need to force update primary.
+                if p || self.set.is_empty() {
+                    self.primary = n.clone();
+                }
+                Ok(self.set.insert(n))
+            }
+            _ => {
+                debug_assert!(false);
+                Err(OperationError::InvalidValueState)
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        self.set.clear();
+    }
+
+    fn remove(&mut self, pv: &PartialValue) -> bool {
+        match pv {
+            PartialValue::PhoneNumber(n) => {
+                let r = self.set.remove(n);
+                if &self.primary == n {
+                    // if we can, inject another former number into primary.
+                    if let Some(next) = self.set.iter().next().cloned() {
+                        self.primary = next
+                    }
+                }
+                r
+            }
+            _ => false,
+        }
+    }
+
+    fn contains(&self, pv: &PartialValue) -> bool {
+        match pv {
+            PartialValue::PhoneNumber(n) => self.set.contains(n),
+            _ => false,
+        }
+    }
+
+    fn substring(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
+    fn startswith(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
+    fn endswith(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
+    fn lessthan(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
+    fn greaterthan(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
+    fn len(&self) -> usize {
+        self.set.len()
+    }
+
+    fn generate_idx_eq_keys(&self) -> Vec<String> {
+        self.set.iter().cloned().collect()
+    }
+
+    fn syntax(&self) -> SyntaxType {
+        SyntaxType::PhoneNumber
+    }
+
+    fn validate(&self, _schema_attr: &SchemaAttribute) -> bool {
+        self.set.contains(&self.primary)
+            && self.set.iter().all(|n| PHONENUMBER_RE.is_match(n.as_str()))
+    }
+
+    fn to_proto_string_clone_iter(&self) -> Box<dyn Iterator<Item = String> + '_> {
+        Box::new(self.set.iter().cloned())
+    }
+
+    fn to_db_valueset_v2(&self) -> DbValueSetV2 {
+        DbValueSetV2::PhoneNumber(self.primary.clone(), self.set.iter().cloned().collect())
+    }
+
+    fn to_partialvalue_iter(&self) -> Box<dyn Iterator<Item = PartialValue> + '_> {
+        Box::new(self.set.iter().cloned().map(PartialValue::PhoneNumber))
+    }
+
+    fn to_value_iter(&self) -> Box<dyn Iterator<Item = Value> + '_> {
+        Box::new(self.set.iter().cloned().map(|n| {
+            let p = n == self.primary;
+            Value::PhoneNumber(n, p)
+        }))
+    }
+
+    fn equal(&self, other: &ValueSet) -> bool {
+        if let Some((p_b, set_b)) = other.as_phonenumber_set() {
+            &self.set == set_b && &self.primary == p_b
+        } else {
+            debug_assert!(false);
+            false
+        }
+    }
+
+    fn merge(&mut self, other: &ValueSet) -> Result<(), OperationError> {
+        if let Some((_p, set_b)) = other.as_phonenumber_set() {
+            mergesets!(self.set, set_b)
+        } else {
+            debug_assert!(false);
+            Err(OperationError::InvalidValueState)
+        }
+    }
+
+    fn as_phonenumber_set(&self) -> Option<(&String, &BTreeSet<String>)> {
+        if self.set.is_empty() {
+            None
+        } else {
+            Some((&self.primary, &self.set))
+        }
+    }
+}
 
 #[cfg(test)]
 mod tests {
-    use super::ValueSetEmailAddress;
-    use crate::value::{PartialValue, Value};
+    use super::{ValueSetAddress, ValueSetEmailAddress, ValueSetPhoneNumber};
+    use crate::value::{Address, PartialValue, Value};
     use crate::valueset::{self, ValueSet};
 
     #[test]
@@ -450,4 +625,73 @@ mod tests {
         assert!(vs.len() == 0);
         assert!(vs.to_email_address_primary_str().is_none());
     }
+
+    #[test]
+    fn test_valueset_phonenumber() {
+        // Numbers are normalised to E.164.
+        assert!(Value::new_phonenumber_s("07 3000 0000").is_none());
+        assert!(
+            Value::new_phonenumber_s("0061 7 3000 0000")
+                == Value::new_phonenumber_s("+61-7-3000-0000")
+        );
+        assert!(
+            Value::new_phonenumber_s("+61 7 3000 0000").and_then(|v| v.to_phonenumber())
+                == Some("+61730000000".to_string())
+        );
+
+        let mut vs: ValueSet = ValueSetPhoneNumber::new("+61730000000".to_string());
+        assert!(
+            vs.insert_checked(
+                Value::new_phonenumber_primary_s("+1 555 010 0000").expect("Invalid Number")
+            ) == Ok(true)
+        );
+        assert!(vs.len() == 2);
+        assert!(vs.contains(
+            &PartialValue::new_phonenumber_s("+61 7 3000 0000").expect("Invalid Number")
+        ));
+
+        // Restore from dbv2, the primary persisted.
+        let vs2 = valueset::from_db_valueset_v2(vs.to_db_valueset_v2())
+            .expect("Failed to construct vs2 from dbvalue");
+        assert!(&vs == &vs2);
+
+        // Remove the primary, the other number is promoted.
+        assert!(
+            vs.remove(&PartialValue::new_phonenumber_s("+15550100000").expect("Invalid Number"))
+        );
+        assert!(vs.len() == 1);
+        assert!(vs.as_phonenumber_set().map(|(p, _)| p.as_str()) == Some("+61730000000"));
+    }
+
+    #[test]
+    fn test_valueset_address() {
+        // The formatted address is composed when it is not given.
+        let a = Address::from_json_str(
+            r#"{"street_address": "1 Example St", "locality": "Brisbane", "region": "QLD", "postal_code": "4000", "country": "au"}"#,
+        )
+        .expect("Invalid Address");
+        assert!(a.formatted == "1 Example St\nBrisbane\nQLD\n4000\nAU");
+
+        // The country must be an ISO 3166-1 alpha-2 code.
+        assert!(
+            Address::from_json_str(r#"{"formatted": "Somewhere", "country": "Australia"}"#)
+                .is_none()
+        );
+        assert!(Address::from_json_str(r#"{"country": "AU"}"#).is_some());
+        assert!(Address::from_json_str("{}").is_none());
+
+        let mut vs: ValueSet = ValueSetAddress::new(a.clone());
+        assert!(vs.contains(&PartialValue::new_address(&a.formatted)));
+
+        // The components survive the proto and the db.
+        let proto: Vec<_> = vs.to_proto_string_clone_iter().collect();
+        assert!(Address::from_json_str(&proto[0]) == Some(a.clone()));
+        let vs2 = valueset::from_db_valueset_v2(vs.to_db_valueset_v2())
+            .expect("Failed to construct vs2 from dbvalue");
+        assert!(&vs == &vs2);
+
+        // An address read from an entry can be given back to remove it.
+        assert!(vs.remove(&PartialValue::new_address(&proto[0])));
+        assert!(vs.is_empty());
+    }
 }
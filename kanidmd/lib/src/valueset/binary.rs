@@ -4,14 +4,23 @@ use std::collections::BTreeMap;
 use smolset::SmolSet;
 
 use crate::prelude::*;
+use crate::redact::redact_all;
 use crate::schema::SchemaAttribute;
 use crate::valueset::{DbValueSetV2, ValueSet};
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct ValueSetPrivateBinary {
     set: SmolSet<[Vec<u8>; 1]>,
 }
 
+impl std::fmt::Debug for ValueSetPrivateBinary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ValueSetPrivateBinary")
+            .field("set", &redact_all(self.set.iter()))
+            .finish()
+    }
+}
+
 impl ValueSetPrivateBinary {
     pub fn new(b: Vec<u8>) -> Box<Self> {
         let mut set = SmolSet::new();
@@ -67,10 +76,22 @@ impl ValueSetT for ValueSetPrivateBinary {
         false
     }
 
+    fn startswith(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
+    fn endswith(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
     fn lessthan(&self, _pv: &PartialValue) -> bool {
         false
     }
 
+    fn greaterthan(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
     fn len(&self) -> usize {
         self.set.len()
     }
@@ -139,6 +160,169 @@ impl ValueSetT for ValueSetPrivateBinary {
     }
 }
 
+/// Binary values that are not secret, such as certificate requests. They are given and returned
+/// as base64, and their size is bounded by the max_size of their attribute.
+#[derive(Debug, Clone)]
+pub struct ValueSetBinary {
+    set: SmolSet<[Vec<u8>; 1]>,
+}
+
+impl ValueSetBinary {
+    pub fn new(b: Vec<u8>) -> Box<Self> {
+        let mut set = SmolSet::new();
+        set.insert(b);
+        Box::new(ValueSetBinary { set })
+    }
+
+    pub fn push(&mut self, b: Vec<u8>) -> bool {
+        self.set.insert(b)
+    }
+
+    pub fn from_dbvs2(data: Vec<Vec<u8>>) -> Result<ValueSet, OperationError> {
+        let set = data.into_iter().collect();
+        Ok(Box::new(ValueSetBinary { set }))
+    }
+
+    // We need to allow this, because rust doesn't allow us to impl FromIterator on foreign
+    // types, and vec is foreign
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_iter<T>(iter: T) -> Option<Box<ValueSetBinary>>
+    where
+        T: IntoIterator<Item = Vec<u8>>,
+    {
+        let set = iter.into_iter().collect();
+        Some(Box::new(ValueSetBinary { set }))
+    }
+}
+
+impl ValueSetT for ValueSetBinary {
+    fn insert_checked(&mut self, value: Value) -> Result<bool, OperationError> {
+        match value {
+            Value::Binary(b) => Ok(self.set.insert(b)),
+            _ => {
+                debug_assert!(false);
+                Err(OperationError::InvalidValueState)
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        self.set.clear();
+    }
+
+    fn remove(&mut self, pv: &PartialValue) -> bool {
+        match pv {
+            PartialValue::Binary(b) => self.set.remove(b),
+            _ => {
+                debug_assert!(false);
+                true
+            }
+        }
+    }
+
+    fn contains(&self, pv: &PartialValue) -> bool {
+        match pv {
+            PartialValue::Binary(b) => self.set.contains(b),
+            _ => false,
+        }
+    }
+
+    fn substring(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
+    fn startswith(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
+    fn endswith(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
+    fn lessthan(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
+    fn greaterthan(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
+    fn len(&self) -> usize {
+        self.set.len()
+    }
+
+    fn generate_idx_eq_keys(&self) -> Vec<String> {
+        self.set.iter().map(base64::encode).collect()
+    }
+
+    fn syntax(&self) -> SyntaxType {
+        SyntaxType::Binary
+    }
+
+    fn validate(&self, schema_attr: &SchemaAttribute) -> bool {
+        let max_size = schema_attr.max_size.unwrap_or(BINARY_MAX_SIZE_DEFAULT) as usize;
+        self.set.iter().all(|b| {
+            if b.len() > max_size {
+                admin_warn!(
+                    attr = %schema_attr.name,
+                    size = b.len(),
+                    max_size,
+                    "binary value exceeds the max_size of its attribute"
+                );
+                false
+            } else {
+                true
+            }
+        })
+    }
+
+    fn to_proto_string_clone_iter(&self) -> Box<dyn Iterator<Item = String> + '_> {
+        Box::new(self.set.iter().map(base64::encode))
+    }
+
+    fn to_db_valueset_v2(&self) -> DbValueSetV2 {
+        DbValueSetV2::Binary(self.set.iter().cloned().collect())
+    }
+
+    fn to_partialvalue_iter(&self) -> Box<dyn Iterator<Item = PartialValue> + '_> {
+        Box::new(self.set.iter().cloned().map(PartialValue::Binary))
+    }
+
+    fn to_value_iter(&self) -> Box<dyn Iterator<Item = Value> + '_> {
+        Box::new(self.set.iter().cloned().map(Value::Binary))
+    }
+
+    fn equal(&self, other: &ValueSet) -> bool {
+        if let Some(other) = other.as_binary_set() {
+            &self.set == other
+        } else {
+            debug_assert!(false);
+            false
+        }
+    }
+
+    fn merge(&mut self, other: &ValueSet) -> Result<(), OperationError> {
+        if let Some(b) = other.as_binary_set() {
+            mergesets!(self.set, b)
+        } else {
+            debug_assert!(false);
+            Err(OperationError::InvalidValueState)
+        }
+    }
+
+    fn to_binary_single(&self) -> Option<&[u8]> {
+        if self.set.len() == 1 {
+            self.set.iter().map(|b| b.as_slice()).take(1).next()
+        } else {
+            None
+        }
+    }
+
+    fn as_binary_set(&self) -> Option<&SmolSet<[Vec<u8>; 1]>> {
+        Some(&self.set)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ValueSetPublicBinary {
     map: BTreeMap<String, Vec<u8>>,
@@ -209,10 +393,22 @@ impl ValueSetT for ValueSetPublicBinary {
         false
     }
 
+    fn startswith(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
+    fn endswith(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
     fn lessthan(&self, _pv: &PartialValue) -> bool {
         false
     }
 
+    fn greaterthan(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
     fn len(&self) -> usize {
         self.map.len()
     }
@@ -277,3 +473,49 @@ impl ValueSetT for ValueSetPublicBinary {
         Some(&self.map)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::ValueSetBinary;
+    use crate::prelude::*;
+    use crate::schema::SchemaAttribute;
+
+    #[test]
+    fn test_valueset_binary_max_size() {
+        let mut schema_attr = SchemaAttribute {
+            name: AttrString::from("test_binary"),
+            uuid: Uuid::new_v4(),
+            description: String::new(),
+            multivalue: true,
+            unique: false,
+            phantom: false,
+            sync_allowed: false,
+            index: Vec::new(),
+            encrypted: false,
+            max_size: Some(4),
+            syntax: SyntaxType::Binary,
+        };
+
+        let mut vs: ValueSet = ValueSetBinary::new(vec![0, 1, 2, 3]);
+        assert!(vs.validate(&schema_attr));
+
+        assert!(vs.insert_checked(Value::new_binary(vec![0, 1, 2, 3, 4])) == Ok(true));
+        assert!(!vs.validate(&schema_attr));
+
+        // Without a max_size, the default applies.
+        schema_attr.max_size = None;
+        assert!(vs.validate(&schema_attr));
+        assert!(vs
+            .insert_checked(Value::new_binary(vec![
+                0;
+                BINARY_MAX_SIZE_DEFAULT as usize + 1
+            ]))
+            .is_ok());
+        assert!(!vs.validate(&schema_attr));
+
+        // Values are given and returned as base64.
+        let pv = PartialValue::new_binary_base64("AAECAwQ=").expect("Invalid base64");
+        assert!(vs.contains(&pv));
+        assert!(vs.to_proto_string_clone_iter().any(|s| s == "AAECAwQ="));
+    }
+}
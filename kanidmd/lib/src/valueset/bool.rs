@@ -73,10 +73,22 @@ impl ValueSetT for ValueSetBool {
         false
     }
 
+    fn startswith(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
+    fn endswith(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
     fn lessthan(&self, _pv: &PartialValue) -> bool {
         false
     }
 
+    fn greaterthan(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
     fn len(&self) -> usize {
         self.set.len()
     }
@@ -8,14 +8,28 @@ use crate::be::dbvalue::{
 };
 use crate::credential::Credential;
 use crate::prelude::*;
+use crate::redact::Redacted;
 use crate::schema::SchemaAttribute;
 use crate::valueset::{DbValueSetV2, IntentTokenState, ValueSet};
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct ValueSetCredential {
     map: BTreeMap<String, Credential>,
 }
 
+impl std::fmt::Debug for ValueSetCredential {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let map: BTreeMap<_, _> = self
+            .map
+            .iter()
+            .map(|(k, v)| (k, Redacted::new(v)))
+            .collect();
+        f.debug_struct("ValueSetCredential")
+            .field("map", &map)
+            .finish()
+    }
+}
+
 impl ValueSetCredential {
     pub fn new(t: String, c: Credential) -> Box<Self> {
         let mut map = BTreeMap::new();
@@ -89,10 +103,22 @@ impl ValueSetT for ValueSetCredential {
         false
     }
 
+    fn startswith(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
+    fn endswith(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
     fn lessthan(&self, _pv: &PartialValue) -> bool {
         false
     }
 
+    fn greaterthan(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
     fn len(&self) -> usize {
         self.map.len()
     }
@@ -169,11 +195,25 @@ impl ValueSetT for ValueSetCredential {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct ValueSetIntentToken {
     map: BTreeMap<String, IntentTokenState>,
 }
 
+/// The ids of the tokens are redacted, as they are what the user presents to redeem them.
+impl std::fmt::Debug for ValueSetIntentToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let map: Vec<_> = self
+            .map
+            .iter()
+            .map(|(k, v)| (Redacted::new(k), v))
+            .collect();
+        f.debug_struct("ValueSetIntentToken")
+            .field("map", &map)
+            .finish()
+    }
+}
+
 impl ValueSetIntentToken {
     pub fn new(t: String, s: IntentTokenState) -> Box<Self> {
         let mut map = BTreeMap::new();
@@ -263,10 +303,22 @@ impl ValueSetT for ValueSetIntentToken {
         false
     }
 
+    fn startswith(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
+    fn endswith(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
     fn lessthan(&self, _pv: &PartialValue) -> bool {
         false
     }
 
+    fn greaterthan(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
     fn len(&self) -> usize {
         self.map.len()
     }
@@ -352,11 +404,24 @@ impl ValueSetT for ValueSetIntentToken {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct ValueSetPasskey {
     map: BTreeMap<Uuid, (String, PasskeyV4)>,
 }
 
+impl std::fmt::Debug for ValueSetPasskey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let map: BTreeMap<_, _> = self
+            .map
+            .iter()
+            .map(|(u, (tag, key))| (u, (tag, Redacted::new(key))))
+            .collect();
+        f.debug_struct("ValueSetPasskey")
+            .field("map", &map)
+            .finish()
+    }
+}
+
 impl ValueSetPasskey {
     pub fn new(u: Uuid, t: String, k: PasskeyV4) -> Box<Self> {
         let mut map = BTreeMap::new();
@@ -427,10 +492,22 @@ impl ValueSetT for ValueSetPasskey {
         false
     }
 
+    fn startswith(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
+    fn endswith(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
     fn lessthan(&self, _pv: &PartialValue) -> bool {
         false
     }
 
+    fn greaterthan(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
     fn len(&self) -> usize {
         self.map.len()
     }
@@ -511,11 +588,24 @@ impl ValueSetT for ValueSetPasskey {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct ValueSetDeviceKey {
     map: BTreeMap<Uuid, (String, DeviceKeyV4)>,
 }
 
+impl std::fmt::Debug for ValueSetDeviceKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let map: BTreeMap<_, _> = self
+            .map
+            .iter()
+            .map(|(u, (tag, key))| (u, (tag, Redacted::new(key))))
+            .collect();
+        f.debug_struct("ValueSetDeviceKey")
+            .field("map", &map)
+            .finish()
+    }
+}
+
 impl ValueSetDeviceKey {
     pub fn new(u: Uuid, t: String, k: DeviceKeyV4) -> Box<Self> {
         let mut map = BTreeMap::new();
@@ -586,10 +676,22 @@ impl ValueSetT for ValueSetDeviceKey {
         false
     }
 
+    fn startswith(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
+    fn endswith(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
     fn lessthan(&self, _pv: &PartialValue) -> bool {
         false
     }
 
+    fn greaterthan(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
     fn len(&self) -> usize {
         self.map.len()
     }
@@ -78,10 +78,28 @@ impl ValueSetT for ValueSetDateTime {
         false
     }
 
-    fn lessthan(&self, _pv: &PartialValue) -> bool {
+    fn startswith(&self, _pv: &PartialValue) -> bool {
         false
     }
 
+    fn endswith(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
+    fn lessthan(&self, pv: &PartialValue) -> bool {
+        match pv {
+            PartialValue::DateTime(u) => self.set.iter().any(|i| i < u),
+            _ => false,
+        }
+    }
+
+    fn greaterthan(&self, pv: &PartialValue) -> bool {
+        match pv {
+            PartialValue::DateTime(u) => self.set.iter().any(|i| i > u),
+            _ => false,
+        }
+    }
+
     fn len(&self) -> usize {
         self.set.len()
     }
@@ -161,3 +179,45 @@ impl ValueSetT for ValueSetDateTime {
         Some(&self.set)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::ValueSetDateTime;
+    use crate::prelude::*;
+
+    #[test]
+    fn test_valueset_datetime_lessthan() {
+        let vs = ValueSetDateTime::new(
+            time::OffsetDateTime::parse("2022-06-01T00:00:00Z", time::Format::Rfc3339)
+                .expect("Invalid datetime"),
+        );
+
+        let pv = |s| PartialValue::new_datetime_s(s).expect("Invalid datetime");
+        assert!(!vs.lessthan(&pv("2022-01-01T00:00:00Z")));
+        assert!(!vs.lessthan(&pv("2022-06-01T00:00:00Z")));
+        assert!(vs.lessthan(&pv("2022-06-01T00:00:01Z")));
+        // Comparisons are on the instant, rather than the rendition.
+        assert!(vs.lessthan(&pv("2022-06-01T02:00:01+02:00")));
+        assert!(!vs.lessthan(&PartialValue::new_uint32(1)));
+    }
+
+    #[test]
+    fn test_valueset_datetime_greaterthan() {
+        let mut vs = ValueSetDateTime::new(
+            time::OffsetDateTime::parse("2022-01-01T00:00:00Z", time::Format::Rfc3339)
+                .expect("Invalid datetime"),
+        );
+        vs.push(
+            time::OffsetDateTime::parse("2022-06-01T00:00:00Z", time::Format::Rfc3339)
+                .expect("Invalid datetime"),
+        );
+
+        let pv = |s| PartialValue::new_datetime_s(s).expect("Invalid datetime");
+        assert!(vs.greaterthan(&pv("2021-12-31T00:00:00Z")));
+        // Only one of the values needs to be greater.
+        assert!(vs.greaterthan(&pv("2022-03-01T00:00:00Z")));
+        assert!(vs.lessthan(&pv("2022-03-01T00:00:00Z")));
+        assert!(!vs.greaterthan(&pv("2022-06-01T00:00:00Z")));
+        assert!(!vs.greaterthan(&PartialValue::new_uint32(1)));
+    }
+}
@@ -0,0 +1,276 @@
+use smolset::SmolSet;
+
+use crate::be::dbvalue::{DbValueImageTypeV1, DbValueImageV1};
+use crate::prelude::*;
+use crate::schema::SchemaAttribute;
+use crate::value::{ImageType, ImageValue};
+use crate::valueset::{DbValueSetV2, ValueSet};
+
+#[derive(Debug, Clone)]
+pub struct ValueSetImage {
+    set: SmolSet<[ImageValue; 1]>,
+}
+
+impl ValueSetImage {
+    pub fn new(i: ImageValue) -> Box<Self> {
+        let mut set = SmolSet::new();
+        set.insert(i);
+        Box::new(ValueSetImage { set })
+    }
+
+    pub fn push(&mut self, i: ImageValue) -> bool {
+        self.set.insert(i)
+    }
+
+    pub fn from_dbvs2(data: Vec<DbValueImageV1>) -> Result<ValueSet, OperationError> {
+        let set = data
+            .into_iter()
+            .map(|DbValueImageV1 { filetype, contents }| ImageValue {
+                filetype: match filetype {
+                    DbValueImageTypeV1::Png => ImageType::Png,
+                    DbValueImageTypeV1::Jpeg => ImageType::Jpeg,
+                    DbValueImageTypeV1::Webp => ImageType::Webp,
+                },
+                contents,
+            })
+            .collect();
+        Ok(Box::new(ValueSetImage { set }))
+    }
+}
+
+impl FromIterator<ImageValue> for Option<Box<ValueSetImage>> {
+    fn from_iter<T>(iter: T) -> Option<Box<ValueSetImage>>
+    where
+        T: IntoIterator<Item = ImageValue>,
+    {
+        let set = iter.into_iter().collect();
+        Some(Box::new(ValueSetImage { set }))
+    }
+}
+
+impl ValueSetT for ValueSetImage {
+    fn insert_checked(&mut self, value: Value) -> Result<bool, OperationError> {
+        match value {
+            Value::Image(i) => Ok(self.set.insert(i)),
+            _ => {
+                debug_assert!(false);
+                Err(OperationError::InvalidValueState)
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        self.set.clear();
+    }
+
+    fn remove(&mut self, pv: &PartialValue) -> bool {
+        match pv {
+            PartialValue::Image(hash) => {
+                match self.set.iter().find(|i| &i.hash() == hash).cloned() {
+                    Some(i) => self.set.remove(&i),
+                    None => false,
+                }
+            }
+            _ => {
+                debug_assert!(false);
+                true
+            }
+        }
+    }
+
+    fn contains(&self, pv: &PartialValue) -> bool {
+        match pv {
+            PartialValue::Image(hash) => self.set.iter().any(|i| &i.hash() == hash),
+            _ => false,
+        }
+    }
+
+    fn substring(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
+    fn startswith(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
+    fn endswith(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
+    fn lessthan(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
+    fn greaterthan(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
+    fn len(&self) -> usize {
+        self.set.len()
+    }
+
+    fn generate_idx_eq_keys(&self) -> Vec<String> {
+        self.set.iter().map(|i| i.hash()).collect()
+    }
+
+    fn syntax(&self) -> SyntaxType {
+        SyntaxType::Image
+    }
+
+    fn validate(&self, schema_attr: &SchemaAttribute) -> bool {
+        let max_size = schema_attr.max_size.unwrap_or(IMAGE_MAX_SIZE_DEFAULT) as usize;
+        self.set.iter().all(|i| {
+            if i.contents.len() > max_size {
+                admin_warn!(
+                    attr = %schema_attr.name,
+                    size = i.contents.len(),
+                    max_size,
+                    "image exceeds the max_size of its attribute"
+                );
+                false
+            } else {
+                i.validate()
+            }
+        })
+    }
+
+    fn to_proto_string_clone_iter(&self) -> Box<dyn Iterator<Item = String> + '_> {
+        Box::new(self.set.iter().map(|i| i.to_data_url()))
+    }
+
+    fn to_db_valueset_v2(&self) -> DbValueSetV2 {
+        DbValueSetV2::Image(
+            self.set
+                .iter()
+                .map(|i| DbValueImageV1 {
+                    filetype: match i.filetype {
+                        ImageType::Png => DbValueImageTypeV1::Png,
+                        ImageType::Jpeg => DbValueImageTypeV1::Jpeg,
+                        ImageType::Webp => DbValueImageTypeV1::Webp,
+                    },
+                    contents: i.contents.clone(),
+                })
+                .collect(),
+        )
+    }
+
+    fn to_partialvalue_iter(&self) -> Box<dyn Iterator<Item = PartialValue> + '_> {
+        Box::new(self.set.iter().map(|i| PartialValue::Image(i.hash())))
+    }
+
+    fn to_value_iter(&self) -> Box<dyn Iterator<Item = Value> + '_> {
+        Box::new(self.set.iter().cloned().map(Value::Image))
+    }
+
+    fn equal(&self, other: &ValueSet) -> bool {
+        if let Some(other) = other.as_image_set() {
+            &self.set == other
+        } else {
+            debug_assert!(false);
+            false
+        }
+    }
+
+    fn merge(&mut self, other: &ValueSet) -> Result<(), OperationError> {
+        if let Some(b) = other.as_image_set() {
+            mergesets!(self.set, b)
+        } else {
+            debug_assert!(false);
+            Err(OperationError::InvalidValueState)
+        }
+    }
+
+    fn to_image_single(&self) -> Option<&ImageValue> {
+        if self.set.len() == 1 {
+            self.set.iter().take(1).next()
+        } else {
+            None
+        }
+    }
+
+    fn as_image_set(&self) -> Option<&SmolSet<[ImageValue; 1]>> {
+        Some(&self.set)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ValueSetImage;
+    use crate::prelude::*;
+    use crate::schema::SchemaAttribute;
+    use crate::value::{ImageType, ImageValue};
+
+    fn png(w: u32, h: u32) -> Vec<u8> {
+        let mut b = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR".to_vec();
+        b.extend_from_slice(&w.to_be_bytes());
+        b.extend_from_slice(&h.to_be_bytes());
+        b.extend_from_slice(&[8, 6, 0, 0, 0]);
+        b
+    }
+
+    #[test]
+    fn test_image_value_formats() {
+        let i = ImageValue::from_bytes(png(16, 8)).expect("Invalid png");
+        assert!(i.filetype == ImageType::Png);
+        assert!(i.dimensions() == Some((16, 8)));
+
+        // An app segment is skipped to find the start of frame.
+        let jpeg = vec![
+            0xff, 0xd8, 0xff, 0xe0, 0x00, 0x04, 0x00, 0x00, 0xff, 0xc0, 0x00, 0x11, 0x08, 0x00,
+            0x20, 0x00, 0x40, 0x03,
+        ];
+        let i = ImageValue::from_bytes(jpeg).expect("Invalid jpeg");
+        assert!(i.filetype == ImageType::Jpeg);
+        assert!(i.dimensions() == Some((64, 32)));
+
+        let mut webp = b"RIFF\x00\x00\x00\x00WEBPVP8X\x0a\x00\x00\x00\x00\x00\x00\x00".to_vec();
+        webp.extend_from_slice(&[99, 0, 0, 49, 0, 0]);
+        let i = ImageValue::from_bytes(webp).expect("Invalid webp");
+        assert!(i.filetype == ImageType::Webp);
+        assert!(i.dimensions() == Some((100, 50)));
+
+        // Images that are too large, or are not images, are rejected.
+        assert!(ImageValue::from_bytes(png(IMAGE_MAX_DIMENSION + 1, 8)).is_none());
+        assert!(ImageValue::from_bytes(b"GIF89a".to_vec()).is_none());
+        assert!(ImageValue::from_bytes(png(16, 8)[..20].to_vec()).is_none());
+
+        // The data url an image is returned as can be given back.
+        let i = ImageValue::from_bytes(png(16, 8)).expect("Invalid png");
+        let url = i.to_data_url();
+        assert!(url.starts_with("data:image/png;base64,"));
+        assert!(ImageValue::from_base64(&url) == Some(i));
+    }
+
+    #[test]
+    fn test_valueset_image_max_size() {
+        let schema_attr = SchemaAttribute {
+            name: AttrString::from("image"),
+            uuid: Uuid::new_v4(),
+            description: String::new(),
+            multivalue: false,
+            unique: false,
+            phantom: false,
+            sync_allowed: false,
+            index: Vec::new(),
+            encrypted: false,
+            max_size: Some(32),
+            syntax: SyntaxType::Image,
+        };
+
+        let i = ImageValue::from_bytes(png(16, 8)).expect("Invalid png");
+        let pv = PartialValue::Image(i.hash());
+        let mut vs: ValueSet = ValueSetImage::new(i);
+        assert!(vs.validate(&schema_attr));
+        assert!(vs.contains(&pv));
+
+        let mut large = png(16, 8);
+        large.resize(64, 0);
+        let large = ImageValue::from_bytes(large).expect("Invalid png");
+        let mut vs_large: ValueSet = ValueSetImage::new(large);
+        assert!(!vs_large.validate(&schema_attr));
+
+        assert!(!vs_large.remove(&pv));
+        assert!(vs.remove(&pv));
+        assert!(vs.is_empty());
+    }
+}
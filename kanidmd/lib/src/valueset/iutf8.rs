@@ -80,10 +80,34 @@ impl ValueSetT for ValueSetIutf8 {
         }
     }
 
+    fn startswith(&self, pv: &PartialValue) -> bool {
+        match pv {
+            PartialValue::Iutf8(s2) => self.set.iter().any(|s1| s1.starts_with(s2)),
+            _ => {
+                debug_assert!(false);
+                false
+            }
+        }
+    }
+
+    fn endswith(&self, pv: &PartialValue) -> bool {
+        match pv {
+            PartialValue::Iutf8(s2) => self.set.iter().any(|s1| s1.ends_with(s2)),
+            _ => {
+                debug_assert!(false);
+                false
+            }
+        }
+    }
+
     fn lessthan(&self, _pv: &PartialValue) -> bool {
         false
     }
 
+    fn greaterthan(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
     fn len(&self) -> usize {
         self.set.len()
     }
@@ -2,14 +2,23 @@ use compact_jwt::{JwaAlg, JwsSigner};
 use hashbrown::HashSet;
 
 use crate::prelude::*;
+use crate::redact::redact_all;
 use crate::schema::SchemaAttribute;
 use crate::valueset::{DbValueSetV2, ValueSet};
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct ValueSetJwsKeyEs256 {
     set: HashSet<JwsSigner>,
 }
 
+impl std::fmt::Debug for ValueSetJwsKeyEs256 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ValueSetJwsKeyEs256")
+            .field("set", &redact_all(self.set.iter()))
+            .finish()
+    }
+}
+
 impl ValueSetJwsKeyEs256 {
     pub fn new(k: JwsSigner) -> Box<Self> {
         debug_assert!(k.get_jwa_alg() == JwaAlg::ES256);
@@ -83,10 +92,22 @@ impl ValueSetT for ValueSetJwsKeyEs256 {
         false
     }
 
+    fn startswith(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
+    fn endswith(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
     fn lessthan(&self, _pv: &PartialValue) -> bool {
         false
     }
 
+    fn greaterthan(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
     fn len(&self) -> usize {
         self.set.len()
     }
@@ -161,11 +182,19 @@ impl ValueSetT for ValueSetJwsKeyEs256 {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct ValueSetJwsKeyRs256 {
     set: HashSet<JwsSigner>,
 }
 
+impl std::fmt::Debug for ValueSetJwsKeyRs256 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ValueSetJwsKeyRs256")
+            .field("set", &redact_all(self.set.iter()))
+            .finish()
+    }
+}
+
 impl ValueSetJwsKeyRs256 {
     pub fn new(k: JwsSigner) -> Box<Self> {
         debug_assert!(k.get_jwa_alg() == JwaAlg::RS256);
@@ -239,10 +268,22 @@ impl ValueSetT for ValueSetJwsKeyRs256 {
         false
     }
 
+    fn startswith(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
+    fn endswith(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
     fn lessthan(&self, _pv: &PartialValue) -> bool {
         false
     }
 
+    fn greaterthan(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
     fn len(&self) -> usize {
         self.set.len()
     }
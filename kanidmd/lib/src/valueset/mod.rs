@@ -16,7 +16,7 @@ use crate::credential::Credential;
 use crate::prelude::*;
 use crate::repl::cid::Cid;
 use crate::schema::SchemaAttribute;
-use crate::value::{Address, IntentTokenState, Oauth2Session, Session};
+use crate::value::{Address, ImageValue, IntentTokenState, Oauth2Session, Session};
 
 mod address;
 mod binary;
@@ -24,6 +24,7 @@ mod bool;
 mod cid;
 mod cred;
 mod datetime;
+mod image;
 mod iname;
 mod index;
 mod iutf8;
@@ -43,12 +44,13 @@ mod url;
 mod utf8;
 mod uuid;
 
-pub use self::address::{ValueSetAddress, ValueSetEmailAddress};
-pub use self::binary::{ValueSetPrivateBinary, ValueSetPublicBinary};
+pub use self::address::{ValueSetAddress, ValueSetEmailAddress, ValueSetPhoneNumber};
+pub use self::binary::{ValueSetBinary, ValueSetPrivateBinary, ValueSetPublicBinary};
 pub use self::bool::ValueSetBool;
 pub use self::cid::ValueSetCid;
 pub use self::cred::{ValueSetCredential, ValueSetDeviceKey, ValueSetIntentToken, ValueSetPasskey};
 pub use self::datetime::ValueSetDateTime;
+pub use self::image::ValueSetImage;
 pub use self::iname::ValueSetIname;
 pub use self::index::ValueSetIndex;
 pub use self::iutf8::ValueSetIutf8;
@@ -91,8 +93,14 @@ pub trait ValueSetT: std::fmt::Debug + DynClone {
 
     fn substring(&self, pv: &PartialValue) -> bool;
 
+    fn startswith(&self, pv: &PartialValue) -> bool;
+
+    fn endswith(&self, pv: &PartialValue) -> bool;
+
     fn lessthan(&self, pv: &PartialValue) -> bool;
 
+    fn greaterthan(&self, pv: &PartialValue) -> bool;
+
     fn len(&self) -> usize;
 
     fn generate_idx_eq_keys(&self) -> Vec<String>;
@@ -268,6 +276,16 @@ pub trait ValueSetT: std::fmt::Debug + DynClone {
         None
     }
 
+    fn as_binary_set(&self) -> Option<&SmolSet<[Vec<u8>; 1]>> {
+        debug_assert!(false);
+        None
+    }
+
+    fn as_image_set(&self) -> Option<&SmolSet<[ImageValue; 1]>> {
+        debug_assert!(false);
+        None
+    }
+
     fn as_oauthscope_set(&self) -> Option<&BTreeSet<String>> {
         debug_assert!(false);
         None
@@ -288,6 +306,11 @@ pub trait ValueSetT: std::fmt::Debug + DynClone {
         None
     }
 
+    fn as_phonenumber_set(&self) -> Option<(&String, &BTreeSet<String>)> {
+        debug_assert!(false);
+        None
+    }
+
     fn as_sshkey_map(&self) -> Option<&BTreeMap<String, String>> {
         None
     }
@@ -459,6 +482,16 @@ pub trait ValueSetT: std::fmt::Debug + DynClone {
         None
     }
 
+    fn to_binary_single(&self) -> Option<&[u8]> {
+        debug_assert!(false);
+        None
+    }
+
+    fn to_image_single(&self) -> Option<&ImageValue> {
+        debug_assert!(false);
+        None
+    }
+
     fn to_passkey_single(&self) -> Option<&PasskeyV4> {
         debug_assert!(false);
         None
@@ -557,11 +590,13 @@ pub fn from_result_value_iter(
         Value::SshKey(t, k) => ValueSetSshKey::new(t, k),
         Value::OauthScopeMap(u, m) => ValueSetOauthScopeMap::new(u, m),
         Value::PublicBinary(t, b) => ValueSetPublicBinary::new(t, b),
+        Value::Binary(b) => ValueSetBinary::new(b),
+        Value::Image(i) => ValueSetImage::new(i),
         Value::IntentToken(u, s) => ValueSetIntentToken::new(u, s),
         Value::EmailAddress(a, _) => ValueSetEmailAddress::new(a),
         Value::UiHint(u) => ValueSetUiHint::new(u),
-        Value::PhoneNumber(_, _)
-        | Value::Passkey(_, _, _)
+        Value::PhoneNumber(n, _) => ValueSetPhoneNumber::new(n),
+        Value::Passkey(_, _, _)
         | Value::DeviceKey(_, _, _)
         | Value::TrustedDeviceEnrollment(_)
         | Value::Session(_, _)
@@ -614,6 +649,8 @@ pub fn from_value_iter(mut iter: impl Iterator<Item = Value>) -> Result<ValueSet
         Value::SshKey(t, k) => ValueSetSshKey::new(t, k),
         Value::OauthScopeMap(u, m) => ValueSetOauthScopeMap::new(u, m),
         Value::PublicBinary(t, b) => ValueSetPublicBinary::new(t, b),
+        Value::Binary(b) => ValueSetBinary::new(b),
+        Value::Image(i) => ValueSetImage::new(i),
         Value::IntentToken(u, s) => ValueSetIntentToken::new(u, s),
         Value::EmailAddress(a, _) => ValueSetEmailAddress::new(a),
         Value::Passkey(u, t, k) => ValueSetPasskey::new(u, t, k),
@@ -623,7 +660,8 @@ pub fn from_value_iter(mut iter: impl Iterator<Item = Value>) -> Result<ValueSet
         Value::Session(u, m) => ValueSetSession::new(u, m),
         Value::Oauth2Session(u, m) => ValueSetOauth2Session::new(u, m),
         Value::UiHint(u) => ValueSetUiHint::new(u),
-        Value::PhoneNumber(_, _) | Value::TrustedDeviceEnrollment(_) => {
+        Value::PhoneNumber(n, _) => ValueSetPhoneNumber::new(n),
+        Value::TrustedDeviceEnrollment(_) => {
             debug_assert!(false);
             return Err(OperationError::InvalidValueState);
         }
@@ -661,6 +699,8 @@ pub fn from_db_valueset_v2(dbvs: DbValueSetV2) -> Result<ValueSet, OperationErro
         DbValueSetV2::SshKey(set) => ValueSetSshKey::from_dbvs2(set),
         DbValueSetV2::OauthScopeMap(set) => ValueSetOauthScopeMap::from_dbvs2(set),
         DbValueSetV2::PublicBinary(set) => ValueSetPublicBinary::from_dbvs2(set),
+        DbValueSetV2::Binary(set) => ValueSetBinary::from_dbvs2(set),
+        DbValueSetV2::Image(set) => ValueSetImage::from_dbvs2(set),
         DbValueSetV2::IntentToken(set) => ValueSetIntentToken::from_dbvs2(set),
         DbValueSetV2::EmailAddress(primary, set) => ValueSetEmailAddress::from_dbvs2(primary, set),
         DbValueSetV2::Passkey(set) => ValueSetPasskey::from_dbvs2(set),
@@ -670,8 +710,13 @@ pub fn from_db_valueset_v2(dbvs: DbValueSetV2) -> Result<ValueSet, OperationErro
         DbValueSetV2::JwsKeyEs256(set) => ValueSetJwsKeyEs256::from_dbvs2(&set),
         DbValueSetV2::JwsKeyRs256(set) => ValueSetJwsKeyEs256::from_dbvs2(&set),
         DbValueSetV2::UiHint(set) => ValueSetUiHint::from_dbvs2(set),
-        DbValueSetV2::PhoneNumber(_, _) | DbValueSetV2::TrustedDeviceEnrollment(_) => {
+        DbValueSetV2::PhoneNumber(primary, set) => ValueSetPhoneNumber::from_dbvs2(primary, set),
+        DbValueSetV2::TrustedDeviceEnrollment(_) => {
             todo!()
         }
+        DbValueSetV2::Sealed(_) => {
+            admin_error!("Sealed value was not unsealed, is the database seal key configured?");
+            Err(OperationError::InvalidValueState)
+        }
     }
 }
@@ -1,14 +1,23 @@
 use smolset::SmolSet;
 
 use crate::prelude::*;
+use crate::redact::redact_all;
 use crate::schema::SchemaAttribute;
 use crate::valueset::{DbValueSetV2, ValueSet};
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct ValueSetSecret {
     set: SmolSet<[String; 1]>,
 }
 
+impl std::fmt::Debug for ValueSetSecret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ValueSetSecret")
+            .field("set", &redact_all(self.set.iter()))
+            .finish()
+    }
+}
+
 impl ValueSetSecret {
     pub fn new(b: String) -> Box<Self> {
         let mut set = SmolSet::new();
@@ -64,10 +73,22 @@ impl ValueSetT for ValueSetSecret {
         false
     }
 
+    fn startswith(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
+    fn endswith(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
     fn lessthan(&self, _pv: &PartialValue) -> bool {
         false
     }
 
+    fn greaterthan(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
     fn len(&self) -> usize {
         self.set.len()
     }
@@ -8,15 +8,29 @@ use crate::be::dbvalue::{
 };
 use crate::identity::{AccessScope, IdentityId};
 use crate::prelude::*;
+use crate::redact::Redacted;
 use crate::schema::SchemaAttribute;
 use crate::value::{Oauth2Session, Session};
 use crate::valueset::{uuid_to_proto_string, DbValueSetV2, ValueSet};
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct ValueSetSession {
     map: BTreeMap<Uuid, Session>,
 }
 
+impl std::fmt::Debug for ValueSetSession {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let map: BTreeMap<_, _> = self
+            .map
+            .iter()
+            .map(|(k, v)| (k, Redacted::new(v)))
+            .collect();
+        f.debug_struct("ValueSetSession")
+            .field("map", &map)
+            .finish()
+    }
+}
+
 impl ValueSetSession {
     pub fn new(u: Uuid, m: Session) -> Box<Self> {
         let mut map = BTreeMap::new();
@@ -155,10 +169,22 @@ impl ValueSetT for ValueSetSession {
         false
     }
 
+    fn startswith(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
+    fn endswith(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
     fn lessthan(&self, _pv: &PartialValue) -> bool {
         false
     }
 
+    fn greaterthan(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
     fn len(&self) -> usize {
         self.map.len()
     }
@@ -255,7 +281,7 @@ impl ValueSetT for ValueSetSession {
 
 // == oauth2 session ==
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct ValueSetOauth2Session {
     map: BTreeMap<Uuid, Oauth2Session>,
     // this is a "filter" to tell us if as rs_id is used anywhere
@@ -266,6 +292,20 @@ pub struct ValueSetOauth2Session {
     rs_filter: BTreeSet<Uuid>,
 }
 
+impl std::fmt::Debug for ValueSetOauth2Session {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let map: BTreeMap<_, _> = self
+            .map
+            .iter()
+            .map(|(k, v)| (k, Redacted::new(v)))
+            .collect();
+        f.debug_struct("ValueSetOauth2Session")
+            .field("map", &map)
+            .field("rs_filter", &self.rs_filter)
+            .finish()
+    }
+}
+
 impl ValueSetOauth2Session {
     pub fn new(u: Uuid, m: Oauth2Session) -> Box<Self> {
         let mut map = BTreeMap::new();
@@ -292,6 +332,7 @@ impl ValueSetOauth2Session {
                         expiry,
                         issued_at,
                         rs_uuid,
+                        delegated_from,
                     } => {
                         // Convert things.
                         let issued_at = OffsetDateTime::parse(issued_at, time::Format::Rfc3339)
@@ -337,6 +378,7 @@ impl ValueSetOauth2Session {
                                 expiry,
                                 issued_at,
                                 rs_uuid,
+                                delegated_from,
                             },
                         ))
                     }
@@ -422,10 +464,22 @@ impl ValueSetT for ValueSetOauth2Session {
         false
     }
 
+    fn startswith(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
+    fn endswith(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
     fn lessthan(&self, _pv: &PartialValue) -> bool {
         false
     }
 
+    fn greaterthan(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
     fn len(&self) -> usize {
         self.map.len()
     }
@@ -471,6 +525,7 @@ impl ValueSetT for ValueSetOauth2Session {
                         m.issued_at.format(time::Format::Rfc3339)
                     },
                     rs_uuid: m.rs_uuid,
+                    delegated_from: m.delegated_from,
                 })
                 .collect(),
         )
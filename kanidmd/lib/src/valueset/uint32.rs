@@ -73,6 +73,14 @@ impl ValueSetT for ValueSetUint32 {
         false
     }
 
+    fn startswith(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
+    fn endswith(&self, _pv: &PartialValue) -> bool {
+        false
+    }
+
     fn lessthan(&self, pv: &PartialValue) -> bool {
         match pv {
             PartialValue::Uint32(u) => self.set.iter().any(|i| i < u),
@@ -80,6 +88,13 @@ impl ValueSetT for ValueSetUint32 {
         }
     }
 
+    fn greaterthan(&self, pv: &PartialValue) -> bool {
+        match pv {
+            PartialValue::Uint32(u) => self.set.iter().any(|i| i > u),
+            _ => false,
+        }
+    }
+
     fn len(&self) -> usize {
         self.set.len()
     }
@@ -154,4 +169,21 @@ mod tests {
         assert!(vs.insert_checked(Value::new_uint32(1)) == Ok(true));
         assert!(vs.insert_checked(Value::new_uint32(1)) == Ok(false));
     }
+
+    #[test]
+    fn test_valueset_uint32_ordering() {
+        // Ordering is existential - any one value satisfying the comparison is a match.
+        let mut vs = ValueSetUint32::new(1);
+        vs.push(10);
+
+        assert!(!vs.lessthan(&PartialValue::new_uint32(1)));
+        assert!(vs.lessthan(&PartialValue::new_uint32(5)));
+        assert!(vs.lessthan(&PartialValue::new_uint32(11)));
+
+        assert!(vs.greaterthan(&PartialValue::new_uint32(0)));
+        assert!(vs.greaterthan(&PartialValue::new_uint32(5)));
+        assert!(!vs.greaterthan(&PartialValue::new_uint32(10)));
+
+        assert!(!vs.greaterthan(&PartialValue::new_iutf8("5")));
+    }
 }
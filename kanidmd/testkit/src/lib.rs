@@ -32,6 +32,14 @@ pub fn is_free_port(port: u16) -> bool {
     }
 }
 
+/// The admin socket of the test server that listens on this port.
+pub fn admin_socket_path(port: u16) -> String {
+    std::env::temp_dir()
+        .join(format!("kanidmd_testkit_{}.sock", port))
+        .to_string_lossy()
+        .to_string()
+}
+
 // Test external behaviours of the service.
 
 // allowed because the use of this function is behind a test gate
@@ -70,6 +78,8 @@ pub async fn setup_async_test() -> (KanidmClient, CoreHandle) {
     // config.log_level = Some(LogLevel::Verbose as u32);
     // config.log_level = Some(LogLevel::FullTrace as u32);
     config.threads = 1;
+    config.audit_log_retention = Some(86400);
+    config.adminbindpath = Some(admin_socket_path(port));
 
     let core_handle = create_server_core(config, false)
         .await
@@ -37,3 +37,23 @@ async fn test_https_middleware_headers(rsclient: KanidmClient) {
     );
     assert_eq!(response.headers().get("content-security-policy"), None);
 }
+
+#[kanidmd_testkit::test]
+async fn test_https_middleware_opid(rsclient: KanidmClient) {
+    let addr = rsclient.get_url();
+
+    // Every response has an operation id, even those that no handler made.
+    let response = match reqwest::get(format!("{}/v1/not_a_route", &addr)).await {
+        Ok(value) => value,
+        Err(error) => {
+            panic!("Failed to query {:?} : {:#?}", addr, error);
+        }
+    };
+    assert_eq!(response.status(), 404);
+    let opid = response
+        .headers()
+        .get("X-KANIDM-OPID")
+        .and_then(|hv| hv.to_str().ok())
+        .expect("No opid header");
+    assert!(kanidmd_lib::prelude::Uuid::parse_str(opid).is_ok());
+}
@@ -6,7 +6,7 @@ use std::str::FromStr;
 use compact_jwt::{JwkKeySet, JwsValidator, OidcToken, OidcUnverified};
 use kanidm_proto::oauth2::{
     AccessTokenIntrospectRequest, AccessTokenIntrospectResponse, AccessTokenRequest,
-    AccessTokenResponse, AuthorisationResponse, OidcDiscoveryResponse,
+    AccessTokenResponse, AuthorisationResponse, GrantTypeReq, OidcDiscoveryResponse,
 };
 use oauth2_ext::PkceCodeChallenge;
 use url::Url;
@@ -277,12 +277,13 @@ async fn test_oauth2_openid_basic_flow(rsclient: KanidmClient) {
     // the authorisation server to request a token.
 
     let form_req = AccessTokenRequest {
-        grant_type: "authorization_code".to_string(),
-        code: code.to_string(),
-        redirect_uri: Url::parse("https://demo.example.com/oauth2/flow").expect("Invalid URL"),
+        grant_type: GrantTypeReq::AuthorizationCode {
+            code: code.to_string(),
+            redirect_uri: Url::parse("https://demo.example.com/oauth2/flow").expect("Invalid URL"),
+            code_verifier: Some(pkce_code_verifier.secret().clone()),
+        },
         client_id: None,
         client_secret: None,
-        code_verifier: Some(pkce_code_verifier.secret().clone()),
     };
 
     let response = client
@@ -2,9 +2,13 @@
 use std::time::SystemTime;
 
 use kanidm_proto::v1::{
-    ApiToken, CURegState, CredentialDetailType, Entry, Filter, Modify, ModifyList, UserAuthToken,
+    ApiToken, AuditOutcome, AuditSearchRequest, BatchOperation, BatchOperationResult, CURegState,
+    CreateRequest, CredentialDetailType, DeleteRequest, Entry, EntryChange, ErrorResponse, Filter,
+    Modify, ModifyList, ModifyRequest, OperationError, SearchRequest, UserAuthToken,
 };
+use kanidmd_lib::constants::UUID_ADMIN;
 use kanidmd_lib::credential::totp::Totp;
+use kanidmd_lib::prelude::Uuid;
 use tracing::debug;
 
 use std::str::FromStr;
@@ -14,6 +18,7 @@ use webauthn_authenticator_rs::softpasskey::SoftPasskey;
 use webauthn_authenticator_rs::WebauthnAuthenticator;
 
 use kanidm_client::KanidmClient;
+use kanidmd_core::admin::{submit_admin_task, AdminTaskRequest, AdminTaskResponse};
 use kanidmd_testkit::ADMIN_TEST_PASSWORD;
 
 const UNIX_TEST_PASSWORD: &str = "unix test user password";
@@ -139,6 +144,695 @@ async fn test_server_search(rsclient: KanidmClient) {
     assert!(name == &vec!["admin".to_string()]);
 }
 
+#[kanidmd_testkit::test]
+async fn test_server_error_response(rsclient: KanidmClient) {
+    // We need to do manual reqwests here to see the response body.
+    let addr = rsclient.get_url();
+    let client = reqwest::Client::new();
+
+    let response = client
+        .post(format!("{}/v1/raw/search", &addr))
+        .header("Content-Type", "application/json")
+        .body(r#"{"filter": {"eq": ["name", "admin"]}}"#)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 401);
+    let opid = response
+        .headers()
+        .get("X-KANIDM-OPID")
+        .and_then(|hv| hv.to_str().ok())
+        .unwrap()
+        .to_string();
+
+    let body: ErrorResponse = serde_json::from_str(&response.text().await.unwrap()).unwrap();
+    assert!(matches!(body.error, OperationError::NotAuthenticated));
+    assert_eq!(body.opid, opid);
+    assert!(!body.message.is_empty());
+}
+
+#[kanidmd_testkit::test]
+async fn test_server_response_compression(rsclient: KanidmClient) {
+    let res = rsclient
+        .auth_simple_password("admin", ADMIN_TEST_PASSWORD)
+        .await;
+    assert!(res.is_ok());
+    let token = rsclient.get_token().await.expect("No bearer token present");
+
+    let addr = rsclient.get_url();
+    // Disable decompression to see how the response was sent.
+    let client = reqwest::Client::builder().no_gzip().build().unwrap();
+    let get = |path: &str| {
+        client
+            .get(format!("{}{}", &addr, path))
+            .bearer_auth(&token)
+            .header("Accept-Encoding", "gzip")
+            .send()
+    };
+
+    // The schema is a large read, and is compressed.
+    let response = get("/v1/schema").await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(
+        response
+            .headers()
+            .get("Content-Encoding")
+            .and_then(|hv| hv.to_str().ok()),
+        Some("gzip")
+    );
+
+    // Tokens are never compressed.
+    let response = get("/v1/self/_uat").await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert!(response.headers().get("Content-Encoding").is_none());
+}
+
+#[kanidmd_testkit::test]
+async fn test_server_request_size_limit(rsclient: KanidmClient) {
+    let addr = rsclient.get_url();
+    let client = reqwest::Client::new();
+
+    // The body is refused before it is parsed, so it needn't be valid.
+    let response = client
+        .post(format!("{}/v1/raw/create", &addr))
+        .header("Content-Type", "application/json")
+        .body(vec![b'a'; 5 * 1024 * 1024])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 413);
+}
+
+#[kanidmd_testkit::test]
+async fn test_server_admin_socket_recover_account(rsclient: KanidmClient) {
+    let port = url::Url::parse(&rsclient.get_url())
+        .unwrap()
+        .port()
+        .unwrap();
+    let path = kanidmd_testkit::admin_socket_path(port);
+
+    let password = match submit_admin_task(
+        &path,
+        AdminTaskRequest::RecoverAccount {
+            name: "admin".to_string(),
+        },
+    )
+    .await
+    .unwrap()
+    {
+        AdminTaskResponse::RecoverAccount { password } => password,
+        _ => unreachable!(),
+    };
+
+    // The old password no longer works, and the new one does.
+    assert!(rsclient
+        .auth_simple_password("admin", ADMIN_TEST_PASSWORD)
+        .await
+        .is_err());
+    assert!(rsclient
+        .auth_simple_password("admin", &password)
+        .await
+        .is_ok());
+
+    // Unknown accounts can't be recovered.
+    let resp = submit_admin_task(
+        &path,
+        AdminTaskRequest::RecoverAccount {
+            name: "nonexistent".to_string(),
+        },
+    )
+    .await
+    .unwrap();
+    assert!(matches!(resp, AdminTaskResponse::Error(_)));
+}
+
+#[kanidmd_testkit::test]
+async fn test_server_admin_socket_tail(rsclient: KanidmClient) {
+    let port = url::Url::parse(&rsclient.get_url())
+        .unwrap()
+        .port()
+        .unwrap();
+    let path = kanidmd_testkit::admin_socket_path(port);
+
+    // The tail starts before any lines are sent.
+    let resp = submit_admin_task(
+        &path,
+        AdminTaskRequest::Tail {
+            filter: "info,[auth]=debug".to_string(),
+        },
+    )
+    .await
+    .unwrap();
+    assert!(matches!(resp, AdminTaskResponse::Success));
+
+    let resp = submit_admin_task(
+        &path,
+        AdminTaskRequest::Tail {
+            filter: "[[not a filter".to_string(),
+        },
+    )
+    .await
+    .unwrap();
+    assert!(matches!(resp, AdminTaskResponse::Error(_)));
+}
+
+#[kanidmd_testkit::test]
+async fn test_server_status_and_ready(rsclient: KanidmClient) {
+    // Both probes are unauthenticated.
+    let addr = rsclient.get_url();
+
+    let response = reqwest::get(format!("{}/status", &addr)).await.unwrap();
+    assert_eq!(response.status(), 200);
+
+    let response = reqwest::get(format!("{}/ready", &addr)).await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert!(response.headers().get("X-KANIDM-OPID").is_some());
+    let ready: bool = serde_json::from_str(&response.text().await.unwrap()).unwrap();
+    assert!(ready);
+}
+
+#[kanidmd_testkit::test]
+async fn test_server_openapi_document(rsclient: KanidmClient) {
+    let addr = rsclient.get_url();
+    let response = reqwest::get(format!("{}/v1/openapi.json", &addr))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let doc: serde_json::Value = serde_json::from_str(&response.text().await.unwrap()).unwrap();
+    assert_eq!(doc["openapi"], "3.0.3");
+    assert_eq!(
+        doc["paths"]["/v1/raw/search"]["post"]["requestBody"]["content"]["application/json"]
+            ["schema"]["$ref"],
+        "#/components/schemas/SearchRequest"
+    );
+    assert!(doc["paths"]["/v1/person/{id}"]["get"]["parameters"].is_array());
+    assert!(doc["components"]["schemas"]["Filter"].is_object());
+}
+
+#[kanidmd_testkit::test]
+async fn test_server_etag_if_match(rsclient: KanidmClient) {
+    let res = rsclient
+        .auth_simple_password("admin", ADMIN_TEST_PASSWORD)
+        .await;
+    assert!(res.is_ok());
+    rsclient
+        .idm_group_add_members("idm_admins", &["admin"])
+        .await
+        .unwrap();
+    rsclient
+        .idm_person_account_create("etag_account", "Etag Account")
+        .await
+        .unwrap();
+    let token = rsclient.get_token().await.expect("No bearer token present");
+
+    let url = format!("{}/v1/person/etag_account", rsclient.get_url());
+    let client = reqwest::Client::new();
+    let get_etag = || {
+        let response = client.get(&url).bearer_auth(&token).send();
+        async move {
+            let response = response.await.unwrap();
+            assert_eq!(response.status(), 200);
+            response
+                .headers()
+                .get("ETag")
+                .and_then(|hv| hv.to_str().ok())
+                .map(str::to_string)
+                .expect("No etag")
+        }
+    };
+    let patch = |etag: String, displayname: &str| {
+        client
+            .patch(&url)
+            .bearer_auth(&token)
+            .header("If-Match", etag)
+            .json(&serde_json::json!({ "attrs": { "displayname": [displayname] } }))
+            .send()
+    };
+
+    // A change with the current etag succeeds, and changes the etag.
+    let etag = get_etag().await;
+    let response = patch(etag.clone(), "Etag Changed").await.unwrap();
+    assert_eq!(response.status(), 200);
+    let new_etag = get_etag().await;
+    assert_ne!(etag, new_etag);
+
+    // The old etag is now stale, so changes made with it are refused.
+    let response = patch(etag.clone(), "Etag Lost Update").await.unwrap();
+    assert_eq!(response.status(), 412);
+
+    // The same applies to changes of a single attribute.
+    let attr_url = format!("{}/_attr/displayname", url);
+    let response = client
+        .put(&attr_url)
+        .bearer_auth(&token)
+        .header("If-Match", etag.clone())
+        .json(&["Etag Lost Update"])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 412);
+    let response = client
+        .delete(&attr_url)
+        .bearer_auth(&token)
+        .header("If-Match", etag.clone())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 412);
+    let response = client
+        .put(&attr_url)
+        .bearer_auth(&token)
+        .header("If-Match", new_etag)
+        .json(&["Etag Attr Changed"])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let new_etag = get_etag().await;
+
+    let response = client
+        .delete(&url)
+        .bearer_auth(&token)
+        .header("If-Match", etag)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 412);
+
+    let response = client
+        .delete(&url)
+        .bearer_auth(&token)
+        .header("If-Match", new_etag)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+}
+
+#[kanidmd_testkit::test]
+async fn test_server_ldif_export(rsclient: KanidmClient) {
+    let res = rsclient
+        .auth_simple_password("admin", ADMIN_TEST_PASSWORD)
+        .await;
+    assert!(res.is_ok());
+    rsclient
+        .idm_group_add_members("idm_admins", &["admin"])
+        .await
+        .unwrap();
+    rsclient
+        .idm_person_account_create("ldif_account", "Ldif Account")
+        .await
+        .unwrap();
+    let token = rsclient.get_token().await.expect("No bearer token present");
+    let client = reqwest::Client::new();
+
+    // The list endpoints return ldif when asked with the query flag.
+    let response = client
+        .get(format!("{}/v1/person?format=ldif", rsclient.get_url()))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(
+        response
+            .headers()
+            .get("Content-Type")
+            .and_then(|hv| hv.to_str().ok()),
+        Some("text/ldif")
+    );
+    let ldif = response.text().await.unwrap();
+    assert!(ldif.starts_with("version: 1\n"));
+    assert!(ldif.contains("\ndn: spn=ldif_account@"));
+    assert!(ldif.contains("\nname: ldif_account\n"));
+
+    // Searches return ldif when asked with the accept header.
+    let response = client
+        .post(format!("{}/v1/raw/search", rsclient.get_url()))
+        .bearer_auth(&token)
+        .header("Accept", "text/ldif")
+        .json(&SearchRequest::new(Filter::Eq(
+            "name".to_string(),
+            "ldif_account".to_string(),
+        )))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let ldif = response.text().await.unwrap();
+    assert!(ldif.starts_with("version: 1\n"));
+    assert!(ldif.contains("\ndisplayname: Ldif Account\n"));
+    assert_eq!(ldif.matches("\ndn: ").count(), 1);
+
+    // Without either, the response is json as before.
+    let response = client
+        .get(format!("{}/v1/person", rsclient.get_url()))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let entries: Vec<Entry> = response.json().await.unwrap();
+    assert!(!entries.is_empty());
+}
+
+#[kanidmd_testkit::test]
+async fn test_server_audit_log(rsclient: KanidmClient) {
+    // Only system administrators can search the audit log.
+    let res = rsclient.auth_anonymous().await;
+    assert!(res.is_ok());
+    assert!(rsclient
+        .system_audit_log_search(AuditSearchRequest::default())
+        .await
+        .is_err());
+
+    let res = rsclient
+        .auth_simple_password("admin", ADMIN_TEST_PASSWORD)
+        .await;
+    assert!(res.is_ok());
+    rsclient
+        .idm_group_add_members("idm_admins", &["admin"])
+        .await
+        .unwrap();
+    rsclient
+        .idm_person_account_create("audit_account", "Audit Account")
+        .await
+        .unwrap();
+    let account = rsclient
+        .idm_person_account_get("audit_account")
+        .await
+        .unwrap()
+        .expect("No account");
+    let account_uuid = account
+        .attrs
+        .get("uuid")
+        .and_then(|v| v.first())
+        .and_then(|u| Uuid::parse_str(u).ok())
+        .expect("No uuid");
+
+    let records = rsclient
+        .system_audit_log_search(AuditSearchRequest {
+            target: Some(account_uuid),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].operation, EntryChange::Create);
+    assert_eq!(records[0].identity, UUID_ADMIN);
+    assert_eq!(records[0].outcome, AuditOutcome::Success);
+}
+
+#[kanidmd_testkit::test]
+async fn test_server_batch(rsclient: KanidmClient) {
+    let res = rsclient
+        .auth_simple_password("admin", ADMIN_TEST_PASSWORD)
+        .await;
+    assert!(res.is_ok());
+
+    let group = |name: &str| {
+        let mut e = Entry::default();
+        e.attrs.insert(
+            "class".to_string(),
+            vec!["group".to_string(), "object".to_string()],
+        );
+        e.attrs.insert("name".to_string(), vec![name.to_string()]);
+        e
+    };
+    let eq_name = |name: &str| Filter::Eq("name".to_string(), name.to_string());
+
+    // Later operations see the changes of earlier ones.
+    let response = rsclient
+        .batch(vec![
+            BatchOperation::Create(CreateRequest::new(vec![group("batch_group")])),
+            BatchOperation::Modify(ModifyRequest::new(
+                eq_name("batch_group"),
+                ModifyList::new_list(vec![Modify::Present(
+                    "description".to_string(),
+                    "batched".to_string(),
+                )]),
+            )),
+        ])
+        .await
+        .unwrap();
+    assert!(response.committed);
+    assert_eq!(
+        response.results,
+        vec![BatchOperationResult::Success, BatchOperationResult::Success]
+    );
+    let g = rsclient
+        .idm_group_get("batch_group")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        g.attrs.get("description"),
+        Some(&vec!["batched".to_string()])
+    );
+
+    // The second create conflicts, so nothing is applied.
+    let response = rsclient
+        .batch(vec![
+            BatchOperation::Create(CreateRequest::new(vec![group("batch_group_2")])),
+            BatchOperation::Create(CreateRequest::new(vec![group("batch_group")])),
+            BatchOperation::Delete(DeleteRequest::new(eq_name("batch_group"))),
+        ])
+        .await
+        .unwrap();
+    assert!(!response.committed);
+    assert_eq!(response.results[0], BatchOperationResult::Success);
+    assert!(matches!(
+        response.results[1],
+        BatchOperationResult::Failed(_)
+    ));
+    assert_eq!(response.results[2], BatchOperationResult::NotAttempted);
+    assert!(rsclient
+        .idm_group_get("batch_group_2")
+        .await
+        .unwrap()
+        .is_none());
+    assert!(rsclient
+        .idm_group_get("batch_group")
+        .await
+        .unwrap()
+        .is_some());
+}
+
+#[kanidmd_testkit::test]
+async fn test_server_scim_v2_users_and_groups(rsclient: KanidmClient) {
+    let res = rsclient
+        .auth_simple_password("admin", ADMIN_TEST_PASSWORD)
+        .await;
+    assert!(res.is_ok());
+    rsclient
+        .idm_group_add_members("idm_admins", &["admin"])
+        .await
+        .unwrap();
+    let token = rsclient.get_token().await.expect("No bearer token present");
+
+    let addr = rsclient.get_url();
+    let client = reqwest::Client::new();
+    let read = |response: reqwest::Response| async move {
+        let status = response.status();
+        let body: serde_json::Value =
+            serde_json::from_str(&response.text().await.unwrap()).unwrap();
+        (status, body)
+    };
+
+    // Create a user, which is read back as its resource.
+    let (status, user) = read(
+        client
+            .post(format!("{}/scim/v2/Users", &addr))
+            .bearer_auth(&token)
+            .header("Content-Type", "application/scim+json")
+            .body(
+                serde_json::json!({
+                    "schemas": ["urn:ietf:params:scim:schemas:core:2.0:User"],
+                    "userName": "scim_user",
+                    "displayName": "Scim User",
+                    "emails": [{ "value": "scim_user@example.com", "primary": true }]
+                })
+                .to_string(),
+            )
+            .send()
+            .await
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(status, 201);
+    assert_eq!(user["userName"], "scim_user");
+    let user_id = user["id"].as_str().unwrap().to_string();
+
+    // Find it with a filter.
+    let (status, list) = read(
+        client
+            .get(format!("{}/scim/v2/Users", &addr))
+            .bearer_auth(&token)
+            .query(&[("filter", r#"userName eq "scim_user""#)])
+            .send()
+            .await
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(status, 200);
+    assert_eq!(list["totalResults"], 1);
+    assert_eq!(list["Resources"][0]["id"], user_id.as_str());
+
+    // An unsupported filter is refused with a scim error.
+    let (status, err) = read(
+        client
+            .get(format!("{}/scim/v2/Users", &addr))
+            .bearer_auth(&token)
+            .query(&[("filter", r#"userName gt "a""#)])
+            .send()
+            .await
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(status, 400);
+    assert_eq!(err["scimType"], "invalidFilter");
+
+    // Create a group and add the user to it.
+    let (status, group) = read(
+        client
+            .post(format!("{}/scim/v2/Groups", &addr))
+            .bearer_auth(&token)
+            .body(serde_json::json!({ "displayName": "scim_group" }).to_string())
+            .send()
+            .await
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(status, 201);
+    let group_id = group["id"].as_str().unwrap().to_string();
+
+    let (status, group) = read(
+        client
+            .patch(format!("{}/scim/v2/Groups/{}", &addr, group_id))
+            .bearer_auth(&token)
+            .body(
+                serde_json::json!({
+                    "schemas": ["urn:ietf:params:scim:api:messages:2.0:PatchOp"],
+                    "Operations": [{ "op": "add", "path": "members", "value": [{ "value": user_id }] }]
+                })
+                .to_string(),
+            )
+            .send()
+            .await
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(status, 200);
+    assert_eq!(group["members"][0]["value"], "scim_user@localhost");
+
+    // Delete both, after which they are not found.
+    for path in [format!("Users/{}", user_id), format!("Groups/{}", group_id)] {
+        let response = client
+            .delete(format!("{}/scim/v2/{}", &addr, path))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 204);
+
+        let (status, err) = read(
+            client
+                .get(format!("{}/scim/v2/{}", &addr, path))
+                .bearer_auth(&token)
+                .send()
+                .await
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, 404);
+        assert_eq!(err["status"], "404");
+    }
+}
+
+#[kanidmd_testkit::test]
+async fn test_server_scim_v2_list_paged(rsclient: KanidmClient) {
+    let res = rsclient
+        .auth_simple_password("admin", ADMIN_TEST_PASSWORD)
+        .await;
+    assert!(res.is_ok());
+    rsclient
+        .idm_group_add_members("idm_admins", &["admin"])
+        .await
+        .unwrap();
+    let token = rsclient.get_token().await.expect("No bearer token present");
+
+    // More groups than the default page of a rest search.
+    for i in 0..70 {
+        rsclient
+            .idm_group_create(&format!("scim_paged_group_{}", i))
+            .await
+            .unwrap();
+    }
+
+    let addr = rsclient.get_url();
+    let client = reqwest::Client::new();
+    let list = |start_index: usize| {
+        let request = client
+            .get(format!("{}/scim/v2/Groups", &addr))
+            .bearer_auth(&token)
+            .query(&[("startIndex", start_index), ("count", 10)]);
+        async move {
+            let response = request.send().await.unwrap();
+            assert_eq!(response.status(), 200);
+            let body: serde_json::Value =
+                serde_json::from_str(&response.text().await.unwrap()).unwrap();
+            body
+        }
+    };
+
+    // SCIM pages over the whole result, so every group is counted and reachable.
+    let first = list(1).await;
+    let total = first["totalResults"].as_u64().unwrap() as usize;
+    assert!(total >= 70);
+    assert_eq!(first["Resources"].as_array().unwrap().len(), 10);
+
+    let last = list(total - 4).await;
+    assert_eq!(last["totalResults"], first["totalResults"]);
+    assert_eq!(last["startIndex"], total - 4);
+    assert_eq!(last["Resources"].as_array().unwrap().len(), 5);
+}
+
+#[kanidmd_testkit::test]
+async fn test_server_search_paged(rsclient: KanidmClient) {
+    let res = rsclient
+        .auth_simple_password("admin", ADMIN_TEST_PASSWORD)
+        .await;
+    assert!(res.is_ok());
+
+    let f = Filter::Eq("class".to_string(), "group".to_string());
+
+    let page_1 = rsclient
+        .search_page(f.clone(), Some(2), None)
+        .await
+        .unwrap();
+    assert!(page_1.entries.len() == 2);
+    assert!(page_1.next_page_token.is_some());
+
+    let page_2 = rsclient
+        .search_page(f.clone(), Some(2), page_1.next_page_token.clone())
+        .await
+        .unwrap();
+    assert!(page_2.entries.len() == 2);
+    assert!(page_2.entries.iter().all(|e| !page_1.entries.contains(e)));
+
+    // Searching follows the pages to return every entry.
+    let all = rsclient.search(f.clone()).await.unwrap();
+    assert!(all.len() > 4);
+    assert!(page_1.entries.iter().all(|e| all.contains(e)));
+
+    // A token must be valid.
+    let res = rsclient
+        .search_page(f, Some(2), Some("invalid".to_string()))
+        .await;
+    assert!(res.is_err());
+}
+
 // test the rest group endpoint.
 #[kanidmd_testkit::test]
 async fn test_server_rest_group_read(rsclient: KanidmClient) {
@@ -579,6 +1273,70 @@ async fn test_server_rest_posix_lifecycle(rsclient: KanidmClient) {
     assert!(r3.name == "posix_group");
 }
 
+#[kanidmd_testkit::test]
+async fn test_server_rest_posix_remove(rsclient: KanidmClient) {
+    let res = rsclient
+        .auth_simple_password("admin", ADMIN_TEST_PASSWORD)
+        .await;
+    assert!(res.is_ok());
+    // Not recommended in production!
+    rsclient
+        .idm_group_add_members("idm_admins", &["admin"])
+        .await
+        .unwrap();
+
+    rsclient
+        .idm_person_account_create("posix_account", "Posix Demo Account")
+        .await
+        .unwrap();
+    rsclient
+        .idm_person_account_unix_extend("posix_account", Some(5000), Some("/bin/sh"))
+        .await
+        .unwrap();
+    rsclient
+        .idm_person_account_unix_cred_put("posix_account", UNIX_TEST_PASSWORD)
+        .await
+        .unwrap();
+    rsclient.idm_group_create("posix_group").await.unwrap();
+    rsclient
+        .idm_group_unix_extend("posix_group", Some(5001))
+        .await
+        .unwrap();
+
+    // Remove the extensions, and their attributes go with them.
+    rsclient
+        .idm_person_account_unix_remove("posix_account")
+        .await
+        .unwrap();
+    rsclient.idm_group_unix_remove("posix_group").await.unwrap();
+
+    assert!(rsclient
+        .idm_account_unix_token_get("posix_account")
+        .await
+        .is_err());
+    assert!(rsclient
+        .idm_group_unix_token_get("posix_group")
+        .await
+        .is_err());
+    let e = rsclient
+        .idm_person_account_get("posix_account")
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(e.attrs.get("gidnumber").is_none());
+    assert!(e.attrs.get("loginshell").is_none());
+
+    // The accounts can be extended again, with the same gidnumber.
+    rsclient
+        .idm_person_account_unix_extend("posix_account", Some(5000), None)
+        .await
+        .unwrap();
+    rsclient
+        .idm_group_unix_extend("posix_group", Some(5001))
+        .await
+        .unwrap();
+}
+
 #[kanidmd_testkit::test]
 async fn test_server_rest_posix_auth_lifecycle(rsclient: KanidmClient) {
     let res = rsclient
@@ -1225,6 +1983,53 @@ async fn test_server_api_token_lifecycle(rsclient: KanidmClient) {
     // No need to test expiry, that's validated in the server internal tests.
 }
 
+#[kanidmd_testkit::test]
+async fn test_server_host_enrol(rsclient: KanidmClient) {
+    let res = rsclient
+        .auth_simple_password("admin", ADMIN_TEST_PASSWORD)
+        .await;
+    assert!(res.is_ok());
+
+    // Not recommended in production!
+    rsclient
+        .idm_group_add_members("idm_admins", &["admin"])
+        .await
+        .unwrap();
+
+    rsclient
+        .idm_host_create("test_host", "Test Host")
+        .await
+        .expect("Failed to create host");
+
+    let enrol_token = rsclient
+        .idm_host_enrol_token_create("test_host")
+        .await
+        .expect("Failed to create host enrolment token");
+
+    // The host enrols without any other credential.
+    let host_client = rsclient.new_session().expect("Failed to create session");
+    let credential = host_client
+        .host_enrol(&enrol_token)
+        .await
+        .expect("Failed to enrol host");
+    assert!(host_client.host_enrol(&enrol_token).await.is_err());
+
+    host_client.set_token(credential).await;
+    let e = host_client
+        .whoami()
+        .await
+        .expect("Failed to whoami")
+        .expect("Host credential was refused");
+    assert!(e.attrs.get("name") == Some(&vec!["test_host".to_string()]));
+
+    rsclient
+        .idm_host_credential_revoke("test_host")
+        .await
+        .expect("Failed to revoke host credential");
+
+    // Revocation after the grace window is validated in the server internal tests.
+}
+
 #[kanidmd_testkit::test]
 async fn test_server_user_auth_token_lifecycle(rsclient: KanidmClient) {
     let res = rsclient
@@ -1,8 +1,8 @@
 // use anyhow::Error;
 use gloo::console;
 pub use kanidm_proto::oauth2::{
-    AccessTokenRequest, AccessTokenResponse, AuthorisationRequest, AuthorisationResponse,
-    CodeChallengeMethod, ErrorResponse,
+    AccessTokenRequest, AccessTokenResponse, AuthorisationRequest, AuthorisationRequestOrPushed,
+    AuthorisationResponse, CodeChallengeMethod, ErrorResponse,
 };
 use wasm_bindgen::{JsCast, JsValue, UnwrapThrowExt};
 use wasm_bindgen_futures::JsFuture;
@@ -22,6 +22,7 @@ enum State {
     SubmitAuthReq,
     Consent {
         client_name: String,
+        client_image: Option<String>,
         #[allow(dead_code)]
         scopes: Vec<String>,
         pii_scopes: Vec<String>,
@@ -43,6 +44,7 @@ pub enum Oauth2Msg {
     TokenValid,
     Consent {
         client_name: String,
+        client_image: Option<String>,
         scopes: Vec<String>,
         pii_scopes: Vec<String>,
         consent_token: String,
@@ -98,7 +100,7 @@ impl Oauth2App {
         }
     }
 
-    async fn fetch_authreq(authreq: AuthorisationRequest) -> Result<Oauth2Msg, FetchError> {
+    async fn fetch_authreq(authreq: AuthorisationRequestOrPushed) -> Result<Oauth2Msg, FetchError> {
         let authreq_jsvalue = serde_json::to_string(&authreq)
             .map(|s| JsValue::from(&s))
             .expect_throw("Failed to serialise authreq");
@@ -134,11 +136,13 @@ impl Oauth2App {
             match state {
                 AuthorisationResponse::ConsentRequested {
                     client_name,
+                    client_image,
                     scopes,
                     pii_scopes,
                     consent_token,
                 } => Ok(Oauth2Msg::Consent {
                     client_name,
+                    client_image,
                     scopes,
                     pii_scopes,
                     consent_token,
@@ -221,7 +225,7 @@ impl Component for Oauth2App {
             .location()
             .expect_throw("Can't access current location");
 
-        let query: Option<AuthorisationRequest> = location
+        let query: Option<AuthorisationRequestOrPushed> = location
             .query()
             .map_err(|e| {
                 let e_msg = format!("lstorage error -> {:?}", e);
@@ -250,8 +254,12 @@ impl Component for Oauth2App {
         // as to the users name.
         // See: https://openid.net/specs/openid-connect-basic-1_0.html#RequestParameters
         // specifically, login_hint
-        if let Some(login_hint) = query.oidc_ext.login_hint.clone() {
-            models::push_login_hint(login_hint)
+        // A pushed request is opaque to us, so it can't provide a hint.
+        if let AuthorisationRequestOrPushed::Request(AuthorisationRequest { oidc_ext, .. }) = &query
+        {
+            if let Some(login_hint) = oidc_ext.login_hint.clone() {
+                models::push_login_hint(login_hint)
+            }
         }
         // Push the request down. This covers if we move to LoginRequired.
         models::push_oauth2_authorisation_request(query);
@@ -318,6 +326,7 @@ impl Component for Oauth2App {
             }
             Oauth2Msg::Consent {
                 client_name,
+                client_image,
                 scopes,
                 pii_scopes,
                 consent_token,
@@ -325,6 +334,7 @@ impl Component for Oauth2App {
                 self.state = match &self.state {
                     State::SubmitAuthReq => State::Consent {
                         client_name,
+                        client_image,
                         scopes,
                         pii_scopes,
                         consent_token,
@@ -428,12 +438,20 @@ impl Component for Oauth2App {
             }
             State::Consent {
                 client_name,
+                client_image,
                 scopes: _,
                 pii_scopes,
                 consent_token: _,
             } => {
                 let client_name = client_name.clone();
 
+                let logo = match client_image {
+                    Some(url) => html! {
+                      <img src={ url.clone() } alt="" class="mb-3" width="96" height="96" />
+                    },
+                    None => html! {},
+                };
+
                 let pii_req = if pii_scopes.is_empty() {
                     html! {
                       <div>
@@ -466,6 +484,7 @@ impl Component for Oauth2App {
                         } ) }
                         action="javascript:void(0);"
                       >
+                        { logo }
                         <h2 class="h3 mb-3 fw-normal">{"Consent to Proceed to " }{ app_name }</h2>
                         { pii_req }
 
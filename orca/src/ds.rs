@@ -434,4 +434,15 @@ impl DirectoryServer {
     ) -> Result<(Duration, Duration, usize), ()> {
         self.ldap.search_name(test_start, ids).await
     }
+
+    pub async fn modify_displayname(
+        &self,
+        test_start: Instant,
+        name: &str,
+        display_name: &str,
+    ) -> Result<(Duration, Duration), ()> {
+        self.ldap
+            .modify_displayname(test_start, name, display_name)
+            .await
+    }
 }
@@ -0,0 +1,105 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::path::Path;
+use std::time::Duration;
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+use uuid::Uuid;
+
+use crate::data::*;
+
+// How many operations each synthetic connection performs after it binds.
+const OPS_PER_CONN: usize = 10;
+// The most entries that a single synthetic search will request.
+const MAX_SEARCH_NAMES: usize = 3;
+
+/// Generate a synthetic data set of accounts and groups, so that an instance can be populated
+/// and loaded without an access log to preprocess.
+pub fn doit(output: &Path, users: usize, groups: usize, members: usize, searches: usize) {
+    info!(
+        "Generating {} accounts and {} groups to {} ...",
+        users,
+        groups,
+        output.to_str().unwrap()
+    );
+
+    if users == 0 {
+        error!("At least one account is required to authenticate the test connections");
+        return;
+    }
+
+    let out_file = match File::create(output) {
+        Ok(f) => f,
+        Err(e) => {
+            error!("Failed to open {} - {:?}", output.to_str().unwrap(), e);
+            return;
+        }
+    };
+
+    let mut rng = rand::thread_rng();
+
+    let account_ids: Vec<Uuid> = (0..users).map(|_| Uuid::new_v4()).collect();
+
+    let mut all_entities: HashMap<Uuid, Entity> = account_ids
+        .iter()
+        .map(|u| (*u, Entity::Account(Account::generate(*u))))
+        .collect();
+
+    for _ in 0..groups {
+        let uuid = Uuid::new_v4();
+        let group_members = account_ids
+            .choose_multiple(&mut rng, members)
+            .copied()
+            .collect();
+        all_entities.insert(uuid, Entity::Group(Group::generate(uuid, group_members)));
+    }
+
+    // Each connection binds as a random account, then searches for a few random entries.
+    let entity_ids: Vec<Uuid> = all_entities.keys().copied().collect();
+    let mut connections = Vec::with_capacity(searches / OPS_PER_CONN + 1);
+    let mut remaining = searches;
+    while remaining > 0 {
+        let count = remaining.min(OPS_PER_CONN);
+        remaining -= count;
+
+        let bind = account_ids.choose(&mut rng).copied().unwrap();
+        let mut ops = vec![Op {
+            orig_etime: Duration::ZERO,
+            rtime: Duration::ZERO,
+            op_type: OpType::Bind(bind),
+        }];
+        ops.extend((0..count).map(|i| {
+            let names = rng.gen_range(1..=MAX_SEARCH_NAMES);
+            Op {
+                orig_etime: Duration::ZERO,
+                rtime: Duration::from_millis(i as u64 + 1),
+                op_type: OpType::Search(
+                    entity_ids
+                        .choose_multiple(&mut rng, names)
+                        .copied()
+                        .collect(),
+                ),
+            }
+        }));
+
+        connections.push(Conn {
+            id: connections.len() as i32,
+            ops,
+        });
+    }
+
+    let td = TestData {
+        accounts: account_ids.iter().copied().collect(),
+        precreate: all_entities.keys().copied().collect::<HashSet<_>>(),
+        all_entities,
+        // The synthetic connections only read, so they need no extra access.
+        access: HashMap::new(),
+        connections,
+    };
+
+    // Finally, write it out;
+    if let Err(e) = serde_json::to_writer_pretty(out_file, &td) {
+        error!("Writing to file -> {:?}", e);
+    };
+}
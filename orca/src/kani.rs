@@ -322,6 +322,27 @@ impl KaniHttpServer {
 
         Ok((rel_diff, diff, l))
     }
+
+    pub async fn modify_displayname(
+        &self,
+        test_start: Instant,
+        name: &str,
+        display_name: &str,
+    ) -> Result<(Duration, Duration), ()> {
+        let start = Instant::now();
+        self.client
+            .idm_person_account_set_attr(name, "displayname", &[display_name])
+            .await
+            .map_err(|e| {
+                error!("{:?}", e);
+            })?;
+
+        let end = Instant::now();
+        let diff = end.duration_since(start);
+        let rel_diff = start.duration_since(test_start);
+
+        Ok((rel_diff, diff))
+    }
 }
 
 impl KaniLdapServer {
@@ -419,4 +440,14 @@ impl KaniLdapServer {
     ) -> Result<(Duration, Duration, usize), ()> {
         self.ldap.search_name(test_start, ids).await
     }
+
+    pub async fn modify_displayname(
+        &self,
+        _test_start: Instant,
+        _name: &str,
+        _display_name: &str,
+    ) -> Result<(Duration, Duration), ()> {
+        error!("Kanidm LDAP is read only, writes must use the kanidm target");
+        Err(())
+    }
 }
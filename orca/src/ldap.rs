@@ -189,6 +189,39 @@ impl LdapClient {
         Ok((rel_diff, diff, res.len()))
     }
 
+    pub async fn modify_displayname(
+        &self,
+        test_start: Instant,
+        name: &str,
+        display_name: &str,
+    ) -> Result<(Duration, Duration), ()> {
+        let dn = match self.schema {
+            LdapSchema::Kanidm => name.to_string(),
+            LdapSchema::Rfc2307bis => format!("uid={},ou=people,{}", name, self.basedn),
+        };
+
+        let req = LdapModifyRequest {
+            dn,
+            changes: vec![LdapModify {
+                operation: LdapModifyType::Replace,
+                modification: LdapPartialAttribute {
+                    atype: "displayName".to_string(),
+                    vals: vec![display_name.as_bytes().to_vec()],
+                },
+            }],
+        };
+
+        let start = Instant::now();
+
+        self.modify(req).await?;
+
+        let end = Instant::now();
+        let diff = end.duration_since(start);
+        let rel_diff = start.duration_since(test_start);
+
+        Ok((rel_diff, diff))
+    }
+
     pub async fn search(&self, filter: LdapFilter) -> Result<Vec<LdapSearchResultEntry>, ()> {
         // Create the search filter
         let req = LdapSearchRequest {
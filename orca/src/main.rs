@@ -27,6 +27,7 @@ use crate::kani::{KaniHttpServer, KaniLdapServer};
 
 mod data;
 mod ds;
+mod generate;
 mod kani;
 mod ldap;
 mod preprocess;
@@ -40,6 +41,7 @@ impl OrcaOpt {
     pub fn debug(&self) -> bool {
         match self {
             OrcaOpt::PreProc(opt) => opt.copt.debug,
+            OrcaOpt::Generate(opt) => opt.copt.debug,
             OrcaOpt::Setup(opt) => opt.copt.debug,
             OrcaOpt::Run(opt) => opt.copt.debug,
         }
@@ -176,6 +178,21 @@ impl TargetServer {
             TargetServer::DirSrv(k) => k.search(test_start, ids).await,
         }
     }
+
+    async fn modify_displayname(
+        &self,
+        test_start: Instant,
+        name: &str,
+        display_name: &str,
+    ) -> Result<(Duration, Duration), ()> {
+        match self {
+            TargetServer::Kanidm(k) => k.modify_displayname(test_start, name, display_name).await,
+            TargetServer::KanidmLdap(k) => {
+                k.modify_displayname(test_start, name, display_name).await
+            }
+            TargetServer::DirSrv(k) => k.modify_displayname(test_start, name, display_name).await,
+        }
+    }
 }
 
 #[tokio::main]
@@ -194,6 +211,13 @@ async fn main() {
     debug!("cli -> {:?}", opt);
     match opt {
         OrcaOpt::PreProc(opt) => preprocess::doit(&opt.input_path, &opt.output_path),
+        OrcaOpt::Generate(opt) => generate::doit(
+            &opt.output_path,
+            opt.users,
+            opt.groups,
+            opt.members,
+            opt.searches,
+        ),
         OrcaOpt::Setup(opt) => {
             let _ = setup::doit(&opt.target, &opt.profile_path).await;
         }
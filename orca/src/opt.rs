@@ -19,6 +19,27 @@ struct PreProcOpt {
     pub output_path: PathBuf,
 }
 
+#[derive(Debug, Parser)]
+struct GenerateOpt {
+    #[clap(flatten)]
+    pub copt: CommonOpt,
+    #[clap(parse(from_os_str), short, long = "output")]
+    /// Path to write the generated data set.
+    pub output_path: PathBuf,
+    #[clap(long, default_value = "1000")]
+    /// The number of accounts to generate.
+    pub users: usize,
+    #[clap(long, default_value = "100")]
+    /// The number of groups to generate.
+    pub groups: usize,
+    #[clap(long, default_value = "10")]
+    /// The number of accounts that are members of each group.
+    pub members: usize,
+    #[clap(long, default_value = "1000")]
+    /// The number of searches to generate for the tests to sample from.
+    pub searches: usize,
+}
+
 #[derive(Debug, Parser)]
 struct SetupOpt {
     #[clap(flatten)]
@@ -79,6 +100,9 @@ pub(crate) enum TestTypeOpt {
     #[clap(name = "search-basic")]
     /// Perform a basic search-only test
     SearchBasic,
+    #[clap(name = "mixed")]
+    /// Perform a mixed test of searches, self modifications and authentications
+    Mixed,
 }
 
 impl FromStr for TestTypeOpt {
@@ -87,6 +111,7 @@ impl FromStr for TestTypeOpt {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "search-basic" => Ok(TestTypeOpt::SearchBasic),
+            "mixed" => Ok(TestTypeOpt::Mixed),
             _ => Err("Invalid test type."),
         }
     }
@@ -96,6 +121,7 @@ impl std::fmt::Display for TestTypeOpt {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match &self {
             TestTypeOpt::SearchBasic => write!(f, "search-basic"),
+            TestTypeOpt::Mixed => write!(f, "mixed"),
         }
     }
 }
@@ -109,7 +135,9 @@ Orca works in a few steps.
 
 1. Create an orca config which defines the targets you want to be able to setup and load test. See example_profiles/small/orca.toml
 
-2. (Optional) preprocess an anonymised 389-ds access log (created from an external tool) into an orca data set.
+2. (Optional) preprocess an anonymised 389-ds access log (created from an external tool) into an orca data set, or generate a synthetic data set of a chosen size. For example:
+
+    orca generate -o ./example_profiles/small/data.json --users 10000 --groups 500
 
 3. 'orca setup' the kanidm/389-ds instance from the orca data set. You can see an example of this in example_profiles/small/data.json. This will reset the database, and add tons of entries etc. For example:
 
@@ -119,12 +147,19 @@ Orca works in a few steps.
 
     orca run -p example_profiles/small/orca.toml kanidm search-basic
 
+The mixed test also modifies and authenticates, and reports the latency percentiles of each operation:
+
+    orca run -p example_profiles/small/orca.toml kanidm mixed
+
 "
 )]
 enum OrcaOpt {
     #[clap(name = "preprocess")]
     /// Preprocess a dataset that can be used for testing
     PreProc(PreProcOpt),
+    #[clap(name = "generate")]
+    /// Generate a synthetic dataset that can be used for testing
+    Generate(GenerateOpt),
     #[clap(name = "setup")]
     /// Setup a server as defined by a test profile
     Setup(SetupOpt),
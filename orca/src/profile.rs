@@ -37,6 +37,31 @@ impl Default for SearchBasicConfig {
     }
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct MixedConfig {
+    pub warmup_seconds: u32,
+    pub test_seconds: u32,
+    pub workers: u32,
+    // The relative weights of each kind of operation that the workers choose from.
+    pub search_weight: u32,
+    pub modify_weight: u32,
+    pub auth_weight: u32,
+}
+
+impl Default for MixedConfig {
+    fn default() -> Self {
+        MixedConfig {
+            warmup_seconds: 5,
+            test_seconds: 30,
+            workers: 16,
+            search_weight: 80,
+            modify_weight: 10,
+            auth_weight: 10,
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct Profile {
     pub name: String,
@@ -47,4 +72,6 @@ pub struct Profile {
     pub kani_ldap_config: Option<KaniLdapConfig>,
     #[serde(default)]
     pub search_basic_config: SearchBasicConfig,
+    #[serde(default)]
+    pub mixed_config: MixedConfig,
 }
@@ -0,0 +1,295 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crossbeam::channel::unbounded;
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::seq::{IteratorRandom, SliceRandom};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tokio::task;
+
+use super::{arbiter, report_optimes, TestPhase};
+use crate::data::{Entity, OpType, TestData};
+use crate::profile::Profile;
+use crate::{TargetServer, TargetServerBuilder};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum MixedOp {
+    Search,
+    Modify,
+    Auth,
+}
+
+// In the same order as the weights that choose them.
+const MIXED_OPS: [MixedOp; 3] = [MixedOp::Search, MixedOp::Modify, MixedOp::Auth];
+
+#[derive(Serialize, Deserialize)]
+struct CsvRow {
+    op: MixedOp,
+    start: f64,
+    duration: f64,
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn mixed_worker(
+    test_start: Instant,
+    builder: TargetServerBuilder,
+    name: String,
+    pw: String,
+    searches: Arc<Vec<Vec<String>>>,
+    weights: WeightedIndex<u32>,
+    mut broadcast_rx: tokio::sync::broadcast::Receiver<TestPhase>,
+    raw_results_tx: crossbeam::channel::Sender<(MixedOp, Duration, Duration)>,
+) {
+    debug!("Starting worker ...");
+
+    let server = match builder.build() {
+        Ok(s) => s,
+        Err(_) => {
+            error!("Failed to build client");
+            return;
+        }
+    };
+
+    if server
+        .open_user_connection(test_start, &name, &pw)
+        .await
+        .is_err()
+    {
+        error!("Failed to authenticate connection");
+        return;
+    }
+
+    let mut modifications: u64 = 0;
+
+    loop {
+        // While nothing in broadcast.
+        match broadcast_rx.try_recv() {
+            Ok(TestPhase::Shutdown) => {
+                // Complete.
+                break;
+            }
+            Err(tokio::sync::broadcast::error::TryRecvError::Empty) | Ok(_) => {
+                // Ignore
+            }
+            Err(_) => {
+                error!("broadcast error");
+                return;
+            }
+        }
+
+        let (op, s) = {
+            let mut rng = rand::thread_rng();
+            (
+                MIXED_OPS[weights.sample(&mut rng)],
+                searches.as_slice().choose(&mut rng).unwrap(),
+            )
+        };
+
+        let r = match op {
+            MixedOp::Search => server
+                .search(test_start, s.as_slice())
+                .await
+                .map(|(start, d, _)| (start, d)),
+            MixedOp::Modify => {
+                modifications += 1;
+                let display_name = format!("{} {}", name, modifications);
+                server
+                    .modify_displayname(test_start, &name, &display_name)
+                    .await
+            }
+            MixedOp::Auth => {
+                // Ensure we are logged out, so that this is a fresh authentication.
+                server.close_connection().await;
+                server.open_user_connection(test_start, &name, &pw).await
+            }
+        };
+
+        match r {
+            Ok((start, d)) => {
+                let _ = raw_results_tx.send((op, start, d));
+            }
+            Err(_) => {
+                error!("{:?} Error", op);
+                if op == MixedOp::Auth {
+                    // Without a session the other operations can't proceed.
+                    return;
+                }
+            }
+        }
+    }
+    // Done
+    debug!("Stopping worker ...");
+}
+
+pub(crate) async fn mixed(
+    data: TestData,
+    profile: Profile,
+    server: TargetServer,
+    result_path: PathBuf,
+) -> Result<(), ()> {
+    let config = &profile.mixed_config;
+
+    if matches!(server, TargetServer::KanidmLdap(_)) && config.modify_weight > 0 {
+        error!("Kanidm LDAP is read only, set modify_weight = 0 or use the kanidm target");
+        return Err(());
+    }
+
+    let weights = WeightedIndex::new([
+        config.search_weight,
+        config.modify_weight,
+        config.auth_weight,
+    ])
+    .map_err(|e| error!("Invalid operation weights in mixed_config - {:?}", e))?;
+
+    // From all the data, process and find all the search events.
+    let searches: Vec<Vec<String>> = data
+        .connections
+        .iter()
+        .flat_map(|conn| conn.ops.iter())
+        .filter_map(|op| {
+            if let OpType::Search(list) = &op.op_type {
+                let names: Vec<String> = list
+                    .iter()
+                    .map(|u| data.all_entities.get(u).unwrap().get_name().to_string())
+                    .collect();
+                Some(names)
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    if searches.is_empty() {
+        error!("No searches found in data set, unable to proceed");
+        return Err(());
+    }
+
+    let searches = Arc::new(searches);
+
+    // We need a channel for all the results.
+    let (raw_results_tx, raw_results_rx) = unbounded();
+
+    // Setup a broadcast for the notifications.
+    let (broadcast_tx, broadcast_rx) = broadcast::channel(2);
+
+    let warmup_seconds = config.warmup_seconds;
+    let test_seconds = config.test_seconds;
+    let arbiter_join_handle = task::spawn(async move {
+        arbiter(broadcast_rx, &raw_results_rx, warmup_seconds, test_seconds).await
+    });
+
+    // Each worker modifies its own account, so they should not share them where possible.
+    let mut rng = rand::thread_rng();
+    let accs = data
+        .accounts
+        .intersection(&data.precreate)
+        .choose_multiple(&mut rng, config.workers as usize);
+
+    let mut accs: Vec<_> = accs
+        .into_iter()
+        .filter_map(|u| {
+            let e = data.all_entities.get(u).unwrap();
+            if let Entity::Account(aref) = e {
+                Some((aref.name.clone(), aref.password.clone()))
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    if accs.is_empty() {
+        error!("No accounts found in data set, unable to proceed");
+        return Err(());
+    }
+
+    while accs.len() < (config.workers as usize) {
+        let mut dup = accs.clone();
+        accs.append(&mut dup);
+    }
+
+    let test_start = Instant::now();
+
+    for i in 0..config.workers {
+        let builder = server.builder();
+        let name = accs[i as usize].0.clone();
+        let pw = accs[i as usize].1.clone();
+        let searches_c = searches.clone();
+        let weights_c = weights.clone();
+        let broadcast_rx_c = broadcast_tx.subscribe();
+        let raw_results_tx_c = raw_results_tx.clone();
+        task::spawn(async move {
+            mixed_worker(
+                test_start,
+                builder,
+                name,
+                pw,
+                searches_c,
+                weights_c,
+                broadcast_rx_c,
+                raw_results_tx_c,
+            )
+            .await
+        });
+    }
+
+    // Tell the arbiter to start the warm up counter now.
+    broadcast_tx
+        .send(TestPhase::WarmUp)
+        .map_err(|_| error!("Unable to broadcast warmup state change"))?;
+
+    // Wait on the arbiter, it will return our results when it's ready.
+    let raw_results = arbiter_join_handle.await.map_err(|_| {
+        error!("Test arbiter was unable to rejoin.");
+    })?;
+
+    // Now signal the workers to stop. We don't care if this fails.
+    let _ = broadcast_tx
+        .send(TestPhase::Shutdown)
+        .map_err(|_| error!("Unable to broadcast stop state change"));
+
+    // Each kind of operation has its own distribution, so they are reported separately.
+    for op in MIXED_OPS {
+        report_optimes(
+            &format!("{:?}", op).to_lowercase(),
+            raw_results
+                .iter()
+                .filter(|(o, _, _)| *o == op)
+                .map(|(_, _, d)| d.as_secs_f64())
+                .collect(),
+        );
+    }
+
+    // Write the raw results out.
+    let result_name = format!("mixed_{}.csv", server.rname());
+    let result_path = result_path.join(result_name);
+
+    let result_file = match File::create(&result_path) {
+        Ok(f) => f,
+        Err(e) => {
+            error!("Failed to open {} - {:?}", result_path.to_str().unwrap(), e);
+            return Err(());
+        }
+    };
+
+    let mut wtr = csv::Writer::from_writer(BufWriter::new(result_file));
+
+    raw_results
+        .into_iter()
+        .try_for_each(|(op, s, d)| {
+            wtr.serialize(CsvRow {
+                op,
+                start: s.as_secs_f64(),
+                duration: d.as_secs_f64(),
+            })
+        })
+        .map_err(|e| error!("csv error {:?}", e))?;
+
+    wtr.flush().map_err(|e| error!("csv error {:?}", e))?;
+
+    Ok(())
+}
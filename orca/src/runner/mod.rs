@@ -1,12 +1,119 @@
 use std::fs::create_dir_all;
 use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
+use crossbeam::channel::RecvTimeoutError;
 use dialoguer::Confirm;
+use mathru::statistics::distrib::{Continuous, Normal};
 
 use crate::setup::config;
 use crate::{TargetOpt, TestTypeOpt};
+mod mixed;
 mod search;
 
+#[derive(Debug, Clone)]
+enum TestPhase {
+    WarmUp,
+    // Running,
+    Shutdown,
+}
+
+async fn arbiter<T>(
+    mut broadcast_rx: tokio::sync::broadcast::Receiver<TestPhase>,
+    raw_results_rx: &crossbeam::channel::Receiver<T>,
+    warmup_seconds: u32,
+    test_seconds: u32,
+) -> Vec<T> {
+    info!("Starting test arbiter ...");
+
+    // Wait on the message that the workers have started the warm up.
+    let bcast_msg = broadcast_rx.recv().await.unwrap();
+
+    if !matches!(bcast_msg, TestPhase::WarmUp) {
+        error!("Invalid broadcast state to arbiter");
+        return Vec::new();
+    }
+
+    // Wait for warmup seconds.
+    // end of warmup
+
+    let end_of_warmup = Instant::now() + Duration::from_secs(warmup_seconds as u64);
+
+    let mut count = 0;
+
+    loop {
+        match raw_results_rx.recv_deadline(end_of_warmup) {
+            // We are currently discarding results.
+            Ok(_) => {
+                count += 1;
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                break;
+            }
+            Err(_) => {
+                error!("Worker channel error");
+                return Vec::new();
+            }
+        }
+    }
+
+    info!("Warmup has passed, collecting data");
+
+    let mut results = Vec::with_capacity(count * 4);
+
+    // Now we are running, so collect our data.
+    let end_of_test = Instant::now() + Duration::from_secs(test_seconds as u64);
+
+    loop {
+        match raw_results_rx.recv_deadline(end_of_test) {
+            Ok(datum) => results.push(datum),
+            Err(RecvTimeoutError::Timeout) => {
+                break;
+            }
+            Err(_) => {
+                error!("Worker channel error");
+                return Vec::new();
+            }
+        }
+    }
+
+    info!(
+        "Stopping test arbiter. Gathered {} datapoints",
+        results.len()
+    );
+    results
+}
+
+/// The value below which `pct` percent of the sorted `optimes` fall, by the nearest rank.
+fn percentile(optimes: &[f64], pct: f64) -> f64 {
+    if optimes.is_empty() {
+        return 0.0;
+    }
+    let rank = ((pct / 100.0) * optimes.len() as f64).ceil() as usize;
+    optimes[rank.clamp(1, optimes.len()) - 1]
+}
+
+/// Report the distribution of the operation times, in seconds, of one kind of operation.
+fn report_optimes(label: &str, mut optimes: Vec<f64>) {
+    if optimes.is_empty() {
+        info!("{}: no operations completed", label);
+        return;
+    }
+    optimes.sort_unstable_by(|a, b| a.total_cmp(b));
+
+    let distrib: Normal<f64> = Normal::from_data(&optimes);
+    let sd = distrib.variance().sqrt();
+
+    info!("{}: {} operations", label, optimes.len());
+    info!("{} mean: {} seconds", label, distrib.mean());
+    info!("{} variance: {}", label, distrib.variance());
+    info!("{} SD: {} seconds", label, sd);
+    for pct in [50.0, 90.0, 95.0, 99.0] {
+        info!("{} p{}: {} seconds", label, pct, percentile(&optimes, pct));
+    }
+    info!("{} max: {} seconds", label, optimes[optimes.len() - 1]);
+}
+
 pub(crate) async fn doit(
     testtype: &TestTypeOpt,
     target: &TargetOpt,
@@ -59,6 +166,7 @@ pub(crate) async fn doit(
     // Match on what kind of test we are doing. It takes over from here.
     match testtype {
         TestTypeOpt::SearchBasic => search::basic(data, profile, server, result_path).await?,
+        TestTypeOpt::Mixed => mixed::mixed(data, profile, server, result_path).await?,
     };
 
     info!("Test {} complete.", testtype);
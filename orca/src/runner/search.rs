@@ -4,24 +4,17 @@ use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
-use crossbeam::channel::{unbounded, RecvTimeoutError};
-use mathru::statistics::distrib::{Continuous, Normal};
+use crossbeam::channel::unbounded;
 use rand::seq::{IteratorRandom, SliceRandom};
 use serde::{Deserialize, Serialize};
 use tokio::sync::broadcast;
 use tokio::task;
 
+use super::{arbiter, report_optimes, TestPhase};
 use crate::data::{Entity, OpType, TestData};
 use crate::profile::Profile;
 use crate::{TargetServer, TargetServerBuilder};
 
-#[derive(Debug, Clone)]
-enum TestPhase {
-    WarmUp,
-    // Running,
-    Shutdown,
-}
-
 #[derive(Serialize, Deserialize)]
 struct CsvRow {
     start: f64,
@@ -29,72 +22,6 @@ struct CsvRow {
     count: usize,
 }
 
-async fn basic_arbiter(
-    mut broadcast_rx: tokio::sync::broadcast::Receiver<TestPhase>,
-    raw_results_rx: &crossbeam::channel::Receiver<(Duration, Duration, usize)>,
-    warmup_seconds: u32,
-) -> Vec<(Duration, Duration, usize)> {
-    info!("Starting test arbiter ...");
-
-    // Wait on the message that the workers have started the warm up.
-    let bcast_msg = broadcast_rx.recv().await.unwrap();
-
-    if !matches!(bcast_msg, TestPhase::WarmUp) {
-        error!("Invalid broadcast state to arbiter");
-        return Vec::new();
-    }
-
-    // Wait for warmup seconds.
-    // end of warmup
-
-    let end_of_warmup = Instant::now() + Duration::from_secs(warmup_seconds as u64);
-
-    let mut count = 0;
-
-    loop {
-        match raw_results_rx.recv_deadline(end_of_warmup) {
-            // We are currently discarding results.
-            Ok(_) => {
-                count += 1;
-            }
-            Err(RecvTimeoutError::Timeout) => {
-                break;
-            }
-            Err(_) => {
-                error!("Worker channel error");
-                return Vec::new();
-            }
-        }
-    }
-
-    info!("Warmup has passed, collecting data");
-
-    let mut results = Vec::with_capacity(count * 4);
-
-    // Now we are running, so collect our data.
-    let end_of_test = Instant::now() + Duration::from_secs(10);
-
-    loop {
-        match raw_results_rx.recv_deadline(end_of_test) {
-            // We are currently discarding results.
-            Ok(datum) => results.push(datum),
-            Err(RecvTimeoutError::Timeout) => {
-                break;
-            }
-            Err(_) => {
-                error!("Worker channel error");
-                return Vec::new();
-            }
-        }
-    }
-
-    info!(
-        "Stopping test arbiter. Gathered {} datapoints",
-        results.len()
-    );
-    results
-}
-
 async fn basic_worker(
     test_start: Instant,
     builder: TargetServerBuilder,
@@ -208,7 +135,7 @@ pub(crate) async fn basic(
     let warmup_seconds = profile.search_basic_config.warmup_seconds;
     let arbiter_join_handle =
         task::spawn(
-            async move { basic_arbiter(broadcast_rx, &raw_results_rx, warmup_seconds).await },
+            async move { arbiter(broadcast_rx, &raw_results_rx, warmup_seconds, 10).await },
         );
 
     // Get out our conn details
@@ -287,7 +214,13 @@ pub(crate) async fn basic(
         .map_err(|_| error!("Unable to broadcast stop state change"));
 
     // Now we can finalise our data, based on what analysis we can actually do here.
-    process_raw_results(&raw_results);
+    report_optimes(
+        "search",
+        raw_results
+            .iter()
+            .map(|(_, d, _)| d.as_secs_f64())
+            .collect(),
+    );
 
     // Write the raw results out.
 
@@ -319,21 +252,3 @@ pub(crate) async fn basic(
 
     Ok(())
 }
-
-fn process_raw_results(raw_results: &[(Duration, Duration, usize)]) {
-    // Do nerd shit.
-
-    // Get the times
-    let optimes: Vec<_> = raw_results
-        .iter()
-        .map(|(_, d, _)| d.as_secs_f64())
-        .collect();
-
-    let distrib: Normal<f64> = Normal::from_data(&optimes);
-    let sd = distrib.variance().sqrt();
-
-    info!("mean: {} seconds", distrib.mean());
-    info!("variance: {}", distrib.variance());
-    info!("SD: {} seconds", sd);
-    info!("95%: {}", distrib.mean() + (2.0 * sd));
-}
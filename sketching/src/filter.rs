@@ -0,0 +1,133 @@
+//! A log filter that can be replaced while the server runs. It takes the directives of an
+//! [EnvFilter], so that a span can be made quieter or more verbose than the rest of the log,
+//! such as `info,[be::search]=warn`. Security events, both tagged and of the security event
+//! stream, are always logged, whatever the directives are, so that silencing a noisy span can't
+//! hide them.
+
+use std::sync::{PoisonError, RwLock};
+
+use tracing::callsite;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::subscriber::Interest;
+use tracing::{Event, Metadata, Subscriber};
+use tracing_subscriber::filter::{LevelFilter, ParseError};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::EnvFilter;
+
+use crate::security::SECURITY_EVENT_TARGET;
+use crate::EventTag;
+
+/// The directives in use. There is only one logger, so they are shared by every [LogFilter].
+static DIRECTIVES: RwLock<Option<EnvFilter>> = RwLock::new(None);
+
+/// Replace the directives of the log filter.
+pub fn set_log_filter(directives: &str) -> Result<(), ParseError> {
+    let filter = EnvFilter::try_new(directives)?;
+    *DIRECTIVES.write().unwrap_or_else(PoisonError::into_inner) = Some(filter);
+    // Callsites cache whether they are enabled, which may now be wrong.
+    callsite::rebuild_interest_cache();
+    Ok(())
+}
+
+fn with_directives<T>(default: T, f: impl FnOnce(&EnvFilter) -> T) -> T {
+    DIRECTIVES
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .as_ref()
+        .map_or(default, f)
+}
+
+/// If this is an event of the tagged macros, such as `security_info!`.
+fn is_tagged(metadata: &Metadata<'_>) -> bool {
+    metadata.is_event() && metadata.fields().field("event_tag_id").is_some()
+}
+
+fn is_security_event(metadata: &Metadata<'_>) -> bool {
+    metadata.is_event() && metadata.target() == SECURITY_EVENT_TARGET
+}
+
+#[derive(Default)]
+struct EventTagVisitor(Option<EventTag>);
+
+impl Visit for EventTagVisitor {
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        if field.name() == "event_tag_id" {
+            self.0 = EventTag::try_from(value).ok();
+        }
+    }
+
+    fn record_debug(&mut self, _field: &Field, _value: &dyn std::fmt::Debug) {}
+}
+
+pub struct LogFilter;
+
+impl LogFilter {
+    pub fn new(directives: &str) -> Result<Self, ParseError> {
+        set_log_filter(directives)?;
+        Ok(LogFilter)
+    }
+}
+
+impl<S: Subscriber> Layer<S> for LogFilter {
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        if is_security_event(metadata) {
+            Interest::always()
+        } else if is_tagged(metadata) {
+            // The tag is only known once the event is recorded, so these are always checked.
+            Interest::sometimes()
+        } else {
+            with_directives(Interest::sometimes(), |f| {
+                Layer::<S>::register_callsite(f, metadata)
+            })
+        }
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>, ctx: Context<'_, S>) -> bool {
+        is_security_event(metadata)
+            || is_tagged(metadata)
+            || with_directives(true, |f| Layer::<S>::enabled(f, metadata, ctx))
+    }
+
+    fn event_enabled(&self, event: &Event<'_>, ctx: Context<'_, S>) -> bool {
+        if !is_tagged(event.metadata()) {
+            return true;
+        }
+        let mut visitor = EventTagVisitor::default();
+        event.record(&mut visitor);
+        matches!(
+            visitor.0,
+            Some(
+                EventTag::SecurityCritical
+                    | EventTag::SecurityInfo
+                    | EventTag::SecurityAccess
+                    | EventTag::SecurityError
+            )
+        ) || with_directives(true, |f| Layer::<S>::enabled(f, event.metadata(), ctx))
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        // Security events are logged at any level, and the directives may change.
+        None
+    }
+
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        with_directives((), |f| Layer::<S>::on_new_span(f, attrs, id, ctx))
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        with_directives((), |f| Layer::<S>::on_record(f, id, values, ctx))
+    }
+
+    fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+        with_directives((), |f| Layer::<S>::on_enter(f, id, ctx))
+    }
+
+    fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
+        with_directives((), |f| Layer::<S>::on_exit(f, id, ctx))
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        with_directives((), |f| Layer::<S>::on_close(f, id, ctx))
+    }
+}
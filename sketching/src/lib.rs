@@ -1,12 +1,23 @@
 #![deny(warnings)]
 #![warn(unused_extern_crates)]
 
+use std::fmt;
+
 use num_enum::{IntoPrimitive, TryFromPrimitive};
+use serde::Deserialize;
+use tracing_forest::printer::{Formatter, Pretty};
+use tracing_forest::tree::Tree;
 use tracing_forest::util::*;
 use tracing_forest::Tag;
 
+pub mod filter;
 pub mod macros;
 pub mod middleware;
+pub mod otel;
+pub mod security;
+pub mod sink;
+pub mod slow;
+pub mod tail;
 
 pub use {tracing, tracing_forest, tracing_subscriber};
 
@@ -26,6 +37,33 @@ pub fn event_tagger(_event: &Event) -> Option<Tag> {
     None
 }
 
+/// How each tree of events is written to the log.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Indented for a person to read.
+    #[default]
+    Pretty,
+    /// One json object per line, with the spans nested and their timings, for log collectors.
+    Json,
+}
+
+impl Formatter for LogFormat {
+    type Error = fmt::Error;
+
+    fn fmt(&self, tree: &Tree) -> Result<String, fmt::Error> {
+        match self {
+            LogFormat::Pretty => Pretty.fmt(tree),
+            LogFormat::Json => serde_json::to_string(tree)
+                .map(|mut line| {
+                    line.push('\n');
+                    line
+                })
+                .map_err(|_| fmt::Error),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, IntoPrimitive, TryFromPrimitive)]
 #[repr(u64)]
 pub enum EventTag {
@@ -3,6 +3,9 @@ use tracing::{self, instrument};
 
 use crate::{request_error, request_info, request_warn, security_info, *};
 
+/// The header of the operation id, which matches a response to the server log.
+pub const KOPID: &str = "X-KANIDM-OPID";
+
 pub struct TreeMiddleware {
     trust_x_forward_for: bool,
 }
@@ -14,7 +17,7 @@ impl TreeMiddleware {
         }
     }
 
-    #[instrument(name = "tide-request", skip(self, req, next))]
+    #[instrument(name = "tide-request", skip(self, req, next), fields(opid))]
     async fn log<'a, State: Clone + Send + Sync + 'static>(
         &'a self,
         mut req: Request<State>,
@@ -27,6 +30,11 @@ impl TreeMiddleware {
         }
         req.set_ext(TreeMiddlewareFinished);
 
+        // The operation id is the id of this span, which handlers return to the client.
+        // It is recorded so that exported spans can be searched by it.
+        let opid = tracing_forest::id().as_hyphenated().to_string();
+        tracing::Span::current().record("opid", opid.as_str());
+
         let remote_address = if self.trust_x_forward_for {
             req.remote()
         } else {
@@ -51,7 +59,11 @@ impl TreeMiddleware {
             "Request received"
         );
 
-        let response = next.run(req).await;
+        let mut response = next.run(req).await;
+        // Responses that no handler made, such as those of the rate limit, need it too.
+        if response.header(KOPID).is_none() {
+            response.insert_header(KOPID, opid);
+        }
         let status = response.status();
 
         if status.is_server_error() {
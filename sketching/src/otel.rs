@@ -0,0 +1,43 @@
+//! Export of spans to an OpenTelemetry collector over OTLP, so that a slow request can be
+//! followed from the frontend through the query server, plugins and backend in an existing
+//! observability stack.
+
+use opentelemetry::sdk::trace::{self, Tracer};
+use opentelemetry::sdk::Resource;
+use opentelemetry::trace::TraceError;
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use tracing::Subscriber;
+use tracing_opentelemetry::OpenTelemetryLayer;
+use tracing_subscriber::registry::LookupSpan;
+
+/// A layer that exports spans to the OTLP gRPC `endpoint`, such as `http://localhost:4317`.
+/// Spans are sent in batches from a tokio task, so this must be called within the runtime.
+pub fn otel_layer<S>(
+    endpoint: &str,
+    service_name: &'static str,
+) -> Result<OpenTelemetryLayer<S, Tracer>, TraceError>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    let tracer = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(endpoint),
+        )
+        .with_trace_config(
+            trace::config().with_resource(Resource::new(vec![KeyValue::new(
+                "service.name",
+                service_name,
+            )])),
+        )
+        .install_batch(opentelemetry::runtime::Tokio)?;
+    Ok(tracing_opentelemetry::layer().with_tracer(tracer))
+}
+
+/// Send the spans that are waiting in the batch, before the process exits.
+pub fn otel_shutdown() {
+    opentelemetry::global::shutdown_tracer_provider();
+}
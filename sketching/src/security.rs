@@ -0,0 +1,64 @@
+//! The security event stream. Security events are emitted with [SECURITY_EVENT_TARGET] as their
+//! target and the event as json in their `security_event` field. They are always logged, and
+//! [SecurityEventLayer] also writes them, one per line, to a file of their own that a SIEM can
+//! ingest without parsing the rest of the log.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Mutex, PoisonError};
+
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+pub const SECURITY_EVENT_TARGET: &str = "kanidm::security_event";
+pub const SECURITY_EVENT_FIELD: &str = "security_event";
+
+#[derive(Default)]
+struct SecurityEventVisitor(Option<String>);
+
+impl Visit for SecurityEventVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == SECURITY_EVENT_FIELD {
+            self.0 = Some(value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == SECURITY_EVENT_FIELD {
+            self.0 = Some(format!("{:?}", value));
+        }
+    }
+}
+
+pub struct SecurityEventLayer {
+    file: Mutex<File>,
+}
+
+impl SecurityEventLayer {
+    /// Append security events to the file at `path`, which is created if it does not exist.
+    pub fn new<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(SecurityEventLayer {
+            file: Mutex::new(file),
+        })
+    }
+}
+
+impl<S: Subscriber> Layer<S> for SecurityEventLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        if event.metadata().target() != SECURITY_EVENT_TARGET {
+            return;
+        }
+        let mut visitor = SecurityEventVisitor::default();
+        event.record(&mut visitor);
+        if let Some(mut line) = visitor.0 {
+            line.push('\n');
+            let mut file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+            if let Err(e) = file.write_all(line.as_bytes()) {
+                eprintln!("Failed to write security event - {:?}", e);
+            }
+        }
+    }
+}
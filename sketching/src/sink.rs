@@ -0,0 +1,221 @@
+//! Where the trees of events are written. Each tree is written as one message, so that a
+//! request stays together in syslog or the journal as it does on stdout.
+
+// The helpers of syslog and journald are not used where their sockets are not available.
+#![cfg_attr(not(target_family = "unix"), allow(dead_code))]
+
+use std::io::{self, Write};
+#[cfg(target_family = "unix")]
+use std::os::unix::net::UnixDatagram;
+
+use serde::Deserialize;
+use tracing::Level;
+use tracing_forest::printer::Formatter;
+use tracing_forest::processor::{self, Processor};
+use tracing_forest::tree::Tree;
+
+use crate::slow::{SlowOperation, SlowOperationLog};
+use crate::LogFormat;
+
+#[cfg(target_family = "unix")]
+const SYSLOG_SOCKET: &str = "/dev/log";
+#[cfg(target_family = "unix")]
+const JOURNALD_SOCKET: &str = "/run/systemd/journal/socket";
+const IDENTIFIER: &str = "kanidmd";
+/// The syslog facility of system daemons.
+const FACILITY_DAEMON: u8 = 3;
+
+/// The destination of the server log.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogSink {
+    #[default]
+    Stdout,
+    /// The local syslog daemon, through its socket.
+    Syslog,
+    /// The systemd journal, with the span and fields of each tree as journal fields.
+    Journald,
+}
+
+impl LogSink {
+    /// Connect to the sink, which fails if the socket of syslog or journald is not present.
+    pub fn processor(self, format: LogFormat) -> io::Result<SinkProcessor> {
+        let sink = match self {
+            LogSink::Stdout => Sink::Stdout,
+            #[cfg(target_family = "unix")]
+            LogSink::Syslog => Sink::Syslog(connect(SYSLOG_SOCKET)?),
+            #[cfg(target_family = "unix")]
+            LogSink::Journald => Sink::Journald(connect(JOURNALD_SOCKET)?),
+            #[cfg(not(target_family = "unix"))]
+            LogSink::Syslog | LogSink::Journald => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "syslog and journald are only available on unix",
+                ))
+            }
+        };
+        Ok(SinkProcessor {
+            format,
+            sink,
+            slow_operation_log: SlowOperationLog::default(),
+        })
+    }
+}
+
+#[cfg(target_family = "unix")]
+fn connect(path: &str) -> io::Result<UnixDatagram> {
+    let socket = UnixDatagram::unbound()?;
+    socket.connect(path)?;
+    Ok(socket)
+}
+
+enum Sink {
+    Stdout,
+    #[cfg(target_family = "unix")]
+    Syslog(UnixDatagram),
+    #[cfg(target_family = "unix")]
+    Journald(UnixDatagram),
+}
+
+pub struct SinkProcessor {
+    format: LogFormat,
+    sink: Sink,
+    slow_operation_log: SlowOperationLog,
+}
+
+impl SinkProcessor {
+    /// Report the operations that exceed the thresholds of `slow_operation_log`.
+    pub fn with_slow_operation_log(mut self, slow_operation_log: SlowOperationLog) -> Self {
+        self.slow_operation_log = slow_operation_log;
+        self
+    }
+
+    fn write(&self, tree: &Tree) -> io::Result<()> {
+        let message = self
+            .format
+            .fmt(tree)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        self.send(&message, tree_level(tree), |datagram| {
+            journald_tree_fields(datagram, tree)
+        })
+    }
+
+    fn write_slow_operation(&self, report: &SlowOperation) -> io::Result<()> {
+        self.send(&report.fmt(self.format), Level::WARN, |datagram| {
+            journald_field(datagram, "KANIDM_SLOW_OPERATION", &report.operation);
+            journald_field(datagram, "KANIDM_SPAN_UUID", &report.uuid);
+            journald_field(
+                datagram,
+                "KANIDM_DURATION_MS",
+                &report.duration_ms.to_string(),
+            );
+        })
+    }
+
+    /// Send a message to the sink. `journald_fields` adds the fields of the message to a
+    /// journal datagram.
+    #[cfg_attr(not(target_family = "unix"), allow(unused_variables))]
+    fn send<F>(&self, message: &str, level: Level, journald_fields: F) -> io::Result<()>
+    where
+        F: FnOnce(&mut Vec<u8>),
+    {
+        match &self.sink {
+            Sink::Stdout => io::stdout().lock().write_all(message.as_bytes()),
+            #[cfg(target_family = "unix")]
+            Sink::Syslog(socket) => {
+                let priority = FACILITY_DAEMON * 8 + severity(level);
+                let datagram = format!(
+                    "<{}>{}[{}]: {}",
+                    priority,
+                    IDENTIFIER,
+                    std::process::id(),
+                    message.trim_end()
+                );
+                socket.send(datagram.as_bytes()).map(|_| ())
+            }
+            #[cfg(target_family = "unix")]
+            Sink::Journald(socket) => {
+                let mut datagram = Vec::new();
+                journald_field(&mut datagram, "MESSAGE", message.trim_end());
+                journald_field(&mut datagram, "PRIORITY", &severity(level).to_string());
+                journald_field(&mut datagram, "SYSLOG_IDENTIFIER", IDENTIFIER);
+                journald_fields(&mut datagram);
+                socket.send(&datagram).map(|_| ())
+            }
+        }
+    }
+}
+
+impl Processor for SinkProcessor {
+    fn process(&self, tree: Tree) -> processor::Result {
+        let res = self
+            .write(&tree)
+            .and_then(|()| match self.slow_operation_log.check(&tree) {
+                Some(report) => self.write_slow_operation(&report),
+                None => Ok(()),
+            });
+        match res {
+            Ok(()) => Ok(()),
+            Err(e) => Err(processor::error(tree, e.into())),
+        }
+    }
+}
+
+fn tree_level(tree: &Tree) -> Level {
+    match tree {
+        Tree::Event(event) => event.level(),
+        Tree::Span(span) => span.level(),
+    }
+}
+
+/// The syslog severity of a level, which journald also uses as the priority.
+fn severity(level: Level) -> u8 {
+    match level {
+        Level::ERROR => 3,
+        Level::WARN => 4,
+        Level::INFO => 6,
+        Level::DEBUG | Level::TRACE => 7,
+    }
+}
+
+/// A journal field name is uppercase letters, digits and underscores.
+fn journald_field_name(key: &str) -> String {
+    let name: String = key
+        .chars()
+        .map(|c| match c {
+            'a'..='z' => c.to_ascii_uppercase(),
+            'A'..='Z' | '0'..='9' => c,
+            _ => '_',
+        })
+        .collect();
+    format!("KANIDM_{}", name)
+}
+
+/// Append a field in the native journal protocol. Values are written with their length, so
+/// they may contain newlines.
+fn journald_field(datagram: &mut Vec<u8>, name: &str, value: &str) {
+    datagram.extend_from_slice(name.as_bytes());
+    datagram.push(b'\n');
+    datagram.extend_from_slice(&(value.len() as u64).to_le_bytes());
+    datagram.extend_from_slice(value.as_bytes());
+    datagram.push(b'\n');
+}
+
+fn journald_tree_fields(datagram: &mut Vec<u8>, tree: &Tree) {
+    match tree {
+        Tree::Event(event) => {
+            for field in event.fields() {
+                journald_field(datagram, &journald_field_name(field.key()), field.value());
+            }
+        }
+        Tree::Span(span) => {
+            journald_field(datagram, "KANIDM_SPAN", span.name());
+            journald_field(datagram, "KANIDM_SPAN_UUID", &span.uuid().to_string());
+            journald_field(
+                datagram,
+                "KANIDM_DURATION_US",
+                &span.total_duration().as_micros().to_string(),
+            );
+        }
+    }
+}
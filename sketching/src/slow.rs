@@ -0,0 +1,134 @@
+//! The slow operation log. Each tree already has the time of its spans, so once a tree is
+//! complete its root and its segments can be compared to thresholds. An operation that exceeds
+//! one is reported at warning level with the time of each segment, such as the filter
+//! resolution, index lookups and plugins, so that an operator can see where the time went.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tracing_forest::tree::{Span, Tree};
+
+use crate::LogFormat;
+
+/// The thresholds of the slow operation log, in milliseconds.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SlowOperationLog {
+    /// The time of the whole operation.
+    pub threshold_ms: Option<u64>,
+    /// The time of all spans of a name in the operation, such as `be::search`.
+    #[serde(default)]
+    pub segments: BTreeMap<String, u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SegmentTiming {
+    pub name: String,
+    pub count: usize,
+    pub duration_ms: u128,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SlowOperation {
+    pub operation: String,
+    pub uuid: String,
+    pub duration_ms: u128,
+    /// The thresholds that were exceeded, by name, or `operation` for the whole operation.
+    pub exceeded: Vec<String>,
+    /// The segments of the operation, slowest first.
+    pub segments: Vec<SegmentTiming>,
+}
+
+fn collect_segments(span: &Span, segments: &mut BTreeMap<String, (usize, Duration)>) {
+    for node in span.nodes() {
+        if let Tree::Span(child) = node {
+            let entry = segments
+                .entry(child.name().to_string())
+                .or_insert((0, Duration::ZERO));
+            entry.0 += 1;
+            entry.1 += child.total_duration();
+            collect_segments(child, segments);
+        }
+    }
+}
+
+impl SlowOperationLog {
+    /// The report of the operation, if it exceeded a threshold.
+    pub fn check(&self, tree: &Tree) -> Option<SlowOperation> {
+        let span = match tree {
+            Tree::Span(span) => span,
+            Tree::Event(_) => return None,
+        };
+        let duration = span.total_duration();
+
+        let mut segments = BTreeMap::new();
+        collect_segments(span, &mut segments);
+
+        let mut exceeded: Vec<String> = self
+            .threshold_ms
+            .filter(|ms| duration > Duration::from_millis(*ms))
+            .map(|_| "operation".to_string())
+            .into_iter()
+            .collect();
+        exceeded.extend(
+            self.segments
+                .iter()
+                .filter(|(name, ms)| {
+                    segments
+                        .get(*name)
+                        .map(|(_, d)| *d > Duration::from_millis(**ms))
+                        .unwrap_or(false)
+                })
+                .map(|(name, _)| name.clone()),
+        );
+        if exceeded.is_empty() {
+            return None;
+        }
+
+        let mut segments: Vec<SegmentTiming> = segments
+            .into_iter()
+            .map(|(name, (count, d))| SegmentTiming {
+                name,
+                count,
+                duration_ms: d.as_millis(),
+            })
+            .collect();
+        segments.sort_by(|a, b| b.duration_ms.cmp(&a.duration_ms));
+
+        Some(SlowOperation {
+            operation: span.name().to_string(),
+            uuid: span.uuid().to_string(),
+            duration_ms: duration.as_millis(),
+            exceeded,
+            segments,
+        })
+    }
+}
+
+impl SlowOperation {
+    /// The report as one line, in the format of the log.
+    pub fn fmt(&self, format: LogFormat) -> String {
+        match format {
+            LogFormat::Pretty => {
+                let segments: Vec<String> = self
+                    .segments
+                    .iter()
+                    .map(|s| format!("{} {}ms x{}", s.name, s.duration_ms, s.count))
+                    .collect();
+                format!(
+                    "WARN ⏳ slow operation {} [ {} ] took {}ms, exceeded: {} | {}\n",
+                    self.operation,
+                    self.uuid,
+                    self.duration_ms,
+                    self.exceeded.join(", "),
+                    segments.join(", ")
+                )
+            }
+            LogFormat::Json => {
+                let mut line = serde_json::json!({ "slow_operation": self }).to_string();
+                line.push('\n');
+                line
+            }
+        }
+    }
+}
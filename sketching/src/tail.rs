@@ -0,0 +1,173 @@
+//! The live tail of the log. [TailLayer] sends each event, as it happens, to the tails that
+//! [subscribe] to them, so that an operator can follow a running server without waiting for
+//! requests to complete or reading the whole log. A tail has its own directives, in the syntax
+//! of the log filter, which select from the events that the log filter passes. To see events
+//! that the log filter drops, it can be changed while the server runs.
+
+use std::fmt::{self, Write};
+use std::sync::{PoisonError, RwLock};
+
+use time::OffsetDateTime;
+use tokio::sync::mpsc;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::filter::ParseError;
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::EnvFilter;
+
+use crate::LogFormat;
+
+/// The lines a tail may fall behind by. A tail that falls further behind misses lines, so that
+/// a slow reader never holds up the server.
+const TAIL_BUFFER_LINES: usize = 1024;
+
+struct Tail {
+    filter: EnvFilter,
+    tx: mpsc::Sender<String>,
+}
+
+static TAILS: RwLock<Vec<Tail>> = RwLock::new(Vec::new());
+
+/// Follow the events that match `directives`, one formatted line each. The tail ends when the
+/// receiver is dropped.
+pub fn subscribe(directives: &str) -> Result<mpsc::Receiver<String>, ParseError> {
+    let filter = EnvFilter::try_new(directives)?;
+    let (tx, rx) = mpsc::channel(TAIL_BUFFER_LINES);
+    TAILS
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .push(Tail { filter, tx });
+    Ok(rx)
+}
+
+#[derive(Default)]
+struct TailVisitor {
+    message: String,
+    fields: Vec<(&'static str, String)>,
+}
+
+impl Visit for TailVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            self.fields.push((field.name(), value.to_string()));
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{:?}", value);
+        } else {
+            self.fields.push((field.name(), format!("{:?}", value)));
+        }
+    }
+}
+
+pub struct TailLayer {
+    format: LogFormat,
+}
+
+impl TailLayer {
+    pub fn new(format: LogFormat) -> Self {
+        TailLayer { format }
+    }
+
+    fn fmt_event<S>(&self, event: &Event<'_>, ctx: &Context<'_, S>) -> String
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        let mut visitor = TailVisitor::default();
+        event.record(&mut visitor);
+        let metadata = event.metadata();
+        let time = OffsetDateTime::now_utc().format(time::Format::Rfc3339);
+        let spans: Vec<&str> = ctx
+            .event_scope(event)
+            .map(|scope| scope.from_root().map(|span| span.name()).collect())
+            .unwrap_or_default();
+
+        match self.format {
+            LogFormat::Pretty => {
+                let mut line = format!("{} {:>5} {}", time, metadata.level(), spans.join(":"));
+                let _ = write!(line, " {}", visitor.message);
+                for (name, value) in &visitor.fields {
+                    let _ = write!(line, " {}={}", name, value);
+                }
+                line
+            }
+            LogFormat::Json => {
+                let fields: serde_json::Map<String, serde_json::Value> = visitor
+                    .fields
+                    .into_iter()
+                    .map(|(name, value)| (name.to_string(), value.into()))
+                    .collect();
+                serde_json::json!({
+                    "time": time,
+                    "level": metadata.level().as_str(),
+                    "target": metadata.target(),
+                    "spans": spans,
+                    "message": visitor.message,
+                    "fields": fields,
+                })
+                .to_string()
+            }
+        }
+    }
+}
+
+fn with_tails(f: impl FnMut(&Tail)) {
+    TAILS
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .iter()
+        .for_each(f)
+}
+
+impl<S> Layer<S> for TailLayer
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut closed = false;
+        let mut line = None;
+        with_tails(|tail| {
+            if tail.tx.is_closed() {
+                closed = true;
+            } else if Layer::<S>::enabled(&tail.filter, event.metadata(), ctx.clone()) {
+                let line = line.get_or_insert_with(|| self.fmt_event(event, &ctx));
+                // A tail that is full misses this line.
+                let _ = tail.tx.try_send(line.clone());
+            }
+        });
+        if closed {
+            TAILS
+                .write()
+                .unwrap_or_else(PoisonError::into_inner)
+                .retain(|tail| !tail.tx.is_closed());
+        }
+    }
+
+    // The directives of a tail may select events by their spans, so its filter follows them.
+
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        with_tails(|tail| Layer::<S>::on_new_span(&tail.filter, attrs, id, ctx.clone()))
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        with_tails(|tail| Layer::<S>::on_record(&tail.filter, id, values, ctx.clone()))
+    }
+
+    fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+        with_tails(|tail| Layer::<S>::on_enter(&tail.filter, id, ctx.clone()))
+    }
+
+    fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
+        with_tails(|tail| Layer::<S>::on_exit(&tail.filter, id, ctx.clone()))
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        with_tails(|tail| Layer::<S>::on_close(&tail.filter, id.clone(), ctx.clone()))
+    }
+}